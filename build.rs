@@ -0,0 +1,12 @@
+fn main() {
+    // Expose PyO3's interpreter cfgs (Py_GIL_DISABLED, Py_LIMITED_API) to this crate
+    pyo3_build_config::use_pyo3_cfgs();
+
+    // Free-threaded CPython has no stable ABI yet, so PyO3 silently drops abi3 there
+    if std::env::var_os("CARGO_FEATURE_ABI3").is_some() && pyo3_build_config::get().is_free_threaded() {
+        println!(
+            "cargo:warning=the abi3 feature is ignored on free-threaded CPython; \
+             building a version-specific extension instead"
+        );
+    }
+}
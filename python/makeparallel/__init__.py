@@ -0,0 +1,5 @@
+"""True parallelism for Python, implemented in Rust."""
+
+from .makeparallel import *  # noqa: F401,F403
+from .makeparallel import __doc__, _reinitialize  # noqa: F401
+from ._selftest import selftest  # noqa: F401
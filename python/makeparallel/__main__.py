@@ -0,0 +1,128 @@
+"""Command line tools: ``python -m makeparallel ctl ...`` and ``python -m makeparallel selftest``
+
+``ctl`` talks to a process that called ``makeparallel.start_control_server()``::
+
+    python -m makeparallel ctl --pid 4242 list
+    python -m makeparallel ctl --address 127.0.0.1:7070 cancel --tag batch
+    python -m makeparallel ctl --pid 4242 pause task_17
+
+``selftest`` checks that this installation works (see ``makeparallel.selftest()``)::
+
+    python -m makeparallel selftest --level stress
+"""
+
+import argparse
+import json
+import os
+import socket
+import sys
+import tempfile
+
+
+def default_address(pid):
+    """The socket start_control_server() listens on by default in process `pid`"""
+    return os.path.join(tempfile.gettempdir(), "makeparallel-%d.sock" % pid)
+
+
+def connect(address, timeout=5.0):
+    host, sep, port = address.rpartition(":")
+    if sep and port.isdigit() and os.sep not in address:
+        return socket.create_connection((host.strip("[]"), int(port)), timeout=timeout)
+    sock = socket.socket(socket.AF_UNIX, socket.SOCK_STREAM)
+    sock.settimeout(timeout)
+    sock.connect(address)
+    return sock
+
+
+def request(address, command, **fields):
+    """Send one command to a control server and return its reply"""
+    with connect(address) as sock:
+        sock.sendall(json.dumps(dict(fields, command=command)).encode() + b"\n")
+        reply = sock.makefile("rb").readline()
+    if not reply:
+        raise ConnectionError("control server at %s closed the connection" % address)
+    return json.loads(reply)
+
+
+def print_tasks(reply):
+    if reply["paused"]:
+        print("scheduling paused")
+    if not reply["tasks"]:
+        print("no active tasks")
+    for task in reply["tasks"]:
+        line = "%-12s %-10s %-30s %8.1fs" % (task["task_id"], task["state"], task["name"], task["elapsed"])
+        if task["tags"]:
+            line += "  [%s]" % ", ".join(task["tags"])
+        if task["submitted_from"]:
+            line += "  " + task["submitted_from"]
+        print(line)
+
+
+def ctl(args):
+    fields = {}
+    if args.command == "cancel":
+        if (args.task_id is None) == (args.tag is None):
+            sys.exit("cancel needs a task id or --tag")
+        fields = {"task_id": args.task_id} if args.task_id is not None else {"tag": args.tag}
+    elif args.command in ("pause", "resume") and args.task_id is not None:
+        fields = {"task_id": args.task_id}
+
+    address = args.address or default_address(args.pid)
+    try:
+        reply = request(address, args.command, **fields)
+    except OSError as e:
+        sys.exit("cannot reach control server at %s: %s" % (address, e))
+    if not reply["ok"]:
+        sys.exit(reply["error"])
+
+    if args.json:
+        print(json.dumps(reply, indent=2))
+    elif args.command == "list":
+        print_tasks(reply)
+    elif args.command == "cancel":
+        print("cancelled %d, already finishing %d" % (reply["cancelled"], reply["already_finishing"]))
+    elif "task_id" in reply:
+        if not reply["found"]:
+            sys.exit("task %s is not %s" % (reply["task_id"], "active" if args.command == "pause" else "paused"))
+        print("task %s %s" % (reply["task_id"], "paused" if args.command == "pause" else "resumed"))
+    elif args.command in ("pause", "resume"):
+        print("scheduling paused" if reply["paused"] else "scheduling resumed")
+    else:
+        print(json.dumps(reply["metrics"], indent=2))
+
+
+def selftest(args):
+    from . import selftest as run_selftest
+
+    report = run_selftest(args.level, verbose=not args.json)
+    if args.json:
+        print(json.dumps(report, indent=2))
+    sys.exit(0 if report["ok"] else 1)
+
+
+def main(argv=None):
+    parser = argparse.ArgumentParser(prog="python -m makeparallel")
+    tools = parser.add_subparsers(dest="tool", required=True)
+
+    ctl_parser = tools.add_parser("ctl", help="control a running process over its control socket")
+    target = ctl_parser.add_mutually_exclusive_group(required=True)
+    target.add_argument("--pid", type=int, help="process listening on the default socket")
+    target.add_argument("--address", help="socket path or host:port given to start_control_server()")
+    ctl_parser.add_argument("--json", action="store_true", help="print the raw reply")
+    ctl_parser.add_argument("command", choices=["list", "cancel", "pause", "resume", "metrics"])
+    ctl_parser.add_argument("task_id", nargs="?", help="task to cancel, pause or resume")
+    ctl_parser.add_argument("--tag", help="cancel every task with this tag")
+
+    selftest_parser = tools.add_parser("selftest", help="check that makeparallel works in this environment")
+    selftest_parser.add_argument("--level", choices=["quick", "stress"], default="quick")
+    selftest_parser.add_argument("--json", action="store_true", help="print the report as JSON")
+
+    args = parser.parse_args(argv)
+    if args.tool == "selftest":
+        selftest(args)
+    else:
+        ctl(args)
+
+
+if __name__ == "__main__":
+    main()
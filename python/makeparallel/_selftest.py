@@ -0,0 +1,260 @@
+"""Built-in checks run by ``makeparallel.selftest()`` and ``python -m makeparallel selftest``"""
+
+import os
+import platform
+import subprocess
+import sys
+import threading
+import time
+
+from . import makeparallel as mp
+
+# Tag on every task the checks submit, so leaks can be told apart from the host's own tasks
+TAG = "makeparallel-selftest"
+
+# Work per check and seconds a check may take before it counts as an anomaly
+LEVELS = {
+    "quick": {"tasks": 20, "chain": 5, "items": 1000, "budget": 10.0},
+    "stress": {"tasks": 2000, "chain": 100, "items": 200000, "budget": 120.0},
+}
+
+SHUTDOWN_SCRIPT = """
+import threading
+import time
+import makeparallel as mp
+
+started = threading.Semaphore(0)
+
+@mp.parallel
+def work(x):
+    started.release()
+    time.sleep(0.05)
+    return x
+
+handles = [work(i) for i in range(4)]
+# Tasks that have not started when shutdown begins are cancelled instead
+for _ in handles:
+    assert started.acquire(timeout=10.0), "a task never started"
+assert mp.shutdown(timeout_secs=10.0, cancel_pending=False), "shutdown timed out"
+assert [h.get() for h in handles] == list(range(4)), "tasks lost during shutdown"
+try:
+    work(0)
+except RuntimeError:
+    pass
+else:
+    raise AssertionError("a task was accepted after shutdown")
+"""
+
+
+class Skipped(Exception):
+    """Raised by a check that cannot run on this platform"""
+
+
+def wait_until(predicate, timeout=5.0):
+    deadline = time.time() + timeout
+    while not predicate():
+        if time.time() > deadline:
+            return False
+        time.sleep(0.01)
+    return True
+
+
+def check_submission(scale):
+    @mp.parallel
+    def double(x):
+        return x * 2
+
+    @mp.parallel
+    def fail():
+        raise ValueError("expected failure")
+
+    handles = [double(i, tags=[TAG]) for i in range(scale["tasks"])]
+    results = [h.get() for h in handles]
+    assert results == [i * 2 for i in range(scale["tasks"])], "wrong or reordered results"
+
+    failing = fail(tags=[TAG])
+    try:
+        failing.get()
+    except Exception:
+        pass
+    else:
+        raise AssertionError("a failing task returned normally")
+    assert failing.status() == mp.TaskStatus.FAILED, "failed task reported as %s" % failing.status()
+
+
+def check_cancellation(scale):
+    if mp.get_execution_mode() == "sync":
+        raise Skipped("tasks run inline, so they finish before they can be cancelled")
+
+    started = threading.Semaphore(0)
+
+    @mp.parallel
+    def spin():
+        started.release()
+        while True:
+            mp.check_cancelled()
+            time.sleep(0.005)
+
+    count = max(1, scale["tasks"] // 20)
+    handles = [spin(tags=[TAG]) for _ in range(count)]
+    for _ in handles:
+        assert started.acquire(timeout=10.0), "a task never started"
+    for h in handles:
+        h.cancel()
+    assert wait_until(lambda: all(h.status() == mp.TaskStatus.CANCELLED for h in handles)), "cancel() not final"
+    ids = {h.get_task_id() for h in handles}
+    stuck = lambda: [task for task in mp.list_tasks(tag=TAG) if task["task_id"] in ids]
+    assert wait_until(lambda: not stuck()), "%d cancelled tasks kept running" % len(stuck())
+
+
+def check_dependencies(scale):
+    @mp.parallel_with_deps
+    def first():
+        return 0
+
+    @mp.parallel_with_deps
+    def step(deps):
+        return deps[0] + 1
+
+    @mp.parallel_with_deps
+    def broken():
+        raise ValueError("expected failure")
+
+    handle = first(tags=[TAG])
+    for _ in range(scale["chain"]):
+        handle = step(depends_on=[handle], tags=[TAG])
+    assert handle.get() == scale["chain"], "dependency chain produced the wrong result"
+
+    dependent = step(depends_on=[broken(tags=[TAG])], tags=[TAG])
+    try:
+        dependent.get()
+    except Exception:
+        pass
+    else:
+        raise AssertionError("a task ran although its dependency failed")
+
+
+def check_backpressure(scale):
+    if mp.get_execution_mode() == "sync":
+        raise Skipped("tasks run inline, so nothing ever queues")
+
+    name = "%s-%d-%d" % (TAG, os.getpid(), threading.get_ident())
+    release = threading.Event()
+    lock = threading.Lock()
+    state = {"running": 0, "peak": 0}
+
+    @mp.parallel(pool=name)
+    def hold():
+        with lock:
+            state["running"] += 1
+            state["peak"] = max(state["peak"], state["running"])
+        release.wait(10.0)
+        with lock:
+            state["running"] -= 1
+
+    with mp.Pool(name, 2, max_queue=2) as pool:
+        try:
+            handles = [hold(tags=[TAG]) for _ in range(2)]
+            assert wait_until(lambda: pool.stats()["running"] == 2), "pool threads did not pick up work"
+            handles += [hold(tags=[TAG]) for _ in range(2)]
+            try:
+                hold(tags=[TAG])
+            except mp.PoolFull:
+                pass
+            else:
+                raise AssertionError("a full queue accepted another task")
+        finally:
+            release.set()
+        for h in handles:
+            h.get()
+    assert state["peak"] <= 2, "%d tasks ran on a 2-thread pool" % state["peak"]
+
+
+def check_parallel_map(scale):
+    items = list(range(scale["items"]))
+    assert mp.parallel_map(lambda x: x + 1, items) == [x + 1 for x in items], "parallel_map lost or reordered items"
+
+
+def check_shutdown(scale):
+    if not sys.executable:
+        raise Skipped("no Python executable to start a child process with")
+    try:
+        child = subprocess.run(
+            [sys.executable, "-c", SHUTDOWN_SCRIPT],
+            stdout=subprocess.PIPE,
+            stderr=subprocess.PIPE,
+            timeout=scale["budget"],
+            env=dict(os.environ, PYTHONPATH=os.pathsep.join(p for p in sys.path if p)),
+        )
+    except OSError as e:
+        raise Skipped("cannot start a child process: %s" % e)
+    if child.returncode != 0:
+        lines = child.stderr.decode(errors="replace").strip().splitlines()
+        raise AssertionError(lines[-1] if lines else "child exited with %d" % child.returncode)
+
+
+CHECKS = [
+    ("submission", check_submission),
+    ("cancellation", check_cancellation),
+    ("dependencies", check_dependencies),
+    ("backpressure", check_backpressure),
+    ("parallel_map", check_parallel_map),
+    ("shutdown", check_shutdown),
+]
+
+
+def selftest(level="quick", verbose=False):
+    """Run a battery of checks against this installation and report what went wrong
+
+    ``level="quick"`` takes a few seconds; ``"stress"`` submits thousands of tasks.
+    Covers submission, cancellation, dependencies, backpressure, parallel_map and
+    shutdown (in a child process, so this runtime keeps working). Returns a dict with
+    ``ok``, the ``environment``, one entry per check in ``checks``, and ``anomalies``:
+    failures, checks slower than expected, and tasks still registered afterwards.
+    """
+    if level not in LEVELS:
+        raise ValueError("level must be %s, got %r" % (" or ".join(map(repr, LEVELS)), level))
+    scale = LEVELS[level]
+    report = {
+        "ok": True,
+        "level": level,
+        "environment": {
+            "python": sys.version.split()[0],
+            "platform": platform.platform(),
+            "execution_mode": mp.get_execution_mode(),
+            "free_threaded": mp.is_free_threaded(),
+            "cpu_count": os.cpu_count(),
+        },
+        "checks": [],
+        "anomalies": [],
+    }
+
+    for name, check in CHECKS:
+        start = time.perf_counter()
+        outcome, detail = "passed", None
+        try:
+            check(scale)
+        except Skipped as e:
+            outcome, detail = "skipped", str(e)
+        except Exception as e:
+            outcome, detail = "failed", "%s: %s" % (type(e).__name__, e)
+        seconds = time.perf_counter() - start
+
+        if outcome == "failed":
+            report["anomalies"].append("%s failed: %s" % (name, detail))
+        elif seconds > scale["budget"]:
+            report["anomalies"].append("%s took %.1fs (expected under %.0fs)" % (name, seconds, scale["budget"]))
+        if mp.list_tasks(tag=TAG) and not wait_until(lambda: not mp.list_tasks(tag=TAG), timeout=2.0):
+            report["anomalies"].append("%s left %d tasks registered" % (name, len(mp.list_tasks(tag=TAG))))
+            mp.cancel_tasks(lambda task: TAG in task["tags"])
+
+        report["checks"].append({"name": name, "outcome": outcome, "seconds": seconds, "detail": detail})
+        if verbose:
+            print("%-14s %-8s %6.2fs  %s" % (name, outcome, seconds, detail or ""))
+
+    report["ok"] = not report["anomalies"]
+    if verbose:
+        for anomaly in report["anomalies"]:
+            print("anomaly: " + anomaly)
+        print("selftest %s" % ("passed" if report["ok"] else "FAILED"))
+    return report
@@ -0,0 +1,192 @@
+//! Container resource limits
+//!
+//! Inside Docker or Kubernetes the host's memory and CPU totals overstate what the
+//! process may use. These read the limits of the process's control group instead,
+//! from cgroup v2 (`memory.max`, `cpu.max`) or the v1 memory and cpu controllers.
+//! A limit set on any ancestor group applies too, so the tightest one along the
+//! path wins. Everything returns None outside Linux or when no limit is set.
+
+// The parsers are only called from the Linux implementation
+#![cfg_attr(not(target_os = "linux"), allow(dead_code))]
+
+/// v1 files report "no limit" as a huge page-aligned number rather than a keyword
+const UNLIMITED_V1: u64 = 1 << 62;
+
+/// Memory limit of the process's cgroup in bytes
+pub(crate) fn memory_limit() -> Option<u64> {
+    imp::memory_limit()
+}
+
+/// Memory the cgroup is using in bytes, not counting page cache the kernel can reclaim
+pub(crate) fn memory_usage() -> Option<u64> {
+    imp::memory_usage()
+}
+
+/// CPU quota of the process's cgroup in cores, e.g. 1.5
+pub(crate) fn cpu_limit() -> Option<f64> {
+    imp::cpu_limit()
+}
+
+/// cgroup version the limits are read from, None if none is mounted
+pub(crate) fn version() -> Option<u8> {
+    imp::version()
+}
+
+/// Parse `memory.max` or `memory.limit_in_bytes`
+pub(crate) fn parse_memory_limit(contents: &str) -> Option<u64> {
+    match contents.trim() {
+        "max" => None,
+        value => value.parse().ok().filter(|&bytes| bytes < UNLIMITED_V1),
+    }
+}
+
+/// Parse cgroup v2 `cpu.max`, "<quota> <period>" or "max <period>"
+pub(crate) fn parse_cpu_max(contents: &str) -> Option<f64> {
+    let mut fields = contents.split_whitespace();
+    let quota = fields.next()?;
+    let period = fields.next().unwrap_or("100000");
+    cpu_quota(quota.parse().ok()?, period.parse().ok()?)
+}
+
+/// Cores allowed by a CFS quota; a negative quota (v1's -1) means none
+pub(crate) fn cpu_quota(quota_us: i64, period_us: i64) -> Option<f64> {
+    (quota_us > 0 && period_us > 0).then(|| quota_us as f64 / period_us as f64)
+}
+
+/// Look up `key` in a `memory.stat` file
+pub(crate) fn parse_stat(contents: &str, key: &str) -> Option<u64> {
+    contents.lines().find_map(|line| {
+        let (name, value) = line.split_once(' ')?;
+        (name == key).then(|| value.trim().parse().ok())?
+    })
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::{cpu_quota, parse_cpu_max, parse_memory_limit, parse_stat};
+    use once_cell::sync::Lazy;
+    use std::path::{Path, PathBuf};
+
+    /// Directories of the process's cgroup, innermost first, per controller
+    struct Groups {
+        version: u8,
+        memory: Vec<PathBuf>,
+        cpu: Vec<PathBuf>,
+    }
+
+    // Membership is fixed for the life of a containerized process
+    static GROUPS: Lazy<Option<Groups>> = Lazy::new(find_groups);
+
+    fn find_groups() -> Option<Groups> {
+        let membership = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+        let mounts = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+        let v1 = |controller: &str| {
+            let mount = mounts.lines().find_map(|line| {
+                let (mount, fs) = mount_fields(line)?;
+                (fs.0 == "cgroup" && fs.1.split(',').any(|option| option == controller)).then_some(mount)
+            })?;
+            let path = membership.lines().find_map(|line| {
+                let mut fields = line.splitn(3, ':');
+                let (_, controllers, path) = (fields.next()?, fields.next()?, fields.next()?);
+                controllers.split(',').any(|c| c == controller).then_some(path)
+            })?;
+            Some(ancestors(&mount, path))
+        };
+        if let (Some(memory), Some(cpu)) = (v1("memory"), v1("cpu")) {
+            return Some(Groups { version: 1, memory, cpu });
+        }
+        let mount = mounts.lines().find_map(|line| {
+            let (mount, fs) = mount_fields(line)?;
+            (fs.0 == "cgroup2").then_some(mount)
+        })?;
+        let path = membership.lines().find_map(|line| line.strip_prefix("0::"))?;
+        let groups = ancestors(&mount, path);
+        Some(Groups { version: 2, memory: groups.clone(), cpu: groups })
+    }
+
+    /// Mount point and (filesystem type, super options) of a mountinfo line
+    fn mount_fields(line: &str) -> Option<(PathBuf, (&str, &str))> {
+        let (left, right) = line.split_once(" - ")?;
+        let mount = left.split(' ').nth(4)?;
+        let mut fs = right.split(' ');
+        let fs_type = fs.next()?;
+        let options = fs.nth(1).unwrap_or("");
+        Some((PathBuf::from(mount), (fs_type, options)))
+    }
+
+    /// `path` under `mount` and each parent up to the mount itself
+    ///
+    /// Without a cgroup namespace the listed path may not exist inside the
+    /// container's view, which is then rooted at the group itself.
+    fn ancestors(mount: &Path, path: &str) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = Path::new(path.trim_start_matches('/'))
+            .ancestors()
+            .map(|relative| mount.join(relative))
+            .filter(|dir| dir.is_dir())
+            .collect();
+        if dirs.is_empty() {
+            dirs.push(mount.to_path_buf());
+        }
+        dirs
+    }
+
+    fn read(dir: &Path, file: &str) -> Option<String> {
+        std::fs::read_to_string(dir.join(file)).ok()
+    }
+
+    pub(super) fn version() -> Option<u8> {
+        GROUPS.as_ref().map(|groups| groups.version)
+    }
+
+    pub(super) fn memory_limit() -> Option<u64> {
+        let groups = GROUPS.as_ref()?;
+        let file = if groups.version == 2 { "memory.max" } else { "memory.limit_in_bytes" };
+        groups.memory.iter().filter_map(|dir| parse_memory_limit(&read(dir, file)?)).min()
+    }
+
+    pub(super) fn memory_usage() -> Option<u64> {
+        let groups = GROUPS.as_ref()?;
+        let (usage, inactive) = if groups.version == 2 {
+            ("memory.current", "inactive_file")
+        } else {
+            ("memory.usage_in_bytes", "total_inactive_file")
+        };
+        let dir = groups.memory.first()?;
+        let used: u64 = read(dir, usage)?.trim().parse().ok()?;
+        let reclaimable = read(dir, "memory.stat").and_then(|stat| parse_stat(&stat, inactive)).unwrap_or(0);
+        Some(used.saturating_sub(reclaimable))
+    }
+
+    pub(super) fn cpu_limit() -> Option<f64> {
+        let groups = GROUPS.as_ref()?;
+        let quota = |dir: &PathBuf| {
+            if groups.version == 2 {
+                parse_cpu_max(&read(dir, "cpu.max")?)
+            } else {
+                let quota = read(dir, "cpu.cfs_quota_us")?.trim().parse().ok()?;
+                let period = read(dir, "cpu.cfs_period_us")?.trim().parse().ok()?;
+                cpu_quota(quota, period)
+            }
+        };
+        groups.cpu.iter().filter_map(quota).reduce(f64::min)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    pub(super) fn memory_limit() -> Option<u64> {
+        None
+    }
+
+    pub(super) fn memory_usage() -> Option<u64> {
+        None
+    }
+
+    pub(super) fn cpu_limit() -> Option<f64> {
+        None
+    }
+
+    pub(super) fn version() -> Option<u8> {
+        None
+    }
+}
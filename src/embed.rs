@@ -0,0 +1,128 @@
+//! Rust API for applications that embed Python
+//!
+//! Lets a Rust host drive the makeparallel scheduler directly: submit Python
+//! callables, poll or wait on their handles and shut the scheduler down, without
+//! going through the Python-level module functions. Build with
+//! `default-features = false, features = ["embed"]` so PyO3 links against libpython.
+//!
+//! ```ignore
+//! makeparallel::embed::register();
+//! Python::initialize();
+//! Python::attach(|py| {
+//!     let func = py.eval(c"lambda x: x * 2", None, None)?;
+//!     let handle = makeparallel::embed::submit(py, &func, (21,), &SubmitOptions::default())?;
+//!     assert_eq!(handle.get(py)?.extract::<i32>(py)?, 42);
+//!     Ok::<_, PyErr>(())
+//! })
+//! ```
+
+use std::time::Duration;
+
+use pyo3::prelude::*;
+use pyo3::BoundObject;
+use pyo3::types::{PyDict, PyTuple};
+
+use crate::{makeparallel, AsyncHandle, Isolation, ParallelWrapper, PriorityParallelWrapper};
+
+/// Make `import makeparallel` work in the embedded interpreter
+///
+/// Must be called before the interpreter is initialized.
+pub fn register() {
+    pyo3::append_to_inittab!(makeparallel);
+}
+
+/// Options for submit()
+#[derive(Debug, Default)]
+pub struct SubmitOptions {
+    /// Run through the priority queue with this priority instead of a dedicated thread
+    pub priority: Option<i32>,
+    /// Per-attempt timeout in seconds
+    pub timeout: Option<f64>,
+    /// Timeout in seconds for the whole task, including retries
+    pub total_timeout: Option<f64>,
+    /// Tags for cancel_tasks()/cancel_all() and scheduler events
+    pub tags: Vec<String>,
+    /// Keyword arguments for the call
+    pub kwargs: Option<Py<PyDict>>,
+}
+
+/// Handle of a task submitted from Rust
+pub struct TaskHandle {
+    handle: Py<AsyncHandle>,
+}
+
+impl TaskHandle {
+    /// Task ID, as reported to Python callbacks and events
+    pub fn task_id(&self, py: Python) -> String {
+        self.handle.borrow(py).task_id.clone()
+    }
+
+    /// Whether the task has finished (or been cancelled)
+    pub fn is_ready(&self, py: Python) -> bool {
+        *self.handle.borrow(py).is_complete.lock()
+    }
+
+    /// Result if the task has finished, without blocking
+    pub fn try_get(&self, py: Python) -> PyResult<Option<Py<PyAny>>> {
+        self.handle.borrow(py).try_get(py)
+    }
+
+    /// Block until the task finishes and return its result
+    ///
+    /// The GIL is released while waiting.
+    pub fn get(&self, py: Python) -> PyResult<Py<PyAny>> {
+        self.handle.borrow(py).get(py)
+    }
+
+    /// Wait up to `timeout` (forever if None); returns whether the task finished
+    pub fn wait(&self, py: Python, timeout: Option<Duration>) -> PyResult<bool> {
+        self.handle.borrow(py).wait(py, timeout.map(|t| t.as_secs_f64()))
+    }
+
+    /// Ask the task to stop
+    pub fn cancel(&self, py: Python) -> PyResult<()> {
+        self.handle.borrow(py).cancel()
+    }
+
+    /// The Python `AsyncHandle`, for passing the task to Python code
+    pub fn into_py(self) -> Py<PyAny> {
+        self.handle.into_any()
+    }
+}
+
+/// Run `func(*args, **options.kwargs)` on the scheduler, as a `@parallel` (or, with
+/// a priority, `@parallel_priority`) call would
+pub fn submit<'py>(
+    py: Python<'py>,
+    func: &Bound<'py, PyAny>,
+    args: impl IntoPyObject<'py, Target = PyTuple>,
+    options: &SubmitOptions,
+) -> PyResult<TaskHandle> {
+    let args = args.into_pyobject(py).map_err(Into::into)?.into_bound();
+    let kwargs = options.kwargs.as_ref().map(|k| k.bind(py));
+    let tags = Some(options.tags.clone());
+
+    let handle = match options.priority {
+        Some(priority) => {
+            let func = func.clone().unbind();
+            let wrapper = PriorityParallelWrapper { func, name: None, priority_class: None, namespaced: false };
+            wrapper.call_with(py, &args, priority, None, None, options.timeout, options.total_timeout, tags, kwargs)?
+        }
+        None => ParallelWrapper::new(py, func.clone().unbind(), Isolation::Thread, None, None, Vec::new(), None)
+            .call_with(py, &args, options.timeout, options.total_timeout, tags, false, false, kwargs)?
+            .extract(py)?,
+    };
+    Ok(TaskHandle { handle })
+}
+
+/// Number of tasks currently running or queued
+pub fn active_task_count() -> usize {
+    crate::get_active_task_count()
+}
+
+/// Stop accepting tasks and wait up to `timeout` for running ones to finish
+///
+/// Returns whether every task finished in time.
+pub fn shutdown(timeout: Duration, cancel_pending: bool) -> PyResult<bool> {
+    Python::attach(|py| crate::shutdown(py, Some(timeout.as_secs_f64()), cancel_pending))
+}
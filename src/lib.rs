@@ -1,22 +1,26 @@
 use pyo3::IntoPyObjectExt;
 use pyo3::prelude::*;
-use pyo3::types::{PyCFunction, PyDict, PyTuple};
+use pyo3::basic::CompareOp;
+use pyo3::types::{
+    PyBool, PyBytes, PyCFunction, PyDict, PyFloat, PyInt, PyIterator, PyList, PySet, PyString, PyTuple, PyType,
+};
 use pyo3::wrap_pyfunction;
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet, VecDeque};
 use std::sync::mpsc::{Receiver, Sender, channel};
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 use std::cmp::Ordering as CmpOrdering;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::path::PathBuf;
 
 // Optimized imports
 use crossbeam::channel::{Receiver as CrossbeamReceiver, Sender as CrossbeamSender, unbounded};
 use dashmap::DashMap;
 use rayon::prelude::*;
 use once_cell::sync::Lazy;
-use parking_lot::Mutex;  // Faster mutex implementation
+use parking_lot::{Condvar, Mutex};  // Faster mutex implementation
 
 // Logging
 use log::{debug, warn, error};
@@ -24,8 +28,22 @@ use log::{debug, warn, error};
 // System monitoring
 use sysinfo::System;
 
+// Randomized backoff
+use rand::Rng;
+
+// Encryption of persisted results
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_128_GCM, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+
 // Module imports
 mod types;
+mod platform;
+mod cgroup;
+#[cfg(feature = "embed")]
+pub mod embed;
+
+use platform::ThreadPriority;
+use types::MakeParallelError;
 use types::TaskError as CustomTaskError;
 
 type TaskError = CustomTaskError;
@@ -33,23 +51,17 @@ type TaskError = CustomTaskError;
 // Callback types
 type CallbackFunc = Arc<Mutex<Option<Py<PyAny>>>>;
 
+// Channel types carrying a task result back to its handle
+type ResultSender = Sender<PyResult<Py<PyAny>>>;
+type ResultReceiver = Receiver<PyResult<Py<PyAny>>>;
+
 // Task dependency tracking
 static TASK_DEPENDENCIES: Lazy<Arc<DashMap<String, Vec<String>>>> =
     Lazy::new(|| Arc::new(DashMap::new()));
 
-static TASK_RESULTS: Lazy<Arc<DashMap<String, Py<PyAny>>>> =
-    Lazy::new(|| Arc::new(DashMap::new()));
-
-// Store task errors for dependency failure propagation
-static TASK_ERRORS: Lazy<Arc<DashMap<String, String>>> =
-    Lazy::new(|| Arc::new(DashMap::new()));
-
-// Track dependency reference counts for cleanup
-static DEPENDENCY_COUNTS: Lazy<Arc<DashMap<String, usize>>> =
-    Lazy::new(|| Arc::new(DashMap::new()));
-
 // Timeout cancellation handles
-static TIMEOUT_HANDLES: Lazy<Arc<Mutex<Vec<(String, Sender<()>)>>>> =
+type TimeoutHandle = (String, Sender<()>);
+static TIMEOUT_HANDLES: Lazy<Arc<Mutex<Vec<TimeoutHandle>>>> =
     Lazy::new(|| Arc::new(Mutex::new(Vec::new())));
 
 // System monitor for memory checking
@@ -58,8 +70,79 @@ static SYSTEM_MONITOR: Lazy<Mutex<System>> = Lazy::new(|| Mutex::new(System::new
 /// Global shutdown flag
 static SHUTDOWN_FLAG: Lazy<Arc<AtomicBool>> = Lazy::new(|| Arc::new(AtomicBool::new(false)));
 
+/// Registry entry for a submitted task that has not finished yet
+#[derive(Clone)]
+struct ActiveTask {
+    task_id: String,
+    func_name: String,
+    tags: Vec<String>,
+    cancel_token: Arc<AtomicBool>,
+    is_complete: Arc<Mutex<bool>>,
+    started_at: Instant,
+    /// "file:line" of the Python code that submitted the task, when captured
+    submitted_from: Option<String>,
+}
+
+impl From<String> for ActiveTask {
+    fn from(task_id: String) -> Self {
+        ActiveTask {
+            task_id,
+            func_name: "unknown".to_string(),
+            tags: Vec::new(),
+            cancel_token: Arc::new(AtomicBool::new(false)),
+            is_complete: Arc::new(Mutex::new(false)),
+            started_at: Instant::now(),
+            submitted_from: None,
+        }
+    }
+}
+
+impl ActiveTask {
+    /// Cancel the task like `AsyncHandle.cancel()`. Returns false when the
+    /// task was already finishing or cancelled.
+    fn cancel(&self) -> bool {
+        let mut is_complete = self.is_complete.lock();
+        if *is_complete || self.cancel_token.load(Ordering::Acquire) {
+            return false;
+        }
+        self.cancel_token.store(true, Ordering::Release);
+        *is_complete = true;
+        true
+    }
+
+    /// "pending" or "queued" until its function starts, then "running", or "paused" while
+    /// blocked in checkpoint(); "cancelling" once cancelled
+    fn state(&self) -> &'static str {
+        match unfinished_status(&self.task_id, &self.cancel_token, &self.is_complete) {
+            TaskStatus::Pending => "pending",
+            TaskStatus::Running if is_blocked_in_checkpoint(&self.task_id) => "paused",
+            TaskStatus::Running => "running",
+            TaskStatus::Cancelled => "cancelling",
+            _ => "queued",
+        }
+    }
+
+    /// Describe the task as a Python dict
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("task_id", &self.task_id)?;
+        dict.set_item("name", &self.func_name)?;
+        dict.set_item("tags", self.tags.clone())?;
+        dict.set_item("state", self.state())?;
+        dict.set_item("elapsed", self.started_at.elapsed().as_secs_f64())?;
+        let run_time = TASK_STARTS.lock().get(&self.task_id).map(|started| started.elapsed().as_secs_f64());
+        dict.set_item("run_time", run_time)?;
+        dict.set_item("progress", TASK_PROGRESS_MAP.get(&self.task_id).map(|p| *p).unwrap_or(0.0))?;
+        dict.set_item("submitted_from", self.submitted_from.as_deref())?;
+        Ok(dict)
+    }
+}
+
 /// Active task handles for shutdown
-static ACTIVE_TASKS: Lazy<Arc<Mutex<Vec<String>>>> = Lazy::new(|| Arc::new(Mutex::new(Vec::new())));
+static ACTIVE_TASKS: Lazy<Arc<Mutex<Vec<ActiveTask>>>> = Lazy::new(|| Arc::new(Mutex::new(Vec::new())));
+
+/// When each active task's function started; tasks missing here are still queued
+static TASK_STARTS: Lazy<Mutex<HashMap<String, Instant>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 
 /// Task ID counter
 static TASK_ID_COUNTER: Lazy<Arc<AtomicU64>> = Lazy::new(|| Arc::new(AtomicU64::new(0)));
@@ -70,14 +153,82 @@ fn is_shutdown_requested() -> bool {
 }
 
 /// Register a task as active
-fn register_task(task_id: String) {
-    ACTIVE_TASKS.lock().push(task_id);
+fn register_task(task: impl Into<ActiveTask>) {
+    ACTIVE_TASKS.lock().push(task.into());
 }
 
 /// Unregister a task
 fn unregister_task(task_id: &str) {
-    let mut tasks = ACTIVE_TASKS.lock();
-    tasks.retain(|id| id != task_id);
+    let mut active = ACTIVE_TASKS.lock();
+    let cancelled = active
+        .iter()
+        .find(|task| task.task_id == task_id)
+        .is_some_and(|task| task.cancel_token.load(Ordering::Acquire));
+    active.retain(|task| task.task_id != task_id);
+    drop(active);
+    TASK_STARTS.lock().remove(task_id);
+    PAUSED_TASKS.lock().remove(task_id);
+    release_task_workspace(task_id);
+    release_task_checkpoints(task_id, cancelled);
+}
+
+/// Record that a task's function has started running
+fn mark_task_running(task_id: &str) {
+    TASK_STARTS.lock().entry(task_id.to_string()).or_insert_with(Instant::now);
+}
+
+/// Lifecycle state of a task, as returned by `handle.status()`
+///
+/// PENDING tasks wait on dependencies or a parent task, QUEUED ones on a worker.
+/// SUCCEEDED, FAILED, CANCELLED and TIMED_OUT are final.
+#[pyclass(eq, eq_int, frozen, rename_all = "SCREAMING_SNAKE_CASE")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TaskStatus {
+    Pending,
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+    TimedOut,
+}
+
+#[pymethods]
+impl TaskStatus {
+    /// Whether the task has reached a final state
+    #[getter]
+    fn is_final(&self) -> bool {
+        matches!(self, TaskStatus::Succeeded | TaskStatus::Failed | TaskStatus::Cancelled | TaskStatus::TimedOut)
+    }
+}
+
+impl TaskStatus {
+    /// Final status of a task that failed with `task_error`
+    fn of_failure(task_error: &TaskError, timeout_limit: Option<f64>) -> TaskStatus {
+        if task_error.error_type == "CancellationError" {
+            TaskStatus::Cancelled
+        } else if timeout_limit.is_some() || task_error.error_type == "TimeoutError" {
+            TaskStatus::TimedOut
+        } else {
+            TaskStatus::Failed
+        }
+    }
+}
+
+/// Status of a task without an outcome yet
+///
+/// cancel() counts as final right away, as it does for is_ready(); a timeout
+/// only shows once the task has stopped.
+fn unfinished_status(task_id: &str, cancel_token: &AtomicBool, is_complete: &Mutex<bool>) -> TaskStatus {
+    if cancel_token.load(Ordering::Acquire) && *is_complete.lock() {
+        TaskStatus::Cancelled
+    } else if TASK_STARTS.lock().contains_key(task_id) {
+        TaskStatus::Running
+    } else if TASK_DEPENDENCIES.contains_key(task_id) || !ACTIVE_TASKS.lock().iter().any(|task| task.task_id == task_id) {
+        TaskStatus::Pending
+    } else {
+        TaskStatus::Queued
+    }
 }
 
 /// Get active task count
@@ -86,9 +237,138 @@ fn get_active_task_count() -> usize {
     ACTIVE_TASKS.lock().len()
 }
 
+/// Submitted tasks that have not finished, optionally filtered by state and tag
+///
+/// Each task is a dict with `task_id`, `name`, `tags`, `state` ("pending", "queued",
+/// "running", "paused" or "cancelling"), `elapsed` since submission, `run_time` since its function
+/// started (None until then), `progress` from report_progress() and `submitted_from`.
+#[pyfunction]
+#[pyo3(signature = (status=None, tag=None))]
+fn list_tasks<'py>(py: Python<'py>, status: Option<&str>, tag: Option<&str>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    if let Some(status) = status {
+        if !["pending", "queued", "running", "paused", "cancelling"].contains(&status) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "status must be \"pending\", \"queued\", \"running\", \"paused\" or \"cancelling\", got {:?}",
+                status
+            )));
+        }
+    }
+    let snapshot: Vec<ActiveTask> = ACTIVE_TASKS.lock().clone();
+    snapshot
+        .iter()
+        .filter(|task| status.is_none_or(|status| task.state() == status))
+        .filter(|task| tag.is_none_or(|tag| task.tags.iter().any(|t| t == tag)))
+        .map(|task| task.to_dict(py))
+        .collect()
+}
+
+/// Info on one unfinished task, as in list_tasks(), or None once it has finished
+#[pyfunction]
+fn get_task_status<'py>(py: Python<'py>, task_id: &str) -> PyResult<Option<Bound<'py, PyDict>>> {
+    let task = ACTIVE_TASKS.lock().iter().find(|task| task.task_id == task_id).cloned();
+    task.map(|task| task.to_dict(py)).transpose()
+}
+
+/// Whether submissions record the caller's file and line
+static CAPTURE_SUBMISSION_SITES: AtomicBool = AtomicBool::new(false);
+
+/// Record where each task is submitted from
+///
+/// The caller's "file:line" is read from its Python frame at submission and shows up
+/// as `submitted_from` in handle metadata, task info dicts, events and TaskError.
+#[pyfunction]
+#[pyo3(signature = (enabled=true))]
+fn capture_submission_sites(enabled: bool) {
+    CAPTURE_SUBMISSION_SITES.store(enabled, Ordering::Release);
+}
+
+/// "file:line" of the innermost Python frame on this thread, if capture is on
+fn submission_site() -> Option<String> {
+    if !CAPTURE_SUBMISSION_SITES.load(Ordering::Acquire) {
+        return None;
+    }
+    Python::attach(|py| {
+        // Worker threads running no Python code have no frame; _getframe raises there
+        let frame = py.import("sys").ok()?.call_method1("_getframe", (0,)).ok()?;
+        let filename: String = frame.getattr("f_code").ok()?.getattr("co_filename").ok()?.extract().ok()?;
+        let line: u32 = frame.getattr("f_lineno").ok()?.extract().ok()?;
+        Some(format!("{}:{}", filename, line))
+    })
+}
+
+/// Where an active task was submitted from, when captured
+fn task_submission_site(task_id: &str) -> Option<String> {
+    ACTIVE_TASKS.lock().iter().find(|task| task.task_id == task_id)?.submitted_from.clone()
+}
+
+/// Metadata a new handle starts with
+fn initial_metadata(submitted_from: &Option<String>) -> Arc<Mutex<HashMap<String, String>>> {
+    let metadata = submitted_from.iter().map(|site| ("submitted_from".to_string(), site.clone())).collect();
+    Arc::new(Mutex::new(metadata))
+}
+
+/// Build the summary dict returned by the batch cancel functions
+fn cancel_summary(py: Python, cancelled: usize, already_finishing: usize) -> PyResult<Py<PyDict>> {
+    debug!(
+        "Batch cancel: {} cancelled, {} already finishing",
+        cancelled, already_finishing
+    );
+    let dict = PyDict::new(py);
+    dict.set_item("cancelled", cancelled)?;
+    dict.set_item("already_finishing", already_finishing)?;
+    Ok(dict.unbind())
+}
+
+/// Cancel every active task for which `predicate(task_info)` is true.
+///
+/// `task_info` is a dict with `task_id`, `name`, `tags` and `elapsed`.
+#[pyfunction]
+fn cancel_tasks(py: Python, predicate: Py<PyAny>) -> PyResult<Py<PyDict>> {
+    // Snapshot the registry in one lock acquisition; the predicate runs
+    // without the lock held so it may call back into makeparallel
+    let snapshot: Vec<ActiveTask> = ACTIVE_TASKS.lock().clone();
+
+    let mut cancelled = 0;
+    let mut already_finishing = 0;
+    for task in snapshot.iter() {
+        if !predicate.bind(py).call1((task.to_dict(py)?,))?.is_truthy()? {
+            continue;
+        }
+        if task.cancel() {
+            cancelled += 1;
+        } else {
+            already_finishing += 1;
+        }
+    }
+
+    cancel_summary(py, cancelled, already_finishing)
+}
+
+/// Cancel every active task except those carrying one of `except_tags`
+#[pyfunction]
+#[pyo3(signature = (except_tags=None))]
+fn cancel_all(py: Python, except_tags: Option<Vec<String>>) -> PyResult<Py<PyDict>> {
+    let except_tags = except_tags.unwrap_or_default();
+    let mut cancelled = 0;
+    let mut already_finishing = 0;
+
+    for task in ACTIVE_TASKS.lock().iter() {
+        if task.tags.iter().any(|tag| except_tags.contains(tag)) {
+            continue;
+        }
+        if task.cancel() {
+            cancelled += 1;
+        } else {
+            already_finishing += 1;
+        }
+    }
+
+    cancel_summary(py, cancelled, already_finishing)
+}
+
 /// Initiate graceful shutdown
 #[pyfunction]
-fn shutdown(timeout_secs: Option<f64>, cancel_pending: bool) -> PyResult<bool> {
+fn shutdown(py: Python, timeout_secs: Option<f64>, cancel_pending: bool) -> PyResult<bool> {
     println!("Initiating graceful shutdown...");
     SHUTDOWN_FLAG.store(true, Ordering::Release);
 
@@ -103,6 +383,7 @@ fn shutdown(timeout_secs: Option<f64>, cancel_pending: bool) -> PyResult<bool> {
         let active_count = get_active_task_count();
         if active_count == 0 {
             println!("All tasks completed. Shutdown successful.");
+            close_all_resources(py);
             return Ok(true);
         }
 
@@ -115,7 +396,8 @@ fn shutdown(timeout_secs: Option<f64>, cancel_pending: bool) -> PyResult<bool> {
             return Ok(false);
         }
 
-        thread::sleep(Duration::from_millis(100));
+        // Without the GIL, so the tasks being waited for can finish
+        py.detach(|| thread::sleep(Duration::from_millis(100)));
     }
 }
 
@@ -139,12 +421,12 @@ fn set_max_concurrent_tasks(max_tasks: usize) -> PyResult<()> {
 
 /// Wait for available slot (backpressure)
 fn wait_for_slot() {
-    if let Some(max) = *MAX_CONCURRENT_TASKS.lock() {
+    if let Some(max) = concurrency_limit() {
         let start = Instant::now();
         let timeout = Duration::from_secs(300); // 5 minute timeout
         let mut backoff = Duration::from_millis(10);
 
-        while get_active_task_count() >= max {
+        while get_active_task_count() >= concurrency_limit().unwrap_or(max) {
             // CRITICAL FIX: Check shutdown
             if is_shutdown_requested() {
                 warn!("wait_for_slot cancelled: shutdown in progress");
@@ -165,1993 +447,14112 @@ fn wait_for_slot() {
     }
 }
 
+/// Concurrency limit adjusted from task outcomes by additive-increase/multiplicative-decrease
+///
+/// A task that fails or runs past `latency_threshold` multiplies the limit by
+/// `backoff_ratio`. Any other task raises it by one, but only while at least half
+/// the limit is in use, so an idle system doesn't inflate it.
+#[derive(Clone, Debug)]
+struct AimdLimit {
+    limit: usize,
+    min_limit: usize,
+    max_limit: usize,
+    latency_threshold_ms: Option<f64>,
+    decrease_on_failure: bool,
+    backoff_ratio: f64,
+    increases: u64,
+    decreases: u64,
+}
+
+impl AimdLimit {
+    fn on_sample(&mut self, duration_ms: f64, success: bool, in_flight: usize) {
+        let overloaded = (!success && self.decrease_on_failure)
+            || self.latency_threshold_ms.is_some_and(|threshold| duration_ms > threshold);
+        if overloaded {
+            let reduced = ((self.limit as f64 * self.backoff_ratio) as usize).max(self.min_limit);
+            if reduced < self.limit {
+                self.limit = reduced;
+                self.decreases += 1;
+            }
+        } else if in_flight * 2 >= self.limit && self.limit < self.max_limit {
+            self.limit += 1;
+            self.increases += 1;
+        }
+    }
+}
+
+static ADAPTIVE_CONCURRENCY: Lazy<Mutex<Option<AimdLimit>>> = Lazy::new(|| Mutex::new(None));
+
+/// Current task limit: the adaptive one if enabled, else set_max_concurrent_tasks()
+fn concurrency_limit() -> Option<usize> {
+    if let Some(aimd) = &*ADAPTIVE_CONCURRENCY.lock() {
+        return Some(aimd.limit);
+    }
+    *MAX_CONCURRENT_TASKS.lock()
+}
+
+/// Feed a finished task to the adaptive limit, if enabled
+fn observe_task_outcome(duration_ms: f64, success: bool) {
+    if let Some(aimd) = &mut *ADAPTIVE_CONCURRENCY.lock() {
+        aimd.on_sample(duration_ms, success, get_active_task_count());
+    }
+}
+
+/// Let the concurrent task limit follow observed latency and failures (AIMD)
+///
+/// Replaces the fixed set_max_concurrent_tasks() limit until disabled. The limit
+/// starts at `initial_limit` (default: the fixed limit, or the CPU count) and moves
+/// between `min_limit` and `max_limit`. Each task that fails (with
+/// `decrease_on_failure`) or takes longer than `latency_threshold` seconds multiplies
+/// it by `backoff_ratio`; each other task adds one while at least half of it is in use.
+#[pyfunction]
+#[pyo3(signature = (
+    initial_limit=None,
+    min_limit=1,
+    max_limit=None,
+    latency_threshold=None,
+    decrease_on_failure=true,
+    backoff_ratio=0.9
+))]
+fn enable_adaptive_concurrency(
+    initial_limit: Option<usize>,
+    min_limit: usize,
+    max_limit: Option<usize>,
+    latency_threshold: Option<f64>,
+    decrease_on_failure: bool,
+    backoff_ratio: f64,
+) -> PyResult<()> {
+    let max_limit = max_limit.unwrap_or(usize::MAX);
+    if min_limit == 0 || max_limit < min_limit {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("need 1 <= min_limit <= max_limit"));
+    }
+    if !(backoff_ratio > 0.0 && backoff_ratio < 1.0) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("backoff_ratio must be between 0 and 1"));
+    }
+    if latency_threshold.is_some_and(|secs| !secs.is_finite() || secs <= 0.0) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "latency_threshold must be a positive number of seconds"
+        ));
+    }
+    let initial_limit = initial_limit
+        .or(*MAX_CONCURRENT_TASKS.lock())
+        .unwrap_or_else(default_thread_count);
+    *ADAPTIVE_CONCURRENCY.lock() = Some(AimdLimit {
+        limit: initial_limit.clamp(min_limit, max_limit),
+        min_limit,
+        max_limit,
+        latency_threshold_ms: latency_threshold.map(|secs| secs * 1000.0),
+        decrease_on_failure,
+        backoff_ratio,
+        increases: 0,
+        decreases: 0,
+    });
+    Ok(())
+}
+
+/// Return to the fixed set_max_concurrent_tasks() limit; returns False if adaptive concurrency was off
+#[pyfunction]
+fn disable_adaptive_concurrency() -> bool {
+    ADAPTIVE_CONCURRENCY.lock().take().is_some()
+}
+
+/// The limit wait_for_slot() applies now, tasks in flight, and the adaptive controller's counts
+#[pyfunction]
+fn get_concurrency_limit(py: Python) -> PyResult<Py<PyDict>> {
+    let aimd = ADAPTIVE_CONCURRENCY.lock().clone();
+    let info = PyDict::new(py);
+    info.set_item("adaptive", aimd.is_some())?;
+    info.set_item("limit", concurrency_limit())?;
+    info.set_item("in_flight", get_active_task_count())?;
+    info.set_item("increases", aimd.as_ref().map_or(0, |a| a.increases))?;
+    info.set_item("decreases", aimd.as_ref().map_or(0, |a| a.decreases))?;
+    Ok(info.unbind())
+}
+
+// =============================================================================
+// CONTAINER LIMITS
+// =============================================================================
+
+/// Threads to use when the caller doesn't say: the CPUs available, capped by a container CPU quota
+fn default_thread_count() -> usize {
+    let cpus = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    match cgroup::cpu_limit() {
+        Some(quota) => cpus.min(quota.ceil() as usize).max(1),
+        None => cpus,
+    }
+}
+
+/// Limits of the container (cgroup) this process runs in
+///
+/// Returns `cgroup_version`, `memory_limit_bytes`, `memory_usage_bytes` and
+/// `cpu_limit` (cores); each is None when not running under a limit.
+#[pyfunction]
+fn get_container_limits(py: Python) -> PyResult<Py<PyDict>> {
+    let memory_limit = cgroup::memory_limit();
+    let dict = PyDict::new(py);
+    dict.set_item("cgroup_version", cgroup::version())?;
+    dict.set_item("memory_limit_bytes", memory_limit)?;
+    dict.set_item("memory_usage_bytes", memory_limit.and(cgroup::memory_usage()))?;
+    dict.set_item("cpu_limit", cgroup::cpu_limit())?;
+    Ok(dict.unbind())
+}
+
 // =============================================================================
 // MEMORY MONITORING
 // =============================================================================
 
-/// Global memory limit (percentage)
-static MEMORY_LIMIT_PERCENT: Lazy<Arc<Mutex<Option<f64>>>> =
-    Lazy::new(|| Arc::new(Mutex::new(None)));
+/// Whose memory a memory limit measures
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MemoryScope {
+    /// Memory used by everything on the host
+    System,
+    /// This process's resident set size
+    Process,
+}
+
+/// Limits checked before each task start
+struct MemoryLimit {
+    scope: MemoryScope,
+    max_percent: Option<f64>,
+    max_bytes: Option<u64>,
+}
+
+/// Global memory limit
+static MEMORY_LIMIT: Lazy<Mutex<Option<MemoryLimit>>> = Lazy::new(|| Mutex::new(None));
 
 /// Configure memory limit
+///
+/// `scope="system"` (the default) compares the host's used memory against
+/// `max_memory_percent`, so other processes count too. `scope="process"` compares this
+/// process's resident memory instead, against `max_memory_percent` of total memory
+/// and/or a hard cap of `max_bytes`. Call with no limits to remove it.
 #[pyfunction]
-fn configure_memory_limit(max_memory_percent: f64) -> PyResult<()> {
-    if max_memory_percent <= 0.0 || max_memory_percent > 100.0 {
+#[pyo3(signature = (max_memory_percent=None, *, scope="system", max_bytes=None))]
+fn configure_memory_limit(max_memory_percent: Option<f64>, scope: &str, max_bytes: Option<u64>) -> PyResult<()> {
+    let scope = match scope {
+        "system" => MemoryScope::System,
+        "process" => MemoryScope::Process,
+        other => {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "scope must be \"system\" or \"process\", got {:?}",
+                other
+            )))
+        }
+    };
+    if max_memory_percent.is_some_and(|percent| percent <= 0.0 || percent > 100.0) {
         return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
             "max_memory_percent must be between 0 and 100"
         ));
     }
-    *MEMORY_LIMIT_PERCENT.lock() = Some(max_memory_percent);
+    if max_bytes.is_some() && scope != MemoryScope::Process {
+        return Err(pyo3::exceptions::PyValueError::new_err("max_bytes requires scope=\"process\""));
+    }
+    if max_bytes == Some(0) {
+        return Err(pyo3::exceptions::PyValueError::new_err("max_bytes must be at least 1"));
+    }
+    let limit = (max_memory_percent.is_some() || max_bytes.is_some())
+        .then_some(MemoryLimit { scope, max_percent: max_memory_percent, max_bytes });
+    *MEMORY_LIMIT.lock() = limit;
     Ok(())
 }
 
+/// Resident memory of this process in bytes, None if it cannot be read
+fn process_memory_bytes(sys: &mut System) -> Option<u64> {
+    let pid = sysinfo::get_current_pid().ok()?;
+    sys.refresh_processes_specifics(
+        sysinfo::ProcessesToUpdate::Some(&[pid]),
+        sysinfo::ProcessRefreshKind::new().with_memory(),
+    );
+    sys.process(pid).map(|process| process.memory())
+}
+
 /// Check if memory usage is acceptable
 fn check_memory_ok() -> bool {
-    if let Some(limit_percent) = *MEMORY_LIMIT_PERCENT.lock() {
-        // CRITICAL FIX: Implement actual memory monitoring
-        let mut sys = SYSTEM_MONITOR.lock();
-        sys.refresh_memory();
-
-        let total = sys.total_memory();
-        let used = sys.used_memory();
-        let usage_percent = (used as f64 / total as f64) * 100.0;
+    let limit = MEMORY_LIMIT.lock();
+    let Some(limit) = limit.as_ref() else {
+        return true;
+    };
+    let mut sys = SYSTEM_MONITOR.lock();
+    sys.refresh_memory();
+
+    // Inside a container the cgroup's limit and usage stand in for the host's
+    let container_limit = cgroup::memory_limit();
+    let total = container_limit.map_or(sys.total_memory(), |limit| limit.min(sys.total_memory()));
+    let (scope, used) = match limit.scope {
+        MemoryScope::System => match container_limit.and(cgroup::memory_usage()) {
+            Some(used) => ("Container", used),
+            None => ("System", sys.used_memory()),
+        },
+        MemoryScope::Process => match process_memory_bytes(&mut sys) {
+            Some(rss) => ("Process", rss),
+            None => {
+                warn!("Cannot read this process's memory usage; memory limit not enforced");
+                return true;
+            }
+        },
+    };
+    let usage_percent = (used as f64 / total as f64) * 100.0;
 
+    if let Some(limit_percent) = limit.max_percent {
         if usage_percent > limit_percent {
             warn!(
-                "Memory limit exceeded: {:.1}% used (limit: {:.1}%)",
+                "{} memory limit exceeded: {:.1}% used (limit: {:.1}%)",
+                scope,
                 usage_percent,
                 limit_percent
             );
             return false;
         }
-
-        debug!("Memory usage: {:.1}%", usage_percent);
-        true
-    } else {
-        true
     }
+    if let Some(max_bytes) = limit.max_bytes {
+        if used > max_bytes {
+            warn!("Process memory limit exceeded: {} bytes resident (limit: {} bytes)", used, max_bytes);
+            return false;
+        }
+    }
+
+    debug!("{} memory usage: {:.1}%", scope, usage_percent);
+    true
 }
 
 // =============================================================================
-// PROGRESS TRACKING
+// LOAD MONITORING
 // =============================================================================
 
-/// Global progress tracking
-static TASK_PROGRESS_MAP: Lazy<Arc<DashMap<String, f64>>> =
-    Lazy::new(|| Arc::new(DashMap::new()));
-
-// Thread-local storage for current task ID
-thread_local! {
-    static CURRENT_TASK_ID: RefCell<Option<String>> = RefCell::new(None);
-}
+/// Fraction below the limit that usage must fall to before paused starts resume
+const LOAD_HYSTERESIS: f64 = 0.1;
 
-/// Set the current task ID for this thread (internal use)
-fn set_current_task_id(task_id: Option<String>) {
-    CURRENT_TASK_ID.with(|id| {
-        *id.borrow_mut() = task_id;
-    });
+/// Host load limits and whether task starts are currently paused
+struct LoadLimit {
+    max_load_avg: Option<f64>,
+    max_cpu_percent: Option<f64>,
+    paused: bool,
 }
 
-/// Get the current task ID for this thread
-#[pyfunction]
-fn get_current_task_id() -> PyResult<Option<String>> {
-    Ok(CURRENT_TASK_ID.with(|id| id.borrow().clone()))
-}
+static LOAD_LIMIT: Lazy<Mutex<Option<LoadLimit>>> = Lazy::new(|| Mutex::new(None));
 
-/// Report progress from within a task (with explicit task_id)
+/// Delay new task starts while the host is overloaded
+///
+/// Starts pause once the 1-minute load average or global CPU usage exceeds its limit,
+/// and resume only after it drops 10% below the limit. Call with no arguments to remove
+/// the limit.
 #[pyfunction]
-#[pyo3(signature = (progress, task_id=None))]
-fn report_progress(progress: f64, task_id: Option<String>) -> PyResult<()> {
-    // CRITICAL FIX: Add NaN/Inf check
-    if !progress.is_finite() {
+#[pyo3(signature = (max_load_avg=None, max_cpu_percent=None))]
+fn configure_load_limit(max_load_avg: Option<f64>, max_cpu_percent: Option<f64>) -> PyResult<()> {
+    if max_load_avg.is_some_and(|limit| limit <= 0.0) {
         return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "progress must be a finite number (not NaN or Infinity)"
+            "max_load_avg must be positive"
         ));
     }
-
-    if progress < 0.0 || progress > 1.0 {
+    if max_cpu_percent.is_some_and(|limit| limit <= 0.0 || limit > 100.0) {
         return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            "progress must be between 0.0 and 1.0"
+            "max_cpu_percent must be between 0 and 100"
         ));
     }
+    *LOAD_LIMIT.lock() = (max_load_avg.is_some() || max_cpu_percent.is_some()).then_some(LoadLimit {
+        max_load_avg,
+        max_cpu_percent,
+        paused: false,
+    });
+    Ok(())
+}
 
-    // Use provided task_id or get from thread-local storage
-    let actual_task_id = if let Some(tid) = task_id {
-        tid
+/// Whether usage is over its limit, given whether starts are already paused
+fn over_load_limit(usage: f64, limit: f64, paused: bool) -> bool {
+    if paused {
+        usage >= limit * (1.0 - LOAD_HYSTERESIS)
     } else {
-        CURRENT_TASK_ID.with(|id| {
-            id.borrow().clone().ok_or_else(|| {
-                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                    "No task_id found. report_progress must be called from within a @parallel decorated function, or you must provide task_id explicitly."
-                )
-            })
-        })?
-    };
-
-    TASK_PROGRESS_MAP.insert(actual_task_id.clone(), progress);
-
-    // CRITICAL FIX: Non-blocking callback with error handling
-    if let Some(callback) = TASK_PROGRESS_CALLBACKS.get(&actual_task_id) {
-        Python::attach(|py| {
-            // Execute callback with error handling
-            match callback.bind(py).call1((progress,)) {
-                Ok(_) => {},
-                Err(e) => {
-                    warn!("Progress callback failed for task {}: {}", actual_task_id, e);
-                }
-            }
-        });
+        usage > limit
     }
-
-    Ok(())
 }
 
-/// Global map for progress callbacks
-static TASK_PROGRESS_CALLBACKS: Lazy<Arc<DashMap<String, Py<PyAny>>>> =
-    Lazy::new(|| Arc::new(DashMap::new()));
+/// Check host load against the configured limit, updating the paused state
+fn check_load_ok() -> bool {
+    let mut guard = LOAD_LIMIT.lock();
+    let Some(limit) = guard.as_mut() else {
+        return true;
+    };
 
-/// Register progress callback for a task (internal)
-fn register_progress_callback(task_id: String, callback: Py<PyAny>) {
-    TASK_PROGRESS_CALLBACKS.insert(task_id, callback);
-}
+    let load_over = limit.max_load_avg.map(|max| {
+        let load = System::load_average().one;
+        (over_load_limit(load, max, limit.paused), format!("load average {:.2} (limit: {:.2})", load, max))
+    });
+    let cpu_over = limit.max_cpu_percent.map(|max| {
+        let mut sys = SYSTEM_MONITOR.lock();
+        sys.refresh_cpu_usage();
+        let usage = sys.global_cpu_usage() as f64;
+        (over_load_limit(usage, max, limit.paused), format!("CPU {:.1}% (limit: {:.1}%)", usage, max))
+    });
 
-/// Unregister progress callback (internal)
-fn unregister_progress_callback(task_id: &str) {
-    TASK_PROGRESS_CALLBACKS.remove(task_id);
+    let overloaded: Vec<String> = [load_over, cpu_over]
+        .into_iter()
+        .flatten()
+        .filter_map(|(over, reading)| over.then_some(reading))
+        .collect();
+    let was_paused = limit.paused;
+    limit.paused = !overloaded.is_empty();
+
+    if limit.paused && !was_paused {
+        warn!("Host overloaded, delaying new tasks: {}", overloaded.join(", "));
+    } else if was_paused && !limit.paused {
+        debug!("Host load back under limit, resuming task starts");
+    }
+    !limit.paused
 }
 
-/// Clear progress for a completed task (internal cleanup)
-fn clear_task_progress(task_id: &str) {
-    TASK_PROGRESS_MAP.remove(task_id);
-    unregister_progress_callback(task_id);
+/// Wait until host load allows a new task to start
+fn wait_for_load() {
+    let start = Instant::now();
+    let timeout = Duration::from_secs(300); // 5 minute timeout, as in wait_for_slot
+
+    while !check_load_ok() {
+        if is_shutdown_requested() {
+            warn!("wait_for_load cancelled: shutdown in progress");
+            return;
+        }
+        if start.elapsed() > timeout {
+            error!("wait_for_load timed out after 5 minutes");
+            return;
+        }
+        thread::sleep(Duration::from_millis(250));
+    }
 }
 
 // =============================================================================
-// THREAD POOL CONFIGURATION
+// GPU MEMORY ADMISSION
 // =============================================================================
 
-/// Global thread pool configuration
-static CUSTOM_THREAD_POOL: Lazy<Arc<Mutex<Option<rayon::ThreadPool>>>> =
-    Lazy::new(|| Arc::new(Mutex::new(None)));
+/// Tag marking tasks that allocate GPU memory
+const GPU_TAG: &str = "gpu";
+
+/// Device index and memory usage percentage above which GPU tasks wait to start
+static GPU_MEMORY_LIMIT: Lazy<Mutex<Option<(u32, f64)>>> = Lazy::new(|| Mutex::new(None));
 
-/// Configure the global thread pool size
+/// Hold back tasks tagged "gpu" while a device's memory usage is over `percent`
+///
+/// Memory usage is read through NVML (the `pynvml` module from nvidia-ml-py).
+/// Pass `percent=None` to remove the limit.
 #[pyfunction]
-#[pyo3(signature = (num_threads=None, stack_size=None))]
-fn configure_thread_pool(py: Python, num_threads: Option<usize>, stack_size: Option<usize>) -> PyResult<()> {
-    py.detach(|| {
-        let mut builder = rayon::ThreadPoolBuilder::new();
+#[pyo3(signature = (device=0, percent=None))]
+fn configure_gpu_memory_limit(py: Python, device: u32, percent: Option<f64>) -> PyResult<()> {
+    let Some(percent) = percent else {
+        *GPU_MEMORY_LIMIT.lock() = None;
+        return Ok(());
+    };
+    if percent <= 0.0 || percent > 100.0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "percent must be between 0 and 100"
+        ));
+    }
 
-        if let Some(threads) = num_threads {
-            builder = builder.num_threads(threads);
-        }
-
-        if let Some(stack) = stack_size {
-            builder = builder.stack_size(stack);
-        }
-
-        let pool = builder.build().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to build thread pool: {}", e))
-        })?;
+    let nvml = py.import("pynvml").map_err(|_| {
+        pyo3::exceptions::PyImportError::new_err(
+            "configure_gpu_memory_limit() requires pynvml (pip install nvidia-ml-py)"
+        )
+    })?;
+    nvml.call_method0("nvmlInit")?;
+    // Fail now rather than at submission if the device does not exist
+    gpu_memory_percent(py, device)?;
 
-        *CUSTOM_THREAD_POOL.lock() = Some(pool);
-        Ok(())
-    })
+    *GPU_MEMORY_LIMIT.lock() = Some((device, percent));
+    Ok(())
 }
 
-/// Get current thread pool info
-#[pyfunction]
-fn get_thread_pool_info(py: Python) -> PyResult<Py<PyDict>> {
-    let dict = PyDict::new(py);
-    let pool = CUSTOM_THREAD_POOL.lock();
+/// Percentage of a device's memory in use, as reported by NVML
+fn gpu_memory_percent(py: Python, device: u32) -> PyResult<f64> {
+    let nvml = py.import("pynvml")?;
+    let handle = nvml.call_method1("nvmlDeviceGetHandleByIndex", (device,))?;
+    let info = nvml.call_method1("nvmlDeviceGetMemoryInfo", (handle,))?;
+    let used: f64 = info.getattr("used")?.extract()?;
+    let total: f64 = info.getattr("total")?.extract()?;
+    Ok(used / total * 100.0)
+}
 
-    if let Some(p) = pool.as_ref() {
-        dict.set_item("configured", true)?;
-        dict.set_item("current_num_threads", p.current_num_threads())?;
-    } else {
-        dict.set_item("configured", false)?;
-        dict.set_item("current_num_threads", rayon::current_num_threads())?;
+/// Wait until the configured GPU has memory to spare, if the task is tagged "gpu"
+fn wait_for_gpu_memory(py: Python, tags: &[String]) {
+    if !tags.iter().any(|tag| tag == GPU_TAG) {
+        return;
     }
+    let start = Instant::now();
+    let timeout = Duration::from_secs(300); // 5 minute timeout, as in wait_for_slot
+    let mut warned = false;
 
-    Ok(dict.unbind())
+    loop {
+        let Some((device, limit)) = *GPU_MEMORY_LIMIT.lock() else {
+            return;
+        };
+        let usage = match gpu_memory_percent(py, device) {
+            Ok(usage) => usage,
+            Err(e) => {
+                warn!("Could not read memory of GPU {}, admitting task: {}", device, e);
+                return;
+            }
+        };
+        if usage <= limit {
+            return;
+        }
+        if !warned {
+            warn!("GPU {} memory at {:.1}% (limit: {:.1}%), delaying GPU task", device, usage, limit);
+            warned = true;
+        }
+        if is_shutdown_requested() {
+            warn!("wait_for_gpu_memory cancelled: shutdown in progress");
+            return;
+        }
+        if start.elapsed() > timeout {
+            error!("wait_for_gpu_memory timed out after 5 minutes");
+            return;
+        }
+        py.detach(|| thread::sleep(Duration::from_millis(250)));
+    }
 }
 
 // =============================================================================
-// PRIORITY QUEUE IMPLEMENTATION
+// TASK COST ADMISSION
 // =============================================================================
 
-/// Priority task wrapper
-struct PriorityTask {
-    priority: i32,
-    func: Py<PyAny>,
-    args: Py<PyTuple>,
-    kwargs: Option<Py<PyDict>>,
-    sender: CrossbeamSender<PyResult<Py<PyAny>>>,
+/// Resources a task declares it needs, e.g. `@parallel(cpu=2, memory_mb=512)`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct TaskCost {
+    cpu: f64,
+    memory_mb: f64,
 }
 
-impl Eq for PriorityTask {}
+impl TaskCost {
+    fn add(self, other: TaskCost) -> TaskCost {
+        TaskCost { cpu: self.cpu + other.cpu, memory_mb: self.memory_mb + other.memory_mb }
+    }
 
-impl PartialEq for PriorityTask {
-    fn eq(&self, other: &Self) -> bool {
-        self.priority == other.priority
+    fn sub(self, other: TaskCost) -> TaskCost {
+        TaskCost { cpu: self.cpu - other.cpu, memory_mb: self.memory_mb - other.memory_mb }
     }
 }
 
-impl PartialOrd for PriorityTask {
-    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
-        Some(self.cmp(other))
-    }
+/// Declared resources of running tasks against the configured capacity
+#[derive(Default)]
+struct CapacityLedger {
+    cpu_capacity: Option<f64>,
+    memory_mb_capacity: Option<f64>,
+    in_use: TaskCost,
+    running: usize,
 }
 
-impl Ord for PriorityTask {
-    fn cmp(&self, other: &Self) -> CmpOrdering {
-        // Higher priority values come first
-        self.priority.cmp(&other.priority)
+impl CapacityLedger {
+    /// Whether a task of this cost can start now
+    ///
+    /// A task is always admitted when nothing else holds capacity, so one larger than
+    /// the whole capacity runs alone instead of waiting forever.
+    fn fits(&self, cost: TaskCost) -> bool {
+        let total = self.in_use.add(cost);
+        self.running == 0
+            || (self.cpu_capacity.is_none_or(|cap| total.cpu <= cap)
+                && self.memory_mb_capacity.is_none_or(|cap| total.memory_mb <= cap))
     }
 }
 
-/// Global priority queue
-static PRIORITY_QUEUE: Lazy<Arc<Mutex<BinaryHeap<PriorityTask>>>> =
-    Lazy::new(|| Arc::new(Mutex::new(BinaryHeap::new())));
+static CAPACITY: Lazy<(Mutex<CapacityLedger>, Condvar)> =
+    Lazy::new(|| (Mutex::new(CapacityLedger::default()), Condvar::new()));
 
-/// Worker thread flag
-static PRIORITY_WORKER_RUNNING: Lazy<Arc<AtomicBool>> =
-    Lazy::new(|| Arc::new(AtomicBool::new(false)));
+/// Declared resources held by a running task, returned to the ledger on drop
+struct CostReservation(TaskCost);
 
-/// Start the priority queue worker
-#[pyfunction]
-fn start_priority_worker(py: Python) -> PyResult<()> {
-    if PRIORITY_WORKER_RUNNING.load(Ordering::Acquire) {
-        return Ok(());
+impl Drop for CostReservation {
+    fn drop(&mut self) {
+        let (ledger, released) = &*CAPACITY;
+        let mut ledger = ledger.lock();
+        ledger.in_use = ledger.in_use.sub(self.0);
+        ledger.running -= 1;
+        released.notify_all();
     }
+}
 
-    PRIORITY_WORKER_RUNNING.store(true, Ordering::Release);
-
-    py.detach(|| {
-        thread::spawn(move || {
-            while PRIORITY_WORKER_RUNNING.load(Ordering::Acquire) {
-                let task_opt = {
-                    let mut queue = PRIORITY_QUEUE.lock();
-                    queue.pop()
-                };
-
-                if let Some(task) = task_opt {
-                    Python::attach(|py| {
-                        let exec_start = Instant::now();
-
-                        // Get function name for profiling
-                        let func_name = task.func
-                            .bind(py)
-                            .getattr("__name__")
-                            .ok()
-                            .and_then(|n| n.extract::<String>().ok())
-                            .unwrap_or_else(|| "unknown".to_string());
-
-                        let result = task.func
-                            .bind(py)
-                            .call(task.args.bind(py), task.kwargs.as_ref().map(|k| k.bind(py)));
-
-                        let exec_time = exec_start.elapsed().as_secs_f64() * 1000.0; // Convert to ms
-
-                        let to_send = match result {
-                            Ok(val) => {
-                                record_task_execution(&func_name, exec_time, true);
-                                Ok(val.unbind())
-                            }
-                            Err(e) => {
-                                record_task_execution(&func_name, exec_time, false);
-                                Err(e)
-                            }
-                        };
-
-                        // CRITICAL FIX: Handle channel send errors
-                        if let Err(e) = task.sender.send(to_send) {
-                            error!("Failed to send priority task result: {}", e);
-                        }
-                    });
-                } else {
-                    thread::sleep(Duration::from_millis(10));
-                }
-            }
-        })
-    });
-
-    Ok(())
+/// Wait until the task's declared cost fits the free capacity and reserve it
+///
+/// Tasks wait independently, so smaller tasks start while a larger one is still
+/// waiting for room. Returns None if the task is cancelled or shutdown begins first.
+fn reserve_capacity(cost: TaskCost, cancel_token: &AtomicBool) -> Option<CostReservation> {
+    let (ledger, released) = &*CAPACITY;
+    let mut ledger = ledger.lock();
+    while !ledger.fits(cost) {
+        if cancel_token.load(Ordering::Acquire) || is_shutdown_requested() {
+            return None;
+        }
+        released.wait_for(&mut ledger, Duration::from_millis(100));
+    }
+    ledger.in_use = ledger.in_use.add(cost);
+    ledger.running += 1;
+    Some(CostReservation(cost))
 }
 
-/// Stop the priority queue worker
+/// Set the resource capacity shared by tasks that declare a cost
+///
+/// Tasks declaring `cpu=`/`memory_mb=` on `@parallel` only start while the sum of
+/// running tasks' declarations fits. A dimension left as None is unlimited.
 #[pyfunction]
-fn stop_priority_worker() -> PyResult<()> {
-    PRIORITY_WORKER_RUNNING.store(false, Ordering::Release);
+#[pyo3(signature = (cpu=None, memory_mb=None))]
+fn configure_capacity(cpu: Option<f64>, memory_mb: Option<f64>) -> PyResult<()> {
+    for (name, value) in [("cpu", cpu), ("memory_mb", memory_mb)] {
+        if value.is_some_and(|v| !v.is_finite() || v <= 0.0) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "{} capacity must be a positive number",
+                name
+            )));
+        }
+    }
+    let (ledger, released) = &*CAPACITY;
+    let mut ledger = ledger.lock();
+    ledger.cpu_capacity = cpu;
+    ledger.memory_mb_capacity = memory_mb;
+    released.notify_all();
     Ok(())
 }
 
-// =============================================================================
-// PERFORMANCE PROFILING
-// =============================================================================
-
-/// Performance metrics
-#[pyclass]
-#[derive(Clone)]
-struct PerformanceMetrics {
-    #[pyo3(get)]
-    total_tasks: u64,
-    #[pyo3(get)]
-    completed_tasks: u64,
-    #[pyo3(get)]
-    failed_tasks: u64,
-    #[pyo3(get)]
-    total_execution_time_ms: f64,
-    #[pyo3(get)]
-    average_execution_time_ms: f64,
+/// Declared resources in use by running tasks, and the configured capacity
+#[pyfunction]
+fn get_capacity_usage(py: Python) -> PyResult<Py<PyDict>> {
+    let ledger = CAPACITY.0.lock();
+    let usage = PyDict::new(py);
+    usage.set_item("cpu", ledger.in_use.cpu)?;
+    usage.set_item("memory_mb", ledger.in_use.memory_mb)?;
+    usage.set_item("cpu_capacity", ledger.cpu_capacity)?;
+    usage.set_item("memory_mb_capacity", ledger.memory_mb_capacity)?;
+    usage.set_item("running", ledger.running)?;
+    Ok(usage.unbind())
 }
 
-/// Global metrics tracker
-static METRICS: Lazy<Arc<Mutex<HashMap<String, PerformanceMetrics>>>> =
-    Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
-
-static TASK_COUNTER: Lazy<Arc<AtomicU64>> = Lazy::new(|| Arc::new(AtomicU64::new(0)));
-static COMPLETED_COUNTER: Lazy<Arc<AtomicU64>> = Lazy::new(|| Arc::new(AtomicU64::new(0)));
-static FAILED_COUNTER: Lazy<Arc<AtomicU64>> = Lazy::new(|| Arc::new(AtomicU64::new(0)));
+/// Per-function limit from `@parallel(max_concurrency=n)`, shared by all its calls
+struct ConcurrencyLimit {
+    max: usize,
+    running: Mutex<usize>,
+    freed: Condvar,
+}
 
-/// Record task execution
-fn record_task_execution(name: &str, duration_ms: f64, success: bool) {
-    TASK_COUNTER.fetch_add(1, Ordering::Relaxed);
+/// A running call's slot in its function's ConcurrencyLimit, freed on drop
+struct ConcurrencyPermit(Arc<ConcurrencyLimit>);
 
-    if success {
-        COMPLETED_COUNTER.fetch_add(1, Ordering::Relaxed);
-    } else {
-        FAILED_COUNTER.fetch_add(1, Ordering::Relaxed);
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        *self.0.running.lock() -= 1;
+        self.0.freed.notify_one();
     }
+}
 
-    let mut metrics = METRICS.lock();
-    let entry = metrics.entry(name.to_string()).or_insert(PerformanceMetrics {
-        total_tasks: 0,
-        completed_tasks: 0,
-        failed_tasks: 0,
-        total_execution_time_ms: 0.0,
-        average_execution_time_ms: 0.0,
-    });
+impl ConcurrencyLimit {
+    fn new(max: usize) -> Self {
+        ConcurrencyLimit { max, running: Mutex::new(0), freed: Condvar::new() }
+    }
 
-    entry.total_tasks += 1;
-    if success {
-        entry.completed_tasks += 1;
-    } else {
-        entry.failed_tasks += 1;
+    /// Wait for a free slot; returns None if the task is cancelled or shutdown begins first
+    fn acquire(self: &Arc<Self>, cancel_token: &AtomicBool) -> Option<ConcurrencyPermit> {
+        let mut running = self.running.lock();
+        while *running >= self.max {
+            if cancel_token.load(Ordering::Acquire) || is_shutdown_requested() {
+                return None;
+            }
+            self.freed.wait_for(&mut running, Duration::from_millis(100));
+        }
+        *running += 1;
+        Some(ConcurrencyPermit(self.clone()))
     }
-    entry.total_execution_time_ms += duration_ms;
-    entry.average_execution_time_ms = entry.total_execution_time_ms / entry.total_tasks as f64;
 }
 
-/// Get performance metrics for a specific function
-#[pyfunction]
-fn get_metrics(name: String) -> PyResult<Option<PerformanceMetrics>> {
-    let metrics = METRICS.lock();
-    Ok(metrics.get(&name).cloned())
+// =============================================================================
+// CAPACITY PLANNING SIMULATION
+// =============================================================================
+
+/// One task of a simulated workload, times in seconds
+#[derive(Clone, Copy, Debug)]
+struct SimTask {
+    arrival: f64,
+    duration: f64,
+    priority: i32,
+    cost: TaskCost,
 }
 
-/// Get all performance metrics
-#[pyfunction]
-fn get_all_metrics(py: Python) -> PyResult<Py<PyDict>> {
-    let dict = PyDict::new(py);
-    let metrics = METRICS.lock();
+/// A simulated task finishing at a point in time; orders the earliest first in a max-heap
+struct SimFinish(f64, usize);
 
-    for (name, metric) in metrics.iter() {
-        let metric_dict = PyDict::new(py);
-        metric_dict.set_item("total_tasks", metric.total_tasks)?;
-        metric_dict.set_item("completed_tasks", metric.completed_tasks)?;
-        metric_dict.set_item("failed_tasks", metric.failed_tasks)?;
-        metric_dict.set_item("total_execution_time_ms", metric.total_execution_time_ms)?;
-        metric_dict.set_item("average_execution_time_ms", metric.average_execution_time_ms)?;
-        dict.set_item(name.as_str(), metric_dict)?;
+impl PartialEq for SimFinish {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
     }
+}
 
-    dict.set_item("_global_total", TASK_COUNTER.load(Ordering::SeqCst))?;
-    dict.set_item("_global_completed", COMPLETED_COUNTER.load(Ordering::SeqCst))?;
-    dict.set_item("_global_failed", FAILED_COUNTER.load(Ordering::SeqCst))?;
+impl Eq for SimFinish {}
 
-    Ok(dict.unbind())
+impl PartialOrd for SimFinish {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
 }
 
-/// Reset all metrics
-#[pyfunction]
-fn reset_metrics() -> PyResult<()> {
-    METRICS.lock().clear();
-    TASK_COUNTER.store(0, Ordering::SeqCst);
-    COMPLETED_COUNTER.store(0, Ordering::SeqCst);
-    FAILED_COUNTER.store(0, Ordering::SeqCst);
-    Ok(())
+impl Ord for SimFinish {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        other.0.total_cmp(&self.0).then(other.1.cmp(&self.1))
+    }
 }
 
-// Helper wrapper that supports the descriptor protocol for methods
-#[pyclass]
-struct MethodWrapper {
-    #[allow(dead_code)]
-    func: Py<PyAny>,
-    wrapper: Py<PyAny>,
+/// Outcome of a simulation run
+#[derive(Debug, Default)]
+struct SimReport {
+    /// Time each task spent queued, in workload order
+    delays: Vec<f64>,
+    peak_concurrency: usize,
+    peak_queue_length: usize,
+    makespan: f64,
 }
 
-#[pymethods]
-impl MethodWrapper {
-    #[pyo3(signature = (*args, **kwargs))]
-    fn __call__(
-        &self,
-        py: Python,
-        args: &Bound<'_, PyTuple>,
-        kwargs: Option<&Bound<'_, PyDict>>,
-    ) -> PyResult<Py<PyAny>> {
-        self.wrapper.bind(py).call(args, kwargs).map(|r| r.unbind())
-    }
+/// Replay `tasks` through the admission rules without running anything
+///
+/// A queued task starts once a slot under `max_concurrent` is free and its cost fits
+/// `ledger`, in priority order (higher first) and then arrival order. Like the real
+/// ledger, a smaller task may start while a larger one waits for room.
+fn run_simulation(tasks: &[SimTask], max_concurrent: Option<usize>, mut ledger: CapacityLedger) -> SimReport {
+    let mut arrivals: Vec<usize> = (0..tasks.len()).collect();
+    arrivals.sort_by(|&a, &b| tasks[a].arrival.total_cmp(&tasks[b].arrival));
+    let mut arrivals = arrivals.into_iter().peekable();
 
-    fn __get__(
-        &self,
-        py: Python,
-        obj: &Bound<'_, PyAny>,
-        _objtype: Option<&Bound<'_, PyAny>>,
-    ) -> PyResult<Py<PyAny>> {
-        if obj.is_none() {
-            // Unbound method access, return self
-            return Ok(self.wrapper.clone_ref(py));
+    let mut report = SimReport { delays: vec![0.0; tasks.len()], ..Default::default() };
+    let mut queue: Vec<usize> = Vec::new();
+    let mut running: BinaryHeap<SimFinish> = BinaryHeap::new();
+
+    loop {
+        let next_finish = running.peek().map(|f| f.0);
+        let next_arrival = arrivals.peek().map(|&i| tasks[i].arrival);
+        let now = match (next_finish, next_arrival) {
+            (None, None) => break,
+            (Some(f), Some(a)) => f.min(a),
+            (Some(t), None) | (None, Some(t)) => t,
+        };
+
+        // Finishing tasks free their capacity before tasks arriving at the same moment queue up
+        while running.peek().is_some_and(|f| f.0 <= now) {
+            let SimFinish(end, index) = running.pop().expect("peeked");
+            ledger.in_use = ledger.in_use.sub(tasks[index].cost);
+            ledger.running -= 1;
+            report.makespan = report.makespan.max(end);
+        }
+        while let Some(index) = arrivals.next_if(|&i| tasks[i].arrival <= now) {
+            let task = &tasks[index];
+            let position = queue.partition_point(|&q| {
+                tasks[q].priority > task.priority
+                    || (tasks[q].priority == task.priority && tasks[q].arrival <= task.arrival)
+            });
+            queue.insert(position, index);
         }
 
-        // Bound method access, create a partial with obj as first argument
-        let functools = py.import("functools")?;
-        let partial = functools.getattr("partial")?;
-        partial
-            .call1((self.wrapper.bind(py), obj))
-            .map(|r| r.unbind())
+        let mut position = 0;
+        while position < queue.len() && max_concurrent.is_none_or(|max| ledger.running < max) {
+            let index = queue[position];
+            if !ledger.fits(tasks[index].cost) {
+                position += 1;
+                continue;
+            }
+            queue.remove(position);
+            ledger.in_use = ledger.in_use.add(tasks[index].cost);
+            ledger.running += 1;
+            report.delays[index] = now - tasks[index].arrival;
+            running.push(SimFinish(now + tasks[index].duration, index));
+        }
+
+        report.peak_concurrency = report.peak_concurrency.max(ledger.running);
+        report.peak_queue_length = report.peak_queue_length.max(queue.len());
     }
+    report
 }
 
-// 1. Timer Decorator
-#[pyfunction]
-fn timer(py: Python, func: Py<PyAny>) -> PyResult<Py<PyAny>> {
-    let func_clone = func.clone_ref(py);
-    let wrapper = move |args: &Bound<'_, PyTuple>,
-                        kwargs: Option<&Bound<'_, PyDict>>|
-          -> PyResult<Py<PyAny>> {
-        let py = args.py();
-        let start = Instant::now();
-        let result = func_clone.bind(py).call(args, kwargs)?;
-        let duration = start.elapsed();
-        println!("Execution took: {:?}", duration);
-        Ok(result.unbind())
+/// Read a workload spec: a list of task dicts, or a dict describing evenly spaced identical tasks
+fn parse_workload(workload: &Bound<'_, PyAny>) -> PyResult<Vec<SimTask>> {
+    fn field<'py, T: pyo3::conversion::FromPyObjectOwned<'py>>(spec: &Bound<'py, PyDict>, key: &str, default: T) -> PyResult<T> {
+        match spec.get_item(key)? {
+            Some(value) => value.extract().map_err(Into::into),
+            None => Ok(default),
+        }
+    }
+    let task = |spec: &Bound<'_, PyDict>, arrival: f64| -> PyResult<SimTask> {
+        let duration: f64 = spec
+            .get_item("duration")?
+            .ok_or_else(|| pyo3::exceptions::PyValueError::new_err("workload task is missing 'duration'"))?
+            .extract()?;
+        let task = SimTask {
+            arrival: field(spec, "arrival", arrival)?,
+            duration,
+            priority: field(spec, "priority", 0)?,
+            cost: TaskCost { cpu: field(spec, "cpu", 0.0)?, memory_mb: field(spec, "memory_mb", 0.0)? },
+        };
+        if [task.arrival, task.duration, task.cost.cpu, task.cost.memory_mb]
+            .iter()
+            .any(|v| !v.is_finite() || *v < 0.0)
+        {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "workload times and costs must be non-negative numbers",
+            ));
+        }
+        Ok(task)
     };
-    let wrapped = PyCFunction::new_closure(py, None, None, wrapper)?;
 
-    // Wrap in MethodWrapper to support methods
-    let method_wrapper = Py::new(
-        py,
-        MethodWrapper {
-            func: func.clone_ref(py),
-            wrapper: wrapped.into(),
-        },
-    )?;
-    Ok(method_wrapper.into())
+    if let Ok(spec) = workload.cast::<PyDict>() {
+        let count: usize = field(spec, "count", 1)?;
+        let interval: f64 = field(spec, "interval", 0.0)?;
+        (0..count).map(|i| task(spec, i as f64 * interval)).collect()
+    } else {
+        workload.try_iter()?.map(|spec| task(spec?.cast::<PyDict>()?, 0.0)).collect()
+    }
 }
 
-// 3. Call Counter Decorator (as a PyClass)
-#[pyclass(name = "CallCounter")]
-struct CallCounter {
-    func: Py<PyAny>,
-    call_count: Arc<Mutex<i32>>,
+/// Project queue delays and concurrency peaks of a workload under the given limits
+///
+/// Replays the workload through the scheduler's admission rules (`max_concurrent`
+/// like set_max_concurrent_tasks(), `cpu`/`memory_mb` like configure_capacity())
+/// without executing any user code. A limit left as None is unlimited.
+#[pyfunction]
+#[pyo3(signature = (workload, max_concurrent=None, cpu=None, memory_mb=None))]
+fn simulate(
+    py: Python,
+    workload: &Bound<'_, PyAny>,
+    max_concurrent: Option<usize>,
+    cpu: Option<f64>,
+    memory_mb: Option<f64>,
+) -> PyResult<Py<PyDict>> {
+    if max_concurrent == Some(0) {
+        return Err(pyo3::exceptions::PyValueError::new_err("max_concurrent must be at least 1"));
+    }
+    let tasks = parse_workload(workload)?;
+    let ledger = CapacityLedger { cpu_capacity: cpu, memory_mb_capacity: memory_mb, ..Default::default() };
+    let report = py.detach(|| run_simulation(&tasks, max_concurrent, ledger));
+
+    let mut sorted = report.delays.clone();
+    sorted.sort_by(f64::total_cmp);
+    let percentile = |p: f64| {
+        let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+        sorted.get(rank.saturating_sub(1)).copied().unwrap_or(0.0)
+    };
+    let busy: f64 = tasks.iter().map(|t| t.duration).sum();
+
+    let delays = PyDict::new(py);
+    delays.set_item("mean", if sorted.is_empty() { 0.0 } else { sorted.iter().sum::<f64>() / sorted.len() as f64 })?;
+    delays.set_item("p50", percentile(50.0))?;
+    delays.set_item("p95", percentile(95.0))?;
+    delays.set_item("p99", percentile(99.0))?;
+    delays.set_item("max", sorted.last().copied().unwrap_or(0.0))?;
+
+    let result = PyDict::new(py);
+    result.set_item("tasks", tasks.len())?;
+    result.set_item("makespan", report.makespan)?;
+    result.set_item("queue_delay", delays)?;
+    result.set_item("peak_concurrency", report.peak_concurrency)?;
+    result.set_item("peak_queue_length", report.peak_queue_length)?;
+    result.set_item("mean_concurrency", if report.makespan > 0.0 { busy / report.makespan } else { 0.0 })?;
+    Ok(result.unbind())
+}
+
+// =============================================================================
+// PROGRESS TRACKING
+// =============================================================================
+
+/// Global progress tracking
+static TASK_PROGRESS_MAP: Lazy<Arc<DashMap<String, f64>>> =
+    Lazy::new(|| Arc::new(DashMap::new()));
+
+// Thread-local storage for current task ID
+thread_local! {
+    static CURRENT_TASK_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Set the current task ID for this thread (internal use)
+fn set_current_task_id(task_id: Option<String>) {
+    CURRENT_TASK_ID.with(|id| {
+        *id.borrow_mut() = task_id;
+    });
+}
+
+/// Get the current task ID for this thread
+#[pyfunction]
+fn get_current_task_id() -> PyResult<Option<String>> {
+    Ok(CURRENT_TASK_ID.with(|id| id.borrow().clone()))
+}
+
+/// Report progress from within a task (with explicit task_id)
+#[pyfunction]
+#[pyo3(signature = (progress, task_id=None))]
+fn report_progress(progress: f64, task_id: Option<String>) -> PyResult<()> {
+    // CRITICAL FIX: Add NaN/Inf check
+    if !progress.is_finite() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "progress must be a finite number (not NaN or Infinity)"
+        ));
+    }
+
+    if !(0.0..=1.0).contains(&progress) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "progress must be between 0.0 and 1.0"
+        ));
+    }
+
+    // Use provided task_id or get from thread-local storage
+    let actual_task_id = if let Some(tid) = task_id {
+        tid
+    } else {
+        CURRENT_TASK_ID.with(|id| {
+            id.borrow().clone().ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                    "No task_id found. report_progress must be called from within a @parallel decorated function, or you must provide task_id explicitly."
+                )
+            })
+        })?
+    };
+
+    TASK_PROGRESS_MAP.insert(actual_task_id.clone(), progress);
+
+    // CRITICAL FIX: Non-blocking callback with error handling
+    if let Some(callback) = TASK_PROGRESS_CALLBACKS.get(&actual_task_id) {
+        Python::attach(|py| {
+            // Execute callback with error handling
+            match callback.bind(py).call1((progress,)) {
+                Ok(_) => {},
+                Err(e) => {
+                    warn!("Progress callback failed for task {}: {}", actual_task_id, e);
+                }
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Global map for progress callbacks
+static TASK_PROGRESS_CALLBACKS: Lazy<Arc<DashMap<String, Py<PyAny>>>> =
+    Lazy::new(|| Arc::new(DashMap::new()));
+
+/// Register progress callback for a task (internal)
+fn register_progress_callback(task_id: String, callback: Py<PyAny>) {
+    TASK_PROGRESS_CALLBACKS.insert(task_id, callback);
+}
+
+/// Unregister progress callback (internal)
+fn unregister_progress_callback(task_id: &str) {
+    TASK_PROGRESS_CALLBACKS.remove(task_id);
+}
+
+/// Clear progress for a completed task (internal cleanup)
+fn clear_task_progress(task_id: &str) {
+    TASK_PROGRESS_MAP.remove(task_id);
+    unregister_progress_callback(task_id);
+    TASK_STREAMS.remove(task_id);
+}
+
+/// Completions of running thread tasks, by task ID, for emit()
+static TASK_STREAMS: Lazy<DashMap<String, Arc<TaskCompletion>>> = Lazy::new(DashMap::new);
+
+/// Buffer size for partial results of tasks started from now on; None for unbounded
+static STREAM_CAPACITY: Mutex<Option<usize>> = Mutex::new(None);
+
+/// Let emit() calls from the task's thread reach its handle (internal)
+fn open_result_stream(task_id: &str, completion: &Arc<TaskCompletion>) {
+    completion.state.lock().emit_capacity = *STREAM_CAPACITY.lock();
+    TASK_STREAMS.insert(task_id.to_string(), completion.clone());
+}
+
+/// Limit how many emitted partial results a task buffers before emit() blocks
+///
+/// Applies to tasks started afterwards; None removes the limit.
+#[pyfunction]
+#[pyo3(signature = (maxsize=None))]
+fn configure_stream_capacity(maxsize: Option<usize>) -> PyResult<()> {
+    if maxsize == Some(0) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "maxsize must be at least 1, or None for unbounded"
+        ));
+    }
+    *STREAM_CAPACITY.lock() = maxsize;
+    Ok(())
+}
+
+pyo3::create_exception!(
+    makeparallel,
+    StreamClosed,
+    TaskCancelled,
+    "Raised by emit() once the consumer has stopped reading the task's results."
+);
+
+/// Send a partial result from within a task to its `handle.iter_results()`
+///
+/// Blocks while the task's buffer is full; raises TimeoutError if it is still
+/// full after `timeout` seconds. If the consumer closed the stream, the task is
+/// cancelled and StreamClosed is raised so it can clean up.
+#[pyfunction]
+#[pyo3(signature = (value, timeout=None))]
+fn emit(py: Python, value: Py<PyAny>, timeout: Option<f64>) -> PyResult<()> {
+    validate_timeouts(timeout, None)?;
+    let task_id = CURRENT_TASK_ID.with(|id| id.borrow().clone());
+    let Some(completion) = task_id.and_then(|id| TASK_STREAMS.get(&id).map(|c| c.clone())) else {
+        return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+            "emit() must be called from within a @parallel, @parallel_with_deps or @parallel_priority task"
+        ));
+    };
+    let queued = !completion.is_stream_closed() && completion.emit(py, value, timeout)?;
+    if completion.is_stream_closed() {
+        if let Some(flag) = current_cancel_flag() {
+            flag.store(true, Ordering::Release);
+        }
+        return Err(StreamClosed::new_err("the consumer closed the result stream"));
+    }
+    if !queued {
+        return Err(pyo3::exceptions::PyTimeoutError::new_err("result stream still full after timeout"));
+    }
+    Ok(())
+}
+
+// =============================================================================
+// COOPERATIVE CANCELLATION
+// =============================================================================
+
+pyo3::create_exception!(
+    makeparallel,
+    TaskCancelled,
+    pyo3::exceptions::PyException,
+    "Raised by check_cancelled() once the running task has been cancelled."
+);
+
+/// A task's cancellation flag, for task code that wants to stop early
+#[pyclass]
+struct CancellationToken {
+    flag: Arc<AtomicBool>,
+}
+
+#[pymethods]
+impl CancellationToken {
+    /// Standalone token, cancelled with cancel()
+    #[new]
+    fn new() -> Self {
+        CancellationToken { flag: Arc::new(AtomicBool::new(false)) }
+    }
+
+    #[getter]
+    fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::Acquire)
+    }
+
+    fn cancel(&self) {
+        self.flag.store(true, Ordering::Release);
+    }
+
+    /// Raise TaskCancelled if the token has been cancelled
+    fn raise_if_cancelled(&self) -> PyResult<()> {
+        if self.is_cancelled() {
+            return Err(TaskCancelled::new_err("Task was cancelled or timed out"));
+        }
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("CancellationToken(cancelled={})", self.is_cancelled())
+    }
+}
+
+thread_local! {
+    // Token of the task last looked up on this thread, to keep polling cheap
+    static CACHED_CANCEL_TOKEN: RefCell<Option<(String, Arc<AtomicBool>)>> = const { RefCell::new(None) };
+}
+
+/// Cancellation flag of the task running on this thread
+fn current_cancel_flag() -> Option<Arc<AtomicBool>> {
+    let task_id = CURRENT_TASK_ID.with(|id| id.borrow().clone())?;
+    CACHED_CANCEL_TOKEN.with(|cached| {
+        let mut cached = cached.borrow_mut();
+        if let Some((ref id, ref flag)) = *cached {
+            if *id == task_id {
+                return Some(flag.clone());
+            }
+        }
+        let flag = ACTIVE_TASKS
+            .lock()
+            .iter()
+            .find(|task| task.task_id == task_id)
+            .map(|task| task.cancel_token.clone())?;
+        *cached = Some((task_id, flag.clone()));
+        Some(flag)
+    })
+}
+
+/// Raise TaskCancelled if the calling task has been cancelled (or timed out)
+///
+/// Call it periodically from long-running loops; outside a task it does nothing.
+#[pyfunction]
+fn check_cancelled() -> PyResult<()> {
+    match current_cancel_flag() {
+        Some(flag) => CancellationToken { flag }.raise_if_cancelled(),
+        None => Ok(()),
+    }
+}
+
+/// Error type recorded for a task that raised `e`; TaskCancelled counts as a cancellation
+fn task_error_type(py: Python, e: &PyErr) -> String {
+    if e.is_instance_of::<TaskCancelled>(py) {
+        return "CancellationError".to_string();
+    }
+    e.get_type(py)
+        .name()
+        .map(|n| n.to_string())
+        .unwrap_or_else(|_| "UnknownError".to_string())
+}
+
+/// Cancellation token of the calling task, or None outside a task
+#[pyfunction]
+fn current_cancellation_token() -> Option<CancellationToken> {
+    current_cancel_flag().map(|flag| CancellationToken { flag })
+}
+
+// =============================================================================
+// TASK PAUSING
+// =============================================================================
+
+/// Tasks asked to pause, by ID; true once the task is blocked in checkpoint()
+static PAUSED_TASKS: Lazy<Mutex<HashMap<String, bool>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Signalled when paused tasks are resumed
+static TASKS_RESUMED: Condvar = Condvar::new();
+
+/// Ask an unfinished task to pause at its next checkpoint(); false if it is not active
+fn pause_task(task_id: &str) -> bool {
+    // Insert while holding ACTIVE_TASKS, so unregister_task() cannot finish in between
+    // and leave the entry behind for a task that is gone
+    let active_tasks = ACTIVE_TASKS.lock();
+    let active = active_tasks.iter().any(|task| task.task_id == task_id && !*task.is_complete.lock());
+    if active {
+        PAUSED_TASKS.lock().entry(task_id.to_string()).or_insert(false);
+    }
+    active
+}
+
+/// Let a paused task continue; false if it was not paused
+fn resume_task(task_id: &str) -> bool {
+    let resumed = PAUSED_TASKS.lock().remove(task_id).is_some();
+    if resumed {
+        TASKS_RESUMED.notify_all();
+    }
+    resumed
+}
+
+fn is_blocked_in_checkpoint(task_id: &str) -> bool {
+    PAUSED_TASKS.lock().get(task_id).copied().unwrap_or(false)
+}
+
+/// Point in task code where the task may be paused or stopped
+///
+/// While the task's handle is paused, blocks with the GIL released until it is
+/// resumed. Raises TaskCancelled if the task is cancelled or times out, paused
+/// or not, like check_cancelled(). Outside a task it does nothing.
+#[pyfunction]
+fn checkpoint(py: Python) -> PyResult<()> {
+    check_cancelled()?;
+    let Some(task_id) = CURRENT_TASK_ID.with(|id| id.borrow().clone()) else {
+        return Ok(());
+    };
+    let cancel_flag = current_cancel_flag();
+    let paused_for = py.detach(|| {
+        let mut paused = PAUSED_TASKS.lock();
+        let started = Instant::now();
+        while let Some(blocked) = paused.get_mut(&task_id) {
+            // Cancellation does not signal the condvar, so poll for it
+            if cancel_flag.as_ref().is_some_and(|flag| flag.load(Ordering::Acquire)) {
+                paused.remove(&task_id);
+                break;
+            }
+            *blocked = true;
+            TASKS_RESUMED.wait_for(&mut paused, Duration::from_millis(50));
+        }
+        started.elapsed()
+    });
+    if paused_for >= Duration::from_millis(1) {
+        debug!("Task {} was paused for {:.3}s", task_id, paused_for.as_secs_f64());
+    }
+    check_cancelled()
+}
+
+// =============================================================================
+// TASK WORKSPACES
+// =============================================================================
+
+/// Settings for `task_workspace()` directories
+#[derive(Default)]
+struct WorkspaceConfig {
+    keep_failed: bool,
+    base_dir: Option<PathBuf>,
+}
+
+static WORKSPACE_CONFIG: Lazy<Mutex<WorkspaceConfig>> = Lazy::new(|| Mutex::new(WorkspaceConfig::default()));
+
+/// Workspace directory of each task that asked for one, and whether the task failed
+static TASK_WORKSPACES: Lazy<DashMap<String, (PathBuf, bool)>> = Lazy::new(DashMap::new);
+
+/// Temporary directory private to the calling task, created on first use
+///
+/// The directory is removed when the task finishes; see configure_task_workspaces()
+/// to keep the workspaces of failed tasks for inspection.
+#[pyfunction]
+fn task_workspace() -> PyResult<PathBuf> {
+    let task_id = CURRENT_TASK_ID.with(|id| id.borrow().clone()).ok_or_else(|| {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+            "task_workspace() must be called from within a @parallel decorated function"
+        )
+    })?;
+    if let Some(workspace) = TASK_WORKSPACES.get(&task_id) {
+        return Ok(workspace.0.clone());
+    }
+
+    let base_dir = WORKSPACE_CONFIG.lock().base_dir.clone().unwrap_or_else(std::env::temp_dir);
+    let prefix = format!("makeparallel-{}-{}", std::process::id(), task_id);
+    let path = new_private_dir(&base_dir, &prefix).map_err(|e| {
+        pyo3::exceptions::PyOSError::new_err(format!("Failed to create workspace in {}: {}", base_dir.display(), e))
+    })?;
+    TASK_WORKSPACES.insert(task_id, (path.clone(), false));
+    Ok(path)
+}
+
+/// Create `path` as a directory only its owner can open, failing if anything is already there
+fn create_private_dir(path: &std::path::Path) -> std::io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(path)
+}
+
+/// A new private directory in `base`, named `prefix` plus a random suffix
+///
+/// A name that is already taken is never reused, since whoever created it could read
+/// what is written there; another suffix is tried instead.
+fn new_private_dir(base: &std::path::Path, prefix: &str) -> std::io::Result<PathBuf> {
+    const ATTEMPTS: usize = 16;
+    let mut last_error = None;
+    for _ in 0..ATTEMPTS {
+        let path = base.join(format!("{}-{:08x}", prefix, rand::random::<u32>()));
+        match create_private_dir(&path) {
+            Ok(()) => return Ok(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => last_error = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_error.expect("at least one attempt was made"))
+}
+
+/// Configure task workspaces: keep those of failed tasks, and where to create them
+#[pyfunction]
+#[pyo3(signature = (keep_failed=false, base_dir=None))]
+fn configure_task_workspaces(keep_failed: bool, base_dir: Option<PathBuf>) -> PyResult<()> {
+    if let Some(ref dir) = base_dir {
+        if !dir.is_dir() {
+            return Err(pyo3::exceptions::PyFileNotFoundError::new_err(format!(
+                "base_dir is not an existing directory: {}",
+                dir.display()
+            )));
+        }
+    }
+    *WORKSPACE_CONFIG.lock() = WorkspaceConfig { keep_failed, base_dir };
+    Ok(())
+}
+
+/// Remember that a task failed so its workspace can be kept (internal)
+fn mark_workspace_failed(task_id: &str) {
+    if let Some(mut workspace) = TASK_WORKSPACES.get_mut(task_id) {
+        workspace.1 = true;
+    }
+}
+
+/// Remove a finished task's workspace, unless it failed and failures are kept (internal cleanup)
+fn release_task_workspace(task_id: &str) {
+    let Some((_, (path, failed))) = TASK_WORKSPACES.remove(task_id) else {
+        return;
+    };
+    if failed && WORKSPACE_CONFIG.lock().keep_failed {
+        warn!("Keeping workspace of failed task {}: {}", task_id, path.display());
+        return;
+    }
+    if let Err(e) = std::fs::remove_dir_all(&path) {
+        warn!("Failed to remove workspace {} of task {}: {}", path.display(), task_id, e);
+    }
+}
+
+// =============================================================================
+// TASK CHECKPOINTS
+// =============================================================================
+
+/// Latest pickled state saved under each key, when checkpoints are not written to disk
+static TASK_CHECKPOINTS: Lazy<DashMap<String, Vec<u8>>> = Lazy::new(DashMap::new);
+
+/// Directory checkpoints are written to instead of memory, set by configure_task_checkpoints()
+static TASK_CHECKPOINT_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Keys each unfinished task saved checkpoints under, and whether to keep them when it ends
+static TASK_CHECKPOINT_KEYS: Lazy<DashMap<String, (HashSet<String>, bool)>> = Lazy::new(DashMap::new);
+
+/// Write task checkpoints to files in `directory`, so they survive the process
+///
+/// Each key is stored in its own file, written atomically and sealed with the key from
+/// configure_persistence() if one is set. Pass None to keep checkpoints in memory again.
+#[pyfunction]
+#[pyo3(signature = (directory=None))]
+fn configure_task_checkpoints(directory: Option<PathBuf>) -> PyResult<()> {
+    if let Some(ref dir) = directory {
+        if !dir.is_dir() {
+            return Err(pyo3::exceptions::PyFileNotFoundError::new_err(format!(
+                "directory is not an existing directory: {}",
+                dir.display()
+            )));
+        }
+    }
+    *TASK_CHECKPOINT_DIR.lock() = directory;
+    Ok(())
+}
+
+/// `key`, or the ID of the calling task while checkpoints are kept in memory
+///
+/// Task IDs restart with each process, so a checkpoint file named after one would be
+/// picked up by an unrelated task of a later process; files need an explicit key.
+fn checkpoint_key(key: Option<String>, caller: &str) -> PyResult<String> {
+    if let Some(key) = key {
+        return Ok(key);
+    }
+    if TASK_CHECKPOINT_DIR.lock().is_some() {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "{}() needs a key while configure_task_checkpoints() writes checkpoints to a directory",
+            caller
+        )));
+    }
+    CURRENT_TASK_ID
+        .with(|id| id.borrow().clone())
+        .ok_or_else(|| pyo3::exceptions::PyRuntimeError::new_err(format!("{}() outside a task needs a key", caller)))
+}
+
+/// File holding the checkpoint saved under `key`
+fn checkpoint_path(dir: &std::path::Path, key: &str) -> PathBuf {
+    // Keys may hold any text, so escape everything but a safe set of characters
+    let name: String = key
+        .bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+    dir.join(format!("{}.ckpt", name))
+}
+
+/// Save `state` as the latest checkpoint of the calling task, or under `key`
+///
+/// The state is pickled, so later changes to the object are not saved. A task resumes
+/// by calling load_checkpoint() with the same key. In memory the key defaults to the
+/// task ID, which is reused by later processes and lets a retry find its own state but
+/// not a resubmitted task; with configure_task_checkpoints() a key is required.
+/// Checkpoints a task saved are deleted when it succeeds, and kept when it fails, is
+/// cancelled or times out.
+#[pyfunction]
+#[pyo3(signature = (state, key=None))]
+fn save_checkpoint(py: Python, state: &Bound<'_, PyAny>, key: Option<String>) -> PyResult<()> {
+    let key = checkpoint_key(key, "save_checkpoint")?;
+    let pickled: Vec<u8> = py.import("pickle")?.call_method1("dumps", (state,))?.extract()?;
+
+    let dir = TASK_CHECKPOINT_DIR.lock().clone();
+    match dir {
+        Some(dir) => {
+            let data = match persistence_key() {
+                Some(sealing_key) => {
+                    let sealed = seal(&sealing_key, key.as_bytes(), &pickled)
+                        .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("Failed to encrypt the checkpoint"))?;
+                    [SEALED_FILE_MAGIC.as_slice(), &sealed].concat()
+                }
+                None => pickled,
+            };
+            let path = checkpoint_path(&dir, &key);
+            // Write then rename, so a crash mid-write leaves the previous checkpoint intact
+            let partial = path.with_extension("ckpt.partial");
+            py.detach(|| std::fs::write(&partial, data).and_then(|()| std::fs::rename(&partial, &path)))
+                .map_err(|e| {
+                    pyo3::exceptions::PyOSError::new_err(format!("Failed to write {}: {}", path.display(), e))
+                })?;
+        }
+        None => {
+            TASK_CHECKPOINTS.insert(key.clone(), pickled);
+        }
+    }
+
+    if let Some(task_id) = CURRENT_TASK_ID.with(|id| id.borrow().clone()) {
+        TASK_CHECKPOINT_KEYS.entry(task_id).or_default().0.insert(key);
+    }
+    Ok(())
+}
+
+/// The last state saved with save_checkpoint() under `key`
+///
+/// `key` defaults to the calling task's ID only while checkpoints are kept in memory.
+/// Returns `default` when nothing has been saved under the key.
+#[pyfunction]
+#[pyo3(signature = (key=None, default=None))]
+fn load_checkpoint(py: Python, key: Option<String>, default: Option<Py<PyAny>>) -> PyResult<Py<PyAny>> {
+    let key = checkpoint_key(key, "load_checkpoint")?;
+    let dir = TASK_CHECKPOINT_DIR.lock().clone();
+    let pickled = match dir {
+        Some(dir) => {
+            let path = checkpoint_path(&dir, &key);
+            match std::fs::read(&path) {
+                Ok(data) => match (data.strip_prefix(SEALED_FILE_MAGIC), persistence_key()) {
+                    (Some(sealed), Some(sealing_key)) => {
+                        Some(unseal(&sealing_key, key.as_bytes(), sealed).ok_or_else(|| wrong_key_error(&path))?)
+                    }
+                    (Some(_), None) => return Err(missing_key_error(&path)),
+                    (None, Some(_)) => return Err(unencrypted_file_error(&path)),
+                    (None, None) => Some(data),
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    return Err(pyo3::exceptions::PyOSError::new_err(format!(
+                        "Failed to read {}: {}",
+                        path.display(),
+                        e
+                    )))
+                }
+            }
+        }
+        None => TASK_CHECKPOINTS.get(&key).map(|pickled| pickled.clone()),
+    };
+    match pickled {
+        Some(pickled) => Ok(py.import("pickle")?.call_method1("loads", (PyBytes::new(py, &pickled),))?.unbind()),
+        None => Ok(default.unwrap_or_else(|| py.None())),
+    }
+}
+
+/// Delete the checkpoint saved under `key`, defaulting as for load_checkpoint()
+///
+/// Returns False if there was none.
+#[pyfunction]
+#[pyo3(signature = (key=None))]
+fn clear_checkpoint(key: Option<String>) -> PyResult<bool> {
+    let key = checkpoint_key(key, "clear_checkpoint")?;
+    Ok(remove_checkpoint(&key))
+}
+
+fn remove_checkpoint(key: &str) -> bool {
+    let in_memory = TASK_CHECKPOINTS.remove(key).is_some();
+    let on_disk = TASK_CHECKPOINT_DIR.lock().as_ref().is_some_and(|dir| {
+        let path = checkpoint_path(dir, key);
+        match std::fs::remove_file(&path) {
+            Ok(()) => true,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+            Err(e) => {
+                warn!("Failed to remove checkpoint {}: {}", path.display(), e);
+                false
+            }
+        }
+    });
+    in_memory || on_disk
+}
+
+/// Keep a task's checkpoints after it ends, for a resubmission to resume from (internal)
+fn keep_task_checkpoints(task_id: &str) {
+    if let Some(mut saved) = TASK_CHECKPOINT_KEYS.get_mut(task_id) {
+        saved.1 = true;
+    }
+}
+
+/// Delete the checkpoints of a task that finished, unless they are to be kept (internal cleanup)
+fn release_task_checkpoints(task_id: &str, keep: bool) {
+    let Some((_, (keys, kept))) = TASK_CHECKPOINT_KEYS.remove(task_id) else {
+        return;
+    };
+    if keep || kept {
+        debug!("Keeping {} checkpoint(s) of task {}", keys.len(), task_id);
+        return;
+    }
+    for key in keys {
+        remove_checkpoint(&key);
+    }
+}
+
+// =============================================================================
+// RESOURCE INJECTION
+// =============================================================================
+
+/// Lifetime of a resource registered with `provide()`
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ResourceScope {
+    /// Created for each task and closed when it finishes
+    Task,
+    /// One per worker thread, closed when the thread exits or at shutdown
+    Worker,
+    /// One shared instance, closed at shutdown
+    Global,
+}
+
+impl ResourceScope {
+    fn parse(scope: &str) -> PyResult<Self> {
+        match scope {
+            "task" => Ok(ResourceScope::Task),
+            "worker" => Ok(ResourceScope::Worker),
+            "global" => Ok(ResourceScope::Global),
+            other => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "scope must be 'task', 'worker' or 'global', got '{}'",
+                other
+            ))),
+        }
+    }
+}
+
+/// Factory registered with `provide()`
+struct Provider {
+    factory: Py<PyAny>,
+    scope: ResourceScope,
+}
+
+static PROVIDERS: Lazy<Mutex<HashMap<String, Provider>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+static GLOBAL_RESOURCES: Lazy<DashMap<String, Py<PyAny>>> = Lazy::new(DashMap::new);
+
+static WORKER_RESOURCES: Lazy<DashMap<(thread::ThreadId, String), Py<PyAny>>> = Lazy::new(DashMap::new);
+
+/// Closes the worker-scoped resources of its thread when the thread exits
+struct WorkerResourceGuard(thread::ThreadId);
+
+impl Drop for WorkerResourceGuard {
+    fn drop(&mut self) {
+        let thread = self.0;
+        let resources = take_worker_resources(|owner, _| owner == thread);
+        if resources.is_empty() {
+            return;
+        }
+        if Python::try_attach(|py| close_resources(py, &resources)).is_none() {
+            // The interpreter is gone; there is nothing left to close them with
+            std::mem::forget(resources);
+        }
+    }
+}
+
+thread_local! {
+    static WORKER_RESOURCE_GUARD: WorkerResourceGuard = WorkerResourceGuard(thread::current().id());
+}
+
+/// Remove the worker-scoped resources matching `select(thread, name)` from the registry
+fn take_worker_resources(select: impl Fn(thread::ThreadId, &str) -> bool) -> Vec<(String, Py<PyAny>)> {
+    let keys: Vec<_> = WORKER_RESOURCES
+        .iter()
+        .map(|e| e.key().clone())
+        .filter(|(thread, name)| select(*thread, name))
+        .collect();
+    keys.into_iter()
+        .filter_map(|key| WORKER_RESOURCES.remove(&key))
+        .map(|((_, name), resource)| (name, resource))
+        .collect()
+}
+
+/// Call `close()` on each resource that has one
+fn close_resources(py: Python, resources: &[(String, Py<PyAny>)]) {
+    for (name, resource) in resources {
+        let resource = resource.bind(py);
+        if resource.hasattr("close").unwrap_or(false) {
+            if let Err(e) = resource.call_method0("close") {
+                warn!("Closing resource '{}' failed: {}", name, e);
+            }
+        }
+    }
+}
+
+/// Close every global and worker-scoped resource; they are recreated on next use
+fn close_all_resources(py: Python) {
+    let mut resources = take_worker_resources(|_, _| true);
+    let names: Vec<String> = GLOBAL_RESOURCES.iter().map(|e| e.key().clone()).collect();
+    resources.extend(names.into_iter().filter_map(|name| GLOBAL_RESOURCES.remove(&name)));
+    close_resources(py, &resources);
+}
+
+/// Get the named resource for the calling task, creating it as its scope requires
+fn acquire_resource<'py>(py: Python<'py>, name: &str) -> PyResult<(Bound<'py, PyAny>, ResourceScope)> {
+    let (factory, scope) = PROVIDERS
+        .lock()
+        .get(name)
+        .map(|provider| (provider.factory.clone_ref(py), provider.scope))
+        .ok_or_else(|| {
+            pyo3::exceptions::PyLookupError::new_err(format!("no provider registered for resource '{}'", name))
+        })?;
+
+    let resource = match scope {
+        ResourceScope::Task => factory.bind(py).call0()?,
+        ResourceScope::Worker => {
+            let key = (thread::current().id(), name.to_string());
+            if let Some(resource) = WORKER_RESOURCES.get(&key) {
+                return Ok((resource.bind(py).clone(), scope));
+            }
+            let resource = factory.bind(py).call0()?;
+            WORKER_RESOURCE_GUARD.with(|_| {});
+            WORKER_RESOURCES.insert(key, resource.clone().unbind());
+            resource
+        }
+        ResourceScope::Global => {
+            if let Some(resource) = GLOBAL_RESOURCES.get(name) {
+                return Ok((resource.bind(py).clone(), scope));
+            }
+            // The factory may release the GIL, so another task can get there first
+            let created = factory.bind(py).call0()?;
+            let resource = GLOBAL_RESOURCES
+                .entry(name.to_string())
+                .or_insert_with(|| created.clone().unbind())
+                .bind(py)
+                .clone();
+            if !resource.is(&created) {
+                close_resources(py, &[(name.to_string(), created.unbind())]);
+            }
+            resource
+        }
+    };
+    Ok((resource, scope))
+}
+
+/// Call `call` with `names` added to its keyword arguments, closing task-scoped resources afterwards
+///
+/// Keyword arguments passed explicitly take precedence over injected resources.
+fn call_with_resources<'py>(
+    py: Python<'py>,
+    names: &[String],
+    kwargs: Option<&Bound<'py, PyDict>>,
+    call: impl FnOnce(Option<&Bound<'py, PyDict>>) -> PyResult<Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    if names.is_empty() {
+        return call(kwargs);
+    }
+    let kwargs = kwargs.map_or_else(|| Ok(PyDict::new(py)), |k| k.copy())?;
+    let mut task_scoped = Vec::new();
+    let result = (|| {
+        for name in names {
+            if kwargs.contains(name)? {
+                continue;
+            }
+            let (resource, scope) = acquire_resource(py, name)?;
+            if scope == ResourceScope::Task {
+                task_scoped.push((name.clone(), resource.clone().unbind()));
+            }
+            kwargs.set_item(name, resource)?;
+        }
+        call(Some(&kwargs))
+    })();
+    close_resources(py, &task_scoped);
+    result
+}
+
+/// Register `factory` as the provider of the resource `name`
+///
+/// Tasks declaring `@parallel(inject=[name])` receive the resource as the keyword
+/// argument `name`. With scope "task" each call gets a fresh instance, closed when it
+/// finishes; "worker" shares one per worker thread; "global" shares one everywhere.
+/// Shared instances are closed (via their `close()` method, if any) once shutdown()
+/// has drained the running tasks. Registering a name again replaces its provider.
+#[pyfunction]
+#[pyo3(signature = (name, factory, scope="task"))]
+fn provide(py: Python, name: String, factory: Py<PyAny>, scope: &str) -> PyResult<()> {
+    let scope = ResourceScope::parse(scope)?;
+    if !factory.bind(py).is_callable() {
+        return Err(pyo3::exceptions::PyTypeError::new_err("factory must be callable"));
+    }
+    PROVIDERS.lock().insert(name.clone(), Provider { factory, scope });
+
+    // Instances made by the previous provider are not handed out any more
+    let mut stale = take_worker_resources(|_, resource| resource == name);
+    stale.extend(GLOBAL_RESOURCES.remove(&name));
+    close_resources(py, &stale);
+    Ok(())
+}
+
+// =============================================================================
+// TIMEOUT ENFORCEMENT
+// =============================================================================
+
+/// Timeout bounds for the task running on the current thread.
+///
+/// `attempt` bounds a single call of the user function (`timeout=`), while
+/// `deadline` bounds the whole task including retries and backoff sleeps
+/// (`total_timeout=`).
+#[derive(Clone, Copy, Default)]
+struct TaskTimeouts {
+    attempt: Option<Duration>,
+    deadline: Option<Instant>,
+}
+
+impl TaskTimeouts {
+    fn new(timeout: Option<f64>, total_timeout: Option<f64>, start: Instant) -> Self {
+        TaskTimeouts {
+            attempt: timeout.map(Duration::from_secs_f64),
+            deadline: total_timeout.map(|secs| start + Duration::from_secs_f64(secs)),
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT_TASK_TIMEOUTS: Cell<TaskTimeouts> = const {
+        Cell::new(TaskTimeouts { attempt: None, deadline: None })
+    };
+    // Set once a retry decorator has taken over per-attempt enforcement
+    static ATTEMPTS_MANAGED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Validate the `timeout`/`total_timeout` pair passed at submission
+fn validate_timeouts(timeout: Option<f64>, total_timeout: Option<f64>) -> PyResult<()> {
+    for (name, value) in [("timeout", timeout), ("total_timeout", total_timeout)] {
+        if let Some(secs) = value {
+            if !secs.is_finite() || secs <= 0.0 {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "{} must be a positive number of seconds",
+                    name
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Keyword arguments of a call to a decorated function, split into task options and its own
+///
+/// An option can always be passed with the `_mp_` prefix (`_mp_timeout=5`). Its plain name
+/// (`timeout=5`) works too, unless the decorator was given `namespaced=True`: then plain
+/// names are left for the function, so it can take parameters such as `timeout` itself.
+struct CallOptions<'a, 'py> {
+    kwargs: Option<&'a Bound<'py, PyDict>>,
+    namespaced: bool,
+}
+
+impl<'a, 'py> CallOptions<'a, 'py> {
+    fn new(kwargs: Option<&'a Bound<'py, PyDict>>, namespaced: bool) -> Self {
+        CallOptions { kwargs, namespaced }
+    }
+
+    /// Remove option `name` from the keyword arguments; None when absent or None
+    fn take<T: pyo3::conversion::FromPyObjectOwned<'py>>(&self, name: &str) -> PyResult<Option<T>> {
+        let Some(kwargs) = self.kwargs else { return Ok(None) };
+        let prefixed = format!("_mp_{}", name);
+        let mut value = kwargs.get_item(&prefixed)?;
+        if value.is_some() {
+            kwargs.del_item(&prefixed)?;
+        }
+        if !self.namespaced {
+            if let Some(plain) = kwargs.get_item(name)? {
+                if value.is_some() {
+                    return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                        "got both '{}' and '{}'",
+                        name, prefixed
+                    )));
+                }
+                kwargs.del_item(name)?;
+                value = Some(plain);
+            }
+        }
+        match value {
+            Some(value) if !value.is_none() => {
+                let value: PyResult<T> = value.extract().map_err(Into::into);
+                value.map(Some).map_err(|e| {
+                    pyo3::exceptions::PyTypeError::new_err(format!("argument '{}': {}", name, e))
+                })
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Install timeout bounds for the task about to run on this thread (internal use)
+fn set_current_task_timeouts(timeouts: TaskTimeouts) {
+    CURRENT_TASK_TIMEOUTS.with(|t| t.set(timeouts));
+    ATTEMPTS_MANAGED.with(|m| m.set(false));
+}
+
+/// Whether a single attempt started at `attempt_start` overran `timeout=`.
+/// Called by the retry decorators, which then own per-attempt enforcement.
+fn attempt_timed_out(attempt_start: Instant) -> bool {
+    ATTEMPTS_MANAGED.with(|m| m.set(true));
+    CURRENT_TASK_TIMEOUTS.with(|t| {
+        t.get()
+            .attempt
+            .is_some_and(|limit| attempt_start.elapsed() > limit)
+    })
+}
+
+/// Whether sleeping for `delay` before the next attempt stays within `total_timeout=`
+fn backoff_fits_deadline(delay: Duration) -> bool {
+    CURRENT_TASK_TIMEOUTS.with(|t| {
+        t.get()
+            .deadline
+            .is_none_or(|deadline| Instant::now() + delay < deadline)
+    })
+}
+
+/// Call `func` as attempt number `attempt` of a retried task, failing it if it overran `timeout=`
+fn run_attempt<'py>(
+    func: &Bound<'py, PyAny>,
+    args: &Bound<'py, PyTuple>,
+    kwargs: Option<&Bound<'py, PyDict>>,
+    attempt: usize,
+) -> PyResult<Bound<'py, PyAny>> {
+    let attempt_start = Instant::now();
+    let timer = AttemptTimer::start();
+    let outcome = func.call(args, kwargs);
+    drop(timer);
+    if attempt_timed_out(attempt_start) {
+        return Err(attempt_timeout_error(attempt));
+    }
+    outcome
+}
+
+/// Error raised by a retry decorator when an attempt overran `timeout=`
+fn attempt_timeout_error(attempt: usize) -> PyErr {
+    let limit = CURRENT_TASK_TIMEOUTS.with(|t| t.get().attempt).unwrap_or_default();
+    PyErr::new::<pyo3::exceptions::PyTimeoutError, _>(format!(
+        "Attempt {} exceeded per-attempt timeout of {}s",
+        attempt,
+        limit.as_secs_f64()
+    ))
+}
+
+/// Error raised by a retry decorator when backing off would overrun `total_timeout=`
+fn retry_budget_exhausted(last_err: PyErr) -> PyErr {
+    PyErr::new::<pyo3::exceptions::PyTimeoutError, _>(format!(
+        "Retry budget exhausted: next attempt would exceed total_timeout (last error: {})",
+        last_err
+    ))
+}
+
+/// Check a finished task against its timeouts, returning the limit it overran.
+///
+/// The total deadline always applies. The per-attempt limit is only checked
+/// here when no retry decorator enforced it attempt by attempt.
+fn exceeded_task_timeout(
+    timeout: Option<f64>,
+    total_timeout: Option<f64>,
+    start: Instant,
+) -> Option<f64> {
+    let elapsed = start.elapsed().as_secs_f64();
+    if let Some(total) = total_timeout {
+        if elapsed > total {
+            return Some(total);
+        }
+    }
+    let managed = ATTEMPTS_MANAGED.with(|m| m.get());
+    match timeout {
+        Some(limit) if !managed && elapsed > limit => Some(limit),
+        _ => None,
+    }
+}
+
+pyo3::create_exception!(
+    makeparallel,
+    TaskExecutionFailed,
+    pyo3::exceptions::PyException,
+    "A task's result failed its `result_schema` validation."
+);
+
+pyo3::create_exception!(
+    makeparallel,
+    TaskTimeout,
+    pyo3::exceptions::PyTimeoutError,
+    "Raised inside a task's thread when the task runs past its timeout."
+);
+
+/// Python thread idents of threads currently running a task's function, by task id
+static TASK_THREADS: Lazy<Mutex<HashMap<String, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Makes the current thread interruptible by its task's timeout until dropped
+///
+/// Drop it as soon as the task's function returns, before running any other Python code.
+struct TaskThreadGuard {
+    task_id: String,
+}
+
+impl TaskThreadGuard {
+    fn register(py: Python, task_id: &str) -> Self {
+        match py
+            .import("threading")
+            .and_then(|threading| threading.call_method0("get_ident"))
+            .and_then(|ident| ident.extract::<u64>())
+        {
+            Ok(ident) => {
+                TASK_THREADS.lock().insert(task_id.to_string(), ident);
+            }
+            Err(e) => warn!("Task {} cannot be interrupted on timeout: {}", task_id, e),
+        }
+        mark_task_running(task_id);
+        TaskThreadGuard { task_id: task_id.to_string() }
+    }
+}
+
+impl Drop for TaskThreadGuard {
+    fn drop(&mut self) {
+        if let Some(ident) = TASK_THREADS.lock().remove(&self.task_id) {
+            // A timeout that fired just as the function returned must not surface in later code
+            Python::attach(|_| set_async_exc(ident, std::ptr::null_mut()));
+        }
+    }
+}
+
+/// Schedule `exc` to be raised in the Python thread `ident`; a null `exc` clears a pending one.
+/// Must be called while attached to the interpreter.
+fn set_async_exc(ident: u64, exc: *mut pyo3::ffi::PyObject) {
+    #[cfg(not(PyPy))]
+    unsafe {
+        pyo3::ffi::PyThreadState_SetAsyncExc(ident as std::os::raw::c_long, exc);
+    }
+    #[cfg(PyPy)]
+    let _ = (ident, exc);
+}
+
+/// Raise TaskTimeout in the thread running `task_id`, if it is still inside the task's function
+///
+/// The exception is delivered at the thread's next Python bytecode, so a blocking C call
+/// (such as `time.sleep`) finishes first.
+fn interrupt_task_thread(task_id: &str) {
+    Python::attach(|py| {
+        // Held while raising so the task thread cannot unregister (and clear) in between
+        let threads = TASK_THREADS.lock();
+        if let Some(&ident) = threads.get(task_id) {
+            set_async_exc(ident, py.get_type::<TaskTimeout>().as_ptr());
+        }
+    });
+}
+
+/// Interrupts one attempt of a retried task once it overruns `timeout=`
+///
+/// Each attempt gets its own limit, so while one runs the task-wide timer only
+/// enforces `total_timeout=`. Dropping the timer ends the attempt.
+struct AttemptTimer {
+    task_id: String,
+    // Disconnecting wakes the timer thread
+    _stop: Sender<()>,
+    armed: Arc<AtomicBool>,
+    fired: Arc<AtomicBool>,
+}
+
+impl AttemptTimer {
+    /// Time the attempt about to run on this thread, if its task has a `timeout=`
+    fn start() -> Option<Self> {
+        if sync_fallback() {
+            return None;
+        }
+        let timeouts = CURRENT_TASK_TIMEOUTS.with(|t| t.get());
+        let limit = timeouts.attempt?;
+        let task_id = CURRENT_TASK_ID.with(|id| id.borrow().clone())?;
+        if timeouts.deadline.is_none() {
+            // Armed at submission with timeout=, which would otherwise span every attempt
+            stop_timeout_timer(&task_id);
+        }
+
+        let (stop, stop_receiver) = channel::<()>();
+        let armed = Arc::new(AtomicBool::new(true));
+        let fired = Arc::new(AtomicBool::new(false));
+        let (timer_armed, timer_fired, timer_task_id) = (armed.clone(), fired.clone(), task_id.clone());
+        thread::spawn(move || {
+            if let Err(std::sync::mpsc::RecvTimeoutError::Timeout) = stop_receiver.recv_timeout(limit) {
+                Python::attach(|py| {
+                    // Checked under the lock so an attempt that just ended is left alone
+                    let threads = TASK_THREADS.lock();
+                    if let (true, Some(&ident)) = (timer_armed.load(Ordering::Acquire), threads.get(&timer_task_id)) {
+                        timer_fired.store(true, Ordering::Release);
+                        set_async_exc(ident, py.get_type::<TaskTimeout>().as_ptr());
+                    }
+                });
+            }
+        });
+        Some(AttemptTimer { task_id, _stop: stop, armed, fired })
+    }
+}
+
+impl Drop for AttemptTimer {
+    fn drop(&mut self) {
+        Python::attach(|_| {
+            let threads = TASK_THREADS.lock();
+            self.armed.store(false, Ordering::Release);
+            // A timeout that fired as the attempt returned must not hit the next one
+            if self.fired.load(Ordering::Acquire) {
+                if let Some(&ident) = threads.get(&self.task_id) {
+                    set_async_exc(ident, std::ptr::null_mut());
+                }
+            }
+        });
+    }
+}
+
+/// Start a timer that flags `cancel_token` once `secs` have passed and interrupts the task.
+/// The timer is cancelled early via `stop_timeout_timer` when the task finishes.
+fn start_timeout_timer(task_id: &str, secs: f64, cancel_token: Arc<AtomicBool>) {
+    // Without threads nothing can interrupt the call; overruns are still reported once it returns
+    if sync_fallback() {
+        return;
+    }
+    let (stop_sender, stop_receiver) = channel::<()>();
+    TIMEOUT_HANDLES.lock().push((task_id.to_string(), stop_sender));
+
+    let task_id = task_id.to_string();
+    thread::spawn(move || {
+        // A message or a disconnect means the task finished first
+        if let Err(std::sync::mpsc::RecvTimeoutError::Timeout) =
+            stop_receiver.recv_timeout(Duration::from_secs_f64(secs))
+        {
+            debug!("Task {} reached its timeout of {}s", task_id, secs);
+            cancel_token.store(true, Ordering::Release);
+            interrupt_task_thread(&task_id);
+        }
+        TIMEOUT_HANDLES.lock().retain(|(id, _)| id != &task_id);
+    });
+}
+
+/// Stop the timeout timer for a finished task (internal cleanup)
+fn stop_timeout_timer(task_id: &str) {
+    let mut handles = TIMEOUT_HANDLES.lock();
+    if let Some(pos) = handles.iter().position(|(id, _)| id == task_id) {
+        let (_, stop_sender) = handles.remove(pos);
+        let _ = stop_sender.send(());
+    }
+}
+
+/// Build the TaskError sent back when a task overran its timeout
+fn timeout_task_error(func_name: &str, task_id: &str, limit: f64, start: Instant) -> TaskError {
+    TaskError {
+        task_name: func_name.to_string(),
+        elapsed_time: start.elapsed().as_secs_f64(),
+        error_message: MakeParallelError::TaskTimeout {
+            task_id: task_id.to_string(),
+            timeout_secs: limit,
+        }
+        .to_string(),
+        error_type: "TimeoutError".to_string(),
+        task_id: task_id.to_string(),
+        submitted_from: task_submission_site(task_id),
+    }
+}
+
+// =============================================================================
+// THREAD POOL CONFIGURATION
+// =============================================================================
+
+/// A pool built by configure_thread_pool(), with a count of the work handed to it
+struct ConfiguredPool {
+    pool: rayon::ThreadPool,
+    in_flight: AtomicUsize,
+}
+
+impl ConfiguredPool {
+    /// Count one piece of work against the pool until the returned guard drops
+    fn track(self: &Arc<Self>) -> PoolWork {
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+        PoolWork { pool: self.clone() }
+    }
+}
+
+/// Work running on a configured pool
+struct PoolWork {
+    pool: Arc<ConfiguredPool>,
+}
+
+impl Drop for PoolWork {
+    fn drop(&mut self) {
+        self.pool.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Global thread pool configuration
+static CUSTOM_THREAD_POOL: Lazy<Arc<Mutex<Option<Arc<ConfiguredPool>>>>> =
+    Lazy::new(|| Arc::new(Mutex::new(None)));
+
+/// Pools replaced by configure_thread_pool() while work was still running on them
+static DRAINING_THREAD_POOLS: Lazy<Mutex<Vec<Arc<ConfiguredPool>>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// The pool set by configure_thread_pool(), if any
+fn configured_pool() -> Option<Arc<ConfiguredPool>> {
+    CUSTOM_THREAD_POOL.lock().clone()
+}
+
+/// Run `op` in the configured pool, so its parallel iterators use that pool's threads
+fn in_thread_pool<R: Send>(op: impl FnOnce() -> R + Send) -> R {
+    match configured_pool() {
+        Some(pool) => {
+            let _work = pool.track();
+            pool.pool.install(op)
+        }
+        None => op(),
+    }
+}
+
+/// Worker threads of the pool pool-based execution runs on
+fn pool_num_threads() -> usize {
+    configured_pool().map_or_else(rayon::current_num_threads, |pool| pool.pool.current_num_threads())
+}
+
+/// Replaced pools still finishing work, and how much work they have left
+fn draining_thread_pools() -> (usize, usize) {
+    let mut draining = DRAINING_THREAD_POOLS.lock();
+    draining.retain(|pool| pool.in_flight.load(Ordering::Acquire) > 0);
+    (draining.len(), draining.iter().map(|pool| pool.in_flight.load(Ordering::Acquire)).sum())
+}
+
+/// Configure the thread pool behind @parallel_pool, parallel_map() and the other pool-based helpers
+///
+/// Can be called again at any time to resize: new work goes to the new pool at once,
+/// while work already running drains on the old one, whose threads exit after it.
+#[pyfunction]
+#[pyo3(signature = (num_threads=None, stack_size=None))]
+fn configure_thread_pool(py: Python, num_threads: Option<usize>, stack_size: Option<usize>) -> PyResult<()> {
+    py.detach(|| {
+        let mut builder = rayon::ThreadPoolBuilder::new();
+
+        if let Some(threads) = num_threads {
+            builder = builder.num_threads(threads);
+        }
+
+        if let Some(stack) = stack_size {
+            builder = builder.stack_size(stack);
+        }
+
+        let pool = builder.build().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to build thread pool: {}", e))
+        })?;
+
+        let pool = Arc::new(ConfiguredPool { pool, in_flight: AtomicUsize::new(0) });
+        let replaced = CUSTOM_THREAD_POOL.lock().replace(pool);
+        if let Some(old) = replaced.filter(|old| old.in_flight.load(Ordering::Acquire) > 0) {
+            DRAINING_THREAD_POOLS.lock().push(old);
+        }
+        Ok(())
+    })
+}
+
+/// Get current thread pool info
+#[pyfunction]
+fn get_thread_pool_info(py: Python) -> PyResult<Py<PyDict>> {
+    let dict = PyDict::new(py);
+
+    if let Some(p) = configured_pool() {
+        dict.set_item("configured", true)?;
+        dict.set_item("current_num_threads", p.pool.current_num_threads())?;
+        dict.set_item("in_flight", p.in_flight.load(Ordering::Acquire))?;
+    } else {
+        dict.set_item("configured", false)?;
+        dict.set_item("current_num_threads", rayon::current_num_threads())?;
+        dict.set_item("in_flight", 0)?;
+    }
+    let (draining_pools, draining_tasks) = draining_thread_pools();
+    dict.set_item("draining_pools", draining_pools)?;
+    dict.set_item("draining_tasks", draining_tasks)?;
+    dict.set_item("free_threaded", FREE_THREADED.load(Ordering::Relaxed))?;
+    dict.set_item("priority_workers", priority_worker_count())?;
+
+    Ok(dict.unbind())
+}
+
+// =============================================================================
+// NAMED WORKER POOLS
+// =============================================================================
+
+pyo3::create_exception!(
+    makeparallel,
+    PoolFull,
+    pyo3::exceptions::PyRuntimeError,
+    "Raised when a call is routed to a named Pool whose queue is full."
+);
+
+type PoolJob = Box<dyn FnOnce() + Send>;
+
+/// A job and when it was queued
+type QueuedJob = (Instant, PoolJob);
+
+/// Worker threads and queue behind a named `Pool`
+struct WorkerPool {
+    name: String,
+    /// Threads wanted without autoscaling; changed by `Pool.resize()`
+    num_threads: AtomicUsize,
+    /// Most calls allowed to wait, or NO_QUEUE_LIMIT
+    max_queue: AtomicUsize,
+    /// None once the pool has been shut down
+    jobs: Mutex<Option<CrossbeamSender<QueuedJob>>>,
+    /// Receiving end of `jobs`, for workers started after the pool was built
+    queue: CrossbeamReceiver<QueuedJob>,
+    workers: Mutex<Vec<JoinHandle<()>>>,
+    /// Worker threads alive, and how many are wanted; idle workers above the target exit
+    live: AtomicUsize,
+    target: AtomicUsize,
+    spawned: AtomicUsize,
+    autoscaler: Mutex<AutoscalerState>,
+    /// Longest a job waited for a worker since the autoscaler last looked, in microseconds
+    max_wait_seen: AtomicU64,
+    queued: AtomicUsize,
+    running: AtomicUsize,
+    completed: AtomicU64,
+    rejected: AtomicU64,
+    /// Set when the interpreter that queued the jobs is gone, so they must not run
+    abandoned: AtomicBool,
+}
+
+/// `WorkerPool::max_queue` value for a pool whose queue is unbounded
+const NO_QUEUE_LIMIT: usize = usize::MAX;
+
+/// Pools by name, for routing `@parallel(pool=...)` calls
+static POOLS: Lazy<DashMap<String, Arc<WorkerPool>>> = Lazy::new(DashMap::new);
+
+impl WorkerPool {
+    fn named(name: &str) -> PyResult<Arc<WorkerPool>> {
+        POOLS.get(name).map(|pool| pool.value().clone()).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!("no pool named '{}'; create it with Pool()", name))
+        })
+    }
+
+    /// Take a place in the queue, or fail with PoolFull
+    fn reserve(self: &Arc<Self>) -> PyResult<PoolSlot> {
+        if self.jobs.lock().is_none() {
+            return Err(pyo3::exceptions::PyRuntimeError::new_err(format!("pool '{}' has been shut down", self.name)));
+        }
+        let max_queue = self.max_queue.load(Ordering::Acquire);
+        let reserved = self.queued.fetch_update(Ordering::AcqRel, Ordering::Acquire, |queued| {
+            (queued < max_queue).then_some(queued + 1)
+        });
+        match reserved {
+            Ok(_) => Ok(PoolSlot { pool: self.clone() }),
+            Err(queued) => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                Err(PoolFull::new_err(format!("pool '{}' queue is full ({} tasks waiting)", self.name, queued)))
+            }
+        }
+    }
+
+    fn run(&self, job: PoolJob) {
+        if self.abandoned.load(Ordering::Acquire) {
+            std::mem::forget(job);
+            return;
+        }
+        self.running.fetch_add(1, Ordering::AcqRel);
+        if std::panic::catch_unwind(std::panic::AssertUnwindSafe(job)).is_err() {
+            error!("A task panicked in pool '{}'", self.name);
+        }
+        self.running.fetch_sub(1, Ordering::AcqRel);
+        self.completed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Start `count` more worker threads
+    fn spawn_workers(self: &Arc<Self>, count: usize) -> std::io::Result<()> {
+        for _ in 0..count {
+            self.live.fetch_add(1, Ordering::AcqRel);
+            self.spawn_counted_worker()?;
+        }
+        Ok(())
+    }
+
+    /// Start a worker thread already counted in `live`
+    fn spawn_counted_worker(self: &Arc<Self>) -> std::io::Result<()> {
+        let pool = self.clone();
+        let index = self.spawned.fetch_add(1, Ordering::Relaxed);
+        let worker = thread::Builder::new()
+            .name(format!("makeparallel-{}-{}", self.name, index))
+            .spawn(move || pool.work());
+        let mut workers = self.workers.lock();
+        workers.retain(|worker| !worker.is_finished());
+        match worker {
+            Ok(worker) => {
+                workers.push(worker);
+                Ok(())
+            }
+            Err(e) => {
+                self.live.fetch_sub(1, Ordering::AcqRel);
+                Err(e)
+            }
+        }
+    }
+
+    /// Start workers reaped while idle, up to the target and the calls waiting or running
+    fn respawn_workers(self: &Arc<Self>) {
+        let wanted = self.target.load(Ordering::Acquire).min(
+            self.queued.load(Ordering::Acquire) + self.running.load(Ordering::Acquire),
+        );
+        let reserve = |live: usize| (live < wanted).then_some(live + 1);
+        let mut started = false;
+        while self.live.fetch_update(Ordering::AcqRel, Ordering::Acquire, reserve).is_ok() {
+            if let Err(e) = self.spawn_counted_worker() {
+                warn!("Failed to restart a worker for pool '{}': {}", self.name, e);
+                break;
+            }
+            started = true;
+        }
+        if started {
+            let mut autoscaler = self.autoscaler.lock();
+            if autoscaler.config.is_some() && !autoscaler.monitor_running {
+                autoscaler.monitor_running = true;
+                let pool = self.clone();
+                thread::spawn(move || pool.run_autoscaler());
+            }
+        }
+    }
+
+    /// Run jobs until the pool is shut down, or until idle while there are more workers
+    /// than wanted or for longer than the idle timeout
+    fn work(&self) {
+        let mut idle_since = Instant::now();
+        loop {
+            match self.queue.recv_timeout(AUTOSCALE_INTERVAL) {
+                Ok((queued_at, job)) => {
+                    let waited = queued_at.elapsed().as_micros() as u64;
+                    self.max_wait_seen.fetch_max(waited, Ordering::AcqRel);
+                    self.run(job);
+                    idle_since = Instant::now();
+                    // Shrunk while busy: leave now rather than once the queue runs dry
+                    if self.retire_surplus_worker() {
+                        return;
+                    }
+                }
+                Err(crossbeam::channel::RecvTimeoutError::Timeout) if idle_expired(idle_since) => {
+                    self.live.fetch_sub(1, Ordering::AcqRel);
+                    // A job sent before the count dropped is seen here; one sent after sees the
+                    // lower count and respawns a worker
+                    if self.queue.is_empty() {
+                        return;
+                    }
+                    self.live.fetch_add(1, Ordering::AcqRel);
+                }
+                Err(crossbeam::channel::RecvTimeoutError::Timeout) => {
+                    if self.retire_surplus_worker() {
+                        return;
+                    }
+                }
+                Err(crossbeam::channel::RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        self.live.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// Uncount the calling worker if there are more than the target, returning true if it should exit
+    fn retire_surplus_worker(&self) -> bool {
+        self.live
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |live| {
+                (live > self.target.load(Ordering::Acquire)).then(|| live - 1)
+            })
+            .is_ok()
+    }
+
+    /// Sample the queue and resize the pool until autoscaling is turned off or the pool shuts down
+    fn run_autoscaler(self: Arc<Self>) {
+        let mut quiet_since = None;
+        loop {
+            thread::sleep(AUTOSCALE_INTERVAL);
+            let mut state = self.autoscaler.lock();
+            // All threads reaped while idle: respawn_workers() brings the autoscaler back
+            let reaped = self.live.load(Ordering::Acquire) == 0 && self.queued.load(Ordering::Acquire) == 0;
+            let config = match state.config.clone() {
+                Some(config) if self.jobs.lock().is_some() && !reaped => config,
+                _ => {
+                    state.monitor_running = false;
+                    return;
+                }
+            };
+            let depth = self.queued.load(Ordering::Acquire);
+            let oldest_wait = Duration::from_micros(self.max_wait_seen.swap(0, Ordering::AcqRel));
+            let target = self.target.load(Ordering::Acquire);
+            let wanted = config.decide(target, depth, oldest_wait, &mut quiet_since, Instant::now());
+            let event = match wanted.cmp(&target) {
+                CmpOrdering::Greater => {
+                    state.scale_ups += 1;
+                    "pool_scaled_up"
+                }
+                CmpOrdering::Less => {
+                    state.scale_downs += 1;
+                    "pool_scaled_down"
+                }
+                CmpOrdering::Equal => continue,
+            };
+            drop(state);
+
+            self.target.store(wanted, Ordering::Release);
+            let live = self.live.load(Ordering::Acquire);
+            if wanted > live {
+                if let Err(e) = self.spawn_workers(wanted - live) {
+                    warn!("Failed to grow pool '{}': {}", self.name, e);
+                }
+            }
+            debug!("Pool '{}' scaled {} -> {} threads (queue depth {})", self.name, target, wanted, depth);
+            publish_task_event(
+                event,
+                "",
+                &self.name,
+                serde_json::json!({ "pool": self.name, "from": target, "to": wanted, "queued": depth }),
+            );
+        }
+    }
+
+    /// Stop accepting tasks and unregister the name; queued tasks still run
+    fn close(self: &Arc<Self>) -> Vec<JoinHandle<()>> {
+        self.jobs.lock().take();
+        POOLS.remove_if(&self.name, |_, pool| Arc::ptr_eq(pool, self));
+        self.workers.lock().drain(..).collect()
+    }
+}
+
+/// A reserved place in a pool's queue, given back when dropped
+struct PoolSlot {
+    pool: Arc<WorkerPool>,
+}
+
+impl Drop for PoolSlot {
+    fn drop(&mut self) {
+        self.pool.queued.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Run `job` on the pool whose slot was reserved, or on a task thread without one
+fn spawn_pooled_task(slot: Option<PoolSlot>, job: impl FnOnce() + Send + 'static) -> Option<JoinHandle<()>> {
+    let Some(slot) = slot else {
+        return spawn_task_thread(job);
+    };
+    let pool = slot.pool.clone();
+    // The slot leaves the queue as a worker picks the job up
+    let job: PoolJob = Box::new(move || {
+        drop(slot);
+        job();
+    });
+    if sync_fallback() {
+        pool.run(job);
+        return None;
+    }
+    let sent = match *pool.jobs.lock() {
+        Some(ref jobs) => jobs.send((Instant::now(), job)).map_err(|e| e.into_inner().1),
+        None => Err(job),
+    };
+    match sent {
+        Ok(()) => {
+            pool.respawn_workers();
+            None
+        }
+        // Shut down since the slot was reserved: the task is already registered, so run it anyway
+        Err(job) => spawn_task_thread(job),
+    }
+}
+
+/// Named, independently sized set of worker threads for `@parallel(pool=name)`
+///
+/// At most `num_threads` routed calls run at once; up to `max_queue` more wait
+/// (unbounded by default), and further calls raise PoolFull. `configure_autoscaling()`
+/// lets the thread count follow the queue instead.
+#[pyclass(frozen)]
+struct Pool {
+    inner: Arc<WorkerPool>,
+}
+
+#[pymethods]
+impl Pool {
+    #[new]
+    #[pyo3(signature = (name, num_threads, max_queue=None))]
+    fn new(name: String, num_threads: usize, max_queue: Option<usize>) -> PyResult<Self> {
+        if num_threads == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("num_threads must be at least 1"));
+        }
+        let (jobs, queue) = unbounded::<QueuedJob>();
+        let inner = Arc::new(WorkerPool {
+            name: name.clone(),
+            num_threads: AtomicUsize::new(num_threads),
+            max_queue: AtomicUsize::new(max_queue.unwrap_or(NO_QUEUE_LIMIT)),
+            jobs: Mutex::new(Some(jobs)),
+            queue,
+            workers: Mutex::new(Vec::new()),
+            live: AtomicUsize::new(0),
+            target: AtomicUsize::new(num_threads),
+            spawned: AtomicUsize::new(0),
+            autoscaler: Mutex::new(AutoscalerState::default()),
+            max_wait_seen: AtomicU64::new(0),
+            queued: AtomicUsize::new(0),
+            running: AtomicUsize::new(0),
+            completed: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            abandoned: AtomicBool::new(false),
+        });
+        match POOLS.entry(name) {
+            dashmap::mapref::entry::Entry::Occupied(_) => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "a pool named '{}' already exists",
+                    inner.name
+                )));
+            }
+            dashmap::mapref::entry::Entry::Vacant(slot) => {
+                slot.insert(inner.clone());
+            }
+        }
+
+        if !sync_fallback() {
+            if let Err(e) = inner.spawn_workers(num_threads) {
+                inner.close();
+                return Err(pyo3::exceptions::PyRuntimeError::new_err(format!(
+                    "Failed to start pool '{}': {}",
+                    inner.name, e
+                )));
+            }
+        }
+        Ok(Pool { inner })
+    }
+
+    #[getter]
+    fn name(&self) -> String {
+        self.inner.name.clone()
+    }
+
+    #[getter]
+    fn num_threads(&self) -> usize {
+        self.inner.num_threads.load(Ordering::Acquire)
+    }
+
+    #[getter]
+    fn max_queue(&self) -> Option<usize> {
+        Some(self.inner.max_queue.load(Ordering::Acquire)).filter(|&max| max != NO_QUEUE_LIMIT)
+    }
+
+    /// Change the number of threads while the pool is in use
+    ///
+    /// Growing starts threads at once. Shrinking lets each surplus thread finish the
+    /// call it is running before it exits; queued calls wait for the remaining threads.
+    fn resize(&self, py: Python, num_threads: usize) -> PyResult<()> {
+        let pool = &self.inner;
+        if num_threads == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("num_threads must be at least 1"));
+        }
+        if pool.jobs.lock().is_none() {
+            return Err(pyo3::exceptions::PyRuntimeError::new_err(format!("pool '{}' has been shut down", pool.name)));
+        }
+        let autoscaler = pool.autoscaler.lock();
+        if autoscaler.config.is_some() {
+            return Err(pyo3::exceptions::PyRuntimeError::new_err(format!(
+                "pool '{}' is autoscaling; change its bounds with configure_autoscaling()",
+                pool.name
+            )));
+        }
+        let from = pool.num_threads.swap(num_threads, Ordering::AcqRel);
+        pool.target.store(num_threads, Ordering::Release);
+        drop(autoscaler);
+
+        if !sync_fallback() {
+            let live = pool.live.load(Ordering::Acquire);
+            py.detach(|| pool.spawn_workers(num_threads.saturating_sub(live))).map_err(|e| {
+                pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to grow pool '{}': {}", pool.name, e))
+            })?;
+        }
+        publish_task_event(
+            "pool_resized",
+            "",
+            &pool.name,
+            serde_json::json!({ "pool": pool.name, "from": from, "to": num_threads }),
+        );
+        Ok(())
+    }
+
+    /// Change how many calls may wait; None removes the limit
+    ///
+    /// Calls already queued beyond a lowered limit still run; only new calls raise PoolFull.
+    fn set_max_queue(&self, max_queue: Option<usize>) {
+        self.inner.max_queue.store(max_queue.unwrap_or(NO_QUEUE_LIMIT), Ordering::Release);
+    }
+
+    /// Grow and shrink the pool's threads with its queue, as configure_autoscaling() does for priority workers
+    ///
+    /// The thread count moves between `min_threads` and `max_threads` (default:
+    /// `num_threads`), and each change publishes a `pool_scaled_up` or
+    /// `pool_scaled_down` event. Pass `enabled=False` to return to `num_threads`.
+    #[pyo3(signature = (
+        min_threads=1,
+        max_threads=None,
+        queue_per_thread=2,
+        max_wait=0.1,
+        scale_down_after=5.0,
+        enabled=true
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn configure_autoscaling(
+        &self,
+        py: Python,
+        min_threads: usize,
+        max_threads: Option<usize>,
+        queue_per_thread: usize,
+        max_wait: f64,
+        scale_down_after: f64,
+        enabled: bool,
+    ) -> PyResult<()> {
+        let pool = &self.inner;
+        let num_threads = pool.num_threads.load(Ordering::Acquire);
+        let max_threads = max_threads.unwrap_or(num_threads);
+        let config = Autoscaling::new(min_threads, max_threads, queue_per_thread, max_wait, scale_down_after)?;
+        let config = enabled.then_some(config);
+
+        let mut state = pool.autoscaler.lock();
+        let target = match &config {
+            Some(config) => pool.target.load(Ordering::Acquire).clamp(config.min_threads, config.max_threads),
+            None => num_threads,
+        };
+        let start_monitor = config.is_some() && !state.monitor_running && !sync_fallback();
+        state.config = config;
+        state.monitor_running |= start_monitor;
+        drop(state);
+
+        if sync_fallback() || pool.jobs.lock().is_none() {
+            return Ok(());
+        }
+        pool.target.store(target, Ordering::Release);
+        let live = pool.live.load(Ordering::Acquire);
+        py.detach(|| {
+            pool.spawn_workers(target.saturating_sub(live))?;
+            if start_monitor {
+                let pool = pool.clone();
+                thread::spawn(move || pool.run_autoscaler());
+            }
+            Ok::<_, std::io::Error>(())
+        })
+        .map_err(|e| {
+            pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to grow pool '{}': {}", pool.name, e))
+        })
+    }
+
+    /// Current `queued` and `running` counts, and totals of `completed` and `rejected` calls
+    ///
+    /// Also the live `threads` and the `target_threads` wanted, and how often
+    /// autoscaling has scaled up and down.
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let stats = PyDict::new(py);
+        stats.set_item("queued", self.inner.queued.load(Ordering::Acquire))?;
+        stats.set_item("running", self.inner.running.load(Ordering::Acquire))?;
+        stats.set_item("completed", self.inner.completed.load(Ordering::Relaxed))?;
+        stats.set_item("rejected", self.inner.rejected.load(Ordering::Relaxed))?;
+        stats.set_item("threads", self.inner.live.load(Ordering::Acquire))?;
+        stats.set_item("target_threads", self.inner.target.load(Ordering::Acquire))?;
+        let autoscaler = self.inner.autoscaler.lock();
+        stats.set_item("autoscaling", autoscaler.config.is_some())?;
+        stats.set_item("scale_ups", autoscaler.scale_ups)?;
+        stats.set_item("scale_downs", autoscaler.scale_downs)?;
+        Ok(stats)
+    }
+
+    /// Stop accepting calls and free the name; with `wait=True` block until queued calls finish
+    #[pyo3(signature = (wait=true))]
+    fn shutdown(&self, py: Python, wait: bool) {
+        let workers = self.inner.close();
+        if wait {
+            py.detach(|| {
+                for worker in workers {
+                    let _ = worker.join();
+                }
+            });
+        }
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &self,
+        py: Python,
+        _exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> bool {
+        self.shutdown(py, true);
+        false
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Pool(name={:?}, num_threads={})", self.inner.name, self.num_threads())
+    }
+}
+
+// =============================================================================
+// DURABLE QUEUE
+// =============================================================================
+
+/// Jobs of a DurableQueue file; a row is deleted once its job succeeds
+#[cfg(feature = "durable-queue")]
+const DURABLE_QUEUE_SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+    PRAGMA synchronous = FULL;
+    CREATE TABLE IF NOT EXISTS jobs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        module TEXT NOT NULL,
+        qualname TEXT NOT NULL,
+        payload BLOB NOT NULL,
+        state TEXT NOT NULL,
+        submitted_at REAL NOT NULL,
+        error TEXT
+    );
+    CREATE INDEX IF NOT EXISTS jobs_by_state ON jobs (state, id);
+";
+
+#[cfg(feature = "durable-queue")]
+type JobStore = Arc<Mutex<Option<rusqlite::Connection>>>;
+
+/// Starts a job payload sealed with the persistence key, with the job id as context
+#[cfg(feature = "durable-queue")]
+const SEALED_JOB_MAGIC: &[u8; 8] = b"MPJOBE1\n";
+
+/// The pickled arguments of job `id`, decrypting them if they were sealed
+///
+/// While a key is set an unsealed payload is refused, as anyone able to write the
+/// queue file could have put it there.
+#[cfg(feature = "durable-queue")]
+fn open_job_payload(path: &std::path::Path, id: i64, payload: &[u8]) -> PyResult<Vec<u8>> {
+    match (payload.strip_prefix(SEALED_JOB_MAGIC), persistence_key()) {
+        (Some(sealed), Some(key)) => unseal(&key, &id.to_le_bytes(), sealed).ok_or_else(|| wrong_key_error(path)),
+        (Some(_), None) => Err(missing_key_error(path)),
+        (None, Some(_)) => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "job {} in {} is not encrypted, but configure_persistence() set a key",
+            id,
+            path.display()
+        ))),
+        (None, None) => Ok(payload.to_vec()),
+    }
+}
+
+#[cfg(feature = "durable-queue")]
+fn job_store_error(path: &std::path::Path, e: rusqlite::Error) -> PyErr {
+    pyo3::exceptions::PyOSError::new_err(format!("durable queue {}: {}", path.display(), e))
+}
+
+/// Run `f` on the queue file, failing once close() has released it
+#[cfg(feature = "durable-queue")]
+fn with_job_store<T>(
+    store: &JobStore,
+    f: impl FnOnce(&mut rusqlite::Connection) -> rusqlite::Result<T>,
+) -> rusqlite::Result<T> {
+    match store.lock().as_mut() {
+        Some(db) => f(db),
+        None => Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+            Some("the queue is closed".to_string()),
+        )),
+    }
+}
+
+/// Record a job's new state, logging rather than raising so the job's own outcome stands
+#[cfg(feature = "durable-queue")]
+fn update_job(store: &JobStore, id: i64, sql: &str, params: impl rusqlite::Params) {
+    if let Err(e) = with_job_store(store, |db| db.execute(sql, params)) {
+        warn!("Failed to update durable job {}: {}", id, e);
+    }
+}
+
+/// The module-level function `module.qualname`
+#[cfg(feature = "durable-queue")]
+fn resolve_job_function<'py>(py: Python<'py>, module: &str, qualname: &str) -> PyResult<Bound<'py, PyAny>> {
+    let mut target = py.import(module)?.into_any();
+    for part in qualname.split('.') {
+        target = target.getattr(part)?;
+    }
+    Ok(target)
+}
+
+/// One job of a DurableQueue, as run by its pool: tracks its row while calling the function
+#[cfg(feature = "durable-queue")]
+#[pyclass]
+struct DurableJob {
+    store: JobStore,
+    id: i64,
+    func: Py<PyAny>,
+    args: Py<PyTuple>,
+    kwargs: Option<Py<PyDict>>,
+}
+
+#[cfg(feature = "durable-queue")]
+#[pymethods]
+impl DurableJob {
+    fn __call__(&self, py: Python) -> PyResult<Py<PyAny>> {
+        let (store, id) = (&self.store, self.id);
+        py.detach(|| update_job(store, id, "UPDATE jobs SET state = 'running' WHERE id = ?1", [id]));
+        let result = self.func.call(py, self.args.bind(py), self.kwargs.as_ref().map(|kwargs| kwargs.bind(py)));
+        let error = result.as_ref().err().map(|e| e.to_string());
+        py.detach(|| match error {
+            None => update_job(store, id, "DELETE FROM jobs WHERE id = ?1", [id]),
+            Some(error) => update_job(
+                store,
+                id,
+                "UPDATE jobs SET state = 'failed', error = ?2 WHERE id = ?1",
+                rusqlite::params![id, error],
+            ),
+        });
+        result
+    }
+}
+
+/// Job queue kept in a SQLite file, so jobs not yet started survive a restart
+///
+/// `submit()` records the function (by module and qualified name) and its pickled
+/// arguments before queueing the job for one of `workers` threads. Opening the file
+/// again re-submits the jobs that were still queued; their handles are in `recovered`.
+#[cfg(feature = "durable-queue")]
+#[pyclass]
+struct DurableQueue {
+    path: PathBuf,
+    store: JobStore,
+    pool: Pool,
+    #[pyo3(get)]
+    recovered: Vec<Py<AsyncHandle>>,
+}
+
+#[cfg(feature = "durable-queue")]
+impl DurableQueue {
+    /// Queue job `id` on the pool, returning its handle
+    fn run(
+        &self,
+        py: Python,
+        id: i64,
+        func: &Bound<'_, PyAny>,
+        qualname: &str,
+        args: Py<PyTuple>,
+        kwargs: Option<Py<PyDict>>,
+    ) -> PyResult<Py<AsyncHandle>> {
+        let job = DurableJob { store: self.store.clone(), id, func: func.clone().unbind(), args, kwargs };
+        let wrapper = ParallelWrapper::new(py, Py::new(py, job)?.into_any(), Isolation::Thread, None, None, Vec::new(), None)
+            .with_pool(Some(self.pool.inner.name.clone()))
+            .with_name(Some(qualname.to_string()));
+        let handle: Py<AsyncHandle> = wrapper
+            .call_with(py, &PyTuple::empty(py), None, None, None, false, false, None)?
+            .extract(py)?;
+
+        // Cancelled before it started: drop the job, unless the whole process is shutting down
+        let store = self.store.clone();
+        handle.borrow(py).completion.subscribe(
+            py,
+            Box::new(move |_, _| {
+                if !is_shutdown_requested() {
+                    update_job(&store, id, "DELETE FROM jobs WHERE id = ?1 AND state = 'queued'", [id]);
+                }
+            }),
+        );
+        Ok(handle)
+    }
+}
+
+#[cfg(feature = "durable-queue")]
+#[pymethods]
+impl DurableQueue {
+    /// Open or create the queue at `path`, re-submitting jobs left queued by the last process
+    ///
+    /// Jobs that were running when it stopped are marked "interrupted" and not rerun,
+    /// as they may have had side effects. The file is locked until close(), so only one
+    /// process at a time can use it.
+    #[new]
+    #[pyo3(signature = (path, workers=1))]
+    fn new(py: Python, path: PathBuf, workers: usize) -> PyResult<Self> {
+        let store = rusqlite::Connection::open(&path)
+            .and_then(|db| {
+                db.pragma_update(None, "locking_mode", "EXCLUSIVE")?;
+                db.execute_batch(DURABLE_QUEUE_SCHEMA)?;
+                db.execute("UPDATE jobs SET state = 'interrupted' WHERE state = 'running'", [])?;
+                Ok(db)
+            })
+            .map_err(|e| job_store_error(&path, e))?;
+        let name = format!("durable:{}", path.canonicalize().unwrap_or_else(|_| path.clone()).display());
+        let mut queue = DurableQueue {
+            store: Arc::new(Mutex::new(Some(store))),
+            pool: Pool::new(name, workers, None)?,
+            path,
+            recovered: Vec::new(),
+        };
+
+        let queued: Vec<(i64, String, String, Vec<u8>)> = with_job_store(&queue.store, |db| {
+            db.prepare("SELECT id, module, qualname, payload FROM jobs WHERE state = 'queued' ORDER BY id")?
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+                .collect()
+        })
+        .map_err(|e| job_store_error(&queue.path, e))?;
+        let pickle = py.import("pickle")?;
+        for (id, module, qualname, payload) in queued {
+            let job = resolve_job_function(py, &module, &qualname).and_then(|func| {
+                let payload = open_job_payload(&queue.path, id, &payload)?;
+                let (args, kwargs): (Py<PyTuple>, Option<Py<PyDict>>) =
+                    pickle.call_method1("loads", (PyBytes::new(py, &payload),))?.extract()?;
+                Ok((func, args, kwargs))
+            });
+            match job {
+                Ok((func, args, kwargs)) => {
+                    let handle = queue.run(py, id, &func, &qualname, args, kwargs)?;
+                    queue.recovered.push(handle);
+                }
+                Err(e) => {
+                    warn!("Cannot recover durable job {} ({}.{}): {}", id, module, qualname, e);
+                    update_job(
+                        &queue.store,
+                        id,
+                        "UPDATE jobs SET state = 'failed', error = ?2 WHERE id = ?1",
+                        rusqlite::params![id, format!("cannot recover: {}", e)],
+                    );
+                }
+            }
+        }
+        if !queue.recovered.is_empty() {
+            log::info!("Recovered {} queued jobs from {}", queue.recovered.len(), queue.path.display());
+        }
+        Ok(queue)
+    }
+
+    /// Record `func(*args, **kwargs)` in the queue file, then queue it to run
+    ///
+    /// `func` must be a module-level function, found again by name after a restart,
+    /// and the arguments must pickle. The job is on disk when this returns, with its
+    /// arguments encrypted if configure_persistence() set a key.
+    #[pyo3(signature = (func, *args, **kwargs))]
+    fn submit(
+        &self,
+        py: Python,
+        func: &Bound<'_, PyAny>,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<AsyncHandle>> {
+        let name = |attr: &str| func.getattr(attr).and_then(|name| name.extract::<String>()).ok();
+        let resolvable = |module: &str, qualname: &str| {
+            resolve_job_function(py, module, qualname).is_ok_and(|found| found.is(func))
+        };
+        let (module, qualname) = match (name("__module__"), name("__qualname__")) {
+            (Some(module), Some(qualname)) if resolvable(&module, &qualname) => (module, qualname),
+            _ => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "{} is not a module-level function, so it could not be found again after a restart",
+                    callable_name(func)
+                )))
+            }
+        };
+        let payload: Vec<u8> = py.import("pickle")?.call_method1("dumps", ((args, kwargs),))?.extract()?;
+        let key = persistence_key();
+
+        // A sealed payload is bound to its job id, known once the row exists, so the row
+        // is inserted and then filled in within one transaction
+        let id = py.detach(|| {
+            let mut encrypt_failed = false;
+            let stored = with_job_store(&self.store, |db| {
+                let tx = db.transaction()?;
+                tx.execute(
+                    "INSERT INTO jobs (module, qualname, payload, state, submitted_at) \
+                     VALUES (?1, ?2, ?3, 'queued', ?4)",
+                    rusqlite::params![module, qualname, payload, unix_now()],
+                )?;
+                let id = tx.last_insert_rowid();
+                if let Some(ref key) = key {
+                    let Ok(sealed) = seal(key, &id.to_le_bytes(), &payload) else {
+                        // Dropping the transaction rolls the row back
+                        encrypt_failed = true;
+                        return Ok(id);
+                    };
+                    let sealed = [SEALED_JOB_MAGIC.as_slice(), &sealed].concat();
+                    tx.execute("UPDATE jobs SET payload = ?2 WHERE id = ?1", rusqlite::params![id, sealed])?;
+                }
+                tx.commit()?;
+                Ok(id)
+            });
+            match stored {
+                Ok(_) if encrypt_failed => {
+                    Err(pyo3::exceptions::PyRuntimeError::new_err("Failed to encrypt the job's arguments"))
+                }
+                Ok(id) => Ok(id),
+                Err(e) => Err(job_store_error(&self.path, e)),
+            }
+        })?;
+        self.run(py, id, func, &qualname, args.clone().unbind(), kwargs.map(|kwargs| kwargs.clone().unbind()))
+    }
+
+    /// Number of jobs `queued`, `running`, `failed` and `interrupted` in the file
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let counts: Vec<(String, i64)> = with_job_store(&self.store, |db| {
+            db.prepare("SELECT state, COUNT(*) FROM jobs GROUP BY state")?
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect()
+        })
+        .map_err(|e| job_store_error(&self.path, e))?;
+        let stats = PyDict::new(py);
+        for state in ["queued", "running", "failed", "interrupted"] {
+            stats.set_item(state, 0)?;
+        }
+        for (state, count) in counts {
+            stats.set_item(state, count)?;
+        }
+        Ok(stats)
+    }
+
+    /// Stop taking jobs; with `wait=True` block until queued jobs finish
+    ///
+    /// Jobs still queued when the process exits run again on the next open. Once the
+    /// jobs have finished the file lock is released, so the queue can be opened again.
+    #[pyo3(signature = (wait=true))]
+    fn close(&self, py: Python, wait: bool) {
+        self.pool.shutdown(py, wait);
+        if wait {
+            // A WAL database keeps its exclusive lock until the connection closes
+            if let Some(db) = py.detach(|| self.store.lock().take()) {
+                if let Err((_, e)) = db.close() {
+                    warn!("Failed to close durable queue {}: {}", self.path.display(), e);
+                }
+            }
+        }
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &self,
+        py: Python,
+        _exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> bool {
+        self.close(py, true);
+        false
+    }
+
+    fn __repr__(&self) -> String {
+        format!("DurableQueue(path={:?}, workers={})", self.path.display().to_string(), self.pool.num_threads())
+    }
+}
+
+// =============================================================================
+// FREE-THREADING SUPPORT
+// =============================================================================
+
+/// Whether Python code runs without a GIL in this interpreter, detected at module init
+static FREE_THREADED: AtomicBool = AtomicBool::new(false);
+
+/// The build may be free-threaded while the GIL is still on (PYTHON_GIL=1 or a GIL-only extension)
+#[cfg(Py_GIL_DISABLED)]
+fn detect_free_threading(py: Python) -> bool {
+    py.import("sys")
+        .and_then(|sys| sys.call_method0("_is_gil_enabled"))
+        .and_then(|enabled| enabled.extract::<bool>())
+        .map(|enabled| !enabled)
+        .unwrap_or(false)
+}
+
+#[cfg(not(Py_GIL_DISABLED))]
+fn detect_free_threading(_py: Python) -> bool {
+    false
+}
+
+/// Number of threads draining the priority queue
+///
+/// With a GIL extra workers would only contend for it; without one each worker runs Python in parallel.
+fn priority_worker_count() -> usize {
+    if FREE_THREADED.load(Ordering::Relaxed) {
+        default_thread_count()
+    } else {
+        1
+    }
+}
+
+/// Check whether tasks run Python code truly in parallel (free-threaded CPython with the GIL disabled)
+#[pyfunction]
+fn is_free_threaded() -> bool {
+    FREE_THREADED.load(Ordering::Relaxed)
+}
+
+// =============================================================================
+// SYNCHRONOUS FALLBACK
+// =============================================================================
+
+/// Set at module init on platforms without threads (WASM, locked-down sandboxes):
+/// tasks then run to completion on the submitting thread behind the same handle API
+static SYNC_FALLBACK: AtomicBool = AtomicBool::new(false);
+
+/// Whether threads can't be spawned here; `MAKEPARALLEL_SYNC_FALLBACK=1` forces the fallback
+fn detect_sync_fallback() -> bool {
+    if std::env::var("MAKEPARALLEL_SYNC_FALLBACK").is_ok_and(|v| v == "1") {
+        return true;
+    }
+    match thread::Builder::new().spawn(|| {}) {
+        Ok(probe) => {
+            let _ = probe.join();
+            false
+        }
+        Err(_) => true,
+    }
+}
+
+fn sync_fallback() -> bool {
+    SYNC_FALLBACK.load(Ordering::Relaxed)
+}
+
+/// Run `job` on a new thread, or inline when threads are unavailable
+fn spawn_task_thread(job: impl FnOnce() + Send + 'static) -> Option<JoinHandle<()>> {
+    if sync_fallback() {
+        job();
+        None
+    } else {
+        Some(thread::spawn(job))
+    }
+}
+
+/// Run `job` on the configured thread pool, or inline when threads are unavailable
+fn spawn_pool_job(job: impl FnOnce() + Send + 'static) {
+    if sync_fallback() {
+        job();
+    } else if let Some(pool) = configured_pool() {
+        let work = pool.track();
+        pool.pool.spawn(move || {
+            let _work = work;
+            job();
+        });
+    } else {
+        rayon::spawn(job);
+    }
+}
+
+/// Set once the first failure to change a worker's OS priority has been logged
+static OS_PRIORITY_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Move the current worker thread to `priority`, logging (once) if the OS refuses
+fn apply_os_priority(priority: ThreadPriority) {
+    if let Err(e) = platform::set_current_thread_priority(priority) {
+        if !OS_PRIORITY_WARNED.swap(true, Ordering::Relaxed) {
+            warn!("Could not set os_priority=\"{}\" for worker threads: {}", priority.as_str(), e);
+        } else {
+            debug!("Could not set os_priority=\"{}\": {}", priority.as_str(), e);
+        }
+    }
+}
+
+/// How tasks execute: "threads", or "sync" when the platform can't spawn threads
+#[pyfunction]
+fn get_execution_mode() -> &'static str {
+    if sync_fallback() {
+        "sync"
+    } else {
+        "threads"
+    }
+}
+
+// =============================================================================
+// PRIORITY QUEUE IMPLEMENTATION
+// =============================================================================
+
+/// Priority task wrapper
+struct PriorityTask {
+    priority: i32,
+    /// Unix timestamp by which the task must start
+    deadline: Option<f64>,
+    task_id: String,
+    func: Py<PyAny>,
+    func_name: String,
+    args: Py<PyTuple>,
+    kwargs: Option<Py<PyDict>>,
+    sender: CrossbeamSender<PyResult<Py<PyAny>>>,
+    timeout: Option<f64>,
+    total_timeout: Option<f64>,
+    submitted_at: Instant,
+    failure_watch: Arc<FailureWatch>,
+    completion: Arc<TaskCompletion>,
+}
+
+impl Eq for PriorityTask {}
+
+impl PartialEq for PriorityTask {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl PartialOrd for PriorityTask {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PriorityTask {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        // Earliest deadline first, then tasks without a deadline; higher priority
+        // values break ties
+        let by_deadline = match (self.deadline, other.deadline) {
+            (Some(mine), Some(theirs)) => theirs.total_cmp(&mine),
+            (Some(_), None) => CmpOrdering::Greater,
+            (None, Some(_)) => CmpOrdering::Less,
+            (None, None) => CmpOrdering::Equal,
+        };
+        by_deadline.then(self.priority.cmp(&other.priority))
+    }
+}
+
+pyo3::create_exception!(
+    makeparallel,
+    DeadlineExceeded,
+    pyo3::exceptions::PyTimeoutError,
+    "A priority task's deadline passed before a worker could start it."
+);
+
+/// Global priority queue
+static PRIORITY_QUEUE: Lazy<Arc<Mutex<BinaryHeap<PriorityTask>>>> =
+    Lazy::new(|| Arc::new(Mutex::new(BinaryHeap::new())));
+
+/// Worker thread flag
+static PRIORITY_WORKER_RUNNING: Lazy<Arc<AtomicBool>> =
+    Lazy::new(|| Arc::new(AtomicBool::new(false)));
+
+/// Priority worker threads currently running
+static PRIORITY_WORKERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of priority workers wanted; idle workers above it exit
+static PRIORITY_WORKER_TARGET: AtomicUsize = AtomicUsize::new(0);
+
+/// Priority workers currently running a task
+static PRIORITY_WORKERS_BUSY: AtomicUsize = AtomicUsize::new(0);
+
+/// Start the priority queue worker
+#[pyfunction]
+fn start_priority_worker(py: Python) -> PyResult<()> {
+    if PRIORITY_WORKER_RUNNING.load(Ordering::Acquire) {
+        return Ok(());
+    }
+
+    PRIORITY_WORKER_RUNNING.store(true, Ordering::Release);
+
+    let mut autoscaler = AUTOSCALER.lock();
+    let workers = autoscaler.config.as_ref().map_or_else(priority_worker_count, |config| config.min_threads);
+    // The autoscaler exits while the workers are stopped or reaped; bring it back with them
+    let start_monitor = autoscaler.config.is_some() && !autoscaler.monitor_running;
+    autoscaler.monitor_running |= start_monitor;
+    drop(autoscaler);
+
+    PRIORITY_WORKER_TARGET.store(workers, Ordering::Release);
+    py.detach(|| {
+        spawn_priority_workers(workers);
+        if start_monitor {
+            thread::spawn(run_autoscaler);
+        }
+    });
+
+    Ok(())
+}
+
+/// Start `count` more priority workers under the current runtime generation
+fn spawn_priority_workers(count: usize) {
+    let generation = RUNTIME_GENERATION.load(Ordering::Acquire);
+    for _ in 0..count {
+        PRIORITY_WORKERS.fetch_add(1, Ordering::AcqRel);
+        thread::spawn(move || run_priority_worker(generation));
+    }
+}
+
+/// Pop and run priority tasks until the worker is stopped, the runtime is reset,
+/// or it finds the queue empty while there are more workers than wanted
+fn run_priority_worker(generation: u64) {
+    let mut idle_since = Instant::now();
+    while PRIORITY_WORKER_RUNNING.load(Ordering::Acquire)
+        && RUNTIME_GENERATION.load(Ordering::Acquire) == generation
+    {
+        let task_opt = {
+            let mut queue = PRIORITY_QUEUE.lock();
+            queue.pop()
+        };
+
+        if let Some(task) = task_opt {
+            PRIORITY_WORKERS_BUSY.fetch_add(1, Ordering::AcqRel);
+            run_priority_task(task);
+            PRIORITY_WORKERS_BUSY.fetch_sub(1, Ordering::AcqRel);
+            idle_since = Instant::now();
+        } else if retire_priority_worker(idle_since) {
+            return;
+        } else {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+    PRIORITY_WORKERS.fetch_sub(1, Ordering::AcqRel);
+}
+
+/// Claim a way out for an idle worker, returning true if it should exit
+///
+/// Any worker may go once idle_expired(); the last one to go marks the workers
+/// stopped so the next submission starts them again. Otherwise only workers
+/// above the target may go.
+fn retire_priority_worker(idle_since: Instant) -> bool {
+    if idle_expired(idle_since) {
+        // Decided under the queue lock: a task pushed before this is seen here,
+        // and one pushed after sees the workers stopped and restarts them
+        let queue = PRIORITY_QUEUE.lock();
+        if !queue.is_empty() {
+            return false;
+        }
+        if PRIORITY_WORKERS.fetch_sub(1, Ordering::AcqRel) == 1 {
+            PRIORITY_WORKER_RUNNING.store(false, Ordering::Release);
+        }
+        return true;
+    }
+    PRIORITY_WORKERS
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |live| {
+            (live > PRIORITY_WORKER_TARGET.load(Ordering::Acquire)).then(|| live - 1)
+        })
+        .is_ok()
+}
+
+/// Run one queued priority task and deliver its outcome
+fn run_priority_task(task: PriorityTask) {
+    Python::attach(|py| {
+        let exec_start = Instant::now();
+
+        let func_name = task.func_name.clone();
+
+        if let Some(deadline) = task.deadline.filter(|&deadline| unix_now() > deadline) {
+            let missed_by = unix_now() - deadline;
+            let task_error = TaskError {
+                task_name: func_name.clone(),
+                elapsed_time: task.submitted_at.elapsed().as_secs_f64(),
+                error_message: format!("deadline passed {:.3}s before the task could start", missed_by),
+                error_type: "DeadlineExceeded".to_string(),
+                task_id: task.task_id.clone(),
+                submitted_from: task_submission_site(&task.task_id),
+            };
+            report_task_failure(py, &task.failure_watch, &task_error, None);
+            let outcome = Err(DeadlineExceeded::new_err(format!(
+                "Task {} ({}) missed its deadline by {:.3}s",
+                task.task_id, func_name, missed_by
+            )));
+            if let Err(e) = task.sender.send(clone_outcome(py, &outcome)) {
+                error!("Failed to send priority task result: {}", e);
+            }
+            task.completion.finish(py, outcome);
+            stop_timeout_timer(&task.task_id);
+            clear_task_progress(&task.task_id);
+            return;
+        }
+
+        set_current_task_timeouts(TaskTimeouts::new(
+            task.timeout,
+            task.total_timeout,
+            task.submitted_at,
+        ));
+
+        set_current_task_id(Some(task.task_id.clone()));
+        let interruptible = TaskThreadGuard::register(py, &task.task_id);
+        let cpu_clock = CpuClock::start();
+        let result = task.func
+            .bind(py)
+            .call(task.args.bind(py), task.kwargs.as_ref().map(|k| k.bind(py)));
+        let cpu_ms = cpu_clock.elapsed_ms();
+        task.completion.state.lock().cpu_time = cpu_ms.map(|ms| ms / 1000.0);
+        drop(interruptible);
+        set_current_task_id(None);
+
+        let exec_time = exec_start.elapsed().as_secs_f64() * 1000.0; // Convert to ms
+
+        let overran = exceeded_task_timeout(
+            task.timeout,
+            task.total_timeout,
+            task.submitted_at,
+        );
+        set_current_task_timeouts(TaskTimeouts::default());
+        stop_timeout_timer(&task.task_id);
+
+        let to_send = match (result, overran) {
+            (_, Some(limit)) => {
+                record_task_execution(&func_name, exec_time, false, cpu_ms);
+                let task_error =
+                    timeout_task_error(&func_name, &task.task_id, limit, exec_start);
+                report_task_failure(py, &task.failure_watch, &task_error, Some(limit));
+                Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                    task_error.__str__()
+                ))
+            }
+            (Ok(val), None) => {
+                record_task_execution(&func_name, exec_time, true, cpu_ms);
+                publish_task_event(
+                    "task_completed",
+                    &task.task_id,
+                    &func_name,
+                    completed_event_details(py, &val, exec_time / 1000.0),
+                );
+                Ok(val.unbind())
+            }
+            (Err(e), None) => {
+                record_task_execution(&func_name, exec_time, false, cpu_ms);
+                report_task_failure(
+                    py,
+                    &task.failure_watch,
+                    &TaskError {
+                        task_name: func_name.clone(),
+                        elapsed_time: exec_time / 1000.0,
+                        error_message: e.to_string(),
+                        error_type: task_error_type(py, &e),
+                        task_id: task.task_id.clone(),
+                        submitted_from: task_submission_site(&task.task_id),
+                    },
+                    None,
+                );
+                Err(e)
+            }
+        };
+
+        // CRITICAL FIX: Handle channel send errors
+        if let Err(e) = task.sender.send(clone_outcome(py, &to_send)) {
+            error!("Failed to send priority task result: {}", e);
+        }
+        task.completion.finish(py, to_send);
+        clear_task_progress(&task.task_id);
+    });
+}
+
+/// Run queued priority tasks on this thread until the queue is empty (synchronous fallback)
+fn drain_priority_queue() {
+    loop {
+        let Some(task) = PRIORITY_QUEUE.lock().pop() else {
+            break;
+        };
+        run_priority_task(task);
+    }
+}
+
+/// Stop the priority queue worker
+#[pyfunction]
+fn stop_priority_worker() -> PyResult<()> {
+    PRIORITY_WORKER_RUNNING.store(false, Ordering::Release);
+    Ok(())
+}
+
+/// Change the priority of a task still waiting in the priority queue
+///
+/// Returns False if no queued task has that ID, e.g. because it already started.
+/// Deadlines still order before priorities.
+#[pyfunction]
+fn reprioritize(task_id: &str, priority: i32) -> bool {
+    let classes: Vec<Arc<PriorityClass>> = PRIORITY_CLASSES.lock().classes.values().cloned().collect();
+    std::iter::once(&**PRIORITY_QUEUE)
+        .chain(classes.iter().map(|class| &class.queue))
+        .any(|queue| reprioritize_in(&mut queue.lock(), task_id, priority))
+}
+
+/// Change the priority of `task_id` if it is waiting in `queue`
+fn reprioritize_in(queue: &mut BinaryHeap<PriorityTask>, task_id: &str, priority: i32) -> bool {
+    if !queue.iter().any(|task| task.task_id == task_id) {
+        return false;
+    }
+    // BinaryHeap has no in-place update, so re-heapify with the new priority
+    let mut tasks = std::mem::take(queue).into_vec();
+    for task in tasks.iter_mut().filter(|task| task.task_id == task_id) {
+        task.priority = priority;
+    }
+    *queue = BinaryHeap::from(tasks);
+    true
+}
+
+// =============================================================================
+// PRIORITY CLASSES
+// =============================================================================
+
+/// A priority class: its own queue, served only by the workers reserved for it
+struct PriorityClass {
+    queue: Mutex<BinaryHeap<PriorityTask>>,
+    /// Signalled when a task is queued or the class is retired
+    ready: Condvar,
+    workers: usize,
+    busy: AtomicUsize,
+    /// Set once the class is configured away; its workers drain the queue and exit
+    retired: AtomicBool,
+}
+
+impl PriorityClass {
+    fn retire(&self) {
+        self.retired.store(true, Ordering::Release);
+        self.ready.notify_all();
+    }
+}
+
+#[derive(Default)]
+struct PriorityClasses {
+    classes: HashMap<String, Arc<PriorityClass>>,
+    /// Class of tasks submitted without one; None leaves them to the shared workers
+    default: Option<String>,
+}
+
+static PRIORITY_CLASSES: Lazy<Mutex<PriorityClasses>> = Lazy::new(|| Mutex::new(PriorityClasses::default()));
+
+/// Reserve priority workers for named classes of tasks
+///
+/// `classes` maps each class to its number of workers, e.g. `{"critical": 2, "normal": 6}`.
+/// A task submitted with `priority_class=` waits in its class's queue and runs only on
+/// that class's workers, so a backlog in one class never delays another. Tasks without
+/// a class go to `default`, or to the shared priority workers when it is None.
+/// Configuring again replaces the classes; workers of a replaced class finish the tasks
+/// already queued for it first. `classes=None` removes them all.
+#[pyfunction]
+#[pyo3(signature = (classes, default=None))]
+fn configure_priority_classes(
+    py: Python,
+    classes: Option<HashMap<String, usize>>,
+    default: Option<String>,
+) -> PyResult<()> {
+    let classes = classes.unwrap_or_default();
+    if sync_fallback() && !classes.is_empty() {
+        return Err(pyo3::exceptions::PyRuntimeError::new_err(
+            "priority classes need threads, which are unavailable on this platform",
+        ));
+    }
+    if let Some(name) = classes.iter().find_map(|(name, &workers)| (workers == 0).then_some(name)) {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "priority class {:?} needs at least one worker",
+            name
+        )));
+    }
+    if let Some(ref default) = default {
+        if !classes.contains_key(default) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "default class {:?} is not one of the configured classes",
+                default
+            )));
+        }
+    }
+
+    let generation = RUNTIME_GENERATION.load(Ordering::Acquire);
+    let classes: HashMap<String, Arc<PriorityClass>> = classes
+        .into_iter()
+        .map(|(name, workers)| {
+            let class = PriorityClass {
+                queue: Mutex::new(BinaryHeap::new()),
+                ready: Condvar::new(),
+                workers,
+                busy: AtomicUsize::new(0),
+                retired: AtomicBool::new(false),
+            };
+            (name, Arc::new(class))
+        })
+        .collect();
+    let started: Vec<Arc<PriorityClass>> = classes.values().cloned().collect();
+
+    let mut state = PRIORITY_CLASSES.lock();
+    for class in state.classes.values() {
+        class.retire();
+    }
+    state.classes = classes;
+    state.default = default;
+    drop(state);
+
+    py.detach(|| {
+        for class in started {
+            for _ in 0..class.workers {
+                let class = class.clone();
+                thread::spawn(move || run_priority_class_worker(class, generation));
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Workers, busy workers and queue depth of each priority class
+#[pyfunction]
+fn get_priority_class_stats(py: Python) -> PyResult<Py<PyDict>> {
+    let stats = PyDict::new(py);
+    for (name, class) in PRIORITY_CLASSES.lock().classes.iter() {
+        let class_stats = PyDict::new(py);
+        class_stats.set_item("workers", class.workers)?;
+        class_stats.set_item("busy", class.busy.load(Ordering::Acquire))?;
+        class_stats.set_item("queue_depth", class.queue.lock().len())?;
+        stats.set_item(name, class_stats)?;
+    }
+    Ok(stats.unbind())
+}
+
+/// Check the class a priority task asked for, falling back to the default class
+fn resolve_priority_class(requested: Option<String>) -> PyResult<Option<String>> {
+    let state = PRIORITY_CLASSES.lock();
+    match requested.or_else(|| state.default.clone()) {
+        Some(name) if !state.classes.contains_key(&name) => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "unknown priority class {:?}; set up classes with configure_priority_classes()",
+            name
+        ))),
+        class => Ok(class),
+    }
+}
+
+/// Queue a task for its class's workers
+///
+/// Returns the task when it belongs on the shared queue: it has no class, or its class
+/// was configured away since it was resolved.
+fn enqueue_in_class(class: Option<&str>, task: PriorityTask) -> Option<PriorityTask> {
+    // Under the classes lock, so a class cannot be retired between the lookup and the push
+    let state = PRIORITY_CLASSES.lock();
+    let Some(class) = class.and_then(|name| state.classes.get(name)) else {
+        return Some(task);
+    };
+    class.queue.lock().push(task);
+    class.ready.notify_one();
+    None
+}
+
+/// Run one class's tasks until it is retired and drained, or the runtime is reset
+fn run_priority_class_worker(class: Arc<PriorityClass>, generation: u64) {
+    loop {
+        let task = {
+            let mut queue = class.queue.lock();
+            loop {
+                if RUNTIME_GENERATION.load(Ordering::Acquire) != generation {
+                    return;
+                }
+                if let Some(task) = queue.pop() {
+                    break task;
+                }
+                if class.retired.load(Ordering::Acquire) {
+                    return;
+                }
+                class.ready.wait_for(&mut queue, Duration::from_millis(100));
+            }
+        };
+        class.busy.fetch_add(1, Ordering::AcqRel);
+        run_priority_task(task);
+        class.busy.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+// =============================================================================
+// PRIORITY WORKER AUTOSCALING
+// =============================================================================
+
+/// How often the autoscaler samples the priority queue
+const AUTOSCALE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Bounds and thresholds from configure_autoscaling()
+#[derive(Clone, Debug)]
+struct Autoscaling {
+    min_threads: usize,
+    max_threads: usize,
+    /// Queued tasks per worker above which a worker is added
+    queue_per_thread: usize,
+    /// Queue wait of the oldest task above which a worker is added
+    max_wait: Duration,
+    /// How long the queue must stay quiet before a worker is removed
+    scale_down_after: Duration,
+}
+
+impl Autoscaling {
+    fn new(
+        min_threads: usize,
+        max_threads: usize,
+        queue_per_thread: usize,
+        max_wait: f64,
+        scale_down_after: f64,
+    ) -> PyResult<Autoscaling> {
+        if min_threads == 0 || max_threads < min_threads {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "autoscaling needs 1 <= min_threads <= max_threads"
+            ));
+        }
+        if queue_per_thread == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("queue_per_thread must be at least 1"));
+        }
+        if [max_wait, scale_down_after].iter().any(|secs| !secs.is_finite() || *secs < 0.0) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "max_wait and scale_down_after must be non-negative numbers of seconds"
+            ));
+        }
+        Ok(Autoscaling {
+            min_threads,
+            max_threads,
+            queue_per_thread,
+            max_wait: Duration::from_secs_f64(max_wait),
+            scale_down_after: Duration::from_secs_f64(scale_down_after),
+        })
+    }
+
+    /// Workers wanted next, given the current `target` and a sample of the queue
+    ///
+    /// Grows as soon as either threshold is crossed. Shrinks by one only after the
+    /// queue has stayed under half of both thresholds, as if there were one worker
+    /// fewer, for `scale_down_after`; `quiet_since` tracks that period.
+    fn decide(
+        &self,
+        target: usize,
+        depth: usize,
+        oldest_wait: Duration,
+        quiet_since: &mut Option<Instant>,
+        now: Instant,
+    ) -> usize {
+        let target = target.clamp(self.min_threads, self.max_threads);
+        if depth > self.queue_per_thread * target || oldest_wait > self.max_wait {
+            *quiet_since = None;
+            let wanted = depth.div_ceil(self.queue_per_thread).max(target + 1);
+            return wanted.min(self.max_threads);
+        }
+        let quiet = 2 * depth <= self.queue_per_thread * target.saturating_sub(1) && 2 * oldest_wait <= self.max_wait;
+        if !quiet || target == self.min_threads {
+            *quiet_since = None;
+            return target;
+        }
+        match *quiet_since {
+            Some(since) if now.duration_since(since) >= self.scale_down_after => {
+                *quiet_since = Some(now);
+                target - 1
+            }
+            Some(_) => target,
+            None => {
+                *quiet_since = Some(now);
+                target
+            }
+        }
+    }
+}
+
+/// Autoscaling config, whether its monitor thread is running, and what it has done
+#[derive(Default)]
+struct AutoscalerState {
+    config: Option<Autoscaling>,
+    monitor_running: bool,
+    scale_ups: u64,
+    scale_downs: u64,
+}
+
+static AUTOSCALER: Lazy<Mutex<AutoscalerState>> = Lazy::new(|| Mutex::new(AutoscalerState::default()));
+
+/// Grow and shrink the priority workers with the queue
+///
+/// Every 50 ms the priority queue is sampled. A worker is added while more than
+/// `queue_per_thread` tasks per worker are queued or the oldest has waited longer
+/// than `max_wait` seconds. One is removed after the queue has stayed under half of
+/// both thresholds for `scale_down_after` seconds; removed workers exit once idle.
+/// Call with no `max_threads` to turn autoscaling off and return to the fixed count.
+#[pyfunction]
+#[pyo3(signature = (min_threads=1, max_threads=None, queue_per_thread=2, max_wait=0.1, scale_down_after=5.0))]
+fn configure_autoscaling(
+    py: Python,
+    min_threads: usize,
+    max_threads: Option<usize>,
+    queue_per_thread: usize,
+    max_wait: f64,
+    scale_down_after: f64,
+) -> PyResult<()> {
+    let config = max_threads
+        .map(|max_threads| Autoscaling::new(min_threads, max_threads, queue_per_thread, max_wait, scale_down_after))
+        .transpose()?;
+
+    let mut state = AUTOSCALER.lock();
+    let target = match &config {
+        Some(config) => PRIORITY_WORKER_TARGET.load(Ordering::Acquire).clamp(config.min_threads, config.max_threads),
+        None => priority_worker_count(),
+    };
+    // Without running workers there is nothing to scale; start_priority_worker() starts the monitor
+    let start_monitor = config.is_some()
+        && !state.monitor_running
+        && !sync_fallback()
+        && PRIORITY_WORKER_RUNNING.load(Ordering::Acquire);
+    state.config = config;
+    state.monitor_running |= start_monitor;
+    drop(state);
+
+    if sync_fallback() {
+        return Ok(());
+    }
+    py.detach(|| {
+        PRIORITY_WORKER_TARGET.store(target, Ordering::Release);
+        if PRIORITY_WORKER_RUNNING.load(Ordering::Acquire) {
+            spawn_priority_workers(target.saturating_sub(PRIORITY_WORKERS.load(Ordering::Acquire)));
+        }
+        if start_monitor {
+            thread::spawn(run_autoscaler);
+        }
+    });
+    Ok(())
+}
+
+/// Sample the priority queue and adjust the worker target until autoscaling is turned off
+fn run_autoscaler() {
+    let mut quiet_since = None;
+    loop {
+        thread::sleep(AUTOSCALE_INTERVAL);
+        let mut state = AUTOSCALER.lock();
+        // Stopped or reaped workers: start_priority_worker() brings the autoscaler back
+        let config = match state.config.clone() {
+            Some(config) if PRIORITY_WORKER_RUNNING.load(Ordering::Acquire) => config,
+            _ => {
+                state.monitor_running = false;
+                return;
+            }
+        };
+
+        let (depth, oldest_wait) = {
+            let queue = PRIORITY_QUEUE.lock();
+            let oldest = queue.iter().map(|task| task.submitted_at.elapsed()).max();
+            (queue.len(), oldest.unwrap_or_default())
+        };
+        let target = PRIORITY_WORKER_TARGET.load(Ordering::Acquire);
+        let wanted = config.decide(target, depth, oldest_wait, &mut quiet_since, Instant::now());
+        match wanted.cmp(&target) {
+            CmpOrdering::Greater => state.scale_ups += 1,
+            CmpOrdering::Less => state.scale_downs += 1,
+            CmpOrdering::Equal => {}
+        }
+        drop(state);
+
+        PRIORITY_WORKER_TARGET.store(wanted, Ordering::Release);
+        let live = PRIORITY_WORKERS.load(Ordering::Acquire);
+        if wanted > live {
+            debug!("Autoscaling priority workers {} -> {} (queue depth {})", live, wanted, depth);
+            spawn_priority_workers(wanted - live);
+        }
+    }
+}
+
+/// Autoscaling settings, the current and wanted worker counts, and how often it has scaled
+#[pyfunction]
+fn get_autoscaling_stats(py: Python) -> PyResult<Py<PyDict>> {
+    let state = AUTOSCALER.lock();
+    let stats = PyDict::new(py);
+    stats.set_item("enabled", state.config.is_some())?;
+    stats.set_item("min_threads", state.config.as_ref().map(|c| c.min_threads))?;
+    stats.set_item("max_threads", state.config.as_ref().map(|c| c.max_threads))?;
+    stats.set_item("workers", PRIORITY_WORKERS.load(Ordering::Acquire))?;
+    stats.set_item("target_workers", PRIORITY_WORKER_TARGET.load(Ordering::Acquire))?;
+    stats.set_item("queue_depth", PRIORITY_QUEUE.lock().len())?;
+    stats.set_item("scale_ups", state.scale_ups)?;
+    stats.set_item("scale_downs", state.scale_downs)?;
+    Ok(stats.unbind())
+}
+
+// =============================================================================
+// HUNG TASK WATCHDOG
+// =============================================================================
+
+/// What the watchdog does with a task that runs past `max_runtime`
+enum WatchdogAction {
+    Warn,
+    Cancel,
+    Callback(Py<PyAny>),
+}
+
+/// Settings from configure_watchdog()
+struct WatchdogConfig {
+    max_runtime: Duration,
+    check_interval: Duration,
+    action: WatchdogAction,
+}
+
+#[derive(Default)]
+struct WatchdogState {
+    config: Option<WatchdogConfig>,
+    monitor_running: bool,
+    /// Tasks already reported, so each hung task is acted on once
+    flagged: HashSet<String>,
+    hung_tasks: u64,
+}
+
+static WATCHDOG: Lazy<Mutex<WatchdogState>> = Lazy::new(|| Mutex::new(WatchdogState::default()));
+
+/// Watch for tasks running longer than `max_runtime` seconds
+///
+/// `action="warn"` logs the task and its Python stack, `"cancel"` also cancels it
+/// and raises TaskTimeout in its thread, and `"callback"` calls `callback(info)`
+/// with the task's `task_id`, `name`, `tags`, `elapsed` and `stack` (None when the
+/// stack can't be read). Each hung task is reported once. `max_runtime=None` stops
+/// the watchdog.
+#[pyfunction]
+#[pyo3(signature = (max_runtime=Some(300.0), action="warn", callback=None, check_interval=1.0))]
+fn configure_watchdog(
+    py: Python,
+    max_runtime: Option<f64>,
+    action: &str,
+    callback: Option<Py<PyAny>>,
+    check_interval: f64,
+) -> PyResult<()> {
+    let Some(max_runtime) = max_runtime else {
+        // Dropped outside the lock; the monitor thread notices and exits
+        let old = WATCHDOG.lock().config.take();
+        drop(old);
+        return Ok(());
+    };
+    if !max_runtime.is_finite() || max_runtime <= 0.0 {
+        return Err(pyo3::exceptions::PyValueError::new_err("max_runtime must be a positive number of seconds"));
+    }
+    if !check_interval.is_finite() || check_interval <= 0.0 {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "check_interval must be a positive number of seconds",
+        ));
+    }
+    let action = match (action, callback) {
+        ("warn", None) => WatchdogAction::Warn,
+        ("cancel", None) => WatchdogAction::Cancel,
+        ("callback", Some(callback)) if callback.bind(py).is_callable() => WatchdogAction::Callback(callback),
+        ("callback", _) => {
+            return Err(pyo3::exceptions::PyTypeError::new_err("action=\"callback\" needs a callable callback"))
+        }
+        ("warn" | "cancel", Some(_)) => {
+            return Err(pyo3::exceptions::PyValueError::new_err("callback is only used with action=\"callback\""))
+        }
+        (other, _) => {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "action must be \"warn\", \"cancel\" or \"callback\", got {:?}",
+                other
+            )))
+        }
+    };
+
+    let config = WatchdogConfig {
+        max_runtime: Duration::from_secs_f64(max_runtime),
+        check_interval: Duration::from_secs_f64(check_interval),
+        action,
+    };
+    let mut state = WATCHDOG.lock();
+    let old = state.config.replace(config);
+    let start_monitor = !state.monitor_running && !sync_fallback();
+    state.monitor_running |= start_monitor;
+    drop(state);
+    drop(old);
+    if start_monitor {
+        thread::spawn(run_watchdog);
+    }
+    Ok(())
+}
+
+/// Number of tasks the watchdog has found running past `max_runtime`
+#[pyfunction]
+fn get_watchdog_stats(py: Python) -> PyResult<Py<PyDict>> {
+    let state = WATCHDOG.lock();
+    let dict = PyDict::new(py);
+    dict.set_item("enabled", state.config.is_some())?;
+    dict.set_item("max_runtime", state.config.as_ref().map(|config| config.max_runtime.as_secs_f64()))?;
+    dict.set_item("hung_tasks", state.hung_tasks)?;
+    Ok(dict.unbind())
+}
+
+/// Python stack of the thread running `task_id`, if it is inside the task's function
+fn task_stack(py: Python, task_id: &str) -> Option<String> {
+    let ident = *TASK_THREADS.lock().get(task_id)?;
+    let frame = py.import("sys").ok()?.call_method0("_current_frames").ok()?.get_item(ident).ok()?;
+    let lines = py.import("traceback").ok()?.call_method1("format_stack", (frame,)).ok()?;
+    lines.extract::<Vec<String>>().ok().map(|lines| lines.concat())
+}
+
+fn run_watchdog() {
+    loop {
+        let interval = match WATCHDOG.lock().config {
+            Some(ref config) => config.check_interval,
+            None => Duration::ZERO,
+        };
+        thread::sleep(interval);
+
+        let active: Vec<ActiveTask> = ACTIVE_TASKS.lock().clone();
+        let mut state = WATCHDOG.lock();
+        let Some(max_runtime) = state.config.as_ref().map(|config| config.max_runtime) else {
+            state.monitor_running = false;
+            state.flagged.clear();
+            return;
+        };
+        state.flagged.retain(|task_id| active.iter().any(|task| &task.task_id == task_id));
+        let hung: Vec<ActiveTask> = active
+            .into_iter()
+            .filter(|task| task.started_at.elapsed() > max_runtime && !*task.is_complete.lock())
+            .filter(|task| !state.flagged.contains(&task.task_id))
+            .collect();
+        if hung.is_empty() {
+            continue;
+        }
+        state.hung_tasks += hung.len() as u64;
+        state.flagged.extend(hung.iter().map(|task| task.task_id.clone()));
+        drop(state);
+
+        Python::attach(|py| {
+            for task in &hung {
+                report_hung_task(py, task);
+            }
+        });
+    }
+}
+
+/// Apply the configured action to one hung task
+fn report_hung_task(py: Python, task: &ActiveTask) {
+    let stack = task_stack(py, &task.task_id);
+    let elapsed = task.started_at.elapsed().as_secs_f64();
+    publish_task_event("task_hung", &task.task_id, &task.func_name, serde_json::json!({ "elapsed": elapsed }));
+
+    // Taken out of the lock so the callback may reconfigure the watchdog
+    let callback = match WATCHDOG.lock().config.as_ref().map(|config| &config.action) {
+        Some(WatchdogAction::Callback(callback)) => Some(callback.clone_ref(py)),
+        Some(WatchdogAction::Warn | WatchdogAction::Cancel) => None,
+        None => return,
+    };
+    if let Some(callback) = callback {
+        let info = task.to_dict(py).and_then(|info| {
+            info.set_item("stack", stack.as_deref())?;
+            callback.bind(py).call1((info,))
+        });
+        if let Err(e) = info {
+            warn!("Watchdog callback failed for task {}: {}", task.task_id, e);
+        }
+        return;
+    }
+
+    let stack = stack.as_deref().unwrap_or("    (stack not available)\n");
+    warn!(
+        "Task {} ({}) has been running for {:.1}s\n{}",
+        task.task_id, task.func_name, elapsed, stack
+    );
+    let cancel = matches!(WATCHDOG.lock().config.as_ref().map(|config| &config.action), Some(WatchdogAction::Cancel));
+    if cancel && task.cancel() {
+        interrupt_task_thread(&task.task_id);
+    }
+}
+
+// =============================================================================
+// CONTROL SOCKET
+// =============================================================================
+
+/// Set while pause_scheduling() holds back new task starts
+static SCHEDULING_PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Hold back new task starts until resume_scheduling()
+///
+/// Submissions block as they do under backpressure; tasks already running are not affected.
+#[pyfunction]
+fn pause_scheduling() {
+    if !SCHEDULING_PAUSED.swap(true, Ordering::AcqRel) {
+        warn!("Task scheduling paused");
+    }
+}
+
+/// Let submissions held back by pause_scheduling() start
+#[pyfunction]
+fn resume_scheduling() {
+    if SCHEDULING_PAUSED.swap(false, Ordering::AcqRel) {
+        warn!("Task scheduling resumed");
+    }
+}
+
+/// Wait while scheduling is paused; a pause lasts until resumed, so there is no timeout
+fn wait_while_paused() {
+    while SCHEDULING_PAUSED.load(Ordering::Acquire) {
+        if is_shutdown_requested() {
+            warn!("wait_while_paused cancelled: shutdown in progress");
+            return;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Socket a control server accepts connections on
+enum ControlListener {
+    Tcp(std::net::TcpListener),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener),
+}
+
+/// The running control server
+struct ControlServer {
+    address: String,
+    stop: Arc<AtomicBool>,
+    /// Unix socket file to remove when the server stops
+    socket_path: Option<PathBuf>,
+}
+
+static CONTROL_SERVER: Lazy<Mutex<Option<ControlServer>>> = Lazy::new(|| Mutex::new(None));
+
+/// A socket in the temp directory named after the process id, or any free loopback port
+fn default_control_address() -> String {
+    if cfg!(unix) {
+        std::env::temp_dir().join(format!("makeparallel-{}.sock", std::process::id())).display().to_string()
+    } else {
+        "127.0.0.1:0".to_string()
+    }
+}
+
+/// Accept commands from `python -m makeparallel ctl` on a local socket
+///
+/// `address` is a Unix socket path or a loopback "host:port"; by default a socket in
+/// the temp directory named after the process id (a free loopback port on Windows).
+/// Requests and replies are JSON objects, one per line. Connecting requires no
+/// credentials, so Unix sockets are made owner-only and TCP is limited to loopback.
+/// Returns the address listened on.
+#[pyfunction]
+#[pyo3(signature = (address=None))]
+fn start_control_server(address: Option<String>) -> PyResult<String> {
+    if sync_fallback() {
+        return Err(pyo3::exceptions::PyRuntimeError::new_err(
+            "the control server needs threads, which are unavailable on this platform",
+        ));
+    }
+    let mut server = CONTROL_SERVER.lock();
+    if let Some(ref running) = *server {
+        return Err(pyo3::exceptions::PyRuntimeError::new_err(format!(
+            "control server already listening on {}",
+            running.address
+        )));
+    }
+    let (listener, address, socket_path) = bind_control_listener(&address.unwrap_or_else(default_control_address))?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_flag = stop.clone();
+    thread::spawn(move || serve_control(listener, stop_flag));
+    *server = Some(ControlServer { address: address.clone(), stop, socket_path });
+    Ok(address)
+}
+
+/// Stop the control server, returning whether one was running
+///
+/// Connections already open are served until the client closes them.
+#[pyfunction]
+fn stop_control_server() -> bool {
+    let Some(server) = CONTROL_SERVER.lock().take() else {
+        return false;
+    };
+    server.stop.store(true, Ordering::Release);
+    if let Some(path) = server.socket_path {
+        let _ = std::fs::remove_file(path);
+    }
+    true
+}
+
+fn bind_control_listener(address: &str) -> PyResult<(ControlListener, String, Option<PathBuf>)> {
+    if let Ok(addr) = address.parse::<std::net::SocketAddr>() {
+        if !addr.ip().is_loopback() {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "control server must listen on a loopback address, got {}",
+                addr
+            )));
+        }
+        let listener = std::net::TcpListener::bind(addr)?;
+        let bound = listener.local_addr()?.to_string();
+        return Ok((ControlListener::Tcp(listener), bound, None));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+        let path = PathBuf::from(address);
+        // A socket left by a process that died without stopping its server refuses connections
+        let stale = std::fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_socket())
+            && std::os::unix::net::UnixStream::connect(&path).is_err();
+        if stale {
+            let _ = std::fs::remove_file(&path);
+        }
+        // Bound inside a new owner-only directory and restricted before being linked to
+        // `path`, so other users can never connect; the link fails if `path` is taken
+        let parent = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(std::path::Path::new("."));
+        let staging = new_private_dir(parent, ".makeparallel-ctl")?;
+        let staged = staging.join("s");
+        let bound = std::os::unix::net::UnixListener::bind(&staged).and_then(|listener| {
+            std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+            std::fs::hard_link(&staged, &path)?;
+            Ok(listener)
+        });
+        let _ = std::fs::remove_dir_all(&staging);
+        Ok((ControlListener::Unix(bound?), address.to_string(), Some(path)))
+    }
+    #[cfg(not(unix))]
+    Err(pyo3::exceptions::PyValueError::new_err(format!(
+        "control server address must be \"host:port\", got {:?}",
+        address
+    )))
+}
+
+fn serve_control(listener: ControlListener, stop: Arc<AtomicBool>) {
+    // Polled so stop_control_server() takes effect without a connection to wake accept()
+    let nonblocking = match &listener {
+        ControlListener::Tcp(listener) => listener.set_nonblocking(true),
+        #[cfg(unix)]
+        ControlListener::Unix(listener) => listener.set_nonblocking(true),
+    };
+    if let Err(e) = nonblocking {
+        error!("Control server could not start: {}", e);
+        return;
+    }
+
+    while !stop.load(Ordering::Acquire) {
+        // Accepted sockets may inherit non-blocking mode on some platforms
+        let accepted = match &listener {
+            ControlListener::Tcp(listener) => listener.accept().and_then(|(stream, _)| {
+                stream.set_nonblocking(false)?;
+                spawn_control_connection(stream.try_clone()?, stream)
+            }),
+            #[cfg(unix)]
+            ControlListener::Unix(listener) => listener.accept().and_then(|(stream, _)| {
+                stream.set_nonblocking(false)?;
+                spawn_control_connection(stream.try_clone()?, stream)
+            }),
+        };
+        match accepted {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(100)),
+            Err(e) => {
+                warn!("Control server failed to accept a connection: {}", e);
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+}
+
+fn spawn_control_connection<S>(reader: S, mut writer: S) -> std::io::Result<()>
+where
+    S: std::io::Read + std::io::Write + Send + 'static,
+{
+    use std::io::BufRead;
+    thread::spawn(move || {
+        for line in std::io::BufReader::new(reader).lines() {
+            let Ok(line) = line else {
+                return;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let reply = match serde_json::from_str(&line) {
+                Ok(request) => control_command(&request),
+                Err(e) => serde_json::json!({ "ok": false, "error": format!("invalid request: {}", e) }),
+            };
+            if writeln!(writer, "{}", reply).is_err() {
+                return;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Run one control request and build its reply
+///
+/// Commands: `list`, `cancel` with a `task_id` or a `tag`, `pause`, `resume` and `metrics`.
+fn control_command(request: &serde_json::Value) -> serde_json::Value {
+    let field = |name: &str| request.get(name).and_then(|value| value.as_str());
+    let failure = |message: String| serde_json::json!({ "ok": false, "error": message });
+
+    match field("command") {
+        Some("list") => {
+            let tasks: Vec<serde_json::Value> = ACTIVE_TASKS
+                .lock()
+                .iter()
+                .map(|task| {
+                    serde_json::json!({
+                        "task_id": task.task_id,
+                        "name": task.func_name,
+                        "tags": task.tags,
+                        "state": task.state(),
+                        "elapsed": task.started_at.elapsed().as_secs_f64(),
+                        "submitted_from": task.submitted_from,
+                    })
+                })
+                .collect();
+            serde_json::json!({ "ok": true, "tasks": tasks, "paused": SCHEDULING_PAUSED.load(Ordering::Acquire) })
+        }
+        Some("cancel") => {
+            let (task_id, tag) = (field("task_id"), field("tag"));
+            if task_id.is_some() == tag.is_some() {
+                return failure("cancel needs either a task_id or a tag".to_string());
+            }
+            let mut cancelled = 0;
+            let mut already_finishing = 0;
+            let wanted = |task: &&ActiveTask| {
+                Some(task.task_id.as_str()) == task_id || task.tags.iter().any(|t| Some(t.as_str()) == tag)
+            };
+            for task in ACTIVE_TASKS.lock().iter().filter(wanted) {
+                if task.cancel() {
+                    cancelled += 1;
+                } else {
+                    already_finishing += 1;
+                }
+            }
+            serde_json::json!({ "ok": true, "cancelled": cancelled, "already_finishing": already_finishing })
+        }
+        Some("pause") => match field("task_id") {
+            Some(task_id) => serde_json::json!({ "ok": true, "task_id": task_id, "found": pause_task(task_id) }),
+            None => {
+                pause_scheduling();
+                serde_json::json!({ "ok": true, "paused": true })
+            }
+        },
+        Some("resume") => match field("task_id") {
+            Some(task_id) => serde_json::json!({ "ok": true, "task_id": task_id, "found": resume_task(task_id) }),
+            None => {
+                resume_scheduling();
+                serde_json::json!({ "ok": true, "paused": false })
+            }
+        },
+        Some("metrics") => match export_metrics("json").map(|text| serde_json::from_str::<serde_json::Value>(&text)) {
+            Ok(Ok(metrics)) => serde_json::json!({ "ok": true, "metrics": metrics }),
+            _ => failure("metrics could not be exported".to_string()),
+        },
+        Some(other) => failure(format!(
+            "unknown command {:?}, expected list, cancel, pause, resume or metrics",
+            other
+        )),
+        None => failure("request needs a \"command\"".to_string()),
+    }
+}
+
+// =============================================================================
+// IDLE THREAD REAPING
+// =============================================================================
+
+/// How long idle background threads wait for work before exiting; None keeps them
+static IDLE_TIMEOUT: Lazy<Mutex<Option<Duration>>> = Lazy::new(|| Mutex::new(None));
+
+/// When shrink_pools() last asked idle threads to exit
+static SHRINK_REQUESTED: Lazy<Mutex<Option<Instant>>> = Lazy::new(|| Mutex::new(None));
+
+/// Whether a thread idle since `idle_since` should exit: past the idle timeout, or asked to by shrink_pools()
+fn idle_expired(idle_since: Instant) -> bool {
+    if IDLE_TIMEOUT.lock().is_some_and(|timeout| idle_since.elapsed() >= timeout) {
+        return true;
+    }
+    SHRINK_REQUESTED.lock().is_some_and(|requested| requested >= idle_since)
+}
+
+/// Let background threads exit after `seconds` without work; None (the default) keeps them
+///
+/// Applies to the priority workers, named Pool threads, the autoscalers watching
+/// them, and the event delivery thread. Each is started again when work arrives.
+#[pyfunction]
+#[pyo3(signature = (seconds=None))]
+fn configure_idle_timeout(seconds: Option<f64>) -> PyResult<()> {
+    if seconds.is_some_and(|secs| !secs.is_finite() || secs < 0.0) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "idle timeout must be a non-negative number of seconds"
+        ));
+    }
+    *IDLE_TIMEOUT.lock() = seconds.map(Duration::from_secs_f64);
+    Ok(())
+}
+
+/// Background threads of the priority queue and named pools, and how many are running a task
+fn worker_thread_counts() -> (usize, usize) {
+    let pools: Vec<Arc<WorkerPool>> = POOLS.iter().map(|pool| pool.value().clone()).collect();
+    let live = PRIORITY_WORKERS.load(Ordering::Acquire)
+        + pools.iter().map(|pool| pool.live.load(Ordering::Acquire)).sum::<usize>();
+    let busy = PRIORITY_WORKERS_BUSY.load(Ordering::Acquire)
+        + pools.iter().map(|pool| pool.running.load(Ordering::Acquire)).sum::<usize>();
+    (live, busy)
+}
+
+/// Make idle worker threads exit now, returning how many did
+///
+/// Idle priority workers and named Pool threads exit, as does the event delivery
+/// thread if nothing is waiting to be sent. Busy threads finish their task and
+/// keep going. Everything is started again when work arrives. Waits up to a second
+/// for the idle threads to go.
+#[pyfunction]
+fn shrink_pools(py: Python) -> usize {
+    py.detach(|| {
+        let (before, _) = worker_thread_counts();
+        *SHRINK_REQUESTED.lock() = Some(Instant::now());
+        {
+            // Dropping the sender lets the delivery thread drain and exit
+            let mut events = EVENT_QUEUE.lock();
+            if events.as_ref().is_some_and(|sender| sender.is_empty()) {
+                events.take();
+            }
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        loop {
+            let (live, busy) = worker_thread_counts();
+            if live <= busy || Instant::now() >= deadline {
+                return before.saturating_sub(live);
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    })
+}
+
+// =============================================================================
+// MODULE LIFECYCLE
+// =============================================================================
+
+/// Whether module init has already run in this process
+static MODULE_INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Bumped by every runtime reset; long-lived workers exit once it moves past
+/// the value they started under
+static RUNTIME_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Release a Python object held in global state
+///
+/// Without an interpreter (it was finalized) the object is leaked instead:
+/// decrementing its refcount would touch freed memory.
+fn release_py<T>(py: Option<Python>, obj: T) {
+    match py {
+        Some(_) => drop(obj),
+        None => std::mem::forget(obj),
+    }
+}
+
+/// Empty a map of Python objects, releasing them with release_py()
+fn release_py_map<V>(py: Option<Python>, map: &DashMap<String, V>) {
+    let keys: Vec<String> = map.iter().map(|entry| entry.key().clone()).collect();
+    for key in keys {
+        if let Some((_, value)) = map.remove(&key) {
+            release_py(py, value);
+        }
+    }
+}
+
+/// Return the scheduler to a fresh state; returns the number of queued tasks discarded
+///
+/// Priority workers from before the reset exit after their current task, queued
+/// priority tasks fail, and the shutdown flag is cleared. With `py` None the previous
+/// interpreter is gone, so everything it owned is dropped without touching it.
+fn reset_runtime(py: Option<Python>) -> usize {
+    RUNTIME_GENERATION.fetch_add(1, Ordering::AcqRel);
+    PRIORITY_WORKER_RUNNING.store(false, Ordering::Release);
+    {
+        let mut autoscaler = AUTOSCALER.lock();
+        autoscaler.config = None;
+        autoscaler.scale_ups = 0;
+        autoscaler.scale_downs = 0;
+    }
+    {
+        let mut watchdog = WATCHDOG.lock();
+        watchdog.hung_tasks = 0;
+        release_py(py, watchdog.config.take());
+    }
+    if let Some(server) = CONTROL_SERVER.lock().take() {
+        server.stop.store(true, Ordering::Release);
+    }
+    SCHEDULING_PAUSED.store(false, Ordering::Release);
+
+    let mut queued: Vec<PriorityTask> = PRIORITY_QUEUE.lock().drain().collect();
+    for class in std::mem::take(&mut *PRIORITY_CLASSES.lock()).classes.into_values() {
+        class.retire();
+        queued.extend(class.queue.lock().drain());
+    }
+    let discarded = queued.len();
+    for task in queued {
+        match py {
+            Some(py) => {
+                let outcome = Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                    "Task discarded: makeparallel was reinitialized before it started"
+                ));
+                let _ = task.sender.send(clone_outcome(py, &outcome));
+                task.completion.finish(py, outcome);
+                stop_timeout_timer(&task.task_id);
+                unregister_task(&task.task_id);
+            }
+            None => std::mem::forget(task),
+        }
+    }
+
+    if let Some(pool) = DEFAULT_PROCESS_POOL.lock().take() {
+        match py {
+            Some(py) => pool.borrow(py).shutdown(py, false),
+            None => std::mem::forget(pool),
+        }
+    }
+    // Named pools are closed; calls already queued still run unless the interpreter is gone
+    let pools: Vec<Arc<WorkerPool>> = POOLS.iter().map(|entry| entry.value().clone()).collect();
+    for pool in pools {
+        pool.abandoned.store(py.is_none(), Ordering::Release);
+        pool.close();
+    }
+    release_py_map(py, &TASK_PROGRESS_CALLBACKS);
+    release_py_map(py, &TASK_STREAMS);
+    match py {
+        Some(py) => close_all_resources(py),
+        None => {
+            std::mem::forget(take_worker_resources(|_, _| true));
+            release_py_map(None, &GLOBAL_RESOURCES);
+        }
+    }
+    TASK_DEPENDENCIES.clear();
+    TASK_CHECKPOINT_KEYS.clear();
+    PAUSED_TASKS.lock().clear();
+    TASKS_RESUMED.notify_all();
+
+    if py.is_none() {
+        // Tasks and handlers of the finalized interpreter can never complete or run
+        ACTIVE_TASKS.lock().clear();
+        TASK_THREADS.lock().clear();
+        TASK_STARTS.lock().clear();
+        TASK_PROGRESS_MAP.clear();
+        MEMO_CACHES.lock().clear();
+        for (_, entries) in LOADED_MEMO_ENTRIES.lock().drain() {
+            std::mem::forget(entries);
+        }
+        if let Some(handler) = GLOBAL_ERROR_HANDLER.lock().take() {
+            std::mem::forget(handler);
+        }
+        if let Some(pending) = PENDING.lock().take() {
+            std::mem::forget(pending);
+        }
+        for provider in PROVIDERS.lock().drain() {
+            std::mem::forget(provider);
+        }
+    }
+
+    SHUTDOWN_FLAG.store(false, Ordering::Release);
+    discarded
+}
+
+/// Reset scheduler state left over from before `importlib.reload()` or a shutdown()
+///
+/// Stops the priority workers (they restart on the next submission), fails tasks
+/// still queued for them, shuts down the default process pool and resets the
+/// shutdown flag. Configuration such as limits, webhooks and the global error
+/// handler is kept. Returns the number of queued tasks discarded.
+#[pyfunction]
+fn _reinitialize(py: Python) -> usize {
+    let discarded = reset_runtime(Some(py));
+    FREE_THREADED.store(detect_free_threading(py), Ordering::Relaxed);
+    discarded
+}
+
+// =============================================================================
+// PERFORMANCE PROFILING
+// =============================================================================
+
+/// Performance metrics
+#[pyclass]
+#[derive(Clone, Default)]
+struct PerformanceMetrics {
+    #[pyo3(get)]
+    total_tasks: u64,
+    #[pyo3(get)]
+    completed_tasks: u64,
+    #[pyo3(get)]
+    failed_tasks: u64,
+    #[pyo3(get)]
+    total_execution_time_ms: f64,
+    #[pyo3(get)]
+    average_execution_time_ms: f64,
+    /// CPU time the tasks' threads used; well below execution time means waiting (I/O, the GIL)
+    #[pyo3(get)]
+    total_cpu_time_ms: f64,
+    #[pyo3(get)]
+    average_cpu_time_ms: f64,
+    /// Tasks whose CPU time could be measured
+    cpu_timed_tasks: u64,
+    /// Admission counts when the function is decorated with @bulkhead
+    #[pyo3(get)]
+    bulkhead: Option<BulkheadStats>,
+}
+
+/// Global metrics tracker
+static METRICS: Lazy<Arc<Mutex<HashMap<String, PerformanceMetrics>>>> =
+    Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
+
+static TASK_COUNTER: Lazy<Arc<AtomicU64>> = Lazy::new(|| Arc::new(AtomicU64::new(0)));
+static COMPLETED_COUNTER: Lazy<Arc<AtomicU64>> = Lazy::new(|| Arc::new(AtomicU64::new(0)));
+static FAILED_COUNTER: Lazy<Arc<AtomicU64>> = Lazy::new(|| Arc::new(AtomicU64::new(0)));
+
+/// Reads the CPU time the calling thread spends on a task
+struct CpuClock(Option<Duration>);
+
+impl CpuClock {
+    fn start() -> CpuClock {
+        CpuClock(platform::current_thread_cpu_time())
+    }
+
+    /// Milliseconds of CPU used since start(), None where threads have no CPU clock
+    fn elapsed_ms(&self) -> Option<f64> {
+        let now = platform::current_thread_cpu_time()?;
+        Some(now.saturating_sub(self.0?).as_secs_f64() * 1000.0)
+    }
+}
+
+/// Record task execution, with its CPU time when measured
+fn record_task_execution(name: &str, duration_ms: f64, success: bool, cpu_ms: Option<f64>) {
+    TASK_COUNTER.fetch_add(1, Ordering::Relaxed);
+    observe_task_outcome(duration_ms, success);
+
+    if success {
+        COMPLETED_COUNTER.fetch_add(1, Ordering::Relaxed);
+    } else {
+        FAILED_COUNTER.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let mut metrics = METRICS.lock();
+    let entry = metrics.entry(name.to_string()).or_default();
+
+    entry.total_tasks += 1;
+    if success {
+        entry.completed_tasks += 1;
+    } else {
+        entry.failed_tasks += 1;
+    }
+    entry.total_execution_time_ms += duration_ms;
+    entry.average_execution_time_ms = entry.total_execution_time_ms / entry.total_tasks as f64;
+    if let Some(cpu_ms) = cpu_ms {
+        entry.cpu_timed_tasks += 1;
+        entry.total_cpu_time_ms += cpu_ms;
+        entry.average_cpu_time_ms = entry.total_cpu_time_ms / entry.cpu_timed_tasks as f64;
+    }
+}
+
+/// Metrics of one function, including its bulkhead counts
+fn function_metrics(name: &str) -> Option<PerformanceMetrics> {
+    let bulkhead = BULKHEADS.get(name).map(|b| b.stats.lock().clone());
+    let metrics = METRICS.lock().get(name).cloned();
+    if metrics.is_none() && bulkhead.is_none() {
+        return None;
+    }
+    Some(PerformanceMetrics { bulkhead, ..metrics.unwrap_or_default() })
+}
+
+/// Metrics of every function that has run or has a bulkhead, by name
+fn all_function_metrics() -> Vec<(String, PerformanceMetrics)> {
+    let mut names: Vec<String> = METRICS.lock().keys().cloned().collect();
+    names.extend(BULKHEADS.iter().map(|b| b.key().clone()));
+    names.sort();
+    names.dedup();
+    names.into_iter().filter_map(|name| function_metrics(&name).map(|m| (name, m))).collect()
+}
+
+/// Get performance metrics for a specific function
+#[pyfunction]
+fn get_metrics(name: String) -> PyResult<Option<PerformanceMetrics>> {
+    Ok(function_metrics(&name))
+}
+
+/// Get all performance metrics
+#[pyfunction]
+fn get_all_metrics(py: Python) -> PyResult<Py<PyDict>> {
+    let dict = PyDict::new(py);
+    for (name, metric) in all_function_metrics() {
+        let metric_dict = PyDict::new(py);
+        metric_dict.set_item("total_tasks", metric.total_tasks)?;
+        metric_dict.set_item("completed_tasks", metric.completed_tasks)?;
+        metric_dict.set_item("failed_tasks", metric.failed_tasks)?;
+        metric_dict.set_item("total_execution_time_ms", metric.total_execution_time_ms)?;
+        metric_dict.set_item("average_execution_time_ms", metric.average_execution_time_ms)?;
+        metric_dict.set_item("total_cpu_time_ms", metric.total_cpu_time_ms)?;
+        metric_dict.set_item("average_cpu_time_ms", metric.average_cpu_time_ms)?;
+        if let Some(stats) = metric.bulkhead {
+            metric_dict.set_item("bulkhead", stats.to_dict(py)?)?;
+        }
+        dict.set_item(name.as_str(), metric_dict)?;
+    }
+
+    dict.set_item("_global_total", TASK_COUNTER.load(Ordering::SeqCst))?;
+    dict.set_item("_global_completed", COMPLETED_COUNTER.load(Ordering::SeqCst))?;
+    dict.set_item("_global_failed", FAILED_COUNTER.load(Ordering::SeqCst))?;
+
+    Ok(dict.unbind())
+}
+
+/// Reset all metrics
+#[pyfunction]
+fn reset_metrics() -> PyResult<()> {
+    METRICS.lock().clear();
+    for bulkhead in BULKHEADS.iter() {
+        let mut stats = bulkhead.stats.lock();
+        stats.accepted = 0;
+        stats.rejected = 0;
+    }
+    TASK_COUNTER.store(0, Ordering::SeqCst);
+    COMPLETED_COUNTER.store(0, Ordering::SeqCst);
+    FAILED_COUNTER.store(0, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Static labels attached to every exported series, in the order they were given
+static METRIC_LABELS: Lazy<Mutex<Vec<(String, String)>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Set the labels (service, host, pid, version, ...) attached to exported metrics
+///
+/// Replaces any previous labels; pass an empty dict to clear them. Values are
+/// converted with str(). Names must be valid Prometheus label names.
+#[pyfunction]
+fn set_metric_labels(labels: &Bound<'_, PyDict>) -> PyResult<()> {
+    let mut parsed = Vec::with_capacity(labels.len());
+    for (key, value) in labels.iter() {
+        let key: String = key.extract()?;
+        let valid = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !key.starts_with("__");
+        if !valid {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!("invalid metric label name {:?}", key)));
+        }
+        if key == "function" || key == "outcome" {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "metric label {:?} is reserved for makeparallel's own series",
+                key
+            )));
+        }
+        parsed.push((key, value.str()?.to_cow()?.into_owned()));
+    }
+    *METRIC_LABELS.lock() = parsed;
+    Ok(())
+}
+
+/// Labels set by set_metric_labels()
+#[pyfunction]
+fn get_metric_labels(py: Python) -> PyResult<Py<PyDict>> {
+    let dict = PyDict::new(py);
+    for (key, value) in METRIC_LABELS.lock().iter() {
+        dict.set_item(key, value)?;
+    }
+    Ok(dict.unbind())
+}
+
+/// One Prometheus series: its own labels and its value
+type PrometheusSample<'a> = (Vec<(&'a str, &'a str)>, f64);
+
+/// Append a Prometheus metric family, adding the static labels to every sample
+fn prometheus_family(out: &mut String, metric: &str, kind: &str, help: &str, samples: &[PrometheusSample]) {
+    let labels = METRIC_LABELS.lock();
+    out.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", metric, help, metric, kind));
+    for (own, value) in samples {
+        let pairs: Vec<String> = own
+            .iter()
+            .copied()
+            .chain(labels.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+            .map(|(k, v)| {
+                format!("{}=\"{}\"", k, v.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+            })
+            .collect();
+        out.push_str(&format!("{}{{{}}} {}\n", metric, pairs.join(","), value));
+    }
+}
+
+/// Export all metrics as text, with the labels from set_metric_labels()
+///
+/// `format="prometheus"` gives the text exposition format, ready to serve from a
+/// /metrics endpoint; `format="json"` gives the get_all_metrics() data as JSON.
+#[pyfunction]
+#[pyo3(signature = (format="prometheus"))]
+fn export_metrics(format: &str) -> PyResult<String> {
+    let functions = all_function_metrics();
+    match format {
+        "prometheus" => {
+            let mut out = String::new();
+            let outcomes: Vec<PrometheusSample> = functions
+                .iter()
+                .flat_map(|(name, m)| {
+                    [
+                        (vec![("function", name.as_str()), ("outcome", "completed")], m.completed_tasks as f64),
+                        (vec![("function", name.as_str()), ("outcome", "failed")], m.failed_tasks as f64),
+                    ]
+                })
+                .collect();
+            prometheus_family(&mut out, "makeparallel_tasks_total", "counter", "Tasks finished, by outcome", &outcomes);
+            let seconds: Vec<PrometheusSample> = functions
+                .iter()
+                .map(|(name, m)| (vec![("function", name.as_str())], m.total_execution_time_ms / 1000.0))
+                .collect();
+            prometheus_family(
+                &mut out,
+                "makeparallel_task_execution_seconds_total",
+                "counter",
+                "Time spent running tasks",
+                &seconds,
+            );
+            let cpu_seconds: Vec<PrometheusSample> = functions
+                .iter()
+                .map(|(name, m)| (vec![("function", name.as_str())], m.total_cpu_time_ms / 1000.0))
+                .collect();
+            prometheus_family(
+                &mut out,
+                "makeparallel_task_cpu_seconds_total",
+                "counter",
+                "CPU time used by task threads while running tasks",
+                &cpu_seconds,
+            );
+
+            let bulkheads: Vec<_> =
+                functions.iter().filter_map(|(name, m)| m.bulkhead.as_ref().map(|b| (name.as_str(), b))).collect();
+            if !bulkheads.is_empty() {
+                let gauge = |read: fn(&BulkheadStats) -> f64| -> Vec<PrometheusSample> {
+                    bulkheads.iter().map(|(name, stats)| (vec![("function", *name)], read(stats))).collect()
+                };
+                prometheus_family(
+                    &mut out,
+                    "makeparallel_bulkhead_running",
+                    "gauge",
+                    "Calls running inside a bulkhead",
+                    &gauge(|b| b.running as f64),
+                );
+                prometheus_family(
+                    &mut out,
+                    "makeparallel_bulkhead_queued",
+                    "gauge",
+                    "Calls waiting for a bulkhead slot",
+                    &gauge(|b| b.queued as f64),
+                );
+                prometheus_family(
+                    &mut out,
+                    "makeparallel_bulkhead_rejected_total",
+                    "counter",
+                    "Calls rejected by a full bulkhead",
+                    &gauge(|b| b.rejected as f64),
+                );
+            }
+            Ok(out)
+        }
+        "json" => {
+            let mut metrics = serde_json::Map::new();
+            for (name, metric) in functions {
+                let mut entry = serde_json::json!({
+                    "total_tasks": metric.total_tasks,
+                    "completed_tasks": metric.completed_tasks,
+                    "failed_tasks": metric.failed_tasks,
+                    "total_execution_time_ms": metric.total_execution_time_ms,
+                    "average_execution_time_ms": metric.average_execution_time_ms,
+                    "total_cpu_time_ms": metric.total_cpu_time_ms,
+                    "average_cpu_time_ms": metric.average_cpu_time_ms,
+                });
+                if let Some(stats) = metric.bulkhead {
+                    entry["bulkhead"] = serde_json::json!({
+                        "max_concurrent": stats.max_concurrent,
+                        "max_queue": stats.max_queue,
+                        "running": stats.running,
+                        "queued": stats.queued,
+                        "accepted": stats.accepted,
+                        "rejected": stats.rejected,
+                    });
+                }
+                metrics.insert(name, entry);
+            }
+            metrics.insert("_global_total".to_string(), TASK_COUNTER.load(Ordering::SeqCst).into());
+            metrics.insert("_global_completed".to_string(), COMPLETED_COUNTER.load(Ordering::SeqCst).into());
+            metrics.insert("_global_failed".to_string(), FAILED_COUNTER.load(Ordering::SeqCst).into());
+            Ok(serde_json::Value::Object(metrics).to_string())
+        }
+        _ => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "unknown metrics format {:?}, expected 'prometheus' or 'json'",
+            format
+        ))),
+    }
+}
+
+// =============================================================================
+// BULKHEAD ISOLATION
+// =============================================================================
+
+pyo3::create_exception!(
+    makeparallel,
+    BulkheadFull,
+    pyo3::exceptions::PyRuntimeError,
+    "Raised by a @bulkhead function when all its slots and its queue are full."
+);
+
+/// Admission counts of one @bulkhead function
+#[pyclass]
+#[derive(Clone, Default)]
+struct BulkheadStats {
+    #[pyo3(get)]
+    max_concurrent: usize,
+    #[pyo3(get)]
+    max_queue: usize,
+    /// Calls currently executing
+    #[pyo3(get)]
+    running: usize,
+    /// Calls currently waiting for a slot
+    #[pyo3(get)]
+    queued: usize,
+    #[pyo3(get)]
+    accepted: u64,
+    #[pyo3(get)]
+    rejected: u64,
+}
+
+#[pymethods]
+impl BulkheadStats {
+    fn __repr__(&self) -> String {
+        format!(
+            "BulkheadStats(running={}/{}, queued={}/{}, accepted={}, rejected={})",
+            self.running, self.max_concurrent, self.queued, self.max_queue, self.accepted, self.rejected
+        )
+    }
+}
+
+impl BulkheadStats {
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("max_concurrent", self.max_concurrent)?;
+        dict.set_item("max_queue", self.max_queue)?;
+        dict.set_item("running", self.running)?;
+        dict.set_item("queued", self.queued)?;
+        dict.set_item("accepted", self.accepted)?;
+        dict.set_item("rejected", self.rejected)?;
+        Ok(dict)
+    }
+}
+
+/// Shared limits of one @bulkhead function
+struct Bulkhead {
+    stats: Mutex<BulkheadStats>,
+    freed: Condvar,
+}
+
+/// Bulkheads by function name, read by get_metrics()
+static BULKHEADS: Lazy<DashMap<String, Arc<Bulkhead>>> = Lazy::new(DashMap::new);
+
+/// A running slot in a bulkhead, freed when dropped
+struct BulkheadPermit(Arc<Bulkhead>);
+
+impl Drop for BulkheadPermit {
+    fn drop(&mut self) {
+        self.0.stats.lock().running -= 1;
+        self.0.freed.notify_one();
+    }
+}
+
+impl Bulkhead {
+    /// Take a slot, waiting in the queue without the GIL if all slots are busy
+    fn enter(self: &Arc<Self>, py: Python, name: &str) -> PyResult<BulkheadPermit> {
+        fn take_slot(stats: &mut BulkheadStats) -> bool {
+            let free = stats.running < stats.max_concurrent;
+            if free {
+                stats.running += 1;
+                stats.accepted += 1;
+            }
+            free
+        }
+
+        {
+            let mut stats = self.stats.lock();
+            if take_slot(&mut stats) {
+                return Ok(BulkheadPermit(self.clone()));
+            }
+            if stats.queued >= stats.max_queue {
+                stats.rejected += 1;
+                return Err(BulkheadFull::new_err(format!(
+                    "bulkhead for {} is full: {} calls running, {} queued",
+                    name, stats.running, stats.queued
+                )));
+            }
+            stats.queued += 1;
+        }
+
+        let admitted = wait_for_state(py, &self.stats, &self.freed, None, |stats| {
+            let taken = take_slot(stats);
+            if taken {
+                stats.queued -= 1;
+            }
+            taken
+        });
+        match admitted {
+            Ok(_) => Ok(BulkheadPermit(self.clone())),
+            Err(e) => {
+                self.stats.lock().queued -= 1;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Limit a function to `max_concurrent` simultaneous calls
+///
+/// Up to `max_queue` further calls wait for a slot; calls beyond that raise
+/// BulkheadFull immediately. Counts are reported by `get_metrics(name).bulkhead`.
+#[pyfunction]
+#[pyo3(signature = (max_concurrent=10, max_queue=50))]
+fn bulkhead(py: Python<'_>, max_concurrent: usize, max_queue: usize) -> PyResult<Py<PyAny>> {
+    if max_concurrent == 0 {
+        return Err(pyo3::exceptions::PyValueError::new_err("max_concurrent must be at least 1"));
+    }
+    let decorator = PyCFunction::new_closure(
+        py,
+        None,
+        None,
+        move |args: &Bound<'_, PyTuple>, _kwargs: Option<&Bound<'_, PyDict>>| -> PyResult<Py<PyAny>> {
+            let py = args.py();
+            let func = args.get_item(0)?.unbind();
+            let func_name = callable_name(func.bind(py));
+            let limits = Arc::new(Bulkhead {
+                stats: Mutex::new(BulkheadStats { max_concurrent, max_queue, ..Default::default() }),
+                freed: Condvar::new(),
+            });
+            BULKHEADS.insert(func_name.clone(), limits.clone());
+
+            let wrapper = move |args: &Bound<'_, PyTuple>,
+                                kwargs: Option<&Bound<'_, PyDict>>|
+                  -> PyResult<Py<PyAny>> {
+                let py = args.py();
+                let _permit = limits.enter(py, &func_name)?;
+                Ok(func.bind(py).call(args, kwargs)?.unbind())
+            };
+            Ok(PyCFunction::new_closure(py, None, None, wrapper)?.into())
+        },
+    )?;
+    Ok(decorator.into())
+}
+
+// =============================================================================
+// RETRY STATE TRACKING
+// =============================================================================
+
+/// Observed retry behaviour of one retry-decorated function
+#[pyclass]
+#[derive(Clone, Default)]
+struct RetryState {
+    #[pyo3(get)]
+    consecutive_failures: u64,
+    #[pyo3(get)]
+    total_retries: u64,
+    #[pyo3(get)]
+    total_failures: u64,
+    #[pyo3(get)]
+    total_successes: u64,
+    #[pyo3(get)]
+    total_giveups: u64,
+    /// Unix timestamp of the next scheduled attempt while backing off
+    #[pyo3(get)]
+    next_attempt_at: Option<f64>,
+    #[pyo3(get)]
+    last_error: Option<String>,
+}
+
+#[pymethods]
+impl RetryState {
+    fn __repr__(&self) -> String {
+        format!(
+            "RetryState(consecutive_failures={}, total_retries={}, total_giveups={}, next_attempt_at={:?})",
+            self.consecutive_failures, self.total_retries, self.total_giveups, self.next_attempt_at
+        )
+    }
+}
+
+/// Global retry state tracker, keyed by function name
+static RETRY_STATES: Lazy<Arc<DashMap<String, RetryState>>> =
+    Lazy::new(|| Arc::new(DashMap::new()));
+
+/// Seconds since the Unix epoch
+fn unix_now() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+/// Get a display name for a Python callable
+///
+/// Functions use `__name__`. Lambdas add their qualified name and line, partials
+/// name the function they wrap, and other callables their class, so different
+/// callables don't share one metrics entry.
+fn callable_name(func: &Bound<'_, PyAny>) -> String {
+    let attr = |obj: &Bound<'_, PyAny>, name: &str| obj.getattr(name).ok().and_then(|v| v.extract::<String>().ok());
+    match attr(func, "__name__") {
+        Some(name) if name == "<lambda>" => {
+            let qualname = attr(func, "__qualname__").unwrap_or(name);
+            let line = func
+                .getattr("__code__")
+                .and_then(|code| code.getattr("co_firstlineno"))
+                .and_then(|line| line.extract::<u32>());
+            match line {
+                Ok(line) => format!("{}:{}", qualname, line),
+                Err(_) => qualname,
+            }
+        }
+        Some(name) => name,
+        None => {
+            // functools.partial and similar wrappers expose the wrapped callable as `func`
+            let inner = unwrap_partial(func);
+            if !inner.is(func) {
+                return format!("partial({})", callable_name(&inner));
+            }
+            if let Some(qualname) = attr(func, "__qualname__") {
+                return qualname;
+            }
+            let class = func.get_type();
+            let qualname = attr(class.as_any(), "__qualname__").unwrap_or_else(|| "unknown".to_string());
+            match attr(class.as_any(), "__module__") {
+                Some(module) if module != "builtins" => format!("{}.{}", module, qualname),
+                _ => qualname,
+            }
+        }
+    }
+}
+
+/// Apply `wrap` to `func`, or when `func` is None return a decorator that does
+///
+/// Lets decorators be used both bare and with options, e.g. `@profiled(name="x")`.
+fn decorate<F>(py: Python, func: Option<Py<PyAny>>, wrap: F) -> PyResult<Py<PyAny>>
+where
+    F: Fn(Python, Py<PyAny>) -> PyResult<Py<PyAny>> + Send + Sync + 'static,
+{
+    if let Some(func) = func {
+        return wrap(py, func);
+    }
+    let decorator = PyCFunction::new_closure(
+        py,
+        None,
+        None,
+        move |args: &Bound<'_, PyTuple>, _kwargs: Option<&Bound<'_, PyDict>>| {
+            wrap(args.py(), args.get_item(0)?.unbind())
+        },
+    )?;
+    Ok(decorator.into_any().unbind())
+}
+
+/// Record a failed attempt; `retry_in` is the backoff before the next attempt,
+/// or None when the decorator gives up
+fn record_retry_failure(name: &str, error: &PyErr, retry_in: Option<Duration>) {
+    let mut state = RETRY_STATES.entry(name.to_string()).or_default();
+    state.consecutive_failures += 1;
+    state.total_failures += 1;
+    state.last_error = Some(error.to_string());
+    match retry_in {
+        Some(delay) => {
+            state.total_retries += 1;
+            state.next_attempt_at = Some(unix_now() + delay.as_secs_f64());
+        }
+        None => {
+            state.total_giveups += 1;
+            state.next_attempt_at = None;
+        }
+    }
+}
+
+/// Record a successful attempt
+fn record_retry_success(name: &str) {
+    let mut state = RETRY_STATES.entry(name.to_string()).or_default();
+    state.consecutive_failures = 0;
+    state.total_successes += 1;
+    state.next_attempt_at = None;
+}
+
+/// Get retry state for a specific retry-decorated function
+#[pyfunction]
+fn get_retry_state(function_name: String) -> PyResult<Option<RetryState>> {
+    Ok(RETRY_STATES.get(&function_name).map(|s| s.clone()))
+}
+
+/// Get retry state for every retry-decorated function that has run
+#[pyfunction]
+fn get_all_retry_states(py: Python) -> PyResult<Py<PyDict>> {
+    let dict = PyDict::new(py);
+    for entry in RETRY_STATES.iter() {
+        dict.set_item(entry.key(), entry.value().clone())?;
+    }
+    Ok(dict.unbind())
+}
+
+/// Reset retry state
+#[pyfunction]
+#[pyo3(signature = (function_name=None))]
+fn reset_retry_state(function_name: Option<String>) -> PyResult<()> {
+    match function_name {
+        Some(name) => {
+            RETRY_STATES.remove(&name);
+        }
+        None => RETRY_STATES.clear(),
+    }
+    Ok(())
+}
+
+// =============================================================================
+// SCHEDULER EVENTS
+// =============================================================================
+
+/// Event names published by the scheduler
+const SCHEDULER_EVENTS: &[&str] = &[
+    "task_completed",
+    "task_failed",
+    "task_cancelled",
+    "sla_violated",
+    "task_error_unobserved",
+    "saga_started",
+    "saga_completed",
+    "saga_compensating",
+    "saga_compensated",
+    "pool_scaled_up",
+    "pool_scaled_down",
+    "pool_resized",
+    "task_hung",
+];
+
+/// A scheduler event waiting for delivery
+struct SchedulerEvent {
+    name: &'static str,
+    payload: serde_json::Value,
+}
+
+/// Registered webhook endpoint
+#[derive(Clone)]
+struct Webhook {
+    url: String,
+    events: Option<Vec<String>>,
+    timeout: Duration,
+}
+
+impl Webhook {
+    fn wants(&self, event: &str) -> bool {
+        self.events
+            .as_ref()
+            .is_none_or(|events| events.iter().any(|e| e == event))
+    }
+}
+
+static WEBHOOKS: Lazy<Arc<Mutex<Vec<Webhook>>>> = Lazy::new(|| Arc::new(Mutex::new(Vec::new())));
+
+// Events are delivered from a single background thread so task threads never block on I/O.
+// It is started with the first event and exits once idle (see configure_idle_timeout()).
+static EVENT_QUEUE: Lazy<Mutex<Option<CrossbeamSender<SchedulerEvent>>>> = Lazy::new(|| Mutex::new(None));
+
+/// Hand an event to the delivery thread, starting it if needed
+fn queue_event(event: SchedulerEvent) -> Result<(), crossbeam::channel::SendError<SchedulerEvent>> {
+    EVENT_QUEUE.lock().get_or_insert_with(spawn_event_dispatcher).send(event)
+}
+
+fn spawn_event_dispatcher() -> CrossbeamSender<SchedulerEvent> {
+    let (tx, rx): (CrossbeamSender<SchedulerEvent>, CrossbeamReceiver<SchedulerEvent>) = unbounded();
+    thread::Builder::new()
+        .name("makeparallel-events".to_string())
+        .spawn(move || {
+            let mut idle_since = Instant::now();
+            loop {
+                let poll = IDLE_TIMEOUT.lock().unwrap_or(Duration::from_secs(1));
+                match rx.recv_timeout(poll) {
+                    Ok(event) => {
+                        dispatch_event(&event);
+                        idle_since = Instant::now();
+                    }
+                    Err(crossbeam::channel::RecvTimeoutError::Timeout) if idle_expired(idle_since) => {
+                        // Events are queued under this lock, so none can slip in between
+                        let mut queue = EVENT_QUEUE.lock();
+                        if rx.is_empty() {
+                            queue.take();
+                            return;
+                        }
+                    }
+                    Err(crossbeam::channel::RecvTimeoutError::Timeout) => {}
+                    Err(crossbeam::channel::RecvTimeoutError::Disconnected) => return,
+                }
+            }
+        })
+        .expect("failed to spawn event dispatcher thread");
+    tx
+}
+
+/// How ZeroMQ messages are topic-prefixed
+#[derive(Clone, Copy, PartialEq)]
+enum ZmqTopic {
+    Function,
+    Tag,
+}
+
+/// ZeroMQ PUB socket receiving scheduler events
+#[cfg(feature = "zmq")]
+struct ZmqPublisher {
+    socket: zmq::Socket,
+    endpoint: String,
+    topic: ZmqTopic,
+    include_results: bool,
+}
+
+#[cfg(feature = "zmq")]
+static ZMQ_PUBLISHER: Lazy<Mutex<Option<ZmqPublisher>>> = Lazy::new(|| Mutex::new(None));
+
+#[cfg(feature = "zmq")]
+static ZMQ_CONTEXT: Lazy<zmq::Context> = Lazy::new(zmq::Context::new);
+
+#[cfg(feature = "zmq")]
+impl ZmqPublisher {
+    fn publish(&self, event: &SchedulerEvent) {
+        let tags: Vec<&str> = event.payload["tags"]
+            .as_array()
+            .map(|tags| tags.iter().filter_map(|t| t.as_str()).collect())
+            .unwrap_or_default();
+        let topics = match self.topic {
+            ZmqTopic::Tag if !tags.is_empty() => tags,
+            _ => vec![event.payload["function"].as_str().unwrap_or("unknown")],
+        };
+        let payload = event.payload.to_string();
+        for topic in topics {
+            if let Err(e) = self.socket.send_multipart([topic.as_bytes(), payload.as_bytes()], 0) {
+                warn!("ZeroMQ publish of '{}' on {} failed: {}", event.name, self.endpoint, e);
+            }
+        }
+    }
+}
+
+/// Whether any sink is listening for scheduler events
+fn has_event_sinks() -> bool {
+    #[cfg(feature = "zmq")]
+    if ZMQ_PUBLISHER.lock().is_some() {
+        return true;
+    }
+    !WEBHOOKS.lock().is_empty()
+}
+
+/// Whether completion events should carry the task's result
+fn events_include_results() -> bool {
+    #[cfg(feature = "zmq")]
+    if let Some(ref publisher) = *ZMQ_PUBLISHER.lock() {
+        return publisher.include_results;
+    }
+    false
+}
+
+/// Details of a `task_completed` event, including the JSON-encoded result when wanted
+fn completed_event_details(py: Python, result: &Bound<'_, PyAny>, elapsed: f64) -> serde_json::Value {
+    let mut details = serde_json::json!({ "elapsed": elapsed });
+    if has_event_sinks() && events_include_results() {
+        // Values JSON cannot represent are sent as their repr()
+        let encoded = py
+            .import("json")
+            .and_then(|json| {
+                let kwargs = PyDict::new(py);
+                kwargs.set_item("default", py.import("builtins")?.getattr("repr")?)?;
+                json.call_method("dumps", (result,), Some(&kwargs))?.extract::<String>()
+            })
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok());
+        details["result"] = encoded.unwrap_or(serde_json::Value::Null);
+    }
+    details
+}
+
+/// Deliver an event to every sink subscribed to it
+fn dispatch_event(event: &SchedulerEvent) {
+    let webhooks: Vec<Webhook> = WEBHOOKS
+        .lock()
+        .iter()
+        .filter(|hook| hook.wants(event.name))
+        .cloned()
+        .collect();
+
+    for hook in webhooks {
+        let agent = ureq::AgentBuilder::new().timeout(hook.timeout).build();
+        if let Err(e) = agent
+            .post(&hook.url)
+            .set("Content-Type", "application/json")
+            .send_string(&event.payload.to_string())
+        {
+            warn!("Webhook delivery of '{}' to {} failed: {}", event.name, hook.url, e);
+        }
+    }
+
+    #[cfg(feature = "zmq")]
+    if let Some(ref publisher) = *ZMQ_PUBLISHER.lock() {
+        publisher.publish(event);
+    }
+}
+
+/// Publish a task lifecycle event to registered sinks
+fn publish_task_event(name: &'static str, task_id: &str, func_name: &str, details: serde_json::Value) {
+    if !has_event_sinks() {
+        return;
+    }
+
+    let (tags, submitted_from) = ACTIVE_TASKS
+        .lock()
+        .iter()
+        .find(|task| task.task_id == task_id)
+        .map(|task| (task.tags.clone(), task.submitted_from.clone()))
+        .unwrap_or_default();
+
+    let mut payload = serde_json::json!({
+        "event": name,
+        "task_id": task_id,
+        "function": func_name,
+        "tags": tags,
+        "timestamp": unix_now(),
+    });
+    if let (Some(payload), Some(site)) = (payload.as_object_mut(), submitted_from) {
+        payload.insert("submitted_from".to_string(), site.into());
+    }
+    if let (Some(payload), serde_json::Value::Object(details)) = (payload.as_object_mut(), details) {
+        payload.extend(details);
+    }
+
+    let event = SchedulerEvent { name, payload };
+    if sync_fallback() {
+        dispatch_event(&event);
+    } else if let Err(e) = queue_event(event) {
+        error!("Failed to queue scheduler event '{}': {}", name, e);
+    }
+}
+
+/// Publish the failure of a task, plus an SLA violation when it overran its timeout
+fn publish_task_failure(task_error: &TaskError, timeout_limit: Option<f64>) {
+    if let Some(limit) = timeout_limit {
+        publish_task_event(
+            "sla_violated",
+            &task_error.task_id,
+            &task_error.task_name,
+            serde_json::json!({ "limit": limit, "elapsed": task_error.elapsed_time }),
+        );
+    }
+    let name = if task_error.error_type == "CancellationError" {
+        "task_cancelled"
+    } else {
+        "task_failed"
+    };
+    publish_task_event(
+        name,
+        &task_error.task_id,
+        &task_error.task_name,
+        serde_json::json!({
+            "error_type": task_error.error_type,
+            "error": task_error.error_message,
+            "elapsed": task_error.elapsed_time,
+        }),
+    );
+}
+
+// Process-wide handler invoked for every task failure
+static GLOBAL_ERROR_HANDLER: Lazy<Arc<Mutex<Option<Py<PyAny>>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
+
+/// Tracks whether a failed task's error ever reached the caller
+///
+/// Shared between the worker and the handle: whichever of "task failed" and
+/// "handle dropped" happens last decides whether the failure went unobserved.
+#[derive(Default)]
+struct FailureWatch {
+    state: Mutex<FailureWatchState>,
+}
+
+#[derive(Default)]
+struct FailureWatchState {
+    failure: Option<TaskError>,
+    /// How the task failed, cancellations included
+    status: Option<TaskStatus>,
+    observed: bool,
+    released: bool,
+}
+
+impl FailureWatch {
+    /// Record the failure of the task (worker side)
+    fn record_failure(&self, py: Python, task_error: &TaskError) {
+        let mut state = self.state.lock();
+        state.failure = Some(task_error.clone());
+        if state.released && !state.observed {
+            drop(state);
+            warn_unobserved_failure(py, task_error);
+        }
+    }
+
+    /// Record how the task ended without a result (worker side)
+    fn record_status(&self, status: TaskStatus) {
+        self.state.lock().status = Some(status);
+    }
+
+    /// The caller retrieved the task's error
+    fn mark_observed(&self) {
+        self.state.lock().observed = true;
+    }
+
+    /// The handle was garbage collected (handle side)
+    fn release(&self, py: Python) {
+        let mut state = self.state.lock();
+        state.released = true;
+        if state.observed {
+            return;
+        }
+        if let Some(task_error) = state.failure.take() {
+            drop(state);
+            warn_unobserved_failure(py, &task_error);
+        }
+    }
+}
+
+/// Warn that a task failed and nobody ever retrieved its error
+fn warn_unobserved_failure(py: Python, task_error: &TaskError) {
+    warn!("Task exception was never retrieved: {}", task_error.__str__());
+    let message = std::ffi::CString::new(format!(
+        "Task exception was never retrieved: {}",
+        task_error.__str__()
+    ))
+    .unwrap_or_default();
+    let category = py.get_type::<pyo3::exceptions::PyRuntimeWarning>();
+    if let Err(e) = PyErr::warn(py, &category, &message, 1) {
+        // Warnings configured as errors cannot propagate out of a worker or a destructor
+        e.write_unraisable(py, None);
+    }
+    publish_task_event(
+        "task_error_unobserved",
+        &task_error.task_id,
+        &task_error.task_name,
+        serde_json::json!({
+            "error_type": task_error.error_type,
+            "error": task_error.error_message,
+        }),
+    );
+}
+
+/// Hand a task failure to the handle's watch, the global error handler and the event bus
+fn report_task_failure(
+    py: Python,
+    watch: &FailureWatch,
+    task_error: &TaskError,
+    timeout_limit: Option<f64>,
+) {
+    watch.record_status(TaskStatus::of_failure(task_error, timeout_limit));
+    keep_task_checkpoints(&task_error.task_id);
+    // Cancellation is requested by the caller, so it is not reported as a failure
+    if task_error.error_type != "CancellationError" {
+        mark_workspace_failed(&task_error.task_id);
+        watch.record_failure(py, task_error);
+        let handler = GLOBAL_ERROR_HANDLER.lock().as_ref().map(|h| h.clone_ref(py));
+        if let Some(handler) = handler {
+            if let Err(e) = handler.call1(py, (task_error.clone(),)) {
+                error!("Global error handler failed for task {}: {}", task_error.task_id, e);
+            }
+        }
+    }
+    publish_task_failure(task_error, timeout_limit);
+}
+
+/// Set a handler called with the TaskError of every failed task, or None to clear it
+///
+/// The handler runs on the worker thread as soon as the task fails, whether or not
+/// the result is ever retrieved. Returns the previously installed handler.
+#[pyfunction]
+fn set_global_error_handler(py: Python, handler: Option<Py<PyAny>>) -> PyResult<Option<Py<PyAny>>> {
+    if let Some(ref h) = handler {
+        if !h.bind(py).is_callable() {
+            return Err(pyo3::exceptions::PyTypeError::new_err(
+                "Global error handler must be callable or None",
+            ));
+        }
+    }
+    Ok(std::mem::replace(&mut *GLOBAL_ERROR_HANDLER.lock(), handler))
+}
+
+/// Register a webhook that receives scheduler events as JSON POST requests
+#[pyfunction]
+#[pyo3(signature = (url, events=None, timeout=5.0))]
+fn register_webhook(url: String, events: Option<Vec<String>>, timeout: f64) -> PyResult<()> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Webhook URL must start with http:// or https://, got '{}'",
+            url
+        )));
+    }
+    if let Some(unknown) = events
+        .iter()
+        .flatten()
+        .find(|e| !SCHEDULER_EVENTS.contains(&e.as_str()))
+    {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Unknown event '{}', expected one of {:?}",
+            unknown, SCHEDULER_EVENTS
+        )));
+    }
+    if !(timeout.is_finite() && timeout > 0.0) {
+        return Err(pyo3::exceptions::PyValueError::new_err("timeout must be positive"));
+    }
+
+    let mut webhooks = WEBHOOKS.lock();
+    webhooks.retain(|hook| hook.url != url);
+    webhooks.push(Webhook {
+        url,
+        events,
+        timeout: Duration::from_secs_f64(timeout),
+    });
+    Ok(())
+}
+
+/// Unregister a webhook, returning whether it was registered
+#[pyfunction]
+fn unregister_webhook(url: String) -> PyResult<bool> {
+    let mut webhooks = WEBHOOKS.lock();
+    let before = webhooks.len();
+    webhooks.retain(|hook| hook.url != url);
+    Ok(webhooks.len() != before)
+}
+
+/// List registered webhooks and the events they receive
+#[pyfunction]
+fn list_webhooks(py: Python) -> PyResult<Vec<Py<PyDict>>> {
+    WEBHOOKS
+        .lock()
+        .iter()
+        .map(|hook| {
+            let dict = PyDict::new(py);
+            dict.set_item("url", &hook.url)?;
+            dict.set_item("events", &hook.events)?;
+            dict.set_item("timeout", hook.timeout.as_secs_f64())?;
+            Ok(dict.unbind())
+        })
+        .collect()
+}
+
+/// Publish scheduler events to a ZeroMQ PUB socket bound at `endpoint`
+///
+/// Messages are two frames: the topic (function name, or each of the task's tags
+/// with `topic="tag"`) and the JSON event. Requires the `zmq` build feature.
+#[pyfunction]
+#[pyo3(signature = (endpoint, topic="function", include_results=true))]
+fn enable_zmq_publisher(endpoint: String, topic: &str, include_results: bool) -> PyResult<()> {
+    let topic = match topic {
+        "function" => ZmqTopic::Function,
+        "tag" => ZmqTopic::Tag,
+        other => {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "topic must be 'function' or 'tag', got '{}'",
+                other
+            )))
+        }
+    };
+
+    #[cfg(feature = "zmq")]
+    {
+        let mut publisher = ZMQ_PUBLISHER.lock();
+        // Release the previous endpoint first so it can be re-bound
+        *publisher = None;
+        let socket = ZMQ_CONTEXT
+            .socket(zmq::PUB)
+            .and_then(|socket| socket.bind(&endpoint).map(|_| socket))
+            .map_err(|e| {
+                pyo3::exceptions::PyRuntimeError::new_err(format!(
+                    "Failed to bind ZeroMQ publisher to {}: {}",
+                    endpoint, e
+                ))
+            })?;
+        *publisher = Some(ZmqPublisher {
+            socket,
+            endpoint,
+            topic,
+            include_results,
+        });
+        Ok(())
+    }
+
+    #[cfg(not(feature = "zmq"))]
+    {
+        let _ = (endpoint, topic, include_results);
+        Err(pyo3::exceptions::PyRuntimeError::new_err(
+            "makeparallel was built without the 'zmq' feature",
+        ))
+    }
+}
+
+/// Stop publishing to ZeroMQ, returning whether a publisher was active
+#[pyfunction]
+fn disable_zmq_publisher() -> PyResult<bool> {
+    #[cfg(feature = "zmq")]
+    {
+        Ok(ZMQ_PUBLISHER.lock().take().is_some())
+    }
+
+    #[cfg(not(feature = "zmq"))]
+    {
+        Ok(false)
+    }
+}
+
+// Helper wrapper that supports the descriptor protocol for methods
+#[pyclass]
+struct MethodWrapper {
+    #[allow(dead_code)]
+    func: Py<PyAny>,
+    wrapper: Py<PyAny>,
+}
+
+#[pymethods]
+impl MethodWrapper {
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(
+        &self,
+        py: Python,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<PyAny>> {
+        self.wrapper.bind(py).call(args, kwargs).map(|r| r.unbind())
+    }
+
+    fn __get__(
+        &self,
+        py: Python,
+        obj: &Bound<'_, PyAny>,
+        _objtype: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Py<PyAny>> {
+        if obj.is_none() {
+            // Unbound method access, return self
+            return Ok(self.wrapper.clone_ref(py));
+        }
+
+        // Bound method access, create a partial with obj as first argument
+        let functools = py.import("functools")?;
+        let partial = functools.getattr("partial")?;
+        partial
+            .call1((self.wrapper.bind(py), obj))
+            .map(|r| r.unbind())
+    }
+}
+
+// 1. Timer Decorator
+#[pyfunction]
+fn timer(py: Python, func: Py<PyAny>) -> PyResult<Py<PyAny>> {
+    let func_clone = func.clone_ref(py);
+    let wrapper = move |args: &Bound<'_, PyTuple>,
+                        kwargs: Option<&Bound<'_, PyDict>>|
+          -> PyResult<Py<PyAny>> {
+        let py = args.py();
+        let start = Instant::now();
+        let result = func_clone.bind(py).call(args, kwargs)?;
+        let duration = start.elapsed();
+        println!("Execution took: {:?}", duration);
+        Ok(result.unbind())
+    };
+    let wrapped = PyCFunction::new_closure(py, None, None, wrapper)?;
+
+    // Wrap in MethodWrapper to support methods
+    let method_wrapper = Py::new(
+        py,
+        MethodWrapper {
+            func: func.clone_ref(py),
+            wrapper: wrapped.into(),
+        },
+    )?;
+    Ok(method_wrapper.into())
+}
+
+// 3. Call Counter Decorator (as a PyClass)
+#[pyclass(name = "CallCounter")]
+struct CallCounter {
+    func: Py<PyAny>,
+    call_count: Arc<Mutex<i32>>,
+}
+
+#[pymethods]
+impl CallCounter {
+    #[new]
+    fn new(func: Py<PyAny>) -> Self {
+        CallCounter {
+            func,
+            call_count: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(
+        &self,
+        py: Python,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<PyAny>> {
+        let mut count = self.call_count.lock();
+        *count += 1;
+        Ok(self.func.bind(py).call(args, kwargs)?.unbind())
+    }
+
+    #[getter]
+    fn get_call_count(&self) -> PyResult<i32> {
+        Ok(*self.call_count.lock())
+    }
+
+    fn reset(&self) -> PyResult<()> {
+        *self.call_count.lock() = 0;
+        Ok(())
+    }
+
+    fn __get__(
+        slf: PyRef<'_, Self>,
+        obj: &Bound<'_, PyAny>,
+        _objtype: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Py<PyAny>> {
+        if obj.is_none() {
+            // Unbound method access, return self
+            let py = slf.py();
+            return Ok(slf.into_bound_py_any(py)?.unbind());
+        }
+
+        // Bound method access, create a BoundMethod wrapper
+        let py = slf.py();
+        let call_count_clone = slf.call_count.clone();
+        let decorator = slf.into_bound_py_any(py)?.unbind();
+        let bound_method = Py::new(
+            py,
+            BoundMethod {
+                obj: obj.clone().unbind(),
+                decorator,
+                call_count: call_count_clone,
+            },
+        )?;
+        Ok(bound_method.into())
+    }
+}
+
+// Helper class for bound methods from CallCounter
+#[pyclass]
+struct BoundMethod {
+    obj: Py<PyAny>,
+    decorator: Py<PyAny>,
+    call_count: Arc<Mutex<i32>>,
+}
+
+#[pymethods]
+impl BoundMethod {
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(
+        &self,
+        py: Python,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<PyAny>> {
+        // Create new tuple with obj as first arg
+        let mut new_args = vec![self.obj.bind(py).clone()];
+        for arg in args.iter() {
+            new_args.push(arg.clone());
+        }
+        let new_tuple = PyTuple::new(py, new_args)?;
+        self.decorator
+            .bind(py)
+            .call(new_tuple, kwargs)
+            .map(|r| r.unbind())
+    }
+
+    #[getter]
+    fn get_call_count(&self) -> PyResult<i32> {
+        Ok(*self.call_count.lock())
+    }
+}
+
+/// Fixed delay between @retry attempts, in seconds
+const RETRY_DELAY_SECS: f64 = 0.05;
+
+// 4. Retry Decorator
+/// Retry a function up to `max_retries` times after a short fixed delay
+///
+/// `retry_on`, `giveup_on` and `jitter` work as for retry_backoff().
+#[pyfunction]
+#[pyo3(signature = (*, max_retries=3, retry_on=None, giveup_on=None, jitter=None))]
+fn retry(
+    _py: Python<'_>,
+    max_retries: usize,
+    retry_on: Option<Bound<'_, PyAny>>,
+    giveup_on: Option<Bound<'_, PyAny>>,
+    jitter: Option<&str>,
+) -> PyResult<Py<PyAny>> {
+    let filter = RetryFilter::new(retry_on.as_ref(), giveup_on.as_ref())?;
+    let jitter = Jitter::parse(jitter)?;
+    let factory = move |py: Python<'_>, func: Py<PyAny>| -> PyResult<Py<PyAny>> {
+        let filter = filter.clone();
+        let func_name = callable_name(func.bind(py));
+        let wrapper = move |args: &Bound<'_, PyTuple>,
+                            kwargs: Option<&Bound<'_, PyDict>>|
+              -> PyResult<Py<PyAny>> {
+            let py = args.py();
+            let mut last_err = None;
+            let mut previous_sleep = RETRY_DELAY_SECS;
+            for attempt in 0..=max_retries {
+                match run_attempt(func.bind(py), args, kwargs, attempt + 1) {
+                    Ok(res) => {
+                        record_retry_success(&func_name);
+                        return Ok(res.unbind());
+                    }
+                    Err(e) if !filter.should_retry(py, &e) => {
+                        record_retry_failure(&func_name, &e, None);
+                        return Err(e);
+                    }
+                    Err(e) => {
+                        println!("Attempt {} failed: {:?}", attempt + 1, e.to_string());
+                        // Decorrelated jitter grows from the fixed delay, capped like retry_backoff's default
+                        previous_sleep = jitter.map_or(RETRY_DELAY_SECS, |jitter| {
+                            jitter.apply(RETRY_DELAY_SECS, previous_sleep, RETRY_DELAY_SECS, 60.0)
+                        });
+                        let delay = Duration::from_secs_f64(previous_sleep);
+                        let will_retry = attempt < max_retries;
+                        if will_retry && !backoff_fits_deadline(delay) {
+                            record_retry_failure(&func_name, &e, None);
+                            return Err(retry_budget_exhausted(e));
+                        }
+                        record_retry_failure(&func_name, &e, will_retry.then_some(delay));
+                        last_err = Some(e);
+                        thread::sleep(delay);
+                    }
+                }
+            }
+            Err(last_err.unwrap())
+        };
+        let wrapped = PyCFunction::new_closure(py, None, None, wrapper)?;
+        Ok(wrapped.into())
+    };
+
+    // This creates a decorator that accepts arguments
+    let decorator = PyCFunction::new_closure(
+        _py,
+        None,
+        None,
+        move |args: &Bound<'_, PyTuple>, _kwargs: Option<&Bound<'_, PyDict>>| {
+            // The real function to be decorated is the first argument
+            let func = args.get_item(0)?.unbind();
+            factory(args.py(), func)
+        },
+    )?;
+    Ok(decorator.into())
+}
+
+/// Cache key for a memoized call, built from the reprs of its arguments
+fn memo_key(args: &Bound<'_, PyTuple>, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<String> {
+    let mut key_parts: Vec<String> = vec![];
+    for arg in args.iter() {
+        key_parts.push(arg.repr()?.to_cow()?.into_owned());
+    }
+    if let Some(kwargs_dict) = kwargs {
+        for (key, val) in kwargs_dict.iter() {
+            key_parts.push(format!("{}={}", key, val.repr()?.to_cow()?));
+        }
+    }
+    Ok(key_parts.join(","))
+}
+
+// 5. Memoize Decorator
+#[pyfunction]
+fn memoize(py: Python, func: Py<PyAny>) -> PyResult<Py<PyAny>> {
+    let cache: Arc<Mutex<HashMap<String, Py<PyAny>>>> = Arc::new(Mutex::new(HashMap::new()));
+    register_memo_cache(py, func.bind(py), MemoStore::Locked(Arc::downgrade(&cache)));
+
+    let wrapper = move |args: &Bound<'_, PyTuple>,
+                        kwargs: Option<&Bound<'_, PyDict>>|
+          -> PyResult<Py<PyAny>> {
+        let py = args.py();
+
+        // Create a cache key from arguments
+        let key = memo_key(args, kwargs)?;
+
+        // Check if result is in cache
+        if let Some(cached_result) = cache.lock().get(&key) {
+            println!("Cache hit for key: {}", key);
+            return Ok(cached_result.clone_ref(py));
+        }
+
+        // If not, call the function and store the result. The lock is not held during
+        // the call, which may release the GIL to a thread waiting on this cache.
+        println!("Cache miss for key: {}", key);
+        let result = func.bind(py).call(args, kwargs)?;
+        let result_unbound = result.unbind();
+        cache.lock().insert(key, result_unbound.clone_ref(py));
+        Ok(result_unbound)
+    };
+
+    let wrapped = PyCFunction::new_closure(py, None, None, wrapper)?;
+    Ok(wrapped.into())
+}
+
+/// Outcome of one in-flight call, shared by every caller that joined it
+#[derive(Default)]
+struct Flight {
+    outcome: Mutex<Option<PyResult<Py<PyAny>>>>,
+    landed: Condvar,
+}
+
+// Single-flight Decorator
+/// Concurrent calls with identical arguments share one execution
+///
+/// The first caller runs the function; callers arriving while it runs wait without
+/// the GIL and receive the same result or exception. Nothing is kept afterwards, so
+/// a later call runs the function again; combine with @memoize to also cache.
+#[pyfunction]
+fn singleflight(py: Python, func: Py<PyAny>) -> PyResult<Py<PyAny>> {
+    let in_flight: Arc<Mutex<HashMap<String, Arc<Flight>>>> = Arc::default();
+
+    let wrapper = move |args: &Bound<'_, PyTuple>,
+                        kwargs: Option<&Bound<'_, PyDict>>|
+          -> PyResult<Py<PyAny>> {
+        let py = args.py();
+        let key = memo_key(args, kwargs)?;
+        let (flight, leader) = {
+            let mut flights = in_flight.lock();
+            match flights.get(&key) {
+                Some(flight) => (flight.clone(), false),
+                None => {
+                    let flight = Arc::new(Flight::default());
+                    flights.insert(key.clone(), flight.clone());
+                    (flight, true)
+                }
+            }
+        };
+
+        if leader {
+            let outcome = func.bind(py).call(args, kwargs).map(Bound::unbind);
+            *flight.outcome.lock() = Some(clone_outcome(py, &outcome));
+            in_flight.lock().remove(&key);
+            flight.landed.notify_all();
+            return outcome;
+        }
+
+        wait_for_state(py, &flight.outcome, &flight.landed, None, |outcome| outcome.is_some())?;
+        let outcome = flight.outcome.lock();
+        clone_outcome(py, outcome.as_ref().expect("waited for the flight to land"))
+    };
+
+    let wrapped = PyCFunction::new_closure(py, None, None, wrapper)?;
+    Ok(wrapped.into())
+}
+
+// =============================================================================
+// PERSISTENCE ENCRYPTION
+// =============================================================================
+
+/// Starts a file written by cache_dump() while an encryption key was set
+const SEALED_FILE_MAGIC: &[u8; 8] = b"MPSEAL1\n";
+
+/// Key results are encrypted with before they are written to disk
+static PERSISTENCE_KEY: Lazy<Mutex<Option<Arc<LessSafeKey>>>> = Lazy::new(|| Mutex::new(None));
+
+/// Encrypt results written to disk by cache_dump(), checkpointed parallel_map() and save_checkpoint(),
+/// and the arguments of DurableQueue jobs
+///
+/// `encryption_key` is a 16- or 32-byte key for AES-128-GCM or AES-256-GCM. Reading
+/// an encrypted file needs the same key, and while a key is set unencrypted files are
+/// refused. Pass `encryption_key=None` to write and read unencrypted files again.
+#[pyfunction]
+#[pyo3(signature = (encryption_key=None))]
+fn configure_persistence(encryption_key: Option<Vec<u8>>) -> PyResult<()> {
+    let Some(key) = encryption_key else {
+        *PERSISTENCE_KEY.lock() = None;
+        return Ok(());
+    };
+    let algorithm = match key.len() {
+        16 => &AES_128_GCM,
+        32 => &AES_256_GCM,
+        len => {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "encryption_key must be 16 or 32 bytes (AES-128 or AES-256), got {}",
+                len
+            )))
+        }
+    };
+    let key = UnboundKey::new(algorithm, &key)
+        .map_err(|_| pyo3::exceptions::PyValueError::new_err("encryption_key was rejected"))?;
+    *PERSISTENCE_KEY.lock() = Some(Arc::new(LessSafeKey::new(key)));
+    Ok(())
+}
+
+/// The key set by configure_persistence(), if any
+fn persistence_key() -> Option<Arc<LessSafeKey>> {
+    PERSISTENCE_KEY.lock().clone()
+}
+
+/// Encrypt `data` as nonce, ciphertext and tag, bound to `context`
+///
+/// Nonces are random, and opening the result with a different context fails, so a
+/// record cannot be moved to another place in a file.
+fn seal(key: &LessSafeKey, context: &[u8], data: &[u8]) -> Result<Vec<u8>, ring::error::Unspecified> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new().fill(&mut nonce)?;
+    let mut sealed = data.to_vec();
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(context), &mut sealed)?;
+    sealed.splice(0..0, nonce);
+    Ok(sealed)
+}
+
+/// Decrypt data from seal(); None for a wrong key or context, or tampered data
+fn unseal(key: &LessSafeKey, context: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+    let mut data = ciphertext.to_vec();
+    let len = key.open_in_place(nonce, Aad::from(context), &mut data).ok()?.len();
+    data.truncate(len);
+    Some(data)
+}
+
+/// Error for an encrypted file read without a key
+fn missing_key_error(path: &std::path::Path) -> PyErr {
+    pyo3::exceptions::PyValueError::new_err(format!(
+        "{} is encrypted; set its key with configure_persistence(encryption_key=...)",
+        path.display()
+    ))
+}
+
+/// Error for an unencrypted file read while a key is set
+///
+/// Unsealed data is not authenticated, so accepting it would let anyone who can write
+/// the file replace what gets unpickled.
+fn unencrypted_file_error(path: &std::path::Path) -> PyErr {
+    pyo3::exceptions::PyValueError::new_err(format!(
+        "{} is not encrypted, but configure_persistence() set a key; \
+         load it with configure_persistence(encryption_key=None) if it is trusted",
+        path.display()
+    ))
+}
+
+/// Error for an encrypted file whose key check or contents fail to decrypt
+fn wrong_key_error(path: &std::path::Path) -> PyErr {
+    pyo3::exceptions::PyValueError::new_err(format!(
+        "cannot decrypt {}: wrong encryption key or corrupted file",
+        path.display()
+    ))
+}
+
+// =============================================================================
+// MEMOIZE CACHE PERSISTENCE
+// =============================================================================
+
+/// Cached results of one memoized function, by argument key
+type MemoEntries = HashMap<String, Py<PyAny>>;
+
+/// Storage behind a memoized function, held weakly so dropped functions free their cache
+enum MemoStore {
+    Locked(Weak<Mutex<MemoEntries>>),
+    Concurrent(Weak<DashMap<String, Py<PyAny>>>),
+}
+
+impl MemoStore {
+    /// Cached entries, or None once the memoized function is gone
+    fn entries(&self, py: Python) -> Option<MemoEntries> {
+        match self {
+            MemoStore::Locked(cache) => cache.upgrade().map(|cache| {
+                cache.lock().iter().map(|(k, v)| (k.clone(), v.clone_ref(py))).collect()
+            }),
+            MemoStore::Concurrent(cache) => cache.upgrade().map(|cache| {
+                cache.iter().map(|entry| (entry.key().clone(), entry.value().clone_ref(py))).collect()
+            }),
+        }
+    }
+
+    fn insert(&self, key: String, value: Py<PyAny>) {
+        match self {
+            MemoStore::Locked(cache) => {
+                if let Some(cache) = cache.upgrade() {
+                    cache.lock().insert(key, value);
+                }
+            }
+            MemoStore::Concurrent(cache) => {
+                if let Some(cache) = cache.upgrade() {
+                    cache.insert(key, value);
+                }
+            }
+        }
+    }
+}
+
+/// Caches of all memoized functions, by `module.qualname`
+static MEMO_CACHES: Lazy<Mutex<Vec<(String, MemoStore)>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Entries from cache_load() kept for functions memoized after the load
+static LOADED_MEMO_ENTRIES: Lazy<Mutex<HashMap<String, MemoEntries>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Name a memoized function's cache is dumped and loaded under
+fn memo_cache_name(func: &Bound<'_, PyAny>) -> String {
+    let module = func
+        .getattr("__module__")
+        .ok()
+        .and_then(|m| m.extract::<String>().ok())
+        .unwrap_or_else(|| "unknown".to_string());
+    let qualname = func
+        .getattr("__qualname__")
+        .ok()
+        .and_then(|n| n.extract::<String>().ok())
+        .unwrap_or_else(|| callable_name(func));
+    format!("{}.{}", module, qualname)
+}
+
+/// Track a new memoize cache, seeding it with any loaded entries for its function
+fn register_memo_cache(py: Python, func: &Bound<'_, PyAny>, store: MemoStore) {
+    let name = memo_cache_name(func);
+    if let Some(entries) = LOADED_MEMO_ENTRIES.lock().get(&name) {
+        for (key, value) in entries {
+            store.insert(key.clone(), value.clone_ref(py));
+        }
+    }
+    let mut caches = MEMO_CACHES.lock();
+    caches.retain(|(_, store)| match store {
+        MemoStore::Locked(cache) => cache.strong_count() > 0,
+        MemoStore::Concurrent(cache) => cache.strong_count() > 0,
+    });
+    caches.push((name, store));
+}
+
+/// Write the caches of all memoized functions to `path`; returns the number of entries written
+///
+/// Values are pickled one by one, and those that cannot be pickled are left out.
+/// The file is encrypted when configure_persistence() set a key.
+#[pyfunction]
+fn cache_dump(py: Python, path: PathBuf) -> PyResult<usize> {
+    let pickle = py.import("pickle")?;
+    let dump = PyDict::new(py);
+    let mut written = 0;
+
+    let caches: Vec<(String, MemoEntries)> = MEMO_CACHES
+        .lock()
+        .iter()
+        .filter_map(|(name, store)| store.entries(py).map(|entries| (name.clone(), entries)))
+        .collect();
+    for (name, entries) in caches {
+        let function_entries = match dump.get_item(&name)? {
+            Some(existing) => existing.cast_into::<PyDict>()?,
+            None => {
+                let created = PyDict::new(py);
+                dump.set_item(&name, &created)?;
+                created
+            }
+        };
+        for (key, value) in entries {
+            match pickle.call_method1("dumps", (value,)) {
+                Ok(pickled) => {
+                    function_entries.set_item(key, pickled)?;
+                    written += 1;
+                }
+                Err(e) => warn!("Skipping unpicklable cache entry {}({}): {}", name, key, e),
+            }
+        }
+    }
+
+    let mut data: Vec<u8> = pickle.call_method1("dumps", (dump,))?.extract()?;
+    if let Some(key) = persistence_key() {
+        let sealed = seal(&key, SEALED_FILE_MAGIC, &data)
+            .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("Failed to encrypt the cache"))?;
+        data = [SEALED_FILE_MAGIC.as_slice(), &sealed].concat();
+    }
+    std::fs::write(&path, data).map_err(|e| {
+        pyo3::exceptions::PyOSError::new_err(format!("Failed to write {}: {}", path.display(), e))
+    })?;
+    Ok(written)
+}
+
+/// Load entries written by cache_dump() into the caches of memoized functions
+///
+/// Functions memoized after the load pick up their entries when decorated.
+/// Returns the number of entries loaded.
+#[pyfunction]
+fn cache_load(py: Python, path: PathBuf) -> PyResult<usize> {
+    let pickle = py.import("pickle")?;
+    let mut data = std::fs::read(&path).map_err(|e| {
+        pyo3::exceptions::PyOSError::new_err(format!("Failed to read {}: {}", path.display(), e))
+    })?;
+    match (data.strip_prefix(SEALED_FILE_MAGIC), persistence_key()) {
+        (Some(sealed), Some(key)) => {
+            data = unseal(&key, SEALED_FILE_MAGIC, sealed).ok_or_else(|| wrong_key_error(&path))?;
+        }
+        (Some(_), None) => return Err(missing_key_error(&path)),
+        (None, Some(_)) => return Err(unencrypted_file_error(&path)),
+        (None, None) => {}
+    }
+    let dump = pickle.call_method1("loads", (PyBytes::new(py, &data),))?;
+    let dump = dump.cast::<PyDict>()?;
+    let mut loaded = 0;
+
+    for (name, entries) in dump.iter() {
+        let name: String = name.extract()?;
+        let mut function_entries = MemoEntries::new();
+        for (key, pickled) in entries.cast::<PyDict>()?.iter() {
+            function_entries.insert(key.extract::<String>()?, pickle.call_method1("loads", (pickled,))?.unbind());
+        }
+        loaded += function_entries.len();
+
+        for (cache_name, store) in MEMO_CACHES.lock().iter() {
+            if *cache_name == name {
+                for (key, value) in &function_entries {
+                    store.insert(key.clone(), value.clone_ref(py));
+                }
+            }
+        }
+        LOADED_MEMO_ENTRIES.lock().entry(name).or_default().extend(function_entries);
+    }
+    Ok(loaded)
+}
+
+/// Call a memoized function over `args_list` in the background to fill its cache
+///
+/// Each entry is a tuple of positional arguments or a single argument. At most
+/// `max_concurrency` calls run at once on the thread pool (default: one per worker).
+/// The returned handle resolves to the number of calls that succeeded.
+#[pyfunction]
+#[pyo3(signature = (func, args_list, max_concurrency=None))]
+fn warm_cache(
+    py: Python,
+    func: Py<PyAny>,
+    args_list: &Bound<'_, PyAny>,
+    max_concurrency: Option<usize>,
+) -> PyResult<AsyncHandle> {
+    if max_concurrency == Some(0) {
+        return Err(pyo3::exceptions::PyValueError::new_err("max_concurrency must be at least 1"));
+    }
+    let items = args_list
+        .try_iter()?
+        .map(|item| item.map(Bound::unbind))
+        .collect::<PyResult<Vec<_>>>()?;
+    let workers = max_concurrency
+        .unwrap_or_else(pool_num_threads)
+        .min(items.len())
+        .max(1);
+
+    let func_name = callable_name(func.bind(py));
+    let (handle, completer) = AsyncHandle::detached(format!("warm_cache({})", func_name), Vec::new(), None);
+    let cancel_token = handle.cancel_token.clone();
+
+    py.detach(|| {
+        spawn_task_thread(move || {
+            let next = AtomicUsize::new(0);
+            let warmed = AtomicUsize::new(0);
+            in_thread_pool(|| rayon::scope(|scope| {
+                for _ in 0..workers {
+                    scope.spawn(|_| loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        if index >= items.len() || cancel_token.load(Ordering::Acquire) {
+                            break;
+                        }
+                        Python::attach(|py| {
+                            let item = items[index].bind(py);
+                            let outcome = match item.cast::<PyTuple>() {
+                                Ok(args) => func.bind(py).call1(args),
+                                Err(_) => func.bind(py).call1((item,)),
+                            };
+                            match outcome {
+                                Ok(_) => {
+                                    warmed.fetch_add(1, Ordering::Relaxed);
+                                }
+                                Err(e) => warn!("warm_cache: {} failed for item {}: {}", func_name, index, e),
+                            }
+                        });
+                    });
+                }
+            }));
+            Python::attach(|py| {
+                let warmed = warmed.load(Ordering::Relaxed).into_pyobject(py).map(|n| n.into_any().unbind());
+                completer.complete(py, warmed.map_err(PyErr::from));
+            });
+        });
+    });
+    Ok(handle)
+}
+
+// =============================================================================
+// SHARED MEMOIZE CACHE
+// =============================================================================
+
+/// Marks an initialized shared cache file ("MPCACHE1")
+const SHARED_CACHE_MAGIC: u64 = u64::from_le_bytes(*b"MPCACHE1");
+/// Magic value while the creating process writes the header
+const SHARED_CACHE_INITIALIZING: u64 = 1;
+/// Bytes reserved for the file header
+const SHARED_CACHE_HEADER_SIZE: usize = 64;
+/// Bytes at the start of each slot before its key and value
+const SHARED_SLOT_HEADER_SIZE: usize = std::mem::size_of::<SharedSlotHeader>();
+/// Slot sizes are multiples of this, so every slot header is aligned
+const SHARED_SLOT_ALIGN: usize = std::mem::align_of::<SharedSlotHeader>();
+/// Slots probed for a key before an occupied one is overwritten
+const SHARED_CACHE_PROBES: u64 = 8;
+
+/// Header at the start of a shared cache file
+#[repr(C)]
+struct SharedCacheHeader {
+    magic: AtomicU64,
+    slot_count: AtomicU64,
+    slot_size: AtomicU64,
+    /// Pid of the process holding the write lock, 0 when free
+    writer: AtomicU32,
+}
+
+/// Header of one slot; `seq` is a seqlock that is odd while the slot is being written
+#[repr(C)]
+struct SharedSlotHeader {
+    seq: AtomicU64,
+    hash: AtomicU64,
+    key_len: AtomicU32,
+    value_len: AtomicU32,
+}
+
+/// What a reader found in a slot
+enum SlotRead {
+    Empty,
+    /// Another key's entry, or one that could not be read consistently
+    Other,
+    /// Key and value of an entry with the looked-up hash
+    Match(Vec<u8>, Vec<u8>),
+}
+
+/// Fixed-size hash table of pickled memoize entries in a memory-mapped file
+///
+/// Every process mapping the file sees the same entries. Readers never lock: they
+/// retry when a slot's seqlock changes under them. Writers serialize on a lock in
+/// the header, which is taken over if its holder has died.
+struct SharedCache {
+    map: *mut u8,
+    len: usize,
+    slot_count: u64,
+    slot_size: usize,
+}
+
+// SAFETY: the mapping is only accessed through atomics and seqlock-checked copies, and it
+// stays mapped until the SharedCache is dropped, so any thread may use it.
+unsafe impl Send for SharedCache {}
+unsafe impl Sync for SharedCache {}
+
+impl SharedCache {
+    #[cfg(unix)]
+    fn open(path: &std::path::Path, size: usize, slot_size: usize) -> std::io::Result<SharedCache> {
+        use std::io::{Error, ErrorKind};
+        use std::os::unix::io::AsRawFd;
+
+        let file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        // Size and lay out the file under an exclusive lock, so two processes creating it
+        // at once can't both size it. The mapping keeps the lock's open file alive, so it is
+        // released explicitly once the header is written (or by munmap on an early return).
+        // SAFETY: flock() only takes an advisory lock on a descriptor we hold open
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        if file.metadata()?.len() == 0 {
+            file.set_len(size as u64)?;
+        }
+        let len = file.metadata()?.len() as usize;
+        if len < SHARED_CACHE_HEADER_SIZE + SHARED_SLOT_HEADER_SIZE {
+            return Err(Error::new(ErrorKind::InvalidData, "file is too small to be a shared cache"));
+        }
+
+        // SAFETY: maps `len` bytes of a file we hold open, at an address the kernel picks;
+        // the result is checked for MAP_FAILED before use.
+        let map = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if map == libc::MAP_FAILED {
+            return Err(Error::last_os_error());
+        }
+        let mut cache = SharedCache { map: map as *mut u8, len, slot_count: 0, slot_size: 0 };
+
+        // The first process to map a fresh file lays out the table; one that finds it mid-way
+        // (its creator died holding the lock) waits for it, then gives up
+        let header = cache.header();
+        match header.magic.compare_exchange(0, SHARED_CACHE_INITIALIZING, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => {
+                header.slot_size.store(slot_size as u64, Ordering::Relaxed);
+                header.slot_count.store(((len - SHARED_CACHE_HEADER_SIZE) / slot_size) as u64, Ordering::Relaxed);
+                header.magic.store(SHARED_CACHE_MAGIC, Ordering::Release);
+            }
+            Err(_) => {
+                let start = Instant::now();
+                while header.magic.load(Ordering::Acquire) == SHARED_CACHE_INITIALIZING {
+                    if start.elapsed() > Duration::from_secs(5) {
+                        return Err(Error::new(ErrorKind::TimedOut, "shared cache was never initialized"));
+                    }
+                    thread::sleep(Duration::from_millis(1));
+                }
+                if header.magic.load(Ordering::Acquire) != SHARED_CACHE_MAGIC {
+                    return Err(Error::new(ErrorKind::InvalidData, "not a makeparallel shared cache file"));
+                }
+            }
+        }
+
+        // SAFETY: releases the advisory lock taken above on the same descriptor
+        unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_UN) };
+
+        // An existing file's layout comes from its header, which slot() relies on: every slot
+        // must be aligned for SharedSlotHeader and lie inside the mapping
+        let slot_size = header.slot_size.load(Ordering::Relaxed);
+        let slot_count = header.slot_count.load(Ordering::Relaxed);
+        let table_end = slot_count
+            .checked_mul(slot_size)
+            .and_then(|table| table.checked_add(SHARED_CACHE_HEADER_SIZE as u64));
+        let valid = slot_count > 0
+            && slot_size > SHARED_SLOT_HEADER_SIZE as u64
+            && slot_size.is_multiple_of(SHARED_SLOT_ALIGN as u64)
+            && table_end.is_some_and(|end| end <= len as u64);
+        if !valid {
+            return Err(Error::new(ErrorKind::InvalidData, "corrupt shared cache header"));
+        }
+        cache.slot_size = slot_size as usize;
+        cache.slot_count = slot_count;
+        Ok(cache)
+    }
+
+    #[cfg(not(unix))]
+    fn open(_path: &std::path::Path, _size: usize, _slot_size: usize) -> std::io::Result<SharedCache> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "shared memoize caches are only supported on Unix",
+        ))
+    }
+
+    fn header(&self) -> &SharedCacheHeader {
+        // SAFETY: the mapping is page-aligned and at least SHARED_CACHE_HEADER_SIZE bytes, which
+        // covers the header; its fields are atomics, so other processes writing them is fine.
+        unsafe { &*(self.map as *const SharedCacheHeader) }
+    }
+
+    /// A slot's header and its data area, as bytes other processes may write concurrently
+    fn slot(&self, index: u64) -> (&SharedSlotHeader, &[AtomicU8]) {
+        let offset = SHARED_CACHE_HEADER_SIZE + (index % self.slot_count) as usize * self.slot_size;
+        // SAFETY: open() checked that all slot_count slots end inside the mapping and that
+        // slot_size is a multiple of SHARED_SLOT_ALIGN, so the header is in bounds and aligned
+        // (the mapping and SHARED_CACHE_HEADER_SIZE are aligned too). Header fields and data
+        // bytes are only accessed as atomics, so concurrent writers are not data races.
+        unsafe {
+            let base = self.map.add(offset);
+            let data = base.add(SHARED_SLOT_HEADER_SIZE) as *const AtomicU8;
+            let data = std::slice::from_raw_parts(data, self.slot_size - SHARED_SLOT_HEADER_SIZE);
+            (&*(base as *const SharedSlotHeader), data)
+        }
+    }
+
+    /// FNV-1a, stable across processes; 0 is reserved for empty slots
+    fn hash(key: &[u8]) -> u64 {
+        let hash = key.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        });
+        hash.max(1)
+    }
+
+    /// Read a slot, retrying while a writer changes it under us
+    ///
+    /// Slots holding another hash are not copied. A slot stuck mid-write (its
+    /// writer died) reads as `Other`.
+    fn read_slot(&self, index: u64, hash: u64) -> SlotRead {
+        let (slot, data) = self.slot(index);
+        for _ in 0..16 {
+            let seq = slot.seq.load(Ordering::Acquire);
+            if seq % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let stored_hash = slot.hash.load(Ordering::Relaxed);
+            if stored_hash == 0 {
+                return SlotRead::Empty;
+            }
+            if stored_hash != hash {
+                return SlotRead::Other;
+            }
+            let key_len = slot.key_len.load(Ordering::Relaxed) as usize;
+            let value_len = slot.value_len.load(Ordering::Relaxed) as usize;
+            let Some(stored) = data.get(..key_len + value_len) else {
+                continue;
+            };
+            // A concurrent writer can only make these bytes stale, which the seqlock check catches
+            let mut key: Vec<u8> = stored.iter().map(|byte| byte.load(Ordering::Relaxed)).collect();
+            std::sync::atomic::fence(Ordering::Acquire);
+            if slot.seq.load(Ordering::Relaxed) == seq {
+                let value = key.split_off(key_len);
+                return SlotRead::Match(key, value);
+            }
+        }
+        SlotRead::Other
+    }
+
+    /// Pickled value stored for `key`, without taking any lock
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let hash = Self::hash(key);
+        for probe in 0..SHARED_CACHE_PROBES {
+            match self.read_slot(hash.wrapping_add(probe), hash) {
+                SlotRead::Empty => return None,
+                SlotRead::Match(stored_key, value) if stored_key == key => return Some(value),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Store `value` for `key`; returns false if it does not fit a slot or the lock is stuck
+    fn insert(&self, key: &[u8], value: &[u8]) -> bool {
+        if SHARED_SLOT_HEADER_SIZE + key.len() + value.len() > self.slot_size {
+            return false;
+        }
+        let Some(_lock) = self.lock_writer() else {
+            return false;
+        };
+
+        // Reuse the key's slot or the first empty one; otherwise evict a random probe
+        let hash = Self::hash(key);
+        let index = (0..SHARED_CACHE_PROBES)
+            .map(|probe| hash.wrapping_add(probe))
+            .find(|&index| match self.read_slot(index, hash) {
+                SlotRead::Empty => true,
+                SlotRead::Match(stored_key, _) => stored_key == key,
+                SlotRead::Other => false,
+            })
+            .unwrap_or_else(|| hash.wrapping_add(rand::random::<u64>() % SHARED_CACHE_PROBES));
+
+        let (slot, data) = self.slot(index);
+        // Keeps an odd sequence left by a writer that died mid-write
+        let writing = slot.seq.load(Ordering::Relaxed) | 1;
+        slot.seq.store(writing, Ordering::Relaxed);
+        std::sync::atomic::fence(Ordering::Release);
+        slot.hash.store(hash, Ordering::Relaxed);
+        slot.key_len.store(key.len() as u32, Ordering::Relaxed);
+        slot.value_len.store(value.len() as u32, Ordering::Relaxed);
+        // Key and value fit the data area (checked on entry); the write lock keeps other
+        // writers out and the odd sequence makes readers retry
+        for (cell, byte) in data.iter().zip(key.iter().chain(value)) {
+            cell.store(*byte, Ordering::Relaxed);
+        }
+        slot.seq.store(writing + 1, Ordering::Release);
+        true
+    }
+
+    /// Take the cross-process write lock, giving up after a second
+    fn lock_writer(&self) -> Option<SharedCacheWriteLock<'_>> {
+        let writer = &self.header().writer;
+        let pid = std::process::id();
+        let start = Instant::now();
+        loop {
+            match writer.compare_exchange(0, pid, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return Some(SharedCacheWriteLock(writer)),
+                Err(holder) if !process_alive(holder) => {
+                    if writer.compare_exchange(holder, pid, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+                        warn!("Took over shared cache lock from dead process {}", holder);
+                        return Some(SharedCacheWriteLock(writer));
+                    }
+                }
+                Err(_) => {}
+            }
+            if start.elapsed() > Duration::from_secs(1) {
+                warn!("Shared cache write lock busy, not caching entry");
+                return None;
+            }
+            thread::yield_now();
+        }
+    }
+}
+
+impl Drop for SharedCache {
+    fn drop(&mut self) {
+        // SAFETY: unmaps exactly the region open() mapped; no references into it outlive self
+        #[cfg(unix)]
+        unsafe {
+            libc::munmap(self.map as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+/// Held write lock of a shared cache, released on drop
+struct SharedCacheWriteLock<'a>(&'a AtomicU32);
+
+impl Drop for SharedCacheWriteLock<'_> {
+    fn drop(&mut self) {
+        self.0.store(0, Ordering::Release);
+    }
+}
+
+/// Whether a process with this pid still exists
+fn process_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        // SAFETY: signal 0 only checks whether the process exists, nothing is delivered
+        let alive = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0;
+        alive || std::io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        true
+    }
+}
+
+/// Memoize into a memory-mapped file shared by every process that opens `path`
+///
+/// Use as `@memoize_shared("/dev/shm/app.cache")` so that e.g. all gunicorn workers
+/// share one cache. Results are pickled; results larger than a slot, or that cannot
+/// be pickled, are returned without being cached. The file is created with
+/// `size_mb` megabytes of `slot_size`-byte slots, rounded up to a multiple of 8 bytes.
+#[pyfunction]
+#[pyo3(signature = (path, size_mb=64, slot_size=4096))]
+fn memoize_shared(py: Python, path: PathBuf, size_mb: usize, slot_size: usize) -> PyResult<Py<PyAny>> {
+    if slot_size <= SHARED_SLOT_HEADER_SIZE || size_mb == 0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "size_mb must be positive and slot_size larger than {} bytes",
+            SHARED_SLOT_HEADER_SIZE
+        )));
+    }
+    let size = size_mb * 1024 * 1024;
+    let slot_size = slot_size.next_multiple_of(SHARED_SLOT_ALIGN);
+    let cache = Arc::new(SharedCache::open(&path, size, slot_size).map_err(|e| {
+        pyo3::exceptions::PyOSError::new_err(format!("Failed to open shared cache {}: {}", path.display(), e))
+    })?);
+
+    let decorator = PyCFunction::new_closure(
+        py,
+        None,
+        None,
+        move |args: &Bound<'_, PyTuple>, _kwargs: Option<&Bound<'_, PyDict>>| -> PyResult<Py<PyAny>> {
+            let py = args.py();
+            let func = args.get_item(0)?.unbind();
+            let name = memo_cache_name(func.bind(py));
+            let cache = cache.clone();
+
+            let wrapper = move |args: &Bound<'_, PyTuple>,
+                                kwargs: Option<&Bound<'_, PyDict>>|
+                  -> PyResult<Py<PyAny>> {
+                let py = args.py();
+                let pickle = py.import("pickle")?;
+                let key = format!("{}({})", name, memo_key(args, kwargs)?);
+
+                if let Some(pickled) = cache.get(key.as_bytes()) {
+                    return Ok(pickle.call_method1("loads", (PyBytes::new(py, &pickled),))?.unbind());
+                }
+
+                let result = func.bind(py).call(args, kwargs)?;
+                match pickle.call_method1("dumps", (&result,)).and_then(|p| p.extract::<Vec<u8>>()) {
+                    Ok(pickled) => {
+                        if !py.detach(|| cache.insert(key.as_bytes(), &pickled)) {
+                            debug!("Not caching {}: entry does not fit a {}-byte slot", key, cache.slot_size);
+                        }
+                    }
+                    Err(e) => debug!("Not caching {}: result cannot be pickled: {}", key, e),
+                }
+                Ok(result.unbind())
+            };
+            Ok(PyCFunction::new_closure(py, None, None, wrapper)?.into_any().unbind())
+        },
+    )?;
+    Ok(decorator.into_any().unbind())
+}
+
+// =============================================================================
+// TASK COMPLETION NOTIFICATION
+// =============================================================================
+
+/// Listener run once with the outcome of a task
+type CompletionListener = Box<dyn FnOnce(Python, &PyResult<Py<PyAny>>) + Send>;
+
+/// Outcome of a task shared between its worker and its handle
+///
+/// Listeners subscribed before the task finishes run on the worker thread as
+/// soon as it does; later subscribers run immediately on the caller's thread.
+#[derive(Default)]
+struct TaskCompletion {
+    state: Mutex<CompletionState>,
+    /// Signalled when the task emits a partial result or finishes
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct CompletionState {
+    outcome: Option<PyResult<Py<PyAny>>>,
+    /// Pending listeners, tagged with the id subscribe_removable() handed out
+    listeners: Vec<(u64, CompletionListener)>,
+    next_listener: u64,
+    /// Partial results from emit() not yet consumed by `iter_results()`
+    emitted: VecDeque<Py<PyAny>>,
+    /// Most partial results buffered before emit() blocks; None for unbounded
+    emit_capacity: Option<usize>,
+    /// The consumer stopped iterating; further emit() calls raise StreamClosed
+    stream_closed: bool,
+    /// `async for` deliveries to schedule on their event loop (loop, delivery)
+    /// when a partial result arrives or the task finishes
+    stream_waiters: Vec<(Py<PyAny>, Py<PyAny>)>,
+    /// Sampled frames of a task submitted with `profile=True`, once it has run
+    profile: Option<Vec<FrameProfile>>,
+    /// Allocation growth of a `trace_malloc=True` task, once it has run
+    allocations: Option<Vec<AllocationSite>>,
+    /// CPU seconds the task's thread used running it, once it has run
+    cpu_time: Option<f64>,
+}
+
+fn clone_outcome(py: Python, outcome: &PyResult<Py<PyAny>>) -> PyResult<Py<PyAny>> {
+    match outcome {
+        Ok(val) => Ok(val.clone_ref(py)),
+        Err(e) => Err(e.clone_ref(py)),
+    }
+}
+
+impl TaskCompletion {
+    /// Record the task's outcome and run pending listeners (worker side)
+    fn finish(&self, py: Python, outcome: PyResult<Py<PyAny>>) {
+        let listeners = {
+            let mut state = self.state.lock();
+            if state.outcome.is_some() {
+                return;
+            }
+            state.outcome = Some(clone_outcome(py, &outcome));
+            std::mem::take(&mut state.listeners)
+        };
+        self.changed.notify_all();
+        self.wake_stream_waiters(py);
+        for (_, listener) in listeners {
+            listener(py, &outcome);
+        }
+    }
+
+    /// Queue a partial result for `iter_results()` (worker side)
+    ///
+    /// While the buffer is at capacity this waits, with the GIL released, for the
+    /// consumer to catch up; returns false if `timeout` expired first.
+    fn emit(&self, py: Python, value: Py<PyAny>, timeout: Option<f64>) -> PyResult<bool> {
+        let mut value = Some(value);
+        let queued = wait_for_state(py, &self.state, &self.changed, timeout, |state| {
+            if state.stream_closed {
+                return true;
+            }
+            if state.emit_capacity.is_some_and(|capacity| state.emitted.len() >= capacity) {
+                return false;
+            }
+            state.emitted.extend(value.take());
+            true
+        })?;
+        self.changed.notify_all();
+        self.wake_stream_waiters(py);
+        Ok(queued)
+    }
+
+    /// Schedule pending `async for` deliveries on their event loops
+    fn wake_stream_waiters(&self, py: Python) {
+        let waiters = std::mem::take(&mut self.state.lock().stream_waiters);
+        for (event_loop, delivery) in waiters {
+            // Fails only once the loop is closed, when nobody is left to deliver to
+            if let Err(e) = event_loop.call_method1(py, "call_soon_threadsafe", (delivery,)) {
+                warn!("Could not wake async result stream: {}", e);
+            }
+        }
+    }
+
+    /// Stop accepting partial results and drop the unconsumed ones (consumer side)
+    fn close_stream(&self) {
+        let dropped = {
+            let mut state = self.state.lock();
+            state.stream_closed = true;
+            std::mem::take(&mut state.emitted)
+        };
+        drop(dropped);
+        self.changed.notify_all();
+    }
+
+    fn is_stream_closed(&self) -> bool {
+        self.state.lock().stream_closed
+    }
+
+    /// The task's outcome, if it has finished
+    fn outcome(&self, py: Python) -> Option<PyResult<Py<PyAny>>> {
+        self.state.lock().outcome.as_ref().map(|outcome| clone_outcome(py, outcome))
+    }
+
+    /// Run `listener` once the task has finished
+    fn subscribe(&self, py: Python, listener: CompletionListener) {
+        self.subscribe_removable(py, listener);
+    }
+
+    /// Like subscribe(), returning an id for unsubscribe() if the listener is left pending
+    fn subscribe_removable(&self, py: Python, listener: CompletionListener) -> Option<u64> {
+        let outcome = {
+            let mut state = self.state.lock();
+            match state.outcome {
+                Some(ref outcome) => clone_outcome(py, outcome),
+                None => {
+                    state.next_listener += 1;
+                    let id = state.next_listener;
+                    state.listeners.push((id, listener));
+                    return Some(id);
+                }
+            }
+        };
+        listener(py, &outcome);
+        None
+    }
+
+    /// Drop a listener that has not run yet
+    fn unsubscribe(&self, id: u64) {
+        let removed = {
+            let mut state = self.state.lock();
+            let position = state.listeners.iter().position(|(listener, _)| *listener == id);
+            position.map(|index| state.listeners.remove(index))
+        };
+        drop(removed);
+    }
+}
+
+/// Iterator over a task's emitted partial results, from `handle.iter_results()`
+///
+/// Ends once the task has finished and every emitted value was consumed; if the
+/// task failed, its error is raised instead. Closing it (or dropping it early, as
+/// a `break` does) makes the task's next emit() raise StreamClosed.
+#[pyclass]
+struct ResultStream {
+    completion: Arc<TaskCompletion>,
+    failure_watch: Arc<FailureWatch>,
+    done: bool,
+}
+
+impl Drop for ResultStream {
+    fn drop(&mut self) {
+        if !self.done {
+            self.completion.close_stream();
+        }
+    }
+}
+
+#[pymethods]
+impl ResultStream {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Stop consuming; the producer is cancelled at its next emit()
+    fn close(&mut self) {
+        if !self.done {
+            self.done = true;
+            self.completion.close_stream();
+        }
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        _exc_type: &Bound<'_, PyAny>,
+        _exc_val: &Bound<'_, PyAny>,
+        _exc_tb: &Bound<'_, PyAny>,
+    ) -> bool {
+        self.close();
+        false
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<Py<PyAny>>> {
+        if self.done {
+            return Ok(None);
+        }
+        wait_for_state(py, &self.completion.state, &self.completion.changed, None, |state| {
+            !state.emitted.is_empty() || state.outcome.is_some()
+        })?;
+
+        let mut state = self.completion.state.lock();
+        if let Some(value) = state.emitted.pop_front() {
+            // Wake a producer blocked on a full buffer
+            self.completion.changed.notify_all();
+            return Ok(Some(value));
+        }
+        self.done = true;
+        match state.outcome {
+            Some(Err(ref e)) => {
+                self.failure_watch.mark_observed();
+                Err(e.clone_ref(py))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// `async for` counterpart of ResultStream, from `async for value in handle`
+///
+/// Each `__anext__` returns an asyncio future on the running loop, resolved from
+/// the loop's thread once a partial result arrives, so no thread polls the task.
+#[pyclass]
+struct AsyncResultStream {
+    completion: Arc<TaskCompletion>,
+    failure_watch: Arc<FailureWatch>,
+    done: Arc<AtomicBool>,
+}
+
+impl Drop for AsyncResultStream {
+    fn drop(&mut self) {
+        if !self.done.load(Ordering::Acquire) {
+            self.completion.close_stream();
+        }
+    }
+}
+
+#[pymethods]
+impl AsyncResultStream {
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__(&self, py: Python) -> PyResult<Option<Py<PyAny>>> {
+        if self.done.load(Ordering::Acquire) {
+            return Ok(None);
+        }
+        let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+        let future = event_loop.call_method0("create_future")?;
+        let delivery = Bound::new(
+            py,
+            StreamDelivery {
+                completion: self.completion.clone(),
+                failure_watch: self.failure_watch.clone(),
+                done: self.done.clone(),
+                event_loop: event_loop.unbind(),
+                future: future.clone().unbind(),
+            },
+        )?;
+        StreamDelivery::__call__(&delivery)?;
+        Ok(Some(future.unbind()))
+    }
+
+    /// Stop consuming; the producer is cancelled at its next emit()
+    fn aclose<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        if !self.done.swap(true, Ordering::AcqRel) {
+            self.completion.close_stream();
+        }
+        let future = py.import("asyncio")?.call_method0("get_running_loop")?.call_method0("create_future")?;
+        future.call_method1("set_result", (py.None(),))?;
+        Ok(future)
+    }
+}
+
+/// Resolves one `__anext__` future, run on the consumer's event loop
+#[pyclass]
+struct StreamDelivery {
+    completion: Arc<TaskCompletion>,
+    failure_watch: Arc<FailureWatch>,
+    done: Arc<AtomicBool>,
+    event_loop: Py<PyAny>,
+    future: Py<PyAny>,
+}
+
+#[pymethods]
+impl StreamDelivery {
+    /// Resolve the future if a value or the outcome is available, else wait for the next wake-up
+    fn __call__(slf: &Bound<'_, Self>) -> PyResult<()> {
+        let py = slf.py();
+        let this = slf.borrow();
+        let future = this.future.bind(py);
+        // Cancelled by the consumer, e.g. through asyncio.wait_for()
+        if future.call_method0("done")?.is_truthy()? {
+            return Ok(());
+        }
+
+        let mut state = this.completion.state.lock();
+        if let Some(value) = state.emitted.pop_front() {
+            drop(state);
+            this.completion.changed.notify_all();
+            future.call_method1("set_result", (value,))?;
+        } else if let Some(ref outcome) = state.outcome {
+            let outcome = clone_outcome(py, outcome);
+            drop(state);
+            this.done.store(true, Ordering::Release);
+            let exc = match outcome {
+                Ok(_) => pyo3::exceptions::PyStopAsyncIteration::new_err(()),
+                Err(e) => {
+                    this.failure_watch.mark_observed();
+                    e
+                }
+            };
+            future.call_method1("set_exception", (exc.into_value(py),))?;
+        } else {
+            state.stream_waiters.push((this.event_loop.clone_ref(py), slf.clone().into_any().unbind()));
+        }
+        Ok(())
+    }
+}
+
+/// What a continuation runs for each parent outcome; a missing branch passes the outcome through
+struct Continuation {
+    /// Called with the parent's result (`then()`)
+    on_success: Option<Py<PyAny>>,
+    /// Called with the parent's exception (`catch()`)
+    on_failure: Option<Py<PyAny>>,
+}
+
+/// Worker-side half of a handle created with `AsyncHandle::detached`
+struct TaskCompleter {
+    sender: ResultSender,
+    is_complete: Arc<Mutex<bool>>,
+    completion: Arc<TaskCompletion>,
+    failure_watch: Arc<FailureWatch>,
+}
+
+impl TaskCompleter {
+    /// Deliver the task's outcome to its handle and completion listeners
+    fn complete(&self, py: Python, outcome: PyResult<Py<PyAny>>) {
+        let _ = self.sender.send(clone_outcome(py, &outcome));
+        *self.is_complete.lock() = true;
+        self.completion.finish(py, outcome);
+    }
+}
+
+impl AsyncHandle {
+    /// Handle for a task run by something other than a dedicated thread
+    ///
+    /// The returned completer must be used to deliver the task's outcome.
+    fn detached(func_name: String, tags: Vec<String>, timeout: Option<f64>) -> (AsyncHandle, TaskCompleter) {
+        let (sender, receiver): (ResultSender, ResultReceiver) = channel();
+        let completer = TaskCompleter {
+            sender,
+            is_complete: Arc::new(Mutex::new(false)),
+            completion: Arc::new(TaskCompletion::default()),
+            failure_watch: Arc::new(FailureWatch::default()),
+        };
+        let handle = AsyncHandle {
+            receiver: Arc::new(Mutex::new(receiver)),
+            thread_handle: Arc::new(Mutex::new(None)),
+            is_complete: completer.is_complete.clone(),
+            result_cache: Arc::new(Mutex::new(None)),
+            cancel_token: Arc::new(AtomicBool::new(false)),
+            func_name,
+            start_time: Instant::now(),
+            task_id: format!("task_{}", TASK_ID_COUNTER.fetch_add(1, Ordering::Relaxed)),
+            metadata: initial_metadata(&submission_site()),
+            timeout,
+            total_timeout: None,
+            tags,
+            on_complete: Arc::new(Mutex::new(None)),
+            on_error: Arc::new(Mutex::new(None)),
+            on_progress: Arc::new(Mutex::new(None)),
+            failure_watch: completer.failure_watch.clone(),
+            completion: completer.completion.clone(),
+        };
+        (handle, completer)
+    }
+
+    /// Registry entry describing this handle's task
+    fn active_task(&self) -> ActiveTask {
+        ActiveTask {
+            task_id: self.task_id.clone(),
+            func_name: self.func_name.clone(),
+            tags: self.tags.clone(),
+            cancel_token: self.cancel_token.clone(),
+            is_complete: self.is_complete.clone(),
+            started_at: self.start_time,
+            submitted_from: self.metadata.lock().get("submitted_from").cloned(),
+        }
+    }
+}
+
+/// Create the handle of a continuation that runs once `parent` finishes
+fn chain_continuation(py: Python, parent: &AsyncHandle, continuation: Continuation) -> PyResult<AsyncHandle> {
+    let branches = [&continuation.on_success, &continuation.on_failure];
+    if branches.iter().flat_map(|f| f.as_ref()).any(|f| !f.bind(py).is_callable()) {
+        return Err(pyo3::exceptions::PyTypeError::new_err("continuation must be callable"));
+    }
+    let Some(first) = branches.into_iter().flatten().next() else {
+        return Err(pyo3::exceptions::PyValueError::new_err("a continuation needs on_success or on_failure"));
+    };
+
+    let (handle, completer) = AsyncHandle::detached(
+        callable_name(first.bind(py)),
+        parent.tags.clone(),
+        None,
+    );
+
+    // The parent's error flows into the continuation, so the parent counts as observed
+    parent.failure_watch.mark_observed();
+
+    let child = handle.active_task();
+    parent.completion.subscribe(
+        py,
+        Box::new(move |py, outcome| {
+            let parent_outcome = clone_outcome(py, outcome);
+            // Run on a fresh thread so the parent's worker is not held up
+            py.detach(|| {
+                spawn_task_thread(move || {
+                    Python::attach(|py| {
+                        let outcome = run_continuation(
+                            py,
+                            &child,
+                            &continuation,
+                            parent_outcome,
+                            &completer.failure_watch,
+                        );
+                        completer.complete(py, outcome);
+                    });
+                });
+            });
+        }),
+    );
+
+    Ok(handle)
+}
+
+/// Run a continuation against its parent's outcome
+fn run_continuation(
+    py: Python,
+    task: &ActiveTask,
+    continuation: &Continuation,
+    parent_outcome: PyResult<Py<PyAny>>,
+    failure_watch: &FailureWatch,
+) -> PyResult<Py<PyAny>> {
+    let exec_start = Instant::now();
+    let branch = match parent_outcome {
+        Ok(_) => continuation.on_success.as_ref(),
+        Err(_) => continuation.on_failure.as_ref(),
+    };
+    // Metrics and errors name the branch that runs
+    let task = &ActiveTask {
+        func_name: branch.map_or_else(|| task.func_name.clone(), |func| callable_name(func.bind(py))),
+        ..task.clone()
+    };
+    let task_error = |error_message: String, error_type: String| TaskError {
+        task_name: task.func_name.clone(),
+        elapsed_time: exec_start.elapsed().as_secs_f64(),
+        error_message,
+        error_type,
+        task_id: task.task_id.clone(),
+        submitted_from: task_submission_site(&task.task_id),
+    };
+
+    if task.cancel_token.load(Ordering::Acquire) {
+        let error = task_error("Task was cancelled".to_string(), "CancellationError".to_string());
+        return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(error.__str__()));
+    }
+
+    let Some(func) = branch else {
+        // Nothing to run: the parent's outcome becomes this handle's outcome
+        if let Err(ref e) = parent_outcome {
+            failure_watch.record_failure(py, &task_error(e.to_string(), "UpstreamError".to_string()));
+        }
+        return parent_outcome;
+    };
+    let input = match parent_outcome {
+        Ok(val) => val,
+        Err(e) => e.value(py).clone().into_any().unbind(),
+    };
+
+    register_task(task.clone());
+    mark_task_running(&task.task_id);
+    let cpu_clock = CpuClock::start();
+    let result = func.bind(py).call1((input.bind(py),));
+    let cpu_ms = cpu_clock.elapsed_ms();
+    let exec_time = exec_start.elapsed().as_secs_f64() * 1000.0;
+    unregister_task(&task.task_id);
+
+    match result {
+        Ok(val) => {
+            record_task_execution(&task.func_name, exec_time, true, cpu_ms);
+            publish_task_event(
+                "task_completed",
+                &task.task_id,
+                &task.func_name,
+                completed_event_details(py, &val, exec_time / 1000.0),
+            );
+            Ok(val.unbind())
+        }
+        Err(e) => {
+            record_task_execution(&task.func_name, exec_time, false, cpu_ms);
+            let error_type = task_error_type(py, &e);
+            let error = task_error(e.to_string(), error_type);
+            report_task_failure(py, failure_watch, &error, None);
+            Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(error.__str__()))
+        }
+    }
+}
+
+/// Schedule one follow-up task depending on how `handle`'s task ends
+///
+/// `on_success(result)` runs if it succeeds and `on_failure(error)` if it fails;
+/// the other is never called. The returned handle carries the continuation's
+/// outcome, or the original outcome when the matching branch is not given.
+#[pyfunction]
+#[pyo3(signature = (handle, *, on_success=None, on_failure=None))]
+fn submit_after(
+    py: Python,
+    handle: PyRef<'_, AsyncHandle>,
+    on_success: Option<Py<PyAny>>,
+    on_failure: Option<Py<PyAny>>,
+) -> PyResult<AsyncHandle> {
+    chain_continuation(py, &handle, Continuation { on_success, on_failure })
+}
+
+// 6. Parallel Decorator - Run functions in Rust threads without GIL
+
+/// AsyncHandle - Handle for async operations with pipe communication
+#[pyclass]
+struct AsyncHandle {
+    receiver: Arc<Mutex<ResultReceiver>>,
+    thread_handle: Arc<Mutex<Option<JoinHandle<()>>>>,
+    is_complete: Arc<Mutex<bool>>,
+    result_cache: Arc<Mutex<Option<PyResult<Py<PyAny>>>>>,
+    cancel_token: Arc<AtomicBool>,
+    func_name: String,
+    start_time: Instant,
+    task_id: String,
+    metadata: Arc<Mutex<HashMap<String, String>>>,
+    timeout: Option<f64>,
+    total_timeout: Option<f64>,
+    tags: Vec<String>,
+    on_complete: CallbackFunc,
+    on_error: CallbackFunc,
+    on_progress: CallbackFunc,
+    failure_watch: Arc<FailureWatch>,
+    completion: Arc<TaskCompletion>,
+}
+
+impl Drop for AsyncHandle {
+    fn drop(&mut self) {
+        Python::attach(|py| self.failure_watch.release(py));
+    }
+}
+
+#[pymethods]
+impl AsyncHandle {
+    /// Check if the result is ready (non-blocking)
+    fn is_ready(&self) -> PyResult<bool> {
+        Ok(*self.is_complete.lock())
+    }
+
+    /// Try to get the result without blocking (returns None if not ready)
+    fn try_get(&self, py: Python) -> PyResult<Option<Py<PyAny>>> {
+        // Check cache first
+        let mut cache = self.result_cache.lock();
+        if let Some(ref cached) = *cache {
+            return match cached {
+                Ok(val) => Ok(Some(val.clone_ref(py))),
+                Err(e) => {
+                    self.failure_watch.mark_observed();
+                    Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                        "Cached error: {}",
+                        e
+                    )))
+                }
+            };
+        }
+
+        // Try to receive without blocking
+        let receiver = self.receiver.lock();
+        match receiver.try_recv() {
+            Ok(result) => {
+                *self.is_complete.lock() = true;
+                match result {
+                    Ok(val) => {
+                        *cache = Some(Ok(val.clone_ref(py)));
+                        Ok(Some(val))
+                    }
+                    Err(e) => {
+                        self.failure_watch.mark_observed();
+                        *cache = Some(Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                            e.to_string(),
+                        )));
+                        Err(e)
+                    }
+                }
+            }
+            Err(_) => Ok(None), // Not ready yet
+        }
+    }
+
+    /// Get the result (blocking until ready)
+    fn get(&self, py: Python) -> PyResult<Py<PyAny>> {
+        // Check cache first
+        let cache = self.result_cache.lock();
+        if let Some(ref cached) = *cache {
+            return match cached {
+                Ok(val) => Ok(val.clone_ref(py)),
+                Err(e) if e.is_instance_of::<DeadlineExceeded>(py) => {
+                    self.failure_watch.mark_observed();
+                    Err(e.clone_ref(py))
+                }
+                Err(e) => {
+                    self.failure_watch.mark_observed();
+                    Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                        "Cached error: {}",
+                        e
+                    )))
+                }
+            };
+        }
+        drop(cache); // Release lock before blocking recv
+
+        // CRITICAL: Release GIL before blocking on recv to avoid deadlock
+        let result = py
+            .detach(|| {
+                let receiver = self.receiver.lock();
+                receiver.recv()
+            })
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        *self.is_complete.lock() = true;
+
+        // Cache the result and trigger callbacks
+        let mut cache = self.result_cache.lock();
+        match result {
+            Ok(ref val) => {
+                *cache = Some(Ok(val.clone_ref(py)));
+
+                // CRITICAL FIX: Proper callback error handling
+                if let Some(ref callback) = *self.on_complete.lock() {
+                    match callback.bind(py).call1((val.bind(py),)) {
+                        Ok(_) => {},
+                        Err(e) => {
+                            error!("on_complete callback failed: {}", e);
+                            // Don't propagate callback errors to task result
+                        }
+                    }
+                }
+
+                Ok(val.clone_ref(py))
+            }
+            Err(e) => {
+                self.failure_watch.mark_observed();
+                let err_str = e.to_string();
+                // The scheduler's own errors keep their type so callers can catch them
+                let raised = if e.is_instance_of::<DeadlineExceeded>(py) {
+                    e
+                } else {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(err_str.clone())
+                };
+                *cache = Some(Err(raised.clone_ref(py)));
+
+                // CRITICAL FIX: Proper error callback handling
+                if let Some(ref callback) = *self.on_error.lock() {
+                    match callback.bind(py).call1((err_str.clone(),)) {
+                        Ok(_) => {},
+                        Err(e) => {
+                            error!("on_error callback failed: {}", e);
+                        }
+                    }
+                }
+
+                Err(raised)
+            }
+        }
+    }
+
+    /// Get the result (blocking until ready), checking it has the expected type
+    ///
+    /// `expected` is one of int, float, str, bytes or list. An int is converted
+    /// when a float is expected; bools are not accepted as ints.
+    fn get_as(&self, py: Python, expected: &Bound<'_, PyType>) -> PyResult<Py<PyAny>> {
+        let value = self.get(py)?.into_bound(py);
+        let is_int = value.is_instance_of::<PyInt>() && !value.is_instance_of::<PyBool>();
+
+        let matches = if expected.is(py.get_type::<PyInt>()) {
+            is_int
+        } else if expected.is(py.get_type::<PyFloat>()) {
+            if is_int {
+                return Ok(PyFloat::new(py, value.extract::<f64>()?).into_any().unbind());
+            }
+            value.is_instance_of::<PyFloat>()
+        } else if expected.is(py.get_type::<PyString>()) {
+            value.is_instance_of::<PyString>()
+        } else if expected.is(py.get_type::<PyBytes>()) {
+            value.is_instance_of::<PyBytes>()
+        } else if expected.is(py.get_type::<PyList>()) {
+            value.is_instance_of::<PyList>()
+        } else {
+            return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                "get_as() supports int, float, str, bytes and list, got {}",
+                expected.name()?
+            )));
+        };
+
+        if !matches {
+            // Keep the message readable when the task returned something large
+            let repr = value.repr()?.to_cow()?.into_owned();
+            let mut shown: String = repr.chars().take(80).collect();
+            if shown.len() < repr.len() {
+                shown.push_str("...");
+            }
+            return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                "Task '{}' ({}) returned {}, expected {}: {}",
+                self.func_name,
+                self.task_id,
+                value.get_type().name()?,
+                expected.name()?,
+                shown
+            )));
+        }
+        Ok(value.unbind())
+    }
+
+    /// Wait for completion with timeout (in seconds)
+    ///
+    /// Returns as soon as the task finishes; the result stays available to get().
+    /// Waits on the completion's condvar, so repeated timed-out calls leave nothing behind.
+    fn wait(&self, py: Python, timeout_secs: Option<f64>) -> PyResult<bool> {
+        if *self.is_complete.lock() {
+            return Ok(true);
+        }
+        validate_timeouts(timeout_secs, None)?;
+
+        let completion = &self.completion;
+        wait_for_state(py, &completion.state, &completion.changed, timeout_secs, |state| state.outcome.is_some())
+    }
+
+    /// Cancel the operation (non-blocking - just sets the flag)
+    fn cancel(&self) -> PyResult<()> {
+        // Set cancellation flag with Release ordering
+        self.cancel_token.store(true, Ordering::Release);
+
+        // Mark as complete to prevent further waits
+        *self.is_complete.lock() = true;
+
+        // Don't join the thread - that would block!
+        // The thread will check the flag and exit on its own
+        Ok(())
+    }
+
+    /// Cancel with timeout (in seconds)
+    fn cancel_with_timeout(&self, timeout_secs: f64) -> PyResult<bool> {
+        self.cancel_token.store(true, Ordering::Release);
+
+        let mut handle = self.thread_handle.lock();
+        if let Some(h) = handle.take() {
+            let start = Instant::now();
+            let timeout = Duration::from_secs_f64(timeout_secs);
+
+            // Try to join with timeout
+            while start.elapsed() < timeout {
+                if h.is_finished() {
+                    let _ = h.join();
+                    return Ok(true);
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+
+            return Ok(false); // Timeout
+        }
+        Ok(true)
+    }
+
+    /// Check if task was cancelled
+    fn is_cancelled(&self) -> PyResult<bool> {
+        Ok(self.cancel_token.load(Ordering::Acquire))
+    }
+
+    /// Where the task is in its lifecycle, as a TaskStatus
+    ///
+    /// A successful cancel() is final even if the function still returns a value.
+    fn status(&self, py: Python) -> TaskStatus {
+        let recorded = self.failure_watch.state.lock().status;
+        let cancelled = self.cancel_token.load(Ordering::Acquire) && *self.is_complete.lock();
+        let state = self.completion.state.lock();
+        match (&state.outcome, recorded) {
+            // Timeouts also set the cancel token
+            (Some(Err(_)), Some(TaskStatus::TimedOut)) => TaskStatus::TimedOut,
+            _ if cancelled => TaskStatus::Cancelled,
+            (Some(Ok(_)), _) => TaskStatus::Succeeded,
+            (Some(Err(_)), Some(status)) => status,
+            // Paths that end without a TaskError (actors, timers) raise the exception itself
+            (Some(Err(e)), None) if e.is_instance_of::<TaskCancelled>(py) => TaskStatus::Cancelled,
+            (Some(Err(e)), None) if e.is_instance_of::<TaskTimeout>(py) => TaskStatus::TimedOut,
+            (Some(Err(_)), None) => TaskStatus::Failed,
+            (None, _) => {
+                drop(state);
+                unfinished_status(&self.task_id, &self.cancel_token, &self.is_complete)
+            }
+        }
+    }
+
+    /// Get elapsed time since task start (in seconds)
+    fn elapsed_time(&self) -> PyResult<f64> {
+        Ok(self.start_time.elapsed().as_secs_f64())
+    }
+
+    /// Get task name
+    fn get_name(&self) -> PyResult<String> {
+        Ok(self.func_name.clone())
+    }
+
+    /// Get task ID
+    fn get_task_id(&self) -> PyResult<String> {
+        Ok(self.task_id.clone())
+    }
+
+    /// Pause the task at its next checkpoint() call, until resume()
+    ///
+    /// Only task code that calls checkpoint() can be paused. Returns False if the
+    /// task has already finished.
+    fn pause(&self) -> bool {
+        !*self.is_complete.lock() && pause_task(&self.task_id)
+    }
+
+    /// Let a paused task continue; returns False if it was not paused
+    fn resume(&self) -> bool {
+        resume_task(&self.task_id)
+    }
+
+    /// The last state the task saved with save_checkpoint() under its task ID, or None
+    fn get_checkpoint(&self, py: Python) -> PyResult<Py<PyAny>> {
+        load_checkpoint(py, Some(self.task_id.clone()), None)
+    }
+
+    /// Whether pause() has been called without a matching resume()
+    fn is_paused(&self) -> bool {
+        PAUSED_TASKS.lock().contains_key(&self.task_id)
+    }
+
+    /// Change the priority of this task while it waits in the priority queue
+    ///
+    /// Returns False once the task has started, or if it was not a priority task.
+    fn set_priority(&self, new_priority: i32) -> bool {
+        reprioritize(&self.task_id, new_priority)
+    }
+
+    /// Set metadata
+    fn set_metadata(&self, key: String, value: String) -> PyResult<()> {
+        self.metadata.lock().insert(key, value);
+        Ok(())
+    }
+
+    /// Get metadata
+    fn get_metadata(&self, key: String) -> PyResult<Option<String>> {
+        Ok(self.metadata.lock().get(&key).cloned())
+    }
+
+    /// Get all metadata
+    fn get_all_metadata(&self, py: Python) -> PyResult<Py<PyDict>> {
+        let dict = PyDict::new(py);
+        let metadata = self.metadata.lock();
+        for (k, v) in metadata.iter() {
+            dict.set_item(k, v)?;
+        }
+        Ok(dict.unbind())
+    }
+
+    /// Get timeout value (bound on each attempt)
+    fn get_timeout(&self) -> PyResult<Option<f64>> {
+        Ok(self.timeout)
+    }
+
+    /// Get total timeout value (bound on all attempts including backoff)
+    fn get_total_timeout(&self) -> PyResult<Option<f64>> {
+        Ok(self.total_timeout)
+    }
+
+    /// Get tags attached at submission
+    fn get_tags(&self) -> PyResult<Vec<String>> {
+        Ok(self.tags.clone())
+    }
+
+    /// Set completion callback
+    fn on_complete(&self, callback: Py<PyAny>) -> PyResult<()> {
+        *self.on_complete.lock() = Some(callback);
+        Ok(())
+    }
+
+    /// Set error callback
+    fn on_error(&self, callback: Py<PyAny>) -> PyResult<()> {
+        *self.on_error.lock() = Some(callback);
+        Ok(())
+    }
+
+    /// Set progress callback
+    fn on_progress(&self, py: Python, callback: Py<PyAny>) -> PyResult<()> {
+        *self.on_progress.lock() = Some(callback.clone_ref(py));
+        register_progress_callback(self.task_id.clone(), callback);
+        Ok(())
+    }
+
+    /// Get current progress (0.0 to 1.0)
+    fn get_progress(&self) -> PyResult<f64> {
+        Ok(TASK_PROGRESS_MAP
+            .get(&self.task_id)
+            .map(|p| *p)
+            .unwrap_or(0.0))
+    }
+
+    /// Push the result into a multiprocessing queue or connection once the task finishes
+    ///
+    /// Anything with `put()` (queues) or `send()` (pipe connections) is accepted; the
+    /// value is pickled by the target. A failed task forwards its RuntimeError instance.
+    fn forward_to(&self, py: Python, target: Py<PyAny>) -> PyResult<()> {
+        let bound = target.bind(py);
+        let method = if bound.hasattr("put")? {
+            "put"
+        } else if bound.hasattr("send")? {
+            "send"
+        } else {
+            return Err(pyo3::exceptions::PyTypeError::new_err(
+                "forward_to() target must have a put() or send() method",
+            ));
+        };
+
+        // A forwarded error reaches the consumer, so it never counts as unobserved
+        self.failure_watch.mark_observed();
+        let task_id = self.task_id.clone();
+        self.completion.subscribe(
+            py,
+            Box::new(move |py, outcome| {
+                let value = match outcome {
+                    Ok(val) => val.clone_ref(py),
+                    Err(e) => e.value(py).clone().into_any().unbind(),
+                };
+                if let Err(e) = target.call_method1(py, method, (value,)) {
+                    error!("Failed to forward result of task {}: {}", task_id, e);
+                }
+            }),
+        );
+        Ok(())
+    }
+
+    /// Python functions the task spent its time in, from a call with `profile=True`
+    ///
+    /// Returns up to `limit` dicts sorted by self time (seconds with the function at
+    /// the top of the stack), or None if the task was not profiled or is still running.
+    #[pyo3(signature = (limit=20))]
+    fn get_profile(&self, py: Python, limit: usize) -> PyResult<Option<Py<PyList>>> {
+        let Some(profile) = self.completion.state.lock().profile.clone() else {
+            return Ok(None);
+        };
+        let frames = PyList::empty(py);
+        for frame in profile.into_iter().take(limit) {
+            let entry = PyDict::new(py);
+            entry.set_item("function", frame.function)?;
+            entry.set_item("filename", frame.filename)?;
+            entry.set_item("line", frame.line)?;
+            entry.set_item("self_time", frame.self_time)?;
+            entry.set_item("total_time", frame.total_time)?;
+            entry.set_item("samples", frame.samples)?;
+            frames.append(entry)?;
+        }
+        Ok(Some(frames.unbind()))
+    }
+
+    /// CPU seconds the task's thread spent running it
+    ///
+    /// None until the task has run, and for tasks run in another process. Much less
+    /// than the wall time means the task mostly waited: on I/O, locks or the GIL.
+    fn cpu_time(&self) -> Option<f64> {
+        self.completion.state.lock().cpu_time
+    }
+
+    /// Source lines whose allocations grew the most during a `trace_malloc=True` call
+    ///
+    /// Returns up to `limit` dicts with `filename`, `line`, `size_diff` and
+    /// `count_diff` (net bytes and blocks since the call started) and `size` (bytes
+    /// still held), or None if the task was not traced or is still running.
+    #[pyo3(signature = (limit=10))]
+    fn get_allocations(&self, py: Python, limit: usize) -> PyResult<Option<Py<PyList>>> {
+        let Some(allocations) = self.completion.state.lock().allocations.clone() else {
+            return Ok(None);
+        };
+        let sites = PyList::empty(py);
+        for site in allocations.into_iter().take(limit) {
+            let entry = PyDict::new(py);
+            entry.set_item("filename", site.filename)?;
+            entry.set_item("line", site.line)?;
+            entry.set_item("size_diff", site.size_diff)?;
+            entry.set_item("count_diff", site.count_diff)?;
+            entry.set_item("size", site.size)?;
+            sites.append(entry)?;
+        }
+        Ok(Some(sites.unbind()))
+    }
+
+    /// Iterate over the partial results the task passes to `emit()`
+    ///
+    /// Values are yielded as they arrive, each to one consumer. Iteration ends when
+    /// the task finishes, raising its error if it failed.
+    fn iter_results(&self) -> ResultStream {
+        ResultStream {
+            completion: self.completion.clone(),
+            failure_watch: self.failure_watch.clone(),
+            done: false,
+        }
+    }
+
+    /// `async for value in handle:` iterates the task's emitted partial results
+    fn __aiter__(&self) -> AsyncResultStream {
+        AsyncResultStream {
+            completion: self.completion.clone(),
+            failure_watch: self.failure_watch.clone(),
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Reader handle that receives this task's outcome without running it again
+    ///
+    /// Every reader gets the result (or error) independently, so any number of
+    /// consumers can wait on one task; cancelling a reader leaves the task running.
+    fn subscribe(&self, py: Python) -> AsyncHandle {
+        let (mut reader, completer) = AsyncHandle::detached(self.func_name.clone(), self.tags.clone(), None);
+        reader.task_id = self.task_id.clone();
+        reader.start_time = self.start_time;
+
+        // Readers receive the error, so the task's failure counts as observed
+        self.failure_watch.mark_observed();
+        self.completion.subscribe(
+            py,
+            Box::new(move |py, outcome| completer.complete(py, clone_outcome(py, outcome))),
+        );
+        reader
+    }
+
+    /// Schedule `func(result)` to run when this task succeeds, returning its handle
+    ///
+    /// If this task fails, the returned handle fails with the same error and
+    /// `func` is not called.
+    fn then(&self, py: Python, func: Py<PyAny>) -> PyResult<AsyncHandle> {
+        chain_continuation(py, self, Continuation { on_success: Some(func), on_failure: None })
+    }
+
+    /// Schedule `handler(error)` to run if this task fails, returning its handle
+    ///
+    /// The handler's return value becomes the result; if this task succeeds its
+    /// result passes through unchanged.
+    fn catch(&self, py: Python, handler: Py<PyAny>) -> PyResult<AsyncHandle> {
+        chain_continuation(py, self, Continuation { on_success: None, on_failure: Some(handler) })
+    }
+}
+
+/// Transparent stand-in for a task's result, returned by `@parallel` calls with `lazy=True`
+///
+/// The first attribute, operator or protocol access blocks on the task's `get()`;
+/// everything is then forwarded to the real result.
+#[pyclass]
+struct LazyResult {
+    handle: Py<AsyncHandle>,
+}
+
+impl LazyResult {
+    /// Block until the task finishes and return its result
+    fn resolve<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        self.handle.bind(py).call_method0("get")
+    }
+
+    /// Apply a function from the `operator` module with the result on either side
+    fn binary(&self, py: Python, op: &str, other: &Bound<'_, PyAny>, reflected: bool) -> PyResult<Py<PyAny>> {
+        let value = self.resolve(py)?;
+        let operator = py.import("operator")?;
+        let result = if reflected {
+            operator.call_method1(op, (other, value))?
+        } else {
+            operator.call_method1(op, (value, other))?
+        };
+        Ok(result.unbind())
+    }
+
+    fn unary(&self, py: Python, op: &str) -> PyResult<Py<PyAny>> {
+        let value = self.resolve(py)?;
+        Ok(py.import("operator")?.call_method1(op, (value,))?.unbind())
+    }
+}
+
+#[pymethods]
+impl LazyResult {
+    /// Report the result's class so isinstance() sees through the proxy
+    #[getter(__class__)]
+    fn class<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyType>> {
+        Ok(self.resolve(py)?.get_type())
+    }
+
+    fn __getattr__<'py>(&self, py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyAny>> {
+        self.resolve(py)?.getattr(name)
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(self.resolve(py)?.repr()?.to_string())
+    }
+
+    fn __str__(&self, py: Python) -> PyResult<String> {
+        Ok(self.resolve(py)?.str()?.to_string())
+    }
+
+    fn __format__(&self, py: Python, spec: &str) -> PyResult<Py<PyAny>> {
+        Ok(self.resolve(py)?.call_method1("__format__", (spec,))?.unbind())
+    }
+
+    fn __bool__(&self, py: Python) -> PyResult<bool> {
+        self.resolve(py)?.is_truthy()
+    }
+
+    fn __len__(&self, py: Python) -> PyResult<usize> {
+        self.resolve(py)?.len()
+    }
+
+    fn __hash__(&self, py: Python) -> PyResult<isize> {
+        self.resolve(py)?.hash()
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        self.resolve(py)?.try_iter()
+    }
+
+    fn __contains__(&self, py: Python, item: &Bound<'_, PyAny>) -> PyResult<bool> {
+        self.resolve(py)?.contains(item)
+    }
+
+    fn __getitem__<'py>(&self, py: Python<'py>, key: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+        self.resolve(py)?.get_item(key)
+    }
+
+    fn __setitem__(&self, py: Python, key: &Bound<'_, PyAny>, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.resolve(py)?.set_item(key, value)
+    }
+
+    fn __delitem__(&self, py: Python, key: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.resolve(py)?.del_item(key)
+    }
+
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__<'py>(
+        &self,
+        py: Python<'py>,
+        args: &Bound<'py, PyTuple>,
+        kwargs: Option<&Bound<'py, PyDict>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        self.resolve(py)?.call(args, kwargs)
+    }
+
+    fn __richcmp__(&self, py: Python, other: &Bound<'_, PyAny>, op: CompareOp) -> PyResult<Py<PyAny>> {
+        Ok(self.resolve(py)?.rich_compare(other, op)?.unbind())
+    }
+
+    fn __int__(&self, py: Python) -> PyResult<Py<PyAny>> {
+        Ok(py.get_type::<PyInt>().call1((self.resolve(py)?,))?.unbind())
+    }
+
+    fn __float__(&self, py: Python) -> PyResult<f64> {
+        self.resolve(py)?.extract()
+    }
+
+    fn __index__(&self, py: Python) -> PyResult<Py<PyAny>> {
+        self.unary(py, "index")
+    }
+
+    fn __neg__(&self, py: Python) -> PyResult<Py<PyAny>> {
+        self.unary(py, "neg")
+    }
+
+    fn __pos__(&self, py: Python) -> PyResult<Py<PyAny>> {
+        self.unary(py, "pos")
+    }
+
+    fn __abs__(&self, py: Python) -> PyResult<Py<PyAny>> {
+        self.unary(py, "abs")
+    }
+
+    fn __invert__(&self, py: Python) -> PyResult<Py<PyAny>> {
+        self.unary(py, "invert")
+    }
+
+    fn __pow__(&self, py: Python, other: &Bound<'_, PyAny>, modulo: Option<&Bound<'_, PyAny>>) -> PyResult<Py<PyAny>> {
+        let value = self.resolve(py)?;
+        let builtins = py.import("builtins")?;
+        Ok(builtins.call_method1("pow", (value, other, modulo))?.unbind())
+    }
+
+    fn __rpow__(&self, py: Python, other: &Bound<'_, PyAny>, _modulo: Option<&Bound<'_, PyAny>>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "pow", other, true)
+    }
+
+    fn __add__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "add", other, false)
+    }
+
+    fn __radd__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "add", other, true)
+    }
+
+    fn __sub__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "sub", other, false)
+    }
+
+    fn __rsub__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "sub", other, true)
+    }
+
+    fn __mul__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "mul", other, false)
+    }
+
+    fn __rmul__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "mul", other, true)
+    }
+
+    fn __matmul__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "matmul", other, false)
+    }
+
+    fn __rmatmul__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "matmul", other, true)
+    }
+
+    fn __truediv__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "truediv", other, false)
+    }
+
+    fn __rtruediv__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "truediv", other, true)
+    }
+
+    fn __floordiv__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "floordiv", other, false)
+    }
+
+    fn __rfloordiv__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "floordiv", other, true)
+    }
+
+    fn __mod__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "mod", other, false)
+    }
+
+    fn __rmod__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "mod", other, true)
+    }
+
+    fn __lshift__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "lshift", other, false)
+    }
+
+    fn __rlshift__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "lshift", other, true)
+    }
+
+    fn __rshift__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "rshift", other, false)
+    }
+
+    fn __rrshift__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "rshift", other, true)
+    }
+
+    fn __and__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "and_", other, false)
+    }
+
+    fn __rand__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "and_", other, true)
+    }
+
+    fn __or__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "or_", other, false)
+    }
+
+    fn __ror__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "or_", other, true)
+    }
+
+    fn __xor__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "xor", other, false)
+    }
+
+    fn __rxor__(&self, py: Python, other: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.binary(py, "xor", other, true)
+    }
+}
+
+// =============================================================================
+// TASK PROFILING
+// =============================================================================
+
+/// Time between stack samples of a profiled task
+const PROFILE_SAMPLE_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Time a profiled task spent in one Python function
+#[derive(Clone)]
+struct FrameProfile {
+    function: String,
+    filename: String,
+    line: u32,
+    /// Seconds with this function at the top of the stack
+    self_time: f64,
+    /// Seconds with this function anywhere on the stack
+    total_time: f64,
+    samples: u64,
+}
+
+/// Samples the Python stack of one task's thread from a background thread
+///
+/// Like py-spy, but in-process: every few milliseconds the sampler reads the
+/// thread's current frame via `sys._current_frames()` and charges the time since
+/// the previous sample to the functions on the stack.
+struct TaskSampler {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<HashMap<(String, String, u32), FrameProfile>>,
+}
+
+impl TaskSampler {
+    /// Start sampling the calling thread, or None when threads are unavailable
+    fn start(py: Python) -> Option<TaskSampler> {
+        if sync_fallback() {
+            return None;
+        }
+        let ident: u64 = py.import("threading").and_then(|t| t.call_method0("get_ident")?.extract()).ok()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = stop.clone();
+        let thread = thread::spawn(move || {
+            let mut frames = HashMap::new();
+            let mut last = Instant::now();
+            while !stop_flag.load(Ordering::Acquire) {
+                thread::sleep(PROFILE_SAMPLE_INTERVAL);
+                Python::attach(|py| {
+                    let now = Instant::now();
+                    let elapsed = now.duration_since(last).as_secs_f64();
+                    last = now;
+                    if let Err(e) = sample_stack(py, ident, elapsed, &mut frames) {
+                        debug!("Failed to sample task thread {}: {}", ident, e);
+                    }
+                });
+            }
+            frames
+        });
+        Some(TaskSampler { stop, thread })
+    }
+
+    /// Stop sampling and return the functions seen, by self time
+    fn finish(self, py: Python) -> Vec<FrameProfile> {
+        self.stop.store(true, Ordering::Release);
+        let frames = py.detach(|| self.thread.join()).unwrap_or_default();
+        let mut profile: Vec<FrameProfile> = frames.into_values().collect();
+        profile.sort_by(|a, b| b.self_time.total_cmp(&a.self_time).then(b.total_time.total_cmp(&a.total_time)));
+        profile
+    }
+}
+
+/// Charge `elapsed` seconds to the functions on thread `ident`'s current stack
+fn sample_stack(
+    py: Python,
+    ident: u64,
+    elapsed: f64,
+    frames: &mut HashMap<(String, String, u32), FrameProfile>,
+) -> PyResult<()> {
+    let current = py.import("sys")?.call_method0("_current_frames")?;
+    let Some(mut frame) = current.cast::<PyDict>()?.get_item(ident)? else {
+        return Ok(());
+    };
+    let mut seen = Vec::new();
+    let mut top = true;
+    while !frame.is_none() {
+        let code = frame.getattr("f_code")?;
+        let key: (String, String, u32) = (
+            code.getattr("co_name")?.extract()?,
+            code.getattr("co_filename")?.extract()?,
+            code.getattr("co_firstlineno")?.extract()?,
+        );
+        let entry = frames.entry(key.clone()).or_insert_with(|| FrameProfile {
+            function: key.0.clone(),
+            filename: key.1.clone(),
+            line: key.2,
+            self_time: 0.0,
+            total_time: 0.0,
+            samples: 0,
+        });
+        if top {
+            entry.self_time += elapsed;
+            entry.samples += 1;
+            top = false;
+        }
+        // Recursive calls count once towards total time
+        if !seen.contains(&key) {
+            entry.total_time += elapsed;
+            seen.push(key);
+        }
+        frame = frame.getattr("f_back")?;
+    }
+    Ok(())
+}
+
+/// Most allocation sites kept per traced task
+const MAX_ALLOCATION_SITES: usize = 50;
+
+/// Memory a traced task allocated at one source line, net of what it freed
+#[derive(Clone)]
+struct AllocationSite {
+    filename: String,
+    line: u32,
+    size_diff: i64,
+    count_diff: i64,
+    /// Bytes still allocated from this line when the task finished
+    size: u64,
+}
+
+/// Running traced tasks, and whether tracemalloc was started on their behalf
+static MALLOC_TRACERS: Lazy<Mutex<(usize, bool)>> = Lazy::new(|| Mutex::new((0, false)));
+
+/// tracemalloc snapshot taken before a `trace_malloc=True` task runs
+///
+/// tracemalloc is process-wide: it is started for the first traced task and
+/// stopped after the last one, unless the application was already tracing.
+/// Allocations by other threads while the task runs appear in its diff too.
+struct MallocTrace {
+    before: Py<PyAny>,
+}
+
+impl MallocTrace {
+    /// Start tracing around the current task, or None if tracemalloc is unavailable
+    fn start(py: Python) -> Option<MallocTrace> {
+        let tracemalloc = py.import("tracemalloc").ok()?;
+        {
+            let mut tracers = MALLOC_TRACERS.lock();
+            if tracers.0 == 0 {
+                let tracing = tracemalloc.call_method0("is_tracing").and_then(|t| t.is_truthy()).unwrap_or(true);
+                tracers.1 = !tracing && tracemalloc.call_method0("start").is_ok();
+            }
+            tracers.0 += 1;
+        }
+        match traced_snapshot(&tracemalloc) {
+            Ok(before) => Some(MallocTrace { before: before.unbind() }),
+            Err(e) => {
+                warn!("Failed to snapshot tracemalloc: {}", e);
+                release_malloc_tracer(&tracemalloc);
+                None
+            }
+        }
+    }
+
+    /// Diff the allocations since start(), largest growth first
+    fn finish(self, py: Python) -> Vec<AllocationSite> {
+        let Ok(tracemalloc) = py.import("tracemalloc") else {
+            return Vec::new();
+        };
+        let sites = self.diff(&tracemalloc).unwrap_or_else(|e| {
+            warn!("Failed to compare tracemalloc snapshots: {}", e);
+            Vec::new()
+        });
+        release_malloc_tracer(&tracemalloc);
+        sites
+    }
+
+    fn diff(&self, tracemalloc: &Bound<'_, PyModule>) -> PyResult<Vec<AllocationSite>> {
+        let after = traced_snapshot(tracemalloc)?;
+        let stats = after.call_method1("compare_to", (self.before.bind(tracemalloc.py()), "lineno"))?;
+        let mut sites = Vec::new();
+        for stat in stats.try_iter()? {
+            let stat = stat?;
+            let size_diff: i64 = stat.getattr("size_diff")?.extract()?;
+            if size_diff == 0 {
+                continue;
+            }
+            let frame = stat.getattr("traceback")?.get_item(0)?;
+            sites.push(AllocationSite {
+                filename: frame.getattr("filename")?.extract()?,
+                line: frame.getattr("lineno")?.extract()?,
+                size_diff,
+                count_diff: stat.getattr("count_diff")?.extract()?,
+                size: stat.getattr("size")?.extract()?,
+            });
+            if sites.len() == MAX_ALLOCATION_SITES {
+                break;
+            }
+        }
+        Ok(sites)
+    }
+}
+
+/// A tracemalloc snapshot without tracemalloc's and the import system's own allocations
+fn traced_snapshot<'py>(tracemalloc: &Bound<'py, PyModule>) -> PyResult<Bound<'py, PyAny>> {
+    let filter = tracemalloc.getattr("Filter")?;
+    let filters = (
+        filter.call1((false, tracemalloc.getattr("__file__")?))?,
+        filter.call1((false, "<frozen importlib._bootstrap>"))?,
+    );
+    tracemalloc.call_method0("take_snapshot")?.call_method1("filter_traces", (filters,))
+}
+
+/// Drop one traced task, stopping tracemalloc after the last if we started it
+fn release_malloc_tracer(tracemalloc: &Bound<'_, PyModule>) {
+    let mut tracers = MALLOC_TRACERS.lock();
+    tracers.0 -= 1;
+    if tracers.0 == 0 && std::mem::take(&mut tracers.1) {
+        if let Err(e) = tracemalloc.call_method0("stop") {
+            warn!("Failed to stop tracemalloc: {}", e);
+        }
+    }
+}
+
+/// Parallel function wrapper that returns AsyncHandle
+#[pyclass]
+struct ParallelWrapper {
+    func: Py<PyAny>,
+    isolation: Isolation,
+    result_schema: Option<Py<PyAny>>,
+    cost: Option<TaskCost>,
+    // Resources from provide() passed to each call as keyword arguments
+    inject: Vec<String>,
+    // Shared with bound-method copies so the limit covers every call of the function
+    concurrency: Option<Arc<ConcurrencyLimit>>,
+    // The function declares a `cancel_token` parameter to receive a CancellationToken
+    wants_cancel_token: bool,
+    // Diff tracemalloc snapshots around each call
+    trace_malloc: bool,
+    // OS scheduling priority of the worker thread
+    os_priority: ThreadPriority,
+    // Name of the Pool calls run on, instead of a thread per call
+    pool: Option<String>,
+    // Name for metrics and events, instead of one derived from the function
+    name: Option<String>,
+    // Task options only come from `_mp_`-prefixed keyword arguments
+    namespaced: bool,
+}
+
+impl ParallelWrapper {
+    fn new(
+        py: Python,
+        func: Py<PyAny>,
+        isolation: Isolation,
+        result_schema: Option<Py<PyAny>>,
+        cost: Option<TaskCost>,
+        inject: Vec<String>,
+        max_concurrency: Option<usize>,
+    ) -> Self {
+        // Tokens cannot cross into a subinterpreter
+        let wants_cancel_token = isolation == Isolation::Thread
+            && py
+                .import("inspect")
+                .and_then(|inspect| inspect.call_method1("signature", (func.bind(py),)))
+                .and_then(|signature| signature.getattr("parameters")?.contains("cancel_token"))
+                .unwrap_or(false);
+        ParallelWrapper {
+            func,
+            isolation,
+            result_schema,
+            cost,
+            inject,
+            concurrency: max_concurrency.map(|max| Arc::new(ConcurrencyLimit::new(max))),
+            wants_cancel_token,
+            trace_malloc: false,
+            os_priority: ThreadPriority::Normal,
+            pool: None,
+            name: None,
+            namespaced: false,
+        }
+    }
+
+    fn with_trace_malloc(self, trace_malloc: bool) -> Self {
+        ParallelWrapper { trace_malloc, ..self }
+    }
+
+    fn with_os_priority(self, os_priority: ThreadPriority) -> Self {
+        ParallelWrapper { os_priority, ..self }
+    }
+
+    fn with_pool(self, pool: Option<String>) -> Self {
+        ParallelWrapper { pool, ..self }
+    }
+
+    fn with_name(self, name: Option<String>) -> Self {
+        ParallelWrapper { name, ..self }
+    }
+
+    fn with_namespaced(self, namespaced: bool) -> Self {
+        ParallelWrapper { namespaced, ..self }
+    }
+
+    /// Submit a call whose task options are already separated from the function's arguments
+    #[allow(clippy::too_many_arguments)]
+    fn call_with(
+        &self,
+        py: Python,
+        args: &Bound<'_, PyTuple>,
+        timeout: Option<f64>,
+        total_timeout: Option<f64>,
+        tags: Option<Vec<String>>,
+        lazy: bool,
+        profile: bool,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<PyAny>> {
+        validate_timeouts(timeout, total_timeout)?;
+        let tags = tags.unwrap_or_default();
+        if profile && self.isolation == Isolation::Subinterpreter {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "profile is not supported with isolation=\"subinterpreter\"",
+            ));
+        }
+
+        // Check if shutdown is requested
+        if is_shutdown_requested() {
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                "Cannot start new tasks: shutdown in progress"
+            ));
+        }
+
+        // Wait for available slot (backpressure)
+        py.detach(wait_while_paused);
+        py.detach(wait_for_slot);
+        py.detach(wait_for_load);
+        wait_for_gpu_memory(py, &tags);
+
+        // Check memory before starting
+        if !check_memory_ok() {
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                "Memory limit reached, cannot start new task"
+            ));
+        }
+
+        // Reserved before the task is registered, so a full queue rejects the call cleanly
+        let pool_slot = self.pool.as_deref().map(|name| WorkerPool::named(name)?.reserve()).transpose()?;
+
+        // Clone function reference for the thread
+        let func = self.func.clone_ref(py);
+
+        // Subinterpreters only receive pickled data, so pickling errors surface here
+        let payload = match self.isolation {
+            Isolation::Subinterpreter => Some(pickle_call(py, func.bind(py), args, kwargs)?),
+            Isolation::Thread => None,
+        };
+
+        // Generate unique task ID
+        let task_id = format!("task_{}", TASK_ID_COUNTER.fetch_add(1, Ordering::Relaxed));
+        let task_id_clone = task_id.clone();
+
+        // Get function name for profiling
+        let func_name = self.name.clone().unwrap_or_else(|| callable_name(func.bind(py)));
+
+        // Convert args and kwargs to owned Python objects
+        let args_py: Py<PyTuple> = args.clone().unbind();
+        let kwargs_py: Option<Py<PyDict>> = kwargs.map(|k| k.clone().unbind());
+        let result_schema = self.result_schema.as_ref().map(|schema| schema.clone_ref(py));
+
+        // Create channel for communication
+        let (sender, receiver): (ResultSender, ResultReceiver) = channel();
+
+        let is_complete = Arc::new(Mutex::new(false));
+        let is_complete_clone = is_complete.clone();
+
+        let cancel_token = Arc::new(AtomicBool::new(false));
+        let cancel_token_clone = cancel_token.clone();
+
+        // Hand the task its token unless the caller passed one explicitly
+        let kwargs_py = match kwargs_py {
+            Some(ref k) if k.bind(py).contains("cancel_token")? => kwargs_py,
+            _ if self.wants_cancel_token => {
+                let injected = kwargs.map_or_else(|| Ok(PyDict::new(py)), |k| k.copy())?;
+                injected.set_item("cancel_token", CancellationToken { flag: cancel_token.clone() })?;
+                Some(injected.unbind())
+            }
+            _ => kwargs_py,
+        };
+
+        let failure_watch = Arc::new(FailureWatch::default());
+        let failure_watch_clone = failure_watch.clone();
+        let completion = Arc::new(TaskCompletion::default());
+        let completion_clone = completion.clone();
+        open_result_stream(&task_id, &completion);
+
+        let func_name_clone = func_name.clone();
+        let start_time = Instant::now();
+
+        // Register task as active
+        let submitted_from = submission_site();
+        register_task(ActiveTask {
+            task_id: task_id.clone(),
+            func_name: func_name.clone(),
+            tags: tags.clone(),
+            cancel_token: cancel_token.clone(),
+            is_complete: is_complete.clone(),
+            started_at: start_time,
+            submitted_from: submitted_from.clone(),
+        });
+
+        // Setup timeout if specified: the timer covers the whole task
+        if let Some(timeout_secs) = total_timeout.or(timeout) {
+            start_timeout_timer(&task_id, timeout_secs, cancel_token.clone());
+        }
+        let timeouts = TaskTimeouts::new(timeout, total_timeout, start_time);
+        let cost = self.cost;
+        let concurrency = self.concurrency.clone();
+        let inject = self.inject.clone();
+        let trace_malloc = self.trace_malloc;
+        let os_priority = self.os_priority;
+
+        // Spawn Rust thread - release GIL first, then spawn thread
+        let handle = py.detach(|| {
+            spawn_pooled_task(pool_slot, move || {
+                if !sync_fallback() {
+                    apply_os_priority(os_priority);
+                }
+                // Held while the function runs; a cancelled wait falls through to the check below
+                let permit = concurrency.and_then(|limit| limit.acquire(&cancel_token_clone));
+                let reservation = cost.and_then(|cost| reserve_capacity(cost, &cancel_token_clone));
+
+                // Acquire GIL inside the thread to call Python function
+                Python::attach(|py| {
+                    let exec_start = Instant::now();
+
+                    // Set task_id in thread-local storage for progress reporting
+                    set_current_task_id(Some(task_id_clone.clone()));
+                    set_current_task_timeouts(timeouts);
+                    // Registered before the cancellation check so a timeout cannot slip in between
+                    let interruptible = TaskThreadGuard::register(py, &task_id_clone);
+
+                    // Check shutdown or cancellation before execution
+                    if is_shutdown_requested() || cancel_token_clone.load(Ordering::Acquire) {
+                        let reason = if is_shutdown_requested() {
+                            "Task cancelled: shutdown requested"
+                        } else {
+                            "Task was cancelled or timed out"
+                        };
+
+                        let task_error = TaskError {
+                            task_name: func_name_clone.clone(),
+                            elapsed_time: exec_start.elapsed().as_secs_f64(),
+                            error_message: reason.to_string(),
+                            error_type: "CancellationError".to_string(),
+                            task_id: task_id_clone.clone(),
+                            submitted_from: task_submission_site(&task_id_clone),
+                        };
+                        report_task_failure(py, &failure_watch_clone, &task_error, None);
+
+                        let outcome = Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                            task_error.__str__()
+                        ));
+
+                        // CRITICAL FIX: Handle channel send errors
+                        if let Err(e) = sender.send(clone_outcome(py, &outcome)) {
+                            error!("Failed to send cancellation error for task {}: {}", task_id_clone, e);
+                        }
+                        *is_complete_clone.lock() = true;
+                        completion_clone.finish(py, outcome);
+                        stop_timeout_timer(&task_id_clone);
+                        unregister_task(&task_id_clone);
+                        clear_task_progress(&task_id_clone);
+                        set_current_task_id(None);
+                        return;
+                    }
+
+                    let sampler = if profile { TaskSampler::start(py) } else { None };
+                    let malloc_trace = if trace_malloc { MallocTrace::start(py) } else { None };
+                    let cpu_clock = CpuClock::start();
+                    let result = match payload {
+                        Some(ref payload) => run_in_subinterpreter(py, &task_id_clone, payload),
+                        None => call_with_resources(py, &inject, kwargs_py.as_ref().map(|k| k.bind(py)), |kwargs| {
+                            func.bind(py).call(args_py.bind(py), kwargs)
+                        }),
+                    };
+                    let cpu_ms = cpu_clock.elapsed_ms();
+                    completion_clone.state.lock().cpu_time = cpu_ms.map(|ms| ms / 1000.0);
+                    if let Some(trace) = malloc_trace {
+                        completion_clone.state.lock().allocations = Some(trace.finish(py));
+                    }
+                    if let Some(sampler) = sampler {
+                        completion_clone.state.lock().profile = Some(sampler.finish(py));
+                    }
+                    drop(interruptible);
+                    drop(reservation);
+                    drop(permit);
+
+                    // Contract violations are reported by the producing task, not its consumers
+                    let result = match (result, &result_schema) {
+                        (Ok(val), Some(schema)) => validate_result(&func_name_clone, schema.bind(py), val),
+                        (result, _) => result,
+                    };
+
+                    let exec_time = exec_start.elapsed().as_secs_f64() * 1000.0; // Convert to ms
+
+                    // A call that finished past its timeout is reported as timed out
+                    let overran = exceeded_task_timeout(timeout, total_timeout, start_time);
+
+                    let to_send = match (result, overran) {
+                        (_, Some(limit)) => {
+                            record_task_execution(&func_name_clone, exec_time, false, cpu_ms);
+                            let task_error = timeout_task_error(
+                                &func_name_clone,
+                                &task_id_clone,
+                                limit,
+                                exec_start,
+                            );
+                            report_task_failure(py, &failure_watch_clone, &task_error, Some(limit));
+                            Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                                task_error.__str__()
+                            ))
+                        }
+                        (Ok(val), None) => {
+                            record_task_execution(&func_name_clone, exec_time, true, cpu_ms);
+                            publish_task_event(
+                                "task_completed",
+                                &task_id_clone,
+                                &func_name_clone,
+                                completed_event_details(py, &val, exec_time / 1000.0),
+                            );
+                            Ok(val.unbind())
+                        }
+                        (Err(e), None) => {
+                            record_task_execution(&func_name_clone, exec_time, false, cpu_ms);
+
+                            // Create enhanced error with context
+                            let error_type = task_error_type(py, &e);
+
+                            let task_error = TaskError {
+                                task_name: func_name_clone.clone(),
+                                elapsed_time: exec_start.elapsed().as_secs_f64(),
+                                error_message: e.to_string(),
+                                error_type,
+                                task_id: task_id_clone.clone(),
+                                submitted_from: task_submission_site(&task_id_clone),
+                            };
+                            report_task_failure(py, &failure_watch_clone, &task_error, None);
+
+                            Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                                task_error.__str__()
+                            ))
+                        }
+                    };
+
+                    // CRITICAL FIX: Handle channel send errors
+                    if let Err(e) = sender.send(clone_outcome(py, &to_send)) {
+                        error!("Failed to send task result for task {}: {}", task_id_clone, e);
+                    }
+                    *is_complete_clone.lock() = true;
+                    completion_clone.finish(py, to_send);
+
+                    // Cleanup: stop the timer, unregister task and clear progress
+                    stop_timeout_timer(&task_id_clone);
+                    unregister_task(&task_id_clone);
+                    clear_task_progress(&task_id_clone);
+                    set_current_task_id(None);
+                });
+            })
+        });
+
+        // Create AsyncHandle
+        let async_handle = AsyncHandle {
+            receiver: Arc::new(Mutex::new(receiver)),
+            thread_handle: Arc::new(Mutex::new(handle)),
+            is_complete,
+            result_cache: Arc::new(Mutex::new(None)),
+            cancel_token,
+            func_name,
+            start_time,
+            task_id,
+            metadata: initial_metadata(&submitted_from),
+            timeout,
+            total_timeout,
+            tags,
+            on_complete: Arc::new(Mutex::new(None)),
+            on_error: Arc::new(Mutex::new(None)),
+            on_progress: Arc::new(Mutex::new(None)),
+            failure_watch,
+            completion,
+        };
+
+        let handle = Py::new(py, async_handle)?;
+        if lazy {
+            Ok(Py::new(py, LazyResult { handle })?.into_any())
+        } else {
+            Ok(handle.into_any())
+        }
+    }
+}
+
+#[pymethods]
+impl ParallelWrapper {
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(
+        &self,
+        py: Python,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<PyAny>> {
+        let options = CallOptions::new(kwargs, self.namespaced);
+        let timeout = options.take("timeout")?;
+        let total_timeout = options.take("total_timeout")?;
+        let tags = options.take("tags")?;
+        let lazy = options.take("lazy")?.unwrap_or(false);
+        let profile = options.take("profile")?.unwrap_or(false);
+        self.call_with(py, args, timeout, total_timeout, tags, lazy, profile, kwargs)
+    }
+
+    fn __get__(
+        slf: PyRef<'_, Self>,
+        obj: &Bound<'_, PyAny>,
+        _objtype: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Py<PyAny>> {
+        let py = slf.py();
+
+        if obj.is_none() || !binds_to_instance(slf.func.bind(py)) {
+            // Unbound access, or a callable that stays unbound as a class attribute - return self
+            return Ok(slf.into_bound_py_any(py)?.unbind());
+        }
+
+        // Bound method access - create a new ParallelWrapper with bound function
+        let functools = py.import("functools")?;
+        let partial = functools.getattr("partial")?;
+        let bound_func = partial.call1((slf.func.bind(py), obj))?.unbind();
+
+        Py::new(
+            py,
+            ParallelWrapper {
+                func: bound_func,
+                isolation: slf.isolation,
+                result_schema: slf.result_schema.as_ref().map(|schema| schema.clone_ref(py)),
+                cost: slf.cost,
+                inject: slf.inject.clone(),
+                concurrency: slf.concurrency.clone(),
+                wants_cancel_token: slf.wants_cancel_token,
+                trace_malloc: slf.trace_malloc,
+                os_priority: slf.os_priority,
+                pool: slf.pool.clone(),
+                // Report calls under the method's name, not the partial's
+                name: Some(slf.name.clone().unwrap_or_else(|| callable_name(slf.func.bind(py)))),
+                namespaced: slf.namespaced,
+            },
+        )
+        .map(|p| p.into())
+    }
+
+    // Names come from the wrapped callable, so introspection and functools.wraps work
+    #[getter]
+    fn __name__(&self, py: Python) -> String {
+        self.name.clone().unwrap_or_else(|| callable_name(self.func.bind(py)))
+    }
+
+    #[getter]
+    fn __qualname__(&self, py: Python) -> String {
+        let func = self.func.bind(py);
+        match &self.name {
+            Some(name) => name.clone(),
+            None => func.getattr("__qualname__").and_then(|n| n.extract()).unwrap_or_else(|_| callable_name(func)),
+        }
+    }
+
+    #[getter]
+    fn __module__(&self, py: Python) -> PyResult<Py<PyAny>> {
+        Ok(unwrap_partial(self.func.bind(py)).getattr("__module__")?.unbind())
+    }
+
+    #[getter]
+    fn __wrapped__(&self, py: Python) -> Py<PyAny> {
+        self.func.clone_ref(py)
+    }
+}
+
+/// The callable a functools.partial (or similar wrapper) calls, or `func` itself
+fn unwrap_partial<'py>(func: &Bound<'py, PyAny>) -> Bound<'py, PyAny> {
+    match func.getattr("func") {
+        Ok(inner) if inner.is_callable() && func.hasattr("keywords").unwrap_or(false) => unwrap_partial(&inner),
+        _ => func.clone(),
+    }
+}
+
+/// Whether `func` becomes a bound method when stored on a class
+///
+/// Functions do; callable instances, builtins and partials don't, just as without a decorator.
+fn binds_to_instance(func: &Bound<'_, PyAny>) -> bool {
+    unwrap_partial(func).is(func) && func.get_type().hasattr("__get__").unwrap_or(false)
+}
+
+/// Check a task's result against its `result_schema`, returning the value to deliver
+///
+/// Pydantic models (anything with `model_validate`) replace the result with the
+/// validated model. Other callables reject a result by raising or returning False.
+fn validate_result<'py>(
+    func_name: &str,
+    schema: &Bound<'py, PyAny>,
+    value: Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let checked = if schema.hasattr("model_validate")? {
+        schema.call_method1("model_validate", (&value,))
+    } else {
+        schema.call1((&value,)).and_then(|verdict| {
+            if verdict.is_instance_of::<PyBool>() && !verdict.is_truthy()? {
+                Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "validator rejected {}",
+                    value.repr()?
+                )))
+            } else {
+                Ok(value.clone())
+            }
+        })
+    };
+
+    checked.map_err(|e| {
+        TaskExecutionFailed::new_err(
+            MakeParallelError::TaskExecutionFailed {
+                message: format!("result of '{}' does not match result_schema: {}", func_name, e),
+            }
+            .to_string(),
+        )
+    })
+}
+
+/// Decorator to run functions in parallel Rust threads without GIL
+///
+/// Use as `@parallel` or with options, e.g. `@parallel(isolation="subinterpreter")`
+/// to run each call in its own subinterpreter (function and arguments passed by
+/// pickle), or `@parallel(result_schema=Model)` to validate results in the worker.
+/// `@parallel(cpu=2, memory_mb=512)` declares each call's cost for configure_capacity(),
+/// and `@parallel(inject=["db"])` passes resources registered with provide().
+/// `@parallel(max_concurrency=4)` runs at most 4 calls of the function at once.
+/// `@parallel(trace_malloc=True)` records each call's allocation growth for
+/// `handle.get_allocations()`.
+/// `@parallel(os_priority="low")` runs calls on threads the OS schedules behind
+/// normal ones; "high" usually needs elevated privileges.
+/// `@parallel(pool="io")` runs calls on the threads of the named Pool.
+/// `@parallel(name="resize")` records metrics and events under that name.
+/// `@parallel(namespaced=True)` takes call options only as `_mp_timeout=`, `_mp_tags=`
+/// and so on, passing `timeout=` and the like to the function.
+#[pyfunction]
+#[pyo3(signature = (
+    func=None,
+    *,
+    isolation="thread",
+    result_schema=None,
+    cpu=None,
+    memory_mb=None,
+    inject=None,
+    max_concurrency=None,
+    trace_malloc=false,
+    os_priority="normal",
+    pool=None,
+    name=None,
+    namespaced=false
+))]
+#[allow(clippy::too_many_arguments)]
+fn parallel(
+    py: Python,
+    func: Option<Py<PyAny>>,
+    isolation: &str,
+    result_schema: Option<Py<PyAny>>,
+    cpu: Option<f64>,
+    memory_mb: Option<f64>,
+    inject: Option<Vec<String>>,
+    max_concurrency: Option<usize>,
+    trace_malloc: bool,
+    os_priority: &str,
+    pool: Option<String>,
+    name: Option<String>,
+    namespaced: bool,
+) -> PyResult<Py<PyAny>> {
+    if max_concurrency == Some(0) {
+        return Err(pyo3::exceptions::PyValueError::new_err("max_concurrency must be at least 1"));
+    }
+    let isolation = Isolation::parse(isolation)?;
+    let os_priority = ThreadPriority::parse(os_priority)?;
+    if pool.is_some() && os_priority != ThreadPriority::Normal {
+        // Pool threads are shared, so a per-call priority would stick to them
+        return Err(pyo3::exceptions::PyValueError::new_err("os_priority cannot be combined with pool"));
+    }
+    let inject = inject.unwrap_or_default();
+    if isolation == Isolation::Subinterpreter && !inject.is_empty() {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "inject is not supported with isolation=\"subinterpreter\"",
+        ));
+    }
+    if isolation == Isolation::Subinterpreter && trace_malloc {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "trace_malloc is not supported with isolation=\"subinterpreter\"",
+        ));
+    }
+    for (name, value) in [("cpu", cpu), ("memory_mb", memory_mb)] {
+        if value.is_some_and(|v| !v.is_finite() || v < 0.0) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "{} must be a non-negative number",
+                name
+            )));
+        }
+    }
+    let cost = (cpu.is_some() || memory_mb.is_some()).then(|| TaskCost {
+        cpu: cpu.unwrap_or(0.0),
+        memory_mb: memory_mb.unwrap_or(0.0),
+    });
+    if let Some(ref schema) = result_schema {
+        if !schema.bind(py).is_callable() {
+            return Err(pyo3::exceptions::PyTypeError::new_err(
+                "result_schema must be a callable or a pydantic model",
+            ));
+        }
+    }
+    if let Some(func) = func {
+        let wrapper = ParallelWrapper::new(py, func, isolation, result_schema, cost, inject, max_concurrency);
+        let wrapper = wrapper.with_trace_malloc(trace_malloc).with_os_priority(os_priority).with_pool(pool);
+        return Ok(Py::new(py, wrapper.with_name(name).with_namespaced(namespaced))?.into_any());
+    }
+
+    let decorator = PyCFunction::new_closure(
+        py,
+        None,
+        None,
+        move |args: &Bound<'_, PyTuple>, _kwargs: Option<&Bound<'_, PyDict>>| {
+            let py = args.py();
+            let func = args.get_item(0)?.unbind();
+            let result_schema = result_schema.as_ref().map(|schema| schema.clone_ref(py));
+            let wrapper = ParallelWrapper::new(py, func, isolation, result_schema, cost, inject.clone(), max_concurrency);
+            let wrapper = wrapper.with_trace_malloc(trace_malloc).with_os_priority(os_priority).with_pool(pool.clone());
+            Py::new(py, wrapper.with_name(name.clone()).with_namespaced(namespaced))
+        },
+    )?;
+    Ok(decorator.into_any().unbind())
+}
+
+/// Run `func(*args, **kwargs)` in a worker thread, like a call of `@parallel(func)`
+///
+/// For lambdas and callables built at runtime that cannot be decorated. Every
+/// keyword argument is passed to `func`, so the per-call `timeout`, `tags` and
+/// similar options are not available here.
+#[pyfunction]
+#[pyo3(signature = (func, /, *args, **kwargs))]
+fn run_in_worker(
+    py: Python,
+    func: Py<PyAny>,
+    args: &Bound<'_, PyTuple>,
+    kwargs: Option<&Bound<'_, PyDict>>,
+) -> PyResult<Py<PyAny>> {
+    if !func.bind(py).is_callable() {
+        return Err(pyo3::exceptions::PyTypeError::new_err("run_in_worker() expects a callable"));
+    }
+    let wrapper = ParallelWrapper::new(py, func, Isolation::Thread, None, None, Vec::new(), None);
+    wrapper.call_with(py, args, None, None, None, false, false, kwargs)
+}
+
+/// A function with some arguments filled in, from bind()
+#[pyclass(frozen)]
+struct BoundCall {
+    #[pyo3(get)]
+    func: Py<PyAny>,
+    #[pyo3(get)]
+    args: Py<PyTuple>,
+    keywords: Py<PyDict>,
+}
+
+#[pymethods]
+impl BoundCall {
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(
+        &self,
+        py: Python,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<PyAny>> {
+        let all_args: Vec<Bound<'_, PyAny>> = self.args.bind(py).iter().chain(args.iter()).collect();
+        let all_args = PyTuple::new(py, all_args)?;
+        let all_kwargs = self.keywords.bind(py).copy()?;
+        if let Some(kwargs) = kwargs {
+            all_kwargs.update(kwargs.as_mapping())?;
+        }
+        self.func.bind(py).call(all_args, Some(&all_kwargs)).map(|r| r.unbind())
+    }
+
+    #[getter]
+    fn keywords(&self, py: Python) -> PyResult<Py<PyDict>> {
+        Ok(self.keywords.bind(py).copy()?.unbind())
+    }
+
+    // Names come from the function, so metrics, events and caches report it
+    #[getter]
+    fn __name__(&self, py: Python) -> String {
+        let func = self.func.bind(py);
+        func.getattr("__name__").and_then(|n| n.extract()).unwrap_or_else(|_| callable_name(func))
+    }
+
+    #[getter]
+    fn __qualname__(&self, py: Python) -> String {
+        let func = self.func.bind(py);
+        func.getattr("__qualname__").and_then(|n| n.extract()).unwrap_or_else(|_| self.__name__(py))
+    }
+
+    #[getter]
+    fn __module__(&self, py: Python) -> PyResult<Py<PyAny>> {
+        Ok(self.func.bind(py).getattr("__module__")?.unbind())
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        let mut parts = vec![self.func.bind(py).repr()?.to_string()];
+        for arg in self.args.bind(py).iter() {
+            parts.push(arg.repr()?.to_string());
+        }
+        for (key, value) in self.keywords.bind(py).iter() {
+            parts.push(format!("{}={}", key.str()?, value.repr()?));
+        }
+        Ok(format!("bind({})", parts.join(", ")))
+    }
+
+    /// Pickles as a call to bind(), so bound functions can go to worker processes
+    fn __reduce__<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyTuple>)> {
+        let bind = py.import("makeparallel")?.getattr("bind")?;
+        let rebuild = py
+            .import("functools")?
+            .getattr("partial")?
+            .call((bind, self.func.bind(py)), Some(self.keywords.bind(py)))?;
+        Ok((rebuild, self.args.bind(py).clone()))
+    }
+}
+
+/// Fill in some of `func`'s arguments, keeping its name
+///
+/// Works like functools.partial, but the result reports the function's `__name__`,
+/// `__qualname__` and `__module__`, so metrics and events for calls made through
+/// it are recorded under the original function instead of "unknown".
+#[pyfunction]
+#[pyo3(signature = (func, /, *args, **kwargs))]
+fn bind(
+    py: Python,
+    func: Py<PyAny>,
+    args: &Bound<'_, PyTuple>,
+    kwargs: Option<&Bound<'_, PyDict>>,
+) -> PyResult<BoundCall> {
+    if !func.bind(py).is_callable() {
+        return Err(pyo3::exceptions::PyTypeError::new_err("bind() expects a callable"));
+    }
+    let keywords = kwargs.map_or_else(|| Ok(PyDict::new(py)), |k| k.copy())?;
+    Ok(BoundCall { func, args: args.clone().unbind(), keywords: keywords.unbind() })
+}
+
+// =============================================================================
+// OPTIMIZED IMPLEMENTATIONS
+// =============================================================================
+
+/// Optimized AsyncHandle using crossbeam channels (lock-free, better performance)
+#[pyclass]
+struct AsyncHandleFast {
+    receiver: Arc<Mutex<CrossbeamReceiver<PyResult<Py<PyAny>>>>>,
+    is_complete: Arc<Mutex<bool>>,
+    result_cache: Arc<Mutex<Option<PyResult<Py<PyAny>>>>>,
+}
+
+#[pymethods]
+impl AsyncHandleFast {
+    fn is_ready(&self) -> PyResult<bool> {
+        Ok(*self.is_complete.lock())
+    }
+
+    fn try_get(&self, py: Python) -> PyResult<Option<Py<PyAny>>> {
+        let mut cache = self.result_cache.lock();
+        if let Some(ref cached) = *cache {
+            return match cached {
+                Ok(val) => Ok(Some(val.clone_ref(py))),
+                Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Cached error: {}",
+                    e
+                ))),
+            };
+        }
+
+        let receiver = self.receiver.lock();
+        match receiver.try_recv() {
+            Ok(result) => {
+                *self.is_complete.lock() = true;
+                match result {
+                    Ok(val) => {
+                        *cache = Some(Ok(val.clone_ref(py)));
+                        Ok(Some(val))
+                    }
+                    Err(e) => {
+                        *cache = Some(Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                            e.to_string(),
+                        )));
+                        Err(e)
+                    }
+                }
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn get(&self, py: Python) -> PyResult<Py<PyAny>> {
+        let cache = self.result_cache.lock();
+        if let Some(ref cached) = *cache {
+            return match cached {
+                Ok(val) => Ok(val.clone_ref(py)),
+                Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Cached error: {}",
+                    e
+                ))),
+            };
+        }
+        drop(cache);
+
+        // Release GIL before blocking
+        let result = py
+            .detach(|| {
+                let receiver = self.receiver.lock();
+                receiver.recv()
+            })
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        *self.is_complete.lock() = true;
+
+        let mut cache = self.result_cache.lock();
+        match result {
+            Ok(ref val) => {
+                *cache = Some(Ok(val.clone_ref(py)));
+                Ok(val.clone_ref(py))
+            }
+            Err(e) => {
+                let err_str = e.to_string();
+                *cache = Some(Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                    err_str.clone(),
+                )));
+                Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(err_str))
+            }
+        }
+    }
+}
+
+// =============================================================================
+// TASK DEPENDENCY SYSTEM
+// =============================================================================
+
+/// Wait for dependencies to complete
+///
+/// Results are read from each dependency's own completion rather than a shared
+/// store, so any number of dependents can consume the same task. The wait sleeps on
+/// the completion's condvar and ends early, raising TaskCancelled, when the dependent
+/// is cancelled or its own timeout expires.
+fn wait_for_dependencies(py: Python, dependencies: &[(String, Arc<TaskCompletion>)]) -> PyResult<Vec<Py<PyAny>>> {
+    let mut results = Vec::new();
+
+    for (dep_id, completion) in dependencies {
+        wait_for_state(py, &completion.state, &completion.changed, None, |state| {
+            state.outcome.is_some() || is_shutdown_requested()
+        })?;
+        match completion.outcome(py) {
+            Some(Ok(result)) => results.push(result),
+            Some(Err(e)) => {
+                error!("Dependency {} failed: {}", dep_id, e);
+                return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                    format!("Dependency {} failed: {}", dep_id, e)
+                ));
+            }
+            None => {
+                warn!("Dependency wait cancelled: shutdown in progress");
+                return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                    "Dependency wait cancelled: shutdown in progress"
+                ));
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// Parallel wrapper with dependency support
+#[pyclass]
+struct ParallelWithDeps {
+    func: Py<PyAny>,
+    name: Option<String>,
+    /// Task options only come from `_mp_`-prefixed keyword arguments
+    namespaced: bool,
+}
+
+impl ParallelWithDeps {
+    /// Submit a call whose task options are already separated from the function's arguments
+    #[allow(clippy::too_many_arguments)]
+    fn call_with(
+        &self,
+        py: Python,
+        args: &Bound<'_, PyTuple>,
+        depends_on: Option<Vec<Py<AsyncHandle>>>,
+        timeout: Option<f64>,
+        total_timeout: Option<f64>,
+        tags: Option<Vec<String>>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<AsyncHandle>> {
+        validate_timeouts(timeout, total_timeout)?;
+        let tags = tags.unwrap_or_default();
+
+        // Dependencies are awaited through their handles' completions
+        let deps: Vec<(String, Arc<TaskCompletion>)> = depends_on
+            .iter()
+            .flatten()
+            .map(|h| {
+                let h = h.borrow(py);
+                h.failure_watch.mark_observed();
+                (h.task_id.clone(), h.completion.clone())
+            })
+            .collect();
+        let dep_ids: Vec<String> = deps.iter().map(|(id, _)| id.clone()).collect();
+
+        // Check if shutdown is requested
+        if is_shutdown_requested() {
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                "Cannot start new tasks: shutdown in progress"
+            ));
+        }
+
+        py.detach(wait_while_paused);
+        py.detach(wait_for_slot);
+        py.detach(wait_for_load);
+        wait_for_gpu_memory(py, &tags);
+
+        if !check_memory_ok() {
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                "Memory limit reached, cannot start new task"
+            ));
+        }
+
+        let func = self.func.clone_ref(py);
+        let task_id = format!("task_{}", TASK_ID_COUNTER.fetch_add(1, Ordering::Relaxed));
+        let task_id_clone = task_id.clone();
+
+        // Register dependencies
+        if !dep_ids.is_empty() {
+            TASK_DEPENDENCIES.insert(task_id.clone(), dep_ids.clone());
+        }
+
+        let func_name = self.name.clone().unwrap_or_else(|| callable_name(func.bind(py)));
+
+        let args_py: Py<PyTuple> = args.clone().unbind();
+        let kwargs_py: Option<Py<PyDict>> = kwargs.map(|k| k.clone().unbind());
+
+        let (sender, receiver): (ResultSender, ResultReceiver) = channel();
+
+        let is_complete = Arc::new(Mutex::new(false));
+        let is_complete_clone = is_complete.clone();
+
+        let cancel_token = Arc::new(AtomicBool::new(false));
+        let cancel_token_clone = cancel_token.clone();
+
+        let failure_watch = Arc::new(FailureWatch::default());
+        let failure_watch_clone = failure_watch.clone();
+        let completion = Arc::new(TaskCompletion::default());
+        let completion_clone = completion.clone();
+        open_result_stream(&task_id, &completion);
+
+        let func_name_clone = func_name.clone();
+        let start_time = Instant::now();
+
+        // Register task as active
+        let submitted_from = submission_site();
+        register_task(ActiveTask {
+            task_id: task_id.clone(),
+            func_name: func_name.clone(),
+            tags: tags.clone(),
+            cancel_token: cancel_token.clone(),
+            is_complete: is_complete.clone(),
+            started_at: start_time,
+            submitted_from: submitted_from.clone(),
+        });
+
+        if let Some(timeout_secs) = total_timeout.or(timeout) {
+            start_timeout_timer(&task_id, timeout_secs, cancel_token.clone());
+        }
+        let timeouts = TaskTimeouts::new(timeout, total_timeout, start_time);
+
+        let handle = py.detach(|| {
+            spawn_task_thread(move || {
+                Python::attach(|py| {
+                    let exec_start = Instant::now();
+                    set_current_task_id(Some(task_id_clone.clone()));
+                    set_current_task_timeouts(timeouts);
+
+                    // Wait for dependencies first
+                    let dep_results = if !deps.is_empty() {
+                        match wait_for_dependencies(py, &deps) {
+                            Ok(results) => results,
+                            Err(e) => {
+                                report_task_failure(
+                                    py,
+                                    &failure_watch_clone,
+                                    &TaskError {
+                                        task_name: func_name_clone.clone(),
+                                        elapsed_time: exec_start.elapsed().as_secs_f64(),
+                                        error_message: e.to_string(),
+                                        error_type: "DependencyError".to_string(),
+                                        task_id: task_id_clone.clone(),
+                                        submitted_from: task_submission_site(&task_id_clone),
+                                    },
+                                    None,
+                                );
+
+                                let outcome = Err(e);
+
+                                // CRITICAL FIX: Handle channel send errors
+                                if let Err(send_err) = sender.send(clone_outcome(py, &outcome)) {
+                                    error!("Failed to send dependency error for task {}: {}", task_id_clone, send_err);
+                                }
+                                *is_complete_clone.lock() = true;
+                                completion_clone.finish(py, outcome);
+                                stop_timeout_timer(&task_id_clone);
+                                unregister_task(&task_id_clone);
+                                clear_task_progress(&task_id_clone);
+                                TASK_DEPENDENCIES.remove(&task_id_clone);
+                                set_current_task_id(None);
+                                return;
+                            }
+                        }
+                    } else {
+                        Vec::new()
+                    };
+
+                    let interruptible = TaskThreadGuard::register(py, &task_id_clone);
+                    if is_shutdown_requested() || cancel_token_clone.load(Ordering::Acquire) {
+                        let reason = if is_shutdown_requested() {
+                            "Task cancelled: shutdown requested"
+                        } else {
+                            "Task was cancelled or timed out"
+                        };
+
+                        let task_error = TaskError {
+                            task_name: func_name_clone.clone(),
+                            elapsed_time: exec_start.elapsed().as_secs_f64(),
+                            error_message: reason.to_string(),
+                            error_type: "CancellationError".to_string(),
+                            task_id: task_id_clone.clone(),
+                            submitted_from: task_submission_site(&task_id_clone),
+                        };
+                        report_task_failure(py, &failure_watch_clone, &task_error, None);
+
+                        let outcome = Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                            task_error.__str__()
+                        ));
+
+                        // CRITICAL FIX: Handle channel send errors
+                        if let Err(e) = sender.send(clone_outcome(py, &outcome)) {
+                            error!("Failed to send cancellation error for task {}: {}", task_id_clone, e);
+                        }
+                        *is_complete_clone.lock() = true;
+                        completion_clone.finish(py, outcome);
+                        stop_timeout_timer(&task_id_clone);
+                        unregister_task(&task_id_clone);
+                        clear_task_progress(&task_id_clone);
+                        TASK_DEPENDENCIES.remove(&task_id_clone);
+                        set_current_task_id(None);
+                        return;
+                    }
+
+                    // If we have dependencies, pass their results as first argument
+                    let cpu_clock = CpuClock::start();
+                    let final_result = if !dep_results.is_empty() {
+                        // Create new tuple with dependency results + original args
+                        let dep_tuple = PyTuple::new(py, dep_results.iter().map(|r| r.bind(py))).unwrap();
+                        let mut combined_args = vec![dep_tuple.into_any().unbind()];
+
+                        for arg in args_py.bind(py).iter() {
+                            combined_args.push(arg.unbind());
+                        }
+
+                        let new_tuple = PyTuple::new(py, combined_args.iter().map(|a| a.bind(py))).unwrap();
+                        func.bind(py).call(new_tuple, kwargs_py.as_ref().map(|k| k.bind(py)))
+                    } else {
+                        func.bind(py).call(args_py.bind(py), kwargs_py.as_ref().map(|k| k.bind(py)))
+                    };
+                    let cpu_ms = cpu_clock.elapsed_ms();
+                    completion_clone.state.lock().cpu_time = cpu_ms.map(|ms| ms / 1000.0);
+                    drop(interruptible);
+
+                    let exec_time = exec_start.elapsed().as_secs_f64() * 1000.0;
+
+                    // A call that finished past its timeout is reported as timed out
+                    let overran = exceeded_task_timeout(timeout, total_timeout, start_time);
+
+                    let to_send = match (final_result, overran) {
+                        (_, Some(limit)) => {
+                            record_task_execution(&func_name_clone, exec_time, false, cpu_ms);
+                            let task_error = timeout_task_error(
+                                &func_name_clone,
+                                &task_id_clone,
+                                limit,
+                                exec_start,
+                            );
+                            report_task_failure(py, &failure_watch_clone, &task_error, Some(limit));
+                            Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                                task_error.__str__()
+                            ))
+                        }
+                        (Ok(val), None) => {
+                            record_task_execution(&func_name_clone, exec_time, true, cpu_ms);
+                            publish_task_event(
+                                "task_completed",
+                                &task_id_clone,
+                                &func_name_clone,
+                                completed_event_details(py, &val, exec_time / 1000.0),
+                            );
+                            Ok(val.unbind())
+                        }
+                        (Err(e), None) => {
+                            record_task_execution(&func_name_clone, exec_time, false, cpu_ms);
+
+                            let error_type = task_error_type(py, &e);
+
+                            let task_error = TaskError {
+                                task_name: func_name_clone.clone(),
+                                elapsed_time: exec_start.elapsed().as_secs_f64(),
+                                error_message: e.to_string(),
+                                error_type,
+                                task_id: task_id_clone.clone(),
+                                submitted_from: task_submission_site(&task_id_clone),
+                            };
+                            report_task_failure(py, &failure_watch_clone, &task_error, None);
+
+                            Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                                task_error.__str__()
+                            ))
+                        }
+                    };
+
+                    let _ = sender.send(clone_outcome(py, &to_send));
+                    *is_complete_clone.lock() = true;
+                    completion_clone.finish(py, to_send);
+
+                    stop_timeout_timer(&task_id_clone);
+                    unregister_task(&task_id_clone);
+                    clear_task_progress(&task_id_clone);
+                    TASK_DEPENDENCIES.remove(&task_id_clone);
+                    set_current_task_id(None);
+                });
+            })
+        });
+
+        let async_handle = AsyncHandle {
+            receiver: Arc::new(Mutex::new(receiver)),
+            thread_handle: Arc::new(Mutex::new(handle)),
+            is_complete,
+            result_cache: Arc::new(Mutex::new(None)),
+            cancel_token,
+            func_name,
+            start_time,
+            task_id,
+            metadata: initial_metadata(&submitted_from),
+            timeout,
+            total_timeout,
+            tags,
+            on_complete: Arc::new(Mutex::new(None)),
+            on_error: Arc::new(Mutex::new(None)),
+            on_progress: Arc::new(Mutex::new(None)),
+            failure_watch,
+            completion,
+        };
+
+        Py::new(py, async_handle)
+    }
+}
+
+#[pymethods]
+impl ParallelWithDeps {
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(
+        &self,
+        py: Python,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<AsyncHandle>> {
+        let options = CallOptions::new(kwargs, self.namespaced);
+        let depends_on = options.take("depends_on")?;
+        let timeout = options.take("timeout")?;
+        let total_timeout = options.take("total_timeout")?;
+        let tags = options.take("tags")?;
+        self.call_with(py, args, depends_on, timeout, total_timeout, tags, kwargs)
+    }
+}
+
+/// Decorator for parallel execution with dependency support
+///
+/// `@parallel_with_deps(name="x")` records metrics and events under "x", and
+/// `namespaced=True` takes call options only as `_mp_depends_on=`, `_mp_timeout=` and so on.
+#[pyfunction]
+#[pyo3(signature = (func=None, *, name=None, namespaced=false))]
+fn parallel_with_deps(
+    py: Python,
+    func: Option<Py<PyAny>>,
+    name: Option<String>,
+    namespaced: bool,
+) -> PyResult<Py<PyAny>> {
+    decorate(py, func, move |py, func| {
+        Ok(Py::new(py, ParallelWithDeps { func, name: name.clone(), namespaced })?.into_any())
+    })
+}
+
+/// Optimized parallel wrapper using crossbeam channels
+#[pyclass]
+struct ParallelFastWrapper {
+    func: Py<PyAny>,
+}
+
+#[pymethods]
+impl ParallelFastWrapper {
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(
+        &self,
+        py: Python,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<AsyncHandleFast>> {
+        let func = self.func.clone_ref(py);
+        let args_py: Py<PyTuple> = args.clone().unbind();
+        let kwargs_py: Option<Py<PyDict>> = kwargs.map(|k| k.clone().unbind());
+
+        // Use crossbeam unbounded channel for better performance
+        let (sender, receiver) = unbounded::<PyResult<Py<PyAny>>>();
+
+        let is_complete = Arc::new(Mutex::new(false));
+        let is_complete_clone = is_complete.clone();
+
+        // Spawn thread without GIL
+        py.detach(|| {
+            spawn_task_thread(move || {
+                Python::attach(|py| {
+                    let result = func
+                        .bind(py)
+                        .call(args_py.bind(py), kwargs_py.as_ref().map(|k| k.bind(py)));
+
+                    let to_send = match result {
+                        Ok(val) => Ok(val.unbind()),
+                        Err(e) => Err(e),
+                    };
+
+                    let _ = sender.send(to_send);
+                    *is_complete_clone.lock() = true;
+                });
+            })
+        });
+
+        let async_handle = AsyncHandleFast {
+            receiver: Arc::new(Mutex::new(receiver)),
+            is_complete,
+            result_cache: Arc::new(Mutex::new(None)),
+        };
+
+        Py::new(py, async_handle)
+    }
+}
+
+/// Optimized parallel decorator using crossbeam channels
+#[pyfunction]
+fn parallel_fast(py: Python, func: Py<PyAny>) -> PyResult<Py<ParallelFastWrapper>> {
+    Py::new(py, ParallelFastWrapper { func })
+}
+
+/// Thread pool using rayon for better resource management
+#[pyclass]
+struct ParallelPoolWrapper {
+    func: Py<PyAny>,
+}
+
+#[pymethods]
+impl ParallelPoolWrapper {
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(
+        &self,
+        py: Python,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<AsyncHandleFast>> {
+        let func = self.func.clone_ref(py);
+        let args_py: Py<PyTuple> = args.clone().unbind();
+        let kwargs_py: Option<Py<PyDict>> = kwargs.map(|k| k.clone().unbind());
+
+        let (sender, receiver) = unbounded();
+        let is_complete = Arc::new(Mutex::new(false));
+        let is_complete_clone = is_complete.clone();
+
+        // Use rayon thread pool - better resource management
+        py.detach(|| {
+            spawn_pool_job(move || {
+                Python::attach(|py| {
+                    let result = func
+                        .bind(py)
+                        .call(args_py.bind(py), kwargs_py.as_ref().map(|k| k.bind(py)));
+
+                    let to_send = match result {
+                        Ok(val) => Ok(val.unbind()),
+                        Err(e) => Err(e),
+                    };
+
+                    let _ = sender.send(to_send);
+                    *is_complete_clone.lock() = true;
+                });
+            });
+        });
+
+        let async_handle = AsyncHandleFast {
+            receiver: Arc::new(Mutex::new(receiver)),
+            is_complete,
+            result_cache: Arc::new(Mutex::new(None)),
+        };
+
+        Py::new(py, async_handle)
+    }
+}
+
+/// Parallel decorator using rayon thread pool (optimized for many small tasks)
+#[pyfunction]
+fn parallel_pool(py: Python, func: Py<PyAny>) -> PyResult<Py<ParallelPoolWrapper>> {
+    Py::new(py, ParallelPoolWrapper { func })
+}
+
+/// Optimized memoize using DashMap (lock-free concurrent hashmap)
+#[pyfunction]
+fn memoize_fast(py: Python, func: Py<PyAny>) -> PyResult<Py<PyAny>> {
+    // Use DashMap - lock-free concurrent hashmap
+    let cache: Arc<DashMap<String, Py<PyAny>>> = Arc::new(DashMap::new());
+    register_memo_cache(py, func.bind(py), MemoStore::Concurrent(Arc::downgrade(&cache)));
+    let func_clone = func.clone_ref(py);
+
+    let wrapper = move |args: &Bound<'_, PyTuple>,
+                        kwargs: Option<&Bound<'_, PyDict>>|
+          -> PyResult<Py<PyAny>> {
+        let py = args.py();
+
+        // Create cache key
+        let key = memo_key(args, kwargs)?;
+
+        // Check cache (lock-free read)
+        if let Some(cached) = cache.get(&key) {
+            println!("Cache hit for key: {}", key);
+            return Ok(cached.clone_ref(py));
+        }
+
+        // Cache miss - compute result
+        println!("Cache miss for key: {}", key);
+        let result = func_clone.bind(py).call(args, kwargs)?;
+        let result_unbound = result.unbind();
+
+        // Insert into cache (lock-free write)
+        cache.insert(key, result_unbound.clone_ref(py));
+
+        Ok(result_unbound)
+    };
+
+    let wrapped = PyCFunction::new_closure(py, None, None, wrapper)?;
+
+    let method_wrapper = Py::new(
+        py,
+        MethodWrapper {
+            func: func.clone_ref(py),
+            wrapper: wrapped.into(),
+        },
+    )?;
+    Ok(method_wrapper.into())
+}
+
+/// Batch parallel processing - execute multiple functions in parallel
+///
+/// Items are processed in chunks of `chunksize`, each under a single GIL acquisition
+/// (default: one chunk per worker thread, or one item per chunk when free-threaded).
+/// With `chunksize="auto"` chunks are sized from the time the items take, starting
+/// at one item, which keeps workers balanced when item costs vary widely.
+/// With `ordered=False` results come back in the order their chunks finished.
+/// With `on_error="collect"` failures don't raise: a MapResult holding every item's
+/// outcome is returned, and its failed items can be rerun with `retry_failures()`.
+/// With `checkpoint=path` each finished result is appended to that file, and a
+/// later call with the same path and item count skips the items already recorded.
+/// With `item_timeout=secs` an item running longer fails with TaskTimeout; the
+/// timeout is raised inside the call, so it lands at the next Python bytecode.
+/// With `seed=n`, task_random() gives each item a generator seeded from `n` and its index.
+#[pyfunction]
+#[pyo3(signature = (
+    func,
+    items,
+    chunksize=None,
+    ordered=true,
+    on_error="raise",
+    checkpoint=None,
+    item_timeout=None,
+    seed=None
+))]
+#[allow(clippy::too_many_arguments)]
+fn parallel_map(
+    py: Python,
+    func: Py<PyAny>,
+    items: Vec<Py<PyAny>>,
+    chunksize: Option<Bound<'_, PyAny>>,
+    ordered: bool,
+    on_error: &str,
+    checkpoint: Option<PathBuf>,
+    item_timeout: Option<f64>,
+    seed: Option<u64>,
+) -> PyResult<Py<PyAny>> {
+    if item_timeout.is_some_and(|secs| !secs.is_finite() || secs <= 0.0) {
+        return Err(pyo3::exceptions::PyValueError::new_err("item_timeout must be a positive number of seconds"));
+    }
+    let chunking = Chunking::parse(chunksize.as_ref())?;
+    let collect = match on_error {
+        "raise" => false,
+        "collect" if !ordered => {
+            return Err(pyo3::exceptions::PyValueError::new_err("on_error='collect' requires ordered=True"));
+        }
+        "collect" => true,
+        _ => return Err(pyo3::exceptions::PyValueError::new_err("on_error must be 'raise' or 'collect'")),
+    };
+    let (checkpoint, mut restored) = match checkpoint {
+        Some(path) => {
+            let (checkpoint, restored) = MapCheckpoint::open(py, path, items.len())?;
+            (Some(Arc::new(checkpoint)), restored)
+        }
+        None => (None, BTreeMap::new()),
+    };
+
+    let pending: Vec<usize> = (0..items.len()).filter(|index| !restored.contains_key(index)).collect();
+    let watchdog = item_timeout.map(ItemWatchdog::start);
+    let outcomes = map_outcomes(py, &pending, chunking, ordered, |py, &index| {
+        map_item(py, &func, &items, index, checkpoint.as_deref(), watchdog.as_deref(), seed)
+    })?;
+    drop(watchdog);
+
+    // Merge restored results back in; pending indices are ascending, so computed ones fill the gaps in turn
+    let outcomes: Vec<PyResult<Py<PyAny>>> = if ordered {
+        let mut computed = outcomes.into_iter();
+        (0..items.len())
+            .map(|index| match restored.remove(&index) {
+                Some(value) => Ok(value),
+                None => computed.next().expect("one outcome per pending item"),
+            })
+            .collect()
+    } else {
+        restored.into_values().map(Ok).chain(outcomes).collect()
+    };
+
+    if !collect {
+        let results = outcomes.into_iter().collect::<PyResult<Vec<_>>>()?;
+        return Ok(PyList::new(py, results)?.into_any().unbind());
+    }
+    let mut result = MapResult {
+        func,
+        items,
+        chunking,
+        checkpoint,
+        item_timeout,
+        seed,
+        results: Vec::with_capacity(outcomes.len()),
+        failures: BTreeMap::new(),
+    };
+    for (index, outcome) in outcomes.into_iter().enumerate() {
+        let value = result.record(index, outcome);
+        result.results.push(value);
+    }
+    Ok(Py::new(py, result)?.into_any())
+}
+
+/// Call `func` on item `index` under the watchdog if any, appending a success to the checkpoint
+fn map_item(
+    py: Python,
+    func: &Py<PyAny>,
+    items: &[Py<PyAny>],
+    index: usize,
+    checkpoint: Option<&MapCheckpoint>,
+    watchdog: Option<&ItemWatchdog>,
+    seed: Option<u64>,
+) -> PyResult<Py<PyAny>> {
+    let _rng = seed.map(|seed| SeededItem::enter(seed, index));
+    let call = || func.bind(py).call1((items[index].bind(py),));
+    let result = match watchdog {
+        Some(watchdog) => watchdog.run(py, index, call)?,
+        None => call()?,
+    };
+    if let Some(checkpoint) = checkpoint {
+        checkpoint.record(py, index, &result);
+    }
+    Ok(result.unbind())
+}
+
+thread_local! {
+    /// Seed of the parallel_map item running on this thread, and its generator once created
+    static TASK_RNG: RefCell<Option<(u64, Option<Py<PyAny>>)>> = const { RefCell::new(None) };
+}
+
+/// Makes task_random() deterministic for one seeded parallel_map item until dropped
+struct SeededItem;
+
+impl SeededItem {
+    fn enter(seed: u64, index: usize) -> SeededItem {
+        TASK_RNG.with(|rng| *rng.borrow_mut() = Some((item_seed(seed, index), None)));
+        SeededItem
+    }
+}
+
+impl Drop for SeededItem {
+    fn drop(&mut self) {
+        TASK_RNG.with(|rng| rng.borrow_mut().take());
+    }
+}
+
+/// Output `index` of the SplitMix64 sequence started at `seed`: well mixed and distinct per index
+fn item_seed(seed: u64, index: usize) -> u64 {
+    let mut z = seed.wrapping_add((index as u64).wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// A `random.Random` for the current task
+///
+/// Inside `parallel_map(..., seed=n)` it is seeded from `n` and the item's index,
+/// so results don't depend on which thread ran an item, and repeated calls within
+/// one item return the same generator. Anywhere else it returns a new generator
+/// seeded from OS randomness.
+#[pyfunction]
+fn task_random(py: Python) -> PyResult<Py<PyAny>> {
+    let random = py.import("random")?.getattr("Random")?;
+    TASK_RNG.with(|rng| match &mut *rng.borrow_mut() {
+        Some((_, Some(generator))) => Ok(generator.clone_ref(py)),
+        Some((seed, generator)) => {
+            let created = random.call1((*seed,))?.unbind();
+            *generator = Some(created.clone_ref(py));
+            Ok(created)
+        }
+        None => Ok(random.call0()?.unbind()),
+    })
+}
+
+/// An item the watchdog is timing
+struct WatchedItem {
+    /// Python thread ident, None if it could not be read
+    ident: Option<u64>,
+    started: Instant,
+    interrupted: bool,
+}
+
+/// Interrupts the items of one parallel_map() run that overrun `item_timeout`
+///
+/// A single thread checks the items in progress and raises TaskTimeout in the
+/// threads of those past the limit, the way task timeouts are delivered.
+struct ItemWatchdog {
+    limit: Duration,
+    running: Mutex<HashMap<usize, WatchedItem>>,
+    stopped: Mutex<bool>,
+    stop: Condvar,
+}
+
+impl ItemWatchdog {
+    /// Start the watchdog thread; it runs until the returned guard is dropped
+    fn start(item_timeout: f64) -> WatchdogGuard {
+        let watchdog = Arc::new(ItemWatchdog {
+            limit: Duration::from_secs_f64(item_timeout),
+            running: Mutex::new(HashMap::new()),
+            stopped: Mutex::new(false),
+            stop: Condvar::new(),
+        });
+        // Without threads nothing can interrupt an item; overruns are still caught once it returns
+        if !sync_fallback() {
+            let check_every = (watchdog.limit / 4).clamp(Duration::from_millis(1), Duration::from_millis(50));
+            let watched = watchdog.clone();
+            thread::spawn(move || watched.watch(check_every));
+        }
+        WatchdogGuard(watchdog)
+    }
+
+    fn watch(&self, check_every: Duration) {
+        loop {
+            {
+                let mut stopped = self.stopped.lock();
+                if !*stopped {
+                    self.stop.wait_for(&mut stopped, check_every);
+                }
+                if *stopped {
+                    return;
+                }
+            }
+            let overdue = |item: &WatchedItem| !item.interrupted && item.started.elapsed() > self.limit;
+            if !self.running.lock().values().any(overdue) {
+                continue;
+            }
+            Python::attach(|py| {
+                // Held while raising so the item cannot finish (and clear) in between
+                let mut running = self.running.lock();
+                for item in running.values_mut().filter(|item| overdue(item)) {
+                    if let Some(ident) = item.ident {
+                        set_async_exc(ident, py.get_type::<TaskTimeout>().as_ptr());
+                    }
+                    item.interrupted = true;
+                }
+            });
+        }
+    }
+
+    /// Run item `index`'s call, failing it with TaskTimeout if it overruns the limit
+    fn run<'py>(
+        &self,
+        py: Python<'py>,
+        index: usize,
+        call: impl FnOnce() -> PyResult<Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let ident = current_thread_ident(py);
+        let started = Instant::now();
+        self.running.lock().insert(index, WatchedItem { ident, started, interrupted: false });
+        let result = call();
+        if let Some(WatchedItem { ident: Some(ident), interrupted: true, .. }) = self.running.lock().remove(&index) {
+            // A timeout raised just as the call returned must not surface in the next item
+            set_async_exc(ident, std::ptr::null_mut());
+        }
+
+        if started.elapsed() > self.limit {
+            return Err(TaskTimeout::new_err(format!(
+                "item {} ran past item_timeout of {}s",
+                index,
+                self.limit.as_secs_f64()
+            )));
+        }
+        result
+    }
+}
+
+/// Stops the watchdog thread when dropped
+struct WatchdogGuard(Arc<ItemWatchdog>);
+
+impl std::ops::Deref for WatchdogGuard {
+    type Target = ItemWatchdog;
+
+    fn deref(&self) -> &ItemWatchdog {
+        &self.0
+    }
+}
+
+impl Drop for WatchdogGuard {
+    fn drop(&mut self) {
+        *self.0.stopped.lock() = true;
+        self.0.stop.notify_all();
+    }
+}
+
+thread_local! {
+    static PYTHON_THREAD_IDENT: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// `threading.get_ident()` of the current thread, cached since it never changes
+fn current_thread_ident(py: Python) -> Option<u64> {
+    if let Some(ident) = PYTHON_THREAD_IDENT.with(Cell::get) {
+        return Some(ident);
+    }
+    let ident = py
+        .import("threading")
+        .and_then(|threading| threading.call_method0("get_ident"))
+        .and_then(|ident| ident.extract::<u64>())
+        .inspect_err(|e| warn!("parallel_map items on this thread cannot be interrupted: {}", e))
+        .ok()?;
+    PYTHON_THREAD_IDENT.with(|cached| cached.set(Some(ident)));
+    Some(ident)
+}
+
+/// Identifies a parallel_map() checkpoint file, followed by its item count
+const CHECKPOINT_MAGIC: &[u8; 8] = b"MPCKPT1\n";
+
+/// Identifies a checkpoint written with a persistence key
+///
+/// The item count is followed by a random file id, then by the header sealed with an
+/// empty payload, which checks the key before any record is read.
+const ENCRYPTED_CHECKPOINT_MAGIC: &[u8; 8] = b"MPCKPTE\n";
+
+/// Length of the random id in an encrypted checkpoint's header
+const CHECKPOINT_FILE_ID_LEN: usize = 16;
+
+/// Length of an empty payload after seal(): the nonce and the tag
+const SEALED_OVERHEAD: usize = NONCE_LEN + 16;
+
+/// Context a record is sealed with: the checkpoint's file id, then the item index
+///
+/// The file id keeps a record copied from another checkpoint made with the same key
+/// from decrypting as this file's result for that index.
+fn checkpoint_record_context(file_id: &[u8], index: usize) -> Vec<u8> {
+    let mut context = file_id.to_vec();
+    context.extend_from_slice(&(index as u64).to_le_bytes());
+    context
+}
+
+/// Frame item `index`'s pickled result as a checkpoint record, sealing it if `key` is set
+fn encode_checkpoint_record(
+    key: Option<&LessSafeKey>,
+    file_id: &[u8],
+    index: usize,
+    pickled: Vec<u8>,
+) -> Result<Vec<u8>, ring::error::Unspecified> {
+    let payload = match key {
+        Some(key) => seal(key, &checkpoint_record_context(file_id, index), &pickled)?,
+        None => pickled,
+    };
+    let mut record = Vec::with_capacity(16 + payload.len());
+    record.extend_from_slice(&(index as u64).to_le_bytes());
+    record.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    record.extend_from_slice(&payload);
+    Ok(record)
+}
+
+/// Item index and pickled result of a checkpoint record; None if it failed to decrypt
+type CheckpointRecord = (usize, Option<Vec<u8>>);
+
+/// The complete records in `data`, and where the last of them ends
+fn decode_checkpoint_records(key: Option<&LessSafeKey>, file_id: &[u8], data: &[u8]) -> (Vec<CheckpointRecord>, usize) {
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = data.get(offset..offset + 16) {
+        let index = u64::from_le_bytes(header[..8].try_into().expect("8-byte slice")) as usize;
+        let len = u64::from_le_bytes(header[8..].try_into().expect("8-byte slice")) as usize;
+        let Some(payload) = (offset + 16).checked_add(len).and_then(|end| data.get(offset + 16..end)) else {
+            break;
+        };
+        offset += 16 + len;
+        let pickled = match key {
+            Some(key) => unseal(key, &checkpoint_record_context(file_id, index), payload),
+            None => Some(payload.to_vec()),
+        };
+        records.push((index, pickled));
+    }
+    (records, offset)
+}
+
+/// Append-only log of the finished items of a checkpointed parallel_map()
+///
+/// After the header, each item is one record: its index and the length of its
+/// pickled result as little-endian u64s, then the pickle. In encrypted checkpoints
+/// the pickle is sealed with the file id and the index as context. A record cut
+/// short by an interrupted run is dropped when the file is reopened.
+struct MapCheckpoint {
+    path: PathBuf,
+    file: Mutex<std::fs::File>,
+    key: Option<Arc<LessSafeKey>>,
+    /// Random id from the header of an encrypted checkpoint; empty otherwise
+    file_id: Vec<u8>,
+}
+
+impl MapCheckpoint {
+    /// Open or create the log at `path`, returning the results already recorded in it
+    fn open(py: Python, path: PathBuf, item_count: usize) -> PyResult<(MapCheckpoint, BTreeMap<usize, Py<PyAny>>)> {
+        use std::io::Write;
+
+        let os_error = |e: std::io::Error| {
+            pyo3::exceptions::PyOSError::new_err(format!("Failed to open {}: {}", path.display(), e))
+        };
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(os_error(e)),
+        };
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(&path).map_err(os_error)?;
+        let key = persistence_key();
+
+        let mut restored = BTreeMap::new();
+        let file_id;
+        if data.is_empty() {
+            let magic = if key.is_some() { ENCRYPTED_CHECKPOINT_MAGIC } else { CHECKPOINT_MAGIC };
+            let mut header = magic.to_vec();
+            header.extend_from_slice(&(item_count as u64).to_le_bytes());
+            file_id = match key {
+                Some(_) => rand::random::<[u8; CHECKPOINT_FILE_ID_LEN]>().to_vec(),
+                None => Vec::new(),
+            };
+            header.extend_from_slice(&file_id);
+            if let Some(ref key) = key {
+                let check = seal(key, &header, &[])
+                    .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("Failed to encrypt the checkpoint"))?;
+                header.extend_from_slice(&check);
+            }
+            (&file).write_all(&header).map_err(os_error)?;
+        } else {
+            let encrypted = data.starts_with(ENCRYPTED_CHECKPOINT_MAGIC);
+            let sealed_at = CHECKPOINT_MAGIC.len() + 8 + if encrypted { CHECKPOINT_FILE_ID_LEN } else { 0 };
+            let header_len = sealed_at + if encrypted { SEALED_OVERHEAD } else { 0 };
+            if data.len() < header_len || !(encrypted || data.starts_with(CHECKPOINT_MAGIC)) {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "{} is not a parallel_map checkpoint",
+                    path.display()
+                )));
+            }
+            match (encrypted, &key) {
+                (true, None) => return Err(missing_key_error(&path)),
+                // The check also authenticates the file id the records are bound to
+                (true, Some(key)) => {
+                    unseal(key, &data[..sealed_at], &data[sealed_at..header_len])
+                        .ok_or_else(|| wrong_key_error(&path))?;
+                }
+                // Appending encrypted records to it would leave earlier results readable
+                (false, Some(_)) => {
+                    return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                        "checkpoint {} was written without encryption; finish it with \
+                         configure_persistence(encryption_key=None) or remove it",
+                        path.display()
+                    )))
+                }
+                (false, None) => {}
+            }
+            let recorded_count = u64::from_le_bytes(data[8..16].try_into().expect("8-byte slice"));
+            if recorded_count != item_count as u64 {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "checkpoint {} was written for {} items, got {}",
+                    path.display(),
+                    recorded_count,
+                    item_count
+                )));
+            }
+
+            file_id = data[16..sealed_at].to_vec();
+            let pickle = py.import("pickle")?;
+            let (records, records_len) = decode_checkpoint_records(key.as_deref(), &file_id, &data[header_len..]);
+            let offset = header_len + records_len;
+            for (index, pickled) in records {
+                let Some(pickled) = pickled else {
+                    warn!("Rerunning item {}: checkpointed result failed to decrypt", index);
+                    continue;
+                };
+                match pickle.call_method1("loads", (PyBytes::new(py, &pickled),)) {
+                    Ok(value) if index < item_count => {
+                        restored.insert(index, value.unbind());
+                    }
+                    Ok(_) => warn!("Ignoring out-of-range item {} in checkpoint {}", index, path.display()),
+                    Err(e) => warn!("Rerunning item {}: checkpointed result failed to unpickle: {}", index, e),
+                }
+            }
+            // New records must start right after the last complete one
+            if offset < data.len() {
+                file.set_len(offset as u64).map_err(os_error)?;
+            }
+        }
+        Ok((MapCheckpoint { path, file: Mutex::new(file), key, file_id }, restored))
+    }
+
+    /// Append item `index`'s result; results that cannot be pickled are rerun on resume
+    fn record(&self, py: Python, index: usize, result: &Bound<'_, PyAny>) {
+        use std::io::Write;
+
+        let pickled = py
+            .import("pickle")
+            .and_then(|pickle| pickle.call_method1("dumps", (result,)))
+            .and_then(|pickled| pickled.extract::<Vec<u8>>());
+        let pickled = match pickled {
+            Ok(pickled) => pickled,
+            Err(e) => {
+                warn!("Not checkpointing item {}: result cannot be pickled: {}", index, e);
+                return;
+            }
+        };
+        let Ok(record) = encode_checkpoint_record(self.key.as_deref(), &self.file_id, index, pickled) else {
+            warn!("Not checkpointing item {}: result failed to encrypt", index);
+            return;
+        };
+        if let Err(e) = self.file.lock().write_all(&record) {
+            warn!("Failed to write checkpoint {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Per-item outcome of `parallel_map(..., on_error="collect")`
+///
+/// `results` holds each item's result in input order, with None for items that
+/// failed; their exceptions are in `failures`, keyed by index.
+#[pyclass]
+struct MapResult {
+    func: Py<PyAny>,
+    items: Vec<Py<PyAny>>,
+    chunking: Chunking,
+    checkpoint: Option<Arc<MapCheckpoint>>,
+    item_timeout: Option<f64>,
+    seed: Option<u64>,
+    results: Vec<Option<Py<PyAny>>>,
+    failures: BTreeMap<usize, PyErr>,
+}
+
+impl MapResult {
+    /// Keep item `index`'s error in `failures`, returning its result if it succeeded
+    fn record(&mut self, index: usize, outcome: PyResult<Py<PyAny>>) -> Option<Py<PyAny>> {
+        match outcome {
+            Ok(value) => {
+                self.failures.remove(&index);
+                Some(value)
+            }
+            Err(e) => {
+                self.failures.insert(index, e);
+                None
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl MapResult {
+    /// Results in input order, None where the item failed
+    #[getter]
+    fn results(&self, py: Python) -> Vec<Option<Py<PyAny>>> {
+        self.results.iter().map(|r| r.as_ref().map(|v| v.clone_ref(py))).collect()
+    }
+
+    /// Exceptions of the failed items, keyed by index
+    #[getter]
+    fn failures<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let failures = PyDict::new(py);
+        for (index, error) in &self.failures {
+            failures.set_item(index, error.value(py))?;
+        }
+        Ok(failures)
+    }
+
+    /// Indices of the items that failed
+    #[getter]
+    fn failed_indices(&self) -> Vec<usize> {
+        self.failures.keys().copied().collect()
+    }
+
+    /// Indices of the items that failed by running past `item_timeout`
+    #[getter]
+    fn timed_out(&self, py: Python) -> Vec<usize> {
+        self.failures
+            .iter()
+            .filter(|(_, error)| error.is_instance_of::<TaskTimeout>(py))
+            .map(|(&index, _)| index)
+            .collect()
+    }
+
+    /// True when every item succeeded
+    #[getter]
+    fn ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// All results in input order, raising the first failure if any item failed
+    fn get(&self, py: Python) -> PyResult<Vec<Py<PyAny>>> {
+        if let Some(error) = self.failures.values().next() {
+            return Err(error.clone_ref(py));
+        }
+        Ok(self.results.iter().flatten().map(|v| v.clone_ref(py)).collect())
+    }
+
+    /// Rerun only the failed items, up to `max_attempts` rounds, merging their results
+    ///
+    /// Each round reruns the items still failing in parallel, with the same `item_timeout`
+    /// and `seed`, recording successes in the map's checkpoint if it has one.
+    /// Returns self, with whatever still fails after the last round left in `failures`.
+    #[pyo3(signature = (max_attempts=3))]
+    fn retry_failures(mut slf: PyRefMut<'_, Self>, max_attempts: usize) -> PyResult<PyRefMut<'_, Self>> {
+        if max_attempts == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("max_attempts must be at least 1"));
+        }
+        let py = slf.py();
+        for _ in 0..max_attempts {
+            if slf.failures.is_empty() {
+                break;
+            }
+            let indices = slf.failed_indices();
+            let this = &*slf;
+            let watchdog = this.item_timeout.map(ItemWatchdog::start);
+            let outcomes = map_outcomes(py, &indices, this.chunking, true, |py, &index| {
+                let (checkpoint, watchdog) = (this.checkpoint.as_deref(), watchdog.as_deref());
+                map_item(py, &this.func, &this.items, index, checkpoint, watchdog, this.seed)
+            })?;
+            for (index, outcome) in indices.into_iter().zip(outcomes) {
+                slf.results[index] = slf.record(index, outcome);
+            }
+        }
+        Ok(slf)
+    }
+
+    fn __len__(&self) -> usize {
+        self.results.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("MapResult(items={}, failed={})", self.results.len(), self.failures.len())
+    }
+}
+
+/// Like parallel_map(), but each item is a tuple of positional arguments
+///
+/// `kwargs_list`, if given, holds one dict of keyword arguments per item.
+#[pyfunction]
+#[pyo3(signature = (func, iterable, kwargs_list=None, chunksize=None, ordered=true))]
+fn parallel_starmap(
+    py: Python,
+    func: Py<PyAny>,
+    iterable: &Bound<'_, PyAny>,
+    kwargs_list: Option<Vec<Option<Py<PyDict>>>>,
+    chunksize: Option<usize>,
+    ordered: bool,
+) -> PyResult<Vec<Py<PyAny>>> {
+    let args_list = iterable
+        .try_iter()?
+        .map(|args| Ok(PyTuple::new(py, args?.try_iter()?.collect::<PyResult<Vec<_>>>()?)?.unbind()))
+        .collect::<PyResult<Vec<Py<PyTuple>>>>()?;
+    let kwargs_list = match kwargs_list {
+        Some(kwargs_list) if kwargs_list.len() != args_list.len() => {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "kwargs_list has {} entries for {} argument tuples",
+                kwargs_list.len(),
+                args_list.len()
+            )));
+        }
+        Some(kwargs_list) => kwargs_list,
+        None => args_list.iter().map(|_| None).collect(),
+    };
+    let items: Vec<_> = args_list.into_iter().zip(kwargs_list).collect();
+
+    map_in_chunks(py, &items, chunksize, ordered, |py, (args, kwargs)| {
+        func.bind(py)
+            .call(args.bind(py), kwargs.as_ref().map(|k| k.bind(py)))
+            .map(|r| r.unbind())
+    })
+}
+
+/// Run `call` over items on the rayon pool, attaching once per chunk
+fn map_in_chunks<T: Sync>(
+    py: Python,
+    items: &[T],
+    chunksize: Option<usize>,
+    ordered: bool,
+    call: impl Fn(Python, &T) -> PyResult<Py<PyAny>> + Sync,
+) -> PyResult<Vec<Py<PyAny>>> {
+    map_outcomes_in_chunks(py, items, chunksize, ordered, call)?.into_iter().collect()
+}
+
+/// Like map_in_chunks(), but returns every item's outcome instead of the first error
+fn map_outcomes_in_chunks<T: Sync>(
+    py: Python,
+    items: &[T],
+    chunksize: Option<usize>,
+    ordered: bool,
+    call: impl Fn(Python, &T) -> PyResult<Py<PyAny>> + Sync,
+) -> PyResult<Vec<PyResult<Py<PyAny>>>> {
+    if chunksize == Some(0) {
+        return Err(pyo3::exceptions::PyValueError::new_err("chunksize must be at least 1"));
+    }
+    let run_chunk = |chunk: &[T]| Python::attach(|py| chunk.iter().map(|item| call(py, item)).collect::<Vec<_>>());
+
+    Ok(py.detach(|| {
+        let chunk_size = chunksize.unwrap_or_else(|| default_chunksize(items.len()));
+
+        // Use rayon for parallel iteration
+        in_thread_pool(|| {
+            if ordered {
+                items.par_chunks(chunk_size).flat_map_iter(run_chunk).collect()
+            } else {
+                let finished = Mutex::new(Vec::with_capacity(items.len()));
+                items.par_chunks(chunk_size).for_each(|chunk| {
+                    let results = run_chunk(chunk);
+                    finished.lock().extend(results);
+                });
+                finished.into_inner()
+            }
+        })
+    }))
+}
+
+/// How parallel_map() splits its items into chunks
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Chunking {
+    /// `chunksize` items per chunk, or default_chunksize() when None
+    Fixed(Option<usize>),
+    /// Size each chunk from the cost of the items measured so far (`chunksize="auto"`)
+    Auto,
+}
+
+impl Chunking {
+    fn parse(chunksize: Option<&Bound<'_, PyAny>>) -> PyResult<Chunking> {
+        match chunksize {
+            None => Ok(Chunking::Fixed(None)),
+            Some(value) if value.is_instance_of::<PyString>() => match value.extract::<&str>()? {
+                "auto" => Ok(Chunking::Auto),
+                other => Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "chunksize must be a positive int or \"auto\", got {:?}",
+                    other
+                ))),
+            },
+            Some(value) => Ok(Chunking::Fixed(Some(value.extract()?))),
+        }
+    }
+}
+
+/// map_outcomes_in_chunks() or map_outcomes_adaptive(), as `chunking` asks
+fn map_outcomes<T: Sync>(
+    py: Python,
+    items: &[T],
+    chunking: Chunking,
+    ordered: bool,
+    call: impl Fn(Python, &T) -> PyResult<Py<PyAny>> + Sync,
+) -> PyResult<Vec<PyResult<Py<PyAny>>>> {
+    match chunking {
+        Chunking::Fixed(chunksize) => map_outcomes_in_chunks(py, items, chunksize, ordered, call),
+        Chunking::Auto => Ok(map_outcomes_adaptive(py, items, ordered, call)),
+    }
+}
+
+/// How long a chunksize="auto" batch should take: enough to amortize attaching, short enough to balance
+const AUTO_BATCH_TARGET: Duration = Duration::from_millis(20);
+
+/// Hands out batches of item indices for chunksize="auto"
+///
+/// Batches start at one item. Each finished batch updates a moving average of the
+/// cost per item, and the next batch is sized to take about AUTO_BATCH_TARGET,
+/// growing at most twofold per batch. Near the end batches shrink so the
+/// remaining items still spread over every worker.
+struct BatchTuner {
+    next: usize,
+    len: usize,
+    workers: usize,
+    /// Size of the last batch handed out
+    batch: usize,
+    /// Smoothed seconds per item, None until a batch has finished
+    item_cost: Option<f64>,
+}
+
+impl BatchTuner {
+    fn new(len: usize, workers: usize) -> BatchTuner {
+        BatchTuner { next: 0, len, workers: workers.max(1), batch: 1, item_cost: None }
+    }
+
+    fn next_batch(&mut self) -> Option<std::ops::Range<usize>> {
+        let remaining = self.len - self.next;
+        if remaining == 0 {
+            return None;
+        }
+        let size = match self.item_cost {
+            None => 1,
+            Some(cost) => {
+                let ideal = (AUTO_BATCH_TARGET.as_secs_f64() / cost.max(1e-9)) as usize;
+                ideal.min(self.batch * 2).min(remaining.div_ceil(2 * self.workers))
+            }
+        };
+        self.batch = size.clamp(1, remaining);
+        let start = self.next;
+        self.next += self.batch;
+        Some(start..self.next)
+    }
+
+    fn record(&mut self, items: usize, elapsed: Duration) {
+        let cost = elapsed.as_secs_f64() / items as f64;
+        self.item_cost = Some(match self.item_cost {
+            Some(previous) => (previous + cost) / 2.0,
+            None => cost,
+        });
+    }
+}
+
+/// Like map_outcomes_in_chunks(), but workers pull batches sized by a BatchTuner
+fn map_outcomes_adaptive<T: Sync>(
+    py: Python,
+    items: &[T],
+    ordered: bool,
+    call: impl Fn(Python, &T) -> PyResult<Py<PyAny>> + Sync,
+) -> Vec<PyResult<Py<PyAny>>> {
+    let workers = pool_num_threads().min(items.len()).max(1);
+    let tuner = Mutex::new(BatchTuner::new(items.len(), workers));
+    let finished = Mutex::new(Vec::new());
+
+    py.detach(|| {
+        in_thread_pool(|| {
+            rayon::scope(|scope| {
+                for _ in 0..workers {
+                    scope.spawn(|_| loop {
+                        let batch = tuner.lock().next_batch();
+                        let Some(batch) = batch else { break };
+                        // Timed after attaching, so waiting for the GIL doesn't count as item cost
+                        let (outcomes, elapsed) = Python::attach(|py| {
+                            let started = Instant::now();
+                            let outcomes: Vec<_> = items[batch.clone()].iter().map(|item| call(py, item)).collect();
+                            (outcomes, started.elapsed())
+                        });
+                        tuner.lock().record(batch.len(), elapsed);
+                        finished.lock().push((batch.start, outcomes));
+                    });
+                }
+            })
+        })
+    });
+
+    let mut finished = finished.into_inner();
+    if ordered {
+        finished.sort_unstable_by_key(|(start, _)| *start);
+    }
+    finished.into_iter().flat_map(|(_, outcomes)| outcomes).collect()
+}
+
+/// Items per GIL acquisition when the caller doesn't choose
+fn default_chunksize(len: usize) -> usize {
+    if FREE_THREADED.load(Ordering::Relaxed) {
+        // No GIL to serialize on: attach per item so rayon can balance the load
+        1
+    } else {
+        // Calls serialize on the GIL anyway, so attach once per chunk instead of per item
+        len.div_ceil(pool_num_threads()).max(1)
+    }
+}
+
+/// Keep the items for which `pred(item)` is truthy, in their original order
+#[pyfunction]
+#[pyo3(signature = (pred, items, chunksize=None))]
+fn parallel_filter(
+    py: Python,
+    pred: Py<PyAny>,
+    items: Vec<Py<PyAny>>,
+    chunksize: Option<usize>,
+) -> PyResult<Vec<Py<PyAny>>> {
+    let keep = map_in_chunks(py, &items, chunksize, true, |py, item| {
+        let keep = pred.bind(py).call1((item.bind(py),))?.is_truthy()?;
+        Ok(PyBool::new(py, keep).to_owned().into_any().unbind())
+    })?;
+    Ok(items
+        .into_iter()
+        .zip(keep)
+        .filter(|(_, keep)| keep.bind(py).is_truthy().unwrap_or(false))
+        .map(|(item, _)| item)
+        .collect())
+}
+
+/// Fold items with `func(acc, item)` as a parallel tree reduction
+///
+/// Chunks are reduced left to right on worker threads and the partial results combined
+/// pairwise, so `func` must be associative. Input order is preserved, so it need not be
+/// commutative. `initializer`, if given, is placed before the first item.
+#[pyfunction]
+#[pyo3(signature = (func, items, initializer=None, chunksize=None))]
+fn parallel_reduce(
+    py: Python,
+    func: Py<PyAny>,
+    items: Vec<Py<PyAny>>,
+    initializer: Option<Py<PyAny>>,
+    chunksize: Option<usize>,
+) -> PyResult<Py<PyAny>> {
+    if chunksize == Some(0) {
+        return Err(pyo3::exceptions::PyValueError::new_err("chunksize must be at least 1"));
+    }
+    let items: Vec<Py<PyAny>> = initializer.into_iter().chain(items).collect();
+    let combine = |py: Python, acc: Py<PyAny>, item: &Py<PyAny>| func.bind(py).call1((acc, item)).map(|r| r.unbind());
+
+    let reduced = py.detach(|| {
+        let chunk_size = chunksize.unwrap_or_else(|| default_chunksize(items.len()));
+        in_thread_pool(|| {
+            items
+                .par_chunks(chunk_size)
+                .map(|chunk| {
+                    Python::attach(|py| {
+                        let (first, rest) = chunk.split_first().expect("chunks are never empty");
+                        rest.iter().try_fold(first.clone_ref(py), |acc, item| combine(py, acc, item))
+                    })
+                })
+                .try_reduce_with(|left, right| Python::attach(|py| combine(py, left, &right)))
+        })
+    });
+
+    reduced.unwrap_or_else(|| {
+        Err(pyo3::exceptions::PyTypeError::new_err(
+            "parallel_reduce() of empty iterable with no initial value",
+        ))
+    })
+}
+
+/// Iterator over `func(item)` results computed ahead of the consumer
+#[pyclass]
+struct PrefetchIterator {
+    func: Py<PyAny>,
+    items: Py<PyIterator>,
+    in_flight: VecDeque<CrossbeamReceiver<PyResult<Py<PyAny>>>>,
+    depth: usize,
+    ordered: bool,
+    exhausted: bool,
+}
+
+impl PrefetchIterator {
+    /// Create the iterator and start computing straight away rather than on the first next()
+    fn start(py: Python, func: Py<PyAny>, items: &Bound<'_, PyAny>, depth: usize, ordered: bool) -> PyResult<Self> {
+        let mut iterator = PrefetchIterator {
+            func,
+            items: items.try_iter()?.unbind(),
+            in_flight: VecDeque::new(),
+            depth,
+            ordered,
+            exhausted: false,
+        };
+        iterator.fill(py)?;
+        Ok(iterator)
+    }
+
+    /// Start computing items until `depth` results are in flight
+    fn fill(&mut self, py: Python) -> PyResult<()> {
+        while !self.exhausted && self.in_flight.len() < self.depth {
+            let Some(item) = self.items.bind(py).clone().next() else {
+                self.exhausted = true;
+                break;
+            };
+            let item = item?.unbind();
+            let func = self.func.clone_ref(py);
+            let (sender, receiver) = crossbeam::channel::bounded(1);
+            spawn_pool_job(move || {
+                Python::attach(|py| {
+                    let _ = sender.send(func.bind(py).call1((item.bind(py),)).map(|r| r.unbind()));
+                });
+            });
+            self.in_flight.push_back(receiver);
+        }
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl PrefetchIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<Py<PyAny>>> {
+        self.fill(py)?;
+        if self.in_flight.is_empty() {
+            return Ok(None);
+        }
+
+        let result = if self.ordered {
+            let next = self.in_flight.pop_front().expect("checked non-empty");
+            // Keep the window full while the caller works on this result
+            self.fill(py)?;
+            py.detach(|| next.recv())
+        } else {
+            let in_flight = &self.in_flight;
+            let (index, result) = py.detach(|| {
+                let mut select = crossbeam::channel::Select::new();
+                for receiver in in_flight {
+                    select.recv(receiver);
+                }
+                let op = select.select();
+                let index = op.index();
+                (index, op.recv(&in_flight[index]))
+            });
+            self.in_flight.remove(index);
+            self.fill(py)?;
+            result
+        };
+
+        result
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+            .map(Some)
+    }
+}
+
+/// Compute `func(item)` for upcoming items in the background while results are consumed in order
+///
+/// At most `depth` results are computed ahead (default: one per worker thread).
+/// Iteration blocks only when the next result is not ready yet.
+#[pyfunction]
+#[pyo3(signature = (func, args_list, depth=None))]
+fn prefetch(
+    py: Python,
+    func: Py<PyAny>,
+    args_list: &Bound<'_, PyAny>,
+    depth: Option<usize>,
+) -> PyResult<PrefetchIterator> {
+    let depth = depth.unwrap_or_else(pool_num_threads);
+    if depth == 0 {
+        return Err(pyo3::exceptions::PyValueError::new_err("depth must be at least 1"));
+    }
+
+    PrefetchIterator::start(py, func, args_list, depth, true)
+}
+
+/// Lazy parallel_map(): pulls items from `iterable` as results are consumed
+///
+/// At most `prefetch` items are in flight at once (default: one per worker thread), so
+/// neither the inputs nor the outputs are materialized. With `ordered=False` results
+/// are yielded as soon as they finish.
+#[pyfunction]
+#[pyo3(signature = (func, iterable, prefetch=None, ordered=true))]
+fn parallel_imap(
+    py: Python,
+    func: Py<PyAny>,
+    iterable: &Bound<'_, PyAny>,
+    prefetch: Option<usize>,
+    ordered: bool,
+) -> PyResult<PrefetchIterator> {
+    let prefetch = prefetch.unwrap_or_else(pool_num_threads);
+    if prefetch == 0 {
+        return Err(pyo3::exceptions::PyValueError::new_err("prefetch must be at least 1"));
+    }
+    PrefetchIterator::start(py, func, iterable, prefetch, ordered)
+}
+
+/// Priority parallel wrapper - tasks execute based on priority
+#[pyclass]
+struct PriorityParallelWrapper {
+    func: Py<PyAny>,
+    name: Option<String>,
+    /// Class used when a call passes no `priority_class`
+    priority_class: Option<String>,
+    /// Task options only come from `_mp_`-prefixed keyword arguments
+    namespaced: bool,
+}
+
+impl PriorityParallelWrapper {
+    /// Submit a call whose task options are already separated from the function's arguments
+    #[allow(clippy::too_many_arguments)]
+    fn call_with(
+        &self,
+        py: Python,
+        args: &Bound<'_, PyTuple>,
+        priority: i32,
+        deadline: Option<f64>,
+        priority_class: Option<String>,
+        timeout: Option<f64>,
+        total_timeout: Option<f64>,
+        tags: Option<Vec<String>>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<AsyncHandle>> {
+        validate_timeouts(timeout, total_timeout)?;
+        if deadline.is_some_and(|d| !d.is_finite()) {
+            return Err(pyo3::exceptions::PyValueError::new_err("deadline must be a finite Unix timestamp"));
+        }
+        let priority_class = resolve_priority_class(priority_class.or_else(|| self.priority_class.clone()))?;
+        let tags = tags.unwrap_or_default();
+
+        // Check if shutdown is requested
+        if is_shutdown_requested() {
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                "Cannot start new tasks: shutdown in progress"
+            ));
+        }
+
+        // Wait for available slot (backpressure)
+        py.detach(wait_while_paused);
+        py.detach(wait_for_slot);
+        py.detach(wait_for_load);
+        wait_for_gpu_memory(py, &tags);
+
+        // Check memory before starting
+        if !check_memory_ok() {
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                "Memory limit reached, cannot start new task"
+            ));
+        }
+
+        let func = self.func.clone_ref(py);
+
+        // Generate unique task ID
+        let task_id = format!("task_{}", TASK_ID_COUNTER.fetch_add(1, Ordering::Relaxed));
+        let task_id_clone = task_id.clone();
+
+        // Get function name for profiling
+        let func_name = self.name.clone().unwrap_or_else(|| callable_name(func.bind(py)));
+
+        let args_py: Py<PyTuple> = args.clone().unbind();
+        let kwargs_py: Option<Py<PyDict>> = kwargs.map(|k| k.clone().unbind());
+
+        // Use crossbeam channel for priority queue
+        let (sender, receiver) = unbounded();
+
+        let is_complete = Arc::new(Mutex::new(false));
+        let cancel_token = Arc::new(AtomicBool::new(false));
+        let start_time = Instant::now();
+
+        // Register task as active
+        let submitted_from = submission_site();
+        register_task(ActiveTask {
+            task_id: task_id.clone(),
+            func_name: func_name.clone(),
+            tags: tags.clone(),
+            cancel_token: cancel_token.clone(),
+            is_complete: is_complete.clone(),
+            started_at: start_time,
+            submitted_from: submitted_from.clone(),
+        });
+
+        // Setup timeout if specified: the timer covers the whole task
+        if let Some(timeout_secs) = total_timeout.or(timeout) {
+            start_timeout_timer(&task_id, timeout_secs, cancel_token.clone());
+        }
+
+        let failure_watch = Arc::new(FailureWatch::default());
+        let completion = Arc::new(TaskCompletion::default());
+        let completion_clone = completion.clone();
+        open_result_stream(&task_id, &completion);
+
+        // Create priority task
+        let task = PriorityTask {
+            priority,
+            deadline,
+            task_id: task_id.clone(),
+            func,
+            func_name: func_name.clone(),
+            args: args_py,
+            kwargs: kwargs_py,
+            sender,
+            timeout,
+            total_timeout,
+            submitted_at: start_time,
+            failure_watch: failure_watch.clone(),
+            completion: completion.clone(),
+        };
+
+        // Push to its class's queue, or the shared priority queue
+        if let Some(task) = enqueue_in_class(priority_class.as_deref(), task) {
+            PRIORITY_QUEUE.lock().push(task);
+
+            // Ensure worker is running
+            if sync_fallback() {
+                drain_priority_queue();
+            } else if !PRIORITY_WORKER_RUNNING.load(Ordering::SeqCst) {
+                start_priority_worker(py)?;
+            }
+        }
+
+        // Create full AsyncHandle with all features
+        let async_handle = AsyncHandle {
+            receiver: Arc::new(Mutex::new({
+                // Convert crossbeam receiver to std::sync::mpsc receiver
+                // We need to spawn a helper thread to bridge the two channel types
+                let (std_sender, std_receiver): (ResultSender, ResultReceiver) = channel();
+                let is_complete_clone = is_complete.clone();
+
+                spawn_task_thread(move || {
+                    match receiver.recv() {
+                        Ok(result) => {
+                            let _ = std_sender.send(result);
+                            *is_complete_clone.lock() = true;
+                            unregister_task(&task_id_clone);
+                        }
+                        Err(_) => {
+                            Python::attach(|py| {
+                                let outcome = Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                                    "Priority task channel closed unexpectedly"
+                                ));
+                                let _ = std_sender.send(clone_outcome(py, &outcome));
+                                *is_complete_clone.lock() = true;
+                                completion_clone.finish(py, outcome);
+                            });
+                            unregister_task(&task_id_clone);
+                        }
+                    }
+                });
+
+                std_receiver
+            })),
+            thread_handle: Arc::new(Mutex::new(None)), // Priority tasks don't have individual thread handles
+            is_complete,
+            result_cache: Arc::new(Mutex::new(None)),
+            cancel_token,
+            func_name,
+            start_time,
+            task_id,
+            metadata: initial_metadata(&submitted_from),
+            timeout,
+            total_timeout,
+            tags,
+            on_complete: Arc::new(Mutex::new(None)),
+            on_error: Arc::new(Mutex::new(None)),
+            on_progress: Arc::new(Mutex::new(None)),
+            failure_watch,
+            completion,
+        };
+
+        Py::new(py, async_handle)
+    }
+}
+
+#[pymethods]
+impl PriorityParallelWrapper {
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(
+        &self,
+        py: Python,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<AsyncHandle>> {
+        let options = CallOptions::new(kwargs, self.namespaced);
+        let priority = options.take("priority")?.unwrap_or(0);
+        let deadline = options.take("deadline")?;
+        let priority_class = options.take("priority_class")?;
+        let timeout = options.take("timeout")?;
+        let total_timeout = options.take("total_timeout")?;
+        let tags = options.take("tags")?;
+        self.call_with(py, args, priority, deadline, priority_class, timeout, total_timeout, tags, kwargs)
+    }
+}
+
+/// Priority parallel decorator
+///
+/// `@parallel_priority(name="x")` records metrics and events under "x", and
+/// `@parallel_priority(priority_class="critical")` queues calls in that class by default.
+/// `namespaced=True` takes call options only as `_mp_priority=`, `_mp_timeout=` and so on.
+#[pyfunction]
+#[pyo3(signature = (func=None, *, name=None, priority_class=None, namespaced=false))]
+fn parallel_priority(
+    py: Python,
+    func: Option<Py<PyAny>>,
+    name: Option<String>,
+    priority_class: Option<String>,
+    namespaced: bool,
+) -> PyResult<Py<PyAny>> {
+    decorate(py, func, move |py, func| {
+        let priority_class = priority_class.clone();
+        let wrapper = PriorityParallelWrapper { func, name: name.clone(), priority_class, namespaced };
+        Ok(Py::new(py, wrapper)?.into_any())
+    })
+}
+
+/// Decorator with profiling enabled
+///
+/// `@profiled(name="x")` records metrics under "x" instead of the function's name.
+#[pyfunction]
+#[pyo3(signature = (func=None, *, name=None))]
+fn profiled(py: Python, func: Option<Py<PyAny>>, name: Option<String>) -> PyResult<Py<PyAny>> {
+    decorate(py, func, move |py, func| profile_calls(py, func, name.clone()))
+}
+
+/// Wrap `func` so each call's time and outcome are recorded under `name`
+fn profile_calls(py: Python, func: Py<PyAny>, name: Option<String>) -> PyResult<Py<PyAny>> {
+    let func_clone = func.clone_ref(py);
+    let func_name = name.unwrap_or_else(|| callable_name(func.bind(py)));
+    let wrapper = move |args: &Bound<'_, PyTuple>,
+                        kwargs: Option<&Bound<'_, PyDict>>|
+          -> PyResult<Py<PyAny>> {
+        let py = args.py();
+
+        let start = Instant::now();
+        let cpu_clock = CpuClock::start();
+        let result = func_clone.bind(py).call(args, kwargs);
+        let cpu_ms = cpu_clock.elapsed_ms();
+        let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+        match result {
+            Ok(val) => {
+                record_task_execution(&func_name, duration_ms, true, cpu_ms);
+                Ok(val.unbind())
+            }
+            Err(e) => {
+                record_task_execution(&func_name, duration_ms, false, cpu_ms);
+                Err(e)
+            }
+        }
+    };
+
+    let wrapped = PyCFunction::new_closure(py, None, None, wrapper)?;
+
+    let method_wrapper = Py::new(
+        py,
+        MethodWrapper {
+            func: func.clone_ref(py),
+            wrapper: wrapped.into(),
+        },
+    )?;
+    Ok(method_wrapper.into())
+}
+
+// =============================================================================
+// PROCESS EXECUTOR
+// =============================================================================
+
+/// Python side of a worker process: length-prefixed frames over stdin/stdout.
+/// Each job is the pickled call followed by its JSON sandbox settings.
+const PROCESS_WORKER_SCRIPT: &str = r#"
+import json, os, pickle, struct, sys
+
+sys.path[:] = json.loads(os.environ.get("MAKEPARALLEL_WORKER_PATH", "[]")) or sys.path
+
+# Like multiprocessing's spawn mode, make functions from the parent's __main__ importable
+main_path = os.environ.get("MAKEPARALLEL_WORKER_MAIN")
+if main_path:
+    import runpy, types
+    main = types.ModuleType("__mp_main__")
+    main.__dict__.update(runpy.run_path(main_path, run_name="__mp_main__"))
+    sys.modules["__main__"] = sys.modules["__mp_main__"] = main
+
+# Keep the protocol stream private: user output on fd 1 goes to stderr instead
+out = os.fdopen(os.dup(1), "wb")
+os.dup2(2, 1)
+sys.stdout = sys.stderr
+inp = sys.stdin.buffer
+
+try:
+    import cloudpickle as serializer
+except ImportError:
+    serializer = pickle
+
+def read_exact(n):
+    data = b""
+    while len(data) < n:
+        chunk = inp.read(n - len(data))
+        if not chunk:
+            raise EOFError
+        data += chunk
+    return data
+
+def read_frame():
+    (size,) = struct.unpack("<Q", read_exact(8))
+    return read_exact(size)
+
+def call_sandboxed(func, args, kwargs, sandbox):
+    # Each task sees only its own cwd/env; the worker's are restored afterwards
+    cwd, environ = os.getcwd(), dict(os.environ)
+    try:
+        if not sandbox["inherit_env"]:
+            os.environ.clear()
+        os.environ.update(sandbox["env"])
+        if sandbox["cwd"] is not None:
+            os.chdir(sandbox["cwd"])
+        return func(*args, **kwargs)
+    finally:
+        os.chdir(cwd)
+        os.environ.clear()
+        os.environ.update(environ)
+
+while True:
+    try:
+        payload = read_frame()
+        sandbox = json.loads(read_frame())
+    except EOFError:
+        break
+    try:
+        func, args, kwargs = pickle.loads(payload)
+        data = serializer.dumps((True, call_sandboxed(func, args, kwargs, sandbox)))
+    except BaseException as exc:
+        data = pickle.dumps((False, (type(exc).__name__, str(exc))))
+    out.write(struct.pack("<Q", len(data)) + data)
+    out.flush()
+"#;
+
+/// How to start worker processes so they can import the parent's code
+#[derive(Clone)]
+struct WorkerLaunch {
+    python: String,
+    sys_path: String,
+    main_path: Option<String>,
+}
+
+impl WorkerLaunch {
+    fn from_current(py: Python) -> PyResult<Self> {
+        let sys = py.import("sys")?;
+        let sys_path = py
+            .import("json")?
+            .call_method1("dumps", (sys.getattr("path")?,))?
+            .extract()?;
+        let main_path = sys
+            .getattr("modules")?
+            .get_item("__main__")
+            .and_then(|main| main.getattr("__file__"))
+            .and_then(|file| file.extract::<String>())
+            .ok();
+        Ok(WorkerLaunch {
+            python: sys.getattr("executable")?.extract()?,
+            sys_path,
+            main_path,
+        })
+    }
+}
+
+/// Pickle `(func, args, kwargs)` for another interpreter, with cloudpickle when installed
+fn pickle_call(
+    py: Python,
+    func: &Bound<'_, PyAny>,
+    args: &Bound<'_, PyTuple>,
+    kwargs: Option<&Bound<'_, PyDict>>,
+) -> PyResult<Vec<u8>> {
+    let serializer = py
+        .import("cloudpickle")
+        .or_else(|_| py.import("pickle"))?;
+    let kwargs = kwargs.cloned().unwrap_or_else(|| PyDict::new(py));
+    serializer.call_method1("dumps", ((func, args, kwargs),))?.extract()
+}
+
+/// One worker subprocess and the thread reading its replies
+struct WorkerProcess {
+    child: std::process::Child,
+    stdin: std::process::ChildStdin,
+    replies: CrossbeamReceiver<std::io::Result<Vec<u8>>>,
+}
+
+impl WorkerProcess {
+    fn spawn(launch: &WorkerLaunch) -> std::io::Result<Self> {
+        use std::io::Read;
+        use std::process::{Command, Stdio};
+
+        let mut command = Command::new(&launch.python);
+        command
+            .arg("-c")
+            .arg(PROCESS_WORKER_SCRIPT)
+            .env("MAKEPARALLEL_WORKER_PATH", &launch.sys_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit());
+        if let Some(ref main_path) = launch.main_path {
+            command.env("MAKEPARALLEL_WORKER_MAIN", main_path);
+        }
+        let mut child = command.spawn()?;
+        let stdin = child.stdin.take().expect("worker stdin is piped");
+        let mut stdout = child.stdout.take().expect("worker stdout is piped");
+
+        let (reply_sender, replies) = unbounded();
+        thread::spawn(move || loop {
+            let mut header = [0u8; 8];
+            let frame = stdout.read_exact(&mut header).and_then(|_| {
+                let mut payload = vec![0u8; u64::from_le_bytes(header) as usize];
+                stdout.read_exact(&mut payload).map(|_| payload)
+            });
+            let failed = frame.is_err();
+            if reply_sender.send(frame).is_err() || failed {
+                break;
+            }
+        });
+
+        Ok(WorkerProcess { child, stdin, replies })
+    }
+
+    fn send(&mut self, payload: &[u8]) -> std::io::Result<()> {
+        use std::io::Write;
+        self.stdin.write_all(&(payload.len() as u64).to_le_bytes())?;
+        self.stdin.write_all(payload)?;
+        self.stdin.flush()
+    }
+
+    /// Stop the worker immediately, abandoning any call in progress
+    fn kill(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+
+    /// Let the worker exit after its current call by closing its input
+    fn close(self) {
+        let WorkerProcess { mut child, stdin, .. } = self;
+        drop(stdin);
+        let _ = child.wait();
+    }
+}
+
+/// A call waiting for a worker process
+struct ProcessJob {
+    payload: Vec<u8>,
+    sandbox: Vec<u8>,
+    task: ActiveTask,
+    timeout: Option<f64>,
+    completer: TaskCompleter,
+}
+
+/// Why a process job produced no reply
+enum ProcessFailure {
+    Cancelled,
+    TimedOut(f64),
+    Crashed(String),
+}
+
+/// Run one job on the dispatcher's worker, replacing the worker if it had to be stopped
+fn run_process_job(
+    launch: &WorkerLaunch,
+    worker: &mut Option<WorkerProcess>,
+    job: &ProcessJob,
+) -> Result<Vec<u8>, ProcessFailure> {
+    if job.task.cancel_token.load(Ordering::Acquire) {
+        return Err(ProcessFailure::Cancelled);
+    }
+
+    let mut current = match worker.take() {
+        Some(current) => current,
+        None => WorkerProcess::spawn(launch)
+            .map_err(|e| ProcessFailure::Crashed(format!("failed to start worker process: {}", e)))?,
+    };
+    if let Err(e) = current.send(&job.payload).and_then(|_| current.send(&job.sandbox)) {
+        current.kill();
+        return Err(ProcessFailure::Crashed(format!("failed to send task to worker process: {}", e)));
+    }
+    mark_task_running(&job.task.task_id);
+
+    let started = Instant::now();
+    loop {
+        match current.replies.recv_timeout(Duration::from_millis(50)) {
+            Ok(Ok(reply)) => {
+                *worker = Some(current);
+                return Ok(reply);
+            }
+            Ok(Err(_)) | Err(crossbeam::channel::RecvTimeoutError::Disconnected) => {
+                current.kill();
+                return Err(ProcessFailure::Crashed("worker process exited unexpectedly".to_string()));
+            }
+            Err(crossbeam::channel::RecvTimeoutError::Timeout) => {
+                // Unlike threads, a worker process can really be stopped mid-call
+                if job.task.cancel_token.load(Ordering::Acquire) {
+                    current.kill();
+                    return Err(ProcessFailure::Cancelled);
+                }
+                if let Some(limit) = job.timeout {
+                    if started.elapsed().as_secs_f64() > limit {
+                        current.kill();
+                        return Err(ProcessFailure::TimedOut(limit));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Turn a worker's reply (or its absence) into the task's outcome
+fn finish_process_job(py: Python, job: ProcessJob, reply: Result<Vec<u8>, ProcessFailure>) {
+    let exec_start = job.task.started_at;
+    let exec_time = exec_start.elapsed().as_secs_f64() * 1000.0;
+    let task = &job.task;
+    let task_error = |error_message: String, error_type: &str| TaskError {
+        task_name: task.func_name.clone(),
+        elapsed_time: exec_start.elapsed().as_secs_f64(),
+        error_message,
+        error_type: error_type.to_string(),
+        task_id: task.task_id.clone(),
+        submitted_from: task_submission_site(&task.task_id),
+    };
+
+    let decoded = reply.map(|bytes| {
+        py.import("pickle")
+            .and_then(|pickle| pickle.call_method1("loads", (PyBytes::new(py, &bytes),)))
+            .and_then(|reply| reply.extract::<(bool, Bound<'_, PyAny>)>())
+    });
+
+    let (error, timeout_limit) = match decoded {
+        Ok(Ok((true, value))) => {
+            record_task_execution(&task.func_name, exec_time, true, None);
+            publish_task_event(
+                "task_completed",
+                &task.task_id,
+                &task.func_name,
+                completed_event_details(py, &value, exec_time / 1000.0),
+            );
+            unregister_task(&task.task_id);
+            job.completer.complete(py, Ok(value.unbind()));
+            return;
+        }
+        Ok(Ok((false, details))) => {
+            let (error_type, message) = details
+                .extract::<(String, String)>()
+                .unwrap_or_else(|_| ("UnknownError".to_string(), details.to_string()));
+            (task_error(message, &error_type), None)
+        }
+        Ok(Err(e)) => (task_error(format!("invalid reply from worker process: {}", e), "WorkerError"), None),
+        Err(ProcessFailure::Cancelled) => (task_error("Task was cancelled".to_string(), "CancellationError"), None),
+        Err(ProcessFailure::TimedOut(limit)) => (
+            timeout_task_error(&task.func_name, &task.task_id, limit, exec_start),
+            Some(limit),
+        ),
+        Err(ProcessFailure::Crashed(message)) => (task_error(message, "WorkerCrashed"), None),
+    };
+
+    if error.error_type != "CancellationError" {
+        record_task_execution(&task.func_name, exec_time, false, None);
+    }
+    report_task_failure(py, &job.completer.failure_watch, &error, timeout_limit);
+    unregister_task(&task.task_id);
+    job.completer.complete(
+        py,
+        Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(error.__str__())),
+    );
+}
+
+/// Dispatcher thread owning one worker process
+fn run_process_dispatcher(launch: WorkerLaunch, jobs: CrossbeamReceiver<ProcessJob>) {
+    // Start the worker up front so the first task does not pay for interpreter startup
+    let mut worker = match WorkerProcess::spawn(&launch) {
+        Ok(worker) => Some(worker),
+        Err(e) => {
+            warn!("Failed to start worker process: {}", e);
+            None
+        }
+    };
+
+    for job in jobs {
+        let reply = run_process_job(&launch, &mut worker, &job);
+        Python::attach(|py| finish_process_job(py, job, reply));
+    }
+
+    if let Some(worker) = worker {
+        worker.close();
+    }
+}
+
+/// Pool of worker processes running pickled calls, for CPU-bound pure-Python code
+///
+/// Functions and arguments are pickled (with cloudpickle when installed), so they
+/// must be importable by the workers; results come back on regular AsyncHandles.
+#[pyclass]
+struct ProcessPool {
+    jobs: Mutex<Option<CrossbeamSender<ProcessJob>>>,
+    dispatchers: Mutex<Vec<JoinHandle<()>>>,
+    max_workers: usize,
 }
 
 #[pymethods]
-impl CallCounter {
+impl ProcessPool {
     #[new]
-    fn new(func: Py<PyAny>) -> Self {
-        CallCounter {
-            func,
-            call_count: Arc::new(Mutex::new(0)),
+    #[pyo3(signature = (max_workers=None))]
+    fn new(py: Python, max_workers: Option<usize>) -> PyResult<Self> {
+        let max_workers = max_workers
+            .unwrap_or_else(default_thread_count);
+        if max_workers == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("max_workers must be at least 1"));
         }
+
+        let launch = WorkerLaunch::from_current(py)?;
+        let (jobs, queue) = unbounded();
+        let dispatchers = (0..max_workers)
+            .map(|_| {
+                let launch = launch.clone();
+                let queue = queue.clone();
+                thread::spawn(move || run_process_dispatcher(launch, queue))
+            })
+            .collect();
+
+        Ok(ProcessPool {
+            jobs: Mutex::new(Some(jobs)),
+            dispatchers: Mutex::new(dispatchers),
+            max_workers,
+        })
     }
 
-    #[pyo3(signature = (*args, **kwargs))]
-    fn __call__(
+    /// Run `func(*args, **kwargs)` in a worker process
+    ///
+    /// `timeout` stops the worker process if the call overruns; cancelling the
+    /// handle stops it as well. `cwd`, `env` and `inherit_env=False` give the call
+    /// its own working directory and environment, undone before the worker's next task.
+    #[pyo3(signature = (func, *args, timeout=None, tags=None, cwd=None, env=None, inherit_env=true, **kwargs))]
+    #[allow(clippy::too_many_arguments)]
+    fn submit(
         &self,
         py: Python,
+        func: Py<PyAny>,
         args: &Bound<'_, PyTuple>,
+        timeout: Option<f64>,
+        tags: Option<Vec<String>>,
+        cwd: Option<std::path::PathBuf>,
+        env: Option<HashMap<String, String>>,
+        inherit_env: bool,
         kwargs: Option<&Bound<'_, PyDict>>,
-    ) -> PyResult<Py<PyAny>> {
-        let mut count = self.call_count.lock();
-        *count += 1;
-        Ok(self.func.bind(py).call(args, kwargs)?.unbind())
+    ) -> PyResult<AsyncHandle> {
+        let name = callable_name(func.bind(py));
+        self.submit_as(py, name, func, args, timeout, tags, cwd, env, inherit_env, kwargs)
+    }
+
+    /// Stop accepting tasks; with `wait=True` block until queued tasks finish and workers exit
+    #[pyo3(signature = (wait=true))]
+    fn shutdown(&self, py: Python, wait: bool) {
+        self.jobs.lock().take();
+        if wait {
+            let dispatchers: Vec<_> = self.dispatchers.lock().drain(..).collect();
+            py.detach(|| {
+                for dispatcher in dispatchers {
+                    let _ = dispatcher.join();
+                }
+            });
+        }
     }
 
     #[getter]
-    fn get_call_count(&self) -> PyResult<i32> {
-        Ok(*self.call_count.lock())
+    fn max_workers(&self) -> usize {
+        self.max_workers
     }
 
-    fn reset(&self) -> PyResult<()> {
-        *self.call_count.lock() = 0;
-        Ok(())
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
     }
 
-    fn __get__(
-        slf: PyRef<'_, Self>,
-        obj: &Bound<'_, PyAny>,
-        _objtype: Option<&Bound<'_, PyAny>>,
-    ) -> PyResult<Py<PyAny>> {
-        if obj.is_none() {
-            // Unbound method access, return self
-            let py = slf.py();
-            return Ok(slf.into_bound_py_any(py)?.unbind());
+    fn __exit__(
+        &self,
+        py: Python,
+        _exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> bool {
+        self.shutdown(py, true);
+        false
+    }
+}
+
+impl ProcessPool {
+    /// submit() with the name the task is reported under
+    #[allow(clippy::too_many_arguments)]
+    fn submit_as(
+        &self,
+        py: Python,
+        name: String,
+        func: Py<PyAny>,
+        args: &Bound<'_, PyTuple>,
+        timeout: Option<f64>,
+        tags: Option<Vec<String>>,
+        cwd: Option<std::path::PathBuf>,
+        env: Option<HashMap<String, String>>,
+        inherit_env: bool,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<AsyncHandle> {
+        validate_timeouts(timeout, None)?;
+        if is_shutdown_requested() {
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                "Cannot start new tasks: shutdown in progress"
+            ));
+        }
+        let tags = tags.unwrap_or_default();
+        py.detach(wait_while_paused);
+        wait_for_gpu_memory(py, &tags);
+        if let Some(ref dir) = cwd {
+            if !dir.is_dir() {
+                return Err(pyo3::exceptions::PyFileNotFoundError::new_err(format!(
+                    "cwd is not an existing directory: {}",
+                    dir.display()
+                )));
+            }
         }
 
-        // Bound method access, create a BoundMethod wrapper
-        let py = slf.py();
-        let call_count_clone = slf.call_count.clone();
-        let decorator = slf.into_bound_py_any(py)?.unbind();
-        let bound_method = Py::new(
-            py,
-            BoundMethod {
-                obj: obj.clone().unbind(),
-                decorator,
-                call_count: call_count_clone,
-            },
-        )?;
-        Ok(bound_method.into())
+        let payload = pickle_call(py, func.bind(py), args, kwargs)?;
+        let sandbox = serde_json::json!({
+            "cwd": cwd.map(|dir| dir.to_string_lossy().into_owned()),
+            "env": env.unwrap_or_default(),
+            "inherit_env": inherit_env,
+        })
+        .to_string()
+        .into_bytes();
+
+        let (handle, completer) = AsyncHandle::detached(name, tags, timeout);
+        let task = handle.active_task();
+        register_task(task.clone());
+
+        let jobs = self.jobs.lock();
+        let Some(ref jobs) = *jobs else {
+            unregister_task(&task.task_id);
+            return Err(pyo3::exceptions::PyRuntimeError::new_err("ProcessPool has been shut down"));
+        };
+        jobs.send(ProcessJob { payload, sandbox, task, timeout, completer })
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        Ok(handle)
     }
 }
 
-// Helper class for bound methods from CallCounter
+// Shared pool behind @parallel_process, created on first use
+static DEFAULT_PROCESS_POOL: Lazy<Mutex<Option<Py<ProcessPool>>>> = Lazy::new(|| Mutex::new(None));
+
+fn default_process_pool(py: Python) -> PyResult<Py<ProcessPool>> {
+    let mut pool = DEFAULT_PROCESS_POOL.lock();
+    if let Some(ref pool) = *pool {
+        return Ok(pool.clone_ref(py));
+    }
+    let created = Py::new(py, ProcessPool::new(py, None)?)?;
+    *pool = Some(created.clone_ref(py));
+    Ok(created)
+}
+
+/// Function wrapper that runs each call in the shared process pool
 #[pyclass]
-struct BoundMethod {
-    obj: Py<PyAny>,
-    decorator: Py<PyAny>,
-    call_count: Arc<Mutex<i32>>,
+struct ParallelProcessWrapper {
+    func: Py<PyAny>,
+    name: Option<String>,
+    /// Task options only come from `_mp_`-prefixed keyword arguments
+    namespaced: bool,
+}
+
+impl ParallelProcessWrapper {
+    /// Submit a call whose task options are already separated from the function's arguments
+    #[allow(clippy::too_many_arguments)]
+    fn call_with(
+        &self,
+        py: Python,
+        args: &Bound<'_, PyTuple>,
+        timeout: Option<f64>,
+        tags: Option<Vec<String>>,
+        cwd: Option<std::path::PathBuf>,
+        env: Option<HashMap<String, String>>,
+        inherit_env: bool,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<AsyncHandle> {
+        let pool = default_process_pool(py)?;
+        let pool = pool.borrow(py);
+        let func = self.func.clone_ref(py);
+        let name = self.name.clone().unwrap_or_else(|| callable_name(func.bind(py)));
+        pool.submit_as(py, name, func, args, timeout, tags, cwd, env, inherit_env, kwargs)
+    }
 }
 
 #[pymethods]
-impl BoundMethod {
+impl ParallelProcessWrapper {
     #[pyo3(signature = (*args, **kwargs))]
     fn __call__(
         &self,
         py: Python,
         args: &Bound<'_, PyTuple>,
         kwargs: Option<&Bound<'_, PyDict>>,
-    ) -> PyResult<Py<PyAny>> {
-        // Create new tuple with obj as first arg
-        let mut new_args = vec![self.obj.bind(py).clone()];
-        for arg in args.iter() {
-            new_args.push(arg.clone());
+    ) -> PyResult<AsyncHandle> {
+        let options = CallOptions::new(kwargs, self.namespaced);
+        let timeout = options.take("timeout")?;
+        let tags = options.take("tags")?;
+        let cwd = options.take("cwd")?;
+        let env = options.take("env")?;
+        let inherit_env = options.take("inherit_env")?.unwrap_or(true);
+        self.call_with(py, args, timeout, tags, cwd, env, inherit_env, kwargs)
+    }
+}
+
+/// Decorator running each call in a worker process (true parallelism for pure-Python CPU work)
+///
+/// `@parallel_process(name="x")` records metrics and events under "x", and
+/// `namespaced=True` takes call options only as `_mp_timeout=`, `_mp_cwd=` and so on.
+#[pyfunction]
+#[pyo3(signature = (func=None, *, name=None, namespaced=false))]
+fn parallel_process(
+    py: Python,
+    func: Option<Py<PyAny>>,
+    name: Option<String>,
+    namespaced: bool,
+) -> PyResult<Py<PyAny>> {
+    decorate(py, func, move |py, func| {
+        Ok(Py::new(py, ParallelProcessWrapper { func, name: name.clone(), namespaced })?.into_any())
+    })
+}
+
+// =============================================================================
+// SUBINTERPRETER ISOLATION
+// =============================================================================
+
+/// Where a `@parallel` task's Python code runs
+#[derive(Clone, Copy, PartialEq)]
+enum Isolation {
+    /// In the calling interpreter, on a worker thread
+    Thread,
+    /// In a fresh subinterpreter with its own GIL (PEP 684), torn down afterwards
+    Subinterpreter,
+}
+
+impl Isolation {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "thread" => Ok(Isolation::Thread),
+            "subinterpreter" => Ok(Isolation::Subinterpreter),
+            other => Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "isolation must be 'thread' or 'subinterpreter', got '{}'",
+                other
+            ))),
         }
-        let new_tuple = PyTuple::new(py, new_args)?;
-        self.decorator
-            .bind(py)
-            .call(new_tuple, kwargs)
-            .map(|r| r.unbind())
     }
+}
 
-    #[getter]
-    fn get_call_count(&self) -> PyResult<i32> {
-        Ok(*self.call_count.lock())
+/// Runs inside the subinterpreter with `payload` and `result_path` in its __main__
+const SUBINTERPRETER_SCRIPT: &str = r#"
+import pickle
+try:
+    func, args, kwargs = pickle.loads(payload)
+    data = pickle.dumps((True, func(*args, **kwargs)))
+except BaseException as exc:
+    data = pickle.dumps((False, (type(exc).__name__, str(exc))))
+with open(result_path, "xb") as f:
+    f.write(data)
+"#;
+
+/// Run a pickled call in a new isolated subinterpreter and return its result
+///
+/// Only bytes and strings cross interpreters, so the result comes back pickled
+/// through a file in a private temporary directory (mode 0700, unpredictable name),
+/// which other local users can neither read nor plant a result in. Exceptions are
+/// rebuilt from their builtin type when possible, else reported as RuntimeError.
+fn run_in_subinterpreter<'py>(py: Python<'py>, task_id: &str, payload: &[u8]) -> PyResult<Bound<'py, PyAny>> {
+    let interpreters = py.import("_interpreters").map_err(|_| {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+            "isolation='subinterpreter' requires Python 3.13 or newer"
+        )
+    })?;
+    let result_dir: PathBuf = py
+        .import("tempfile")?
+        .call_method1("mkdtemp", (format!("makeparallel-{}-", task_id),))?
+        .extract()?;
+    let result_path = result_dir.join("result.pickle");
+
+    let shared = PyDict::new(py);
+    shared.set_item("payload", PyBytes::new(py, payload))?;
+    shared.set_item("result_path", result_path.to_string_lossy())?;
+
+    let interp = interpreters.call_method1("create", ("isolated",))?;
+    let failure = interpreters.call_method1("exec", (&interp, SUBINTERPRETER_SCRIPT, shared));
+    if let Err(e) = interpreters.call_method1("destroy", (&interp,)) {
+        warn!("Failed to destroy subinterpreter for task {}: {}", task_id, e);
+    }
+    let data = std::fs::read(&result_path);
+    let _ = std::fs::remove_dir_all(&result_dir);
+
+    let failure = failure?;
+    if !failure.is_none() {
+        let details = failure.getattr("formatted").unwrap_or(failure);
+        return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+            "subinterpreter failed: {}",
+            details
+        )));
+    }
+    let data = data.map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("subinterpreter produced no result: {}", e))
+    })?;
+
+    let (ok, value): (bool, Bound<'py, PyAny>) = py
+        .import("pickle")?
+        .call_method1("loads", (PyBytes::new(py, &data),))?
+        .extract()?;
+    if ok {
+        return Ok(value);
+    }
+    let (error_type, message): (String, String) = value.extract()?;
+    let builtin_type = py
+        .import("builtins")?
+        .getattr(error_type.as_str())
+        .ok()
+        .and_then(|t| t.cast_into::<PyType>().ok())
+        .filter(|t| t.is_subclass_of::<pyo3::exceptions::PyException>().unwrap_or(false));
+    match builtin_type {
+        Some(exc_type) => Err(PyErr::from_value(exc_type.call1((message,))?)),
+        None => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+            "{}: {}",
+            error_type, message
+        ))),
     }
 }
 
-// 4. Retry Decorator
-#[pyfunction]
-#[pyo3(signature = (*, max_retries=3))]
-fn retry(_py: Python<'_>, max_retries: usize) -> PyResult<Py<PyAny>> {
-    let factory = move |py: Python<'_>, func: Py<PyAny>| -> PyResult<Py<PyAny>> {
-        let wrapper = move |args: &Bound<'_, PyTuple>,
-                            kwargs: Option<&Bound<'_, PyDict>>|
-              -> PyResult<Py<PyAny>> {
-            let py = args.py();
-            let mut last_err = None;
-            for attempt in 0..=max_retries {
-                match func.bind(py).call(args, kwargs) {
-                    Ok(res) => return Ok(res.unbind()),
-                    Err(e) => {
-                        println!("Attempt {} failed: {:?}", attempt + 1, e.to_string());
-                        last_err = Some(e);
-                        thread::sleep(Duration::from_millis(50)); // Small delay
-                    }
-                }
+// =============================================================================
+// SYNCHRONIZATION PRIMITIVES
+// =============================================================================
+
+/// Wait until `ready` accepts the guarded state, which it may then update
+///
+/// The fast path takes the lock with the GIL held; otherwise the wait runs on the
+/// condition variable with the GIL released. `timeout` of Some(0.0) never blocks.
+fn wait_for_state<S: Send>(
+    py: Python,
+    state: &Mutex<S>,
+    condvar: &Condvar,
+    timeout: Option<f64>,
+    mut ready: impl FnMut(&mut S) -> bool + Send,
+) -> PyResult<bool> {
+    if ready(&mut state.lock()) {
+        return Ok(true);
+    }
+    if timeout == Some(0.0) {
+        return Ok(false);
+    }
+    let acquired = wait_detached(py, timeout, |wait| {
+        let mut guard = state.lock();
+        if !ready(&mut guard) {
+            condvar.wait_for(&mut guard, wait);
+            if !ready(&mut guard) {
+                return None;
             }
-            Err(last_err.unwrap())
-        };
-        let wrapped = PyCFunction::new_closure(py, None, None, wrapper)?;
-        Ok(wrapped.into())
-    };
+        }
+        Some(())
+    })?;
+    Ok(acquired.is_some())
+}
 
-    // This creates a decorator that accepts arguments
-    let decorator = PyCFunction::new_closure(
-        _py,
-        None,
-        None,
-        move |args: &Bound<'_, PyTuple>, _kwargs: Option<&Bound<'_, PyDict>>| {
-            // The real function to be decorated is the first argument
-            let func = args.get_item(0)?.unbind();
-            factory(args.py(), func)
-        },
-    )?;
-    Ok(decorator.into())
+/// Timeout of an `acquire(blocking, timeout)` call, in wait_for_state terms
+fn acquire_timeout(blocking: bool, timeout: Option<f64>) -> PyResult<Option<f64>> {
+    validate_timeouts(timeout, None)?;
+    if !blocking && timeout.is_some() {
+        return Err(pyo3::exceptions::PyValueError::new_err("can't specify a timeout for a non-blocking call"));
+    }
+    Ok(if blocking { timeout } else { Some(0.0) })
 }
 
-// 5. Memoize Decorator
-#[pyfunction]
-fn memoize(py: Python, func: Py<PyAny>) -> PyResult<Py<PyAny>> {
-    let cache: Arc<Mutex<HashMap<String, Py<PyAny>>>> = Arc::new(Mutex::new(HashMap::new()));
+/// Counting semaphore whose blocking acquire() releases the GIL
+#[pyclass]
+struct Semaphore {
+    permits: Mutex<usize>,
+    condvar: Condvar,
+}
 
-    let wrapper = move |args: &Bound<'_, PyTuple>,
-                        kwargs: Option<&Bound<'_, PyDict>>|
-          -> PyResult<Py<PyAny>> {
-        let py = args.py();
+#[pymethods]
+impl Semaphore {
+    #[new]
+    #[pyo3(signature = (value=1))]
+    fn new(value: usize) -> Self {
+        Semaphore { permits: Mutex::new(value), condvar: Condvar::new() }
+    }
+
+    /// Take a permit; returns False if none became free within `timeout` seconds
+    #[pyo3(signature = (blocking=true, timeout=None))]
+    fn acquire(&self, py: Python, blocking: bool, timeout: Option<f64>) -> PyResult<bool> {
+        let timeout = acquire_timeout(blocking, timeout)?;
+        wait_for_state(py, &self.permits, &self.condvar, timeout, |permits| {
+            let free = *permits > 0;
+            if free {
+                *permits -= 1;
+            }
+            free
+        })
+    }
+
+    /// Return `n` permits, waking up to `n` waiters
+    #[pyo3(signature = (n=1))]
+    fn release(&self, n: usize) {
+        *self.permits.lock() += n;
+        for _ in 0..n {
+            self.condvar.notify_one();
+        }
+    }
+
+    /// Permits currently free
+    #[getter]
+    fn value(&self) -> usize {
+        *self.permits.lock()
+    }
+
+    fn __enter__(&self, py: Python) -> PyResult<()> {
+        self.acquire(py, true, None).map(drop)
+    }
+
+    fn __exit__(
+        &self,
+        _exc_type: &Bound<'_, PyAny>,
+        _exc_val: &Bound<'_, PyAny>,
+        _exc_tb: &Bound<'_, PyAny>,
+    ) -> bool {
+        self.release(1);
+        false
+    }
+}
+
+/// Flag that tasks can wait on, like `threading.Event`
+#[pyclass]
+struct Event {
+    flag: Mutex<bool>,
+    condvar: Condvar,
+}
 
-        // Create a cache key from arguments
-        let mut key_parts: Vec<String> = vec![];
-        for arg in args.iter() {
-            key_parts.push(arg.repr()?.to_str()?.to_string());
-        }
-        if let Some(kwargs_dict) = kwargs {
-            for (key, val) in kwargs_dict.iter() {
-                key_parts.push(format!("{}={}", key, val.repr()?.to_str()?));
-            }
-        }
-        let key = key_parts.join(",");
+#[pymethods]
+impl Event {
+    #[new]
+    fn new() -> Self {
+        Event { flag: Mutex::new(false), condvar: Condvar::new() }
+    }
 
-        let mut cache_lock = cache.lock();
+    /// Set the flag and wake every waiter
+    fn set(&self) {
+        *self.flag.lock() = true;
+        self.condvar.notify_all();
+    }
 
-        // Check if result is in cache
-        if let Some(cached_result) = cache_lock.get(&key) {
-            println!("Cache hit for key: {}", key);
-            return Ok(cached_result.clone_ref(py));
-        }
+    fn clear(&self) {
+        *self.flag.lock() = false;
+    }
 
-        // If not, call the function and store the result
-        println!("Cache miss for key: {}", key);
-        let result = func.bind(py).call(args, kwargs)?;
-        let result_unbound = result.unbind();
-        cache_lock.insert(key, result_unbound.clone_ref(py));
-        Ok(result_unbound)
-    };
+    fn is_set(&self) -> bool {
+        *self.flag.lock()
+    }
 
-    let wrapped = PyCFunction::new_closure(py, None, None, wrapper)?;
-    Ok(wrapped.into())
+    /// Wait for the flag; returns False if `timeout` seconds pass first
+    #[pyo3(signature = (timeout=None))]
+    fn wait(&self, py: Python, timeout: Option<f64>) -> PyResult<bool> {
+        validate_timeouts(timeout, None)?;
+        wait_for_state(py, &self.flag, &self.condvar, timeout, |flag| *flag)
+    }
 }
 
-// 6. Parallel Decorator - Run functions in Rust threads without GIL
+#[derive(Default)]
+struct BarrierState {
+    waiting: usize,
+    /// Bumped each time the barrier trips or is reset
+    generation: u64,
+    broken: bool,
+    /// Last generation ended by reset() rather than by tripping
+    reset_generation: Option<u64>,
+}
 
-/// AsyncHandle - Handle for async operations with pipe communication
+/// Rendezvous point for a fixed number of tasks, like `threading.Barrier`
+///
+/// A wait() that times out, is interrupted, or is cancelled breaks the barrier:
+/// every waiter then raises `threading.BrokenBarrierError` until reset().
 #[pyclass]
-struct AsyncHandle {
-    receiver: Arc<Mutex<Receiver<PyResult<Py<PyAny>>>>>,
-    thread_handle: Arc<Mutex<Option<JoinHandle<()>>>>,
-    is_complete: Arc<Mutex<bool>>,
-    result_cache: Arc<Mutex<Option<PyResult<Py<PyAny>>>>>,
-    cancel_token: Arc<AtomicBool>,
-    func_name: String,
-    start_time: Instant,
-    task_id: String,
-    metadata: Arc<Mutex<HashMap<String, String>>>,
-    timeout: Option<f64>,
-    on_complete: Arc<Mutex<Option<Py<PyAny>>>>,
-    on_error: Arc<Mutex<Option<Py<PyAny>>>>,
-    on_progress: Arc<Mutex<Option<Py<PyAny>>>>,
+struct Barrier {
+    #[pyo3(get)]
+    parties: usize,
+    state: Mutex<BarrierState>,
+    condvar: Condvar,
+}
+
+impl Barrier {
+    fn broken_error(py: Python) -> PyErr {
+        match py.import("threading").and_then(|threading| threading.getattr("BrokenBarrierError")) {
+            Ok(exc_type) => match exc_type.cast_into::<PyType>() {
+                Ok(exc_type) => PyErr::from_type(exc_type, ()),
+                Err(e) => e.into(),
+            },
+            Err(e) => e,
+        }
+    }
+
+    fn break_barrier(&self) {
+        self.state.lock().broken = true;
+        self.condvar.notify_all();
+    }
 }
 
 #[pymethods]
-impl AsyncHandle {
-    /// Check if the result is ready (non-blocking)
-    fn is_ready(&self) -> PyResult<bool> {
-        Ok(*self.is_complete.lock())
+impl Barrier {
+    #[new]
+    fn new(parties: usize) -> PyResult<Self> {
+        if parties == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("parties must be at least 1"));
+        }
+        Ok(Barrier { parties, state: Mutex::new(BarrierState::default()), condvar: Condvar::new() })
     }
 
-    /// Try to get the result without blocking (returns None if not ready)
-    fn try_get(&self, py: Python) -> PyResult<Option<Py<PyAny>>> {
-        // Check cache first
-        let mut cache = self.result_cache.lock();
-        if let Some(ref cached) = *cache {
-            return match cached {
-                Ok(val) => Ok(Some(val.clone_ref(py))),
-                Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                    "Cached error: {}",
-                    e
-                ))),
-            };
+    /// Wait until `parties` tasks have called wait(); returns this caller's
+    /// arrival index, from 0 to parties - 1
+    #[pyo3(signature = (timeout=None))]
+    fn wait(&self, py: Python, timeout: Option<f64>) -> PyResult<usize> {
+        validate_timeouts(timeout, None)?;
+        let (index, generation) = {
+            let mut state = self.state.lock();
+            if state.broken {
+                return Err(Self::broken_error(py));
+            }
+            let index = state.waiting;
+            state.waiting += 1;
+            if state.waiting == self.parties {
+                state.waiting = 0;
+                state.generation += 1;
+                self.condvar.notify_all();
+                return Ok(index);
+            }
+            (index, state.generation)
+        };
+
+        let waited = wait_for_state(py, &self.state, &self.condvar, timeout, |state| {
+            state.broken || state.generation != generation
+        });
+        let state = self.state.lock();
+        if state.generation != generation {
+            if state.reset_generation == Some(generation) {
+                return Err(Self::broken_error(py));
+            }
+            return Ok(index);
         }
+        drop(state);
 
-        // Try to receive without blocking
-        let receiver = self.receiver.lock();
-        match receiver.try_recv() {
-            Ok(result) => {
-                *self.is_complete.lock() = true;
-                match result {
-                    Ok(val) => {
-                        *cache = Some(Ok(val.clone_ref(py)));
-                        Ok(Some(val))
-                    }
-                    Err(e) => {
-                        *cache = Some(Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                            e.to_string(),
-                        )));
-                        Err(e)
-                    }
-                }
+        // Still waiting on this generation: broken, timed out or interrupted
+        match waited {
+            Ok(_) => {
+                self.break_barrier();
+                Err(Self::broken_error(py))
+            }
+            Err(e) => {
+                self.break_barrier();
+                Err(e)
             }
-            Err(_) => Ok(None), // Not ready yet
         }
     }
 
-    /// Get the result (blocking until ready)
-    fn get(&self, py: Python) -> PyResult<Py<PyAny>> {
-        // Check cache first
-        let cache = self.result_cache.lock();
-        if let Some(ref cached) = *cache {
-            return match cached {
-                Ok(val) => Ok(val.clone_ref(py)),
-                Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                    "Cached error: {}",
-                    e
-                ))),
-            };
+    /// Break the barrier; current and future waiters raise BrokenBarrierError
+    fn abort(&self) {
+        self.break_barrier();
+    }
+
+    /// Return the barrier to its initial state; current waiters raise BrokenBarrierError
+    fn reset(&self) {
+        let mut state = self.state.lock();
+        if state.waiting > 0 {
+            state.reset_generation = Some(state.generation);
         }
-        drop(cache); // Release lock before blocking recv
+        state.generation += 1;
+        state.waiting = 0;
+        state.broken = false;
+        drop(state);
+        self.condvar.notify_all();
+    }
 
-        // CRITICAL: Release GIL before blocking on recv to avoid deadlock
-        let result = py
-            .detach(|| {
-                let receiver = self.receiver.lock();
-                receiver.recv()
-            })
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    /// Tasks currently waiting
+    #[getter]
+    fn n_waiting(&self) -> usize {
+        self.state.lock().waiting
+    }
 
-        *self.is_complete.lock() = true;
+    #[getter]
+    fn broken(&self) -> bool {
+        self.state.lock().broken
+    }
+}
 
-        // Cache the result and trigger callbacks
-        let mut cache = self.result_cache.lock();
-        match result {
-            Ok(ref val) => {
-                *cache = Some(Ok(val.clone_ref(py)));
+/// Count and waiters shared by every CountDownLatch handle on one latch
+struct LatchState {
+    count: Mutex<usize>,
+    condvar: Condvar,
+}
 
-                // CRITICAL FIX: Proper callback error handling
-                if let Some(ref callback) = *self.on_complete.lock() {
-                    match callback.bind(py).call1((val.bind(py),)) {
-                        Ok(_) => {},
-                        Err(e) => {
-                            error!("on_complete callback failed: {}", e);
-                            // Don't propagate callback errors to task result
-                        }
-                    }
-                }
+/// Unresolved named latches; a name is released once its latch opens
+static LATCHES: Lazy<Mutex<HashMap<String, Arc<LatchState>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 
-                Ok(val.clone_ref(py))
-            }
-            Err(e) => {
-                let err_str = e.to_string();
-                *cache = Some(Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                    err_str.clone(),
-                )));
+/// Latch that opens once count_down() has been called `count` times
+///
+/// Latches created with the same `name` share one count until it reaches zero, so
+/// tasks started at different times can rendezvous without passing the latch
+/// around; joining an existing latch keeps its remaining count. Once the latch
+/// opens its name is freed, and the next CountDownLatch with it starts afresh.
+#[pyclass]
+struct CountDownLatch {
+    #[pyo3(get)]
+    name: Option<String>,
+    state: Arc<LatchState>,
+}
 
-                // CRITICAL FIX: Proper error callback handling
-                if let Some(ref callback) = *self.on_error.lock() {
-                    match callback.bind(py).call1((err_str.clone(),)) {
-                        Ok(_) => {},
-                        Err(e) => {
-                            error!("on_error callback failed: {}", e);
-                        }
-                    }
-                }
+#[pymethods]
+impl CountDownLatch {
+    #[new]
+    #[pyo3(signature = (count, name=None))]
+    fn new(count: usize, name: Option<String>) -> Self {
+        let fresh = || Arc::new(LatchState { count: Mutex::new(count), condvar: Condvar::new() });
+        let state = match name {
+            Some(ref name) if count > 0 => LATCHES.lock().entry(name.clone()).or_insert_with(fresh).clone(),
+            _ => fresh(),
+        };
+        CountDownLatch { name, state }
+    }
 
-                Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(err_str))
+    /// Decrement the count, opening the latch and waking every waiter at zero
+    fn count_down(&self) {
+        let opened = {
+            let mut count = self.state.count.lock();
+            if *count == 0 {
+                return;
+            }
+            *count -= 1;
+            *count == 0
+        };
+        if !opened {
+            return;
+        }
+        self.state.condvar.notify_all();
+        if let Some(ref name) = self.name {
+            let mut latches = LATCHES.lock();
+            if latches.get(name).is_some_and(|latch| Arc::ptr_eq(latch, &self.state)) {
+                latches.remove(name);
             }
         }
     }
 
-    /// Wait for completion with timeout (in seconds)
-    fn wait(&self, timeout_secs: Option<f64>) -> PyResult<bool> {
-        if *self.is_complete.lock() {
-            return Ok(true);
-        }
+    /// Calls still needed before the latch opens
+    #[getter]
+    fn count(&self) -> usize {
+        *self.state.count.lock()
+    }
 
-        if let Some(secs) = timeout_secs {
-            thread::sleep(Duration::from_secs_f64(secs));
-            Ok(*self.is_complete.lock())
-        } else {
-            // Wait indefinitely by trying to receive
-            let _ = self.receiver.lock().recv();
-            *self.is_complete.lock() = true;
-            Ok(true)
+    /// Wait for the latch to open; returns False if `timeout` seconds pass first
+    #[pyo3(signature = (timeout=None))]
+    fn wait(&self, py: Python, timeout: Option<f64>) -> PyResult<bool> {
+        validate_timeouts(timeout, None)?;
+        wait_for_state(py, &self.state.count, &self.state.condvar, timeout, |count| *count == 0)
+    }
+
+    fn __repr__(&self) -> String {
+        match self.name {
+            Some(ref name) => format!("CountDownLatch(count={}, name={:?})", self.count(), name),
+            None => format!("CountDownLatch(count={})", self.count()),
         }
     }
+}
 
-    /// Cancel the operation (non-blocking - just sets the flag)
-    fn cancel(&self) -> PyResult<()> {
-        // Set cancellation flag with Release ordering
-        self.cancel_token.store(true, Ordering::Release);
+#[derive(Default)]
+struct RwLockState {
+    readers: usize,
+    writer: bool,
+    /// Writers blocked in acquire_write(); new readers queue behind them
+    writers_waiting: usize,
+}
 
-        // Mark as complete to prevent further waits
-        *self.is_complete.lock() = true;
+/// Readers-writer lock whose blocking acquires release the GIL
+///
+/// Any number of readers may hold it at once, or a single writer. Waiting
+/// writers take precedence over new readers, so writers are not starved.
+#[pyclass(frozen)]
+struct RwLock {
+    state: Mutex<RwLockState>,
+    condvar: Condvar,
+}
 
-        // Don't join the thread - that would block!
-        // The thread will check the flag and exit on its own
-        Ok(())
+#[pymethods]
+impl RwLock {
+    #[new]
+    fn new() -> Self {
+        RwLock { state: Mutex::new(RwLockState::default()), condvar: Condvar::new() }
     }
 
-    /// Cancel with timeout (in seconds)
-    fn cancel_with_timeout(&self, timeout_secs: f64) -> PyResult<bool> {
-        self.cancel_token.store(true, Ordering::Release);
+    /// Take a shared lock; returns False if it was not granted within `timeout` seconds
+    #[pyo3(signature = (blocking=true, timeout=None))]
+    fn acquire_read(&self, py: Python, blocking: bool, timeout: Option<f64>) -> PyResult<bool> {
+        let timeout = acquire_timeout(blocking, timeout)?;
+        wait_for_state(py, &self.state, &self.condvar, timeout, |state| {
+            let free = !state.writer && state.writers_waiting == 0;
+            if free {
+                state.readers += 1;
+            }
+            free
+        })
+    }
 
-        let mut handle = self.thread_handle.lock();
-        if let Some(h) = handle.take() {
-            let start = Instant::now();
-            let timeout = Duration::from_secs_f64(timeout_secs);
+    fn release_read(&self) -> PyResult<()> {
+        let mut state = self.state.lock();
+        if state.readers == 0 {
+            return Err(pyo3::exceptions::PyRuntimeError::new_err("release_read() of an RwLock not held for reading"));
+        }
+        state.readers -= 1;
+        if state.readers == 0 {
+            self.condvar.notify_all();
+        }
+        Ok(())
+    }
 
-            // Try to join with timeout
-            while start.elapsed() < timeout {
-                if h.is_finished() {
-                    let _ = h.join();
-                    return Ok(true);
+    /// Take the exclusive lock; returns False if it was not granted within `timeout` seconds
+    #[pyo3(signature = (blocking=true, timeout=None))]
+    fn acquire_write(&self, py: Python, blocking: bool, timeout: Option<f64>) -> PyResult<bool> {
+        let timeout = acquire_timeout(blocking, timeout)?;
+        let mut queued = false;
+        let acquired = wait_for_state(py, &self.state, &self.condvar, timeout, |state| {
+            if !state.writer && state.readers == 0 {
+                if queued {
+                    state.writers_waiting -= 1;
                 }
-                thread::sleep(Duration::from_millis(10));
+                state.writer = true;
+                true
+            } else {
+                if !queued {
+                    state.writers_waiting += 1;
+                    queued = true;
+                }
+                false
             }
+        });
+        if !matches!(acquired, Ok(true)) && queued {
+            self.state.lock().writers_waiting -= 1;
+            self.condvar.notify_all();
+        }
+        acquired
+    }
 
-            return Ok(false); // Timeout
+    fn release_write(&self) -> PyResult<()> {
+        let mut state = self.state.lock();
+        if !state.writer {
+            return Err(pyo3::exceptions::PyRuntimeError::new_err("release_write() of an RwLock not held for writing"));
         }
-        Ok(true)
+        state.writer = false;
+        drop(state);
+        self.condvar.notify_all();
+        Ok(())
     }
 
-    /// Check if task was cancelled
-    fn is_cancelled(&self) -> PyResult<bool> {
-        Ok(self.cancel_token.load(Ordering::Acquire))
+    /// Context manager holding the lock for reading
+    fn read(slf: Py<Self>) -> RwLockGuard {
+        RwLockGuard { lock: slf, write: false }
     }
 
-    /// Get elapsed time since task start (in seconds)
-    fn elapsed_time(&self) -> PyResult<f64> {
-        Ok(self.start_time.elapsed().as_secs_f64())
+    /// Context manager holding the lock for writing
+    fn write(slf: Py<Self>) -> RwLockGuard {
+        RwLockGuard { lock: slf, write: true }
     }
+}
 
-    /// Get task name
-    fn get_name(&self) -> PyResult<String> {
-        Ok(self.func_name.clone())
+/// `with lock.read():` / `with lock.write():` helper
+#[pyclass]
+struct RwLockGuard {
+    lock: Py<RwLock>,
+    write: bool,
+}
+
+#[pymethods]
+impl RwLockGuard {
+    fn __enter__(&self, py: Python) -> PyResult<()> {
+        let lock = self.lock.get();
+        if self.write {
+            lock.acquire_write(py, true, None).map(drop)
+        } else {
+            lock.acquire_read(py, true, None).map(drop)
+        }
     }
 
-    /// Get task ID
-    fn get_task_id(&self) -> PyResult<String> {
-        Ok(self.task_id.clone())
+    fn __exit__(
+        &self,
+        _exc_type: &Bound<'_, PyAny>,
+        _exc_val: &Bound<'_, PyAny>,
+        _exc_tb: &Bound<'_, PyAny>,
+    ) -> PyResult<bool> {
+        let lock = self.lock.get();
+        if self.write {
+            lock.release_write()?;
+        } else {
+            lock.release_read()?;
+        }
+        Ok(false)
     }
+}
 
-    /// Set metadata
-    fn set_metadata(&self, key: String, value: String) -> PyResult<()> {
-        self.metadata.lock().insert(key, value);
-        Ok(())
+// =============================================================================
+// HELPER FUNCTIONS
+// =============================================================================
+
+/// Placeholder returned by `gather(..., return_partial=True)` for unfinished tasks
+#[pyclass(frozen, name = "PendingType")]
+struct Pending;
+
+#[pymethods]
+impl Pending {
+    fn __repr__(&self) -> &'static str {
+        "PENDING"
     }
 
-    /// Get metadata
-    fn get_metadata(&self, key: String) -> PyResult<Option<String>> {
-        Ok(self.metadata.lock().get(&key).cloned())
+    fn __bool__(&self) -> bool {
+        false
     }
+}
 
-    /// Get all metadata
-    fn get_all_metadata(&self, py: Python) -> PyResult<Py<PyDict>> {
-        let dict = PyDict::new(py);
-        let metadata = self.metadata.lock();
-        for (k, v) in metadata.iter() {
-            dict.set_item(k, v)?;
-        }
-        Ok(dict.unbind())
-    }
+/// The single `PENDING` instance of the current interpreter
+static PENDING: Lazy<Mutex<Option<Py<Pending>>>> = Lazy::new(|| Mutex::new(None));
 
-    /// Get timeout value
-    fn get_timeout(&self) -> PyResult<Option<f64>> {
-        Ok(self.timeout)
+/// The `PENDING` sentinel, created on first use
+fn pending_sentinel(py: Python) -> PyResult<Py<PyAny>> {
+    let mut pending = PENDING.lock();
+    if pending.is_none() {
+        *pending = Some(Py::new(py, Pending)?);
     }
+    Ok(pending.as_ref().map(|p| p.clone_ref(py).into_any()).unwrap_or_else(|| py.None()))
+}
 
-    /// Set completion callback
-    fn on_complete(&self, callback: Py<PyAny>) -> PyResult<()> {
-        *self.on_complete.lock() = Some(callback);
-        Ok(())
+/// Gather results from multiple handles (or `concurrent.futures.Future`s)
+///
+/// With `on_error="group"` every task is awaited and all failures are raised together
+/// as an ExceptionGroup, each exception carrying the `task_id` of its task.
+/// `timeout` bounds the whole wait: tasks unfinished by then raise TimeoutError, or
+/// with `return_partial=True` leave `PENDING` in their slot.
+#[pyfunction]
+#[pyo3(signature = (handles, on_error="raise", timeout=None, return_partial=false))]
+fn gather(
+    py: Python,
+    handles: Vec<Bound<'_, PyAny>>,
+    on_error: &str,
+    timeout: Option<f64>,
+    return_partial: bool,
+) -> PyResult<Vec<Py<PyAny>>> {
+    let results = gather_results(py, "gather", &handles, on_error, timeout, return_partial)?;
+    Ok(results.into_iter().flatten().collect())
+}
+
+/// Gather results from named handles (or futures) into a dict keyed by name
+///
+/// Takes the same `on_error`, `timeout` and `return_partial` options as gather();
+/// with `on_error="skip"` failed entries are left out of the dict.
+#[pyfunction]
+#[pyo3(signature = (handles, on_error="raise", timeout=None, return_partial=false))]
+fn gather_dict(
+    py: Python,
+    handles: &Bound<'_, PyDict>,
+    on_error: &str,
+    timeout: Option<f64>,
+    return_partial: bool,
+) -> PyResult<Py<PyDict>> {
+    let (names, handles): (Vec<_>, Vec<_>) = handles.iter().unzip();
+    let results = gather_results(py, "gather_dict", &handles, on_error, timeout, return_partial)?;
+
+    let gathered = PyDict::new(py);
+    for (name, result) in names.into_iter().zip(results) {
+        if let Some(result) = result {
+            gathered.set_item(name, result)?;
+        }
     }
+    Ok(gathered.unbind())
+}
 
-    /// Set error callback
-    fn on_error(&self, callback: Py<PyAny>) -> PyResult<()> {
-        *self.on_error.lock() = Some(callback);
-        Ok(())
+/// Shared body of gather() and gather_dict(): one slot per handle, None for skipped failures
+fn gather_results(
+    py: Python,
+    caller: &str,
+    handles: &[Bound<'_, PyAny>],
+    on_error: &str,
+    timeout: Option<f64>,
+    return_partial: bool,
+) -> PyResult<Vec<Option<Py<PyAny>>>> {
+    validate_timeouts(timeout, None)?;
+    let deadline = timeout.map(|secs| Instant::now() + Duration::from_secs_f64(secs));
+    let handles = handles
+        .iter()
+        .map(|obj| as_handle(py, obj))
+        .collect::<PyResult<Vec<_>>>()?;
+    let mut results = Vec::new();
+    let mut failures = Vec::new();
+
+    for handle in handles {
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now()).as_secs_f64();
+            let finished = if remaining > 0.0 {
+                handle.borrow(py).wait(py, Some(remaining))?
+            } else {
+                handle.borrow(py).is_ready()?
+            };
+            if !finished {
+                if !return_partial {
+                    return Err(pyo3::exceptions::PyTimeoutError::new_err(format!(
+                        "{}() timed out after {}s waiting for task {}",
+                        caller,
+                        timeout.unwrap_or_default(),
+                        handle.borrow(py).task_id
+                    )));
+                }
+                results.push(Some(pending_sentinel(py)?));
+                continue;
+            }
+        }
+
+        let h = handle.bind(py);
+        match h.call_method0("get") {
+            Ok(result) => results.push(Some(result.unbind())),
+            Err(e) => match on_error {
+                "raise" => return Err(e),
+                "skip" => results.push(None),
+                "none" => results.push(Some(py.None())),
+                "group" => {
+                    let task_id = handle.borrow(py).task_id.clone();
+                    failures.push(tag_task_exception(py, e, &task_id)?);
+                }
+                _ => return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                    "on_error must be 'raise', 'skip', 'none', or 'group'"
+                )),
+            },
+        }
     }
 
-    /// Set progress callback
-    fn on_progress(&self, py: Python, callback: Py<PyAny>) -> PyResult<()> {
-        *self.on_progress.lock() = Some(callback.clone_ref(py));
-        register_progress_callback(self.task_id.clone(), callback);
-        Ok(())
+    if !failures.is_empty() {
+        let message = format!(
+            "{}(): {} of {} tasks failed",
+            caller,
+            failures.len(),
+            failures.len() + results.len()
+        );
+        return Err(exception_group(py, &message, failures)?);
     }
+    Ok(results)
+}
 
-    /// Get current progress (0.0 to 1.0)
-    fn get_progress(&self) -> PyResult<f64> {
-        Ok(TASK_PROGRESS_MAP
-            .get(&self.task_id)
-            .map(|p| *p)
-            .unwrap_or(0.0))
+/// Exception instance of a failed task, with its `task_id` attached (and noted on 3.11+)
+fn tag_task_exception(py: Python, err: PyErr, task_id: &str) -> PyResult<Py<PyAny>> {
+    let exc = err.into_value(py).into_bound(py).into_any();
+    exc.setattr("task_id", task_id)?;
+    if exc.hasattr("add_note")? {
+        exc.call_method1("add_note", (format!("task_id: {}", task_id),))?;
     }
+    Ok(exc.unbind())
 }
 
-/// Parallel function wrapper that returns AsyncHandle
+/// Build a PEP 654 exception group, using the `exceptiongroup` backport before Python 3.11
+fn exception_group(py: Python, message: &str, exceptions: Vec<Py<PyAny>>) -> PyResult<PyErr> {
+    let builtins = py.import("builtins")?;
+    let group_type = match builtins.getattr("BaseExceptionGroup") {
+        Ok(group_type) => group_type,
+        Err(_) => py
+            .import("exceptiongroup")
+            .and_then(|module| module.getattr("BaseExceptionGroup"))
+            .map_err(|_| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                "on_error='group' requires Python 3.11+ or the 'exceptiongroup' package"
+            ))?,
+    };
+    // BaseExceptionGroup() returns an ExceptionGroup when every member is an Exception
+    let group = group_type.call1((message, PyList::new(py, exceptions)?))?;
+    Ok(PyErr::from_value(group))
+}
+
+/// Iterator yielding handles in the order their tasks finish
 #[pyclass]
-struct ParallelWrapper {
-    func: Py<PyAny>,
+struct AsCompletedIterator {
+    handles: Vec<Py<PyAny>>,
+    finished: CrossbeamReceiver<usize>,
+    remaining: usize,
+    deadline: Option<Instant>,
 }
 
 #[pymethods]
-impl ParallelWrapper {
-    #[pyo3(signature = (*args, timeout=None, **kwargs))]
-    fn __call__(
-        &self,
-        py: Python,
-        args: &Bound<'_, PyTuple>,
-        timeout: Option<f64>,
-        kwargs: Option<&Bound<'_, PyDict>>,
-    ) -> PyResult<Py<AsyncHandle>> {
-        // Check if shutdown is requested
-        if is_shutdown_requested() {
-            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                "Cannot start new tasks: shutdown in progress"
-            ));
+impl AsCompletedIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<Py<PyAny>>> {
+        if self.remaining == 0 {
+            return Ok(None);
         }
 
-        // Wait for available slot (backpressure)
-        wait_for_slot();
+        let finished = &self.finished;
+        let deadline = self.deadline;
+        let next = py.detach(|| match deadline {
+            Some(deadline) => finished.recv_deadline(deadline).ok(),
+            None => finished.recv().ok(),
+        });
 
-        // Check memory before starting
-        if !check_memory_ok() {
-            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                "Memory limit reached, cannot start new task"
-            ));
+        match next {
+            Some(index) => {
+                self.remaining -= 1;
+                Ok(Some(self.handles[index].clone_ref(py)))
+            }
+            None => Err(pyo3::exceptions::PyTimeoutError::new_err(format!(
+                "{} (of {}) tasks unfinished",
+                self.remaining,
+                self.handles.len()
+            ))),
         }
+    }
+}
 
-        // Clone function reference for the thread
-        let func = self.func.clone_ref(py);
+/// Yield handles as their tasks finish, in completion order
+///
+/// `concurrent.futures.Future`s may be mixed in and are yielded as themselves.
+/// Raises TimeoutError if `timeout` seconds pass before every task has finished.
+#[pyfunction]
+#[pyo3(signature = (handles, timeout=None))]
+fn as_completed(
+    py: Python,
+    handles: Vec<Bound<'_, PyAny>>,
+    timeout: Option<f64>,
+) -> PyResult<AsCompletedIterator> {
+    validate_timeouts(timeout, None)?;
+    let deadline = timeout.map(|secs| Instant::now() + Duration::from_secs_f64(secs));
+
+    let (notify, finished) = unbounded();
+    for (index, obj) in handles.iter().enumerate() {
+        let handle = as_handle(py, obj)?;
+        let notify = notify.clone();
+        handle.borrow(py).completion.subscribe(
+            py,
+            Box::new(move |_, _| {
+                let _ = notify.send(index);
+            }),
+        );
+    }
 
-        // Generate unique task ID
-        let task_id = format!("task_{}", TASK_ID_COUNTER.fetch_add(1, Ordering::Relaxed));
-        let task_id_clone = task_id.clone();
+    Ok(AsCompletedIterator {
+        remaining: handles.len(),
+        handles: handles.into_iter().map(|obj| obj.unbind()).collect(),
+        finished,
+        deadline,
+    })
+}
 
-        // Register task as active
-        register_task(task_id.clone());
+/// `wait()` stops once any handle finishes
+const FIRST_COMPLETED: &str = "FIRST_COMPLETED";
+/// `wait()` stops once any handle fails, or when all have finished
+const FIRST_EXCEPTION: &str = "FIRST_EXCEPTION";
+/// `wait()` stops once every handle has finished
+const ALL_COMPLETED: &str = "ALL_COMPLETED";
+
+/// Wait for handles to finish, returning `(done, not_done)` sets
+///
+/// Mirrors `concurrent.futures.wait`, and accepts its futures too. Cancelled
+/// tasks count as completed but not as exceptions.
+#[pyfunction]
+#[pyo3(signature = (handles, timeout=None, return_when=ALL_COMPLETED))]
+fn wait<'py>(
+    py: Python<'py>,
+    handles: Vec<Bound<'py, PyAny>>,
+    timeout: Option<f64>,
+    return_when: &str,
+) -> PyResult<(Bound<'py, PySet>, Bound<'py, PySet>)> {
+    if ![FIRST_COMPLETED, FIRST_EXCEPTION, ALL_COMPLETED].contains(&return_when) {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "return_when must be FIRST_COMPLETED, FIRST_EXCEPTION or ALL_COMPLETED, got '{}'",
+            return_when
+        )));
+    }
+    validate_timeouts(timeout, None)?;
+    let deadline = timeout.map(|secs| Instant::now() + Duration::from_secs_f64(secs));
+
+    // Each finished handle reports (index, failed)
+    let (notify, finished) = unbounded();
+    for (index, obj) in handles.iter().enumerate() {
+        let handle = as_handle(py, obj)?;
+        let notify = notify.clone();
+        let cancel_token = handle.borrow(py).cancel_token.clone();
+        handle.borrow(py).completion.subscribe(
+            py,
+            Box::new(move |_, outcome| {
+                let failed = outcome.is_err() && !cancel_token.load(Ordering::Acquire);
+                let _ = notify.send((index, failed));
+            }),
+        );
+    }
+    drop(notify);
 
-        // Get function name for profiling
-        let func_name = func
-            .bind(py)
-            .getattr("__name__")
-            .ok()
-            .and_then(|n| n.extract::<String>().ok())
-            .unwrap_or_else(|| "unknown".to_string());
+    let mut done = vec![false; handles.len()];
+    let mut remaining = handles.len();
+    let mut satisfied = |index: usize, failed: bool| {
+        if !std::mem::replace(&mut done[index], true) {
+            remaining -= 1;
+        }
+        match return_when {
+            FIRST_COMPLETED => true,
+            FIRST_EXCEPTION if failed => true,
+            _ => remaining == 0,
+        }
+    };
 
-        // Convert args and kwargs to owned Python objects
-        let args_py: Py<PyTuple> = args.clone().unbind();
-        let kwargs_py: Option<Py<PyDict>> = kwargs.map(|k| k.clone().unbind());
+    let mut stop = handles.is_empty();
+    // Handles that had already finished were reported during subscribe
+    while let Ok((index, failed)) = finished.try_recv() {
+        stop |= satisfied(index, failed);
+    }
+    while !stop {
+        let next = py.detach(|| match deadline {
+            Some(deadline) => finished.recv_deadline(deadline).ok(),
+            None => finished.recv().ok(),
+        });
+        match next {
+            Some((index, failed)) => stop = satisfied(index, failed),
+            None => break,
+        }
+    }
+    // Include anything else that finished meanwhile
+    while let Ok((index, failed)) = finished.try_recv() {
+        satisfied(index, failed);
+    }
 
-        // Create channel for communication
-        let (sender, receiver): (Sender<PyResult<Py<PyAny>>>, Receiver<PyResult<Py<PyAny>>>) =
-            channel();
+    let done_set = PySet::empty(py)?;
+    let not_done_set = PySet::empty(py)?;
+    for (handle, is_done) in handles.iter().zip(done) {
+        if is_done {
+            done_set.add(handle)?;
+        } else {
+            not_done_set.add(handle)?;
+        }
+    }
+    Ok((done_set, not_done_set))
+}
 
-        let is_complete = Arc::new(Mutex::new(false));
-        let is_complete_clone = is_complete.clone();
+/// Bridge a `concurrent.futures.Future` to an AsyncHandle that finishes with it
+///
+/// The handle yields the future's result, or fails with the exception it raised.
+#[pyfunction]
+fn from_future(py: Python, future: &Bound<'_, PyAny>) -> PyResult<AsyncHandle> {
+    if !is_future(py, future)? {
+        return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+            "from_future() expects a concurrent.futures.Future, got {}",
+            future.get_type().name()?
+        )));
+    }
 
-        let cancel_token = Arc::new(AtomicBool::new(false));
-        let cancel_token_clone = cancel_token.clone();
+    let (handle, completer) = AsyncHandle::detached("from_future".to_string(), Vec::new(), None);
+    let cancel_token = handle.cancel_token.clone();
+    // Done callbacks run once, but the closure has to be Fn
+    let completer = Mutex::new(Some(completer));
+    let on_done = PyCFunction::new_closure(
+        py,
+        None,
+        None,
+        move |args: &Bound<'_, PyTuple>, _kwargs: Option<&Bound<'_, PyDict>>| -> PyResult<()> {
+            let py = args.py();
+            let future = args.get_item(0)?;
+            let Some(completer) = completer.lock().take() else {
+                return Ok(());
+            };
+            let outcome = if future.call_method0("cancelled")?.is_truthy()? {
+                cancel_token.store(true, Ordering::Release);
+                Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Task was cancelled"))
+            } else {
+                let exception = future.call_method0("exception")?;
+                if exception.is_none() {
+                    future.call_method0("result").map(|r| r.unbind())
+                } else {
+                    Err(PyErr::from_value(exception))
+                }
+            };
+            completer.complete(py, outcome);
+            Ok(())
+        },
+    )?;
+    // Runs immediately if the future has already finished
+    future.call_method1("add_done_callback", (on_done,))?;
+    Ok(handle)
+}
 
-        let func_name_clone = func_name.clone();
-        let start_time = Instant::now();
+/// Bridge an AsyncHandle to a `concurrent.futures.Future`
+///
+/// The future is resolved from the task's worker thread, or via `executor.submit`
+/// when an executor is given so its done callbacks run on that executor's threads.
+/// Cancelling the future cancels the task.
+#[pyfunction]
+#[pyo3(signature = (handle, executor=None))]
+fn to_future(py: Python, handle: Py<AsyncHandle>, executor: Option<Py<PyAny>>) -> PyResult<Py<PyAny>> {
+    let future = py.import("concurrent.futures")?.getattr("Future")?.call0()?.unbind();
 
-        // Setup timeout if specified
-        if let Some(timeout_secs) = timeout {
-            let cancel_token_timeout = cancel_token.clone();
-            thread::spawn(move || {
-                thread::sleep(Duration::from_secs_f64(timeout_secs));
-                cancel_token_timeout.store(true, Ordering::Release);
-            });
-        }
+    let task = handle.clone_ref(py);
+    let on_done = PyCFunction::new_closure(
+        py,
+        None,
+        None,
+        move |args: &Bound<'_, PyTuple>, _kwargs: Option<&Bound<'_, PyDict>>| -> PyResult<()> {
+            if args.get_item(0)?.call_method0("cancelled")?.is_truthy()? {
+                task.borrow(args.py()).cancel()?;
+            }
+            Ok(())
+        },
+    )?;
+    future.call_method1(py, "add_done_callback", (on_done,))?;
+
+    let handle = handle.borrow(py);
+    // The error reaches whoever consumes the future, so it never counts as unobserved
+    handle.failure_watch.mark_observed();
+    let cancel_token = handle.cancel_token.clone();
+    let target = future.clone_ref(py);
+    handle.completion.subscribe(
+        py,
+        Box::new(move |py, outcome| {
+            let future = target.bind(py);
+            if future.call_method0("done").and_then(|d| d.is_truthy()).unwrap_or(true) {
+                return;
+            }
+            let delivered = if cancel_token.load(Ordering::Acquire) {
+                future.call_method0("cancel").map(|_| ())
+            } else {
+                let (method, value) = match outcome {
+                    Ok(val) => ("set_result", val.clone_ref(py)),
+                    Err(e) => ("set_exception", e.value(py).clone().into_any().unbind()),
+                };
+                match executor {
+                    Some(ref executor) => future
+                        .getattr(method)
+                        .and_then(|setter| executor.call_method1(py, "submit", (setter, value)))
+                        .map(|_| ()),
+                    None => future.call_method1(method, (value,)).map(|_| ()),
+                }
+            };
+            if let Err(e) = delivered {
+                e.write_unraisable(py, Some(future));
+            }
+        }),
+    );
+    Ok(future)
+}
 
-        // Spawn Rust thread - release GIL first, then spawn thread
-        let handle = py.detach(|| {
-            thread::spawn(move || {
-                // Acquire GIL inside the thread to call Python function
-                Python::attach(|py| {
-                    let exec_start = Instant::now();
+fn is_future(py: Python, obj: &Bound<'_, PyAny>) -> PyResult<bool> {
+    obj.is_instance(&py.import("concurrent.futures")?.getattr("Future")?)
+}
 
-                    // Set task_id in thread-local storage for progress reporting
-                    set_current_task_id(Some(task_id_clone.clone()));
+/// Accept an AsyncHandle or a `concurrent.futures.Future` wherever handles are waited on
+fn as_handle(py: Python, obj: &Bound<'_, PyAny>) -> PyResult<Py<AsyncHandle>> {
+    if let Ok(handle) = obj.cast::<AsyncHandle>() {
+        return Ok(handle.clone().unbind());
+    }
+    if is_future(py, obj)? {
+        return Py::new(py, from_future(py, obj)?);
+    }
+    Err(pyo3::exceptions::PyTypeError::new_err(format!(
+        "expected an AsyncHandle or concurrent.futures.Future, got {}",
+        obj.get_type().name()?
+    )))
+}
 
-                    // Check shutdown or cancellation before execution
-                    if is_shutdown_requested() || cancel_token_clone.load(Ordering::Acquire) {
-                        let reason = if is_shutdown_requested() {
-                            "Task cancelled: shutdown requested"
-                        } else {
-                            "Task was cancelled or timed out"
-                        };
+/// Context manager for parallel execution
+#[pyclass]
+struct ParallelContext {
+    handles: Arc<Mutex<Vec<Py<AsyncHandle>>>>,
+    timeout: Option<f64>,
+}
 
-                        let task_error = TaskError {
-                            task_name: func_name_clone.clone(),
-                            elapsed_time: exec_start.elapsed().as_secs_f64(),
-                            error_message: reason.to_string(),
-                            error_type: "CancellationError".to_string(),
-                            task_id: task_id_clone.clone(),
-                        };
+#[pymethods]
+impl ParallelContext {
+    #[new]
+    #[pyo3(signature = (timeout=None))]
+    fn new(timeout: Option<f64>) -> Self {
+        ParallelContext {
+            handles: Arc::new(Mutex::new(Vec::new())),
+            timeout,
+        }
+    }
 
-                        // CRITICAL FIX: Handle channel send errors
-                        if let Err(e) = sender.send(Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                            task_error.__str__()
-                        ))) {
-                            error!("Failed to send cancellation error for task {}: {}", task_id_clone, e);
-                            store_task_error(task_id_clone.clone(), format!("Cancellation failed: {}", e));
-                        }
-                        *is_complete_clone.lock() = true;
-                        unregister_task(&task_id_clone);
-                        clear_task_progress(&task_id_clone);
-                        set_current_task_id(None);
-                        return;
-                    }
+    /// Submit a task
+    fn submit(&self, py: Python, func: Py<PyAny>, args: &Bound<'_, PyTuple>) -> PyResult<Py<AsyncHandle>> {
+        // Call the function with timeout if specified
+        let handle = if let Some(timeout) = self.timeout {
+            func.bind(py).call_method1("__call__", (args, ("timeout", timeout)))?
+        } else {
+            func.bind(py).call(args, None)?
+        };
 
-                    let result = func
-                        .bind(py)
-                        .call(args_py.bind(py), kwargs_py.as_ref().map(|k| k.bind(py)));
+        let async_handle: Py<AsyncHandle> = handle.extract()?;
+        self.handles.lock().push(async_handle.clone_ref(py));
+        Ok(async_handle)
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &self,
+        py: Python,
+        _exc_type: &Bound<'_, PyAny>,
+        _exc_val: &Bound<'_, PyAny>,
+        _exc_tb: &Bound<'_, PyAny>,
+    ) -> PyResult<bool> {
+        // Wait for all tasks
+        let handles_guard = self.handles.lock();
+        for handle in handles_guard.iter() {
+            let _ = handle.bind(py).call_method0("get");
+        }
+        Ok(false)
+    }
+}
 
-                    let exec_time = exec_start.elapsed().as_secs_f64() * 1000.0; // Convert to ms
+/// Tasks of a TaskGroup, shared with the completion listeners that cancel siblings
+#[derive(Default)]
+struct TaskGroupState {
+    handles: Mutex<Vec<Py<AsyncHandle>>>,
+    failed: AtomicBool,
+}
 
-                    let to_send = match result {
-                        Ok(val) => {
-                            record_task_execution(&func_name_clone, exec_time, true);
-                            Ok(val.unbind())
-                        }
-                        Err(e) => {
-                            record_task_execution(&func_name_clone, exec_time, false);
+impl TaskGroupState {
+    /// Cancel the tasks that have not finished yet
+    fn cancel_all(&self, py: Python) {
+        for handle in self.handles.lock().iter() {
+            let handle = handle.borrow(py);
+            if !*handle.is_complete.lock() {
+                let _ = handle.cancel();
+            }
+        }
+    }
+}
 
-                            // Create enhanced error with context
-                            let error_type = e.get_type(py).name()
-                                .map(|n| n.to_string())
-                                .unwrap_or_else(|_| "UnknownError".to_string());
+/// Structured concurrency scope: tasks started in the `with` block are joined on exit
+///
+/// The first task failure cancels every other task in the group, as does an
+/// exception escaping the block. Task failures are raised on exit as an
+/// ExceptionGroup; cancelled siblings are not included.
+#[pyclass]
+struct TaskGroup {
+    state: Arc<TaskGroupState>,
+}
 
-                            let task_error = TaskError {
-                                task_name: func_name_clone.clone(),
-                                elapsed_time: exec_start.elapsed().as_secs_f64(),
-                                error_message: e.to_string(),
-                                error_type,
-                                task_id: task_id_clone.clone(),
-                            };
+#[pymethods]
+impl TaskGroup {
+    #[new]
+    fn new() -> Self {
+        TaskGroup { state: Arc::new(TaskGroupState::default()) }
+    }
 
-                            Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                                task_error.__str__()
-                            ))
-                        }
-                    };
+    /// Start `func(*args, **kwargs)` in the group
+    ///
+    /// `@parallel`, `@parallel_priority`, `@parallel_with_deps` and `@parallel_process`
+    /// functions are called as-is; plain callables run as `@parallel` tasks.
+    #[pyo3(signature = (func, *args, **kwargs))]
+    fn submit(
+        &self,
+        py: Python,
+        func: &Bound<'_, PyAny>,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<AsyncHandle>> {
+        if self.state.failed.load(Ordering::Acquire) {
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                "TaskGroup is cancelling after a task failure, cannot start new tasks"
+            ));
+        }
 
-                    // CRITICAL FIX: Handle channel send errors
-                    if let Err(e) = sender.send(to_send) {
-                        error!("Failed to send task result for task {}: {}", task_id_clone, e);
-                        store_task_error(task_id_clone.clone(), format!("Channel send failed: {}", e));
-                    }
-                    *is_complete_clone.lock() = true;
+        let submitted = if func.is_instance_of::<ParallelWrapper>()
+            || func.is_instance_of::<PriorityParallelWrapper>()
+            || func.is_instance_of::<ParallelWithDeps>()
+            || func.is_instance_of::<ParallelProcessWrapper>()
+        {
+            func.call(args, kwargs)?
+        } else {
+            let wrapper = ParallelWrapper::new(py, func.clone().unbind(), Isolation::Thread, None, None, Vec::new(), None);
+            Bound::new(py, wrapper)?.call(args, kwargs)?
+        };
+        let handle = as_handle(py, &submitted)?;
+        self.state.handles.lock().push(handle.clone_ref(py));
 
-                    // Cleanup: unregister task and clear progress
-                    unregister_task(&task_id_clone);
-                    clear_task_progress(&task_id_clone);
-                    set_current_task_id(None);
-                });
-            })
-        });
+        let state = self.state.clone();
+        let cancel_token = handle.borrow(py).cancel_token.clone();
+        handle.borrow(py).completion.subscribe(
+            py,
+            Box::new(move |py, outcome| {
+                // Tasks cancelled by the group (or by hand) do not cancel their siblings
+                if outcome.is_err()
+                    && !cancel_token.load(Ordering::Acquire)
+                    && !state.failed.swap(true, Ordering::AcqRel)
+                {
+                    state.cancel_all(py);
+                }
+            }),
+        );
+        Ok(handle)
+    }
 
-        // Create AsyncHandle
-        let async_handle = AsyncHandle {
-            receiver: Arc::new(Mutex::new(receiver)),
-            thread_handle: Arc::new(Mutex::new(Some(handle))),
-            is_complete,
-            result_cache: Arc::new(Mutex::new(None)),
-            cancel_token,
-            func_name,
-            start_time,
-            task_id,
-            metadata: Arc::new(Mutex::new(HashMap::new())),
-            timeout,
-            on_complete: Arc::new(Mutex::new(None)),
-            on_error: Arc::new(Mutex::new(None)),
-            on_progress: Arc::new(Mutex::new(None)),
-        };
+    /// Cancel every task in the group
+    fn cancel(&self, py: Python) {
+        self.state.cancel_all(py);
+    }
 
-        Py::new(py, async_handle)
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
     }
 
-    fn __get__(
-        slf: PyRef<'_, Self>,
-        obj: &Bound<'_, PyAny>,
-        _objtype: Option<&Bound<'_, PyAny>>,
-    ) -> PyResult<Py<PyAny>> {
-        let py = slf.py();
+    fn __exit__(
+        &self,
+        py: Python,
+        exc_type: &Bound<'_, PyAny>,
+        _exc_val: &Bound<'_, PyAny>,
+        _exc_tb: &Bound<'_, PyAny>,
+    ) -> PyResult<bool> {
+        let body_failed = !exc_type.is_none();
+        if body_failed {
+            self.state.cancel_all(py);
+        }
 
-        if obj.is_none() {
-            // Unbound method access - return self
-            return Ok(slf.into_bound_py_any(py)?.unbind());
+        // Join every task, collecting failures that were not caused by cancellation
+        let handles: Vec<Py<AsyncHandle>> =
+            self.state.handles.lock().iter().map(|h| h.clone_ref(py)).collect();
+        let mut failures = Vec::new();
+        for handle in handles {
+            let handle = handle.borrow(py);
+            if let Err(e) = handle.get(py) {
+                if !handle.cancel_token.load(Ordering::Acquire) {
+                    failures.push(tag_task_exception(py, e, &handle.task_id)?);
+                }
+            }
         }
 
-        // Bound method access - create a new ParallelWrapper with bound function
-        let functools = py.import("functools")?;
-        let partial = functools.getattr("partial")?;
-        let bound_func = partial.call1((slf.func.bind(py), obj))?.unbind();
+        // An exception from the block itself takes precedence
+        if !body_failed && !failures.is_empty() {
+            let message = format!("TaskGroup: {} tasks failed", failures.len());
+            return Err(exception_group(py, &message, failures)?);
+        }
+        Ok(false)
+    }
+}
 
-        Py::new(py, ParallelWrapper { func: bound_func }).map(|p| p.into())
+/// One step of a Saga: `action` runs forward, `compensation` undoes it given the action's result
+struct SagaStep {
+    action: Py<PyAny>,
+    compensation: Option<Py<PyAny>>,
+    depends_on: Vec<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SagaStatus {
+    Pending,
+    Running,
+    Completed,
+    Compensated,
+    CompensationFailed,
+}
+
+impl SagaStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            SagaStatus::Pending => "pending",
+            SagaStatus::Running => "running",
+            SagaStatus::Completed => "completed",
+            SagaStatus::Compensated => "compensated",
+            SagaStatus::CompensationFailed => "compensation_failed",
+        }
     }
 }
 
-/// Decorator to run functions in parallel Rust threads without GIL
-#[pyfunction]
-fn parallel(py: Python, func: Py<PyAny>) -> PyResult<Py<ParallelWrapper>> {
-    Py::new(py, ParallelWrapper { func })
+static SAGA_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Start `func(*args)` as a `@parallel` task tagged with the saga's ID
+fn submit_saga_task(py: Python, saga_id: &str, func: &Py<PyAny>, args: &Bound<'_, PyTuple>) -> PyResult<Py<AsyncHandle>> {
+    let wrapper = ParallelWrapper::new(py, func.clone_ref(py), Isolation::Thread, None, None, Vec::new(), None);
+    wrapper
+        .call_with(py, args, None, None, Some(vec![saga_id.to_string()]), false, false, None)?
+        .extract(py)
+        .map_err(Into::into)
 }
 
-// =============================================================================
-// OPTIMIZED IMPLEMENTATIONS
-// =============================================================================
+/// Wait for the handles of one wave, returning each task's outcome by step index
+fn join_saga_wave(py: Python, handles: Vec<(usize, Py<AsyncHandle>)>) -> PyResult<Vec<(usize, PyResult<Py<PyAny>>)>> {
+    let mut outcomes = Vec::with_capacity(handles.len());
+    for (index, handle) in handles {
+        let handle = handle.borrow(py);
+        let outcome = match handle.get(py) {
+            Ok(result) => Ok(result),
+            Err(e) => Err(PyErr::from_value(tag_task_exception(py, e, &handle.task_id)?.into_bound(py))),
+        };
+        outcomes.push((index, outcome));
+    }
+    Ok(outcomes)
+}
 
-/// Optimized AsyncHandle using crossbeam channels (lock-free, better performance)
+/// Transaction of steps with compensating actions
+///
+/// `run()` executes the steps as `@parallel` tasks, each once its dependencies have
+/// completed. If a step fails, no further steps start, and the completed steps are
+/// undone in reverse dependency order; independent compensations run in parallel.
 #[pyclass]
-struct AsyncHandleFast {
-    receiver: Arc<Mutex<CrossbeamReceiver<PyResult<Py<PyAny>>>>>,
-    is_complete: Arc<Mutex<bool>>,
-    result_cache: Arc<Mutex<Option<PyResult<Py<PyAny>>>>>,
+struct Saga {
+    #[pyo3(get)]
+    saga_id: String,
+    #[pyo3(get)]
+    name: String,
+    steps: Mutex<Vec<SagaStep>>,
+    status: Mutex<SagaStatus>,
 }
 
 #[pymethods]
-impl AsyncHandleFast {
-    fn is_ready(&self) -> PyResult<bool> {
-        Ok(*self.is_complete.lock())
+impl Saga {
+    #[new]
+    #[pyo3(signature = (name=None))]
+    fn new(name: Option<String>) -> Self {
+        let saga_id = format!("saga_{}", SAGA_COUNTER.fetch_add(1, Ordering::Relaxed));
+        Saga {
+            name: name.unwrap_or_else(|| saga_id.clone()),
+            saga_id,
+            steps: Mutex::new(Vec::new()),
+            status: Mutex::new(SagaStatus::Pending),
+        }
     }
 
-    fn try_get(&self, py: Python) -> PyResult<Option<Py<PyAny>>> {
-        let mut cache = self.result_cache.lock();
-        if let Some(ref cached) = *cache {
-            return match cached {
-                Ok(val) => Ok(Some(val.clone_ref(py))),
-                Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                    "Cached error: {}",
-                    e
-                ))),
-            };
+    /// Add a step and return its index
+    ///
+    /// `undo` is called with the result of `do` when a later step fails.
+    /// `depends_on` lists the indices of steps that must complete first; by
+    /// default a step depends on the one added before it.
+    #[pyo3(signature = (r#do, undo=None, depends_on=None))]
+    fn step(
+        &self,
+        r#do: &Bound<'_, PyAny>,
+        undo: Option<&Bound<'_, PyAny>>,
+        depends_on: Option<Vec<usize>>,
+    ) -> PyResult<usize> {
+        if *self.status.lock() != SagaStatus::Pending {
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                "Saga has already run, cannot add steps"
+            ));
+        }
+        if !r#do.is_callable() || undo.is_some_and(|u| !u.is_callable()) {
+            return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                "Saga step 'do' and 'undo' must be callable"
+            ));
         }
 
-        let receiver = self.receiver.lock();
-        match receiver.try_recv() {
-            Ok(result) => {
-                *self.is_complete.lock() = true;
-                match result {
-                    Ok(val) => {
-                        *cache = Some(Ok(val.clone_ref(py)));
-                        Ok(Some(val))
-                    }
-                    Err(e) => {
-                        *cache = Some(Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                            e.to_string(),
-                        )));
-                        Err(e)
-                    }
-                }
-            }
-            Err(_) => Ok(None),
+        let mut steps = self.steps.lock();
+        let index = steps.len();
+        let depends_on = depends_on.unwrap_or_else(|| index.checked_sub(1).into_iter().collect());
+        if let Some(&unknown) = depends_on.iter().find(|&&d| d >= index) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Saga step {} can only depend on earlier steps, got {}",
+                index, unknown
+            )));
         }
+        steps.push(SagaStep {
+            action: r#do.clone().unbind(),
+            compensation: undo.map(|u| u.clone().unbind()),
+            depends_on,
+        });
+        Ok(index)
     }
 
-    fn get(&self, py: Python) -> PyResult<Py<PyAny>> {
-        let cache = self.result_cache.lock();
-        if let Some(ref cached) = *cache {
-            return match cached {
-                Ok(val) => Ok(val.clone_ref(py)),
-                Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                    "Cached error: {}",
-                    e
-                ))),
-            };
+    /// Run the saga and return the step results, in step order
+    ///
+    /// After compensating, the failing step's exception is raised, or an exception
+    /// group when several steps or any compensations failed.
+    fn run(&self, py: Python) -> PyResult<Vec<Py<PyAny>>> {
+        {
+            let mut status = self.status.lock();
+            if *status != SagaStatus::Pending {
+                return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Saga has already run"));
+            }
+            *status = SagaStatus::Running;
+        }
+        let steps = std::mem::take(&mut *self.steps.lock());
+        let count = steps.len();
+        publish_task_event("saga_started", &self.saga_id, &self.name, serde_json::json!({ "steps": count }));
+
+        // Forward: start every step whose dependencies completed, wave by wave
+        let no_args = PyTuple::empty(py);
+        let mut results: Vec<Option<Py<PyAny>>> = (0..count).map(|_| None).collect();
+        let mut started = vec![false; count];
+        let mut failures: Vec<(usize, PyErr)> = Vec::new();
+        while failures.is_empty() {
+            let wave: Vec<usize> = (0..count)
+                .filter(|&i| !started[i] && steps[i].depends_on.iter().all(|&d| results[d].is_some()))
+                .collect();
+            if wave.is_empty() {
+                break;
+            }
+            let mut handles = Vec::with_capacity(wave.len());
+            for index in wave {
+                started[index] = true;
+                match submit_saga_task(py, &self.saga_id, &steps[index].action, &no_args) {
+                    Ok(handle) => handles.push((index, handle)),
+                    Err(e) => failures.push((index, e)),
+                }
+            }
+            for (index, outcome) in join_saga_wave(py, handles)? {
+                match outcome {
+                    Ok(result) => results[index] = Some(result),
+                    Err(e) => failures.push((index, e)),
+                }
+            }
         }
-        drop(cache);
 
-        // Release GIL before blocking
-        let result = py
-            .detach(|| {
-                let receiver = self.receiver.lock();
-                receiver.recv()
-            })
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        if failures.is_empty() {
+            *self.status.lock() = SagaStatus::Completed;
+            publish_task_event("saga_completed", &self.saga_id, &self.name, serde_json::json!({ "steps": count }));
+            return Ok(results.into_iter().flatten().collect());
+        }
 
-        *self.is_complete.lock() = true;
+        let failed_steps: Vec<usize> = failures.iter().map(|(index, _)| *index).collect();
+        publish_task_event(
+            "saga_compensating",
+            &self.saga_id,
+            &self.name,
+            serde_json::json!({ "failed_steps": failed_steps }),
+        );
 
-        let mut cache = self.result_cache.lock();
-        match result {
-            Ok(ref val) => {
-                *cache = Some(Ok(val.clone_ref(py)));
-                Ok(val.clone_ref(py))
+        // Backward: undo a completed step once every completed step depending on it is undone
+        let mut undone: Vec<bool> = results.iter().map(Option::is_none).collect();
+        let mut compensation_failures = Vec::new();
+        loop {
+            let wave: Vec<usize> = (0..count)
+                .filter(|&i| !undone[i] && (0..count).all(|j| undone[j] || !steps[j].depends_on.contains(&i)))
+                .collect();
+            if wave.is_empty() {
+                break;
             }
-            Err(e) => {
-                let err_str = e.to_string();
-                *cache = Some(Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                    err_str.clone(),
-                )));
-                Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(err_str))
+            let mut handles = Vec::with_capacity(wave.len());
+            for index in wave {
+                undone[index] = true;
+                let (Some(compensation), Some(result)) = (&steps[index].compensation, &results[index]) else {
+                    continue;
+                };
+                let args = PyTuple::new(py, [result.clone_ref(py)])?;
+                match submit_saga_task(py, &self.saga_id, compensation, &args) {
+                    Ok(handle) => handles.push((index, handle)),
+                    Err(e) => compensation_failures.push(e),
+                }
             }
+            for (_, outcome) in join_saga_wave(py, handles)? {
+                if let Err(e) = outcome {
+                    compensation_failures.push(e);
+                }
+            }
+        }
+
+        let status = if compensation_failures.is_empty() {
+            SagaStatus::Compensated
+        } else {
+            SagaStatus::CompensationFailed
+        };
+        *self.status.lock() = status;
+        publish_task_event(
+            "saga_compensated",
+            &self.saga_id,
+            &self.name,
+            serde_json::json!({
+                "failed_steps": failed_steps,
+                "compensation_failures": compensation_failures.len(),
+            }),
+        );
+
+        if failures.len() == 1 && compensation_failures.is_empty() {
+            return Err(failures.pop().map(|(_, e)| e).expect("one failure"));
         }
+        let message = format!(
+            "Saga '{}': {} steps failed, {} compensations failed",
+            self.name,
+            failures.len(),
+            compensation_failures.len()
+        );
+        let exceptions = failures
+            .into_iter()
+            .map(|(_, e)| e)
+            .chain(compensation_failures)
+            .map(|e| e.into_value(py).into_any())
+            .collect();
+        Err(exception_group(py, &message, exceptions)?)
+    }
+
+    /// "pending", "running", "completed", "compensated" or "compensation_failed"
+    #[getter]
+    fn status(&self) -> &'static str {
+        self.status.lock().as_str()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Saga(name='{}', status='{}')", self.name, self.status())
     }
 }
 
-// =============================================================================
-// TASK DEPENDENCY SYSTEM
-// =============================================================================
+/// Item flowing between pipeline stages; a stage failure travels on to the consumer
+type PipelineItem = PyResult<Py<PyAny>>;
 
-/// Wait for dependencies to complete
-fn wait_for_dependencies(dependencies: &[String]) -> PyResult<Vec<Py<PyAny>>> {
-    let mut results = Vec::new();
+struct PipelineStage {
+    func: Py<PyAny>,
+    workers: usize,
+    maxsize: Option<usize>,
+}
 
-    for dep_id in dependencies {
-        // Wait for dependency result to be available
-        let mut attempts = 0;
-        let max_attempts = 6000; // 10 minutes max wait
+enum PipelineState {
+    Building(Vec<PipelineStage>),
+    Running {
+        /// Dropped by close(); stages wind down once their input disconnects
+        input: Option<CrossbeamSender<PipelineItem>>,
+        output: CrossbeamReceiver<PipelineItem>,
+        /// Stage functions put() applies itself in the synchronous fallback
+        inline: Vec<Py<PyAny>>,
+    },
+}
 
-        loop {
-            // CRITICAL FIX: Check shutdown flag
-            if is_shutdown_requested() {
-                warn!("Dependency wait cancelled: shutdown in progress");
-                return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                    "Dependency wait cancelled: shutdown in progress"
-                ));
+/// Apply a stage function to every item of `input` until it disconnects
+fn run_pipeline_worker(func: Py<PyAny>, input: CrossbeamReceiver<PipelineItem>, output: CrossbeamSender<PipelineItem>) {
+    for item in input {
+        let result = item.and_then(|item| Python::attach(|py| func.bind(py).call1((item,)).map(|r| r.unbind())));
+        if output.send(result).is_err() {
+            // Nobody is consuming the results any more
+            break;
+        }
+    }
+}
+
+/// Multi-stage pipeline whose stages are connected by bounded queues
+///
+/// Each stage runs `workers` threads; a full queue blocks the stage feeding it, so a
+/// slow stage throttles everything upstream. Results come out in completion order.
+/// A stage failure is raised when its item is reached during iteration.
+#[pyclass]
+struct Pipeline {
+    maxsize: usize,
+    state: Mutex<PipelineState>,
+}
+
+impl Pipeline {
+    /// Connect the stages and start their workers, once
+    fn start(&self, py: Python) -> PyResult<()> {
+        let mut state = self.state.lock();
+        let PipelineState::Building(stages) = &mut *state else {
+            return Ok(());
+        };
+        if stages.is_empty() {
+            return Err(pyo3::exceptions::PyValueError::new_err("Pipeline has no stages"));
+        }
+        let stages = std::mem::take(stages);
+
+        *state = if sync_fallback() {
+            let (input, output) = unbounded();
+            PipelineState::Running { input: Some(input), output, inline: stages.into_iter().map(|s| s.func).collect() }
+        } else {
+            let (input, mut receiver) = crossbeam::channel::bounded(stages[0].maxsize.unwrap_or(self.maxsize));
+            for (index, stage) in stages.iter().enumerate() {
+                let capacity = stages.get(index + 1).and_then(|next| next.maxsize).unwrap_or(self.maxsize);
+                let (sender, next_receiver) = crossbeam::channel::bounded(capacity);
+                for _ in 0..stage.workers {
+                    let func = stage.func.clone_ref(py);
+                    let (input, output) = (receiver.clone(), sender.clone());
+                    thread::spawn(move || run_pipeline_worker(func, input, output));
+                }
+                receiver = next_receiver;
+            }
+            PipelineState::Running { input: Some(input), output: receiver, inline: Vec::new() }
+        };
+        Ok(())
+    }
+
+    /// Sender for the first stage, and the functions to apply before sending
+    fn input(&self, py: Python) -> PyResult<(CrossbeamSender<PipelineItem>, Vec<Py<PyAny>>)> {
+        self.start(py)?;
+        match &*self.state.lock() {
+            PipelineState::Running { input: Some(input), inline, .. } => {
+                Ok((input.clone(), inline.iter().map(|f| f.clone_ref(py)).collect()))
             }
+            _ => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Pipeline input is closed")),
+        }
+    }
+}
+
+#[pymethods]
+impl Pipeline {
+    #[new]
+    #[pyo3(signature = (maxsize=64))]
+    fn new(maxsize: usize) -> PyResult<Self> {
+        if maxsize == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("maxsize must be at least 1"));
+        }
+        Ok(Pipeline { maxsize, state: Mutex::new(PipelineState::Building(Vec::new())) })
+    }
 
-            // CRITICAL FIX: Check for task failures via error storage
-            if let Some(error) = TASK_ERRORS.get(dep_id) {
-                error!("Dependency {} failed: {}", dep_id, error.value());
+    /// Append a stage running `func(item)` on `workers` threads; returns the pipeline
+    ///
+    /// `maxsize` bounds the queue feeding this stage (default: the pipeline's maxsize).
+    #[pyo3(signature = (func, workers=1, maxsize=None))]
+    fn stage(
+        slf: PyRef<'_, Self>,
+        func: Py<PyAny>,
+        workers: usize,
+        maxsize: Option<usize>,
+    ) -> PyResult<PyRef<'_, Self>> {
+        if workers == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("workers must be at least 1"));
+        }
+        if maxsize == Some(0) {
+            return Err(pyo3::exceptions::PyValueError::new_err("maxsize must be at least 1"));
+        }
+        match &mut *slf.state.lock() {
+            PipelineState::Building(stages) => stages.push(PipelineStage { func, workers, maxsize }),
+            PipelineState::Running { .. } => {
                 return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                    format!("Dependency {} failed: {}", dep_id, error.value())
+                    "Pipeline is already running, cannot add stages"
                 ));
             }
+        }
+        Ok(slf)
+    }
 
-            if let Some(result) = TASK_RESULTS.get(dep_id) {
-                Python::attach(|py| {
-                    results.push(result.clone_ref(py));
-                });
-                break;
-            }
-
-            if attempts >= max_attempts {
-                error!("Dependency {} timed out after 10 minutes", dep_id);
-                return Err(PyErr::new::<pyo3::exceptions::PyTimeoutError, _>(
-                    format!("Dependency {} timed out after 10 minutes", dep_id)
-                ));
+    /// Feed one item, blocking while the first stage's queue is full
+    ///
+    /// The wait still answers Ctrl-C and the calling task's cancellation.
+    fn put(&self, py: Python, item: Py<PyAny>) -> PyResult<()> {
+        let (input, inline) = self.input(py)?;
+        let mut pending = Some(
+            inline
+                .iter()
+                .try_fold(item, |item, func| func.bind(py).call1((item,)).map(|r| r.unbind())),
+        );
+        let sent = wait_detached(py, None, |wait| match input.send_timeout(pending.take()?, wait) {
+            Ok(()) => Some(true),
+            Err(crossbeam::channel::SendTimeoutError::Timeout(item)) => {
+                pending = Some(item);
+                None
             }
+            Err(crossbeam::channel::SendTimeoutError::Disconnected(_)) => Some(false),
+        })?;
+        if sent != Some(true) {
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Pipeline input is closed"));
+        }
+        Ok(())
+    }
 
-            thread::sleep(Duration::from_millis(100));
-            attempts += 1;
+    /// Signal that no more items will be fed; iteration ends once they are processed
+    fn close(&self, py: Python) -> PyResult<()> {
+        self.start(py)?;
+        if let PipelineState::Running { input, .. } = &mut *self.state.lock() {
+            input.take();
         }
+        Ok(())
     }
 
-    Ok(results)
-}
+    /// Feed every item of `iterable` from a background thread, then close; returns the pipeline
+    fn run<'py>(slf: PyRef<'py, Self>, iterable: &Bound<'py, PyAny>) -> PyResult<PyRef<'py, Self>> {
+        let py = slf.py();
+        let items = iterable.try_iter()?.unbind();
+        let (input, inline) = slf.input(py)?;
+        slf.close(py)?;
+
+        let feed = move || loop {
+            let item = Python::attach(|py| {
+                let next = items.bind(py).clone().next()?;
+                Some(next.and_then(|item| {
+                    inline.iter().try_fold(item.unbind(), |item, func| func.bind(py).call1((item,)).map(|r| r.unbind()))
+                }))
+            });
+            // An error from the iterable itself ends the input after being reported
+            let Some(item) = item else { break };
+            let failed = item.is_err();
+            if input.send(item).is_err() || failed {
+                break;
+            }
+        };
+        py.detach(|| spawn_task_thread(feed));
+        Ok(slf)
+    }
 
-/// Store task result for dependencies
-fn store_task_result(task_id: String, result: Py<PyAny>) {
-    TASK_RESULTS.insert(task_id, result);
-}
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
 
-/// Clear task result after consumption
-fn clear_task_result(task_id: &str) {
-    TASK_RESULTS.remove(task_id);
+    fn __next__(&self, py: Python) -> PyResult<Option<Py<PyAny>>> {
+        self.start(py)?;
+        let output = match &*self.state.lock() {
+            PipelineState::Running { output, .. } => output.clone(),
+            PipelineState::Building(_) => unreachable!("started above"),
+        };
+        let received = wait_detached(py, None, |wait| match output.recv_timeout(wait) {
+            Ok(result) => Some(Some(result)),
+            Err(crossbeam::channel::RecvTimeoutError::Timeout) => None,
+            Err(crossbeam::channel::RecvTimeoutError::Disconnected) => Some(None),
+        })?;
+        received.flatten().transpose()
+    }
 }
 
-/// Store task error for dependency failure propagation
-fn store_task_error(task_id: String, error: String) {
-    TASK_ERRORS.insert(task_id, error);
+/// Method call waiting in an actor's mailbox
+struct ActorMessage {
+    method: String,
+    args: Py<PyTuple>,
+    kwargs: Option<Py<PyDict>>,
+    task: ActiveTask,
+    completer: TaskCompleter,
 }
 
-/// Clear task error
-fn clear_task_error(task_id: &str) {
-    TASK_ERRORS.remove(task_id);
+/// Run one mailbox message against the actor's instance
+fn run_actor_message(py: Python, instance: &Py<PyAny>, message: ActorMessage) {
+    let ActorMessage { method, args, kwargs, task, completer } = message;
+    if task.cancel_token.load(Ordering::Acquire) {
+        unregister_task(&task.task_id);
+        completer.complete(py, Err(TaskCancelled::new_err("Task was cancelled")));
+        return;
+    }
+
+    let exec_start = Instant::now();
+    set_current_task_id(Some(task.task_id.clone()));
+    let interruptible = TaskThreadGuard::register(py, &task.task_id);
+    let cpu_clock = CpuClock::start();
+    let result = instance
+        .bind(py)
+        .getattr(method.as_str())
+        .and_then(|method| method.call(args.bind(py), kwargs.as_ref().map(|k| k.bind(py))));
+    let cpu_ms = cpu_clock.elapsed_ms();
+    completer.completion.state.lock().cpu_time = cpu_ms.map(|ms| ms / 1000.0);
+    drop(interruptible);
+    set_current_task_id(None);
+    let exec_time = exec_start.elapsed().as_secs_f64() * 1000.0;
+
+    match &result {
+        Ok(value) => {
+            record_task_execution(&task.func_name, exec_time, true, cpu_ms);
+            publish_task_event(
+                "task_completed",
+                &task.task_id,
+                &task.func_name,
+                completed_event_details(py, value, exec_time / 1000.0),
+            );
+        }
+        Err(e) => {
+            record_task_execution(&task.func_name, exec_time, false, cpu_ms);
+            report_task_failure(
+                py,
+                &completer.failure_watch,
+                &TaskError {
+                    task_name: task.func_name.clone(),
+                    elapsed_time: exec_time / 1000.0,
+                    error_message: e.to_string(),
+                    error_type: task_error_type(py, e),
+                    task_id: task.task_id.clone(),
+                    submitted_from: task_submission_site(&task.task_id),
+                },
+                None,
+            );
+        }
+    }
+    unregister_task(&task.task_id);
+    completer.complete(py, result.map(Bound::unbind));
 }
 
-/// Parallel wrapper with dependency support
-#[pyclass]
-struct ParallelWithDeps {
-    func: Py<PyAny>,
+/// Mailbox of one actor, shared by its ActorRef and method proxies
+struct ActorMailbox {
+    class_name: String,
+    /// Dropped by stop(); the actor thread exits once the queued calls are done
+    sender: Mutex<Option<CrossbeamSender<ActorMessage>>>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+    /// The instance itself in the synchronous fallback, where calls run inline
+    inline: Mutex<Option<Py<PyAny>>>,
 }
 
-#[pymethods]
-impl ParallelWithDeps {
-    #[pyo3(signature = (*args, depends_on=None, timeout=None, **kwargs))]
-    fn __call__(
+impl ActorMailbox {
+    /// Queue a call of `method` and return its handle
+    fn send(
         &self,
         py: Python,
+        method: &str,
         args: &Bound<'_, PyTuple>,
-        depends_on: Option<Vec<Py<AsyncHandle>>>,
-        timeout: Option<f64>,
         kwargs: Option<&Bound<'_, PyDict>>,
     ) -> PyResult<Py<AsyncHandle>> {
-        // Extract dependency task IDs
-        let dep_ids: Vec<String> = if let Some(deps) = depends_on {
-            deps.iter()
-                .map(|h| h.borrow(py).get_task_id())
-                .collect::<PyResult<Vec<String>>>()?
-        } else {
-            Vec::new()
-        };
-
-        // Check if shutdown is requested
         if is_shutdown_requested() {
             return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
                 "Cannot start new tasks: shutdown in progress"
             ));
         }
+        let (handle, completer) =
+            AsyncHandle::detached(format!("{}.{}", self.class_name, method), Vec::new(), None);
+        let task = handle.active_task();
+        let message = ActorMessage {
+            method: method.to_string(),
+            args: args.clone().unbind(),
+            kwargs: kwargs.map(|k| k.clone().unbind()),
+            task: task.clone(),
+            completer,
+        };
 
-        wait_for_slot();
-
-        if !check_memory_ok() {
-            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                "Memory limit reached, cannot start new task"
-            ));
-        }
-
-        let func = self.func.clone_ref(py);
-        let task_id = format!("task_{}", TASK_ID_COUNTER.fetch_add(1, Ordering::Relaxed));
-        let task_id_clone = task_id.clone();
-
-        // Register dependencies
-        if !dep_ids.is_empty() {
-            TASK_DEPENDENCIES.insert(task_id.clone(), dep_ids.clone());
-        }
-
-        register_task(task_id.clone());
-
-        let func_name = func
-            .bind(py)
-            .getattr("__name__")
-            .ok()
-            .and_then(|n| n.extract::<String>().ok())
-            .unwrap_or_else(|| "unknown".to_string());
-
-        let args_py: Py<PyTuple> = args.clone().unbind();
-        let kwargs_py: Option<Py<PyDict>> = kwargs.map(|k| k.clone().unbind());
-
-        let (sender, receiver): (Sender<PyResult<Py<PyAny>>>, Receiver<PyResult<Py<PyAny>>>) =
-            channel();
-
-        let is_complete = Arc::new(Mutex::new(false));
-        let is_complete_clone = is_complete.clone();
-
-        let cancel_token = Arc::new(AtomicBool::new(false));
-        let cancel_token_clone = cancel_token.clone();
-
-        let func_name_clone = func_name.clone();
-        let start_time = Instant::now();
-
-        if let Some(timeout_secs) = timeout {
-            let cancel_token_timeout = cancel_token.clone();
-            thread::spawn(move || {
-                thread::sleep(Duration::from_secs_f64(timeout_secs));
-                cancel_token_timeout.store(true, Ordering::Release);
-            });
+        register_task(task.clone());
+        let inline = self.inline.lock().as_ref().map(|instance| instance.clone_ref(py));
+        if let Some(instance) = inline {
+            run_actor_message(py, &instance, message);
+        } else if let Some(sender) = self.sender.lock().as_ref() {
+            let _ = sender.send(message);
+        } else {
+            unregister_task(&task.task_id);
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "{} actor has been stopped",
+                self.class_name
+            )));
         }
+        Py::new(py, handle)
+    }
+}
 
-        let handle = py.detach(|| {
-            thread::spawn(move || {
-                Python::attach(|py| {
-                    let exec_start = Instant::now();
-                    set_current_task_id(Some(task_id_clone.clone()));
-
-                    // Wait for dependencies first
-                    let dep_results = if !dep_ids.is_empty() {
-                        match wait_for_dependencies(&dep_ids) {
-                            Ok(results) => results,
-                            Err(e) => {
-                                // CRITICAL FIX: Handle channel send errors
-                                if let Err(send_err) = sender.send(Err(e)) {
-                                    error!("Failed to send dependency error for task {}: {}", task_id_clone, send_err);
-                                    store_task_error(task_id_clone.clone(), format!("Dependency wait failed: {}", send_err));
-                                }
-                                *is_complete_clone.lock() = true;
-                                unregister_task(&task_id_clone);
-                                clear_task_progress(&task_id_clone);
-                                set_current_task_id(None);
-                                return;
-                            }
-                        }
-                    } else {
-                        Vec::new()
-                    };
-
-                    if is_shutdown_requested() || cancel_token_clone.load(Ordering::Acquire) {
-                        let reason = if is_shutdown_requested() {
-                            "Task cancelled: shutdown requested"
-                        } else {
-                            "Task was cancelled or timed out"
-                        };
-
-                        let task_error = TaskError {
-                            task_name: func_name_clone.clone(),
-                            elapsed_time: exec_start.elapsed().as_secs_f64(),
-                            error_message: reason.to_string(),
-                            error_type: "CancellationError".to_string(),
-                            task_id: task_id_clone.clone(),
-                        };
+/// Class decorated with @actor; calling it starts an actor and returns its ActorRef
+#[pyclass]
+struct ActorClass {
+    cls: Py<PyAny>,
+}
 
-                        // CRITICAL FIX: Handle channel send errors
-                        if let Err(e) = sender.send(Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                            task_error.__str__()
-                        ))) {
-                            error!("Failed to send cancellation error for task {}: {}", task_id_clone, e);
-                            store_task_error(task_id_clone.clone(), format!("Cancellation failed: {}", e));
-                        }
-                        *is_complete_clone.lock() = true;
-                        unregister_task(&task_id_clone);
-                        clear_task_progress(&task_id_clone);
-                        set_current_task_id(None);
-                        return;
+#[pymethods]
+impl ActorClass {
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(&self, py: Python, args: &Bound<'_, PyTuple>, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<ActorRef> {
+        let class_name = callable_name(self.cls.bind(py));
+        let instance = self.cls.bind(py).call(args, kwargs)?.unbind();
+
+        let mailbox = ActorMailbox {
+            class_name: class_name.clone(),
+            sender: Mutex::new(None),
+            thread: Mutex::new(None),
+            inline: Mutex::new(None),
+        };
+        if sync_fallback() {
+            *mailbox.inline.lock() = Some(instance);
+        } else {
+            let (sender, messages) = unbounded::<ActorMessage>();
+            let thread = thread::Builder::new()
+                .name(format!("actor-{}", class_name))
+                .spawn(move || {
+                    for message in messages {
+                        Python::attach(|py| run_actor_message(py, &instance, message));
                     }
+                    Python::attach(|_| drop(instance));
+                })
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+            *mailbox.sender.lock() = Some(sender);
+            *mailbox.thread.lock() = Some(thread);
+        }
 
-                    // If we have dependencies, pass their results as first argument
-                    let final_result = if !dep_results.is_empty() {
-                        // Create new tuple with dependency results + original args
-                        let dep_tuple = PyTuple::new(py, dep_results.iter().map(|r| r.bind(py))).unwrap();
-                        let mut combined_args = vec![dep_tuple.into_any().unbind()];
-
-                        for arg in args_py.bind(py).iter() {
-                            combined_args.push(arg.unbind());
-                        }
-
-                        let new_tuple = PyTuple::new(py, combined_args.iter().map(|a| a.bind(py))).unwrap();
-                        func.bind(py).call(new_tuple, kwargs_py.as_ref().map(|k| k.bind(py)))
-                    } else {
-                        func.bind(py).call(args_py.bind(py), kwargs_py.as_ref().map(|k| k.bind(py)))
-                    };
-
-                    let exec_time = exec_start.elapsed().as_secs_f64() * 1000.0;
-
-                    let to_send = match final_result {
-                        Ok(val) => {
-                            record_task_execution(&func_name_clone, exec_time, true);
-                            let unbound = val.unbind();
-                            store_task_result(task_id_clone.clone(), unbound.clone_ref(py));
-                            Ok(unbound)
-                        }
-                        Err(e) => {
-                            record_task_execution(&func_name_clone, exec_time, false);
-
-                            let error_type = e.get_type(py).name()
-                                .map(|n| n.to_string())
-                                .unwrap_or_else(|_| "UnknownError".to_string());
-
-                            let task_error = TaskError {
-                                task_name: func_name_clone.clone(),
-                                elapsed_time: exec_start.elapsed().as_secs_f64(),
-                                error_message: e.to_string(),
-                                error_type,
-                                task_id: task_id_clone.clone(),
-                            };
-
-                            Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                                task_error.__str__()
-                            ))
-                        }
-                    };
-
-                    let _ = sender.send(to_send);
-                    *is_complete_clone.lock() = true;
-
-                    unregister_task(&task_id_clone);
-                    clear_task_progress(&task_id_clone);
-                    TASK_DEPENDENCIES.remove(&task_id_clone);
-                    set_current_task_id(None);
-                });
-            })
-        });
-
-        let async_handle = AsyncHandle {
-            receiver: Arc::new(Mutex::new(receiver)),
-            thread_handle: Arc::new(Mutex::new(Some(handle))),
-            is_complete,
-            result_cache: Arc::new(Mutex::new(None)),
-            cancel_token,
-            func_name,
-            start_time,
-            task_id,
-            metadata: Arc::new(Mutex::new(HashMap::new())),
-            timeout,
-            on_complete: Arc::new(Mutex::new(None)),
-            on_error: Arc::new(Mutex::new(None)),
-            on_progress: Arc::new(Mutex::new(None)),
-        };
+        Ok(ActorRef { cls: self.cls.clone_ref(py), mailbox: Arc::new(mailbox) })
+    }
 
-        Py::new(py, async_handle)
+    #[getter]
+    fn __wrapped__(&self, py: Python) -> Py<PyAny> {
+        self.cls.clone_ref(py)
     }
-}
 
-/// Decorator for parallel execution with dependency support
-#[pyfunction]
-fn parallel_with_deps(py: Python, func: Py<PyAny>) -> PyResult<Py<ParallelWithDeps>> {
-    Py::new(py, ParallelWithDeps { func })
+    fn __repr__(&self, py: Python) -> String {
+        format!("<actor class {}>", callable_name(self.cls.bind(py)))
+    }
 }
 
-/// Optimized parallel wrapper using crossbeam channels
+/// Running actor: its methods return AsyncHandles and run one at a time on the actor's thread
 #[pyclass]
-struct ParallelFastWrapper {
-    func: Py<PyAny>,
+struct ActorRef {
+    cls: Py<PyAny>,
+    mailbox: Arc<ActorMailbox>,
 }
 
 #[pymethods]
-impl ParallelFastWrapper {
-    #[pyo3(signature = (*args, **kwargs))]
-    fn __call__(
-        &self,
-        py: Python,
-        args: &Bound<'_, PyTuple>,
-        kwargs: Option<&Bound<'_, PyDict>>,
-    ) -> PyResult<Py<AsyncHandleFast>> {
-        let func = self.func.clone_ref(py);
-        let args_py: Py<PyTuple> = args.clone().unbind();
-        let kwargs_py: Option<Py<PyDict>> = kwargs.map(|k| k.clone().unbind());
-
-        // Use crossbeam unbounded channel for better performance
-        let (sender, receiver): (
-            CrossbeamSender<PyResult<Py<PyAny>>>,
-            CrossbeamReceiver<PyResult<Py<PyAny>>>,
-        ) = unbounded();
-
-        let is_complete = Arc::new(Mutex::new(false));
-        let is_complete_clone = is_complete.clone();
-
-        // Spawn thread without GIL
-        py.detach(|| {
-            thread::spawn(move || {
-                Python::attach(|py| {
-                    let result = func
-                        .bind(py)
-                        .call(args_py.bind(py), kwargs_py.as_ref().map(|k| k.bind(py)));
-
-                    let to_send = match result {
-                        Ok(val) => Ok(val.unbind()),
-                        Err(e) => Err(e),
-                    };
+impl ActorRef {
+    fn __getattr__(&self, py: Python, name: &str) -> PyResult<ActorMethod> {
+        if name.starts_with('_') || !self.cls.bind(py).hasattr(name)? {
+            return Err(pyo3::exceptions::PyAttributeError::new_err(format!(
+                "{} actor has no method '{}'",
+                self.mailbox.class_name, name
+            )));
+        }
+        Ok(ActorMethod { mailbox: self.mailbox.clone(), name: name.to_string() })
+    }
 
-                    let _ = sender.send(to_send);
-                    *is_complete_clone.lock() = true;
-                });
-            })
-        });
+    /// Stop accepting calls and wait up to `timeout` (forever if None) for queued ones
+    ///
+    /// Returns whether the actor finished in time.
+    #[pyo3(signature = (timeout=None))]
+    fn stop(&self, py: Python, timeout: Option<f64>) -> PyResult<bool> {
+        validate_timeouts(timeout, None)?;
+        self.mailbox.sender.lock().take();
+        if let Some(instance) = self.mailbox.inline.lock().take() {
+            drop(instance);
+            return Ok(true);
+        }
 
-        let async_handle = AsyncHandleFast {
-            receiver: Arc::new(Mutex::new(receiver)),
-            is_complete,
-            result_cache: Arc::new(Mutex::new(None)),
+        let Some(thread) = self.mailbox.thread.lock().take() else {
+            return Ok(true);
         };
-
-        Py::new(py, async_handle)
+        let deadline = timeout.map(|secs| Instant::now() + Duration::from_secs_f64(secs));
+        let finished = py.detach(|| {
+            while !thread.is_finished() {
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    return false;
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+            true
+        });
+        if finished {
+            let _ = thread.join();
+        } else {
+            *self.mailbox.thread.lock() = Some(thread);
+        }
+        Ok(finished)
     }
-}
 
-/// Optimized parallel decorator using crossbeam channels
-#[pyfunction]
-fn parallel_fast(py: Python, func: Py<PyAny>) -> PyResult<Py<ParallelFastWrapper>> {
-    Py::new(py, ParallelFastWrapper { func })
+    fn __repr__(&self) -> String {
+        format!("<actor {}>", self.mailbox.class_name)
+    }
 }
 
-/// Thread pool using rayon for better resource management
+/// Method of a running actor; calling it queues the call and returns an AsyncHandle
 #[pyclass]
-struct ParallelPoolWrapper {
-    func: Py<PyAny>,
+struct ActorMethod {
+    mailbox: Arc<ActorMailbox>,
+    name: String,
 }
 
 #[pymethods]
-impl ParallelPoolWrapper {
+impl ActorMethod {
     #[pyo3(signature = (*args, **kwargs))]
-    fn __call__(
-        &self,
-        py: Python,
-        args: &Bound<'_, PyTuple>,
-        kwargs: Option<&Bound<'_, PyDict>>,
-    ) -> PyResult<Py<AsyncHandleFast>> {
-        let func = self.func.clone_ref(py);
-        let args_py: Py<PyTuple> = args.clone().unbind();
-        let kwargs_py: Option<Py<PyDict>> = kwargs.map(|k| k.clone().unbind());
+    fn __call__(&self, py: Python, args: &Bound<'_, PyTuple>, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<Py<AsyncHandle>> {
+        self.mailbox.send(py, &self.name, args, kwargs)
+    }
+}
 
-        let (sender, receiver) = unbounded();
-        let is_complete = Arc::new(Mutex::new(false));
-        let is_complete_clone = is_complete.clone();
+/// Decorator turning a class into an actor: each instance lives on its own thread
+///
+/// Method calls are queued in a mailbox and run one at a time, so the instance's
+/// state needs no locks; each call returns an AsyncHandle.
+#[pyfunction]
+fn actor(py: Python, cls: Py<PyAny>) -> PyResult<ActorClass> {
+    if !cls.bind(py).is_instance_of::<PyType>() {
+        return Err(pyo3::exceptions::PyTypeError::new_err("@actor can only decorate a class"));
+    }
+    Ok(ActorClass { cls })
+}
 
-        // Use rayon thread pool - better resource management
-        py.detach(|| {
-            rayon::spawn(move || {
-                Python::attach(|py| {
-                    let result = func
-                        .bind(py)
-                        .call(args_py.bind(py), kwargs_py.as_ref().map(|k| k.bind(py)));
+/// Subscriber queues of a Topic
+struct TopicState {
+    subscribers: Vec<CrossbeamSender<Py<PyAny>>>,
+    closed: bool,
+}
 
-                    let to_send = match result {
-                        Ok(val) => Ok(val.unbind()),
-                        Err(e) => Err(e),
-                    };
+/// Publish/subscribe channel between tasks
+///
+/// Every subscription receives each message published after it was created; the
+/// same object is delivered to all of them, not a copy. Subscribers waiting for a
+/// message, and publishers waiting on a full subscription, do not hold the GIL.
+#[pyclass]
+struct Topic {
+    maxsize: Option<usize>,
+    state: Mutex<TopicState>,
+}
 
-                    let _ = sender.send(to_send);
-                    *is_complete_clone.lock() = true;
-                });
-            });
+#[pymethods]
+impl Topic {
+    /// `maxsize` bounds each subscription's backlog; publish() blocks while one is full
+    #[new]
+    #[pyo3(signature = (maxsize=None))]
+    fn new(maxsize: Option<usize>) -> PyResult<Self> {
+        if maxsize == Some(0) {
+            return Err(pyo3::exceptions::PyValueError::new_err("maxsize must be at least 1"));
+        }
+        Ok(Topic { maxsize, state: Mutex::new(TopicState { subscribers: Vec::new(), closed: false }) })
+    }
+
+    /// Send `obj` to every subscription; returns how many received it
+    fn publish(&self, py: Python, obj: Py<PyAny>) -> PyResult<usize> {
+        let subscribers = {
+            let state = self.state.lock();
+            if state.closed {
+                return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Topic is closed"));
+            }
+            state.subscribers.clone()
+        };
+        let copies: Vec<Py<PyAny>> = subscribers.iter().map(|_| obj.clone_ref(py)).collect();
+        let total = subscribers.len();
+
+        let gone: Vec<_> = py.detach(|| {
+            subscribers
+                .into_iter()
+                .zip(copies)
+                .filter_map(|(subscriber, message)| subscriber.send(message).err().map(|_| subscriber))
+                .collect()
         });
+        if !gone.is_empty() {
+            // Subscriptions that were dropped or unsubscribed
+            self.state.lock().subscribers.retain(|s| !gone.iter().any(|g| g.same_channel(s)));
+        }
+        Ok(total - gone.len())
+    }
 
-        let async_handle = AsyncHandleFast {
-            receiver: Arc::new(Mutex::new(receiver)),
-            is_complete,
-            result_cache: Arc::new(Mutex::new(None)),
+    /// New subscription receiving messages published from now on
+    fn subscribe(&self) -> PyResult<Subscription> {
+        let mut state = self.state.lock();
+        if state.closed {
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Topic is closed"));
+        }
+        let (sender, receiver) = match self.maxsize {
+            Some(maxsize) => crossbeam::channel::bounded(maxsize),
+            None => unbounded(),
         };
+        state.subscribers.push(sender);
+        Ok(Subscription { receiver: Mutex::new(Some(receiver)) })
+    }
 
-        Py::new(py, async_handle)
+    /// Stop accepting messages; subscriptions end once they have drained their backlog
+    fn close(&self) {
+        let mut state = self.state.lock();
+        state.closed = true;
+        state.subscribers.clear();
     }
 }
 
-/// Parallel decorator using rayon thread pool (optimized for many small tasks)
-#[pyfunction]
-fn parallel_pool(py: Python, func: Py<PyAny>) -> PyResult<Py<ParallelPoolWrapper>> {
-    Py::new(py, ParallelPoolWrapper { func })
+/// Outcome of waiting on a Subscription or Channel
+enum Received {
+    Message(Py<PyAny>),
+    TimedOut,
+    Closed,
 }
 
-/// Optimized memoize using DashMap (lock-free concurrent hashmap)
-#[pyfunction]
-fn memoize_fast(py: Python, func: Py<PyAny>) -> PyResult<Py<PyAny>> {
-    // Use DashMap - lock-free concurrent hashmap
-    let cache: Arc<DashMap<String, Py<PyAny>>> = Arc::new(DashMap::new());
-    let func_clone = func.clone_ref(py);
+/// Messages of a Topic for one subscriber; iterate it, or call get()
+#[pyclass]
+struct Subscription {
+    receiver: Mutex<Option<CrossbeamReceiver<Py<PyAny>>>>,
+}
 
-    let wrapper = move |args: &Bound<'_, PyTuple>,
-                        kwargs: Option<&Bound<'_, PyDict>>|
-          -> PyResult<Py<PyAny>> {
-        let py = args.py();
+/// Run `attempt` with the GIL released until it yields a value or `timeout` expires
+///
+/// Each attempt blocks for at most the given slice; between attempts the waiter
+/// stays responsive to task cancellation and signals such as Ctrl-C.
+fn wait_detached<T: Send>(
+    py: Python,
+    timeout: Option<f64>,
+    mut attempt: impl FnMut(Duration) -> Option<T> + Send,
+) -> PyResult<Option<T>> {
+    const POLL: Duration = Duration::from_millis(100);
+    let deadline = timeout.map(|secs| Instant::now() + Duration::from_secs_f64(secs));
+    let cancel_flag = current_cancel_flag();
 
-        // Create cache key
-        let mut key_parts: Vec<String> = vec![];
-        for arg in args.iter() {
-            key_parts.push(arg.repr()?.to_str()?.to_string());
+    loop {
+        let wait = deadline.map_or(POLL, |d| d.saturating_duration_since(Instant::now()).min(POLL));
+        if let Some(value) = py.detach(|| attempt(wait)) {
+            return Ok(Some(value));
         }
-        if let Some(kwargs_dict) = kwargs {
-            for (key, val) in kwargs_dict.iter() {
-                key_parts.push(format!("{}={}", key, val.repr()?.to_str()?));
-            }
+        if let Some(ref flag) = cancel_flag {
+            CancellationToken { flag: flag.clone() }.raise_if_cancelled()?;
         }
-        let key = key_parts.join(",");
-
-        // Check cache (lock-free read)
-        if let Some(cached) = cache.get(&key) {
-            println!("Cache hit for key: {}", key);
-            return Ok(cached.clone_ref(py));
+        py.check_signals()?;
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            return Ok(None);
         }
+    }
+}
 
-        // Cache miss - compute result
-        println!("Cache miss for key: {}", key);
-        let result = func_clone.bind(py).call(args, kwargs)?;
-        let result_unbound = result.unbind();
+impl Subscription {
+    /// Wait for the next message without the GIL
+    fn receive(&self, py: Python, timeout: Option<f64>) -> PyResult<Received> {
+        let Some(receiver) = self.receiver.lock().clone() else {
+            return Ok(Received::Closed);
+        };
+        let received = wait_detached(py, timeout, |wait| match receiver.recv_timeout(wait) {
+            Ok(message) => Some(Received::Message(message)),
+            Err(crossbeam::channel::RecvTimeoutError::Disconnected) => Some(Received::Closed),
+            Err(crossbeam::channel::RecvTimeoutError::Timeout) => None,
+        })?;
+        Ok(received.unwrap_or(Received::TimedOut))
+    }
+}
 
-        // Insert into cache (lock-free write)
-        cache.insert(key, result_unbound.clone_ref(py));
+#[pymethods]
+impl Subscription {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
 
-        Ok(result_unbound)
-    };
+    fn __next__(&self, py: Python) -> PyResult<Option<Py<PyAny>>> {
+        match self.receive(py, None)? {
+            Received::Message(message) => Ok(Some(message)),
+            _ => Ok(None),
+        }
+    }
 
-    let wrapped = PyCFunction::new_closure(py, None, None, wrapper)?;
+    /// Next message; raises TimeoutError if none arrives within `timeout` seconds
+    /// and EOFError once the topic is closed and the backlog drained
+    #[pyo3(signature = (timeout=None))]
+    fn get(&self, py: Python, timeout: Option<f64>) -> PyResult<Py<PyAny>> {
+        validate_timeouts(timeout, None)?;
+        match self.receive(py, timeout)? {
+            Received::Message(message) => Ok(message),
+            Received::TimedOut => Err(pyo3::exceptions::PyTimeoutError::new_err("no message within timeout")),
+            Received::Closed => Err(pyo3::exceptions::PyEOFError::new_err("Topic is closed")),
+        }
+    }
 
-    let method_wrapper = Py::new(
-        py,
-        MethodWrapper {
-            func: func.clone_ref(py),
-            wrapper: wrapped.into(),
-        },
-    )?;
-    Ok(method_wrapper.into())
+    /// Stop receiving messages; iteration ends
+    fn unsubscribe(&self) {
+        self.receiver.lock().take();
+    }
 }
 
-/// Batch parallel processing - execute multiple functions in parallel
-#[pyfunction]
-fn parallel_map(py: Python, func: Py<PyAny>, items: Vec<Py<PyAny>>) -> PyResult<Vec<Py<PyAny>>> {
-    py.detach(|| {
-        // Use rayon for parallel iteration
-        let results: Vec<_> = items
-            .par_iter()
-            .map(|item| {
-                Python::attach(|py| func.bind(py).call1((item.bind(py),)).map(|r| r.unbind()))
-            })
-            .collect();
-
-        // Convert results
-        results.into_iter().collect()
-    })
+/// Raise `queue.<name>`, so Channel is a drop-in for `queue.Queue`
+fn queue_error(py: Python, name: &str, message: &str) -> PyErr {
+    match py.import("queue").and_then(|queue| queue.getattr(name)) {
+        Ok(exc_type) => match exc_type.cast_into::<PyType>() {
+            Ok(exc_type) => PyErr::from_type(exc_type, message.to_string()),
+            Err(e) => e.into(),
+        },
+        Err(e) => e,
+    }
 }
 
-/// Priority parallel wrapper - tasks execute based on priority
+/// FIFO queue between threads whose blocking put()/get() release the GIL
+///
+/// Mirrors the `queue.Queue` API; `maxsize <= 0` means unbounded. After close(),
+/// put() raises RuntimeError and get() drains the remaining items, then raises
+/// EOFError; iterating a Channel yields items until it is closed and drained.
 #[pyclass]
-struct PriorityParallelWrapper {
-    func: Py<PyAny>,
+struct Channel {
+    #[pyo3(get)]
+    maxsize: isize,
+    sender: CrossbeamSender<Py<PyAny>>,
+    receiver: CrossbeamReceiver<Py<PyAny>>,
+    closed: AtomicBool,
+    /// Dropped by close(), which disconnects `closed_signal` so select() wakes up
+    close_sender: Mutex<Option<CrossbeamSender<()>>>,
+    closed_signal: CrossbeamReceiver<()>,
+}
+
+impl Channel {
+    fn check_open(&self) -> PyResult<()> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(pyo3::exceptions::PyRuntimeError::new_err("Channel is closed"));
+        }
+        Ok(())
+    }
+
+    /// Wait for an item; `timeout` of Some(0.0) means don't block
+    fn receive(&self, py: Python, timeout: Option<f64>) -> PyResult<Received> {
+        if let Ok(item) = self.receiver.try_recv() {
+            return Ok(Received::Message(item));
+        }
+        if self.closed.load(Ordering::Acquire) {
+            return Ok(Received::Closed);
+        }
+        if timeout == Some(0.0) {
+            return Ok(Received::TimedOut);
+        }
+        let received = wait_detached(py, timeout, |wait| match self.receiver.recv_timeout(wait) {
+            Ok(item) => Some(Received::Message(item)),
+            Err(_) if self.closed.load(Ordering::Acquire) && self.receiver.is_empty() => Some(Received::Closed),
+            Err(_) => None,
+        })?;
+        Ok(received.unwrap_or(Received::TimedOut))
+    }
 }
 
 #[pymethods]
-impl PriorityParallelWrapper {
-    #[pyo3(signature = (*args, priority=0, timeout=None, **kwargs))]
-    fn __call__(
-        &self,
-        py: Python,
-        args: &Bound<'_, PyTuple>,
-        priority: i32,
-        timeout: Option<f64>,
-        kwargs: Option<&Bound<'_, PyDict>>,
-    ) -> PyResult<Py<AsyncHandle>> {
-        // Check if shutdown is requested
-        if is_shutdown_requested() {
-            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                "Cannot start new tasks: shutdown in progress"
-            ));
+impl Channel {
+    #[new]
+    #[pyo3(signature = (maxsize=0))]
+    fn new(maxsize: isize) -> Self {
+        let (sender, receiver) = if maxsize > 0 {
+            crossbeam::channel::bounded(maxsize as usize)
+        } else {
+            crossbeam::channel::unbounded()
+        };
+        let (close_sender, closed_signal) = crossbeam::channel::bounded(0);
+        Channel {
+            maxsize,
+            sender,
+            receiver,
+            closed: AtomicBool::new(false),
+            close_sender: Mutex::new(Some(close_sender)),
+            closed_signal,
         }
+    }
 
-        // Wait for available slot (backpressure)
-        wait_for_slot();
+    /// Add an item, waiting up to `timeout` seconds for space (raises queue.Full)
+    #[pyo3(signature = (item, block=true, timeout=None))]
+    fn put(&self, py: Python, item: Py<PyAny>, block: bool, timeout: Option<f64>) -> PyResult<()> {
+        validate_timeouts(timeout, None)?;
+        self.check_open()?;
+        let mut pending = match self.sender.try_send(item) {
+            Ok(()) => return Ok(()),
+            Err(e) => Some(e.into_inner()),
+        };
+        if !block || timeout == Some(0.0) {
+            return Err(queue_error(py, "Full", "Channel is full"));
+        }
 
-        // Check memory before starting
-        if !check_memory_ok() {
-            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                "Memory limit reached, cannot start new task"
-            ));
+        let sent = wait_detached(py, timeout, |wait| {
+            let item = pending.take()?;
+            match self.sender.send_timeout(item, wait) {
+                Ok(()) => Some(true),
+                Err(e) => {
+                    pending = Some(e.into_inner());
+                    self.closed.load(Ordering::Acquire).then_some(false)
+                }
+            }
+        })?;
+        match sent {
+            Some(true) => Ok(()),
+            Some(false) => Err(pyo3::exceptions::PyRuntimeError::new_err("Channel is closed")),
+            None => Err(queue_error(py, "Full", "Channel is full")),
         }
+    }
 
-        let func = self.func.clone_ref(py);
+    /// Remove and return an item, waiting up to `timeout` seconds (raises queue.Empty)
+    #[pyo3(signature = (block=true, timeout=None))]
+    fn get(&self, py: Python, block: bool, timeout: Option<f64>) -> PyResult<Py<PyAny>> {
+        validate_timeouts(timeout, None)?;
+        match self.receive(py, if block { timeout } else { Some(0.0) })? {
+            Received::Message(item) => Ok(item),
+            Received::TimedOut => Err(queue_error(py, "Empty", "Channel is empty")),
+            Received::Closed => Err(pyo3::exceptions::PyEOFError::new_err("Channel is closed")),
+        }
+    }
 
-        // Generate unique task ID
-        let task_id = format!("task_{}", TASK_ID_COUNTER.fetch_add(1, Ordering::Relaxed));
-        let task_id_clone = task_id.clone();
+    fn put_nowait(&self, py: Python, item: Py<PyAny>) -> PyResult<()> {
+        self.put(py, item, false, None)
+    }
 
-        // Register task as active
-        register_task(task_id.clone());
+    fn get_nowait(&self, py: Python) -> PyResult<Py<PyAny>> {
+        self.get(py, false, None)
+    }
 
-        // Get function name for profiling
-        let func_name = func
-            .bind(py)
-            .getattr("__name__")
-            .ok()
-            .and_then(|n| n.extract::<String>().ok())
-            .unwrap_or_else(|| "unknown".to_string());
+    /// Stop accepting items; blocked and future get() calls end once drained
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.close_sender.lock().take();
+    }
 
-        let args_py: Py<PyTuple> = args.clone().unbind();
-        let kwargs_py: Option<Py<PyDict>> = kwargs.map(|k| k.clone().unbind());
+    #[getter]
+    fn closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
 
-        // Use crossbeam channel for priority queue
-        let (sender, receiver) = unbounded();
+    fn qsize(&self) -> usize {
+        self.receiver.len()
+    }
 
-        let is_complete = Arc::new(Mutex::new(false));
-        let cancel_token = Arc::new(AtomicBool::new(false));
-        let start_time = Instant::now();
+    fn empty(&self) -> bool {
+        self.receiver.is_empty()
+    }
 
-        // Setup timeout if specified
-        if let Some(timeout_secs) = timeout {
-            let cancel_token_timeout = cancel_token.clone();
-            thread::spawn(move || {
-                thread::sleep(Duration::from_secs_f64(timeout_secs));
-                cancel_token_timeout.store(true, Ordering::Release);
-            });
+    fn full(&self) -> bool {
+        self.receiver.is_full()
+    }
+
+    fn __len__(&self) -> usize {
+        self.receiver.len()
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python) -> PyResult<Option<Py<PyAny>>> {
+        match self.receive(py, None)? {
+            Received::Message(item) => Ok(Some(item)),
+            _ => Ok(None),
         }
+    }
+}
 
-        // Create priority task
-        let task = PriorityTask {
-            priority,
-            func,
-            args: args_py,
-            kwargs: kwargs_py,
-            sender,
-        };
+/// Source for select() that becomes ready once `seconds` have passed
+#[pyclass]
+struct Timer {
+    deadline: Instant,
+    #[pyo3(get)]
+    seconds: f64,
+}
+
+#[pymethods]
+impl Timer {
+    #[new]
+    fn new(seconds: f64) -> PyResult<Self> {
+        validate_timeouts(Some(seconds), None)?;
+        Ok(Timer { deadline: Instant::now() + Duration::from_secs_f64(seconds), seconds })
+    }
 
-        // Push to priority queue
-        PRIORITY_QUEUE.lock().push(task);
+    fn __repr__(&self) -> String {
+        format!("Timer({})", self.seconds)
+    }
+}
 
-        // Ensure worker is running
-        if !PRIORITY_WORKER_RUNNING.load(Ordering::SeqCst) {
-            start_priority_worker(py)?;
+/// Handle that completes with None once `seconds` have passed
+///
+/// Composes with gather(), select() and the rest of the handle API like any task;
+/// cancelling the handle stops the timer and fails it with TaskCancelled.
+#[pyfunction]
+fn after(seconds: f64) -> PyResult<AsyncHandle> {
+    validate_timeouts(Some(seconds), None)?;
+    let (handle, completer) = AsyncHandle::detached("after".to_string(), Vec::new(), None);
+    let cancel_token = handle.cancel_token.clone();
+    let deadline = Instant::now() + Duration::from_secs_f64(seconds);
+
+    spawn_task_thread(move || {
+        while !cancel_token.load(Ordering::Acquire) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                Python::attach(|py| completer.complete(py, Ok(py.None())));
+                return;
+            }
+            thread::sleep(remaining.min(Duration::from_millis(50)));
         }
+        Python::attach(|py| completer.complete(py, Err(TaskCancelled::new_err("timer was cancelled"))));
+    });
+    Ok(handle)
+}
 
-        // Create full AsyncHandle with all features
-        let async_handle = AsyncHandle {
-            receiver: Arc::new(Mutex::new({
-                // Convert crossbeam receiver to std::sync::mpsc receiver
-                // We need to spawn a helper thread to bridge the two channel types
-                let (std_sender, std_receiver): (Sender<PyResult<Py<PyAny>>>, Receiver<PyResult<Py<PyAny>>>) = channel();
-                let is_complete_clone = is_complete.clone();
+/// Periodic source for select() and `for` loops, created by interval()
+///
+/// Each tick yields its 1-based number. Ticks missed while nobody is waiting are
+/// dropped rather than queued, so a slow consumer never sees a burst.
+#[pyclass]
+struct Interval {
+    ticks: Mutex<Option<CrossbeamReceiver<Instant>>>,
+    count: AtomicU64,
+    #[pyo3(get)]
+    seconds: f64,
+}
+
+impl Interval {
+    /// Number of the tick just received
+    fn next_tick(&self) -> u64 {
+        self.count.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+#[pymethods]
+impl Interval {
+    /// Stop ticking; iteration ends and select() no longer sees this source
+    fn stop(&self) {
+        self.ticks.lock().take();
+    }
+
+    #[getter]
+    fn stopped(&self) -> bool {
+        self.ticks.lock().is_none()
+    }
 
-                thread::spawn(move || {
-                    match receiver.recv() {
-                        Ok(result) => {
-                            let _ = std_sender.send(result);
-                            *is_complete_clone.lock() = true;
-                            unregister_task(&task_id_clone);
-                        }
-                        Err(_) => {
-                            let _ = std_sender.send(Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                                "Priority task channel closed unexpectedly"
-                            )));
-                            *is_complete_clone.lock() = true;
-                            unregister_task(&task_id_clone);
-                        }
-                    }
-                });
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
 
-                std_receiver
-            })),
-            thread_handle: Arc::new(Mutex::new(None)), // Priority tasks don't have individual thread handles
-            is_complete,
-            result_cache: Arc::new(Mutex::new(None)),
-            cancel_token,
-            func_name,
-            start_time,
-            task_id,
-            metadata: Arc::new(Mutex::new(HashMap::new())),
-            timeout,
-            on_complete: Arc::new(Mutex::new(None)),
-            on_error: Arc::new(Mutex::new(None)),
-            on_progress: Arc::new(Mutex::new(None)),
-        };
+    fn __next__(&self, py: Python) -> PyResult<Option<u64>> {
+        let ticked = wait_detached(py, None, |wait| match self.ticks.lock().clone() {
+            Some(ticks) => ticks.recv_timeout(wait).ok().map(|_| true),
+            None => Some(false),
+        })?;
+        Ok(ticked.unwrap_or(false).then(|| self.next_tick()))
+    }
 
-        Py::new(py, async_handle)
+    fn __repr__(&self) -> String {
+        format!("Interval({}, ticks={})", self.seconds, self.count.load(Ordering::Relaxed))
     }
 }
 
-/// Priority parallel decorator
+/// Source that becomes ready every `seconds`, for select() or iteration
 #[pyfunction]
-fn parallel_priority(py: Python, func: Py<PyAny>) -> PyResult<Py<PriorityParallelWrapper>> {
-    Py::new(py, PriorityParallelWrapper { func })
+fn interval(seconds: f64) -> PyResult<Interval> {
+    if !seconds.is_finite() || seconds <= 0.0 {
+        return Err(pyo3::exceptions::PyValueError::new_err("interval must be a positive number of seconds"));
+    }
+    Ok(Interval {
+        ticks: Mutex::new(Some(crossbeam::channel::tick(Duration::from_secs_f64(seconds)))),
+        count: AtomicU64::new(0),
+        seconds,
+    })
 }
 
-/// Decorator with profiling enabled
-#[pyfunction]
-fn profiled(py: Python, func: Py<PyAny>) -> PyResult<Py<PyAny>> {
-    let func_clone = func.clone_ref(py);
-    let wrapper = move |args: &Bound<'_, PyTuple>,
-                        kwargs: Option<&Bound<'_, PyDict>>|
-          -> PyResult<Py<PyAny>> {
-        let py = args.py();
+/// What select() waits on for one of its sources
+enum SelectSource {
+    /// Signals the source's index on the shared completion channel
+    Handle,
+    Items(CrossbeamReceiver<Py<PyAny>>),
+    /// A Channel's items, plus the signal that disconnects once it is closed
+    Channel(CrossbeamReceiver<Py<PyAny>>, CrossbeamReceiver<()>),
+    Timer(CrossbeamReceiver<Instant>),
+}
 
-        let func_name = func_clone
-            .bind(py)
-            .getattr("__name__")
-            .ok()
-            .and_then(|n| n.extract::<String>().ok())
-            .unwrap_or_else(|| "unknown".to_string());
+/// Wait until one of `sources` is ready and return `(source, value)`
+///
+/// Sources are AsyncHandles (ready once finished; the value is None, so call
+/// `get()`), Channels and Subscriptions (ready with an item, which is consumed;
+/// a Channel that is closed and drained is ready with None), Timers (ready once
+/// elapsed; the value is None) and Intervals (ready on each tick; the value is
+/// the tick number). Sources already ready win in list order. Returns None if
+/// nothing is ready within `timeout` seconds.
+#[pyfunction]
+#[pyo3(signature = (sources, timeout=None))]
+fn select<'py>(
+    py: Python<'py>,
+    sources: Vec<Bound<'py, PyAny>>,
+    timeout: Option<f64>,
+) -> PyResult<Option<(Bound<'py, PyAny>, Py<PyAny>)>> {
+    validate_timeouts(timeout, None)?;
+    let (ready_tx, ready_rx) = unbounded::<usize>();
+
+    let mut waits = Vec::with_capacity(sources.len());
+    for source in &sources {
+        let wait = if let Ok(handle) = source.cast::<AsyncHandle>() {
+            let handle = handle.borrow();
+            if handle.completion.outcome(py).is_some() {
+                return Ok(Some((source.clone(), py.None())));
+            }
+            SelectSource::Handle
+        } else if let Ok(channel) = source.cast::<Channel>() {
+            let channel = channel.borrow();
+            if let Ok(item) = channel.receiver.try_recv() {
+                return Ok(Some((source.clone(), item)));
+            }
+            if channel.closed.load(Ordering::Acquire) {
+                return Ok(Some((source.clone(), py.None())));
+            }
+            SelectSource::Channel(channel.receiver.clone(), channel.closed_signal.clone())
+        } else if let Ok(subscription) = source.cast::<Subscription>() {
+            match subscription.borrow().receiver.lock().clone() {
+                Some(receiver) => SelectSource::Items(receiver),
+                // Unsubscribed: never ready
+                None => SelectSource::Items(crossbeam::channel::never()),
+            }
+        } else if let Ok(timer) = source.cast::<Timer>() {
+            let deadline = timer.borrow().deadline;
+            if Instant::now() >= deadline {
+                return Ok(Some((source.clone(), py.None())));
+            }
+            SelectSource::Timer(crossbeam::channel::at(deadline))
+        } else if let Ok(interval) = source.cast::<Interval>() {
+            let interval = interval.borrow();
+            let Some(ticks) = interval.ticks.lock().clone() else {
+                // Stopped: never ready
+                waits.push(SelectSource::Timer(crossbeam::channel::never()));
+                continue;
+            };
+            if ticks.try_recv().is_ok() {
+                return Ok(Some((source.clone(), interval.next_tick().into_pyobject(py)?.into_any().unbind())));
+            }
+            SelectSource::Timer(ticks)
+        } else {
+            return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                "select() sources must be AsyncHandle, Channel, Subscription, Timer or Interval, got {}",
+                source.get_type().name()?
+            )));
+        };
+        waits.push(wait);
+    }
 
-        let start = Instant::now();
-        let result = func_clone.bind(py).call(args, kwargs);
-        let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+    // Handles report completion through one shared channel, tagged with their index.
+    // The listeners are removed again once select() returns, so a handle that is
+    // selected on repeatedly doesn't collect one per call.
+    let mut listeners = Vec::new();
+    for (index, source) in sources.iter().enumerate() {
+        if let Ok(handle) = source.cast::<AsyncHandle>() {
+            let ready_tx = ready_tx.clone();
+            let completion = handle.borrow().completion.clone();
+            let listener = completion.subscribe_removable(
+                py,
+                Box::new(move |_, _| {
+                    let _ = ready_tx.send(index);
+                }),
+            );
+            listeners.extend(listener.map(|id| (completion, id)));
+        }
+    }
+    drop(ready_tx);
+
+    let mut disconnected = vec![false; waits.len()];
+    let selected = wait_detached(py, timeout, |wait| {
+        let mut select = crossbeam::channel::Select::new();
+        let handles_op = select.recv(&ready_rx);
+        let mut ops = Vec::new();
+        for (index, source) in waits.iter().enumerate() {
+            let op = match source {
+                SelectSource::Items(receiver) if !disconnected[index] => select.recv(receiver),
+                SelectSource::Channel(items, closed_signal) => {
+                    ops.push((select.recv(closed_signal), index, true));
+                    select.recv(items)
+                }
+                SelectSource::Timer(receiver) => select.recv(receiver),
+                _ => continue,
+            };
+            ops.push((op, index, false));
+        }
 
-        match result {
-            Ok(val) => {
-                record_task_execution(&func_name, duration_ms, true);
-                Ok(val.unbind())
-            }
-            Err(e) => {
-                record_task_execution(&func_name, duration_ms, false);
-                Err(e)
+        let operation = select.select_timeout(wait).ok()?;
+        if operation.index() == handles_op {
+            return operation.recv(&ready_rx).ok().map(|index| (index, None));
+        }
+        let (_, index, close_signal) = *ops.iter().find(|(op, _, _)| *op == operation.index())?;
+        match &waits[index] {
+            // close() disconnected the signal; items put before it are still handed out first
+            SelectSource::Channel(items, closed_signal) if close_signal => {
+                let _ = operation.recv(closed_signal);
+                Some((index, items.try_recv().ok()))
             }
+            SelectSource::Channel(items, _) => operation.recv(items).ok().map(|item| (index, Some(item))),
+            SelectSource::Items(receiver) => match operation.recv(receiver) {
+                Ok(item) => Some((index, Some(item))),
+                // The topic closed: drop the subscription from later rounds
+                Err(_) => {
+                    disconnected[index] = true;
+                    None
+                }
+            },
+            SelectSource::Timer(receiver) => operation.recv(receiver).ok().map(|_| (index, None)),
+            SelectSource::Handle => None,
         }
-    };
+    });
+    for (completion, id) in listeners {
+        completion.unsubscribe(id);
+    }
+    let selected = selected?;
 
-    let wrapped = PyCFunction::new_closure(py, None, None, wrapper)?;
+    let Some((index, value)) = selected else {
+        return Ok(None);
+    };
+    let source = sources[index].clone();
+    let value = match source.cast::<Interval>() {
+        Ok(interval) => interval.borrow().next_tick().into_pyobject(py)?.into_any().unbind(),
+        Err(_) => value.unwrap_or_else(|| py.None()),
+    };
+    Ok(Some((source, value)))
+}
 
-    let method_wrapper = Py::new(
-        py,
-        MethodWrapper {
-            func: func.clone_ref(py),
-            wrapper: wrapped.into(),
-        },
-    )?;
-    Ok(method_wrapper.into())
+/// Compute the delay that follows `delay` for a `retry_backoff` strategy
+/// Presets accepted for retry_backoff()'s `backoff`
+const BACKOFF_STRATEGIES: [&str; 3] = ["exponential", "linear", "decorrelated_jitter"];
+
+fn next_backoff_delay(backoff: &str, delay: f64, initial_delay: f64, max_delay: f64) -> f64 {
+    match backoff {
+        "exponential" => (delay * 2.0).min(max_delay),
+        "linear" => (delay + initial_delay).min(max_delay),
+        // AWS-style decorrelated jitter: random between the base and 3x the last delay
+        "decorrelated_jitter" => {
+            let upper = (delay * 3.0).max(initial_delay);
+            rand::thread_rng().gen_range(initial_delay..=upper).min(max_delay)
+        }
+        _ => delay,
+    }
 }
 
-// =============================================================================
-// HELPER FUNCTIONS
-// =============================================================================
+/// Which failures a retry decorator retries, from its `retry_on` and `giveup_on` options
+struct RetryFilter {
+    retry_on: Option<Py<PyAny>>,
+    giveup_on: Option<Py<PyAny>>,
+}
 
-/// Gather results from multiple handles
-#[pyfunction]
-#[pyo3(signature = (handles, on_error="raise"))]
-fn gather(py: Python, handles: Vec<Py<AsyncHandle>>, on_error: &str) -> PyResult<Vec<Py<PyAny>>> {
-    let mut results = Vec::new();
+impl RetryFilter {
+    fn new(retry_on: Option<&Bound<'_, PyAny>>, giveup_on: Option<&Bound<'_, PyAny>>) -> PyResult<Arc<Self>> {
+        Ok(Arc::new(RetryFilter {
+            retry_on: retry_on.map(|types| exception_types("retry_on", types)).transpose()?,
+            giveup_on: giveup_on.map(|types| exception_types("giveup_on", types)).transpose()?,
+        }))
+    }
 
-    for handle in handles {
-        let h = handle.bind(py);
-        match h.call_method0("get") {
-            Ok(result) => results.push(result.unbind()),
-            Err(e) => match on_error {
-                "raise" => return Err(e),
-                "skip" => continue,
-                "none" => results.push(py.None()),
-                _ => return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-                    "on_error must be 'raise', 'skip', or 'none'"
-                )),
-            },
+    /// Whether `error` may be retried; `giveup_on` wins over `retry_on`
+    fn should_retry(&self, py: Python, error: &PyErr) -> bool {
+        if self.giveup_on.as_ref().is_some_and(|types| error.is_instance(py, types.bind(py))) {
+            return false;
         }
+        self.retry_on.as_ref().is_none_or(|types| error.is_instance(py, types.bind(py)))
     }
-
-    Ok(results)
 }
 
-/// Context manager for parallel execution
-#[pyclass]
-struct ParallelContext {
-    handles: Arc<Mutex<Vec<Py<AsyncHandle>>>>,
-    timeout: Option<f64>,
+/// User callbacks from retry_backoff's `on_retry` and `on_giveup` options
+struct RetryHooks {
+    on_retry: Option<Py<PyAny>>,
+    on_giveup: Option<Py<PyAny>>,
 }
 
-#[pymethods]
-impl ParallelContext {
-    #[new]
-    #[pyo3(signature = (timeout=None))]
-    fn new(timeout: Option<f64>) -> Self {
-        ParallelContext {
-            handles: Arc::new(Mutex::new(Vec::new())),
-            timeout,
+impl RetryHooks {
+    fn new(py: Python, on_retry: Option<Py<PyAny>>, on_giveup: Option<Py<PyAny>>) -> PyResult<Arc<Self>> {
+        for (name, hook) in [("on_retry", &on_retry), ("on_giveup", &on_giveup)] {
+            if hook.as_ref().is_some_and(|hook| !hook.bind(py).is_callable()) {
+                return Err(pyo3::exceptions::PyTypeError::new_err(format!("{} must be callable", name)));
+            }
         }
+        Ok(Arc::new(RetryHooks { on_retry, on_giveup }))
     }
 
-    /// Submit a task
-    fn submit(&self, py: Python, func: Py<PyAny>, args: &Bound<'_, PyTuple>) -> PyResult<Py<AsyncHandle>> {
-        // Call the function with timeout if specified
-        let handle = if let Some(timeout) = self.timeout {
-            func.bind(py).call_method1("__call__", (args, ("timeout", timeout)))?
-        } else {
-            func.bind(py).call(args, None)?
-        };
+    fn is_empty(&self) -> bool {
+        self.on_retry.is_none() && self.on_giveup.is_none()
+    }
 
-        let async_handle: Py<AsyncHandle> = handle.extract()?;
-        self.handles.lock().push(async_handle.clone_ref(py));
-        Ok(async_handle)
+    /// Report a failed attempt that will be retried after `next_delay` seconds
+    fn retrying(&self, py: Python, func_name: &str, attempt: usize, error: &PyErr, next_delay: f64) {
+        if let Some(ref hook) = self.on_retry {
+            if let Err(e) = hook.bind(py).call1((attempt, error.value(py), next_delay)) {
+                warn!("on_retry hook failed for {}: {}", func_name, e);
+            }
+        }
     }
 
-    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
-        slf
+    /// Report the error the decorator is about to raise
+    fn giving_up(&self, py: Python, func_name: &str, error: &PyErr) {
+        if let Some(ref hook) = self.on_giveup {
+            if let Err(e) = hook.bind(py).call1((error.value(py),)) {
+                warn!("on_giveup hook failed for {}: {}", func_name, e);
+            }
+        }
     }
+}
 
-    fn __exit__(
-        &self,
-        py: Python,
-        _exc_type: &Bound<'_, PyAny>,
-        _exc_val: &Bound<'_, PyAny>,
-        _exc_tb: &Bound<'_, PyAny>,
-    ) -> PyResult<bool> {
-        // Wait for all tasks
-        let handles_guard = self.handles.lock();
-        for handle in handles_guard.iter() {
-            let _ = handle.bind(py).call_method0("get");
+/// Check that `types` is an exception class or a tuple of them, as `except` accepts
+fn exception_types(param: &str, types: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+    let is_exception_class = |t: &Bound<'_, PyAny>| {
+        t.cast::<PyType>()
+            .is_ok_and(|t| t.is_subclass_of::<pyo3::exceptions::PyBaseException>().unwrap_or(false))
+    };
+    let valid = match types.cast::<PyTuple>() {
+        Ok(tuple) => tuple.iter().all(|t| is_exception_class(&t)),
+        Err(_) => is_exception_class(types),
+    };
+    if !valid {
+        return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+            "{} must be an exception class or a tuple of exception classes",
+            param
+        )));
+    }
+    Ok(types.clone().unbind())
+}
+
+/// Randomization added to retry delays so clients backing off together spread out
+#[derive(Clone, Copy)]
+enum Jitter {
+    /// Sleep a uniform random time between zero and the computed delay
+    Full,
+    /// AWS-style: between the initial delay and three times the previous sleep
+    Decorrelated,
+}
+
+impl Jitter {
+    fn parse(jitter: Option<&str>) -> PyResult<Option<Jitter>> {
+        match jitter {
+            None => Ok(None),
+            Some("full") => Ok(Some(Jitter::Full)),
+            Some("decorrelated") => Ok(Some(Jitter::Decorrelated)),
+            Some(other) => Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "jitter must be 'full' or 'decorrelated', got {:?}",
+                other
+            ))),
         }
-        Ok(false)
     }
+
+    /// Randomize `delay`; `previous` is the last sleep actually taken
+    fn apply(self, delay: f64, previous: f64, initial_delay: f64, max_delay: f64) -> f64 {
+        let mut rng = rand::thread_rng();
+        match self {
+            Jitter::Full => rng.gen_range(0.0..=delay),
+            Jitter::Decorrelated => {
+                rng.gen_range(initial_delay..=(previous * 3.0).max(initial_delay)).min(max_delay)
+            }
+        }
+    }
+}
+
+/// Ask a user `schedule(attempt)` callable for the delay before the next attempt
+fn scheduled_delay(schedule: &Bound<'_, PyAny>, attempt: usize) -> PyResult<f64> {
+    let delay: f64 = schedule.call1((attempt,))?.extract()?;
+    if !delay.is_finite() || delay < 0.0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "schedule({}) returned {}, expected a non-negative number of seconds",
+            attempt, delay
+        )));
+    }
+    Ok(delay)
 }
 
 /// Enhanced retry with exponential backoff
+///
+/// `backoff` is one of "exponential", "linear" or "decorrelated_jitter".
+/// `schedule=callable(attempt) -> delay` overrides the preset entirely.
+/// Only exceptions matching `retry_on` (default: all) and not `giveup_on` are
+/// retried; others are raised at once. `jitter="full"` sleeps a random part of
+/// each delay, `jitter="decorrelated"` randomizes around the previous sleep.
+/// `on_retry(attempt, exc, next_delay)` runs before each backoff sleep and
+/// `on_giveup(exc)` before the final error is raised.
 #[pyfunction]
-#[pyo3(signature = (*, max_attempts=3, backoff="exponential", initial_delay=1.0, max_delay=60.0))]
+#[pyo3(signature = (
+    *,
+    max_attempts=3,
+    backoff="exponential",
+    initial_delay=1.0,
+    max_delay=60.0,
+    schedule=None,
+    retry_on=None,
+    giveup_on=None,
+    jitter=None,
+    on_retry=None,
+    on_giveup=None
+))]
+#[allow(clippy::too_many_arguments)]
 fn retry_backoff(
     _py: Python<'_>,
     max_attempts: usize,
     backoff: &str,
     initial_delay: f64,
     max_delay: f64,
+    schedule: Option<Py<PyAny>>,
+    retry_on: Option<Bound<'_, PyAny>>,
+    giveup_on: Option<Bound<'_, PyAny>>,
+    jitter: Option<&str>,
+    on_retry: Option<Py<PyAny>>,
+    on_giveup: Option<Py<PyAny>>,
 ) -> PyResult<Py<PyAny>> {
+    if let Some(ref s) = schedule {
+        if !s.bind(_py).is_callable() {
+            return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                "schedule must be a callable taking the attempt number"
+            ));
+        }
+    }
+    if !BACKOFF_STRATEGIES.contains(&backoff) {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "backoff must be one of {}, got {:?}",
+            BACKOFF_STRATEGIES.map(|name| format!("'{}'", name)).join(", "),
+            backoff
+        )));
+    }
+    for (name, secs) in [("initial_delay", initial_delay), ("max_delay", max_delay)] {
+        if !secs.is_finite() || secs < 0.0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "{} must be a non-negative number of seconds, got {}",
+                name, secs
+            )));
+        }
+    }
+    let filter = RetryFilter::new(retry_on.as_ref(), giveup_on.as_ref())?;
+    let jitter = Jitter::parse(jitter)?;
+    let hooks = RetryHooks::new(_py, on_retry, on_giveup)?;
+
     let backoff_owned = backoff.to_string();
     let factory = move |py: Python<'_>, func: Py<PyAny>| -> PyResult<Py<PyAny>> {
         let backoff_clone = backoff_owned.clone();
+        let schedule = schedule.as_ref().map(|s| s.clone_ref(py));
+        let filter = filter.clone();
+        let hooks = hooks.clone();
+        let func_name = callable_name(func.bind(py));
         let wrapper = move |args: &Bound<'_, PyTuple>,
                             kwargs: Option<&Bound<'_, PyDict>>|
               -> PyResult<Py<PyAny>> {
             let py = args.py();
             let mut last_err = None;
             let mut delay = initial_delay;
+            let mut previous_sleep = initial_delay;
 
             for attempt in 0..max_attempts {
-                match func.bind(py).call(args, kwargs) {
-                    Ok(res) => return Ok(res.unbind()),
+                match run_attempt(func.bind(py), args, kwargs, attempt + 1) {
+                    Ok(res) => {
+                        record_retry_success(&func_name);
+                        return Ok(res.unbind());
+                    }
+                    Err(e) if !filter.should_retry(py, &e) => {
+                        record_retry_failure(&func_name, &e, None);
+                        hooks.giving_up(py, &func_name, &e);
+                        return Err(e);
+                    }
                     Err(e) => {
-                        println!("Attempt {}/{} failed: {:?}", attempt + 1, max_attempts, e.to_string());
-                        last_err = Some(e);
+                        // Hooks replace the default console output
+                        if hooks.is_empty() {
+                            println!("Attempt {}/{} failed: {:?}", attempt + 1, max_attempts, e.to_string());
+                        }
 
                         if attempt < max_attempts - 1 {
-                            thread::sleep(Duration::from_secs_f64(delay));
+                            let mut sleep_for = match schedule {
+                                Some(ref schedule) => scheduled_delay(schedule.bind(py), attempt + 1)?,
+                                None => delay,
+                            };
+                            if let Some(jitter) = jitter {
+                                sleep_for = jitter.apply(sleep_for, previous_sleep, initial_delay, max_delay);
+                            }
+                            previous_sleep = sleep_for;
+
+                            if !backoff_fits_deadline(Duration::from_secs_f64(sleep_for)) {
+                                record_retry_failure(&func_name, &e, None);
+                                let exhausted = retry_budget_exhausted(e);
+                                hooks.giving_up(py, &func_name, &exhausted);
+                                return Err(exhausted);
+                            }
+                            record_retry_failure(&func_name, &e, Some(Duration::from_secs_f64(sleep_for)));
+                            hooks.retrying(py, &func_name, attempt + 1, &e, sleep_for);
+                            last_err = Some(e);
+
+                            thread::sleep(Duration::from_secs_f64(sleep_for));
 
                             // Calculate next delay
-                            delay = match backoff_clone.as_str() {
-                                "exponential" => (delay * 2.0).min(max_delay),
-                                "linear" => (delay + initial_delay).min(max_delay),
-                                _ => delay,
-                            };
+                            delay = next_backoff_delay(&backoff_clone, delay, initial_delay, max_delay);
+                        } else {
+                            record_retry_failure(&func_name, &e, None);
+                            hooks.giving_up(py, &func_name, &e);
+                            last_err = Some(e);
                         }
                     }
                 }
@@ -2182,6 +14583,7 @@ fn retry_cached(_py: Python<'_>, max_attempts: usize, cache_failures: bool) -> P
     let factory = move |py: Python<'_>, func: Py<PyAny>| -> PyResult<Py<PyAny>> {
         // Use DashMap for thread-safe caching
         let cache: Arc<DashMap<String, PyResult<Py<PyAny>>>> = Arc::new(DashMap::new());
+        let func_name = callable_name(func.bind(py));
 
         let wrapper = move |args: &Bound<'_, PyTuple>,
                             kwargs: Option<&Bound<'_, PyDict>>|
@@ -2191,11 +14593,11 @@ fn retry_cached(_py: Python<'_>, max_attempts: usize, cache_failures: bool) -> P
             // Create cache key
             let mut key_parts: Vec<String> = vec![];
             for arg in args.iter() {
-                key_parts.push(arg.repr()?.to_str()?.to_string());
+                key_parts.push(arg.repr()?.to_cow()?.into_owned());
             }
             if let Some(kwargs_dict) = kwargs {
                 for (key, val) in kwargs_dict.iter() {
-                    key_parts.push(format!("{}={}", key, val.repr()?.to_str()?));
+                    key_parts.push(format!("{}={}", key, val.repr()?.to_cow()?));
                 }
             }
             let key = key_parts.join(",");
@@ -2229,8 +14631,9 @@ fn retry_cached(_py: Python<'_>, max_attempts: usize, cache_failures: bool) -> P
             // Retry logic with caching
             let mut last_err = None;
             for attempt in 0..max_attempts {
-                match func.bind(py).call(args, kwargs) {
+                match run_attempt(func.bind(py), args, kwargs, attempt + 1) {
                     Ok(res) => {
+                        record_retry_success(&func_name);
                         let result = res.unbind();
                         // Cache success
                         cache.insert(key.clone(), Ok(result.clone_ref(py)));
@@ -2239,10 +14642,18 @@ fn retry_cached(_py: Python<'_>, max_attempts: usize, cache_failures: bool) -> P
                     }
                     Err(e) => {
                         println!("✗ Attempt {}/{} failed: {}", attempt + 1, max_attempts, e);
+                        let delay = Duration::from_millis(100 * (attempt + 1) as u64);
+                        let will_retry = attempt < max_attempts - 1;
+
+                        if will_retry && !backoff_fits_deadline(delay) {
+                            record_retry_failure(&func_name, &e, None);
+                            return Err(retry_budget_exhausted(e));
+                        }
+                        record_retry_failure(&func_name, &e, will_retry.then_some(delay));
                         last_err = Some(e);
 
                         if attempt < max_attempts - 1 {
-                            thread::sleep(Duration::from_millis(100 * (attempt + 1) as u64));
+                            thread::sleep(delay);
                         }
                     }
                 }
@@ -2534,7 +14945,7 @@ mod tests {
         let duration_ms = 100.0;
 
         // Record successful execution
-        record_task_execution(func_name, duration_ms, true);
+        record_task_execution(func_name, duration_ms, true, None);
 
         // Verify counters
         assert_eq!(TASK_COUNTER.load(Ordering::SeqCst), 1);
@@ -2542,12 +14953,22 @@ mod tests {
         assert_eq!(FAILED_COUNTER.load(Ordering::SeqCst), 0);
 
         // Record failed execution
-        record_task_execution(func_name, duration_ms, false);
+        record_task_execution(func_name, duration_ms, false, None);
 
         assert_eq!(TASK_COUNTER.load(Ordering::SeqCst), 2);
         assert_eq!(COMPLETED_COUNTER.load(Ordering::SeqCst), 1);
         assert_eq!(FAILED_COUNTER.load(Ordering::SeqCst), 1);
 
+        // CPU time averages over the tasks that measured it
+        record_task_execution(func_name, duration_ms, true, Some(40.0));
+        record_task_execution(func_name, duration_ms, true, Some(20.0));
+        {
+            let metrics = METRICS.lock();
+            let metric = &metrics[func_name];
+            assert_eq!(metric.total_cpu_time_ms, 60.0);
+            assert_eq!(metric.average_cpu_time_ms, 30.0);
+        }
+
         // Clean up
         reset_metrics().unwrap();
     }
@@ -2568,69 +14989,515 @@ mod tests {
         assert!(check_memory_ok());
 
         // Set memory limit
-        configure_memory_limit(75.0).unwrap();
+        configure_memory_limit(Some(75.0), "system", None).unwrap();
 
         // Still returns true (actual memory checking not implemented)
         assert!(check_memory_ok());
+
+        // A process-scoped cap compares only this process's resident memory
+        configure_memory_limit(None, "process", Some(1)).unwrap();
+        assert!(!check_memory_ok());
+        configure_memory_limit(None, "process", Some(u64::MAX)).unwrap();
+        assert!(check_memory_ok());
+        assert!(configure_memory_limit(None, "system", Some(1)).is_err());
+        configure_memory_limit(None, "system", None).unwrap();
+        assert!(MEMORY_LIMIT.lock().is_none());
+    }
+
+    #[test]
+    fn test_load_limit_hysteresis() {
+        // Starts pause only above the limit...
+        assert!(!over_load_limit(80.0, 80.0, false));
+        assert!(over_load_limit(81.0, 80.0, false));
+        // ...and stay paused until usage drops 10% below it
+        assert!(over_load_limit(75.0, 80.0, true));
+        assert!(!over_load_limit(71.0, 80.0, true));
+
+        assert!(configure_load_limit(Some(0.0), None).is_err());
+        assert!(configure_load_limit(None, Some(150.0)).is_err());
+        configure_load_limit(None, None).unwrap();
+        assert!(check_load_ok());
+    }
+
+    #[test]
+    fn test_capacity_ledger_fits() {
+        let mut ledger = CapacityLedger {
+            cpu_capacity: Some(4.0),
+            memory_mb_capacity: Some(1024.0),
+            ..Default::default()
+        };
+        let big = TaskCost { cpu: 8.0, memory_mb: 0.0 };
+        let small = TaskCost { cpu: 1.0, memory_mb: 256.0 };
+
+        // An oversized task runs alone rather than never
+        assert!(ledger.fits(big));
+
+        ledger.in_use = TaskCost { cpu: 3.0, memory_mb: 512.0 };
+        ledger.running = 2;
+        assert!(ledger.fits(small));
+        assert!(!ledger.fits(small.add(small)));
+        assert!(!ledger.fits(big));
+
+        ledger.cpu_capacity = None;
+        assert!(ledger.fits(TaskCost { cpu: 100.0, memory_mb: 512.0 }));
+    }
+
+    #[test]
+    fn test_simulation_admission() {
+        let task = |arrival, duration, priority, cpu| SimTask {
+            arrival,
+            duration,
+            priority,
+            cost: TaskCost { cpu, memory_mb: 0.0 },
+        };
+
+        // Two slots, three tasks at once: the third waits for the first to finish
+        let tasks = [task(0.0, 1.0, 0, 0.0), task(0.0, 2.0, 0, 0.0), task(0.0, 1.0, 0, 0.0)];
+        let report = run_simulation(&tasks, Some(2), CapacityLedger::default());
+        assert_eq!(report.delays, vec![0.0, 0.0, 1.0]);
+        assert_eq!((report.peak_concurrency, report.peak_queue_length), (2, 1));
+        assert_eq!(report.makespan, 2.0);
+
+        // Higher priority jumps the queue; a small task starts while a big one waits for room
+        let tasks = [task(0.0, 1.0, 0, 3.0), task(0.5, 1.0, 0, 3.0), task(0.5, 1.0, 0, 1.0), task(0.5, 1.0, 5, 2.0)];
+        let ledger = CapacityLedger { cpu_capacity: Some(4.0), ..Default::default() };
+        let report = run_simulation(&tasks, None, ledger);
+        assert_eq!(report.delays, vec![0.0, 1.5, 0.0, 0.5]);
+        assert_eq!(report.makespan, 3.0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shared_cache_roundtrip() {
+        let path = std::env::temp_dir().join(format!("makeparallel-test-{}.cache", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let cache = SharedCache::open(&path, 64 * 1024, 256).unwrap();
+        assert_eq!(cache.get(b"missing"), None);
+        assert!(cache.insert(b"key", b"value"));
+        assert!(cache.insert(b"key", b"updated"));
+        assert_eq!(cache.get(b"key"), Some(b"updated".to_vec()));
+        assert!(!cache.insert(b"big", &[0u8; 512]));
+
+        // A second mapping of the file sees the same entries and keeps its layout
+        let reopened = SharedCache::open(&path, 1024 * 1024, 4096).unwrap();
+        assert_eq!(reopened.slot_size, 256);
+        assert_eq!(reopened.get(b"key"), Some(b"updated".to_vec()));
+
+        drop((cache, reopened));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_next_backoff_delay() {
+        // Deterministic presets
+        assert_eq!(next_backoff_delay("exponential", 1.0, 1.0, 60.0), 2.0);
+        assert_eq!(next_backoff_delay("exponential", 40.0, 1.0, 60.0), 60.0);
+        assert_eq!(next_backoff_delay("linear", 3.0, 1.0, 60.0), 4.0);
+        assert_eq!(next_backoff_delay("constant", 3.0, 1.0, 60.0), 3.0);
+
+        // Decorrelated jitter stays between the base delay and 3x the last delay
+        for _ in 0..100 {
+            let delay = next_backoff_delay("decorrelated_jitter", 2.0, 0.5, 60.0);
+            assert!((0.5..=6.0).contains(&delay));
+        }
+
+        // ...and never exceeds the cap
+        for _ in 0..100 {
+            assert!(next_backoff_delay("decorrelated_jitter", 50.0, 1.0, 60.0) <= 60.0);
+        }
+    }
+
+    #[test]
+    fn test_item_seed() {
+        // First SplitMix64 output for seed 0
+        assert_eq!(item_seed(0, 0), 0xE220_A839_7B1D_CDAF);
+        assert_eq!(item_seed(42, 7), item_seed(42, 7));
+
+        let seeds: std::collections::HashSet<u64> = (0..10_000).map(|index| item_seed(42, index)).collect();
+        assert_eq!(seeds.len(), 10_000);
+        assert_ne!(item_seed(42, 0), item_seed(43, 0));
+    }
+
+    #[test]
+    fn test_autoscaling_decide() {
+        let config = Autoscaling {
+            min_threads: 1,
+            max_threads: 4,
+            queue_per_thread: 2,
+            max_wait: Duration::from_millis(100),
+            scale_down_after: Duration::from_secs(1),
+        };
+        let start = Instant::now();
+        let mut quiet = None;
+
+        // Deep queue jumps to what it needs, capped at max_threads
+        assert_eq!(config.decide(1, 6, Duration::ZERO, &mut quiet, start), 3);
+        assert_eq!(config.decide(3, 50, Duration::ZERO, &mut quiet, start), 4);
+        // A stale task adds one even with a short queue
+        assert_eq!(config.decide(2, 1, Duration::from_millis(200), &mut quiet, start), 3);
+
+        // Between the thresholds: hold
+        assert_eq!(config.decide(3, 5, Duration::ZERO, &mut quiet, start), 3);
+        assert!(quiet.is_none());
+
+        // Quiet queue shrinks by one only after scale_down_after
+        assert_eq!(config.decide(3, 0, Duration::ZERO, &mut quiet, start), 3);
+        assert_eq!(config.decide(3, 0, Duration::ZERO, &mut quiet, start + Duration::from_millis(500)), 3);
+        assert_eq!(config.decide(3, 0, Duration::ZERO, &mut quiet, start + Duration::from_secs(1)), 2);
+        assert_eq!(config.decide(2, 0, Duration::ZERO, &mut quiet, start + Duration::from_millis(1500)), 2);
+        assert_eq!(config.decide(1, 0, Duration::ZERO, &mut quiet, start + Duration::from_secs(10)), 1);
+    }
+
+    #[test]
+    fn test_aimd_limit() {
+        let mut aimd = AimdLimit {
+            limit: 10,
+            min_limit: 2,
+            max_limit: 12,
+            latency_threshold_ms: Some(100.0),
+            decrease_on_failure: true,
+            backoff_ratio: 0.5,
+            increases: 0,
+            decreases: 0,
+        };
+        // Fast successes only raise the limit while it is at least half used
+        aimd.on_sample(10.0, true, 2);
+        assert_eq!(aimd.limit, 10);
+        aimd.on_sample(10.0, true, 5);
+        aimd.on_sample(10.0, true, 6);
+        aimd.on_sample(10.0, true, 6);
+        assert_eq!(aimd.limit, 12);
+
+        aimd.on_sample(500.0, true, 12);
+        assert_eq!(aimd.limit, 6);
+        aimd.on_sample(10.0, false, 6);
+        aimd.on_sample(10.0, false, 3);
+        assert_eq!(aimd.limit, 2);
+        assert_eq!((aimd.increases, aimd.decreases), (2, 3));
+
+        aimd.decrease_on_failure = false;
+        aimd.on_sample(10.0, false, 2);
+        assert_eq!(aimd.limit, 3);
+    }
+
+    #[test]
+    fn test_cgroup_parsing() {
+        // Memory limits: v2 says "max", v1 a huge number
+        assert_eq!(cgroup::parse_memory_limit("536870912\n"), Some(536870912));
+        assert_eq!(cgroup::parse_memory_limit("max\n"), None);
+        assert_eq!(cgroup::parse_memory_limit("9223372036854771712\n"), None);
+
+        // CPU quotas in cores
+        assert_eq!(cgroup::parse_cpu_max("150000 100000\n"), Some(1.5));
+        assert_eq!(cgroup::parse_cpu_max("max 100000\n"), None);
+        assert_eq!(cgroup::cpu_quota(-1, 100000), None);
+        assert_eq!(cgroup::cpu_quota(200000, 100000), Some(2.0));
+
+        let stat = "cache 4096\ninactive_file 1024\ntotal_inactive_file 2048\n";
+        assert_eq!(cgroup::parse_stat(stat, "inactive_file"), Some(1024));
+        assert_eq!(cgroup::parse_stat(stat, "total_inactive_file"), Some(2048));
+        assert_eq!(cgroup::parse_stat(stat, "active_file"), None);
+    }
+
+    #[test]
+    fn test_control_commands() {
+        let reply = |request: &str| control_command(&serde_json::from_str(request).unwrap());
+
+        assert_eq!(reply(r#"{"command": "list"}"#)["ok"], true);
+        assert_eq!(reply(r#"{"command": "cancel", "tag": "no-such-tag"}"#)["cancelled"], 0);
+        // Exactly one of task_id and tag selects what to cancel
+        assert_eq!(reply(r#"{"command": "cancel"}"#)["ok"], false);
+        assert_eq!(reply(r#"{"command": "cancel", "task_id": "task_1", "tag": "x"}"#)["ok"], false);
+        // Pausing a single task only finds active ones
+        assert_eq!(reply(r#"{"command": "pause", "task_id": "no-such-task"}"#)["found"], false);
+        assert_eq!(reply(r#"{"command": "resume", "task_id": "no-such-task"}"#)["found"], false);
+        assert_eq!(reply(r#"{"command": "reboot"}"#)["ok"], false);
+        assert_eq!(reply(r#"{}"#)["ok"], false);
+    }
+
+    #[test]
+    fn test_persistence_sealing() {
+        let key = |byte: u8| LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &[byte; 32]).unwrap());
+        let sealed = seal(&key(1), b"item 3", b"result").unwrap();
+        assert_eq!(sealed.len(), b"result".len() + SEALED_OVERHEAD);
+        assert_eq!(unseal(&key(1), b"item 3", &sealed).as_deref(), Some(&b"result"[..]));
+
+        // The key, the context and every byte are authenticated
+        assert_eq!(unseal(&key(2), b"item 3", &sealed), None);
+        assert_eq!(unseal(&key(1), b"item 4", &sealed), None);
+        let mut tampered = sealed.clone();
+        tampered[NONCE_LEN] ^= 1;
+        assert_eq!(unseal(&key(1), b"item 3", &tampered), None);
+        assert_eq!(unseal(&key(1), b"item 3", &sealed[..4]), None);
+
+        // Fresh nonces make sealing the same data twice differ
+        assert_ne!(seal(&key(1), b"item 3", b"result").unwrap(), sealed);
+    }
+
+    #[test]
+    fn test_checkpoint_records_bound_to_file() {
+        let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &[7; 32]).unwrap());
+        let (ours, theirs) = ([1u8; CHECKPOINT_FILE_ID_LEN], [2u8; CHECKPOINT_FILE_ID_LEN]);
+        let mut data = encode_checkpoint_record(Some(&key), &ours, 0, b"first".to_vec()).unwrap();
+        let own_len = data.len();
+
+        // Records spliced in from another checkpoint sealed with the same key are
+        // rejected, whether or not their index is one this file already has
+        data.extend(encode_checkpoint_record(Some(&key), &theirs, 1, b"spliced".to_vec()).unwrap());
+        data.extend(encode_checkpoint_record(Some(&key), &theirs, 0, b"replaced".to_vec()).unwrap());
+        let (records, end) = decode_checkpoint_records(Some(&key), &ours, &data);
+        assert_eq!(records, vec![(0, Some(b"first".to_vec())), (1, None), (0, None)]);
+        assert_eq!(end, data.len());
+
+        // A torn record at the end is left out
+        let (records, end) = decode_checkpoint_records(Some(&key), &ours, &data[..own_len + 20]);
+        assert_eq!(records.len(), 1);
+        assert_eq!(end, own_len);
+    }
+
+    #[test]
+    fn test_private_dirs() {
+        let base = std::env::temp_dir().join(format!("makeparallel-test-{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+
+        let path = new_private_dir(&base, "workspace").unwrap();
+        assert!(path.is_dir());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+
+        // A directory someone else made first is never adopted
+        let err = create_private_dir(&path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_ne!(new_private_dir(&base, "workspace").unwrap(), path);
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_batch_tuner() {
+        let mut tuner = BatchTuner::new(1000, 4);
+        assert_eq!(tuner.next_batch(), Some(0..1));
+        assert_eq!(tuner.next_batch(), Some(1..2));
+
+        // Cheap items: batches double rather than jumping straight to the target
+        tuner.record(1, Duration::from_micros(10));
+        assert_eq!(tuner.next_batch(), Some(2..4));
+        assert_eq!(tuner.next_batch(), Some(4..8));
+
+        // Expensive items: back to one at a time
+        tuner.record(4, Duration::from_secs(1));
+        assert_eq!(tuner.next_batch().map(|b| b.len()), Some(1));
+
+        // Near the end, batches leave work for every worker
+        let mut tuner = BatchTuner::new(16, 4);
+        tuner.next_batch();
+        tuner.record(1, Duration::from_nanos(1));
+        tuner.batch = 1000;
+        assert_eq!(tuner.next_batch(), Some(1..3));
+        while tuner.next_batch().is_some() {}
+        assert_eq!(tuner.next, 16);
     }
 }
 
 /// This module is implemented in Rust.
-#[pymodule]
+#[pymodule(gil_used = false)]
 fn makeparallel(m: &Bound<'_, PyModule>) -> PyResult<()> {
     // Initialize logging (only once)
     let _ = env_logger::try_init();
 
+    // Init only runs again in the same process after the interpreter was finalized and
+    // started anew (embedding hosts); state owned by the old interpreter must not be reused
+    if MODULE_INITIALIZED.swap(true, Ordering::AcqRel) {
+        warn!("makeparallel initialized again in this process; discarding state of the previous interpreter");
+        reset_runtime(None);
+    }
+
+    // The module is safe without a GIL, so importing it keeps free-threaded interpreters GIL-free
+    FREE_THREADED.store(detect_free_threading(m.py()), Ordering::Relaxed);
+    SYNC_FALLBACK.store(detect_sync_fallback(), Ordering::Relaxed);
+    if sync_fallback() {
+        warn!("threads are unavailable on this platform; tasks will run synchronously");
+    }
+
     // Original decorators
     m.add_function(wrap_pyfunction!(timer, m)?)?;
     m.add_class::<CallCounter>()?;
     m.add_function(wrap_pyfunction!(retry, m)?)?;
     m.add_function(wrap_pyfunction!(memoize, m)?)?;
+    m.add_function(wrap_pyfunction!(singleflight, m)?)?;
     m.add_function(wrap_pyfunction!(parallel, m)?)?;
+    m.add_function(wrap_pyfunction!(run_in_worker, m)?)?;
+    m.add_function(wrap_pyfunction!(bind, m)?)?;
+    m.add_class::<BoundCall>()?;
     m.add_class::<AsyncHandle>()?;
+    m.add_class::<TaskStatus>()?;
+    m.add_class::<LazyResult>()?;
+    m.add_class::<ResultStream>()?;
+    m.add_class::<AsyncResultStream>()?;
 
     // Optimized versions
     m.add_function(wrap_pyfunction!(parallel_fast, m)?)?;
     m.add_function(wrap_pyfunction!(parallel_pool, m)?)?;
     m.add_function(wrap_pyfunction!(memoize_fast, m)?)?;
+    m.add_function(wrap_pyfunction!(warm_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(configure_persistence, m)?)?;
+    m.add_function(wrap_pyfunction!(cache_dump, m)?)?;
+    m.add_function(wrap_pyfunction!(cache_load, m)?)?;
+    m.add_function(wrap_pyfunction!(memoize_shared, m)?)?;
     m.add_function(wrap_pyfunction!(parallel_map, m)?)?;
+    m.add_function(wrap_pyfunction!(task_random, m)?)?;
+    m.add_function(wrap_pyfunction!(parallel_starmap, m)?)?;
+    m.add_function(wrap_pyfunction!(parallel_process, m)?)?;
+    m.add_class::<ParallelProcessWrapper>()?;
+    m.add_class::<ProcessPool>()?;
+    m.add_class::<Pool>()?;
+    #[cfg(feature = "durable-queue")]
+    m.add_class::<DurableQueue>()?;
+    m.add_function(wrap_pyfunction!(prefetch, m)?)?;
+    m.add_function(wrap_pyfunction!(parallel_imap, m)?)?;
+    m.add_function(wrap_pyfunction!(parallel_filter, m)?)?;
+    m.add_function(wrap_pyfunction!(parallel_reduce, m)?)?;
+    m.add_class::<PrefetchIterator>()?;
     m.add_class::<AsyncHandleFast>()?;
 
     // Thread pool configuration
     m.add_function(wrap_pyfunction!(configure_thread_pool, m)?)?;
     m.add_function(wrap_pyfunction!(get_thread_pool_info, m)?)?;
+    m.add_function(wrap_pyfunction!(is_free_threaded, m)?)?;
+    m.add_function(wrap_pyfunction!(get_execution_mode, m)?)?;
 
     // Priority queue
     m.add_function(wrap_pyfunction!(parallel_priority, m)?)?;
     m.add_function(wrap_pyfunction!(start_priority_worker, m)?)?;
     m.add_function(wrap_pyfunction!(stop_priority_worker, m)?)?;
+    m.add_function(wrap_pyfunction!(configure_autoscaling, m)?)?;
+    m.add_function(wrap_pyfunction!(get_autoscaling_stats, m)?)?;
+    m.add_function(wrap_pyfunction!(configure_idle_timeout, m)?)?;
+    m.add_function(wrap_pyfunction!(shrink_pools, m)?)?;
+    m.add_function(wrap_pyfunction!(reprioritize, m)?)?;
+    m.add_function(wrap_pyfunction!(configure_priority_classes, m)?)?;
+    m.add_function(wrap_pyfunction!(get_priority_class_stats, m)?)?;
 
     // Performance profiling
     m.add_function(wrap_pyfunction!(profiled, m)?)?;
     m.add_function(wrap_pyfunction!(get_metrics, m)?)?;
     m.add_function(wrap_pyfunction!(get_all_metrics, m)?)?;
     m.add_function(wrap_pyfunction!(reset_metrics, m)?)?;
+    m.add_function(wrap_pyfunction!(set_metric_labels, m)?)?;
+    m.add_function(wrap_pyfunction!(get_metric_labels, m)?)?;
+    m.add_function(wrap_pyfunction!(export_metrics, m)?)?;
     m.add_class::<PerformanceMetrics>()?;
 
     // Error handling and shutdown
     m.add_class::<TaskError>()?;
+    m.add("TaskTimeout", m.py().get_type::<TaskTimeout>())?;
+    m.add("TaskExecutionFailed", m.py().get_type::<TaskExecutionFailed>())?;
+    m.add("TaskCancelled", m.py().get_type::<TaskCancelled>())?;
+    m.add("StreamClosed", m.py().get_type::<StreamClosed>())?;
+    m.add("DeadlineExceeded", m.py().get_type::<DeadlineExceeded>())?;
+    m.add("BulkheadFull", m.py().get_type::<BulkheadFull>())?;
+    m.add("PoolFull", m.py().get_type::<PoolFull>())?;
     m.add_function(wrap_pyfunction!(shutdown, m)?)?;
     m.add_function(wrap_pyfunction!(reset_shutdown, m)?)?;
+    m.add_function(wrap_pyfunction!(_reinitialize, m)?)?;
     m.add_function(wrap_pyfunction!(get_active_task_count, m)?)?;
+    m.add_function(wrap_pyfunction!(list_tasks, m)?)?;
+    m.add_function(wrap_pyfunction!(get_task_status, m)?)?;
+    m.add_function(wrap_pyfunction!(capture_submission_sites, m)?)?;
+    m.add_function(wrap_pyfunction!(cancel_tasks, m)?)?;
+    m.add_function(wrap_pyfunction!(cancel_all, m)?)?;
 
     // Backpressure and resource management
     m.add_function(wrap_pyfunction!(set_max_concurrent_tasks, m)?)?;
+    m.add_function(wrap_pyfunction!(enable_adaptive_concurrency, m)?)?;
+    m.add_function(wrap_pyfunction!(disable_adaptive_concurrency, m)?)?;
+    m.add_function(wrap_pyfunction!(get_concurrency_limit, m)?)?;
     m.add_function(wrap_pyfunction!(configure_memory_limit, m)?)?;
+    m.add_function(wrap_pyfunction!(configure_watchdog, m)?)?;
+    m.add_function(wrap_pyfunction!(get_watchdog_stats, m)?)?;
+    m.add_function(wrap_pyfunction!(pause_scheduling, m)?)?;
+    m.add_function(wrap_pyfunction!(resume_scheduling, m)?)?;
+    m.add_function(wrap_pyfunction!(start_control_server, m)?)?;
+    m.add_function(wrap_pyfunction!(stop_control_server, m)?)?;
+    m.add_function(wrap_pyfunction!(get_container_limits, m)?)?;
+    m.add_function(wrap_pyfunction!(configure_load_limit, m)?)?;
+    m.add_function(wrap_pyfunction!(configure_gpu_memory_limit, m)?)?;
+    m.add_function(wrap_pyfunction!(configure_capacity, m)?)?;
+    m.add_function(wrap_pyfunction!(get_capacity_usage, m)?)?;
+    m.add_function(wrap_pyfunction!(provide, m)?)?;
+    m.add_function(wrap_pyfunction!(simulate, m)?)?;
 
     // Progress tracking
     m.add_function(wrap_pyfunction!(report_progress, m)?)?;
+    m.add_function(wrap_pyfunction!(emit, m)?)?;
+    m.add_function(wrap_pyfunction!(configure_stream_capacity, m)?)?;
+    m.add_function(wrap_pyfunction!(check_cancelled, m)?)?;
+    m.add_function(wrap_pyfunction!(checkpoint, m)?)?;
+    m.add_function(wrap_pyfunction!(save_checkpoint, m)?)?;
+    m.add_function(wrap_pyfunction!(load_checkpoint, m)?)?;
+    m.add_function(wrap_pyfunction!(clear_checkpoint, m)?)?;
+    m.add_function(wrap_pyfunction!(configure_task_checkpoints, m)?)?;
+    m.add_function(wrap_pyfunction!(current_cancellation_token, m)?)?;
+    m.add_function(wrap_pyfunction!(task_workspace, m)?)?;
+    m.add_function(wrap_pyfunction!(configure_task_workspaces, m)?)?;
+    m.add_class::<CancellationToken>()?;
     m.add_function(wrap_pyfunction!(get_current_task_id, m)?)?;
 
     // Helper functions
     m.add_function(wrap_pyfunction!(gather, m)?)?;
+    m.add_function(wrap_pyfunction!(gather_dict, m)?)?;
+    m.add_function(wrap_pyfunction!(from_future, m)?)?;
+    m.add_function(wrap_pyfunction!(to_future, m)?)?;
+    m.add_function(wrap_pyfunction!(as_completed, m)?)?;
+    m.add_function(wrap_pyfunction!(wait, m)?)?;
+    m.add("FIRST_COMPLETED", FIRST_COMPLETED)?;
+    m.add("FIRST_EXCEPTION", FIRST_EXCEPTION)?;
+    m.add("ALL_COMPLETED", ALL_COMPLETED)?;
+    m.add_class::<Pending>()?;
+    m.add("PENDING", pending_sentinel(m.py())?)?;
+    m.add_class::<AsCompletedIterator>()?;
     m.add_class::<ParallelContext>()?;
+    m.add_class::<TaskGroup>()?;
+    m.add_class::<Saga>()?;
+    m.add_class::<Pipeline>()?;
+    m.add_function(wrap_pyfunction!(actor, m)?)?;
+    m.add_class::<ActorClass>()?;
+    m.add_class::<ActorRef>()?;
+    m.add_class::<ActorMethod>()?;
+    m.add_class::<Topic>()?;
+    m.add_class::<Subscription>()?;
+    m.add_class::<Channel>()?;
+    m.add_class::<Timer>()?;
+    m.add_function(wrap_pyfunction!(select, m)?)?;
+    m.add_function(wrap_pyfunction!(after, m)?)?;
+    m.add_function(wrap_pyfunction!(submit_after, m)?)?;
+    m.add_function(wrap_pyfunction!(interval, m)?)?;
+    m.add_class::<Interval>()?;
+    m.add_class::<MapResult>()?;
+    m.add_class::<Semaphore>()?;
+    m.add_class::<Event>()?;
+    m.add_class::<Barrier>()?;
+    m.add_class::<CountDownLatch>()?;
+    m.add_class::<RwLock>()?;
+    m.add_class::<RwLockGuard>()?;
     m.add_function(wrap_pyfunction!(retry_backoff, m)?)?;
     m.add_function(wrap_pyfunction!(retry_cached, m)?)?;
+    m.add_function(wrap_pyfunction!(bulkhead, m)?)?;
+    m.add_function(wrap_pyfunction!(get_retry_state, m)?)?;
+    m.add_function(wrap_pyfunction!(get_all_retry_states, m)?)?;
+    m.add_function(wrap_pyfunction!(reset_retry_state, m)?)?;
+    m.add_function(wrap_pyfunction!(set_global_error_handler, m)?)?;
+    m.add_function(wrap_pyfunction!(register_webhook, m)?)?;
+    m.add_function(wrap_pyfunction!(unregister_webhook, m)?)?;
+    m.add_function(wrap_pyfunction!(list_webhooks, m)?)?;
+    m.add_function(wrap_pyfunction!(enable_zmq_publisher, m)?)?;
+    m.add_function(wrap_pyfunction!(disable_zmq_publisher, m)?)?;
+    m.add_class::<RetryState>()?;
+    m.add_class::<BulkheadStats>()?;
 
     // Task dependencies
     m.add_function(wrap_pyfunction!(parallel_with_deps, m)?)?;
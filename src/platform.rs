@@ -0,0 +1,206 @@
+//! Platform-specific thread controls
+//!
+//! Worker threads can run at a lower or higher OS scheduling priority so bulk
+//! background tasks yield the CPU to latency-sensitive ones. Each platform maps
+//! the levels onto its own mechanism: the per-thread nice value on Linux, the
+//! POSIX scheduling priority on other Unix systems and `SetThreadPriority` on
+//! Windows.
+//!
+//! Tasks are also charged the CPU time of the thread that ran them, read from the
+//! per-thread CPU clock.
+
+use std::io;
+use std::time::Duration;
+
+use pyo3::prelude::*;
+
+/// OS scheduling priority for a worker thread
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ThreadPriority {
+    Low,
+    Normal,
+    High,
+}
+
+impl ThreadPriority {
+    pub(crate) fn parse(value: &str) -> PyResult<ThreadPriority> {
+        match value {
+            "low" => Ok(ThreadPriority::Low),
+            "normal" => Ok(ThreadPriority::Normal),
+            "high" => Ok(ThreadPriority::High),
+            other => Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "os_priority must be \"low\", \"normal\" or \"high\", got {:?}",
+                other
+            ))),
+        }
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            ThreadPriority::Low => "low",
+            ThreadPriority::Normal => "normal",
+            ThreadPriority::High => "high",
+        }
+    }
+}
+
+/// Move the calling thread to `priority`
+///
+/// `Normal` keeps the priority the thread inherited. Raising it above normal
+/// usually needs elevated privileges (CAP_SYS_NICE on Linux) and fails without them.
+pub(crate) fn set_current_thread_priority(priority: ThreadPriority) -> io::Result<()> {
+    match priority {
+        ThreadPriority::Normal => Ok(()),
+        _ => imp::set_current_thread_priority(priority),
+    }
+}
+
+/// CPU time the calling thread has used so far, None where the platform has no per-thread clock
+pub(crate) fn current_thread_cpu_time() -> Option<Duration> {
+    imp::current_thread_cpu_time()
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::ThreadPriority;
+    use std::io;
+
+    // Nice values; Linux keeps one per thread, addressed by thread id
+    const LOW_NICE: libc::c_int = 10;
+    const HIGH_NICE: libc::c_int = -10;
+
+    pub(super) fn set_current_thread_priority(priority: ThreadPriority) -> io::Result<()> {
+        let nice = if priority == ThreadPriority::High { HIGH_NICE } else { LOW_NICE };
+        // SAFETY: gettid() has no preconditions and cannot fail
+        let tid = unsafe { libc::gettid() };
+        // SAFETY: plain syscall on our own thread id; failure is reported through the return value
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, nice) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    pub(super) use super::unix_thread_cpu_time as current_thread_cpu_time;
+}
+
+#[cfg(unix)]
+fn unix_thread_cpu_time() -> Option<Duration> {
+    // SAFETY: timespec is plain integers, for which all-zero bytes are a valid value
+    let mut now: libc::timespec = unsafe { std::mem::zeroed() };
+    // SAFETY: `now` is a valid, exclusively borrowed timespec for the call to fill in
+    if unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut now) } != 0 {
+        return None;
+    }
+    Some(Duration::new(now.tv_sec as u64, now.tv_nsec as u32))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+mod imp {
+    use super::ThreadPriority;
+    use std::io;
+
+    // setpriority() is per-process here, so move within the thread's scheduling policy instead
+    pub(super) fn set_current_thread_priority(priority: ThreadPriority) -> io::Result<()> {
+        // SAFETY: pthread_self() is always valid for the calling thread, sched_param is plain
+        // integers (zeroed is valid), and both out-pointers are live locals for the calls
+        unsafe {
+            let thread = libc::pthread_self();
+            let mut policy = 0;
+            let mut param: libc::sched_param = std::mem::zeroed();
+            check(libc::pthread_getschedparam(thread, &mut policy, &mut param))?;
+            param.sched_priority = if priority == ThreadPriority::High {
+                libc::sched_get_priority_max(policy)
+            } else {
+                libc::sched_get_priority_min(policy)
+            };
+            check(libc::pthread_setschedparam(thread, policy, &param))
+        }
+    }
+
+    fn check(code: libc::c_int) -> io::Result<()> {
+        if code == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::from_raw_os_error(code))
+        }
+    }
+
+    pub(super) use super::unix_thread_cpu_time as current_thread_cpu_time;
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::ThreadPriority;
+    use std::ffi::c_void;
+    use std::io;
+    use std::time::Duration;
+
+    const THREAD_PRIORITY_BELOW_NORMAL: i32 = -1;
+    const THREAD_PRIORITY_ABOVE_NORMAL: i32 = 1;
+
+    /// 100-nanosecond intervals, split into two 32-bit halves
+    #[repr(C)]
+    #[derive(Default)]
+    struct FileTime {
+        low: u32,
+        high: u32,
+    }
+
+    impl FileTime {
+        fn duration(&self) -> Duration {
+            Duration::from_nanos((((self.high as u64) << 32) | self.low as u64) * 100)
+        }
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentThread() -> *mut c_void;
+        fn SetThreadPriority(thread: *mut c_void, priority: i32) -> i32;
+        fn GetThreadTimes(
+            thread: *mut c_void,
+            creation: *mut FileTime,
+            exit: *mut FileTime,
+            kernel: *mut FileTime,
+            user: *mut FileTime,
+        ) -> i32;
+    }
+
+    pub(super) fn current_thread_cpu_time() -> Option<Duration> {
+        let (mut creation, mut exit) = (FileTime::default(), FileTime::default());
+        let (mut kernel, mut user) = (FileTime::default(), FileTime::default());
+        // SAFETY: GetCurrentThread() returns a pseudo-handle that needs no closing, and all four
+        // out-pointers are live, exclusively borrowed FileTime locals
+        let ok = unsafe { GetThreadTimes(GetCurrentThread(), &mut creation, &mut exit, &mut kernel, &mut user) };
+        (ok != 0).then(|| kernel.duration() + user.duration())
+    }
+
+    pub(super) fn set_current_thread_priority(priority: ThreadPriority) -> io::Result<()> {
+        let level = if priority == ThreadPriority::High {
+            THREAD_PRIORITY_ABOVE_NORMAL
+        } else {
+            THREAD_PRIORITY_BELOW_NORMAL
+        };
+        // SAFETY: the pseudo-handle from GetCurrentThread() is valid for this thread; failure is
+        // reported through the return value
+        if unsafe { SetThreadPriority(GetCurrentThread(), level) } != 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use super::ThreadPriority;
+    use std::io;
+
+    pub(super) fn set_current_thread_priority(_priority: ThreadPriority) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "thread priorities are not supported on this platform"))
+    }
+
+    pub(super) fn current_thread_cpu_time() -> Option<std::time::Duration> {
+        None
+    }
+}
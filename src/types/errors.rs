@@ -57,14 +57,18 @@ pub struct TaskError {
     pub error_type: String,
     #[pyo3(get)]
     pub task_id: String,
+    /// "file:line" the task was submitted from, with capture_submission_sites() on
+    #[pyo3(get)]
+    pub submitted_from: Option<String>,
 }
 
 #[pymethods]
 impl TaskError {
     pub fn __str__(&self) -> String {
+        let site = self.submitted_from.as_ref().map(|site| format!(", submitted from {}", site)).unwrap_or_default();
         format!(
-            "TaskError in '{}' (task_id: {}, elapsed: {}s): {} ({})",
-            self.task_name, self.task_id, self.elapsed_time, 
+            "TaskError in '{}' (task_id: {}, elapsed: {}s{}): {} ({})",
+            self.task_name, self.task_id, self.elapsed_time, site,
             self.error_message, self.error_type
         )
     }
@@ -1,3 +1,3 @@
 pub mod errors;
 
-pub use errors::TaskError;
+pub use errors::{MakeParallelError, TaskError};
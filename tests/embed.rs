@@ -0,0 +1,37 @@
+// Rust embedding API tests (run with: cargo test --no-default-features --features embed)
+#![cfg(feature = "embed")]
+
+use std::time::Duration;
+
+use makeparallel::embed::{self, SubmitOptions};
+use pyo3::prelude::*;
+
+#[test]
+fn test_embed_submit_and_poll() {
+    embed::register();
+    Python::initialize();
+
+    Python::attach(|py| -> PyResult<()> {
+        // The module is importable from Python code running in the host
+        py.import("makeparallel")?;
+
+        let double = py.eval(c"lambda x: x * 2", None, None)?;
+        let handle = embed::submit(py, &double, (21,), &SubmitOptions::default())?;
+        assert!(handle.wait(py, Some(Duration::from_secs(10)))?);
+        assert!(handle.is_ready(py));
+        assert_eq!(handle.get(py)?.extract::<i32>(py)?, 42);
+
+        let options = SubmitOptions { priority: Some(5), tags: vec!["host".into()], ..Default::default() };
+        let handle = embed::submit(py, &double, ("ab",), &options)?;
+        assert_eq!(handle.get(py)?.extract::<String>(py)?, "abab");
+
+        let fail = py.eval(c"lambda: 1 / 0", None, None)?;
+        let handle = embed::submit(py, &fail, (), &SubmitOptions::default())?;
+        let err = handle.get(py).unwrap_err();
+        assert!(err.to_string().contains("ZeroDivisionError"));
+
+        assert!(embed::shutdown(Duration::from_secs(5), false)?);
+        Ok(())
+    })
+    .unwrap();
+}
@@ -70,7 +70,7 @@ fn test_thread_local_isolation() {
     use std::cell::RefCell;
 
     thread_local! {
-        static TEST_VAR: RefCell<Option<String>> = RefCell::new(None);
+        static TEST_VAR: RefCell<Option<String>> = const { RefCell::new(None) };
     }
 
     let (tx1, rx1) = std::sync::mpsc::channel();
@@ -308,6 +308,372 @@ def test_parallel_args_kwargs(t):
 # =============================================================================
 # TEST 7: Parallel Fast (Crossbeam)
 # =============================================================================
+@runner.test("Parallel - as_completed() completion order")
+def test_parallel_as_completed(t):
+    @mp.parallel
+    def sleep_then_return(secs):
+        time.sleep(secs)
+        return secs
+
+    handles = [sleep_then_return(s) for s in (0.3, 0.1, 0.2)]
+    order = [h.get() for h in mp.as_completed(handles)]
+    t.assert_equal(order, [0.1, 0.2, 0.3])
+
+    # Already-finished handles are yielded straight away
+    t.assert_equal(len(list(mp.as_completed(handles))), 3)
+
+    slow = sleep_then_return(1.0)
+    fast = sleep_then_return(0.05)
+    it = mp.as_completed([slow, fast], timeout=0.3)
+    t.assert_equal(next(it).get(), 0.05)
+    t.assert_raises(TimeoutError, lambda: next(it))
+    slow.cancel()
+
+
+@runner.test("Parallel - wait() with return_when")
+def test_parallel_wait(t):
+    @mp.parallel
+    def sleep_then_return(secs):
+        time.sleep(secs)
+        if secs < 0.1:
+            raise ValueError("too fast")
+        return secs
+
+    slow, medium, failing = sleep_then_return(0.6), sleep_then_return(0.2), sleep_then_return(0.05)
+
+    done, not_done = mp.wait([slow, medium, failing], return_when=mp.FIRST_EXCEPTION)
+    t.assert_true(failing in done and slow in not_done)
+
+    start = time.time()
+    done, not_done = mp.wait([slow, medium], return_when=mp.FIRST_COMPLETED)
+    t.assert_true(medium in done and slow in not_done)
+    t.assert_true(time.time() - start < 0.5)
+
+    done, not_done = mp.wait([slow, medium], timeout=0.05)
+    t.assert_equal(len(not_done), 1)
+
+    done, not_done = mp.wait([slow, medium, failing])
+    t.assert_equal((len(done), len(not_done)), (3, 0))
+    t.assert_raises(ValueError, lambda: mp.wait([slow], return_when="SOMETIMES"))
+
+    # Per-handle wait() returns on completion and keeps the result
+    handle = sleep_then_return(0.1)
+    start = time.time()
+    t.assert_true(handle.wait(5.0))
+    t.assert_true(time.time() - start < 1.0)
+    t.assert_equal(handle.get(), 0.1)
+
+
+@runner.test("Parallel - then() and catch() chaining")
+def test_parallel_then_catch(t):
+    @mp.parallel
+    def fetch(x):
+        time.sleep(0.05)
+        if x < 0:
+            raise ValueError("negative input")
+        return x
+
+    seen = []
+    # The continuation runs without anyone calling get() on the parent
+    fetch(2).then(lambda v: v * 10).then(seen.append)
+    deadline = time.time() + 2
+    while not seen and time.time() < deadline:
+        time.sleep(0.02)
+    t.assert_equal(seen, [20])
+
+    chained = fetch(3).then(lambda v: v + 1).catch(lambda e: -1)
+    t.assert_equal(chained.get(), 4)
+
+    recovered = fetch(-1).then(lambda v: v + 1).catch(lambda e: f"recovered: {e}")
+    value = recovered.get()
+    t.assert_true(value.startswith("recovered:") and "negative input" in value)
+
+    failed = fetch(-1).then(lambda v: v + 1)
+    t.assert_raises(RuntimeError, failed.get)
+
+    broken = fetch(1).then(lambda v: v / 0)
+    t.assert_raises(RuntimeError, broken.get)
+    t.assert_raises(TypeError, lambda: fetch(1).then(42))
+
+
+@runner.test("Parallel - submit_after() success and failure branches")
+def test_submit_after(t):
+    @mp.parallel
+    def fetch(x):
+        time.sleep(0.05)
+        if x < 0:
+            raise ValueError("negative input")
+        return x
+
+    calls = []
+
+    def store(value):
+        calls.append("store")
+        return value * 2
+
+    def alert(error):
+        calls.append("alert")
+        return f"alerted: {error}"
+
+    t.assert_equal(mp.submit_after(fetch(5), on_success=store, on_failure=alert).get(), 10)
+    alerted = mp.submit_after(fetch(-1), on_success=store, on_failure=alert).get()
+    t.assert_true(alerted.startswith("alerted:") and "negative input" in alerted)
+    t.assert_equal(calls, ["store", "alert"])
+
+    # A missing branch passes the outcome through
+    t.assert_equal(mp.submit_after(fetch(3), on_failure=alert).get(), 3)
+    t.assert_raises(RuntimeError, mp.submit_after(fetch(-1), on_success=store).get)
+    t.assert_equal(calls, ["store", "alert"])
+
+    t.assert_raises(ValueError, lambda: mp.submit_after(fetch(1)))
+    t.assert_raises(TypeError, lambda: mp.submit_after(fetch(1), on_success=42))
+
+
+@runner.test("Parallel - lazy=True result proxies")
+def test_parallel_lazy(t):
+    @mp.parallel
+    def compute(x):
+        time.sleep(0.1)
+        return x
+
+    start = time.time()
+    number = compute(21, lazy=True)
+    text = compute("hello", lazy=True)
+    items = compute([3, 1, 2], lazy=True)
+    t.assert_true(time.time() - start < 0.1, "lazy calls should not block")
+
+    # Used like the real values without any handle awareness
+    t.assert_equal(number * 2, 42)
+    t.assert_equal(2 + number, 23)
+    t.assert_true(number == 21 and number > 20)
+    t.assert_true(isinstance(number, int))
+    t.assert_equal(text.upper(), "HELLO")
+    t.assert_equal(f"{text}!", "hello!")
+    t.assert_equal(sorted(items), [1, 2, 3])
+    t.assert_equal(len(items), 3)
+    t.assert_true(2 in items and items[0] == 3)
+
+    @mp.parallel
+    def broken():
+        raise ValueError("lazy failure")
+
+    failing = broken(lazy=True)
+    t.assert_raises(RuntimeError, lambda: failing + 1)
+
+
+@runner.test("gather() on_error='group'")
+def test_gather_exception_group(t):
+    @mp.parallel
+    def check(x):
+        if x % 2:
+            raise ValueError(f"odd {x}")
+        return x
+
+    handles = [check(i) for i in range(5)]
+    try:
+        mp.gather(handles, on_error="group")
+        t.assert_true(False, "gather() did not raise")
+    except ExceptionGroup as group:
+        t.assert_equal(len(group.exceptions), 2)
+        failed_ids = [exc.task_id for exc in group.exceptions]
+        t.assert_equal(failed_ids, [handles[1].get_task_id(), handles[3].get_task_id()])
+        t.assert_true("2 of 5" in str(group))
+
+    t.assert_equal(mp.gather([check(0), check(2)], on_error="group"), [0, 2])
+
+
+@runner.test("gather() timeout and partial results")
+def test_gather_timeout_partial(t):
+    @mp.parallel
+    def sleepy(seconds):
+        time.sleep(seconds)
+        return seconds
+
+    handles = [sleepy(0.01), sleepy(1.0), sleepy(0.02)]
+    start = time.time()
+    results = mp.gather(handles, timeout=0.3, return_partial=True)
+    t.assert_true(time.time() - start < 0.8, "gather() waited past its timeout")
+    t.assert_equal(results[0], 0.01)
+    t.assert_true(results[1] is mp.PENDING)
+    t.assert_equal(results[2], 0.02)
+    t.assert_true(not mp.PENDING)
+
+    t.assert_raises(TimeoutError, lambda: mp.gather([sleepy(1.0)], timeout=0.1))
+    t.assert_equal(mp.gather([sleepy(0.01)], timeout=5), [0.01])
+
+
+@runner.test("gather_dict() named results")
+def test_gather_dict(t):
+    @mp.parallel
+    def fetch(kind):
+        if kind == "broken":
+            raise ValueError("unavailable")
+        return f"{kind}-data"
+
+    results = mp.gather_dict({"users": fetch("users"), "orders": fetch("orders")})
+    t.assert_equal(results, {"users": "users-data", "orders": "orders-data"})
+
+    named = {"users": fetch("users"), "audit": fetch("broken")}
+    t.assert_equal(mp.gather_dict(named, on_error="skip"), {"users": "users-data"})
+    t.assert_equal(mp.gather_dict(named, on_error="none"), {"users": "users-data", "audit": None})
+    t.assert_raises(RuntimeError, lambda: mp.gather_dict(named))
+
+
+@runner.test("from_future() and to_future() bridging")
+def test_futures_bridging(t):
+    from concurrent.futures import ThreadPoolExecutor
+
+    @mp.parallel
+    def double(x):
+        time.sleep(0.05)
+        return x * 2
+
+    def fail():
+        raise KeyError("missing")
+
+    with ThreadPoolExecutor(max_workers=2) as executor:
+        future = executor.submit(lambda: 21)
+        handle = mp.from_future(future)
+        t.assert_equal(handle.get(), 21)
+        t.assert_raises(RuntimeError, mp.from_future(executor.submit(fail)).get)
+
+        # Handles and futures mix in gather/wait/as_completed
+        slow_future = executor.submit(lambda: (time.sleep(0.05), 5)[1])
+        t.assert_equal(mp.gather([double(1), slow_future, double(3)]), [2, 5, 6])
+        pending = executor.submit(time.sleep, 0.05)
+        done, not_done = mp.wait([double(2), pending])
+        t.assert_true(pending in done and len(done) == 2 and not not_done)
+        mixed = [executor.submit(lambda: 1), double(4)]
+        t.assert_equal(len(list(mp.as_completed(mixed, timeout=5))), 2)
+        t.assert_raises(TypeError, lambda: mp.gather([42]))
+
+        converted = mp.to_future(double(10))
+        t.assert_equal(converted.result(timeout=5), 20)
+
+        @mp.parallel
+        def broken():
+            raise ValueError("bad")
+
+        failed = mp.to_future(broken())
+        t.assert_true(failed.exception(timeout=5) is not None)
+
+        # With an executor, done callbacks run on its threads
+        import threading
+        callback_threads = []
+        via_executor = mp.to_future(double(1), executor=executor)
+        via_executor.add_done_callback(lambda f: callback_threads.append(threading.current_thread().name))
+        t.assert_equal(via_executor.result(timeout=5), 2)
+        time.sleep(0.05)
+        t.assert_true(callback_threads and callback_threads[0].startswith("ThreadPoolExecutor"), str(callback_threads))
+
+    @mp.parallel
+    def long_task():
+        for _ in range(50):
+            time.sleep(0.02)
+        return "finished"
+
+    handle = long_task()
+    future = mp.to_future(handle)
+    t.assert_true(future.cancel())
+    t.assert_true(handle.is_cancelled())
+
+
+@runner.test("Subinterpreter isolation")
+def test_subinterpreter_isolation(t):
+    import functools
+    import math
+
+    try:
+        import _interpreters  # noqa: F401
+    except ImportError:
+        return  # needs Python 3.13+
+
+    import os
+    import tempfile
+
+    def leftovers():
+        return {name for name in os.listdir(tempfile.gettempdir()) if name.startswith("makeparallel-")}
+
+    before = leftovers()
+    isolated = mp.parallel(isolation="subinterpreter")
+    factorial = isolated(math.factorial)
+    t.assert_equal(factorial(20).get(), math.factorial(20))
+    t.assert_raises(RuntimeError, factorial(-1).get)
+    # Results pass through private per-call directories that are removed afterwards
+    t.assert_equal(leftovers() - before, set())
+
+    # Each task starts from a fresh interpreter state
+    sys.mp_isolation_marker = "main"
+    try:
+        probe = isolated(functools.partial(eval, "getattr(__import__('sys'), 'mp_isolation_marker', None)"))
+        t.assert_equal(probe().get(), None)
+    finally:
+        del sys.mp_isolation_marker
+
+    # Arguments are pickled at call time
+    import threading
+    t.assert_raises(TypeError, lambda: factorial(threading.Lock()))
+    t.assert_raises(ValueError, lambda: mp.parallel(isolation="fork"))
+
+
+@runner.test("get_as() typed results")
+def test_get_as(t):
+    @mp.parallel
+    def produce(value):
+        return value
+
+    t.assert_equal(produce(42).get_as(int), 42)
+    t.assert_equal(produce(3).get_as(float), 3.0)
+    t.assert_true(isinstance(produce(3).get_as(float), float))
+    t.assert_equal(produce("text").get_as(str), "text")
+    t.assert_equal(produce(b"raw").get_as(bytes), b"raw")
+    t.assert_equal(produce([1, 2]).get_as(list), [1, 2])
+
+    try:
+        produce("42").get_as(int)
+        t.assert_true(False, "expected TypeError")
+    except TypeError as e:
+        t.assert_true("'produce'" in str(e) and "returned str, expected int" in str(e), str(e))
+    t.assert_raises(TypeError, lambda: produce(True).get_as(int))
+    t.assert_raises(TypeError, lambda: produce((1, 2)).get_as(list))
+    t.assert_raises(TypeError, lambda: produce({}).get_as(dict))
+
+
+@runner.test("result_schema validation")
+def test_result_schema(t):
+    @mp.parallel(result_schema=lambda r: isinstance(r, dict) and "id" in r)
+    def load(record):
+        return record
+
+    t.assert_equal(load({"id": 1}).get(), {"id": 1})
+    try:
+        load({"name": "no id"}).get()
+        t.assert_true(False, "expected validation failure")
+    except RuntimeError as e:
+        t.assert_true("TaskExecutionFailed" in str(e) and "'load'" in str(e), str(e))
+
+    class Point:
+        """Minimal stand-in for a pydantic model."""
+
+        def __init__(self, x, y):
+            self.x, self.y = x, y
+
+        @classmethod
+        def model_validate(cls, data):
+            if set(data) != {"x", "y"}:
+                raise ValueError(f"expected x and y, got {sorted(data)}")
+            return cls(**data)
+
+    @mp.parallel(result_schema=Point)
+    def make_point(data):
+        return data
+
+    point = make_point({"x": 1, "y": 2}).get()
+    t.assert_true(isinstance(point, Point) and point.y == 2)
+    t.assert_raises(RuntimeError, make_point({"x": 1}).get)
+    t.assert_raises(TypeError, lambda: mp.parallel(result_schema=42))
+
+
 @runner.test("Parallel Fast - Basic functionality")
 def test_parallel_fast_basic(t):
     @mp.parallel_fast
@@ -360,6 +726,41 @@ def test_parallel_pool_many(t):
     t.assert_equal(results, expected)
 
 
+@runner.test("Parallel Pool - Runs on the configured thread pool")
+def test_parallel_pool_configured(t):
+    import threading
+    import time
+
+    def run(num_threads):
+        mp.configure_thread_pool(num_threads=num_threads)
+        t.assert_equal(mp.get_thread_pool_info()["current_num_threads"], num_threads)
+        lock = threading.Lock()
+        state = {"active": 0, "peak": 0, "threads": set()}
+
+        def work(x):
+            with lock:
+                state["active"] += 1
+                state["peak"] = max(state["peak"], state["active"])
+                state["threads"].add(threading.get_native_id())
+            time.sleep(0.01)
+            with lock:
+                state["active"] -= 1
+            return x
+
+        task = mp.parallel_pool(work)
+        handles = [task(i) for i in range(12)]
+        t.assert_equal([h.get() for h in handles], list(range(12)))
+        t.assert_equal(mp.parallel_map(work, list(range(12)), chunksize=1), list(range(12)))
+        t.assert_true(state["peak"] <= num_threads)
+        t.assert_true(len(state["threads"]) <= num_threads)
+
+    try:
+        run(2)
+        run(3)
+    finally:
+        mp.configure_thread_pool()
+
+
 # =============================================================================
 # TEST 9: Memoize Fast (DashMap)
 # =============================================================================
@@ -382,6 +783,179 @@ def test_memoize_fast_caching(t):
     t.assert_equal(call_count[0], 2)  # Only 2 actual calls
 
 
+@runner.test("Memoize - warm_cache, cache_dump and cache_load")
+def test_memoize_warm_and_persist(t):
+    import os
+    import tempfile
+
+    calls = []
+
+    def make_cached():
+        @mp.memoize
+        def power(x, exponent):
+            calls.append(x)
+            return x**exponent
+
+        return power
+
+    power = make_cached()
+    handle = mp.warm_cache(power, [(i, 2) for i in range(5)], max_concurrency=2)
+    t.assert_equal(handle.get(), 5)
+    t.assert_equal(power(3, 2), 9)
+    t.assert_equal(len(calls), 5)  # served from the warmed cache
+
+    fd, path = tempfile.mkstemp()
+    os.close(fd)
+    try:
+        t.assert_true(mp.cache_dump(path) >= 5)
+        # A fresh process would decorate the function again, then load
+        calls.clear()
+        restarted = make_cached()
+        t.assert_true(mp.cache_load(path) >= 5)
+        t.assert_equal(restarted(4, 2), 16)
+        t.assert_equal(calls, [])
+    finally:
+        os.remove(path)
+
+
+@runner.test("Memoize - Shared cache across processes")
+def test_memoize_shared_across_processes(t):
+    import os
+    import subprocess
+    import tempfile
+
+    path = os.path.join(tempfile.mkdtemp(), "memo.cache")
+    code = f"""
+import makeparallel as mp
+calls = []
+
+@mp.memoize_shared({path!r}, size_mb=1)
+def slow_square(x):
+    calls.append(x)
+    return {{"square": x * x}}
+
+@mp.memoize_shared({path!r}, size_mb=1)
+def padding(n):
+    calls.append(n)
+    return "x" * n
+"""
+    # Entries are keyed by module and qualname, so run the code as the child will
+    namespace = {"__name__": "__main__"}
+    exec(code, namespace)
+    slow_square, padding = namespace["slow_square"], namespace["padding"]
+    t.assert_equal(slow_square(7), {"square": 49})
+    t.assert_equal(slow_square(7), {"square": 49})
+    t.assert_equal(namespace["calls"], [7])
+
+    # Results larger than a slot are returned but not cached
+    t.assert_equal(len(padding(10000)), 10000)
+    t.assert_equal(len(padding(10000)), 10000)
+    t.assert_equal(namespace["calls"], [7, 10000, 10000])
+
+    # Another process defining the same function hits the entry written above
+    child = subprocess.run(
+        [sys.executable, "-c", code + "print(slow_square(7)['square'], len(calls))"],
+        capture_output=True,
+        text=True,
+        env=dict(os.environ, PYTHONPATH=os.pathsep.join(sys.path)),
+        timeout=60,
+    )
+    t.assert_equal(child.stdout.split(), ["49", "0"], child.stderr)
+    os.remove(path)
+
+
+@runner.test("Memoize - Shared cache with an unaligned slot size")
+def test_memoize_shared_unaligned_slots(t):
+    import os
+    import struct
+    import tempfile
+
+    directory = tempfile.mkdtemp()
+    calls = []
+
+    # slot_size is rounded up so every slot stays aligned
+    @mp.memoize_shared(os.path.join(directory, "odd.cache"), size_mb=1, slot_size=300)
+    def label(x):
+        calls.append(x)
+        return "item-%d" % x
+
+    t.assert_equal([label(i) for i in range(50)], ["item-%d" % i for i in range(50)])
+    t.assert_equal([label(i) for i in range(50)], ["item-%d" % i for i in range(50)])
+    t.assert_equal(len(calls), 50)
+
+    # An existing file whose header describes misaligned slots is refused, not mapped
+    corrupt = os.path.join(directory, "corrupt.cache")
+    with open(corrupt, "wb") as f:
+        f.write(b"MPCACHE1" + struct.pack("<QQ", 1000, 100))
+        f.truncate(1024 * 1024)
+    t.assert_raises(OSError, lambda: mp.memoize_shared(corrupt, size_mb=1))
+
+
+@runner.test("Memoize - Shared cache created by racing processes")
+def test_memoize_shared_racing_creators(t):
+    import os
+    import subprocess
+    import tempfile
+
+    path = os.path.join(tempfile.mkdtemp(), "race.cache")
+    code = f"""
+import sys
+import makeparallel as mp
+
+@mp.memoize_shared({path!r}, size_mb=int(sys.argv[1]))
+def square(x):
+    return x * x
+
+print(sum(square(i) for i in range(200)))
+"""
+    # Processes asking for different sizes all map the one file the first of them created
+    children = [
+        subprocess.Popen(
+            [sys.executable, "-c", code, str(1 + n % 3)],
+            stdout=subprocess.PIPE,
+            stderr=subprocess.PIPE,
+            text=True,
+            env=dict(os.environ, PYTHONPATH=os.pathsep.join(sys.path)),
+        )
+        for n in range(6)
+    ]
+    expected = str(sum(i * i for i in range(200)))
+    for child in children:
+        out, err = child.communicate(timeout=60)
+        t.assert_equal((child.returncode, out.strip()), (0, expected), err)
+    t.assert_true(os.path.getsize(path) in (1 << 20, 2 << 20, 3 << 20))
+    os.remove(path)
+
+
+@runner.test("Singleflight - Concurrent identical calls share one execution")
+def test_singleflight(t):
+    calls = []
+
+    @mp.singleflight
+    def fetch(key):
+        calls.append(key)
+        time.sleep(0.2)
+        if key == "bad":
+            raise KeyError(key)
+        return key.upper()
+
+    @mp.parallel
+    def caller(key):
+        return fetch(key)
+
+    handles = [caller("a") for _ in range(4)] + [caller("b")]
+    t.assert_equal([h.get() for h in handles], ["A"] * 4 + ["B"])
+    t.assert_equal(sorted(calls), ["a", "b"])
+
+    # Failures reach every joined caller; finished flights are not cached
+    failing = [caller("bad") for _ in range(2)]
+    for h in failing:
+        t.assert_raises(Exception, h.get)
+    t.assert_equal(calls.count("bad"), 1)
+    t.assert_equal(fetch("a"), "A")
+    t.assert_equal(calls.count("a"), 2)
+
+
 # =============================================================================
 # TEST 10: Parallel Map (Batch Processing)
 # =============================================================================
@@ -409,182 +983,2105 @@ def test_parallel_map_large(t):
     t.assert_equal(results, expected)
 
 
-# =============================================================================
-# TEST 11: Class Methods
-# =============================================================================
-@runner.test("Timer - On class method")
-def test_timer_class_method(t):
-    class Calculator:
-        @mp.timer
-        def add(self, a, b):
-            return a + b
+@runner.test("Parallel Map - chunksize and unordered results")
+def test_parallel_map_chunksize_unordered(t):
+    def double(x):
+        return x * 2
 
-    calc = Calculator()
-    result = calc.add(10, 20)
-    t.assert_equal(result, 30)
+    items = list(range(1000))
+    expected = [i * 2 for i in items]
+    t.assert_equal(mp.parallel_map(double, items, chunksize=64), expected)
+    t.assert_equal(mp.parallel_map(double, items, chunksize=1), expected)
 
+    unordered = mp.parallel_map(double, items, chunksize=100, ordered=False)
+    t.assert_equal(sorted(unordered), expected)
 
-@runner.test("CallCounter - On class method")
-def test_callcounter_class_method(t):
-    class Counter:
-        @mp.CallCounter
-        def method(self, x):
-            return x * 2
+    t.assert_raises(ValueError, lambda: mp.parallel_map(double, items, chunksize=0))
 
-    obj = Counter()
-    obj.method(5)
-    obj.method(6)
 
-    t.assert_equal(obj.method.call_count, 2)
+@runner.test("Parallel Map - chunksize='auto'")
+def test_parallel_map_chunksize_auto(t):
+    import time
 
+    def uneven(x):
+        if x % 50 == 0:
+            time.sleep(0.02)
+        return x * 2
 
-@runner.test("Parallel - On class method")
-def test_parallel_class_method(t):
-    class Worker:
-        def __init__(self, factor):
-            self.factor = factor
+    items = list(range(500))
+    expected = [i * 2 for i in items]
+    t.assert_equal(mp.parallel_map(uneven, items, chunksize="auto"), expected)
+    t.assert_equal(sorted(mp.parallel_map(uneven, items, chunksize="auto", ordered=False)), expected)
+    t.assert_equal(mp.parallel_map(uneven, [], chunksize="auto"), [])
 
-        @mp.parallel
-        def process(self, x):
-            return x * self.factor
+    def flaky(x):
+        if x == 7:
+            raise ValueError("boom")
+        return x
 
-    worker = Worker(3)
-    handle = worker.process(7)
-    result = handle.get()
-    t.assert_equal(result, 21)
+    result = mp.parallel_map(flaky, list(range(20)), chunksize="auto", on_error="collect")
+    t.assert_equal(result.failed_indices, [7])
 
+    t.assert_raises(ValueError, lambda: mp.parallel_map(uneven, items, chunksize="fast"))
+    t.assert_raises(TypeError, lambda: mp.parallel_map(uneven, items, chunksize=1.5))
 
-# =============================================================================
-# TEST 12: Combined Decorators
-# =============================================================================
-@runner.test("Combined - Memoize + Timer")
-def test_combined_memoize_timer(t):
-    @mp.memoize
-    @mp.timer
-    def cached_slow(x):
-        return x**2
 
-    result1 = cached_slow(5)
-    result2 = cached_slow(5)
+@runner.test("parallel_map on_error='collect' and retry_failures()")
+def test_parallel_map_retry_failures(t):
+    import threading
 
-    t.assert_equal(result1, 25)
-    t.assert_equal(result2, 25)
+    attempts = {}
+    lock = threading.Lock()
 
+    def flaky(x):
+        with lock:
+            attempts[x] = attempts.get(x, 0) + 1
+            count = attempts[x]
+        # Multiples of 10 fail twice, 7 always fails
+        if x == 7 or (x % 10 == 0 and count <= 2):
+            raise ValueError(f"flaky {x}")
+        return x * 2
 
-# =============================================================================
-# TEST 13: Edge Cases
-# =============================================================================
-@runner.test("Edge Case - Empty arguments")
-def test_edge_empty_args(t):
-    @mp.parallel
-    def no_args():
-        return "success"
+    result = mp.parallel_map(flaky, list(range(50)), on_error="collect")
+    t.assert_equal(len(result), 50)
+    t.assert_true(not result.ok)
+    t.assert_equal(result.failed_indices, [0, 7, 10, 20, 30, 40])
+    t.assert_equal(result.results[7], None)
+    t.assert_true(isinstance(result.failures[7], ValueError))
+    t.assert_raises(ValueError, result.get)
+
+    t.assert_true(result.retry_failures(max_attempts=1) is result)
+    t.assert_equal(result.failed_indices, [0, 7, 10, 20, 30, 40])
+    result.retry_failures(max_attempts=3)
+    t.assert_equal(result.failed_indices, [7])
+    t.assert_equal(attempts[7], 5)
+    t.assert_equal(attempts[1], 1)
+    t.assert_equal(result.results[10], 20)
+
+    clean = mp.parallel_map(lambda x: x + 1, [1, 2, 3], on_error="collect")
+    t.assert_true(clean.ok)
+    t.assert_equal(clean.get(), [2, 3, 4])
+    t.assert_raises(ValueError, lambda: result.retry_failures(max_attempts=0))
+    t.assert_raises(ValueError, lambda: mp.parallel_map(flaky, [1], on_error="collect", ordered=False))
+    t.assert_raises(ValueError, lambda: mp.parallel_map(flaky, [1], on_error="skip"))
+
+
+@runner.test("parallel_map checkpoint resumes interrupted runs")
+def test_parallel_map_checkpoint(t):
+    import os
+    import tempfile
+    import threading
+
+    path = os.path.join(tempfile.mkdtemp(), "progress.ckpt")
+    calls = []
+    lock = threading.Lock()
+    interrupt = {"at": 30}
 
-    handle = no_args()
-    result = handle.get()
-    t.assert_equal(result, "success")
+    def square(x):
+        if x == interrupt["at"]:
+            raise KeyboardInterrupt("simulated crash")
+        with lock:
+            calls.append(x)
+        return x * x
+
+    expected = [x * x for x in range(100)]
+    t.assert_raises(KeyboardInterrupt, lambda: mp.parallel_map(square, list(range(100)), checkpoint=path))
+    first_run = len(calls)
+    t.assert_true(0 < first_run < 100)
+
+    # A torn trailing record from the interruption is ignored
+    with open(path, "ab") as f:
+        f.write(b"\x05\x00\x00")
+
+    calls.clear()
+    interrupt["at"] = None
+    t.assert_equal(mp.parallel_map(square, list(range(100)), checkpoint=path), expected)
+    t.assert_equal(len(calls), 100 - first_run)
+
+    calls.clear()
+    t.assert_equal(mp.parallel_map(square, list(range(100)), checkpoint=path, chunksize=7), expected)
+    t.assert_equal(calls, [])
+    unordered = mp.parallel_map(square, list(range(100)), checkpoint=path, ordered=False)
+    t.assert_equal(sorted(unordered), expected)
+
+    t.assert_raises(ValueError, lambda: mp.parallel_map(square, list(range(5)), checkpoint=path))
+    with open(os.path.join(os.path.dirname(path), "other"), "wb") as f:
+        f.write(b"not a checkpoint file")
+    t.assert_raises(ValueError, lambda: mp.parallel_map(square, [1], checkpoint=f.name))
+
+
+@runner.test("parallel_map item_timeout interrupts slow items")
+def test_parallel_map_item_timeout(t):
+    def work(x):
+        if x % 10 == 3:
+            # Pure-Python loop, so the timeout lands promptly
+            end = time.time() + 5
+            while time.time() < end:
+                pass
+        return x
 
+    start = time.time()
+    result = mp.parallel_map(work, list(range(30)), item_timeout=0.2, on_error="collect", chunksize=1)
+    t.assert_true(time.time() - start < 2, "slow items must not stall the map")
+    t.assert_equal(result.failed_indices, [3, 13, 23])
+    t.assert_equal(result.timed_out, [3, 13, 23])
+    t.assert_true(isinstance(result.failures[3], mp.TaskTimeout))
+    t.assert_equal(result.results[4], 4)
+
+    def fails(x):
+        raise ValueError("not a timeout")
+
+    other = mp.parallel_map(fails, [1], item_timeout=1, on_error="collect")
+    t.assert_equal(other.failed_indices, [0])
+    t.assert_equal(other.timed_out, [])
+
+    t.assert_raises(mp.TaskTimeout, lambda: mp.parallel_map(work, [3], item_timeout=0.1))
+    t.assert_equal(mp.parallel_map(work, [1, 2], item_timeout=1), [1, 2])
+    t.assert_raises(ValueError, lambda: mp.parallel_map(work, [1], item_timeout=0))
+
+
+@runner.test("parallel_map seed gives reproducible task_random() streams")
+def test_parallel_map_seed(t):
+    def simulate(steps):
+        rng = mp.task_random()
+        t.assert_true(mp.task_random() is rng)
+        return [rng.random() for _ in range(steps)]
+
+    items = [3] * 40
+    first = mp.parallel_map(simulate, items, seed=42)
+    t.assert_equal(mp.parallel_map(simulate, items, seed=42, chunksize=1), first)
+    t.assert_equal(mp.parallel_map(simulate, items, seed=42, chunksize=40), first)
+    t.assert_equal(len({tuple(stream) for stream in first}), 40)
+    t.assert_true(mp.parallel_map(simulate, items, seed=7) != first)
+
+    # A retried item draws the same numbers it would have the first time
+    failed = set()
+
+    def flaky(index):
+        value = mp.task_random().random()
+        if index == 5 and index not in failed:
+            failed.add(index)
+            raise ValueError("flaky")
+        return value
 
-@runner.test("Edge Case - None return value")
-def test_edge_none_return(t):
-    @mp.parallel
-    def returns_none():
-        return None
+    expected = mp.parallel_map(flaky, list(range(10)), seed=1, on_error="collect")
+    t.assert_equal(expected.failed_indices, [5])
+    retried = expected.retry_failures()
+    t.assert_true(retried.ok)
+    clean = mp.parallel_map(lambda i: mp.task_random().random(), list(range(10)), seed=1)
+    t.assert_equal(retried.get(), clean)
 
-    handle = returns_none()
-    result = handle.get()
-    t.assert_equal(result, None)
+    # Without a seed each call gets an independent generator
+    t.assert_true(mp.task_random() is not mp.task_random())
 
 
-@runner.test("Edge Case - Large data structure")
-def test_edge_large_data(t):
-    @mp.parallel
-    def create_list(n):
-        return list(range(n))
+@runner.test("Parallel Starmap - Argument tuples and kwargs")
+def test_parallel_starmap(t):
+    def power(base, exponent, scale=1):
+        return scale * base**exponent
+
+    pairs = [(2, 3), (3, 2), (10, 0)]
+    t.assert_equal(mp.parallel_starmap(power, pairs), [8, 9, 1])
+    t.assert_equal(
+        mp.parallel_starmap(power, pairs, kwargs_list=[{"scale": 2}, None, {"scale": 5}]),
+        [16, 9, 5],
+    )
+    t.assert_equal(mp.parallel_starmap(power, iter([[2, 2]])), [4])
+    t.assert_raises(ValueError, lambda: mp.parallel_starmap(power, pairs, kwargs_list=[{}]))
 
-    handle = create_list(1000)
-    result = handle.get()
-    t.assert_equal(len(result), 1000)
-    t.assert_equal(result[0], 0)
-    t.assert_equal(result[-1], 999)
+
+@runner.test("Parallel filter and reduce - Ordered tree reduction")
+def test_parallel_filter_reduce(t):
+    items = list(range(50))
+    t.assert_equal(mp.parallel_filter(lambda x: x % 3 == 0, items), [x for x in items if x % 3 == 0])
+    t.assert_equal(mp.parallel_filter(lambda x: x, [0, "", "a", None, 2], chunksize=2), ["a", 2])
+
+    t.assert_equal(mp.parallel_reduce(lambda a, b: a + b, items), sum(items))
+    # Associative but not commutative: order must be preserved across chunks
+    words = [str(i) for i in range(30)]
+    t.assert_equal(mp.parallel_reduce(lambda a, b: a + b, words, chunksize=4), "".join(words))
+    t.assert_equal(mp.parallel_reduce(lambda a, b: a + b, [], initializer=7), 7)
+    t.assert_equal(mp.parallel_reduce(lambda a, b: a * b, [2, 3], initializer=5), 30)
+    t.assert_raises(TypeError, lambda: mp.parallel_reduce(lambda a, b: a + b, []))
+    t.assert_raises(ZeroDivisionError, lambda: mp.parallel_reduce(lambda a, b: a / b, [1, 0]))
 
 
 # =============================================================================
-# TEST 14: Advanced Features
+# TEST 11: Class Methods
 # =============================================================================
-@runner.test("Advanced - AsyncHandle.cancel()")
-def test_advanced_cancel(t):
-    @mp.parallel
-    def long_running_task():
-        time.sleep(2)
-        return "should not complete"
+@runner.test("Prefetch - Ordered results computed ahead")
+def test_prefetch(t):
+    def slow_square(x):
+        time.sleep(0.1)
+        if x == 99:
+            raise KeyError(x)
+        return x * x
+
+    start = time.time()
+    results = []
+    for value in mp.prefetch(slow_square, range(8), depth=4):
+        time.sleep(0.1)  # consumer work overlaps with background computation
+        results.append(value)
+    elapsed = time.time() - start
+
+    t.assert_equal(results, [x * x for x in range(8)])
+    t.assert_true(elapsed < 1.4, f"prefetch did not overlap work ({elapsed:.2f}s)")
+
+    it = mp.prefetch(slow_square, [1, 99, 3], depth=2)
+    t.assert_equal(next(it), 1)
+    t.assert_raises(KeyError, lambda: next(it))
+    t.assert_equal(next(it), 9)
+    t.assert_raises(StopIteration, lambda: next(it))
+    t.assert_raises(ValueError, lambda: mp.prefetch(slow_square, [1], depth=0))
+
+
+@runner.test("Parallel imap - Lazy input with bounded prefetch")
+def test_parallel_imap(t):
+    pulled = []
+
+    def source():
+        for i in range(100):
+            pulled.append(i)
+            yield i
+
+    def slow_double(x):
+        time.sleep(0.01)
+        return x * 2
 
-    handle = long_running_task()
-    time.sleep(0.1)
-    handle.cancel()
+    it = mp.parallel_imap(slow_double, source(), prefetch=3)
+    t.assert_equal(next(it), 0)
+    t.assert_true(len(pulled) <= 4, f"pulled {len(pulled)} items for one result")
+    t.assert_equal(list(it), [x * 2 for x in range(1, 100)])
 
-    t.assert_true(handle.is_cancelled(), "handle.is_cancelled() should be True.")
-    # Note: Once cancelled, the task is marked complete and get() will return the cached error
-    # Since we can't interrupt Python's time.sleep(), the thread continues but is marked cancelled
-    t.assert_true(handle.is_ready(), "handle.is_ready() should be True after cancel.")
+    def sleepy(delay):
+        time.sleep(delay)
+        return delay
 
+    unordered = list(mp.parallel_imap(sleepy, [0.3, 0.0, 0.0], prefetch=3, ordered=False))
+    t.assert_equal(sorted(unordered), [0.0, 0.0, 0.3])
+    t.assert_equal(list(mp.parallel_imap(slow_double, [])), [])
+    t.assert_raises(ValueError, lambda: mp.parallel_imap(slow_double, [1], prefetch=0))
 
-@runner.test("Advanced - Task timeout")
-def test_advanced_timeout(t):
-    @mp.parallel
-    def task_that_will_timeout():
-        time.sleep(1)
-        return "should have timed out"
 
-    handle = task_that_will_timeout(timeout=0.5)
-    t.assert_raises(Exception, handle.get)
+@runner.test("Pipeline - Bounded stages with backpressure")
+def test_pipeline(t):
+    def parse(line):
+        return int(line)
 
+    def slow_square(x):
+        time.sleep(0.005)
+        return x * x
 
-@runner.test("Advanced - Task metadata")
-def test_advanced_metadata(t):
-    @mp.parallel
-    def task_with_metadata(x):
-        return x
+    pipeline = mp.Pipeline(maxsize=2).stage(parse, workers=2).stage(slow_square, workers=3)
+    t.assert_equal(sorted(pipeline.run(str(i) for i in range(40))), [i * i for i in range(40)])
 
-    handle = task_with_metadata(123)
-    handle.set_metadata("user_id", "user-abc")
-    handle.set_metadata("request_id", "req-123")
-    metadata = handle.get_all_metadata()
+    # Items fed by hand come out once the input is closed
+    doubler = mp.Pipeline().stage(lambda x: x * 2)
+    for i in range(3):
+        doubler.put(i)
+    doubler.close()
+    t.assert_equal(sorted(doubler), [0, 2, 4])
+    t.assert_raises(RuntimeError, lambda: doubler.put(1))
+    t.assert_raises(RuntimeError, lambda: doubler.stage(parse))
+
+    # A failing item is raised in place; the rest still flow through
+    results, errors = [], []
+    it = iter(mp.Pipeline().stage(parse).run(["1", "x", "3"]))
+    while True:
+        try:
+            results.append(next(it))
+        except ValueError as e:
+            errors.append(e)
+        except StopIteration:
+            break
+    t.assert_equal((sorted(results), len(errors)), ([1, 3], 1))
 
-    t.assert_equal(metadata.get("user_id"), "user-abc")
-    t.assert_equal(metadata.get("request_id"), "req-123")
+    # Backpressure: a stalled consumer stops the feeder after the queues fill up
+    fed = []
 
+    def source():
+        for i in range(1000):
+            fed.append(i)
+            yield i
 
-@runner.test("Advanced - Thread pool configuration")
-def test_advanced_threadpool_config(t):
-    mp.configure_thread_pool(num_threads=4)
-    info = mp.get_thread_pool_info()
-    t.assert_true(info["configured"])
-    # Note: num_threads info may vary based on implementation
+    stalled = mp.Pipeline(maxsize=1).stage(lambda x: x).run(source())
+    time.sleep(0.2)
+    t.assert_true(len(fed) < 10, f"feeder ran ahead by {len(fed)} items")
+    t.assert_equal(len(list(stalled)), 1000)
 
+    t.assert_raises(ValueError, lambda: mp.Pipeline().stage(parse, workers=0))
+    t.assert_raises(ValueError, lambda: mp.Pipeline().close())
 
-@runner.test("Advanced - @mp.parallel_priority")
-def test_advanced_priority(t):
-    # Start the priority worker
-    mp.start_priority_worker()
+    # A task blocked feeding a full pipeline still ends when it is cancelled
+    gate = __import__("threading").Event()
+    full = mp.Pipeline(maxsize=1).stage(lambda x: gate.wait(10) and x)
+
+    @mp.parallel
+    def feeder():
+        for i in range(100):
+            full.put(i)
+
+    handle = feeder()
+    time.sleep(0.2)
+    handle.cancel()
+    deadline = time.time() + 2
+    while mp.get_task_status(handle.get_task_id()) is not None and time.time() < deadline:
+        time.sleep(0.02)
+    t.assert_equal(mp.get_task_status(handle.get_task_id()), None)
+    gate.set()
+
+
+@runner.test("Parallel - Per-function max_concurrency")
+def test_parallel_max_concurrency(t):
+    import threading
+
+    lock = threading.Lock()
+    running = [0]
+    peak = [0]
+
+    @mp.parallel(max_concurrency=2)
+    def throttled(i):
+        with lock:
+            running[0] += 1
+            peak[0] = max(peak[0], running[0])
+        time.sleep(0.05)
+        with lock:
+            running[0] -= 1
+        return i
+
+    @mp.parallel
+    def unrelated():
+        return "free"
+
+    handles = [throttled(i) for i in range(6)]
+    # Other functions are not held up by the throttled one
+    free = unrelated()
+    t.assert_true(free.wait(1))
+    t.assert_true(not all(h.is_ready() for h in handles))
+    t.assert_equal(free.get(), "free")
+    t.assert_equal([h.get() for h in handles], list(range(6)))
+    t.assert_equal(peak[0], 2)
+
+    # Cancelling a call waiting for a slot releases it without running
+    blockers = [throttled(i) for i in range(2)]
+    waiting = throttled(99)
+    waiting.cancel()
+    t.assert_equal([h.get() for h in blockers], [0, 1])
+    t.assert_raises(RuntimeError, waiting.get)
+
+    t.assert_raises(ValueError, lambda: mp.parallel(max_concurrency=0))
+
+
+@runner.test("Streaming - Partial results with emit()")
+def test_streaming_results(t):
+    @mp.parallel
+    def crawl(pages):
+        for page in range(pages):
+            time.sleep(0.02)
+            mp.emit({"page": page})
+        return pages
+
+    handle = crawl(4)
+    seen = []
+    for partial in handle.iter_results():
+        seen.append(partial["page"])
+        if len(seen) == 1:
+            t.assert_true(not handle.is_ready())  # first value arrives before the task ends
+    t.assert_equal(seen, [0, 1, 2, 3])
+    t.assert_equal(handle.get(), 4)
+
+    # Values emitted before iteration starts are buffered
+    done = crawl(3)
+    done.get()
+    t.assert_equal([p["page"] for p in done.iter_results()], [0, 1, 2])
+    t.assert_equal(list(done.iter_results()), [])
 
     @mp.parallel_priority
-    def priority_task(value):
-        time.sleep(0.1)
-        return value
+    def ranked():
+        mp.emit("first")
+        return "last"
 
-    # Submit tasks with different priorities
-    # Higher priority values should execute first
-    low_prio_handle = priority_task(1, priority=1)
-    medium_prio_handle = priority_task(5, priority=5)
-    high_prio_handle = priority_task(10, priority=10)
+    t.assert_equal(list(ranked(priority=1).iter_results()), ["first"])
 
-    # Wait for all to complete
-    high_result = high_prio_handle.get()
-    medium_result = medium_prio_handle.get()
-    low_result = low_prio_handle.get()
+    @mp.parallel
+    def partial_failure():
+        mp.emit(1)
+        raise ValueError("stream broke")
 
-    # Just verify they all completed successfully
+    stream = partial_failure().iter_results()
+    t.assert_equal(next(stream), 1)
+    t.assert_raises(RuntimeError, lambda: next(stream))
+    t.assert_raises(StopIteration, lambda: next(stream))
+
+    t.assert_raises(RuntimeError, lambda: mp.emit(1))
+
+
+@runner.test("Streaming - Consumer closing the stream stops the producer")
+def test_streaming_consumer_close(t):
+    cleaned_up = []
+
+    @mp.parallel
+    def endless():
+        i = 0
+        try:
+            while True:
+                mp.emit(i)
+                i += 1
+                time.sleep(0.01)
+        except mp.StreamClosed:
+            cleaned_up.append(mp.current_cancellation_token().is_cancelled)
+            raise
+
+    handle = endless()
+    for value in handle.iter_results():
+        if value == 2:
+            break  # dropping the iterator closes the stream
+    t.assert_raises(RuntimeError, handle.get)
+    t.assert_equal(cleaned_up, [True])
+    t.assert_true(issubclass(mp.StreamClosed, mp.TaskCancelled))
+
+    # Explicit close() and the context manager behave the same
+    cleaned_up.clear()
+    handle = endless()
+    with handle.iter_results() as stream:
+        t.assert_equal(next(stream), 0)
+    t.assert_raises(RuntimeError, handle.get)
+    t.assert_equal(cleaned_up, [True])
+
+    @mp.parallel
+    def catches():
+        try:
+            while True:
+                mp.emit("x")
+                time.sleep(0.01)
+        except mp.StreamClosed:
+            return "stopped early"
+
+    handle = catches()
+    stream = handle.iter_results()
+    next(stream)
+    stream.close()
+    t.assert_equal(handle.get(), "stopped early")
+
+    # Exhausting the stream does not close it
+    @mp.parallel
+    def finite():
+        mp.emit(1)
+        return "ok"
+
+    handle = finite()
+    t.assert_equal(list(handle.iter_results()), [1])
+    t.assert_equal(handle.get(), "ok")
+
+
+@runner.test("Streaming - async for over a streaming handle")
+def test_streaming_async_for(t):
+    import asyncio
+
+    @mp.parallel
+    def ticker(n):
+        for i in range(n):
+            time.sleep(0.02)
+            mp.emit(i)
+        return "done"
+
+    @mp.parallel
+    def failing():
+        mp.emit("partial")
+        raise ValueError("broken feed")
+
+    cleaned_up = []
+
+    @mp.parallel
+    def endless():
+        try:
+            while True:
+                mp.emit("tick")
+                time.sleep(0.01)
+        except mp.StreamClosed:
+            cleaned_up.append(True)
+            raise
+
+    async def main():
+        handle = ticker(5)
+        heartbeats = 0
+        values = []
+
+        async def heartbeat():
+            nonlocal heartbeats
+            while True:
+                heartbeats += 1
+                await asyncio.sleep(0.01)
+
+        beat = asyncio.ensure_future(heartbeat())
+        async for value in handle:
+            values.append(value)
+        beat.cancel()
+        t.assert_equal(values, [0, 1, 2, 3, 4])
+        t.assert_true(heartbeats > 3)  # the loop stayed responsive while waiting
+
+        seen = []
+        try:
+            async for value in failing():
+                seen.append(value)
+        except RuntimeError:
+            seen.append("error")
+        t.assert_equal(seen, ["partial", "error"])
+
+        # A timed-out await does not lose values for the next one
+        slow = ticker(2)
+        stream = slow.__aiter__()
+        try:
+            await asyncio.wait_for(stream.__anext__(), 0.001)
+        except asyncio.TimeoutError:
+            pass
+        t.assert_equal([await stream.__anext__(), await stream.__anext__()], [0, 1])
+
+        forever = endless()
+        async for value in forever:
+            break
+        return forever
+
+    forever = asyncio.run(main())
+    t.assert_raises(RuntimeError, forever.get)
+    t.assert_equal(cleaned_up, [True])
+
+
+@runner.test("Streaming - Bounded emit() applies backpressure")
+def test_streaming_backpressure(t):
+    mp.configure_stream_capacity(2)
+    try:
+        produced = []
+
+        @mp.parallel
+        def producer(n):
+            for i in range(n):
+                mp.emit(i)
+                produced.append(i)
+            return n
+
+        handle = producer(10)
+        time.sleep(0.2)
+        t.assert_equal(produced, [0, 1])  # blocked on the third emit
+        t.assert_equal(list(handle.iter_results()), list(range(10)))
+        t.assert_equal(handle.get(), 10)
+
+        @mp.parallel
+        def impatient():
+            mp.emit("a")
+            mp.emit("b")
+            mp.emit("c", timeout=0.05)
+
+        t.assert_raises(RuntimeError, impatient().get)
+
+        # Cancelling a blocked producer unblocks it
+        stuck = producer(5)
+        time.sleep(0.1)
+        stuck.cancel()
+        time.sleep(0.3)
+        t.assert_equal(mp.get_active_task_count(), 0)
+    finally:
+        mp.configure_stream_capacity(None)
+    t.assert_raises(ValueError, lambda: mp.configure_stream_capacity(0))
+
+
+@runner.test("Fan-out - Many consumers of one task")
+def test_fan_out(t):
+    calls = []
+
+    @mp.parallel
+    def load(x):
+        calls.append(x)
+        time.sleep(0.1)
+        return x * 10
+
+    @mp.parallel_with_deps
+    def add(deps, offset):
+        return deps[0] + offset
+
+    source = load(4)
+    readers = [source.subscribe() for _ in range(5)]
+    dependents = [add(i, depends_on=[source]) for i in range(3)]
+    t.assert_equal([r.get() for r in readers], [40] * 5)
+    t.assert_equal([d.get() for d in dependents], [40, 41, 42])
+    t.assert_equal(source.get(), 40)
+    t.assert_equal(calls, [4])
+    t.assert_equal(readers[0].get_task_id(), source.get_task_id())
+
+    # Late readers and dependents still see the result
+    t.assert_equal(source.subscribe().get(), 40)
+    t.assert_equal(add(5, depends_on=[source]).get(), 45)
+
+    # A failure reaches every reader and fails dependents without waiting out their timeout
+    @mp.parallel
+    def broken():
+        raise ValueError("nope")
+
+    failing = broken()
+    reader = failing.subscribe()
+    start = time.time()
+    t.assert_raises(RuntimeError, reader.get)
+    t.assert_raises(RuntimeError, add(1, depends_on=[failing]).get)
+    t.assert_true(time.time() - start < 2)
+
+    # Cancelling a reader leaves the task running
+    slow = load(1)
+    slow.subscribe().cancel()
+    t.assert_equal(slow.get(), 10)
+
+
+@runner.test("Saga - Compensating completed steps after a failure")
+def test_saga(t):
+    import threading
+
+    log = []
+    lock = threading.Lock()
+
+    def record(entry):
+        with lock:
+            log.append(entry)
+        return entry
+
+    saga = mp.Saga("order")
+    saga.step(do=lambda: record("reserve"), undo=lambda r: record("undo " + r))
+    saga.step(do=lambda: record("charge"), undo=lambda r: record("undo " + r))
+    saga.step(do=lambda: record("email"))
+    t.assert_equal(saga.run(), ["reserve", "charge", "email"])
+    t.assert_equal(saga.status, "completed")
+    t.assert_equal(log, ["reserve", "charge", "email"])
+    t.assert_raises(RuntimeError, saga.run)
+    t.assert_raises(RuntimeError, lambda: saga.step(do=print))
+
+    # Two independent steps, then a failing one that depends on both
+    log.clear()
+
+    def ship():
+        raise ValueError("carrier down")
+
+    def slow_undo(r):
+        time.sleep(0.2)
+        return record("undo " + r)
+
+    saga = mp.Saga()
+    a = saga.step(do=lambda: record("flight"), undo=slow_undo, depends_on=[])
+    b = saga.step(do=lambda: record("hotel"), undo=slow_undo, depends_on=[])
+    saga.step(do=ship, depends_on=[a, b])
+    saga.step(do=lambda: record("never"))
+    start = time.time()
+    t.assert_raises(RuntimeError, saga.run)
+    t.assert_true(time.time() - start < 0.35)  # compensations ran in parallel
+    t.assert_equal(saga.status, "compensated")
+    t.assert_equal(sorted(log), ["flight", "hotel", "undo flight", "undo hotel"])
+
+    # Compensations run in reverse dependency order
+    log.clear()
+    saga = mp.Saga()
+    saga.step(do=lambda: record("a"), undo=lambda r: record("undo a"))
+    saga.step(do=lambda: record("b"), undo=lambda r: record("undo b"))
+    saga.step(do=ship)
+    t.assert_raises(RuntimeError, saga.run)
+    t.assert_equal(log, ["a", "b", "undo b", "undo a"])
+
+    # A failing compensation turns the error into an exception group
+    def bad_undo(r):
+        raise KeyError(r)
+
+    saga = mp.Saga()
+    saga.step(do=lambda: 1, undo=bad_undo)
+    saga.step(do=ship)
+    t.assert_raises(ExceptionGroup, saga.run)
+    t.assert_equal(saga.status, "compensation_failed")
+
+    t.assert_raises(ValueError, lambda: mp.Saga().step(do=print, depends_on=[0]))
+    t.assert_raises(TypeError, lambda: mp.Saga().step(do=1))
+
+
+@runner.test("Sync - Semaphore, Event, Barrier and RwLock")
+def test_sync_primitives(t):
+    import threading
+
+    # Semaphore bounds concurrency
+    sem = mp.Semaphore(2)
+    active = []
+    peak = []
+    guard = threading.Lock()
+
+    @mp.parallel
+    def limited(i):
+        with sem:
+            with guard:
+                active.append(i)
+                peak.append(len(active))
+            time.sleep(0.05)
+            with guard:
+                active.remove(i)
+        return i
+
+    t.assert_equal([h.get() for h in [limited(i) for i in range(6)]], list(range(6)))
+    t.assert_true(max(peak) <= 2)
+    t.assert_equal(sem.value, 2)
+    t.assert_true(sem.acquire() and sem.acquire())
+    t.assert_true(not sem.acquire(blocking=False))
+    t.assert_true(not sem.acquire(timeout=0.05))
+    sem.release(2)
+    t.assert_raises(ValueError, lambda: sem.acquire(blocking=False, timeout=1))
+
+    # Event wakes every waiter
+    ready = mp.Event()
+
+    @mp.parallel
+    def waiter():
+        return ready.wait(timeout=5)
+
+    waiters = [waiter() for _ in range(3)]
+    time.sleep(0.05)
+    t.assert_true(not ready.is_set())
+    ready.set()
+    t.assert_equal([w.get() for w in waiters], [True] * 3)
+    ready.clear()
+    t.assert_true(not ready.wait(timeout=0.05))
+
+    # Barrier releases all parties together with distinct indices
+    barrier = mp.Barrier(3)
+
+    @mp.parallel
+    def party():
+        return barrier.wait(timeout=5)
+
+    t.assert_equal(sorted(h.get() for h in [party() for _ in range(3)]), [0, 1, 2])
+    t.assert_raises(threading.BrokenBarrierError, lambda: barrier.wait(timeout=0.05))
+    t.assert_true(barrier.broken)
+    t.assert_raises(threading.BrokenBarrierError, barrier.wait)
+    barrier.reset()
+    t.assert_true(not barrier.broken)
+    t.assert_raises(ValueError, lambda: mp.Barrier(0))
+
+    # RwLock: shared readers, exclusive writer
+    lock = mp.RwLock()
+    with lock.read():
+        t.assert_true(lock.acquire_read(blocking=False))
+        t.assert_true(not lock.acquire_write(timeout=0.05))
+        lock.release_read()
+    with lock.write():
+        t.assert_true(not lock.acquire_read(blocking=False))
+        t.assert_true(not lock.acquire_write(blocking=False))
+    t.assert_true(lock.acquire_write(blocking=False))
+    lock.release_write()
+    t.assert_raises(RuntimeError, lock.release_read)
+    t.assert_raises(RuntimeError, lock.release_write)
+
+    counter = [0]
+
+    @mp.parallel
+    def writer(n):
+        for _ in range(n):
+            with lock.write():
+                value = counter[0]
+                time.sleep(0)
+                counter[0] = value + 1
+
+    t.assert_equal([h.get() for h in [writer(50) for _ in range(4)]], [None] * 4)
+    t.assert_equal(counter[0], 200)
+
+
+@runner.test("Sync - Named CountDownLatch shared across tasks")
+def test_count_down_latch(t):
+    @mp.parallel
+    def worker(n):
+        time.sleep(0.02 * n)
+        mp.CountDownLatch(3, name="warmup").count_down()
+        return n
+
+    @mp.parallel
+    def waiter():
+        return mp.CountDownLatch(3, name="warmup").wait(timeout=2)
+
+    pending = waiter()
+    handles = [worker(n) for n in range(3)]
+    t.assert_equal(pending.get(), True)
+    t.assert_equal([h.get() for h in handles], [0, 1, 2])
+
+    # The resolved name is freed: the next latch starts with a fresh count
+    latch = mp.CountDownLatch(2, name="warmup")
+    t.assert_equal(latch.count, 2)
+    t.assert_equal(latch.wait(timeout=0.05), False)
+    latch.count_down()
+    latch.count_down()
+    latch.count_down()
+    t.assert_equal(latch.count, 0)
+    t.assert_true(latch.wait(timeout=0.01))
+    t.assert_true(mp.CountDownLatch(0).wait())
+
+
+@runner.test("Channel - GIL-releasing queue between tasks")
+def test_channel(t):
+    import queue
+
+    channel = mp.Channel(maxsize=2)
+
+    @mp.parallel
+    def producer(n):
+        for i in range(n):
+            channel.put(i)  # blocks while the channel is full
+        channel.close()
+        return n
+
+    handle = producer(10)
+    t.assert_equal(list(channel), list(range(10)))
+    t.assert_equal(handle.get(), 10)
+    t.assert_true(channel.closed)
+    t.assert_raises(RuntimeError, lambda: channel.put(1))
+    t.assert_raises(EOFError, channel.get)
+
+    bounded = mp.Channel(1)
+    bounded.put_nowait("a")
+    t.assert_true(bounded.full())
+    t.assert_equal(bounded.qsize(), 1)
+    t.assert_raises(queue.Full, lambda: bounded.put_nowait("b"))
+    t.assert_raises(queue.Full, lambda: bounded.put("b", timeout=0.05))
+    t.assert_equal(bounded.get_nowait(), "a")
+    t.assert_true(bounded.empty())
+    t.assert_raises(queue.Empty, bounded.get_nowait)
+    t.assert_raises(queue.Empty, lambda: bounded.get(timeout=0.05))
+
+    # Items put before close() are still delivered
+    unbounded = mp.Channel()
+    for i in range(100):
+        unbounded.put(i)
+    t.assert_true(not unbounded.full())
+    unbounded.close()
+    t.assert_equal(len(unbounded), 100)
+    t.assert_equal(sum(unbounded), sum(range(100)))
+
+    # A blocked get() does not hold up other threads' Python code
+    idle = mp.Channel()
+
+    @mp.parallel
+    def consumer():
+        return idle.get(timeout=5)
+
+    pending = consumer()
+    time.sleep(0.05)
+    t.assert_equal(sum(range(10000)), 49995000)
+    idle.put("done")
+    t.assert_equal(pending.get(), "done")
+
+
+@runner.test("Select - Waiting on handles, channels and timers")
+def test_select(t):
+    @mp.parallel
+    def slow(x, delay):
+        time.sleep(delay)
+        return x
+
+    jobs = mp.Channel()
+    fast, lagging = slow("fast", 0.05), slow("lagging", 1)
+    source, value = mp.select([lagging, jobs, fast])
+    t.assert_true(source is fast)
+    t.assert_equal(value, None)
+    t.assert_equal(source.get(), "fast")
+
+    # Items already queued win immediately, and are consumed
+    jobs.put("job-1")
+    source, value = mp.select([lagging, jobs])
+    t.assert_true(source is jobs)
+    t.assert_equal(value, "job-1")
+    t.assert_true(jobs.empty())
+
+    # An item put by another task while select() waits
+    @mp.parallel
+    def producer():
+        time.sleep(0.05)
+        jobs.put("job-2")
+
+    producer()
+    t.assert_equal(mp.select([lagging, jobs])[1], "job-2")
+
+    # Timers and the overall timeout
+    tick = mp.Timer(0.05)
+    start = time.time()
+    source, value = mp.select([lagging, tick])
+    t.assert_true(source is tick and time.time() - start < 0.5)
+    t.assert_equal(mp.select([jobs], timeout=0.05), None)
+
+    # Subscriptions of a Topic
+    topic = mp.Topic()
+    sub = topic.subscribe()
+    topic.publish({"event": "ping"})
+    t.assert_equal(mp.select([jobs, sub]), (sub, {"event": "ping"}))
+
+    # A closed topic's subscription never becomes ready
+    topic.close()
+    t.assert_equal(mp.select([sub], timeout=0.05), None)
+
+    t.assert_raises(TypeError, lambda: mp.select([42]))
+    t.assert_raises(ValueError, lambda: mp.Timer(-1))
+    t.assert_equal(lagging.get(), "lagging")
+
+
+@runner.test("Select - Closed channels and repeated waits")
+def test_select_closed_channel(t):
+    jobs = mp.Channel()
+
+    @mp.parallel
+    def closer():
+        time.sleep(0.05)
+        jobs.close()
+
+    # Closing a channel wakes a select() that is waiting on it
+    closer()
+    start = time.time()
+    t.assert_equal(mp.select([jobs], timeout=5), (jobs, None))
+    t.assert_true(time.time() - start < 1)
+    t.assert_equal(mp.select([jobs], timeout=0.01), (jobs, None))
+
+    # Items put before the close are still handed out first
+    backlog = mp.Channel()
+    backlog.put("last")
+    backlog.close()
+    t.assert_equal(mp.select([backlog]), (backlog, "last"))
+    t.assert_equal(mp.select([backlog]), (backlog, None))
+
+    # Timed-out selects on a pending handle don't get in the way of a later one
+    release = __import__("threading").Event()
+
+    @mp.parallel
+    def blocked():
+        release.wait(10)
+        return "done"
+
+    handle = blocked()
+    try:
+        for _ in range(100):
+            t.assert_equal(mp.select([handle], timeout=0.001), None)
+    finally:
+        release.set()
+    t.assert_true(mp.select([handle], timeout=5)[0] is handle)
+    t.assert_equal(handle.get(), "done")
+
+
+@runner.test("Select - after() and interval() timers")
+def test_after_and_interval(t):
+    @mp.parallel
+    def slow():
+        time.sleep(0.5)
+        return "slow"
+
+    # after() is a handle: it races task handles and works with gather()
+    task, deadline = slow(), mp.after(0.05)
+    source, _ = mp.select([task, deadline])
+    t.assert_true(source is deadline)
+    t.assert_equal(mp.gather([mp.after(0.01), mp.after(0.02)]), [None, None])
+    t.assert_raises(ValueError, lambda: mp.after(-1))
+
+    ticker = mp.interval(0.02)
+    t.assert_equal([n for _, n in zip(range(3), ticker)], [1, 2, 3])
+    source, tick = mp.select([task, ticker])
+    t.assert_true(source is ticker)
+    t.assert_equal(tick, 4)
+
+    ticker.stop()
+    t.assert_true(ticker.stopped)
+    t.assert_equal(list(ticker), [])
+    t.assert_equal(mp.select([ticker], timeout=0.05), None)
+    t.assert_raises(ValueError, lambda: mp.interval(0))
+
+    cancelled = mp.after(5)
+    cancelled.cancel()
+    t.assert_raises(RuntimeError, lambda: mp.gather([cancelled]))
+    t.assert_equal(task.get(), "slow")
+
+
+@runner.test("Bulkhead - Queueing and rejecting excess calls")
+def test_bulkhead(t):
+    import threading
+
+    release = threading.Event()
+
+    @mp.bulkhead(max_concurrent=1, max_queue=1)
+    def guarded(x):
+        release.wait(2)
+        return x
+
+    @mp.parallel
+    def call(x):
+        return guarded(x)
+
+    first = call(1)
+    time.sleep(0.1)
+    second = call(2)
+    time.sleep(0.1)
+
+    stats = mp.get_metrics("guarded").bulkhead
+    t.assert_equal((stats.running, stats.queued), (1, 1))
+    t.assert_raises(mp.BulkheadFull, lambda: guarded(3))
+
+    release.set()
+    t.assert_equal((first.get(), second.get()), (1, 2))
+    stats = mp.get_metrics("guarded").bulkhead
+    t.assert_equal((stats.running, stats.queued, stats.accepted, stats.rejected), (0, 0, 2, 1))
+    t.assert_equal(mp.get_all_metrics()["guarded"]["bulkhead"]["rejected"], 1)
+    t.assert_raises(ValueError, lambda: mp.bulkhead(max_concurrent=0))
+
+
+@runner.test("Topic - Publish/subscribe between tasks")
+def test_topic(t):
+    topic = mp.Topic()
+    early = topic.subscribe()
+
+    @mp.parallel
+    def consumer(subscription):
+        return [message for message in subscription]
+
+    @mp.parallel
+    def producer(n):
+        return sum(topic.publish(i) for i in range(n))
+
+    handles = [consumer(topic.subscribe()) for _ in range(2)]
+    t.assert_equal(producer(5).get(), 15)  # 3 subscribers x 5 messages
+    topic.close()
+    t.assert_equal([h.get() for h in handles], [[0, 1, 2, 3, 4]] * 2)
+    t.assert_equal(list(early), [0, 1, 2, 3, 4])
+    t.assert_raises(RuntimeError, lambda: topic.publish(1))
+    t.assert_raises(RuntimeError, lambda: topic.subscribe())
+
+    quiet = mp.Topic(maxsize=1)
+    sub = quiet.subscribe()
+    t.assert_raises(TimeoutError, lambda: sub.get(timeout=0.05))
+    sub.unsubscribe()
+    t.assert_equal(quiet.publish("dropped"), 0)
+    quiet.close()
+    t.assert_raises(EOFError, lambda: sub.get(timeout=0.05))
+    t.assert_raises(ValueError, lambda: mp.Topic(maxsize=0))
+
+    # A cancelled task blocked on a subscription stops waiting
+    idle = mp.Topic()
+
+    @mp.parallel
+    def wait_forever(subscription):
+        return subscription.get()
+
+    handle = wait_forever(idle.subscribe())
+    time.sleep(0.05)
+    handle.cancel()
+    time.sleep(0.3)
+    t.assert_equal(mp.get_active_task_count(), 0)
+
+
+@runner.test("Provide - Injected resources with task, worker and global scope")
+def test_provide_inject(t):
+    import itertools
+
+    counter = itertools.count()
+    closed = []
+
+    class Resource:
+        def __init__(self, kind):
+            self.kind = kind
+            self.id = next(counter)
+
+        def close(self):
+            closed.append((self.kind, self.id))
+
+    mp.provide("conn", lambda: Resource("task"))
+    mp.provide("pool", lambda: Resource("global"), scope="global")
+    mp.provide("cache", lambda: Resource("worker"), scope="worker")
+
+    @mp.parallel(inject=["conn", "pool", "cache"])
+    def use(x, conn, pool, cache):
+        return x, conn.id, pool.id, cache.kind
+
+    results = [h.get() for h in [use(i) for i in range(3)]]
+    t.assert_equal([r[0] for r in results], [0, 1, 2])
+    t.assert_equal(len({r[1] for r in results}), 3)  # fresh per task
+    t.assert_equal(len({r[2] for r in results}), 1)  # shared
+    t.assert_equal({r[3] for r in results}, {"worker"})
+
+    # Task-scoped resources are closed as each task finishes; worker ones when its thread exits
+    time.sleep(0.2)
+    t.assert_equal(sorted(k for k, _ in closed), ["task"] * 3 + ["worker"] * 3)
+
+    # Explicit keyword arguments win over injection
+    t.assert_equal(use(9, conn=Resource("explicit")).get()[0], 9)
+
+    @mp.parallel(inject=["missing"])
+    def needs_missing(missing):
+        return missing
+
+    t.assert_raises(RuntimeError, lambda: needs_missing().get())
+    t.assert_raises(ValueError, lambda: mp.provide("x", lambda: 1, scope="request"))
+    t.assert_raises(ValueError, lambda: mp.parallel(isolation="subinterpreter", inject=["conn"]))
+
+    # Replacing a provider closes the instance made by the old one
+    mp.provide("pool", lambda: Resource("global"), scope="global")
+    t.assert_true(("global", results[0][2]) in closed, "replaced global resource was not closed")
+
+
+@runner.test("Actor - Serialized method calls on a dedicated thread")
+def test_actor(t):
+    import threading
+
+    @mp.actor
+    class Counter:
+        def __init__(self, start=0):
+            self.value = start
+            self.threads = set()
+
+        def add(self, n=1):
+            current = self.value
+            time.sleep(0.001)  # would lose updates if calls overlapped
+            self.value = current + n
+            self.threads.add(threading.get_ident())
+            return self.value
+
+        def fail(self):
+            raise KeyError("nope")
+
+        def snapshot(self):
+            return self.value, len(self.threads)
+
+        def thread_id(self):
+            return threading.get_ident()
+
+    counter = Counter(start=10)
+    handles = [counter.add() for _ in range(50)] + [counter.add(n=5)]
+    t.assert_equal(handles[-1].get(), 65)
+    value, threads = counter.snapshot().get()
+    t.assert_equal((value, threads), (65, 1))
+    t.assert_true(counter.thread_id().get() != threading.get_ident(), "method ran on the caller's thread")
+
+    t.assert_raises(RuntimeError, lambda: counter.fail().get())
+    t.assert_equal(counter.add().get(), 66)  # a failing call doesn't kill the actor
+    t.assert_raises(AttributeError, lambda: counter.missing)
+    t.assert_raises(AttributeError, lambda: counter._private)
+
+    t.assert_true(counter.stop(timeout=5.0))
+    t.assert_raises(RuntimeError, lambda: counter.add())
+    t.assert_raises(TypeError, lambda: mp.actor(lambda: None))
+
+
+@runner.test("Simulate - Projected delays for given limits")
+def test_simulate(t):
+    # 10 one-second tasks arriving together, 4 at a time: waves start at 0, 1 and 2 seconds
+    burst = {"count": 10, "duration": 1.0}
+    report = mp.simulate(burst, max_concurrent=4)
+    t.assert_equal(report["tasks"], 10)
+    t.assert_equal(report["makespan"], 3.0)
+    t.assert_equal(report["peak_concurrency"], 4)
+    t.assert_equal(report["peak_queue_length"], 6)
+    t.assert_equal(report["queue_delay"]["max"], 2.0)
+    t.assert_equal(report["queue_delay"]["p50"], 1.0)
+
+    # Without limits nothing queues
+    unlimited = mp.simulate(burst)
+    t.assert_equal((unlimited["peak_concurrency"], unlimited["queue_delay"]["max"]), (10, 0.0))
+
+    # Recorded workloads: declared costs against a capacity
+    recorded = [
+        {"arrival": 0.0, "duration": 2.0, "cpu": 2},
+        {"arrival": 0.5, "duration": 1.0, "cpu": 2},
+        {"arrival": 0.5, "duration": 1.0, "cpu": 1, "priority": 5},
+    ]
+    report = mp.simulate(recorded, cpu=3)
+    t.assert_equal(report["queue_delay"]["max"], 1.5)
+
+    t.assert_raises(ValueError, lambda: mp.simulate([{"arrival": 1.0}]))
+    t.assert_raises(ValueError, lambda: mp.simulate([{"duration": -1.0}]))
+    t.assert_raises(ValueError, lambda: mp.simulate(burst, max_concurrent=0))
+
+
+@runner.test("Sync fallback - Tasks run inline behind the handle API")
+def test_sync_fallback(t):
+    import os
+    import subprocess
+
+    t.assert_equal(mp.get_execution_mode(), "threads")
+
+    code = """
+import threading
+import makeparallel as mp
+
+@mp.parallel
+def where(x):
+    return threading.get_ident(), x * 2
+
+@mp.parallel_priority
+def boom():
+    raise KeyError("boom")
+
+handle = where(21)
+assert handle.is_ready()
+assert handle.get() == (threading.get_ident(), 42)
+assert handle.then(lambda r: r[1] + 1).get() == 43
+try:
+    boom(priority=1).get()
+    raise AssertionError("priority failure was not propagated")
+except Exception as e:
+    assert "boom" in str(e), e
+assert list(mp.parallel_imap(lambda x: x + 1, range(5), prefetch=2)) == [1, 2, 3, 4, 5]
+assert mp.get_active_task_count() == 0
+print(mp.get_execution_mode())
+"""
+    child = subprocess.run(
+        [sys.executable, "-c", code],
+        capture_output=True,
+        text=True,
+        env=dict(os.environ, MAKEPARALLEL_SYNC_FALLBACK="1", PYTHONPATH=os.pathsep.join(sys.path)),
+        timeout=60,
+    )
+    t.assert_equal(child.returncode, 0, child.stderr)
+    t.assert_equal(child.stdout.strip(), "sync")
+
+
+def process_sum_squares(n):
+    """Module-level so worker processes can unpickle it."""
+    if n < 0:
+        raise ValueError("n must be non-negative")
+    return sum(i * i for i in range(n))
+
+
+@runner.test("Process pool - Pickled calls in worker processes")
+def test_process_pool(t):
+    import functools
+    import os
+
+    with mp.ProcessPool(max_workers=2) as pool:
+        t.assert_equal(pool.max_workers, 2)
+        handles = [pool.submit(process_sum_squares, n) for n in (10, 100, 1000)]
+        t.assert_equal([h.get() for h in handles], [process_sum_squares(n) for n in (10, 100, 1000)])
+
+        pids = {pool.submit(os.getpid).get() for _ in range(4)}
+        t.assert_true(os.getpid() not in pids, "calls should run outside this process")
+
+        failing = pool.submit(process_sum_squares, -1)
+        try:
+            failing.get()
+            t.assert_true(False, "worker exception was not propagated")
+        except RuntimeError as e:
+            t.assert_true("ValueError" in str(e), str(e))
+
+        # Timeouts stop the worker process; the pool replaces it
+        slow = pool.submit(functools.partial(time.sleep, 5), timeout=0.3)
+        start = time.time()
+        t.assert_raises(RuntimeError, slow.get)
+        t.assert_true(time.time() - start < 2, "timed out worker was not stopped")
+        t.assert_equal(pool.submit(pow, 2, 10).get(), 1024)
+
+        # Arguments are pickled at submit time, so errors surface immediately
+        import threading
+        t.assert_raises(TypeError, lambda: pool.submit(process_sum_squares, threading.Lock()))
+
+    t.assert_raises(RuntimeError, lambda: pool.submit(pow, 2, 3))
+
+    # Per-task working directory and environment, undone before the next task
+    import os
+    import shutil
+    import tempfile
+    read_env = functools.partial(eval, "dict(__import__('os').environ)")
+    read_secret = functools.partial(eval, "__import__('os').environ.get('MP_TASK_SECRET')")
+    with mp.ProcessPool(max_workers=1) as pool, tempfile.TemporaryDirectory() as workdir:
+        t.assert_equal(pool.submit(os.getcwd, cwd=workdir).get(), os.path.realpath(workdir))
+        t.assert_equal(pool.submit(os.getcwd).get(), os.getcwd())
+        t.assert_equal(pool.submit(read_secret, env={"MP_TASK_SECRET": "s3"}).get(), "s3")
+        t.assert_equal(pool.submit(read_secret).get(), None)
+        t.assert_equal(pool.submit(read_env, env={"ONLY": "1"}, inherit_env=False).get(), {"ONLY": "1"})
+        t.assert_true("PATH" in pool.submit(read_env).get())
+        t.assert_raises(FileNotFoundError, lambda: pool.submit(os.getcwd, cwd=os.path.join(workdir, "missing")))
+
+    square_sum = mp.parallel_process(process_sum_squares)
+    t.assert_equal(square_sum(50).get(), process_sum_squares(50))
+
+
+@runner.test("Timer - On class method")
+def test_timer_class_method(t):
+    class Calculator:
+        @mp.timer
+        def add(self, a, b):
+            return a + b
+
+    calc = Calculator()
+    result = calc.add(10, 20)
+    t.assert_equal(result, 30)
+
+
+@runner.test("CallCounter - On class method")
+def test_callcounter_class_method(t):
+    class Counter:
+        @mp.CallCounter
+        def method(self, x):
+            return x * 2
+
+    obj = Counter()
+    obj.method(5)
+    obj.method(6)
+
+    t.assert_equal(obj.method.call_count, 2)
+
+
+@runner.test("Parallel - On class method")
+def test_parallel_class_method(t):
+    class Worker:
+        def __init__(self, factor):
+            self.factor = factor
+
+        @mp.parallel
+        def process(self, x):
+            return x * self.factor
+
+    worker = Worker(3)
+    handle = worker.process(7)
+    result = handle.get()
+    t.assert_equal(result, 21)
+
+
+# =============================================================================
+# TEST 12: Combined Decorators
+# =============================================================================
+@runner.test("Combined - Memoize + Timer")
+def test_combined_memoize_timer(t):
+    @mp.memoize
+    @mp.timer
+    def cached_slow(x):
+        return x**2
+
+    result1 = cached_slow(5)
+    result2 = cached_slow(5)
+
+    t.assert_equal(result1, 25)
+    t.assert_equal(result2, 25)
+
+
+# =============================================================================
+# TEST 13: Edge Cases
+# =============================================================================
+@runner.test("Edge Case - Empty arguments")
+def test_edge_empty_args(t):
+    @mp.parallel
+    def no_args():
+        return "success"
+
+    handle = no_args()
+    result = handle.get()
+    t.assert_equal(result, "success")
+
+
+@runner.test("Edge Case - None return value")
+def test_edge_none_return(t):
+    @mp.parallel
+    def returns_none():
+        return None
+
+    handle = returns_none()
+    result = handle.get()
+    t.assert_equal(result, None)
+
+
+@runner.test("Edge Case - Large data structure")
+def test_edge_large_data(t):
+    @mp.parallel
+    def create_list(n):
+        return list(range(n))
+
+    handle = create_list(1000)
+    result = handle.get()
+    t.assert_equal(len(result), 1000)
+    t.assert_equal(result[0], 0)
+    t.assert_equal(result[-1], 999)
+
+
+# =============================================================================
+# TEST 14: Advanced Features
+# =============================================================================
+@runner.test("Advanced - AsyncHandle.cancel()")
+def test_advanced_cancel(t):
+    @mp.parallel
+    def long_running_task():
+        time.sleep(2)
+        return "should not complete"
+
+    handle = long_running_task()
+    time.sleep(0.1)
+    handle.cancel()
+
+    t.assert_true(handle.is_cancelled(), "handle.is_cancelled() should be True.")
+    # Note: Once cancelled, the task is marked complete and get() will return the cached error
+    # Since we can't interrupt Python's time.sleep(), the thread continues but is marked cancelled
+    t.assert_true(handle.is_ready(), "handle.is_ready() should be True after cancel.")
+
+
+@runner.test("Advanced - Task timeout")
+def test_advanced_timeout(t):
+    @mp.parallel
+    def task_that_will_timeout():
+        time.sleep(1)
+        return "should have timed out"
+
+    handle = task_that_will_timeout(timeout=0.5)
+    t.assert_raises(Exception, handle.get)
+
+
+@runner.test("Advanced - Timeout interrupts running Python code")
+def test_advanced_timeout_interrupts(t):
+    observed = []
+
+    @mp.parallel
+    def spin_forever():
+        try:
+            while True:
+                pass
+        except mp.TaskTimeout:
+            observed.append("interrupted")
+            raise
+
+    start = time.time()
+    handle = spin_forever(timeout=0.3)
+    t.assert_raises(RuntimeError, handle.get)
+    t.assert_true(time.time() - start < 2, "busy loop was not interrupted")
+    t.assert_equal(observed, ["interrupted"])
+    t.assert_true(issubclass(mp.TaskTimeout, TimeoutError))
+
+    # A task finishing in time is never interrupted afterwards
+    @mp.parallel
+    def quick():
+        return sum(range(1000))
+
+    t.assert_equal(quick(timeout=5).get(), sum(range(1000)))
+
+
+@runner.test("Advanced - Cooperative cancellation")
+def test_advanced_cooperative_cancellation(t):
+    progress = {"steps": 0}
+
+    @mp.parallel
+    def polling_loop():
+        for _ in range(1000):
+            mp.check_cancelled()
+            progress["steps"] += 1
+            time.sleep(0.01)
+        return "finished"
+
+    handle = polling_loop()
+    time.sleep(0.1)
+    handle.cancel()
+    time.sleep(0.1)
+    steps = progress["steps"]
+    time.sleep(0.1)
+    t.assert_equal(progress["steps"], steps, "loop kept running after cancel()")
+    t.assert_true(steps < 1000)
+
+    seen = []
+
+    @mp.parallel
+    def with_token(n, cancel_token=None):
+        seen.append(type(cancel_token).__name__)
+        while not cancel_token.is_cancelled:
+            time.sleep(0.01)
+        cancel_token.raise_if_cancelled()
+
+    handle = with_token(1)
+    time.sleep(0.05)
+    handle.cancel()
+    time.sleep(0.05)
+    t.assert_equal(seen, ["CancellationToken"])
+
+    # Outside tasks the helpers are inert
+    mp.check_cancelled()
+    t.assert_equal(mp.current_cancellation_token(), None)
+    token = mp.CancellationToken()
+    token.cancel()
+    t.assert_raises(mp.TaskCancelled, token.raise_if_cancelled)
+
+
+@runner.test("Advanced - Task workspaces")
+def test_advanced_task_workspaces(t):
+    import os
+    import shutil
+    import tempfile
+
+    @mp.parallel
+    def use_workspace(fail):
+        workdir = mp.task_workspace()
+        t.assert_equal(mp.task_workspace(), workdir)
+        if os.name == "posix":
+            t.assert_equal(os.stat(workdir).st_mode & 0o777, 0o700)
+        (workdir / "out.txt").write_text("data")
+        if fail:
+            raise ValueError("boom")
+        return workdir
+
+    workdir = use_workspace(False).get()
+    time.sleep(0.05)
+    t.assert_true(not workdir.exists(), "workspace of a finished task was kept")
+
+    base = tempfile.mkdtemp()
+    mp.configure_task_workspaces(keep_failed=True, base_dir=base)
+    try:
+        t.assert_raises(Exception, use_workspace(True).get)
+        time.sleep(0.05)
+        kept = os.listdir(base)
+        t.assert_equal(len(kept), 1)
+        t.assert_true(os.path.exists(os.path.join(base, kept[0], "out.txt")))
+    finally:
+        mp.configure_task_workspaces()
+        shutil.rmtree(base)
+
+    t.assert_raises(RuntimeError, mp.task_workspace)
+    t.assert_raises(FileNotFoundError, lambda: mp.configure_task_workspaces(base_dir="/nonexistent/dir"))
+
+
+@runner.test("Advanced - Load limit")
+def test_advanced_load_limit(t):
+    @mp.parallel
+    def quick(x):
+        return x * 2
+
+    # Limits the host cannot exceed never delay submission
+    mp.configure_load_limit(max_load_avg=1e9, max_cpu_percent=100.0)
+    try:
+        t.assert_equal(quick(21).get(), 42)
+    finally:
+        mp.configure_load_limit()
+
+    t.assert_raises(ValueError, lambda: mp.configure_load_limit(max_cpu_percent=0))
+    t.assert_raises(ValueError, lambda: mp.configure_load_limit(max_load_avg=-1.0))
+
+
+@runner.test("Advanced - GPU memory admission")
+def test_advanced_gpu_memory_admission(t):
+    import types
+
+    # Stand-in for pynvml reporting a device that is 95% full
+    memory = types.SimpleNamespace(used=95, total=100)
+    fake_nvml = types.ModuleType("pynvml")
+    fake_nvml.nvmlInit = lambda: None
+    fake_nvml.nvmlDeviceGetHandleByIndex = lambda index: index
+    fake_nvml.nvmlDeviceGetMemoryInfo = lambda handle: memory
+
+    @mp.parallel
+    def infer(x):
+        return x + 1
+
+    saved = sys.modules.get("pynvml")
+    sys.modules["pynvml"] = fake_nvml
+    try:
+        mp.configure_gpu_memory_limit(0, 80.0)
+
+        # Untagged tasks are not held back
+        t.assert_equal(infer(1).get(), 2)
+
+        def free_memory():
+            time.sleep(0.3)
+            memory.used = 50
+
+        import threading
+        threading.Thread(target=free_memory).start()
+        start = time.time()
+        handle = infer(2, tags=["gpu"])
+        t.assert_true(time.time() - start >= 0.25, "GPU task started while memory was full")
+        t.assert_equal(handle.get(), 3)
+    finally:
+        mp.configure_gpu_memory_limit(0, None)
+        if saved is None:
+            del sys.modules["pynvml"]
+        else:
+            sys.modules["pynvml"] = saved
+
+    t.assert_raises(ValueError, lambda: mp.configure_gpu_memory_limit(0, 150.0))
+
+
+@runner.test("Advanced - Task cost admission")
+def test_advanced_task_cost_admission(t):
+    import threading
+
+    lock = threading.Lock()
+    running = {"now": 0, "peak": 0}
+
+    @mp.parallel(cpu=1, memory_mb=100)
+    def job(x):
+        with lock:
+            running["now"] += 1
+            running["peak"] = max(running["peak"], running["now"])
+        time.sleep(0.1)
+        with lock:
+            running["now"] -= 1
+        return x
+
+    mp.configure_capacity(cpu=2)
+    try:
+        t.assert_equal(mp.gather([job(i) for i in range(6)]), list(range(6)))
+        t.assert_equal(running["peak"], 2)
+        usage = mp.get_capacity_usage()
+        t.assert_equal(usage["running"], 0)
+        t.assert_equal(usage["cpu_capacity"], 2.0)
+    finally:
+        mp.configure_capacity()
+
+    t.assert_raises(ValueError, lambda: mp.configure_capacity(cpu=0))
+    t.assert_raises(ValueError, lambda: mp.parallel(cpu=-1))
+
+
+@runner.test("Advanced - TaskGroup structured concurrency")
+def test_advanced_task_group(t):
+    finished = []
+
+    @mp.parallel
+    def work(x):
+        time.sleep(0.05)
+        finished.append(x)
+        return x
+
+    with mp.TaskGroup() as tg:
+        handles = [tg.submit(work, i) for i in range(3)]
+        tg.submit(lambda: finished.append("plain"))
+    # Every task has been joined once the block exits
+    t.assert_equal(sorted(map(str, finished)), ["0", "1", "2", "plain"])
+    t.assert_equal([h.get() for h in handles], [0, 1, 2])
+
+    stopped = []
+
+    @mp.parallel
+    def long_poll():
+        for _ in range(200):
+            mp.check_cancelled()
+            time.sleep(0.01)
+        stopped.append("ran to completion")
+
+    @mp.parallel
+    def fail_fast():
+        time.sleep(0.05)
+        raise ValueError("boom")
+
+    start = time.time()
+    try:
+        with mp.TaskGroup() as tg:
+            sibling = tg.submit(long_poll)
+            failing = tg.submit(fail_fast)
+        t.assert_true(False, "TaskGroup did not raise")
+    except ExceptionGroup as group:
+        t.assert_equal([exc.task_id for exc in group.exceptions], [failing.get_task_id()])
+    t.assert_true(time.time() - start < 1.5, "sibling was not cancelled")
+    t.assert_equal(stopped, [])
+    t.assert_true(sibling.is_ready())
+
+    # An exception in the block cancels the group and propagates unchanged
+    def body_fails():
+        with mp.TaskGroup() as tg:
+            tg.submit(long_poll)
+            raise KeyError("body")
+
+    t.assert_raises(KeyError, body_fails)
+
+
+@runner.test("Advanced - Per-attempt timeout vs total_timeout")
+def test_advanced_attempt_vs_total_timeout(t):
+    attempts = []
+
+    @mp.parallel
+    @mp.retry_backoff(max_attempts=5, initial_delay=0.05)
+    def slow_attempts():
+        attempts.append(1)
+        time.sleep(0.2)
+        return "too slow"
+
+    # Every attempt overruns timeout=0.1; total_timeout stops further retries
+    handle = slow_attempts(timeout=0.1, total_timeout=0.5)
+    t.assert_raises(RuntimeError, handle.get)
+    t.assert_equal(len(attempts), 2, "Retries should stop once the total budget is spent")
+    t.assert_equal(handle.get_timeout(), 0.1)
+    t.assert_equal(handle.get_total_timeout(), 0.5)
+
+    # timeout= limits each attempt, so later attempts within it are not cut short
+    flaky_attempts = []
+
+    @mp.parallel
+    @mp.retry_backoff(max_attempts=3, initial_delay=0.05)
+    def flaky_but_in_time():
+        flaky_attempts.append(1)
+        time.sleep(0.2)
+        if len(flaky_attempts) < 3:
+            raise ConnectionError("flap")
+        return "done"
+
+    t.assert_equal(flaky_but_in_time(timeout=0.3).get(), "done")
+    t.assert_equal(len(flaky_attempts), 3)
+
+    @mp.parallel
+    def quick():
+        return "fast"
+
+    t.assert_equal(quick(timeout=1.0, total_timeout=2.0).get(), "fast")
+    t.assert_raises(ValueError, lambda: quick(total_timeout=-1))
+
+
+@runner.test("Retry Backoff - Custom schedule and decorrelated jitter")
+def test_retry_backoff_schedule(t):
+    scheduled = []
+
+    def schedule(attempt):
+        scheduled.append(attempt)
+        return 0.01 * attempt
+
+    calls = {"count": 0}
+
+    @mp.retry_backoff(max_attempts=4, schedule=schedule)
+    def flaky():
+        calls["count"] += 1
+        if calls["count"] < 3:
+            raise ValueError("not yet")
+        return "ok"
+
+    t.assert_equal(flaky(), "ok")
+    t.assert_equal(scheduled, [1, 2])
+
+    @mp.retry_backoff(max_attempts=3, backoff="decorrelated_jitter", initial_delay=0.01, max_delay=0.05)
+    def always_fails():
+        raise ValueError("nope")
+
+    t.assert_raises(ValueError, always_fails)
+    t.assert_raises(TypeError, lambda: mp.retry_backoff(schedule=42))
+    t.assert_raises(ValueError, lambda: mp.retry_backoff(backoff="exponentail"))
+    t.assert_raises(ValueError, lambda: mp.retry_backoff(initial_delay=float("nan")))
+    t.assert_raises(ValueError, lambda: mp.retry_backoff(max_delay=float("inf")))
+    t.assert_raises(ValueError, lambda: mp.retry_backoff(initial_delay=-1.0))
+
+
+@runner.test("Retry - Exception filtering and jitter")
+def test_retry_filtering(t):
+    calls = []
+
+    @mp.retry(max_retries=3, retry_on=(ConnectionError, TimeoutError))
+    def typo():
+        calls.append("typo")
+        raise NameError("undefined_name")
+
+    # Programming errors are raised at once instead of being retried
+    t.assert_raises(NameError, typo)
+    t.assert_equal(calls, ["typo"])
+
+    @mp.retry_backoff(max_attempts=5, initial_delay=0.01, retry_on=OSError, giveup_on=PermissionError, jitter="full")
+    def locked():
+        calls.append("locked")
+        raise PermissionError("read-only")
+
+    t.assert_raises(PermissionError, locked)
+    t.assert_equal(calls.count("locked"), 1)
+
+    attempts = {"count": 0}
+
+    @mp.retry_backoff(max_attempts=4, initial_delay=0.01, max_delay=0.05, retry_on=ConnectionError, jitter="decorrelated")
+    def transient():
+        attempts["count"] += 1
+        if attempts["count"] < 3:
+            raise ConnectionResetError("reset")
+        return "ok"
+
+    t.assert_equal(transient(), "ok")
+    t.assert_equal(attempts["count"], 3)
+    t.assert_raises(ValueError, lambda: mp.retry(jitter="sometimes"))
+    t.assert_raises(TypeError, lambda: mp.retry_backoff(retry_on="ValueError"))
+    t.assert_raises(TypeError, lambda: mp.retry(giveup_on=(KeyError, 3)))
+
+
+@runner.test("Retry Backoff - on_retry and on_giveup hooks")
+def test_retry_hooks(t):
+    retries, giveups = [], []
+
+    @mp.retry_backoff(
+        max_attempts=3,
+        initial_delay=0.01,
+        on_retry=lambda attempt, exc, delay: retries.append((attempt, str(exc), delay)),
+        on_giveup=lambda exc: giveups.append(type(exc)),
+    )
+    def always_fails():
+        raise ConnectionError("down")
+
+    t.assert_raises(ConnectionError, always_fails)
+    t.assert_equal(retries, [(1, "down", 0.01), (2, "down", 0.02)])
+    t.assert_equal(giveups, [ConnectionError])
+
+    # Errors excluded by retry_on give up straight away; a failing hook is only logged
+    def broken_hook(exc):
+        raise RuntimeError("hook bug")
+
+    @mp.retry_backoff(max_attempts=3, initial_delay=0.01, retry_on=ConnectionError, on_giveup=broken_hook)
+    def typo():
+        raise NameError("undefined")
+
+    t.assert_raises(NameError, typo)
+    t.assert_raises(TypeError, lambda: mp.retry_backoff(on_retry="print"))
+
+
+@runner.test("Retry - State observation")
+def test_retry_state(t):
+    mp.reset_retry_state()
+    calls = {"count": 0}
+
+    @mp.retry(max_retries=3)
+    def flapping_dependency():
+        calls["count"] += 1
+        if calls["count"] < 3:
+            raise ConnectionError("flap")
+        return "up"
+
+    t.assert_equal(flapping_dependency(), "up")
+    state = mp.get_retry_state("flapping_dependency")
+    t.assert_equal(state.total_retries, 2)
+    t.assert_equal(state.total_failures, 2)
+    t.assert_equal(state.total_successes, 1)
+    t.assert_equal(state.consecutive_failures, 0)
+    t.assert_equal(state.next_attempt_at, None)
+    t.assert_true("flap" in state.last_error)
+    t.assert_true("flapping_dependency" in mp.get_all_retry_states())
+    t.assert_equal(mp.get_retry_state("never_called"), None)
+
+
+@runner.test("Advanced - Batch cancel by predicate and tags")
+def test_advanced_batch_cancel(t):
+    @mp.parallel
+    def sleeper(secs):
+        time.sleep(secs)
+        return secs
+
+    keep = sleeper(0.5, tags=["critical"])
+    drop_a = sleeper(0.5, tags=["bulk"])
+    drop_b = sleeper(0.5, tags=["bulk"])
+    t.assert_equal(keep.get_tags(), ["critical"])
+    # Let the tasks start so cancelled ones stay registered while they drain
+    time.sleep(0.1)
+
+    summary = mp.cancel_tasks(lambda info: "bulk" in info["tags"])
+    t.assert_equal(summary["cancelled"], 2)
+    t.assert_true(drop_a.is_cancelled() and drop_b.is_cancelled())
+    t.assert_true(not keep.is_cancelled())
+
+    other = sleeper(0.5)
+    summary = mp.cancel_all(except_tags=["critical"])
+    t.assert_true(other.is_cancelled())
+    t.assert_true(not keep.is_cancelled())
+    t.assert_equal(summary["cancelled"], 1)
+    # Already-cancelled tasks still draining count as finishing, not cancelled
+    t.assert_true(summary["already_finishing"] >= 2)
+    t.assert_equal(keep.get(), 0.5)
+
+
+@runner.test("Advanced - Event webhooks")
+def test_advanced_webhooks(t):
+    import json
+    import threading
+    from http.server import BaseHTTPRequestHandler, HTTPServer
+
+    received = []
+
+    class Receiver(BaseHTTPRequestHandler):
+        def do_POST(self):
+            body = self.rfile.read(int(self.headers["Content-Length"]))
+            received.append(json.loads(body))
+            self.send_response(204)
+            self.end_headers()
+
+        def log_message(self, *args):
+            pass
+
+    server = HTTPServer(("127.0.0.1", 0), Receiver)
+    threading.Thread(target=server.serve_forever, daemon=True).start()
+    url = f"http://127.0.0.1:{server.server_port}/hook"
+
+    t.assert_raises(ValueError, lambda: mp.register_webhook(url, events=["bogus"]))
+    mp.register_webhook(url, events=["task_failed", "sla_violated"])
+    try:
+        @mp.parallel
+        def flaky(x):
+            if x < 0:
+                raise ValueError("negative")
+            time.sleep(x)
+            return x
+
+        t.assert_equal(flaky(0).get(), 0)
+        t.assert_raises(RuntimeError, lambda: flaky(-1).get())
+        t.assert_raises(RuntimeError, lambda: flaky(0.3, timeout=0.1).get())
+
+        deadline = time.time() + 5
+        while len(received) < 3 and time.time() < deadline:
+            time.sleep(0.05)
+        events = sorted(e["event"] for e in received)
+        t.assert_equal(events, ["sla_violated", "task_failed", "task_failed"])
+        t.assert_true(all(e["function"] == "flaky" for e in received))
+    finally:
+        t.assert_true(mp.unregister_webhook(url))
+        server.shutdown()
+
+
+@runner.test("Advanced - ZeroMQ publisher")
+def test_advanced_zmq_publisher(t):
+    t.assert_raises(
+        ValueError, lambda: mp.enable_zmq_publisher("tcp://127.0.0.1:*", topic="bogus")
+    )
+    try:
+        mp.enable_zmq_publisher("tcp://127.0.0.1:*", topic="tag")
+    except RuntimeError as e:
+        # Built without the optional `zmq` feature
+        t.assert_true("zmq" in str(e))
+        t.assert_equal(mp.disable_zmq_publisher(), False)
+    else:
+        t.assert_equal(mp.disable_zmq_publisher(), True)
+
+
+@runner.test("Advanced - Global error handler")
+def test_advanced_global_error_handler(t):
+    failures = []
+
+    @mp.parallel
+    def boom(x):
+        raise KeyError(x)
+
+    t.assert_raises(TypeError, lambda: mp.set_global_error_handler(42))
+    mp.set_global_error_handler(failures.append)
+    try:
+        boom("never-retrieved")
+        handle = boom("retrieved")
+        t.assert_raises(RuntimeError, handle.get)
+
+        deadline = time.time() + 2
+        while len(failures) < 2 and time.time() < deadline:
+            time.sleep(0.02)
+        t.assert_equal(len(failures), 2)
+        t.assert_true(all(isinstance(f, mp.TaskError) for f in failures))
+        t.assert_equal({f.error_type for f in failures}, {"KeyError"})
+        t.assert_true(any(f.task_id == handle.get_task_id() for f in failures))
+    finally:
+        t.assert_equal(mp.set_global_error_handler(None), failures.append)
+
+
+@runner.test("Advanced - Unobserved failure warning")
+def test_advanced_unobserved_failure(t):
+    import gc
+    import warnings
+
+    @mp.parallel
+    def boom(x):
+        raise ValueError(x)
+
+    with warnings.catch_warnings(record=True) as caught:
+        warnings.simplefilter("always")
+
+        # Retrieved errors are not reported
+        handle = boom("seen")
+        t.assert_raises(RuntimeError, handle.get)
+        del handle
+
+        # Handle dropped after the task failed
+        handle = boom("dropped-late")
+        handle.wait(2.0)
+        del handle
+
+        # Handle dropped before the task failed (fire-and-forget)
+        boom("dropped-early")
+
+        deadline = time.time() + 2
+        while len(caught) < 2 and time.time() < deadline:
+            gc.collect()
+            time.sleep(0.02)
+
+    messages = [str(w.message) for w in caught if w.category is RuntimeWarning]
+    t.assert_equal(len(messages), 2)
+    t.assert_true(all("never retrieved" in m for m in messages))
+    t.assert_true(any("dropped-late" in m for m in messages))
+    t.assert_true(any("dropped-early" in m for m in messages))
+    t.assert_true(not any("seen" in m for m in messages))
+
+
+@runner.test("Advanced - Forward results to a queue or pipe")
+def test_advanced_forward_to(t):
+    import multiprocessing
+
+    @mp.parallel
+    def square(x):
+        if x < 0:
+            raise ValueError("negative")
+        time.sleep(0.05)
+        return x * x
+
+    queue = multiprocessing.Queue()
+    for x in range(4):
+        square(x).forward_to(queue)
+    t.assert_equal(sorted(queue.get(timeout=2) for _ in range(4)), [0, 1, 4, 9])
+
+    # Forwarding after completion delivers immediately
+    done = square(5)
+    t.assert_equal(done.get(), 25)
+    receiver, sender = multiprocessing.Pipe(duplex=False)
+    done.forward_to(sender)
+    t.assert_true(receiver.poll(2))
+    t.assert_equal(receiver.recv(), 25)
+
+    square(-1).forward_to(sender)
+    t.assert_true(receiver.poll(2))
+    err = receiver.recv()
+    t.assert_true(isinstance(err, RuntimeError) and "negative" in str(err))
+
+    t.assert_raises(TypeError, lambda: square(1).forward_to(object()))
+
+
+@runner.test("Advanced - Task metadata")
+def test_advanced_metadata(t):
+    @mp.parallel
+    def task_with_metadata(x):
+        return x
+
+    handle = task_with_metadata(123)
+    handle.set_metadata("user_id", "user-abc")
+    handle.set_metadata("request_id", "req-123")
+    metadata = handle.get_all_metadata()
+
+    t.assert_equal(metadata.get("user_id"), "user-abc")
+    t.assert_equal(metadata.get("request_id"), "req-123")
+
+
+@runner.test("Advanced - Thread pool configuration")
+def test_advanced_threadpool_config(t):
+    mp.configure_thread_pool(num_threads=4)
+    info = mp.get_thread_pool_info()
+    t.assert_true(info["configured"])
+    # Note: num_threads info may vary based on implementation
+
+
+@runner.test("Advanced - Free-threading detection")
+def test_advanced_free_threading(t):
+    gil_enabled = getattr(sys, "_is_gil_enabled", lambda: True)()
+    t.assert_equal(mp.is_free_threaded(), not gil_enabled)
+
+    info = mp.get_thread_pool_info()
+    t.assert_equal(info["free_threaded"], mp.is_free_threaded())
+    t.assert_true(info["priority_workers"] >= 1)
+
+    # parallel_map keeps input order however calls are grouped onto threads
+    items = list(range(103))
+    t.assert_equal(mp.parallel_map(lambda x: x * 2, items), [x * 2 for x in items])
+    t.assert_equal(mp.parallel_map(lambda x: x, []), [])
+
+
+@runner.test("Advanced - @mp.parallel_priority")
+def test_advanced_priority(t):
+    # Start the priority worker
+    mp.start_priority_worker()
+
+    @mp.parallel_priority
+    def priority_task(value):
+        time.sleep(0.1)
+        return value
+
+    # Submit tasks with different priorities
+    # Higher priority values should execute first
+    low_prio_handle = priority_task(1, priority=1)
+    medium_prio_handle = priority_task(5, priority=5)
+    high_prio_handle = priority_task(10, priority=10)
+
+    # Wait for all to complete
+    high_result = high_prio_handle.get()
+    medium_result = medium_prio_handle.get()
+    low_result = low_prio_handle.get()
+
+    # Just verify they all completed successfully
     t.assert_equal(high_result, 10)
     t.assert_equal(medium_result, 5)
     t.assert_equal(low_result, 1)
@@ -593,6 +3090,174 @@ def test_advanced_priority(t):
     mp.stop_priority_worker()
 
 
+@runner.test("Advanced - Earliest-deadline-first priority tasks")
+def test_priority_deadlines(t):
+    order = []
+
+    @mp.parallel_priority
+    def job(name, seconds=0.0):
+        time.sleep(seconds)
+        order.append(name)
+        return name
+
+    # Let a worker stopped by an earlier test exit, so one worker runs the queue
+    time.sleep(0.05)
+    blocker = job("blocker", 0.3)
+    time.sleep(0.05)
+    now = time.time()
+    handles = [
+        job("urgent-priority", priority=100),
+        job("late", deadline=now + 10),
+        job("soon", deadline=now + 5),
+        job("missed", deadline=now + 0.05),
+    ]
+    t.assert_equal(blocker.get(), "blocker")
+    t.assert_raises(mp.DeadlineExceeded, handles[3].get)
+    t.assert_equal([h.get() for h in handles[:3]], ["urgent-priority", "late", "soon"])
+    if not mp.is_free_threaded():
+        t.assert_equal(order, ["blocker", "soon", "late", "urgent-priority"])
+    t.assert_true(issubclass(mp.DeadlineExceeded, TimeoutError))
+    t.assert_raises(ValueError, lambda: job("bad", deadline=float("nan")))
+
+    mp.stop_priority_worker()
+
+
+@runner.test("Advanced - Re-prioritizing queued priority tasks")
+def test_priority_reprioritize(t):
+    order = []
+
+    @mp.parallel_priority
+    def job(name, seconds=0.0):
+        time.sleep(seconds)
+        order.append(name)
+        return name
+
+    time.sleep(0.05)
+    blocker = job("blocker", 0.3)
+    time.sleep(0.05)
+    first = job("first", priority=10)
+    second = job("second", priority=5)
+    third = job("third", priority=1)
+
+    t.assert_true(third.set_priority(20))
+    t.assert_true(mp.reprioritize(second.get_task_id(), 15))
+    t.assert_equal(mp.reprioritize("task_does_not_exist", 1), False)
+    t.assert_equal([h.get() for h in (blocker, first, second, third)], ["blocker", "first", "second", "third"])
+    if not mp.is_free_threaded():
+        t.assert_equal(order, ["blocker", "third", "second", "first"])
+    t.assert_equal(blocker.set_priority(99), False)
+
+    mp.stop_priority_worker()
+
+
+@runner.test("Advanced - Priority classes with reserved workers")
+def test_priority_classes(t):
+    import threading
+
+    release = threading.Event()
+    mp.configure_priority_classes({"critical": 1, "bulk": 1}, default="bulk")
+    try:
+        @mp.parallel_priority
+        def bulk(seconds):
+            release.wait(seconds)
+            return "bulk"
+
+        @mp.parallel_priority(priority_class="critical")
+        def alert(name):
+            return name
+
+        backlog = [bulk(5.0, priority=100) for _ in range(3)]
+        time.sleep(0.05)
+        stats = mp.get_priority_class_stats()
+        t.assert_equal(stats["bulk"]["busy"], 1)
+        t.assert_equal(stats["bulk"]["queue_depth"], 2)
+        t.assert_equal(stats["critical"]["workers"], 1)
+
+        start = time.time()
+        t.assert_equal(alert("page", priority=1).get(), "page")
+        t.assert_true(time.time() - start < 1.0)
+        t.assert_true(mp.reprioritize(backlog[2].get_task_id(), 200))
+
+        t.assert_raises(ValueError, lambda: alert("x", priority_class="missing"))
+        t.assert_raises(ValueError, lambda: mp.configure_priority_classes({"none": 0}))
+        t.assert_raises(ValueError, lambda: mp.configure_priority_classes({"a": 1}, default="b"))
+
+        release.set()
+        t.assert_equal([h.get() for h in backlog], ["bulk"] * 3)
+    finally:
+        release.set()
+        mp.configure_priority_classes(None)
+    t.assert_equal(mp.get_priority_class_stats(), {})
+
+
+@runner.test("Advanced - Adaptive concurrency limit")
+def test_adaptive_concurrency(t):
+    t.assert_raises(ValueError, lambda: mp.enable_adaptive_concurrency(backoff_ratio=1.5))
+    t.assert_raises(ValueError, lambda: mp.enable_adaptive_concurrency(min_limit=4, max_limit=2))
+
+    @mp.parallel
+    def job(seconds):
+        time.sleep(seconds)
+        return seconds
+
+    mp.enable_adaptive_concurrency(initial_limit=2, min_limit=1, max_limit=6, latency_threshold=0.05)
+    try:
+        t.assert_equal(mp.get_concurrency_limit()["limit"], 2)
+        [h.get() for h in [job(0.005) for _ in range(20)]]
+        info = mp.get_concurrency_limit()
+        t.assert_true(info["adaptive"])
+        t.assert_true(info["increases"] >= 1)
+        grown = info["limit"]
+        t.assert_true(2 < grown <= 6)
+
+        [h.get() for h in [job(0.1) for _ in range(3)]]
+        info = mp.get_concurrency_limit()
+        t.assert_true(info["decreases"] >= 1)
+        t.assert_true(info["limit"] < grown)
+    finally:
+        t.assert_true(mp.disable_adaptive_concurrency())
+    t.assert_equal(mp.get_concurrency_limit()["adaptive"], False)
+    t.assert_equal(mp.disable_adaptive_concurrency(), False)
+
+
+@runner.test("Advanced - Autoscaling priority workers")
+def test_priority_autoscaling(t):
+    import threading
+
+    t.assert_raises(ValueError, lambda: mp.configure_autoscaling(min_threads=4, max_threads=2))
+    t.assert_raises(ValueError, lambda: mp.configure_autoscaling(max_threads=2, queue_per_thread=0))
+
+    threads = set()
+
+    @mp.parallel_priority
+    def io_job(x):
+        threads.add(threading.get_native_id())
+        time.sleep(0.1)
+        return x
+
+    mp.configure_autoscaling(min_threads=1, max_threads=4, queue_per_thread=1, max_wait=0.05, scale_down_after=0.1)
+    try:
+        t.assert_true(mp.get_autoscaling_stats()["enabled"])
+        handles = [io_job(i) for i in range(12)]
+        t.assert_equal([h.get() for h in handles], list(range(12)))
+        t.assert_true(1 < len(threads) <= 4)
+        stats = mp.get_autoscaling_stats()
+        t.assert_true(stats["scale_ups"] >= 1)
+        t.assert_true(stats["target_workers"] <= 4)
+
+        # Idle queue: shrinks back to min_threads one step at a time
+        deadline = time.time() + 3
+        while mp.get_autoscaling_stats()["workers"] > 1 and time.time() < deadline:
+            time.sleep(0.05)
+        stats = mp.get_autoscaling_stats()
+        t.assert_equal(stats["workers"], 1)
+        t.assert_true(stats["scale_downs"] >= 1)
+    finally:
+        mp.configure_autoscaling()
+        mp.stop_priority_worker()
+    t.assert_equal(mp.get_autoscaling_stats()["enabled"], False)
+
+
 @runner.test("Advanced - @profiled and metrics")
 def test_advanced_profiling(t):
     mp.reset_metrics()
@@ -613,6 +3278,1235 @@ def test_advanced_profiling(t):
     t.assert_true("profiled_func" in all_metrics)
 
 
+@runner.test("Advanced - Sampling profiler per task")
+def test_task_profile(t):
+    def spin(seconds):
+        end = time.time() + seconds
+        while time.time() < end:
+            pass
+
+    def wait(seconds):
+        time.sleep(seconds)
+
+    @mp.parallel
+    def workload():
+        spin(0.2)
+        wait(0.1)
+        return "done"
+
+    handle = workload(profile=True)
+    t.assert_equal(handle.get(), "done")
+    profile = handle.get_profile()
+    t.assert_equal(profile[0]["function"], "spin")
+    t.assert_true(profile[0]["self_time"] > 0.1)
+    by_name = {frame["function"]: frame for frame in profile}
+    t.assert_true(by_name["workload"]["total_time"] >= by_name["spin"]["total_time"])
+    t.assert_true("wait" in by_name)
+    t.assert_equal(len(handle.get_profile(limit=1)), 1)
+
+    plain = workload()
+    plain.get()
+    t.assert_equal(plain.get_profile(), None)
+
+
+@runner.test("Advanced - tracemalloc allocation tracking")
+def test_trace_malloc(t):
+    import tracemalloc
+
+    @mp.parallel(trace_malloc=True)
+    def build(n):
+        rows = [str(i) * 10 for i in range(n)]
+        return rows
+
+    handle = build(20000)
+    rows = handle.get()
+    t.assert_equal(len(rows), 20000)
+    sites = handle.get_allocations()
+    t.assert_true(sites[0]["size_diff"] > 500000)
+    t.assert_true(sites[0]["count_diff"] > 0)
+    t.assert_true(sites[0]["filename"].endswith("test_all.py"))
+    t.assert_equal(len(handle.get_allocations(limit=1)), 1)
+    t.assert_true(not tracemalloc.is_tracing())
+
+    @mp.parallel
+    def plain():
+        return 1
+
+    untraced = plain()
+    untraced.get()
+    t.assert_equal(untraced.get_allocations(), None)
+    t.assert_raises(ValueError, lambda: mp.parallel(isolation="subinterpreter", trace_malloc=True))
+
+
+@runner.test("Parallel - run_in_worker without a decorator")
+def test_run_in_worker(t):
+    import functools
+    import threading
+
+    handle = mp.run_in_worker(lambda: threading.current_thread().name)
+    t.assert_true(handle.get() != threading.current_thread().name)
+
+    scale = functools.partial(lambda x, factor, offset=0: x * factor + offset, factor=3)
+    t.assert_equal(mp.run_in_worker(scale, 2, offset=1).get(), 7)
+    t.assert_equal(mp.run_in_worker(sorted, [3, 1, 2], reverse=True).get(), [3, 2, 1])
+
+    # Keyword arguments all belong to the function, even names @parallel would claim
+    t.assert_equal(mp.run_in_worker(lambda timeout: timeout, timeout=5).get(), 5)
+
+    failing = mp.run_in_worker(lambda: 1 / 0)
+    t.assert_raises(Exception, lambda: failing.get())
+    t.assert_raises(TypeError, lambda: mp.run_in_worker(42))
+
+
+@runner.test("Parallel - bind() keeps the function name")
+def test_bind(t):
+    import pickle
+
+    def resize(image, width, height=100):
+        return (image, width, height)
+
+    mp.reset_metrics()
+    thumb = mp.bind(resize, width=64, height=64)
+    t.assert_equal(thumb.__name__, "resize")
+    t.assert_equal(thumb.__qualname__, resize.__qualname__)
+    t.assert_equal(thumb("a.png"), ("a.png", 64, 64))
+    t.assert_equal(thumb("a.png", height=32), ("a.png", 64, 32))
+    t.assert_equal(mp.bind(resize, "b.png")(10), ("b.png", 10, 100))
+    t.assert_equal(thumb.keywords, {"width": 64, "height": 64})
+
+    t.assert_equal(mp.run_in_worker(thumb, "c.png").get(), ("c.png", 64, 64))
+    t.assert_equal(mp.parallel(thumb)("d.png").get(), ("d.png", 64, 64))
+    t.assert_equal(mp.get_all_metrics()["resize"]["completed_tasks"], 2)
+
+    restored = pickle.loads(pickle.dumps(mp.bind(max, 3, key=abs)))
+    t.assert_equal(restored(-5), -5)
+    t.assert_equal(repr(mp.bind(max, 3, key=abs)), "bind(<built-in function max>, 3, key=<built-in function abs>)")
+    t.assert_raises(TypeError, lambda: mp.bind(None))
+
+
+@runner.test("Advanced - Process-scoped memory limit")
+def test_process_memory_limit(t):
+    @mp.parallel
+    def work():
+        return 1
+
+    try:
+        # Any real process is over a 1-byte cap, whatever else the host is running
+        mp.configure_memory_limit(scope="process", max_bytes=1)
+        t.assert_raises(RuntimeError, lambda: work())
+
+        mp.configure_memory_limit(99.9, scope="process", max_bytes=2**50)
+        t.assert_equal(work().get(), 1)
+
+        t.assert_raises(ValueError, lambda: mp.configure_memory_limit(max_bytes=1))
+        t.assert_raises(ValueError, lambda: mp.configure_memory_limit(50.0, scope="host"))
+    finally:
+        mp.configure_memory_limit()
+    t.assert_equal(work().get(), 1)
+
+
+@runner.test("Metrics - Names for lambdas, partials and callables")
+def test_callable_names(t):
+    import functools
+
+    class Resizer:
+        def __call__(self, x):
+            return x
+
+        @mp.parallel
+        def shrink(self, x):
+            return x
+
+    def scale(x, factor):
+        return x * factor
+
+    mp.reset_metrics()
+    mp.run_in_worker(lambda: 1).get()
+    mp.run_in_worker(functools.partial(scale, factor=2), 1).get()
+    mp.run_in_worker(Resizer(), 1).get()
+    Resizer().shrink(1).get()
+
+    @mp.parallel(name="custom_parallel")
+    def first():
+        return 1
+
+    @mp.profiled(name="custom_profiled")
+    def second():
+        return 2
+
+    @mp.parallel_priority(name="custom_priority")
+    def third():
+        return 3
+
+    @mp.parallel_with_deps(name="custom_deps")
+    def fourth():
+        return 4
+
+    t.assert_equal(first().get(), 1)
+    t.assert_equal(second(), 2)
+    t.assert_equal(third(priority=1).get(), 3)
+    t.assert_equal(fourth().get(), 4)
+
+    names = set(mp.get_all_metrics())
+    t.assert_true("unknown" not in names)
+    t.assert_true(any(n.startswith("test_callable_names.<locals>.<lambda>:") for n in names))
+    t.assert_true("partial(scale)" in names)
+    t.assert_true(any(n.endswith("test_callable_names.<locals>.Resizer") for n in names))
+    t.assert_true("shrink" in names)
+    for name in ["custom_parallel", "custom_profiled", "custom_priority", "custom_deps"]:
+        t.assert_equal(mp.get_all_metrics()[name]["completed_tasks"], 1)
+
+
+@runner.test("Advanced - Container limits")
+def test_container_limits(t):
+    limits = mp.get_container_limits()
+    t.assert_equal(
+        sorted(limits), ["cgroup_version", "cpu_limit", "memory_limit_bytes", "memory_usage_bytes"]
+    )
+    t.assert_true(limits["cgroup_version"] in (None, 1, 2))
+    if limits["memory_limit_bytes"] is None:
+        t.assert_equal(limits["memory_usage_bytes"], None)
+    else:
+        t.assert_true(limits["memory_usage_bytes"] > 0)
+    if limits["cpu_limit"] is not None:
+        t.assert_true(limits["cpu_limit"] > 0)
+
+
+@runner.test("Advanced - Hung task watchdog")
+def test_watchdog(t):
+    seen = []
+
+    @mp.parallel
+    def slow_job():
+        for _ in range(30):
+            time.sleep(0.02)
+        return "done"
+
+    @mp.parallel
+    def stuck():
+        while True:
+            time.sleep(0.01)
+
+    try:
+        mp.configure_watchdog(max_runtime=0.2, action="callback", callback=seen.append, check_interval=0.05)
+        t.assert_equal(slow_job().get(), "done")
+        t.assert_equal(len(seen), 1)
+        t.assert_equal(seen[0]["name"], "slow_job")
+        t.assert_true("slow_job" in seen[0]["stack"])
+
+        mp.configure_watchdog(max_runtime=0.2, action="cancel", check_interval=0.05)
+        t.assert_raises(RuntimeError, lambda: stuck().get())
+        t.assert_equal(mp.get_watchdog_stats()["hung_tasks"], 2)
+
+        t.assert_raises(TypeError, lambda: mp.configure_watchdog(action="callback"))
+        t.assert_raises(ValueError, lambda: mp.configure_watchdog(action="restart"))
+    finally:
+        mp.configure_watchdog(max_runtime=None)
+    t.assert_equal(mp.get_watchdog_stats()["enabled"], False)
+
+
+@runner.test("Advanced - Submission site capture")
+def test_submission_sites(t):
+    import inspect
+    failures = []
+
+    @mp.parallel
+    def fails():
+        raise ValueError("bad")
+
+    t.assert_equal(mp.parallel(lambda: 1)().get_metadata("submitted_from"), None)
+    mp.capture_submission_sites()
+    mp.set_global_error_handler(failures.append)
+    try:
+        line = inspect.currentframe().f_lineno + 1
+        handle = fails()
+        t.assert_raises(RuntimeError, handle.get)
+        t.assert_true(handle.get_metadata("submitted_from").endswith("test_all.py:%d" % line))
+
+        deadline = time.time() + 2
+        while not failures and time.time() < deadline:
+            time.sleep(0.02)
+        t.assert_equal(failures[0].submitted_from, handle.get_metadata("submitted_from"))
+        t.assert_true("submitted from" in str(failures[0]))
+    finally:
+        mp.set_global_error_handler(None)
+        mp.capture_submission_sites(False)
+
+
+@runner.test("Advanced - Control socket")
+def test_control_socket(t):
+    import json
+    import os
+    import subprocess
+    from makeparallel.__main__ import request
+
+    @mp.parallel
+    def stoppable(cancel_token=None):
+        while not cancel_token.is_cancelled:
+            time.sleep(0.01)
+
+    address = mp.start_control_server()
+    try:
+        t.assert_raises(RuntimeError, mp.start_control_server)
+        if os.name == "posix":
+            t.assert_equal(os.stat(address).st_mode & 0o777, 0o600)
+            staged = [name for name in os.listdir(os.path.dirname(address)) if name.startswith(".makeparallel-ctl")]
+            t.assert_equal(staged, [])
+        handle = stoppable(tags=["batch"])
+        tasks = request(address, "list")["tasks"]
+        t.assert_true(any(task["task_id"] == handle.get_task_id() for task in tasks))
+
+        t.assert_equal(request(address, "cancel", tag="batch")["cancelled"], 1)
+        t.assert_true(handle.is_cancelled())
+        t.assert_equal(request(address, "cancel")["ok"], False)
+        t.assert_equal(request(address, "reboot")["ok"], False)
+
+        t.assert_equal(request(address, "pause")["paused"], True)
+        t.assert_equal(request(address, "list")["paused"], True)
+        t.assert_equal(request(address, "resume")["paused"], False)
+        t.assert_true("metrics" in request(address, "metrics"))
+
+        out = subprocess.run(
+            [sys.executable, "-m", "makeparallel", "ctl", "--pid", str(os.getpid()), "--json", "list"],
+            capture_output=True, text=True, timeout=30,
+        )
+        t.assert_equal(out.returncode, 0)
+        t.assert_equal(json.loads(out.stdout)["ok"], True)
+    finally:
+        t.assert_equal(mp.stop_control_server(), True)
+    t.assert_equal(mp.stop_control_server(), False)
+    t.assert_raises(ValueError, lambda: mp.start_control_server("0.0.0.0:0"))
+
+    if os.name == "posix":
+        # A path that is already taken is left alone
+        import tempfile
+        taken = os.path.join(tempfile.mkdtemp(), "ctl.sock")
+        with open(taken, "w") as f:
+            f.write("not a socket")
+        t.assert_raises(FileExistsError, lambda: mp.start_control_server(taken))
+        with open(taken) as f:
+            t.assert_equal(f.read(), "not a socket")
+
+
+@runner.test("Advanced - Encrypted persistence")
+def test_encrypted_persistence(t):
+    import os
+    import pickle
+    import tempfile
+
+    @mp.memoize
+    def secret(x):
+        return "patient-%d" % x
+
+    def double(x):
+        return x * 2
+
+    directory = tempfile.mkdtemp()
+    cache_path = os.path.join(directory, "cache.bin")
+    checkpoint = os.path.join(directory, "progress.ckpt")
+    try:
+        t.assert_raises(ValueError, lambda: mp.configure_persistence(encryption_key=b"short"))
+        mp.configure_persistence(encryption_key=b"k" * 32)
+        secret(7)
+        t.assert_true(mp.cache_dump(cache_path) >= 1)
+        with open(cache_path, "rb") as f:
+            t.assert_true(b"patient-7" not in f.read())
+        t.assert_true(mp.cache_load(cache_path) >= 1)
+        t.assert_equal(mp.parallel_map(double, list(range(10)), checkpoint=checkpoint), [x * 2 for x in range(10)])
+        t.assert_equal(mp.parallel_map(double, list(range(10)), checkpoint=checkpoint), [x * 2 for x in range(10)])
+
+        mp.configure_persistence(encryption_key=b"w" * 16)
+        t.assert_raises(ValueError, lambda: mp.cache_load(cache_path))
+        t.assert_raises(ValueError, lambda: mp.parallel_map(double, list(range(10)), checkpoint=checkpoint))
+        mp.configure_persistence(encryption_key=None)
+        t.assert_raises(ValueError, lambda: mp.cache_load(cache_path))
+        t.assert_raises(ValueError, lambda: mp.parallel_map(double, list(range(10)), checkpoint=checkpoint))
+
+        # With a key set, unencrypted files are refused rather than unpickled unchecked
+        plain_cache = os.path.join(directory, "plain.bin")
+        with open(plain_cache, "wb") as f:
+            f.write(pickle.dumps({}))
+        with open(os.path.join(directory, "swapped.ckpt"), "wb") as f:
+            f.write(pickle.dumps({"rows": 0}))
+        mp.configure_task_checkpoints(directory)
+        t.assert_equal(mp.cache_load(plain_cache), 0)
+        t.assert_equal(mp.load_checkpoint("swapped"), {"rows": 0})
+        mp.configure_persistence(encryption_key=b"k" * 32)
+        t.assert_raises(ValueError, lambda: mp.cache_load(plain_cache))
+        t.assert_raises(ValueError, lambda: mp.load_checkpoint("swapped"))
+    finally:
+        mp.configure_persistence(encryption_key=None)
+        mp.configure_task_checkpoints(None)
+
+
+@runner.test("Advanced - Task registry")
+def test_task_registry(t):
+    import threading
+
+    release = threading.Event()
+
+    @mp.parallel_priority
+    def blocker(step):
+        mp.report_progress(0.5)
+        release.wait(5)
+        return step
+
+    mp.start_priority_worker()
+    try:
+        first = blocker(1, priority=1)
+        deadline = time.time() + 2
+        while not mp.list_tasks(status="running") and time.time() < deadline:
+            time.sleep(0.01)
+        # The priority worker runs one task at a time, so the second waits its turn
+        second = blocker(2, priority=1)
+
+        running = mp.get_task_status(first.get_task_id())
+        t.assert_equal(running["state"], "running")
+        t.assert_equal(running["name"], "blocker")
+        t.assert_true(running["run_time"] is not None)
+        queued = mp.get_task_status(second.get_task_id())
+        t.assert_equal(queued["state"], "queued")
+        t.assert_equal(second.status(), mp.TaskStatus.QUEUED)
+        t.assert_equal(queued["run_time"], None)
+        queued_ids = [task["task_id"] for task in mp.list_tasks(status="queued")]
+        t.assert_true(second.get_task_id() in queued_ids)
+        t.assert_equal(mp.list_tasks(tag="no-such-tag"), [])
+        t.assert_raises(ValueError, lambda: mp.list_tasks(status="done"))
+
+        while mp.get_task_status(first.get_task_id())["progress"] != 0.5 and time.time() < deadline:
+            time.sleep(0.01)
+        t.assert_equal(mp.get_task_status(first.get_task_id())["progress"], 0.5)
+        release.set()
+        t.assert_equal(first.get(), 1)
+        t.assert_equal(second.get(), 2)
+        t.assert_equal(mp.get_task_status(first.get_task_id()), None)
+    finally:
+        release.set()
+        mp.stop_priority_worker()
+
+
+@runner.test("Advanced - TaskStatus on handles")
+def test_task_status(t):
+    import threading
+
+    release = threading.Event()
+    S = mp.TaskStatus
+
+    @mp.parallel_with_deps
+    def gate():
+        release.wait(5)
+        return 1
+
+    @mp.parallel_with_deps
+    def after(x=0):
+        return x
+
+    @mp.parallel
+    def fails():
+        raise ValueError("boom")
+
+    @mp.parallel
+    def spin():
+        while True:
+            time.sleep(0.001)
+
+    @mp.parallel
+    def stoppable(cancel_token=None):
+        while not cancel_token.is_cancelled:
+            time.sleep(0.01)
+        return "ignored cancel"
+
+    try:
+        first = gate()
+        waiting = after(depends_on=[first])
+        deadline = time.time() + 2
+        while first.status() != S.RUNNING and time.time() < deadline:
+            time.sleep(0.01)
+        t.assert_equal(first.status(), S.RUNNING)
+        t.assert_equal(waiting.status(), S.PENDING)
+        t.assert_true(not waiting.status().is_final)
+        release.set()
+        waiting.get()
+        t.assert_equal(first.status(), S.SUCCEEDED)
+        t.assert_true(first.status().is_final)
+
+        failed = fails()
+        t.assert_raises(RuntimeError, failed.get)
+        t.assert_equal(failed.status(), S.FAILED)
+
+        slow = spin(timeout=0.2)
+        t.assert_raises(RuntimeError, slow.get)
+        t.assert_equal(slow.status(), S.TIMED_OUT)
+
+        cancelled = stoppable()
+        cancelled.cancel()
+        t.assert_equal(cancelled.status(), S.CANCELLED)
+        time.sleep(0.1)
+        t.assert_equal(cancelled.status(), S.CANCELLED)
+        t.assert_equal(str(S.QUEUED), "TaskStatus.QUEUED")
+    finally:
+        release.set()
+
+
+@runner.test("Advanced - Reconfiguring pools while busy")
+def test_live_pool_reconfiguration(t):
+    import threading
+
+    release = threading.Event()
+
+    def hold(x):
+        release.wait(5)
+        return x
+
+    mp.configure_thread_pool(num_threads=2)
+    try:
+        old = [mp.parallel_pool(hold)(i) for i in range(2)]
+        time.sleep(0.1)
+        mp.configure_thread_pool(num_threads=3)
+        info = mp.get_thread_pool_info()
+        t.assert_equal(info["current_num_threads"], 3)
+        t.assert_equal((info["draining_pools"], info["draining_tasks"]), (1, 2))
+        # New work is not stuck behind the old pool's busy threads
+        t.assert_equal(mp.parallel_pool(lambda x: x * 2)(21).get(), 42)
+        release.set()
+        t.assert_equal([h.get() for h in old], [0, 1])
+        time.sleep(0.05)
+        t.assert_equal(mp.get_thread_pool_info()["draining_pools"], 0)
+    finally:
+        release.set()
+        mp.configure_thread_pool()
+
+    release.clear()
+    pool = mp.Pool("test-resize", 2, max_queue=1)
+
+    @mp.parallel(pool="test-resize")
+    def blocked(x):
+        release.wait(5)
+        return x
+
+    try:
+        running = []
+        for i in range(3):
+            running.append(blocked(i))
+            time.sleep(0.05)
+        t.assert_raises(mp.PoolFull, lambda: blocked(3))
+        pool.set_max_queue(None)
+        t.assert_equal(pool.max_queue, None)
+        running.append(blocked(3))
+
+        pool.resize(4)
+        time.sleep(0.1)
+        t.assert_equal((pool.num_threads, pool.stats()["running"]), (4, 4))
+        t.assert_raises(ValueError, lambda: pool.resize(0))
+
+        pool.resize(1)
+        release.set()
+        t.assert_equal([h.get() for h in running], [0, 1, 2, 3])
+        deadline = time.time() + 3
+        while pool.stats()["threads"] != 1 and time.time() < deadline:
+            time.sleep(0.05)
+        t.assert_equal(pool.stats()["threads"], 1)
+
+        pool.configure_autoscaling(min_threads=1, max_threads=2)
+        t.assert_raises(RuntimeError, lambda: pool.resize(2))
+    finally:
+        release.set()
+        pool.shutdown()
+
+
+@runner.test("Advanced - Pausing and resuming tasks")
+def test_task_pause_resume(t):
+    import threading
+
+    done = []
+    started = threading.Event()
+
+    @mp.parallel
+    def batch(steps):
+        started.set()
+        for i in range(steps):
+            mp.checkpoint()
+            done.append(i)
+            time.sleep(0.02)
+        return len(done)
+
+    handle = batch(1000)
+    started.wait(2)
+    t.assert_true(handle.pause())
+    t.assert_true(handle.is_paused())
+    time.sleep(0.1)
+    held_at = len(done)
+    time.sleep(0.2)
+    t.assert_equal(len(done), held_at)
+    t.assert_equal([task["state"] for task in mp.list_tasks(status="paused")], ["paused"])
+
+    t.assert_true(handle.resume())
+    t.assert_equal(handle.resume(), False)
+    time.sleep(0.1)
+    t.assert_true(len(done) > held_at)
+
+    # Cancelling a paused task ends it without a resume
+    handle.pause()
+    time.sleep(0.1)
+    handle.cancel()
+    deadline = time.time() + 2
+    while mp.get_task_status(handle.get_task_id()) is not None and time.time() < deadline:
+        time.sleep(0.02)
+    t.assert_equal(mp.get_task_status(handle.get_task_id()), None)
+    t.assert_equal(handle.status(), mp.TaskStatus.CANCELLED)
+    t.assert_equal(handle.pause(), False)
+
+    # Pausing a task as it finishes never leaves it marked paused
+    @mp.parallel
+    def quick(x):
+        return x
+
+    for i in range(50):
+        handle = quick(i)
+        handle.pause()
+        handle.get()
+        deadline = time.time() + 2
+        while mp.get_task_status(handle.get_task_id()) is not None and time.time() < deadline:
+            time.sleep(0.001)
+        t.assert_equal(handle.pause(), False)
+        t.assert_equal(handle.is_paused(), False)
+
+    mp.checkpoint()  # no-op outside a task
+
+
+@runner.test("Advanced - Built-in selftest")
+def test_selftest(t):
+    report = mp.selftest("quick")
+    t.assert_true(report["ok"])
+    t.assert_equal(report["anomalies"], [])
+    t.assert_equal(
+        [check["name"] for check in report["checks"]],
+        ["submission", "cancellation", "dependencies", "backpressure", "parallel_map", "shutdown"],
+    )
+    t.assert_true(all(check["outcome"] == "passed" for check in report["checks"]))
+    t.assert_equal(report["environment"]["execution_mode"], mp.get_execution_mode())
+    t.assert_raises(ValueError, lambda: mp.selftest("thorough"))
+
+    # The shutdown check ran in a child process, so this runtime still accepts tasks
+    @mp.parallel
+    def still_running():
+        return "ok"
+
+    t.assert_equal(still_running().get(), "ok")
+
+
+@runner.test("Advanced - Task checkpoints for resumable work")
+def test_task_checkpoints(t):
+    import os
+    import pickle
+    import tempfile
+    import threading
+
+    reached = threading.Event()
+
+    @mp.parallel
+    def count_to(n, key, stop_at=None):
+        state = mp.load_checkpoint(key, default={"next": 0})
+        resumed_at = state["next"]
+        for i in range(resumed_at, n):
+            if i == stop_at:
+                reached.set()
+                while True:
+                    mp.check_cancelled()
+                    time.sleep(0.01)
+            state["next"] = i + 1
+            mp.save_checkpoint(state, key)
+        return resumed_at
+
+    # Cancelled part-way: the checkpoint is kept and the resubmission resumes from it
+    first = count_to(10, "job-1", stop_at=6)
+    t.assert_true(reached.wait(2))
+    first.cancel()
+    time.sleep(0.1)
+    t.assert_equal(mp.load_checkpoint("job-1")["next"], 6)
+    t.assert_equal(count_to(10, "job-1").get(), 6)
+    # ...and deleted once the task succeeds
+    t.assert_equal(mp.load_checkpoint("job-1"), None)
+
+    # Keyed by task ID by default, and kept when the task fails
+    @mp.parallel
+    def fails_after_saving():
+        mp.save_checkpoint({"step": 3})
+        t.assert_equal(mp.load_checkpoint(), {"step": 3})
+        raise ValueError("crashed")
+
+    failed = fails_after_saving()
+    t.assert_raises(Exception, failed.get)
+    time.sleep(0.05)
+    t.assert_equal(failed.get_checkpoint(), {"step": 3})
+    t.assert_true(mp.clear_checkpoint(failed.get_task_id()))
+    t.assert_equal(mp.clear_checkpoint(failed.get_task_id()), False)
+
+    t.assert_raises(RuntimeError, lambda: mp.save_checkpoint({"x": 1}))
+    t.assert_raises(Exception, lambda: mp.save_checkpoint(lambda: None, "unpicklable"))
+
+    # Persisted to disk, optionally encrypted
+    with tempfile.TemporaryDirectory() as directory:
+        mp.configure_task_checkpoints(directory)
+        try:
+            mp.save_checkpoint({"rows": 1200}, "nightly/etl")
+            t.assert_equal(os.listdir(directory), ["nightly%2Fetl.ckpt"])
+            t.assert_equal(mp.load_checkpoint("nightly/etl"), {"rows": 1200})
+
+            # Task IDs repeat in later processes, so a file left as task_0.ckpt is never
+            # picked up by an unrelated task: on disk the key is required
+            with open(os.path.join(directory, "task_0.ckpt"), "wb") as f:
+                f.write(pickle.dumps({"secret_progress": 42}))
+
+            @mp.parallel
+            def keyless():
+                return mp.load_checkpoint(default="fresh")
+
+            try:
+                keyless().get()
+                t.assert_true(False, "load_checkpoint() without a key read a checkpoint file")
+            except RuntimeError as e:
+                t.assert_true("needs a key" in str(e))
+            t.assert_raises(ValueError, lambda: mp.save_checkpoint({"x": 1}))
+
+            mp.configure_persistence(encryption_key=b"k" * 32)
+            mp.save_checkpoint({"rows": 1300}, "nightly/etl")
+            with open(os.path.join(directory, "nightly%2Fetl.ckpt"), "rb") as f:
+                t.assert_true(f.read().startswith(b"MPSEAL1\n"))
+            t.assert_equal(mp.load_checkpoint("nightly/etl"), {"rows": 1300})
+            mp.configure_persistence(encryption_key=b"x" * 32)
+            t.assert_raises(ValueError, lambda: mp.load_checkpoint("nightly/etl"))
+        finally:
+            mp.configure_persistence(encryption_key=None)
+            mp.configure_task_checkpoints(None)
+        t.assert_raises(FileNotFoundError, lambda: mp.configure_task_checkpoints(os.path.join(directory, "missing")))
+
+
+@runner.test("Advanced - Durable queue")
+def test_durable_queue(t):
+    import math
+    import os
+    import pickle
+    import sqlite3
+    import tempfile
+
+    if not hasattr(mp, "DurableQueue"):
+        # Built without the optional `durable-queue` feature
+        return
+
+    path = os.path.join(tempfile.mkdtemp(), "jobs.db")
+    # Jobs left behind by an earlier process: two queued, one cut off while running
+    db = sqlite3.connect(path)
+    db.execute("CREATE TABLE jobs (id INTEGER PRIMARY KEY AUTOINCREMENT, module TEXT NOT NULL, "
+               "qualname TEXT NOT NULL, payload BLOB NOT NULL, state TEXT NOT NULL, "
+               "submitted_at REAL NOT NULL, error TEXT)")
+    for args, state in [((2, 10), "queued"), ((3, 2), "queued"), ((5, 5), "running")]:
+        db.execute("INSERT INTO jobs (module, qualname, payload, state, submitted_at) VALUES (?, ?, ?, ?, 0)",
+                   ("math", "pow", pickle.dumps((args, None)), state))
+    db.commit()
+    db.close()
+
+    with mp.DurableQueue(path, workers=2) as queue:
+        t.assert_equal(sorted(h.get() for h in queue.recovered), [9.0, 1024.0])
+        t.assert_equal(queue.submit(math.gcd, 12, 18).get(), 6)
+        t.assert_raises(ValueError, lambda: queue.submit(lambda: None))
+        failing = queue.submit(math.sqrt, -1)
+        t.assert_raises(Exception, lambda: failing.get())
+        time.sleep(0.1)
+        stats = queue.stats()
+        t.assert_equal((stats["queued"], stats["interrupted"], stats["failed"]), (0, 1, 1))
+
+    # With a persistence key the arguments are sealed to their job's row
+    sealed_path = os.path.join(tempfile.mkdtemp(), "sealed.db")
+    mp.configure_persistence(encryption_key=b"q" * 32)
+    try:
+        with mp.DurableQueue(sealed_path) as queue:
+            t.assert_raises(Exception, lambda: queue.submit(math.sqrt, -12345.5).get())
+        db = sqlite3.connect(sealed_path)
+        (payload,) = db.execute("SELECT payload FROM jobs").fetchone()
+        t.assert_true(payload.startswith(b"MPJOBE1\n"))
+        t.assert_true(__import__("struct").pack(">d", -12345.5) not in payload)
+        # Requeued as if the process had stopped before running it
+        db.execute("UPDATE jobs SET state = 'queued', id = 7")
+        db.commit()
+        db.close()
+        with mp.DurableQueue(sealed_path) as queue:
+            t.assert_equal(len(queue.recovered), 0)
+            t.assert_equal(queue.stats()["failed"], 1)
+
+        db = sqlite3.connect(sealed_path)
+        db.execute("UPDATE jobs SET state = 'queued', id = 1, payload = ?", (payload,))
+        db.commit()
+        db.close()
+        with mp.DurableQueue(sealed_path) as queue:
+            (handle,) = queue.recovered
+            t.assert_raises(Exception, lambda: handle.get())
+
+        # An unencrypted row slipped into the file is not unpickled while a key is set
+        db = sqlite3.connect(sealed_path)
+        db.execute("INSERT INTO jobs (module, qualname, payload, state, submitted_at) VALUES (?, ?, ?, 'queued', 0)",
+                   ("math", "pow", pickle.dumps(((2, 3), None))))
+        db.commit()
+        db.close()
+        with mp.DurableQueue(sealed_path) as queue:
+            t.assert_equal(len(queue.recovered), 0)
+        db = sqlite3.connect(sealed_path)
+        (error,) = db.execute("SELECT error FROM jobs WHERE qualname = 'pow'").fetchone()
+        db.execute("DELETE FROM jobs WHERE qualname = 'pow'")
+        db.commit()
+        db.close()
+        t.assert_true("not encrypted" in error)
+        # Decrypted and run: the stored error is sqrt's own
+        db = sqlite3.connect(sealed_path)
+        (error,) = db.execute("SELECT error FROM jobs").fetchone()
+        db.close()
+        t.assert_true("math domain error" in error)
+    finally:
+        mp.configure_persistence(encryption_key=None)
+
+
+@runner.test("Advanced - Parallel callable objects and partials")
+def test_parallel_callable_kinds(t):
+    import functools
+
+    class Scale:
+        def __init__(self, factor):
+            self.factor = factor
+
+        def __call__(self, x):
+            return x * self.factor
+
+    def add(a, b):
+        return a + b
+
+    class Host:
+        base = 100
+        scale = mp.parallel(Scale(3))
+        add_one = mp.parallel(functools.partial(add, 1))
+
+        @mp.parallel
+        def offset(self, x):
+            return self.base + x
+
+    host = Host()
+    # Only the plain function binds to the instance, as it would undecorated
+    t.assert_equal(host.offset(5).get(), 105)
+    t.assert_equal(host.scale(5).get(), 15)
+    t.assert_equal(host.add_one(5).get(), 6)
+
+    t.assert_equal(Host.scale.__name__, "%s.%s" % (__name__, Scale.__qualname__))
+    t.assert_equal(host.scale(1).get_name(), Host.scale.__name__)
+    t.assert_equal(Host.add_one.__name__, "partial(add)")
+    t.assert_equal(Host.add_one.__module__, __name__)
+    t.assert_equal(Host.add_one.__wrapped__.func, add)
+    t.assert_equal(Host.offset.__name__, "offset")
+    t.assert_equal(Host.offset.__qualname__.split(".")[-2:], ["Host", "offset"])
+    t.assert_equal(mp.parallel(name="adder")(add).__qualname__, "adder")
+
+
+@runner.test("Advanced - Namespaced call options")
+def test_namespaced_call_options(t):
+    @mp.parallel(namespaced=True)
+    def fetch(url, timeout=30, tags=()):
+        return (url, timeout, tags)
+
+    # Plain names reach the function; only _mp_ options configure the task
+    handle = fetch("a", timeout=5, tags=("x",), _mp_tags=["batch"])
+    t.assert_equal(handle.get(), ("a", 5, ("x",)))
+    t.assert_equal(handle.get_tags(), ["batch"])
+
+    @mp.parallel(namespaced=True)
+    def slow(timeout):
+        time.sleep(timeout)
+
+    t.assert_raises(Exception, lambda: slow(2.0, _mp_timeout=0.2).get())
+
+    # Without namespaced, both spellings are options, and giving both is an error
+    @mp.parallel
+    def plain(x):
+        return x
+
+    t.assert_equal(plain(1, _mp_tags=["p"]).get_tags(), ["p"])
+    t.assert_equal(plain(2, tags=["q"]).get_tags(), ["q"])
+    t.assert_raises(TypeError, lambda: plain(3, tags=["a"], _mp_tags=["b"]))
+    t.assert_raises(TypeError, lambda: plain(4, _mp_timeout="soon"))
+
+    @mp.parallel_with_deps(namespaced=True)
+    def first(timeout=None):
+        return timeout
+
+    @mp.parallel_with_deps(namespaced=True)
+    def step(deps, timeout=None):
+        return (deps[0], timeout)
+
+    t.assert_equal(step(_mp_depends_on=[first(timeout=1)], timeout=2).get(), (1, 2))
+
+    @mp.parallel_priority(namespaced=True)
+    def ranked(priority):
+        return priority
+
+    mp.start_priority_worker()
+    try:
+        t.assert_equal(ranked(priority="high", _mp_priority=5).get(), "high")
+    finally:
+        mp.stop_priority_worker()
+
+
+@runner.test("Advanced - Dependency results are not retained")
+def test_dependency_results_released(t):
+    import gc
+    import weakref
+
+    class Payload:
+        pass
+
+    refs = []
+
+    @mp.parallel_with_deps
+    def produce():
+        payload = Payload()
+        refs.append(weakref.ref(payload))
+        return payload
+
+    @mp.parallel_with_deps
+    def consume(deps):
+        return type(deps[0]).__name__
+
+    source = produce()
+    readers = [consume(depends_on=[source]) for _ in range(3)]
+    t.assert_equal([r.get() for r in readers], ["Payload"] * 3)
+    del source, readers
+    # References dropped on worker threads are released the next time a task runs
+    mp.parallel(lambda: None)().get()
+    gc.collect()
+    t.assert_true(refs[0]() is None)
+
+
+@runner.test("Advanced - Dependents stop waiting when cancelled or timed out")
+def test_dependency_wait_cancellable(t):
+    release = __import__("threading").Event()
+
+    @mp.parallel_with_deps
+    def slow():
+        release.wait(10)
+        return 1
+
+    @mp.parallel_with_deps
+    def after(deps):
+        return deps[0] + 1
+
+    source = slow()
+    try:
+        started = time.time()
+        t.assert_raises(Exception, lambda: after(depends_on=[source], timeout=0.3).get())
+        t.assert_true(time.time() - started < 2)
+
+        waiting = after(depends_on=[source])
+        time.sleep(0.1)
+        waiting.cancel()
+        deadline = time.time() + 2
+        while mp.get_task_status(waiting.get_task_id()) is not None and time.time() < deadline:
+            time.sleep(0.02)
+        t.assert_equal(mp.get_task_status(waiting.get_task_id()), None)
+
+        follower = after(depends_on=[source])
+    finally:
+        release.set()
+    t.assert_equal(follower.get(), 2)
+
+
+@runner.test("Advanced - Repeated timed-out waits")
+def test_repeated_timed_out_waits(t):
+    release = __import__("threading").Event()
+
+    @mp.parallel
+    def blocked():
+        release.wait(10)
+        return "done"
+
+    handle = blocked()
+    try:
+        for _ in range(200):
+            t.assert_equal(handle.wait(0.001), False)
+    finally:
+        release.set()
+    t.assert_equal(handle.wait(5), True)
+    t.assert_equal(handle.get(), "done")
+
+
+@runner.test("Advanced - Per-task CPU time")
+def test_cpu_time(t):
+    mp.reset_metrics()
+
+    # Spin for a CPU-time budget rather than wall time, since other threads may hold the GIL
+    @mp.parallel
+    def spin(cpu_seconds):
+        start = time.thread_time()
+        while time.thread_time() - start < cpu_seconds:
+            pass
+
+    @mp.parallel
+    def nap(seconds):
+        time.sleep(seconds)
+
+    busy = spin(0.2)
+    busy.get()
+    t.assert_true(busy.cpu_time() >= 0.19)
+
+    idle = nap(0.2)
+    idle.get()
+    t.assert_true(idle.cpu_time() < 0.05)
+
+    metrics = mp.get_all_metrics()
+    t.assert_true(metrics["spin"]["total_cpu_time_ms"] >= 190)
+    t.assert_true(metrics["nap"]["average_cpu_time_ms"] < 50)
+    t.assert_true("makeparallel_task_cpu_seconds_total" in mp.export_metrics())
+
+
+@runner.test("Advanced - OS priority for worker threads")
+def test_os_priority(t):
+    import os
+    import sys
+    import threading
+
+    def nice():
+        return os.getpriority(os.PRIO_PROCESS, threading.get_native_id())
+
+    background = mp.parallel(os_priority="low")(nice)
+    default = mp.parallel(nice)
+    if sys.platform.startswith("linux"):
+        t.assert_equal(background().get(), 10)
+        t.assert_equal(default().get(), nice())
+    t.assert_raises(ValueError, lambda: mp.parallel(os_priority="realtime"))
+
+
+@runner.test("Advanced - Named worker pools")
+def test_named_pools(t):
+    import threading
+
+    io_pool = mp.Pool("test-io", 2, max_queue=2)
+    t.assert_equal((io_pool.name, io_pool.num_threads, io_pool.max_queue), ("test-io", 2, 2))
+    t.assert_raises(ValueError, lambda: mp.Pool("test-io", 1))
+    t.assert_raises(ValueError, lambda: mp.Pool("test-empty", 0))
+
+    running = []
+    peak = [0]
+    lock = threading.Lock()
+
+    @mp.parallel(pool="test-io")
+    def fetch(x):
+        with lock:
+            running.append(x)
+            peak[0] = max(peak[0], len(running))
+        time.sleep(0.2)
+        with lock:
+            running.remove(x)
+        return threading.get_native_id()
+
+    handles = [fetch(1), fetch(2)]
+    time.sleep(0.1)
+    handles += [fetch(3), fetch(4)]
+    t.assert_equal(io_pool.stats()["queued"], 2)
+    t.assert_raises(mp.PoolFull, lambda: fetch(5))
+
+    # Four calls shared the pool's two threads
+    t.assert_equal(len({h.get() for h in handles}), 2)
+    t.assert_equal(peak[0], 2)
+    time.sleep(0.05)
+    stats = io_pool.stats()
+    t.assert_equal((stats["queued"], stats["running"], stats["completed"], stats["rejected"]), (0, 0, 4, 1))
+
+    io_pool.shutdown()
+    t.assert_raises(ValueError, lambda: fetch(6))
+    with mp.Pool("test-io", 1) as again:
+        fetch(7).get()
+    t.assert_equal(again.stats()["completed"], 1)
+
+    t.assert_raises(ValueError, lambda: mp.parallel(pool="test-io", os_priority="low"))
+
+
+@runner.test("Advanced - Named pool autoscaling")
+def test_named_pool_autoscaling(t):
+    import json
+    import threading
+    from http.server import BaseHTTPRequestHandler, HTTPServer
+
+    received = []
+
+    class Receiver(BaseHTTPRequestHandler):
+        def do_POST(self):
+            body = self.rfile.read(int(self.headers["Content-Length"]))
+            received.append(json.loads(body))
+            self.send_response(204)
+            self.end_headers()
+
+        def log_message(self, *args):
+            pass
+
+    server = HTTPServer(("127.0.0.1", 0), Receiver)
+    threading.Thread(target=server.serve_forever, daemon=True).start()
+    url = f"http://127.0.0.1:{server.server_port}/hook"
+    mp.register_webhook(url, events=["pool_scaled_up", "pool_scaled_down"])
+
+    pool = mp.Pool("test-auto", 8)
+
+    def wait_for_threads(n):
+        deadline = time.time() + 3
+        while pool.stats()["threads"] != n and time.time() < deadline:
+            time.sleep(0.05)
+        return pool.stats()["threads"]
+
+    try:
+        t.assert_equal(pool.stats()["threads"], 8)
+        t.assert_raises(ValueError, lambda: pool.configure_autoscaling(min_threads=0))
+        pool.configure_autoscaling(min_threads=1, max_threads=6, queue_per_thread=1, max_wait=0.05,
+                                   scale_down_after=0.1)
+        # Idle: sheds threads down to min_threads
+        t.assert_equal(wait_for_threads(1), 1)
+
+        threads = set()
+
+        @mp.parallel(pool="test-auto")
+        def job(x):
+            threads.add(threading.get_native_id())
+            time.sleep(0.1)
+            return x
+
+        t.assert_equal([h.get() for h in [job(i) for i in range(12)]], list(range(12)))
+        t.assert_true(1 < len(threads) <= 6)
+        stats = pool.stats()
+        t.assert_true(stats["autoscaling"])
+        t.assert_true(stats["scale_ups"] >= 1 and stats["scale_downs"] >= 1)
+        t.assert_equal(wait_for_threads(1), 1)
+
+        deadline = time.time() + 5
+        while {"pool_scaled_up", "pool_scaled_down"} - {e["event"] for e in received} and time.time() < deadline:
+            time.sleep(0.05)
+        t.assert_equal({e["event"] for e in received}, {"pool_scaled_up", "pool_scaled_down"})
+        t.assert_true(all(e["function"] == "test-auto" and e["from"] != e["to"] for e in received))
+
+        pool.configure_autoscaling(enabled=False)
+        t.assert_equal(wait_for_threads(8), 8)
+        t.assert_equal(pool.stats()["autoscaling"], False)
+    finally:
+        pool.shutdown()
+        mp.unregister_webhook(url)
+        server.shutdown()
+
+
+@runner.test("Advanced - Idle thread reaping and shrink_pools()")
+def test_idle_reaping(t):
+    def wait_for(check):
+        deadline = time.time() + 3
+        while not check() and time.time() < deadline:
+            time.sleep(0.05)
+        return check()
+
+    t.assert_raises(ValueError, lambda: mp.configure_idle_timeout(-1))
+
+    @mp.parallel_priority
+    def prioritized(x):
+        return x + 1
+
+    pool = mp.Pool("test-idle", 3)
+
+    @mp.parallel(pool="test-idle")
+    def pooled(x):
+        return x * 2
+
+    mp.configure_idle_timeout(0.2)
+    try:
+        t.assert_equal(prioritized(1).get(), 2)
+        t.assert_equal(pooled(1).get(), 2)
+        t.assert_true(wait_for(lambda: mp.get_autoscaling_stats()["workers"] == 0))
+        t.assert_true(wait_for(lambda: pool.stats()["threads"] == 0))
+
+        # Respawned on demand
+        t.assert_equal(prioritized(2).get(), 3)
+        t.assert_equal([h.get() for h in [pooled(i) for i in range(6)]], [i * 2 for i in range(6)])
+        t.assert_true(pool.stats()["threads"] >= 1)
+    finally:
+        mp.configure_idle_timeout()
+
+    # Without a timeout threads stay until asked to go
+    time.sleep(0.3)
+    t.assert_true(pool.stats()["threads"] >= 1)
+    t.assert_true(mp.shrink_pools() >= 1)
+    t.assert_equal(pool.stats()["threads"], 0)
+    t.assert_equal(mp.get_autoscaling_stats()["workers"], 0)
+    t.assert_equal(pooled(5).get(), 10)
+    t.assert_equal(prioritized(5).get(), 6)
+
+    pool.shutdown()
+    mp.stop_priority_worker()
+
+
+@runner.test("Advanced - Metric labels and export")
+def test_metric_labels(t):
+    import json
+    import os
+
+    mp.reset_metrics()
+
+    @mp.profiled
+    def labelled(n):
+        return n
+
+    labelled(1)
+    mp.set_metric_labels({"service": "api", "pid": os.getpid(), "version": '1.2"beta'})
+    try:
+        t.assert_equal(mp.get_metric_labels(), {"service": "api", "pid": str(os.getpid()), "version": '1.2"beta'})
+        text = mp.export_metrics()
+        t.assert_true("# TYPE makeparallel_tasks_total counter" in text)
+        sample = 'makeparallel_tasks_total{function="labelled",outcome="completed",service="api",pid="%d",version="1.2\\"beta"} 1' % os.getpid()
+        t.assert_true(sample in text)
+
+        exported = json.loads(mp.export_metrics(format="json"))
+        t.assert_equal(exported["labelled"]["completed_tasks"], 1)
+        # Labels are only exported as series labels, so every function entry stays a metrics dict
+        t.assert_true("_labels" not in exported)
+        t.assert_true("_labels" not in mp.get_all_metrics())
+
+        t.assert_raises(ValueError, lambda: mp.set_metric_labels({"bad-name": "x"}))
+        t.assert_raises(ValueError, lambda: mp.set_metric_labels({"function": "x"}))
+        t.assert_raises(ValueError, lambda: mp.export_metrics(format="xml"))
+    finally:
+        mp.set_metric_labels({})
+    t.assert_equal(mp.get_metric_labels(), {})
+
+
+@runner.test("Advanced - _reinitialize() resets the scheduler")
+def test_advanced_reinitialize(t):
+    mp.reset_shutdown()
+
+    @mp.parallel_priority
+    def occupy(seconds):
+        time.sleep(seconds)
+        return "ran"
+
+    if mp.get_thread_pool_info()["priority_workers"] == 1:
+        # With the only priority worker busy, the second task is still queued
+        running = occupy(0.3, priority=1)
+        time.sleep(0.05)
+        queued = occupy(0, priority=1)
+        t.assert_equal(mp._reinitialize(), 1)
+        t.assert_equal(running.get(), "ran")
+        t.assert_raises(RuntimeError, queued.get)
+
+    # Workers restart on the next submission
+    t.assert_equal(occupy(0).get(), "ran")
+
+    # A shutdown from the previous life no longer blocks new tasks
+    mp.shutdown(timeout_secs=0.1, cancel_pending=False)
+    mp._reinitialize()
+
+    @mp.parallel
+    def after_reset():
+        return "ok"
+
+    t.assert_equal(after_reset().get(), "ok")
+
+
 # This test is last as it can interfere with other tests
 @runner.test("Advanced - Graceful shutdown")
 def test_advanced_shutdown(t):